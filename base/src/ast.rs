@@ -1,6 +1,7 @@
 //! Module containing the types which make up `gluon`'s AST (Abstract Syntax Tree)
 use std::fmt;
 use std::marker::PhantomData;
+use std::mem;
 use std::ops::{Deref, DerefMut};
 
 use pos::{self, BytePos, HasSpan, Span, Spanned};
@@ -8,6 +9,16 @@ use symbol::Symbol;
 use types::{self, Alias, AliasData, ArcType, Generic, Type, TypeEnv};
 use ordered_float::NotNaN;
 
+#[cfg(feature = "serde")]
+use serialization::{ArenaRef, AstSeed, NodeMap, SeSeed, Seed};
+#[cfg(feature = "serde")]
+use serde::de::DeserializeState;
+#[cfg(feature = "serde")]
+use serde::ser::SerializeState;
+
+use smallvec::SmallVec;
+use typed_arena::Arena as TypedArena;
+
 pub trait DisplayEnv {
     type Ident;
 
@@ -16,6 +27,11 @@ pub trait DisplayEnv {
 
 pub trait IdentEnv: DisplayEnv {
     fn from_str(&mut self, s: &str) -> Self::Ident;
+
+    /// Creates an identifier starting with `prefix` which can't be written in the source and is
+    /// distinct from every other identifier created by `fresh_ident` (see
+    /// `SymbolModule::fresh_symbol`)
+    fn fresh_ident(&mut self, prefix: &str) -> Self::Ident;
 }
 
 pub struct EmptyEnv<T>(PhantomData<T>);
@@ -53,10 +69,22 @@ impl<'a, T: ?Sized + IdentEnv> IdentEnv for &'a mut T {
     fn from_str(&mut self, s: &str) -> Self::Ident {
         (**self).from_str(s)
     }
+
+    fn fresh_ident(&mut self, prefix: &str) -> Self::Ident {
+        (**self).fresh_ident(prefix)
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "Seed<Id, AstType<Id>>"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           Id: DeserializeState<'de, Seed<Id, AstType<Id>>> + Clone + ::std::any::Any")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
 pub struct AstType<Id> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     _typ: Box<(Option<Comment>, Spanned<Type<Id, AstType<Id>>, BytePos>)>,
 }
 
@@ -148,20 +176,49 @@ pub trait Commented {
 }
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
 pub enum CommentType {
     Block,
     Line,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(ser_parameters = "S"))]
 pub struct Comment {
     pub typ: CommentType,
     pub content: String,
 }
 
+/// A comment which is not a documentation comment. The parser attaches these to the nearest
+/// binding, record field or match alternative so that they are not lost from the AST.
+pub type SpannedComment = Spanned<Comment, BytePos>;
+
+/// An attribute such as `#[implicit]` or `#[name(arguments)]` attached to a binding
 #[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
+pub struct Attribute {
+    pub name: String,
+    pub arguments: Option<String>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "S"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           Id: DeserializeState<'de, S>,
+           ArcType<Id>: DeserializeState<'de, S>")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
 pub struct TypedIdent<Id = Symbol> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub typ: ArcType<Id>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub name: Id,
 }
 
@@ -174,6 +231,39 @@ impl<Id> TypedIdent<Id> {
     }
 }
 
+/// A binding which is passed as an implicit argument, applied to the implicit arguments which the
+/// binding takes itself. `name.typ` is the type of the argument, eg. `Show (List Int)` is passed
+/// as `show_list` applied to `show_int`
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "S"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           Id: DeserializeState<'de, S>,
+           ArcType<Id>: DeserializeState<'de, S>")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
+pub struct ImplicitArg<Id = Symbol> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
+    pub name: TypedIdent<Id>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
+    pub args: Vec<ImplicitArg<Id>>,
+}
+
+impl<Id> ImplicitArg<Id> {
+    /// Creates an argument of type `typ` which has not been resolved yet
+    pub fn new(name: Id, typ: ArcType<Id>) -> ImplicitArg<Id> {
+        ImplicitArg {
+            name: TypedIdent {
+                typ: typ,
+                name: name,
+            },
+            args: Vec::new(),
+        }
+    }
+}
+
 impl<Id> AsRef<str> for TypedIdent<Id>
 where
     Id: AsRef<str>,
@@ -184,10 +274,14 @@ where
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
 pub enum Literal {
     Byte(u8),
     Int(i64),
-    Float(NotNaN<f64>),
+    Float(
+        #[cfg_attr(feature = "serde_derive", serde(with = "::serialization::not_nan"))]
+        NotNaN<f64>,
+    ),
     String(String),
     Char(char),
 }
@@ -196,28 +290,58 @@ pub enum Literal {
 pub type SpannedPattern<Id> = Spanned<Pattern<Id>, BytePos>;
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "S"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "Id: DeserializeState<'de, S>, P: DeserializeState<'de, S>")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(serialize = "Id: SerializeState<SeSeed>, P: SerializeState<SeSeed>")))]
 pub struct PatternField<Id, P> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub name: Spanned<Id, BytePos>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub value: Option<P>,
 }
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "S"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           Id: DeserializeState<'de, S>,
+           ArcType<Id>: DeserializeState<'de, S>")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
 pub enum Pattern<Id> {
     /// An as-pattern, eg. `option @ { monoid, functor }`
-    As(Id, Box<SpannedPattern<Id>>),
+    As(
+        #[cfg_attr(feature = "serde_derive", serde(state))] Id,
+        #[cfg_attr(feature = "serde_derive", serde(state))] Box<SpannedPattern<Id>>,
+    ),
     /// Constructor pattern, eg. `Cons x xs`
-    Constructor(TypedIdent<Id>, Vec<SpannedPattern<Id>>),
+    Constructor(
+        #[cfg_attr(feature = "serde_derive", serde(state))] TypedIdent<Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state))] Vec<SpannedPattern<Id>>,
+    ),
     /// Ident pattern, eg: `x`
-    Ident(TypedIdent<Id>),
+    Ident(#[cfg_attr(feature = "serde_derive", serde(state))] TypedIdent<Id>),
     /// Record pattern, eg. `{ x, y = foo }`
     Record {
+        #[cfg_attr(feature = "serde_derive", serde(state))]
         typ: ArcType<Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state))]
         types: Vec<PatternField<Id, Id>>,
+        #[cfg_attr(feature = "serde_derive", serde(state))]
         fields: Vec<PatternField<Id, SpannedPattern<Id>>>,
     },
     /// Tuple pattern, eg: `(x, y)`
     Tuple {
+        #[cfg_attr(feature = "serde_derive", serde(state))]
         typ: ArcType<Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state))]
         elems: Vec<SpannedPattern<Id>>,
     },
     /// A literal pattern
@@ -226,26 +350,74 @@ pub enum Pattern<Id> {
     Error,
 }
 
-#[derive(Clone, PartialEq, Debug)]
-pub struct Alternative<Id> {
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "AstSeed<A, Id>"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "A"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           A: ArenaRef<'ast, Id>,
+           Id: DeserializeState<'de, AstSeed<A, Id>>
+                + DeserializeState<'de, Seed<Id, ArcType<Id>>>
+                + DeserializeState<'de, Seed<Id, AstType<Id>>>
+                + Clone
+                + ::std::any::Any")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
+pub struct Alternative<'ast, Id: 'ast> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
+    pub comments: Vec<SpannedComment>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub pattern: SpannedPattern<Id>,
-    pub expr: SpannedExpr<Id>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
+    pub expr: SpannedExpr<'ast, Id>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
-pub struct Array<Id> {
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "AstSeed<A, Id>"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "A"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           A: ArenaRef<'ast, Id>,
+           Id: DeserializeState<'de, AstSeed<A, Id>>
+                + DeserializeState<'de, Seed<Id, ArcType<Id>>>
+                + DeserializeState<'de, Seed<Id, AstType<Id>>>
+                + Clone
+                + ::std::any::Any")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
+pub struct Array<'ast, Id: 'ast> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub typ: ArcType<Id>,
-    pub exprs: Vec<SpannedExpr<Id>>,
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc_slice"))]
+    pub exprs: &'ast mut [SpannedExpr<'ast, Id>],
 }
 
-#[derive(Clone, PartialEq, Debug)]
-pub struct Lambda<Id> {
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "AstSeed<A, Id>"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "A"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           A: ArenaRef<'ast, Id>,
+           Id: DeserializeState<'de, AstSeed<A, Id>>
+                + DeserializeState<'de, Seed<Id, ArcType<Id>>>
+                + DeserializeState<'de, Seed<Id, AstType<Id>>>
+                + Clone
+                + ::std::any::Any")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
+pub struct Lambda<'ast, Id: 'ast> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub id: TypedIdent<Id>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub args: Vec<SpannedIdent<Id>>,
-    pub body: Box<SpannedExpr<Id>>,
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+    pub body: &'ast mut SpannedExpr<'ast, Id>,
 }
 
-pub type SpannedExpr<Id> = Spanned<Expr<Id>, BytePos>;
+pub type SpannedExpr<'ast, Id> = Spanned<Expr<'ast, Id>, BytePos>;
 
 pub type SpannedIdent<Id> = Spanned<TypedIdent<Id>, BytePos>;
 
@@ -254,77 +426,196 @@ pub type SpannedAlias<Id> = Spanned<AliasData<Id, AstType<Id>>, BytePos>;
 pub type SpannedAstType<Id> = Spanned<Type<Id, AstType<Id>>, BytePos>;
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "S"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "Id: DeserializeState<'de, S>, E: DeserializeState<'de, S>")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(serialize = "Id: SerializeState<SeSeed>, E: SerializeState<SeSeed>")))]
 pub struct ExprField<Id, E> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub comment: Option<Comment>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
+    pub comments: Vec<SpannedComment>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub name: Spanned<Id, BytePos>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub value: Option<E>,
+    /// True if the field is marked with `!`, `{ !x = e }`, in which case the value is forced with
+    /// `seq` when the record is constructed
+    pub strict: bool,
 }
 
-#[derive(Clone, PartialEq, Debug)]
-pub struct Do<Id> {
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "AstSeed<A, Id>"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "A"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           A: ArenaRef<'ast, Id>,
+           Id: DeserializeState<'de, AstSeed<A, Id>>
+                + DeserializeState<'de, Seed<Id, ArcType<Id>>>
+                + DeserializeState<'de, Seed<Id, AstType<Id>>>
+                + Clone
+                + ::std::any::Any")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
+pub struct Do<'ast, Id: 'ast> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub id: SpannedIdent<Id>,
-    pub bound: Box<SpannedExpr<Id>>,
-    pub body: Box<SpannedExpr<Id>>,
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+    pub bound: &'ast mut SpannedExpr<'ast, Id>,
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+    pub body: &'ast mut SpannedExpr<'ast, Id>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub flat_map_id: Option<TypedIdent<Id>>,
 }
 
 
-/// The representation of gluon's expression syntax
-#[derive(Clone, PartialEq, Debug)]
-pub enum Expr<Id> {
+/// The representation of gluon's expression syntax. Sub-expressions are allocated in an `Arena`
+/// which lives for `'ast`.
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "AstSeed<A, Id>"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "A"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           A: ArenaRef<'ast, Id>,
+           Id: DeserializeState<'de, AstSeed<A, Id>>
+                + DeserializeState<'de, Seed<Id, ArcType<Id>>>
+                + DeserializeState<'de, Seed<Id, AstType<Id>>>
+                + Clone
+                + ::std::any::Any")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
+pub enum Expr<'ast, Id: 'ast> {
     /// Identifiers
-    Ident(TypedIdent<Id>),
+    Ident(#[cfg_attr(feature = "serde_derive", serde(state))] TypedIdent<Id>),
     /// Literal values
     Literal(Literal),
     /// Function application, eg. `f x`
-    App(Box<SpannedExpr<Id>>, Vec<SpannedExpr<Id>>),
+    App(
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+        &'ast mut SpannedExpr<'ast, Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc_slice"))]
+        &'ast mut [SpannedExpr<'ast, Id>],
+    ),
+    /// An identifier whose type takes implicit arguments, eg. `show : [Show a] -> a -> String`,
+    /// along with the `#[implicit]` bindings which are passed as those arguments. The typechecker
+    /// creates this node and the bindings are selected when the expression is renamed
+    ImplicitApp(
+        #[cfg_attr(feature = "serde_derive", serde(state))] TypedIdent<Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state))] Vec<ImplicitArg<Id>>,
+    ),
     /// Lambda abstraction, eg. `\x y -> x * y`
-    Lambda(Lambda<Id>),
+    Lambda(#[cfg_attr(feature = "serde_derive", serde(state))] Lambda<'ast, Id>),
     /// If-then-else conditional
     IfElse(
-        Box<SpannedExpr<Id>>,
-        Box<SpannedExpr<Id>>,
-        Box<SpannedExpr<Id>>,
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+        &'ast mut SpannedExpr<'ast, Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+        &'ast mut SpannedExpr<'ast, Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+        &'ast mut SpannedExpr<'ast, Id>,
     ),
     /// Pattern match expression
-    Match(Box<SpannedExpr<Id>>, Vec<Alternative<Id>>),
+    Match(
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+        &'ast mut SpannedExpr<'ast, Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc_slice"))]
+        &'ast mut [Alternative<'ast, Id>],
+    ),
     /// Infix operator expression eg. `f >> g`
-    Infix(Box<SpannedExpr<Id>>, SpannedIdent<Id>, Box<SpannedExpr<Id>>),
-    /// Record field projection, eg. `value.field`
-    Projection(Box<SpannedExpr<Id>>, Id, ArcType<Id>),
+    Infix(
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+        &'ast mut SpannedExpr<'ast, Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state))] SpannedIdent<Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+        &'ast mut SpannedExpr<'ast, Id>,
+    ),
+    /// Record field projection, eg. `value.field`. If the type of the field takes implicit
+    /// arguments the typechecker fills in the last field like it does for `ImplicitApp`
+    Projection(
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+        &'ast mut SpannedExpr<'ast, Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state))] Id,
+        #[cfg_attr(feature = "serde_derive", serde(state))] ArcType<Id>,
+        #[cfg_attr(feature = "serde_derive", serde(state))] Vec<ImplicitArg<Id>>,
+    ),
     /// Array construction
-    Array(Array<Id>),
+    Array(#[cfg_attr(feature = "serde_derive", serde(state))] Array<'ast, Id>),
     /// Record construction
     Record {
+        #[cfg_attr(feature = "serde_derive", serde(state))]
         typ: ArcType<Id>,
-        types: Vec<ExprField<Id, ArcType<Id>>>,
-        exprs: Vec<ExprField<Id, SpannedExpr<Id>>>,
-        base: Option<Box<SpannedExpr<Id>>>,
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc_slice"))]
+        types: &'ast mut [ExprField<Id, ArcType<Id>>],
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc_slice"))]
+        exprs: &'ast mut [ExprField<Id, SpannedExpr<'ast, Id>>],
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc_option"))]
+        base: Option<&'ast mut SpannedExpr<'ast, Id>>,
     },
     /// Tuple construction
     Tuple {
+        #[cfg_attr(feature = "serde_derive", serde(state))]
         typ: ArcType<Id>,
-        elems: Vec<SpannedExpr<Id>>,
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc_slice"))]
+        elems: &'ast mut [SpannedExpr<'ast, Id>],
     },
     /// Declare a series of value bindings
-    LetBindings(Vec<ValueBinding<Id>>, Box<SpannedExpr<Id>>),
+    LetBindings(
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc_slice"))]
+        &'ast mut [ValueBinding<'ast, Id>],
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+        &'ast mut SpannedExpr<'ast, Id>,
+    ),
     /// Declare a series of type aliases
-    TypeBindings(Vec<TypeBinding<Id>>, Box<SpannedExpr<Id>>),
+    TypeBindings(
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc_slice"))]
+        &'ast mut [TypeBinding<Id>],
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc"))]
+        &'ast mut SpannedExpr<'ast, Id>,
+    ),
     /// A group of sequenced expressions
-    Block(Vec<SpannedExpr<Id>>),
-    Do(Do<Id>),
+    Block(
+        #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::alloc_slice"))]
+        &'ast mut [SpannedExpr<'ast, Id>],
+    ),
+    Do(#[cfg_attr(feature = "serde_derive", serde(state))] Do<'ast, Id>),
     /// An invalid expression
     Error(
         /// Provides a hint of what type the expression would have, if any
+        #[cfg_attr(feature = "serde_derive", serde(state))]
         Option<ArcType<Id>>,
     ),
 }
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "S"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           S: AsMut<NodeMap>,
+           Id: DeserializeState<'de, Seed<Id, ArcType<Id>>>
+                + DeserializeState<'de, Seed<Id, AstType<Id>>>
+                + Clone
+                + ::std::any::Any")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
 pub struct TypeBinding<Id> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub comment: Option<Comment>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
+    pub comments: Vec<SpannedComment>,
+    pub attributes: Vec<Attribute>,
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::ident_seed"))]
     pub name: Spanned<Id, BytePos>,
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::type_seed"))]
     pub alias: SpannedAlias<Id>,
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::type_seed"))]
     pub finalized_alias: Option<Alias<Id, ArcType<Id>>>,
 }
 
@@ -335,29 +626,365 @@ impl<Id> TypeBinding<Id> {
 }
 
 
-#[derive(Clone, PartialEq, Debug)]
-pub struct ValueBinding<Id> {
+#[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "AstSeed<A, Id>"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "A"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           A: ArenaRef<'ast, Id>,
+           Id: DeserializeState<'de, AstSeed<A, Id>>
+                + DeserializeState<'de, Seed<Id, ArcType<Id>>>
+                + DeserializeState<'de, Seed<Id, AstType<Id>>>
+                + Clone
+                + ::std::any::Any")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+#[cfg_attr(feature = "serde_derive", serde(bound(serialize = "Id: SerializeState<SeSeed>")))]
+pub struct ValueBinding<'ast, Id: 'ast> {
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub comment: Option<Comment>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
+    pub comments: Vec<SpannedComment>,
+    pub attributes: Vec<Attribute>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub name: SpannedPattern<Id>,
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::type_seed"))]
     pub typ: Option<AstType<Id>>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub resolved_type: ArcType<Id>,
+    /// The implicit arguments, `?x`, which precede the ordinary arguments of a function binding
+    #[cfg_attr(feature = "serde_derive", serde(state))]
+    pub implicit_args: Vec<SpannedIdent<Id>>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub args: Vec<SpannedIdent<Id>>,
-    pub expr: SpannedExpr<Id>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
+    pub expr: SpannedExpr<'ast, Id>,
 }
 
-impl<Id> ValueBinding<Id> {
+impl<'ast, Id> ValueBinding<'ast, Id> {
     pub fn span(&self) -> Span<BytePos> {
         Span::new(self.name.span.start, self.expr.span.end)
     }
+
+    /// Returns the attribute called `name` if the binding has one
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes.iter().find(|attr| attr.name == name)
+    }
+
+    /// Returns true if the binding is marked with `#[implicit]` which lets the typechecker pick
+    /// it automatically among the bindings of the same name based on the expected type
+    pub fn is_implicit(&self) -> bool {
+        self.attribute("implicit").is_some()
+    }
+
+    /// Returns true if the binding takes any arguments, implicit or not. Such bindings are
+    /// functions and may refer to themselves
+    pub fn is_function(&self) -> bool {
+        !self.args.is_empty() || !self.implicit_args.is_empty()
+    }
+
+    /// Returns the name of the vm global which the binding is bound to if it is marked with
+    /// `#[intrinsic(name)]` (or `#[intrinsic("name")]`)
+    pub fn intrinsic(&self) -> Option<&str> {
+        self.attribute("intrinsic")
+            .and_then(|attr| attr.arguments.as_ref())
+            .map(|arguments| arguments.trim_matches('"'))
+    }
+}
+
+/// The typed arenas which the nodes of an expression are allocated from. Every node of an
+/// expression refers to its children through references into the arena which means that an entire
+/// expression is freed at once when the arena is dropped, instead of node by node.
+pub struct Arena<'ast, Id: 'ast> {
+    exprs: TypedArena<SpannedExpr<'ast, Id>>,
+    alternatives: TypedArena<Alternative<'ast, Id>>,
+    value_bindings: TypedArena<ValueBinding<'ast, Id>>,
+    type_bindings: TypedArena<TypeBinding<Id>>,
+    expr_fields: TypedArena<ExprField<Id, SpannedExpr<'ast, Id>>>,
+    type_fields: TypedArena<ExprField<Id, ArcType<Id>>>,
+}
+
+impl<'ast, Id: 'ast> Default for Arena<'ast, Id> {
+    fn default() -> Self {
+        Arena::new()
+    }
+}
+
+impl<'ast, Id: 'ast> Arena<'ast, Id> {
+    pub fn new() -> Arena<'ast, Id> {
+        Arena {
+            exprs: TypedArena::new(),
+            alternatives: TypedArena::new(),
+            value_bindings: TypedArena::new(),
+            type_bindings: TypedArena::new(),
+            expr_fields: TypedArena::new(),
+            type_fields: TypedArena::new(),
+        }
+    }
+
+    pub fn alloc<T>(&'ast self, value: T) -> &'ast mut T
+    where
+        T: AstAlloc<'ast, Id>,
+    {
+        T::arena(self).alloc(value)
+    }
+
+    /// Allocates the values of `iter` as a slice. The values are collected before they are moved
+    /// into the arena so `iter` may itself allocate nodes in the arena.
+    pub fn alloc_extend<T, I>(&'ast self, iter: I) -> &'ast mut [T]
+    where
+        T: AstAlloc<'ast, Id>,
+        I: IntoIterator<Item = T>,
+    {
+        let values: SmallVec<[T; 4]> = iter.into_iter().collect();
+        T::arena(self).alloc_extend(values)
+    }
+}
+
+/// Types which can be allocated in an `Arena`
+pub trait AstAlloc<'ast, Id: 'ast>: Sized {
+    fn arena(arena: &'ast Arena<'ast, Id>) -> &'ast TypedArena<Self>;
+}
+
+macro_rules! impl_ast_alloc {
+    ($($field: ident : $typ: ty),*) => {
+        $(
+        impl<'ast, Id: 'ast> AstAlloc<'ast, Id> for $typ {
+            fn arena(arena: &'ast Arena<'ast, Id>) -> &'ast TypedArena<Self> {
+                &arena.$field
+            }
+        }
+        )*
+    }
+}
+
+impl_ast_alloc! {
+    exprs: SpannedExpr<'ast, Id>,
+    alternatives: Alternative<'ast, Id>,
+    value_bindings: ValueBinding<'ast, Id>,
+    type_bindings: TypeBinding<Id>,
+    expr_fields: ExprField<Id, SpannedExpr<'ast, Id>>,
+    type_fields: ExprField<Id, ArcType<Id>>
+}
+
+/// An expression which owns the `Arena` it is allocated in, letting it be stored and passed around
+/// without being tied to the lifetime of a borrowed arena.
+pub struct RootExpr<Id: 'static> {
+    // Points into `arena`, must never be exposed with the `'static` lifetime
+    expr: *mut SpannedExpr<'static, Id>,
+    arena: Box<Arena<'static, Id>>,
+}
+
+// The expression is only reachable through `RootExpr` so it is sent together with its arena
+unsafe impl<Id: Send + 'static> Send for RootExpr<Id> {}
+
+impl<Id: fmt::Debug + 'static> fmt::Debug for RootExpr<Id> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.expr().fmt(f)
+    }
+}
+
+impl<Id: 'static> RootExpr<Id> {
+    /// Creates a `RootExpr` from the expression which `f` allocates in a new arena
+    pub fn new<F>(f: F) -> RootExpr<Id>
+    where
+        F: for<'ast> FnOnce(&'ast Arena<'ast, Id>) -> SpannedExpr<'ast, Id>,
+    {
+        match RootExpr::try_new(|arena| Ok::<_, (_, ())>(f(arena))) {
+            Ok(expr) => expr,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Creates a `RootExpr` from the expression which `f` allocates in a new arena. Like parsing,
+    /// `f` may return a partial expression together with its error.
+    pub fn try_new<F, E>(f: F) -> Result<RootExpr<Id>, (Option<RootExpr<Id>>, E)>
+    where
+        F: for<'ast> FnOnce(&'ast Arena<'ast, Id>)
+            -> Result<SpannedExpr<'ast, Id>, (Option<SpannedExpr<'ast, Id>>, E)>,
+    {
+        let arena = Box::new(Arena::new());
+        // The arena is boxed so the references into it stay valid when `RootExpr` is moved and
+        // since `f` must work for any lifetime it can not smuggle out references that live
+        // shorter than the arena
+        let arena_ref = unsafe { &*(&*arena as *const Arena<'static, Id>) };
+        let root = |expr: SpannedExpr<'static, Id>| RootExpr {
+            expr: arena_ref.alloc(expr),
+            arena,
+        };
+        match f(arena_ref) {
+            Ok(expr) => Ok(root(expr)),
+            Err((expr, err)) => Err((expr.map(root), err)),
+        }
+    }
+
+    pub fn expr<'a>(&'a self) -> &'a SpannedExpr<'a, Id> {
+        unsafe { mem::transmute::<&'a SpannedExpr<'static, Id>, _>(&*self.expr) }
+    }
+
+    /// Calls `f` with mutable access to the expression and the arena it is allocated in, letting
+    /// `f` add new nodes to the expression
+    pub fn with_mut<F, R>(&mut self, f: F) -> R
+    where
+        F: for<'ast> FnOnce(&'ast Arena<'ast, Id>, &'ast mut SpannedExpr<'ast, Id>) -> R,
+    {
+        let arena = unsafe { &*(&*self.arena as *const Arena<'static, Id>) };
+        f(arena, unsafe { &mut *self.expr })
+    }
+}
+
+impl<Id: Clone + 'static> Clone for RootExpr<Id> {
+    fn clone(&self) -> RootExpr<Id> {
+        RootExpr::new(|arena| clone_expr(arena, self.expr()))
+    }
+}
+
+impl<Id: PartialEq + 'static> PartialEq for RootExpr<Id> {
+    fn eq(&self, other: &RootExpr<Id>) -> bool {
+        self.expr() == other.expr()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<Id> SerializeState<SeSeed> for RootExpr<Id>
+where
+    Id: SerializeState<SeSeed> + 'static,
+{
+    fn serialize_state<S>(&self, serializer: S, seed: &SeSeed) -> Result<S::Ok, S::Error>
+    where
+        S: ::serde::Serializer,
+    {
+        self.expr().serialize_state(serializer, seed)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, Id> DeserializeState<'de, Seed<Id, ArcType<Id>>> for RootExpr<Id>
+where
+    Id: for<'ast> DeserializeState<'de, AstSeed<&'ast Arena<'ast, Id>, Id>>
+        + DeserializeState<'de, Seed<Id, ArcType<Id>>>
+        + DeserializeState<'de, Seed<Id, AstType<Id>>>
+        + Clone
+        + ::std::any::Any,
+{
+    fn deserialize_state<D>(
+        seed: &mut Seed<Id, ArcType<Id>>,
+        deserializer: D,
+    ) -> Result<Self, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        RootExpr::try_new(|arena| {
+            let mut seed = AstSeed::new(arena, seed.clone());
+            SpannedExpr::deserialize_state(&mut seed, deserializer).map_err(|err| (None, err))
+        }).map_err(|(_, err)| err)
+    }
+}
+
+/// Moves the expression out of `expr`, leaving an `Expr::Error` in its place
+pub fn take_expr<'ast, Id>(expr: &mut SpannedExpr<'ast, Id>) -> SpannedExpr<'ast, Id> {
+    let span = expr.span;
+    mem::replace(expr, pos::spanned(span, Expr::Error(None)))
+}
+
+/// Copies `expr` and all of its sub-expressions into `arena`
+pub fn clone_expr<'ast, Id>(
+    arena: &'ast Arena<'ast, Id>,
+    expr: &SpannedExpr<Id>,
+) -> SpannedExpr<'ast, Id>
+where
+    Id: Clone,
+{
+    let alloc = |expr| arena.alloc(clone_expr(arena, expr));
+    let alloc_extend = |exprs: &[SpannedExpr<Id>]| {
+        arena.alloc_extend(exprs.iter().map(|expr| clone_expr(arena, expr)))
+    };
+    let value = match expr.value {
+        Expr::Ident(ref id) => Expr::Ident(id.clone()),
+        Expr::Literal(ref lit) => Expr::Literal(lit.clone()),
+        Expr::App(ref func, ref args) => Expr::App(alloc(func), alloc_extend(args)),
+        Expr::ImplicitApp(ref id, ref args) => Expr::ImplicitApp(id.clone(), args.clone()),
+        Expr::Lambda(ref lambda) => Expr::Lambda(Lambda {
+            id: lambda.id.clone(),
+            args: lambda.args.clone(),
+            body: alloc(&lambda.body),
+        }),
+        Expr::IfElse(ref pred, ref if_true, ref if_false) => {
+            Expr::IfElse(alloc(pred), alloc(if_true), alloc(if_false))
+        }
+        Expr::Match(ref scrutinee, ref alts) => Expr::Match(
+            alloc(scrutinee),
+            arena.alloc_extend(alts.iter().map(|alt| Alternative {
+                comments: alt.comments.clone(),
+                pattern: alt.pattern.clone(),
+                expr: clone_expr(arena, &alt.expr),
+            })),
+        ),
+        Expr::Infix(ref lhs, ref op, ref rhs) => Expr::Infix(alloc(lhs), op.clone(), alloc(rhs)),
+        Expr::Projection(ref expr, ref field, ref typ, ref implicit_args) => {
+            Expr::Projection(alloc(expr), field.clone(), typ.clone(), implicit_args.clone())
+        }
+        Expr::Array(ref array) => Expr::Array(Array {
+            typ: array.typ.clone(),
+            exprs: alloc_extend(&array.exprs),
+        }),
+        Expr::Record {
+            ref typ,
+            ref types,
+            ref exprs,
+            ref base,
+        } => Expr::Record {
+            typ: typ.clone(),
+            types: arena.alloc_extend(types.iter().cloned()),
+            exprs: arena.alloc_extend(exprs.iter().map(|field| ExprField {
+                comment: field.comment.clone(),
+                comments: field.comments.clone(),
+                name: field.name.clone(),
+                value: field.value.as_ref().map(|expr| clone_expr(arena, expr)),
+                strict: field.strict,
+            })),
+            base: base.as_ref().map(|base| alloc(base)),
+        },
+        Expr::Tuple { ref typ, ref elems } => Expr::Tuple {
+            typ: typ.clone(),
+            elems: alloc_extend(elems),
+        },
+        Expr::LetBindings(ref binds, ref body) => Expr::LetBindings(
+            arena.alloc_extend(binds.iter().map(|bind| ValueBinding {
+                comment: bind.comment.clone(),
+                comments: bind.comments.clone(),
+                attributes: bind.attributes.clone(),
+                name: bind.name.clone(),
+                typ: bind.typ.clone(),
+                resolved_type: bind.resolved_type.clone(),
+                implicit_args: bind.implicit_args.clone(),
+                args: bind.args.clone(),
+                expr: clone_expr(arena, &bind.expr),
+            })),
+            alloc(body),
+        ),
+        Expr::TypeBindings(ref binds, ref body) => {
+            Expr::TypeBindings(arena.alloc_extend(binds.iter().cloned()), alloc(body))
+        }
+        Expr::Block(ref exprs) => Expr::Block(alloc_extend(exprs)),
+        Expr::Do(ref do_expr) => Expr::Do(Do {
+            id: do_expr.id.clone(),
+            bound: alloc(&do_expr.bound),
+            body: alloc(&do_expr.body),
+            flat_map_id: do_expr.flat_map_id.clone(),
+        }),
+        Expr::Error(ref typ) => Expr::Error(typ.clone()),
+    };
+    pos::spanned(expr.span, value)
 }
 
 /// Visitor trait which walks over expressions calling `visit_*` on all encountered elements. By
 /// default the `visit_*` functions just walk the tree. If they are overridden the user will need to
 /// call `walk_mut_*` to continue traversing the tree.
-pub trait MutVisitor {
-    type Ident;
+pub trait MutVisitor<'ast> {
+    type Ident: 'ast;
 
-    fn visit_expr(&mut self, e: &mut SpannedExpr<Self::Ident>) {
+    fn visit_expr(&mut self, e: &mut SpannedExpr<'ast, Self::Ident>) {
         walk_mut_expr(self, e);
     }
 
@@ -381,7 +1008,10 @@ pub trait MutVisitor {
     }
 }
 
-pub fn walk_mut_expr<V: ?Sized + MutVisitor>(v: &mut V, e: &mut SpannedExpr<V::Ident>) {
+pub fn walk_mut_expr<'ast, V>(v: &mut V, e: &mut SpannedExpr<'ast, V::Ident>)
+where
+    V: ?Sized + MutVisitor<'ast>,
+{
     match e.value {
         Expr::IfElse(ref mut pred, ref mut if_true, ref mut if_false) => {
             v.visit_expr(pred);
@@ -394,9 +1024,9 @@ pub fn walk_mut_expr<V: ?Sized + MutVisitor>(v: &mut V, e: &mut SpannedExpr<V::I
             v.visit_expr(rhs);
         }
         Expr::LetBindings(ref mut bindings, ref mut body) => {
-            for bind in bindings {
+            for bind in bindings.iter_mut() {
                 v.visit_pattern(&mut bind.name);
-                for arg in &mut bind.args {
+                for arg in bind.implicit_args.iter_mut().chain(&mut bind.args) {
                     v.visit_spanned_typed_ident(arg);
                 }
                 v.visit_typ(&mut bind.resolved_type);
@@ -409,24 +1039,25 @@ pub fn walk_mut_expr<V: ?Sized + MutVisitor>(v: &mut V, e: &mut SpannedExpr<V::I
         }
         Expr::App(ref mut func, ref mut args) => {
             v.visit_expr(func);
-            for arg in args {
+            for arg in args.iter_mut() {
                 v.visit_expr(arg);
             }
         }
-        Expr::Projection(ref mut expr, _, ref mut typ) => {
+        Expr::Projection(ref mut expr, _, ref mut typ, ref mut implicit_args) => {
             v.visit_expr(expr);
             v.visit_typ(typ);
+            walk_mut_implicit_args(v, implicit_args);
         }
         Expr::Match(ref mut expr, ref mut alts) => {
             v.visit_expr(expr);
-            for alt in alts {
+            for alt in alts.iter_mut() {
                 v.visit_pattern(&mut alt.pattern);
                 v.visit_expr(&mut alt.expr);
             }
         }
         Expr::Array(ref mut a) => {
             v.visit_typ(&mut a.typ);
-            for expr in &mut a.exprs {
+            for expr in a.exprs.iter_mut() {
                 v.visit_expr(expr);
             }
         }
@@ -438,10 +1069,10 @@ pub fn walk_mut_expr<V: ?Sized + MutVisitor>(v: &mut V, e: &mut SpannedExpr<V::I
             ..
         } => {
             v.visit_typ(typ);
-            for typ in types {
+            for typ in types.iter_mut() {
                 v.visit_spanned_ident(&mut typ.name);
             }
-            for field in exprs {
+            for field in exprs.iter_mut() {
                 v.visit_spanned_ident(&mut field.name);
                 if let Some(ref mut expr) = field.value {
                     v.visit_expr(expr);
@@ -456,11 +1087,11 @@ pub fn walk_mut_expr<V: ?Sized + MutVisitor>(v: &mut V, e: &mut SpannedExpr<V::I
             ref mut elems,
         } => {
             v.visit_typ(typ);
-            for expr in elems {
+            for expr in elems.iter_mut() {
                 v.visit_expr(expr);
             }
         }
-        Expr::Block(ref mut exprs) => for expr in exprs {
+        Expr::Block(ref mut exprs) => for expr in exprs.iter_mut() {
             v.visit_expr(expr);
         },
 
@@ -494,12 +1125,29 @@ pub fn walk_mut_expr<V: ?Sized + MutVisitor>(v: &mut V, e: &mut SpannedExpr<V::I
             v.visit_expr(expr)
         }
         Expr::Ident(ref mut id) => v.visit_ident(id),
+        Expr::ImplicitApp(ref mut id, ref mut args) => {
+            v.visit_ident(id);
+            walk_mut_implicit_args(v, args);
+        }
         Expr::Literal(..) | Expr::Error(..) => (),
     }
 }
 
+fn walk_mut_implicit_args<'ast, V>(v: &mut V, args: &mut [ImplicitArg<V::Ident>])
+where
+    V: ?Sized + MutVisitor<'ast>,
+{
+    for arg in args {
+        v.visit_ident(&mut arg.name);
+        walk_mut_implicit_args(v, &mut arg.args);
+    }
+}
+
 /// Walks a pattern, calling `visit_*` on all relevant elements
-pub fn walk_mut_pattern<V: ?Sized + MutVisitor>(v: &mut V, p: &mut Pattern<V::Ident>) {
+pub fn walk_mut_pattern<'ast, V>(v: &mut V, p: &mut Pattern<V::Ident>)
+where
+    V: ?Sized + MutVisitor<'ast>,
+{
     match *p {
         Pattern::As(_, ref mut pat) => {
             v.visit_pattern(pat);
@@ -537,9 +1185,12 @@ pub fn walk_mut_pattern<V: ?Sized + MutVisitor>(v: &mut V, p: &mut Pattern<V::Id
     }
 }
 
-pub fn walk_mut_ast_type<V: ?Sized + MutVisitor>(v: &mut V, s: &mut SpannedAstType<V::Ident>) {
+pub fn walk_mut_ast_type<'ast, V>(v: &mut V, s: &mut SpannedAstType<V::Ident>)
+where
+    V: ?Sized + MutVisitor<'ast>,
+{
     match s.value {
-        Type::Hole | Type::Opaque | Type::Builtin(_) => (),
+        Type::Hole | Type::Opaque | Type::Builtin(_) | Type::Nat(_) => (),
         Type::Forall(_, ref mut ast_type, ref mut ast_types) => {
             v.visit_ast_type(&mut ast_type._typ.1);
             if let &mut Some(ref mut ast_types) = ast_types {
@@ -575,10 +1226,10 @@ pub fn walk_mut_ast_type<V: ?Sized + MutVisitor>(v: &mut V, s: &mut SpannedAstTy
     }
 }
 
-pub trait Visitor<'a> {
-    type Ident: 'a;
+pub trait Visitor<'a, 'ast> {
+    type Ident: 'a + 'ast;
 
-    fn visit_expr(&mut self, e: &'a SpannedExpr<Self::Ident>) {
+    fn visit_expr(&mut self, e: &'a SpannedExpr<'ast, Self::Ident>) {
         walk_expr(self, e);
     }
 
@@ -589,7 +1240,10 @@ pub trait Visitor<'a> {
     fn visit_typ(&mut self, _: &'a ArcType<Self::Ident>) {}
 }
 
-pub fn walk_expr<'a, V: ?Sized + Visitor<'a>>(v: &mut V, e: &'a SpannedExpr<V::Ident>) {
+pub fn walk_expr<'a, 'ast, V>(v: &mut V, e: &'a SpannedExpr<'ast, V::Ident>)
+where
+    V: ?Sized + Visitor<'a, 'ast>,
+{
     match e.value {
         Expr::IfElse(ref pred, ref if_true, ref if_false) => {
             v.visit_expr(pred);
@@ -602,7 +1256,7 @@ pub fn walk_expr<'a, V: ?Sized + Visitor<'a>>(v: &mut V, e: &'a SpannedExpr<V::I
             v.visit_expr(rhs);
         }
         Expr::LetBindings(ref bindings, ref body) => {
-            for bind in bindings {
+            for bind in bindings.iter() {
                 v.visit_pattern(&bind.name);
                 v.visit_expr(&bind.expr);
             }
@@ -610,24 +1264,25 @@ pub fn walk_expr<'a, V: ?Sized + Visitor<'a>>(v: &mut V, e: &'a SpannedExpr<V::I
         }
         Expr::App(ref func, ref args) => {
             v.visit_expr(func);
-            for arg in args {
+            for arg in args.iter() {
                 v.visit_expr(arg);
             }
         }
-        Expr::Projection(ref expr, _, ref typ) => {
+        Expr::Projection(ref expr, _, ref typ, ref implicit_args) => {
             v.visit_expr(expr);
             v.visit_typ(typ);
+            walk_implicit_args(v, implicit_args);
         }
         Expr::Match(ref expr, ref alts) => {
             v.visit_expr(expr);
-            for alt in alts {
+            for alt in alts.iter() {
                 v.visit_pattern(&alt.pattern);
                 v.visit_expr(&alt.expr);
             }
         }
         Expr::Array(ref a) => {
             v.visit_typ(&a.typ);
-            for expr in &a.exprs {
+            for expr in a.exprs.iter() {
                 v.visit_expr(expr);
             }
         }
@@ -638,7 +1293,7 @@ pub fn walk_expr<'a, V: ?Sized + Visitor<'a>>(v: &mut V, e: &'a SpannedExpr<V::I
             ..
         } => {
             v.visit_typ(typ);
-            for field in exprs {
+            for field in exprs.iter() {
                 if let Some(ref expr) = field.value {
                     v.visit_expr(expr);
                 }
@@ -650,7 +1305,7 @@ pub fn walk_expr<'a, V: ?Sized + Visitor<'a>>(v: &mut V, e: &'a SpannedExpr<V::I
         Expr::Tuple {
             elems: ref exprs, ..
         }
-        | Expr::Block(ref exprs) => for expr in exprs {
+        | Expr::Block(ref exprs) => for expr in exprs.iter() {
             v.visit_expr(expr);
         },
         Expr::Do(Do {
@@ -667,12 +1322,29 @@ pub fn walk_expr<'a, V: ?Sized + Visitor<'a>>(v: &mut V, e: &'a SpannedExpr<V::I
         }
         Expr::TypeBindings(_, ref expr) => v.visit_expr(expr),
         Expr::Ident(ref id) => v.visit_typ(&id.typ),
+        Expr::ImplicitApp(ref id, ref args) => {
+            v.visit_typ(&id.typ);
+            walk_implicit_args(v, args);
+        }
         Expr::Literal(..) | Expr::Error(..) => (),
     }
 }
 
+fn walk_implicit_args<'a, 'ast, V>(v: &mut V, args: &'a [ImplicitArg<V::Ident>])
+where
+    V: ?Sized + Visitor<'a, 'ast>,
+{
+    for arg in args {
+        v.visit_typ(&arg.name.typ);
+        walk_implicit_args(v, &arg.args);
+    }
+}
+
 /// Walks a pattern, calling `visit_*` on all relevant elements
-pub fn walk_pattern<'a, V: ?Sized + Visitor<'a>>(v: &mut V, p: &'a Pattern<V::Ident>) {
+pub fn walk_pattern<'a, 'ast, V>(v: &mut V, p: &'a Pattern<V::Ident>)
+where
+    V: ?Sized + Visitor<'a, 'ast>,
+{
     match *p {
         Pattern::As(_, ref pat) => {
             v.visit_pattern(&pat);
@@ -740,13 +1412,17 @@ impl Typed for Literal {
     }
 }
 
-impl Typed for Expr<Symbol> {
+impl<'ast> Typed for Expr<'ast, Symbol> {
     type Ident = Symbol;
 
     fn try_type_of(&self, env: &TypeEnv) -> Result<ArcType, String> {
         match *self {
             Expr::Ident(ref id) => Ok(id.typ.clone()),
-            Expr::Projection(_, _, ref typ)
+            Expr::ImplicitApp(ref id, _) => Ok(id.typ.remove_implicit_args().clone()),
+            Expr::Projection(_, _, ref typ, ref implicit_args) if !implicit_args.is_empty() => {
+                Ok(typ.remove_implicit_args().clone())
+            }
+            Expr::Projection(_, _, ref typ, _)
             | Expr::Record { ref typ, .. }
             | Expr::Tuple { ref typ, .. } => Ok(typ.clone()),
             Expr::Literal(ref lit) => lit.try_type_of(env),
@@ -846,8 +1522,11 @@ fn get_return_type(
     get_return_type(env, &typ, arg_count)
 }
 
+/// The characters which operators are made of
+pub const OPERATOR_CHARS: &str = "!#$%&*+-./<=>?@\\^|~:";
+
 pub fn is_operator_char(c: char) -> bool {
-    "!#$%&*+-./<=>?@\\^|-~:".chars().any(|x| x == c)
+    OPERATOR_CHARS.chars().any(|x| x == c)
 }
 
 pub fn is_constructor(s: &str) -> bool {
@@ -863,7 +1542,7 @@ pub fn expr_to_path(expr: &SpannedExpr<Symbol>, path: &mut String) -> Result<(),
             path.push_str(id.name.declared_name());
             Ok(())
         }
-        Expr::Projection(ref expr, ref id, _) => {
+        Expr::Projection(ref expr, ref id, _, _) => {
             expr_to_path(expr, path)?;
             path.push('.');
             path.push_str(id.declared_name());
@@ -8,7 +8,9 @@ use std::iter::{Extend, FromIterator};
 use std::slice;
 use std::vec;
 
-use pos::{BytePos, Location, Span, Spanned, spanned2};
+use ansi_term::{Colour, Style};
+
+use pos::{BytePos, Location, Span, Spanned, spanned2, NO_EXPANSION};
 use source::Source;
 
 /// An error type which can represent multiple errors.
@@ -131,21 +133,128 @@ impl<T: fmt::Display + fmt::Debug + Any> StdError for Errors<T> {
     }
 }
 
+/// Options which control how errors are emitted
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EmitOptions {
+    /// Whether the output should be colored using ANSI escape codes
+    pub color: bool,
+    /// The number of errors to emit before the remaining errors are only counted (default: all)
+    pub max_errors: Option<usize>,
+}
+
+/// A location in the source which is related to an error, for instance where a binding which
+/// the error refers to was introduced
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    pub span: Span<BytePos>,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new<S>(span: Span<BytePos>, message: S) -> Label
+    where
+        S: Into<String>,
+    {
+        Label {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+/// Trait for errors which can point out other locations in the source than the one the error is
+/// reported at
+pub trait Labels {
+    /// Returns the secondary locations related to this error (default: none)
+    fn labels(&self) -> Vec<Label> {
+        Vec::new()
+    }
+}
+
+impl Labels for Box<StdError + Send + Sync> {}
+
+impl<E, H> Labels for Help<E, H>
+where
+    E: Labels,
+{
+    fn labels(&self) -> Vec<Label> {
+        self.error.labels()
+    }
+}
+
+/// The line of source a span starts at along with the span itself
+#[derive(Debug, PartialEq)]
+struct Excerpt {
+    line: String,
+    span: Span<Location>,
+}
+
+impl Excerpt {
+    fn new(source: &Source, span: Span<BytePos>) -> Option<Excerpt> {
+        let start = source.location(span.start)?;
+        let end = source.location(span.end)?;
+        let (_, line) = source.line_at_byte(span.start)?;
+        Some(Excerpt {
+            line: line.to_string(),
+            span: Span::new(start, end),
+        })
+    }
+
+    fn emit<W>(&self, writer: &mut W, style: Style, marker: char, underline: char) -> fmt::Result
+    where
+        W: fmt::Write,
+    {
+        let Span { start, end, .. } = self.span;
+        writeln!(writer, "{}", self.line)?;
+
+        // Spans covering multiple lines are underlined until the end of the first line
+        let end_column = if end.line == start.line {
+            end.column.to_usize()
+        } else {
+            self.line.len()
+        };
+        let mut marker_line = marker.to_string();
+        for _ in (start.column.to_usize() + 1)..end_column {
+            marker_line.push(underline);
+        }
+        writeln!(
+            writer,
+            "{:2$}{}",
+            "",
+            style.paint(marker_line),
+            start.column.to_usize()
+        )
+    }
+}
+
 #[derive(Debug, PartialEq)]
 struct SourceContext<E> {
     line: String,
     error: Spanned<E, Location>,
+    labels: Vec<(Excerpt, String)>,
 }
 
-impl<E> SourceContext<E> {
+impl<E: Labels> SourceContext<E> {
     fn new(source: &Source, error: Spanned<E, BytePos>) -> SourceContext<E> {
         let start = source.location(error.span.start).unwrap();
         let end = source.location(error.span.end).unwrap();
         let (_, line) = source.line_at_byte(error.span.start).unwrap();
 
+        // Labels pointing into macro expansions or other files can't be shown in this source
+        let labels = error
+            .value
+            .labels()
+            .into_iter()
+            .filter(|label| label.span.expansion_id == NO_EXPANSION)
+            .filter_map(|label| {
+                Excerpt::new(source, label.span).map(|excerpt| (excerpt, label.message))
+            })
+            .collect();
+
         SourceContext {
             line: line.to_string(),
             error: spanned2(start, end, error.value),
+            labels,
         }
     }
 }
@@ -157,7 +266,7 @@ pub struct InFile<E> {
     error: Errors<SourceContext<E>>,
 }
 
-impl<E: fmt::Display> InFile<E> {
+impl<E: fmt::Display + Labels> InFile<E> {
     /// Creates a new `InFile` error which states that the error occurred in `file` using the file
     /// contents in `source` to provide a context to the span.
     pub fn new(source_name: &str, source: &str, error: Errors<Spanned<E, BytePos>>) -> InFile<E> {
@@ -174,36 +283,77 @@ impl<E: fmt::Display> InFile<E> {
             },
         }
     }
+}
 
+impl<E: fmt::Display> InFile<E> {
     pub fn errors(self) -> Errors<Spanned<E, Location>> {
         Errors {
             errors: self.error.errors.into_iter().map(|err| err.error).collect(),
         }
     }
-}
 
-impl<E: fmt::Display> fmt::Display for InFile<E> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for error in &self.error.errors {
-            let Span { start, end, .. } = error.error.span;
+    /// The number of errors in the file
+    pub fn len(&self) -> usize {
+        self.error.len()
+    }
 
-            write!(f, "{}:{}\n{}\n", self.source_name, error.error, error.line)?;
+    pub fn is_empty(&self) -> bool {
+        self.error.is_empty()
+    }
 
-            for _ in 0..start.column.to_usize() {
-                write!(f, " ")?;
-            }
+    /// Writes each error along with an excerpt of the source it points to. Secondary labels are
+    /// written after the error they belong to. If `color` is `true` the output is colored using
+    /// ANSI escape codes.
+    pub fn emit<W>(&self, writer: &mut W, color: bool) -> fmt::Result
+    where
+        W: fmt::Write,
+    {
+        self.emit_first(writer, color, self.len())
+    }
 
-            write!(f, "^")?;
-            for _ in (start.column.to_usize() + 1)..end.column.to_usize() {
-                write!(f, "~")?;
+    /// Like `emit` but only writes the first `count` errors
+    pub fn emit_first<W>(&self, writer: &mut W, color: bool, count: usize) -> fmt::Result
+    where
+        W: fmt::Write,
+    {
+        let (primary, secondary) = if color {
+            (Colour::Red.bold(), Colour::Blue.bold())
+        } else {
+            (Style::new(), Style::new())
+        };
+        for error in self.error.errors.iter().take(count) {
+            write!(
+                writer,
+                "{}: {}\n",
+                primary.paint(format!("{}:{}", self.source_name, error.error.span.start)),
+                error.error.value
+            )?;
+            let excerpt = Excerpt {
+                line: error.line.clone(),
+                span: error.error.span,
+            };
+            excerpt.emit(writer, primary, '^', '~')?;
+
+            for &(ref excerpt, ref message) in &error.labels {
+                writeln!(
+                    writer,
+                    "{}: {}",
+                    secondary.paint(format!("{}:{}", self.source_name, excerpt.span.start)),
+                    message
+                )?;
+                excerpt.emit(writer, secondary, '-', '-')?;
             }
-
-            writeln!(f, "")?;
         }
         Ok(())
     }
 }
 
+impl<E: fmt::Display> fmt::Display for InFile<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.emit(f, false)
+    }
+}
+
 impl<E: fmt::Display + fmt::Debug + Any> StdError for InFile<E> {
     fn description(&self) -> &str {
         "Error in file"
@@ -0,0 +1,150 @@
+//! Interface files (`.gli`) which record the types exported by a module.
+//!
+//! An interface is produced after a module has been typechecked and lets the modules importing it
+//! be typechecked through an `InterfaceEnv` without checking the module itself again.
+
+#[cfg(feature = "serde_json")]
+use std::io;
+
+use fnv::FnvMap;
+use kind::{ArcKind, Kind, KindEnv};
+use symbol::{Name, Symbol, SymbolRef};
+use types::{Alias, ArcType, RecordSelector, Type, TypeEnv};
+
+#[cfg(feature = "serde_json")]
+use serialization::{SeSeed, Seed};
+
+/// The file extension used for interface files
+pub const EXTENSION: &str = "gli";
+
+/// The types exported by a module
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "Seed<Symbol, ArcType>"))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "SeSeed"))]
+pub struct Interface {
+    /// The name of the module (`std.option`)
+    pub module: String,
+    /// Hash of the source code the interface were produced from
+    pub source_hash: u64,
+    /// The type of the module
+    #[cfg_attr(feature = "serde_derive", serde(state))]
+    pub typ: ArcType,
+}
+
+impl Interface {
+    pub fn new(module: String, source_hash: u64, typ: ArcType) -> Interface {
+        Interface {
+            module,
+            source_hash,
+            typ,
+        }
+    }
+
+    /// Returns the type `name` (`Option`) which the module exports
+    pub fn find_type_info(&self, name: &str) -> Option<&Alias<Symbol, ArcType>> {
+        self.typ
+            .type_field_iter()
+            .find(|field| field.name.as_ref() == name)
+            .map(|field| &field.typ)
+    }
+
+    /// Writes the interface in the format used by `.gli` files
+    #[cfg(feature = "serde_json")]
+    pub fn write<W>(&self, writer: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        use serde::ser::SerializeState;
+
+        let mut serializer = ::serde_json::Serializer::new(writer);
+        self.serialize_state(&mut serializer, &SeSeed::default())?;
+        Ok(())
+    }
+
+    /// Reads an interface written by `Interface::write`
+    #[cfg(feature = "serde_json")]
+    pub fn read<R>(reader: R) -> io::Result<Interface>
+    where
+        R: io::Read,
+    {
+        use serde::de::DeserializeState;
+
+        let mut deserializer = ::serde_json::Deserializer::from_reader(reader);
+        let interface =
+            Interface::deserialize_state(&mut Seed::default(), &mut deserializer)?;
+        Ok(interface)
+    }
+}
+
+/// `TypeEnv` over the interfaces of modules which have not been typechecked in this process
+#[derive(Clone, Debug, Default)]
+pub struct InterfaceEnv {
+    interfaces: FnvMap<String, Interface>,
+}
+
+impl InterfaceEnv {
+    pub fn new() -> InterfaceEnv {
+        InterfaceEnv::default()
+    }
+
+    /// Adds `interface`, replacing any earlier interface of the same module
+    pub fn insert(&mut self, interface: Interface) {
+        self.interfaces.insert(interface.module.clone(), interface);
+    }
+
+    pub fn get(&self, module: &str) -> Option<&Interface> {
+        self.interfaces.get(module)
+    }
+
+    /// Returns `true` if the interface of `module` were produced from a source with the hash
+    /// `source_hash`
+    pub fn is_up_to_date(&self, module: &str, source_hash: u64) -> bool {
+        self.get(module)
+            .map_or(false, |interface| interface.source_hash == source_hash)
+    }
+}
+
+impl KindEnv for InterfaceEnv {
+    fn find_kind(&self, type_name: &SymbolRef) -> Option<ArcKind> {
+        self.find_type_info(type_name).map(|alias| {
+            alias.params().iter().rev().fold(Kind::typ(), |acc, arg| {
+                Kind::function(arg.kind.clone(), acc)
+            })
+        })
+    }
+}
+
+impl TypeEnv for InterfaceEnv {
+    fn find_type(&self, id: &SymbolRef) -> Option<&ArcType> {
+        let id = id.definition_name();
+        if let Some(interface) = self.interfaces.get(id) {
+            return Some(&interface.typ);
+        }
+        // Constructors of the variant types the modules export
+        self.interfaces
+            .values()
+            .flat_map(|interface| interface.typ.type_field_iter())
+            .filter_map(|field| match **field.typ.unresolved_type().remove_forall() {
+                Type::Variant(ref row) => row.row_iter().find(|field| field.name.as_ref() == id),
+                _ => None,
+            })
+            .next()
+            .map(|field| &field.typ)
+    }
+
+    fn find_type_info(&self, id: &SymbolRef) -> Option<&Alias<Symbol, ArcType>> {
+        let name = Name::new(id.definition_name());
+        self.interfaces
+            .get(name.module().as_str())
+            .and_then(|interface| interface.find_type_info(name.name().as_str()))
+    }
+
+    fn find_record(
+        &self,
+        _fields: &[Symbol],
+        _selector: RecordSelector,
+    ) -> Option<(ArcType, ArcType)> {
+        None
+    }
+}
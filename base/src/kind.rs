@@ -41,11 +41,16 @@ pub enum Kind {
     Type,
     /// Kinds of rows (for polymorphic records).
     Row,
+    /// Kind of type-level natural numbers such as the length in `Array 3 Float`.
+    Nat,
     /// Constructor which takes two kinds, taking the first as argument and returning the second.
     Function(
         #[cfg_attr(feature = "serde_derive", serde(state))] ArcKind,
         #[cfg_attr(feature = "serde_derive", serde(state))] ArcKind,
     ),
+    /// A named kind variable, such as `k` in `type Proxy (a : k) = | Proxy`. The parameters of a
+    /// type which have the same kind variable must be instantiated with the same kind.
+    Generic(String),
 }
 
 impl Kind {
@@ -65,9 +70,17 @@ impl Kind {
         ArcKind::new(Kind::Row)
     }
 
+    pub fn nat() -> ArcKind {
+        ArcKind::new(Kind::Nat)
+    }
+
     pub fn function(l: ArcKind, r: ArcKind) -> ArcKind {
         ArcKind::new(Kind::Function(l, r))
     }
+
+    pub fn generic(name: &str) -> ArcKind {
+        ArcKind::new(Kind::Generic(name.to_string()))
+    }
 }
 
 #[derive(PartialEq, Copy, Clone, PartialOrd)]
@@ -104,6 +117,8 @@ impl<'a> fmt::Display for DisplayKind<'a> {
             Kind::Variable(i) => i.fmt(f),
             Kind::Type => "Type".fmt(f),
             Kind::Row => "Row".fmt(f),
+            Kind::Nat => "Nat".fmt(f),
+            Kind::Generic(ref name) => name.fmt(f),
             Kind::Function(ref arg, ref ret) => match self.0 {
                 Prec::Function => write!(f, "({} -> {})", DisplayKind(Prec::Function, arg), ret),
                 Prec::Top => write!(f, "{} -> {}", DisplayKind(Prec::Function, arg), ret),
@@ -172,7 +187,7 @@ impl fmt::Display for ArcKind {
     }
 }
 
-type_cache! { KindCache() { ArcKind, Kind } row hole typ }
+type_cache! { KindCache() { ArcKind, Kind } row hole typ nat }
 
 impl<'a, F: ?Sized> Walker<'a, ArcKind> for F
 where
@@ -193,6 +208,11 @@ where
             f.walk(a);
             f.walk(r);
         }
-        Kind::Hole | Kind::Variable(_) | Kind::Type | Kind::Row => (),
+        Kind::Hole
+        | Kind::Variable(_)
+        | Kind::Type
+        | Kind::Row
+        | Kind::Nat
+        | Kind::Generic(_) => (),
     }
 }
@@ -2,6 +2,7 @@
 //! AST and some basic containers.
 #![doc(html_root_url = "https://docs.rs/gluon_base/0.7.1")] // # GLUON
 
+extern crate ansi_term;
 #[macro_use]
 extern crate collect_mac;
 extern crate itertools;
@@ -11,6 +12,7 @@ extern crate pretty;
 #[macro_use]
 extern crate quick_error;
 extern crate smallvec;
+extern crate typed_arena;
 
 extern crate ordered_float;
 #[cfg(feature = "serde_derive")]
@@ -21,6 +23,8 @@ extern crate serde_derive;
 extern crate serde_derive_state;
 #[cfg(feature = "serde")]
 extern crate serde_state as serde;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
 
 macro_rules! type_cache {
     ($name: ident ($($args: ident),*) { $typ: ty, $inner_type: ident } $( $id: ident )+) => {
@@ -76,8 +80,10 @@ pub mod ast;
 pub mod error;
 pub mod fixed;
 pub mod fnv;
+pub mod interface;
 pub mod kind;
 pub mod merge;
+pub mod message;
 pub mod metadata;
 pub mod pos;
 pub mod resolve;
@@ -85,7 +91,9 @@ pub mod scoped_map;
 #[cfg(feature = "serde")]
 pub mod serialization;
 pub mod source;
+pub mod suggest;
 pub mod symbol;
+pub mod timings;
 pub mod types;
 
 
@@ -10,3 +10,29 @@ macro_rules! ice {
         panic!(concat!($fmt, ". Please report an issue at https://github.com/gluon-lang/gluon/issues"), $($arg)+)
     });
 }
+
+/// Declares constants of `MessageId` for the messages of a module together with the `MESSAGES`
+/// constant listing all of them
+#[macro_export]
+macro_rules! declare_messages {
+    ($($(#[$attr:meta])* $name:ident = $id:tt : $template:tt,)*) => {
+        $(
+            $(#[$attr])*
+            pub const $name: $crate::message::MessageId = $crate::message::MessageId {
+                id: $id,
+                template: $template,
+            };
+        )*
+
+        /// All messages declared in this module
+        pub const MESSAGES: &[$crate::message::MessageId] = &[$($name),*];
+    };
+}
+
+/// Formats the message `$id` with the named arguments, `message!(UNDEFINED, name = name)`
+#[macro_export]
+macro_rules! message {
+    ($id:expr $(, $name:ident = $value:expr)* $(,)*) => {
+        $id.format(&[$((stringify!($name), &$value as &::std::fmt::Display)),*])
+    };
+}
@@ -0,0 +1,145 @@
+//! Catalog of the messages used in diagnostics.
+//!
+//! Every message which is shown to users is declared with `declare_messages!` which gives it a
+//! stable id and an English template. Embedders which want to show diagnostics in another
+//! language install a `Catalog` with `set_catalog` which maps the ids to translated templates.
+//!
+//! Templates refer to the parameters of a message as `{name}`, `{{` and `}}` produce literal
+//! braces.
+//!
+//! ```
+//! #[macro_use]
+//! extern crate gluon_base;
+//!
+//! use std::collections::HashMap;
+//!
+//! use gluon_base::message;
+//!
+//! declare_messages! {
+//!     /// Example message
+//!     UNDEFINED = "example.undefined": "Undefined variable `{name}`",
+//! }
+//!
+//! # fn main() {
+//! assert_eq!(message!(UNDEFINED, name = "x").to_string(), "Undefined variable `x`");
+//!
+//! let mut catalog = HashMap::new();
+//! catalog.insert("example.undefined".to_string(), "Variable indéfinie `{name}`".to_string());
+//! message::set_catalog(Some(Box::new(catalog)));
+//! assert_eq!(message!(UNDEFINED, name = "x").to_string(), "Variable indéfinie `x`");
+//! # message::set_catalog(None);
+//! # }
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::{Once, RwLock, ONCE_INIT};
+
+/// Identifies a message together with the English template which is used when the installed
+/// catalog does not have a translation for it
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct MessageId {
+    pub id: &'static str,
+    pub template: &'static str,
+}
+
+impl MessageId {
+    /// Returns the message with `{name}` replaced with the argument named `name` in `args`
+    pub fn format<'a>(&self, args: &'a [(&'a str, &'a fmt::Display)]) -> Message<'a> {
+        Message { id: *self, args }
+    }
+}
+
+/// A message with its arguments, formatted with the template from the installed catalog
+pub struct Message<'a> {
+    id: MessageId,
+    args: &'a [(&'a str, &'a fmt::Display)],
+}
+
+impl<'a> fmt::Display for Message<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The lock is released before the arguments are formatted as they may be messages as well
+        let translated = catalog()
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|catalog| catalog.template(self.id.id).map(String::from));
+        let template = translated
+            .as_ref()
+            .map_or(self.id.template, |template| &template[..]);
+        interpolate(f, template, self.args)
+    }
+}
+
+/// Writes `template` to `f`, replacing each `{name}` with the argument of that name. Parameters
+/// which do not have an argument are written as is.
+pub fn interpolate(
+    f: &mut fmt::Formatter,
+    template: &str,
+    args: &[(&str, &fmt::Display)],
+) -> fmt::Result {
+    let mut rest = template;
+    while let Some(i) = rest.find(|c: char| c == '{' || c == '}') {
+        f.write_str(&rest[..i])?;
+        rest = &rest[i..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            f.write_str(&rest[..1])?;
+            rest = &rest[2..];
+            continue;
+        }
+        let param = if rest.starts_with('{') {
+            rest.find('}').and_then(|end| {
+                let name = &rest[1..end];
+                args.iter()
+                    .find(|arg| arg.0 == name)
+                    .map(|arg| (arg.1, end + 1))
+            })
+        } else {
+            None
+        };
+        match param {
+            Some((value, len)) => {
+                write!(f, "{}", value)?;
+                rest = &rest[len..];
+            }
+            None => {
+                f.write_str(&rest[..1])?;
+                rest = &rest[1..];
+            }
+        }
+    }
+    f.write_str(rest)
+}
+
+/// Provides the templates of translated messages
+pub trait Catalog: Send + Sync {
+    /// Returns the template for the message with the id `id` or `None` if the English template
+    /// should be used
+    fn template(&self, id: &str) -> Option<&str>;
+}
+
+impl<S> Catalog for HashMap<String, String, S>
+where
+    S: BuildHasher + Send + Sync,
+{
+    fn template(&self, id: &str) -> Option<&str> {
+        self.get(id).map(|s| &s[..])
+    }
+}
+
+static INIT: Once = ONCE_INIT;
+static mut CATALOG: *const RwLock<Option<Box<Catalog>>> = 0 as *const _;
+
+fn catalog() -> &'static RwLock<Option<Box<Catalog>>> {
+    unsafe {
+        INIT.call_once(|| {
+            CATALOG = Box::into_raw(Box::new(RwLock::new(None)));
+        });
+        &*CATALOG
+    }
+}
+
+/// Installs the catalog which all messages are looked up in. `None` restores the English messages.
+pub fn set_catalog(new_catalog: Option<Box<Catalog>>) {
+    *catalog().write().unwrap() = new_catalog;
+}
@@ -16,18 +16,23 @@ impl<'a, T: ?Sized + MetadataEnv> MetadataEnv for &'a T {
 #[cfg_attr(feature = "serde_derive", derive(Deserialize, Serialize))]
 pub struct Metadata {
     pub comment: Option<String>,
+    /// The attributes of the binding, mapping the name of each attribute to its arguments
+    pub attributes: BTreeMap<String, Option<String>>,
     pub module: BTreeMap<String, Metadata>,
 }
 
 impl Metadata {
     pub fn has_data(&self) -> bool {
-        self.comment.is_some() || !self.module.is_empty()
+        self.comment.is_some() || !self.attributes.is_empty() || !self.module.is_empty()
     }
 
     pub fn merge(mut self, other: Metadata) -> Metadata {
         if self.comment.is_none() {
             self.comment = other.comment;
         }
+        if self.attributes.is_empty() {
+            self.attributes = other.attributes;
+        }
         if self.module.is_empty() {
             self.module = other.module;
         }
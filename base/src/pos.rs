@@ -123,6 +123,7 @@ impl fmt::Display for Location {
 
 /// An expansion identifier tracks whether a span originated from a macro expansion or not.
 #[derive(Copy, Clone, Default, Ord, PartialOrd, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(Serialize, Deserialize))]
 pub struct ExpansionId(pub u32);
 
 pub const NO_EXPANSION: ExpansionId = ExpansionId(0);
@@ -131,6 +132,7 @@ pub const UNKNOWN_EXPANSION: ExpansionId = ExpansionId(1);
 
 /// A span between two locations in a source file
 #[derive(Copy, Clone, Default, Eq, Debug)]
+#[cfg_attr(feature = "serde_derive", derive(Serialize, Deserialize))]
 pub struct Span<Pos> {
     pub start: Pos,
     pub end: Pos,
@@ -240,8 +242,20 @@ impl<Pos: Ord> Span<Pos> {
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
+#[cfg_attr(feature = "serde_derive", serde(deserialize_state = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(de_parameters = "S"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(deserialize = "
+           T: ::serde::de::DeserializeState<'de, S>,
+           Pos: ::serde::Deserialize<'de>")))]
+#[cfg_attr(feature = "serde_derive", serde(serialize_state = "S"))]
+#[cfg_attr(feature = "serde_derive", serde(ser_parameters = "S"))]
+#[cfg_attr(feature = "serde_derive",
+           serde(bound(serialize = "T: ::serde::ser::SerializeState<S>, Pos: ::serde::Serialize")))]
 pub struct Spanned<T, Pos> {
     pub span: Span<Pos>,
+    #[cfg_attr(feature = "serde_derive", serde(state))]
     pub value: T,
 }
 
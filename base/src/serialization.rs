@@ -9,8 +9,9 @@ use std::rc::Rc;
 use std::sync::Arc;
 
 use serde::de::{DeserializeSeed, DeserializeState, Deserializer, Error};
-use serde::ser::{SerializeState, Serializer};
+use serde::ser::{Seeded, SerializeState, Serializer};
 
+use ast::{Arena, AstAlloc};
 use kind::ArcKind;
 use symbol::Symbol;
 use types::{AliasData, ArcType, Type};
@@ -75,6 +76,226 @@ impl<Id, T> Clone for Seed<Id, T> {
     }
 }
 
+/// Seed used to deserialize expressions. Sub-expressions are allocated in `arena` as they are
+/// deserialized.
+///
+/// The arena is referred to through the type parameter `A` instead of a `&'ast Arena` as the
+/// derived deserializers borrow the seed for a lifetime which `'ast` is not known to outlive.
+pub struct AstSeed<A, Id> {
+    arena: A,
+    types: Seed<Id, ArcType<Id>>,
+}
+
+/// A reference to the arena which an `AstSeed` allocates in
+pub trait ArenaRef<'ast, Id: 'ast>: Copy {
+    fn arena(self) -> &'ast Arena<'ast, Id>;
+}
+
+impl<'ast, Id: 'ast> ArenaRef<'ast, Id> for &'ast Arena<'ast, Id> {
+    fn arena(self) -> &'ast Arena<'ast, Id> {
+        self
+    }
+}
+
+impl<'ast, Id: 'ast> AstSeed<&'ast Arena<'ast, Id>, Id> {
+    pub fn new(arena: &'ast Arena<'ast, Id>, types: Seed<Id, ArcType<Id>>) -> Self {
+        AstSeed {
+            arena: arena,
+            types: types,
+        }
+    }
+}
+
+impl<A, Id> AsMut<NodeMap> for AstSeed<A, Id> {
+    fn as_mut(&mut self) -> &mut NodeMap {
+        &mut self.types.nodes
+    }
+}
+
+impl<'de, A, Id> DeserializeState<'de, AstSeed<A, Id>> for ArcType<Id>
+where
+    Id: DeserializeState<'de, Seed<Id, ArcType<Id>>> + Clone + Any,
+{
+    fn deserialize_state<D>(seed: &mut AstSeed<A, Id>, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        ArcType::deserialize_state(&mut seed.types, deserializer)
+    }
+}
+
+impl<'de, A, Id> DeserializeState<'de, AstSeed<A, Id>> for Symbol {
+    fn deserialize_state<D>(seed: &mut AstSeed<A, Id>, deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        <Symbol as DeserializeState<'de, Seed<Id, ArcType<Id>>>>::deserialize_state(
+            &mut seed.types,
+            deserializer,
+        )
+    }
+}
+
+/// Deserializes a value which needs a `Seed` of another type, sharing the nodes of `seed`
+pub mod type_seed {
+    use super::*;
+
+    pub fn deserialize<'de, S, D, Id, T, U>(seed: &mut S, deserializer: D) -> Result<U, D::Error>
+    where
+        S: AsMut<NodeMap>,
+        D: Deserializer<'de>,
+        U: DeserializeState<'de, Seed<Id, T>>,
+    {
+        let mut seed = Seed::new(seed.as_mut().clone());
+        U::deserialize_state(&mut seed, deserializer)
+    }
+
+    pub fn serialize<S, T, Seed>(self_: &T, serializer: S, seed: &Seed) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: SerializeState<Seed>,
+    {
+        self_.serialize_state(serializer, seed)
+    }
+}
+
+/// Like `type_seed` but for values which only contain identifiers
+pub mod ident_seed {
+    use super::*;
+
+    pub use super::type_seed::serialize;
+
+    pub fn deserialize<'de, S, D, Id, U>(seed: &mut S, deserializer: D) -> Result<U, D::Error>
+    where
+        S: AsMut<NodeMap>,
+        D: Deserializer<'de>,
+        U: DeserializeState<'de, Seed<Id, ArcType<Id>>>,
+    {
+        type_seed::deserialize::<_, _, Id, ArcType<Id>, _>(seed, deserializer)
+    }
+}
+
+/// (De)serializes a reference to an expression node which is allocated in the arena of the
+/// `AstSeed`
+pub mod alloc {
+    use super::*;
+
+    pub fn deserialize<'de, 'ast, D, Id, A, T>(
+        seed: &mut AstSeed<A, Id>,
+        deserializer: D,
+    ) -> Result<&'ast mut T, D::Error>
+    where
+        D: Deserializer<'de>,
+        Id: 'ast,
+        A: ArenaRef<'ast, Id>,
+        T: AstAlloc<'ast, Id> + DeserializeState<'de, AstSeed<A, Id>>,
+    {
+        let value = T::deserialize_state(seed, deserializer)?;
+        Ok(seed.arena.arena().alloc(value))
+    }
+
+    pub fn serialize<'a, S, T, Seed>(
+        self_: &&'a mut T,
+        serializer: S,
+        seed: &Seed,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: SerializeState<Seed>,
+    {
+        (**self_).serialize_state(serializer, seed)
+    }
+}
+
+/// Like `alloc` but for slices of nodes
+pub mod alloc_slice {
+    use super::*;
+
+    pub fn deserialize<'de, 'ast, D, Id, A, T>(
+        seed: &mut AstSeed<A, Id>,
+        deserializer: D,
+    ) -> Result<&'ast mut [T], D::Error>
+    where
+        D: Deserializer<'de>,
+        Id: 'ast,
+        A: ArenaRef<'ast, Id>,
+        T: AstAlloc<'ast, Id> + DeserializeState<'de, AstSeed<A, Id>>,
+    {
+        let values = Vec::<T>::deserialize_state(seed, deserializer)?;
+        Ok(seed.arena.arena().alloc_extend(values))
+    }
+
+    pub fn serialize<'a, S, T, Seed>(
+        self_: &&'a mut [T],
+        serializer: S,
+        seed: &Seed,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: SerializeState<Seed>,
+    {
+        serializer.collect_seq(self_.iter().map(|value| Seeded::new(seed, value)))
+    }
+}
+
+/// Like `alloc` but for optional nodes
+pub mod alloc_option {
+    use super::*;
+
+    pub fn deserialize<'de, 'ast, D, Id, A, T>(
+        seed: &mut AstSeed<A, Id>,
+        deserializer: D,
+    ) -> Result<Option<&'ast mut T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        Id: 'ast,
+        A: ArenaRef<'ast, Id>,
+        T: AstAlloc<'ast, Id> + DeserializeState<'de, AstSeed<A, Id>>,
+    {
+        let arena = seed.arena.arena();
+        let value = Option::<T>::deserialize_state(seed, deserializer)?;
+        Ok(value.map(|value| arena.alloc(value)))
+    }
+
+    pub fn serialize<'a, S, T, Seed>(
+        self_: &Option<&'a mut T>,
+        serializer: S,
+        seed: &Seed,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: SerializeState<Seed>,
+    {
+        match *self_ {
+            Some(ref value) => serializer.serialize_some(&Seeded::new(seed, &**value)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+/// Serializes a `NotNaN<f64>` as a plain `f64`
+pub mod not_nan {
+    use ordered_float::NotNaN;
+    use serde::de::Deserialize;
+    use serde::ser::Serialize;
+    use super::*;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<NotNaN<f64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let f = f64::deserialize(deserializer)?;
+        NotNaN::new(f).map_err(|_| D::Error::custom("NaN is not a valid float literal"))
+    }
+
+    pub fn serialize<S>(self_: &NotNaN<f64>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self_.into_inner().serialize(serializer)
+    }
+}
+
 
 pub fn deserialize_group<'de, Id, T, D>(
     seed: &mut Seed<Id, T>,
@@ -0,0 +1,70 @@
+//! Functions for finding names which are similar to a name which could not be found, used to add
+//! "did you mean ...?" hints to errors.
+use std::cmp;
+
+use ast::is_operator_char;
+
+/// Computes the Levenshtein distance between `l` and `r`, that is the number of single character
+/// insertions, deletions and substitutions needed to turn `l` into `r`
+pub fn levenshtein_distance(l: &str, r: &str) -> usize {
+    let r: Vec<char> = r.chars().collect();
+    let mut previous: Vec<usize> = (0..r.len() + 1).collect();
+    let mut current = vec![0; r.len() + 1];
+    for (i, l_char) in l.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &r_char) in r.iter().enumerate() {
+            let substitution = previous[j] + if l_char == r_char { 0 } else { 1 };
+            current[j + 1] = cmp::min(substitution, cmp::min(previous[j + 1], current[j]) + 1);
+        }
+        ::std::mem::swap(&mut previous, &mut current);
+    }
+    previous[r.len()]
+}
+
+/// Returns the candidate which is closest to `name`, if any candidate is close enough to likely be
+/// what was meant. Ties are broken by picking the alphabetically smallest candidate so that the
+/// result does not depend on the iteration order of `candidates`. Operators are only matched
+/// against other operators and identifiers against other identifiers.
+pub fn closest_match<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = cmp::max(name.chars().count(), 3) / 3;
+    let is_operator = name.starts_with(is_operator_char);
+    candidates
+        .into_iter()
+        .filter(|&candidate| candidate != name)
+        .filter(|&candidate| candidate.starts_with(is_operator_char) == is_operator)
+        .map(|candidate| (levenshtein_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        assert_eq!(levenshtein_distance("", ""), 0);
+        assert_eq!(levenshtein_distance("abc", ""), 3);
+        assert_eq!(levenshtein_distance("", "abc"), 3);
+        assert_eq!(levenshtein_distance("kitten", "sitting"), 3);
+        assert_eq!(levenshtein_distance("Option", "Optoin"), 2);
+    }
+
+    #[test]
+    fn closest() {
+        let candidates = ["Option", "Result", "List", "Int"];
+        assert_eq!(
+            closest_match("Optin", candidates.iter().cloned()),
+            Some("Option")
+        );
+        assert_eq!(closest_match("Lst", candidates.iter().cloned()), Some("List"));
+        assert_eq!(closest_match("Float", candidates.iter().cloned()), None);
+        assert_eq!(closest_match("x", ["y", "z"].iter().cloned()), Some("y"));
+        assert_eq!(closest_match("+", ["_", "x"].iter().cloned()), None);
+        assert_eq!(closest_match("<=", ["_", "<"].iter().cloned()), Some("<"));
+    }
+}
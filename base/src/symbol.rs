@@ -369,6 +369,8 @@ impl<'a> From<&'a Name> for NameBuf {
 pub struct Symbols {
     strings: FnvMap<Symbol, NameBuf>,
     indexes: FnvMap<NameBuf, Symbol>,
+    /// The number of symbols created by `SymbolModule::fresh_symbol` for each module and prefix
+    fresh_counts: FnvMap<(NameBuf, String), usize>,
 }
 
 impl Symbols {
@@ -376,6 +378,7 @@ impl Symbols {
         Symbols {
             strings: FnvMap::default(),
             indexes: FnvMap::default(),
+            fresh_counts: FnvMap::default(),
         }
     }
 
@@ -446,6 +449,56 @@ impl<'a> SymbolModule<'a> {
         symbol
     }
 
+    /// Creates a symbol named `prefix` which is distinct from every other symbol created with
+    /// `fresh_symbol` for the module, including symbols created through other `SymbolModule`s
+    /// wrapping the same `Symbols`. The symbol is numbered by how many symbols the module has
+    /// created with the same prefix since the last call to `reset_fresh_symbols`, so compiling a
+    /// module again creates the same symbols as long as the counts are reset first.
+    ///
+    /// Since `:` can not be part of an identifier the symbol can not collide with a name
+    /// written in the source.
+    ///
+    /// ```
+    /// # use gluon_base::symbol::{Symbols, SymbolModule};
+    /// let mut symbols = Symbols::new();
+    /// let x = SymbolModule::new(String::from("test"), &mut symbols).fresh_symbol("x");
+    /// let mut module = SymbolModule::new(String::from("test"), &mut symbols);
+    /// assert_eq!(x.as_ref(), "x:1");
+    /// assert_eq!(module.fresh_symbol("x").as_ref(), "x:2");
+    /// assert_eq!(module.fresh_symbol("y").as_ref(), "y:1");
+    /// assert_eq!(x.declared_name(), "x");
+    /// ```
+    pub fn fresh_symbol(&mut self, prefix: &str) -> Symbol {
+        let count = {
+            let count = self.symbols
+                .fresh_counts
+                .entry((self.module.clone(), prefix.to_string()))
+                .or_insert(0);
+            *count += 1;
+            *count
+        };
+        self.symbols.symbol(format!("{}:{}", prefix, count))
+    }
+
+    /// Restarts the numbering of the symbols created by `fresh_symbol` for the module. Called
+    /// before each compilation of the module so that the compilation creates the same symbols
+    /// every time.
+    ///
+    /// ```
+    /// # use gluon_base::symbol::{Symbols, SymbolModule};
+    /// let mut symbols = Symbols::new();
+    /// let mut module = SymbolModule::new(String::from("test"), &mut symbols);
+    /// let x = module.fresh_symbol("x");
+    /// module.reset_fresh_symbols();
+    /// assert_eq!(module.fresh_symbol("x"), x);
+    /// ```
+    pub fn reset_fresh_symbols(&mut self) {
+        let module = &self.module;
+        self.symbols
+            .fresh_counts
+            .retain(|&(ref counted_module, _), _| counted_module != module);
+    }
+
     pub fn module(&self) -> &Name {
         &self.module
     }
@@ -473,6 +526,10 @@ impl IdentEnv for Symbols {
     fn from_str(&mut self, s: &str) -> Symbol {
         self.symbol(s)
     }
+
+    fn fresh_ident(&mut self, prefix: &str) -> Symbol {
+        SymbolModule::new(String::new(), self).fresh_symbol(prefix)
+    }
 }
 
 impl<'s> DisplayEnv for SymbolModule<'s> {
@@ -487,4 +544,8 @@ impl<'a> IdentEnv for SymbolModule<'a> {
     fn from_str(&mut self, s: &str) -> Symbol {
         self.symbol(s)
     }
+
+    fn fresh_ident(&mut self, prefix: &str) -> Symbol {
+        self.fresh_symbol(prefix)
+    }
 }
@@ -0,0 +1,381 @@
+//! Wall time and peak memory usage of each phase of the compiler, collected per module.
+//!
+//! Collection is disabled by default and is enabled for the current thread with `enable`. While a
+//! phase is running any phase started inside of it (such as the compilation of a module imported
+//! during macro expansion) pauses it so the reported numbers only include the work done by the
+//! phase itself.
+//!
+//! Peak memory usage is the growth of the peak resident set size of the process while a phase is
+//! running, so it includes memory used by other threads. It is only measured on Linux.
+use std::cell::RefCell;
+use std::cmp;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The phases of the compiler which are timed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Lex,
+    Parse,
+    MacroExpansion,
+    Typecheck,
+    Rename,
+    Metadata,
+    Codegen,
+}
+
+const PHASES: [Phase; 7] = [
+    Phase::Lex,
+    Phase::Parse,
+    Phase::MacroExpansion,
+    Phase::Typecheck,
+    Phase::Rename,
+    Phase::Metadata,
+    Phase::Codegen,
+];
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Phase::Lex => "lexing",
+            Phase::Parse => "parsing",
+            Phase::MacroExpansion => "macro expansion",
+            Phase::Typecheck => "typechecking",
+            Phase::Rename => "renaming",
+            Phase::Metadata => "metadata",
+            Phase::Codegen => "codegen",
+        };
+        name.fmt(f)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod memory {
+    use std::fs::{File, OpenOptions};
+    use std::io::{Read, Write};
+
+    /// Reads a field of `/proc/self/status` which is given in kilobytes
+    fn status_bytes(field: &str) -> Option<usize> {
+        let mut status = String::new();
+        File::open("/proc/self/status")
+            .ok()?
+            .read_to_string(&mut status)
+            .ok()?;
+        let line = status.lines().find(|line| line.starts_with(field))?;
+        let kilobytes = line[field.len()..]
+            .trim()
+            .trim_right_matches("kB")
+            .trim()
+            .parse::<usize>()
+            .ok()?;
+        Some(kilobytes * 1024)
+    }
+
+    /// Resets the peak resident set size to the current one, which is returned
+    pub fn reset_peak() -> Option<usize> {
+        OpenOptions::new()
+            .write(true)
+            .open("/proc/self/clear_refs")
+            .ok()?
+            .write_all(b"5")
+            .ok()?;
+        status_bytes("VmRSS:")
+    }
+
+    pub fn peak() -> Option<usize> {
+        status_bytes("VmHWM:")
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod memory {
+    pub fn reset_peak() -> Option<usize> {
+        None
+    }
+
+    pub fn peak() -> Option<usize> {
+        None
+    }
+}
+
+/// The time spent in a phase and the largest amount of memory it had allocated at once
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhaseTiming {
+    pub time: Duration,
+    /// `None` if the peak memory usage could not be measured
+    pub peak_bytes: Option<usize>,
+    /// Number of times the phase was run
+    pub runs: usize,
+}
+
+impl PhaseTiming {
+    fn merge(&mut self, other: &PhaseTiming) {
+        self.time += other.time;
+        self.peak_bytes = match (self.peak_bytes, other.peak_bytes) {
+            (Some(l), Some(r)) => Some(cmp::max(l, r)),
+            (l, r) => l.or(r),
+        };
+        self.runs += other.runs;
+    }
+}
+
+/// The timings of each phase which was run for a module
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleTimings {
+    pub module: String,
+    phases: [PhaseTiming; 7],
+}
+
+impl ModuleTimings {
+    fn new(module: &str) -> ModuleTimings {
+        ModuleTimings {
+            module: module.to_string(),
+            phases: Default::default(),
+        }
+    }
+
+    pub fn phase(&self, phase: Phase) -> &PhaseTiming {
+        &self.phases[phase as usize]
+    }
+
+    /// Iterates over the phases which were run at least once
+    pub fn phases<'a>(&'a self) -> Box<Iterator<Item = (Phase, &'a PhaseTiming)> + 'a> {
+        Box::new(
+            PHASES
+                .iter()
+                .map(move |&phase| (phase, self.phase(phase)))
+                .filter(|&(_, timing)| timing.runs != 0),
+        )
+    }
+}
+
+/// The timings of every module which was compiled while timings were collected, in the order that
+/// the modules were first seen
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Timings {
+    pub modules: Vec<ModuleTimings>,
+}
+
+impl Timings {
+    pub fn module(&self, module: &str) -> Option<&ModuleTimings> {
+        self.modules.iter().find(|timings| timings.module == module)
+    }
+
+    /// Returns the sum of the timings of each module
+    pub fn total(&self) -> ModuleTimings {
+        let mut total = ModuleTimings::new("total");
+        for timings in &self.modules {
+            for (l, r) in total.phases.iter_mut().zip(&timings.phases) {
+                l.merge(r);
+            }
+        }
+        total
+    }
+
+    fn record(&mut self, module: &str, phase: Phase, timing: &PhaseTiming) {
+        let index = match self.modules.iter().position(|m| m.module == module) {
+            Some(index) => index,
+            None => {
+                self.modules.push(ModuleTimings::new(module));
+                self.modules.len() - 1
+            }
+        };
+        self.modules[index].phases[phase as usize].merge(timing);
+    }
+}
+
+fn fmt_bytes(f: &mut fmt::Formatter, bytes: Option<usize>) -> fmt::Result {
+    match bytes {
+        None => write!(f, "{:>12}", "-"),
+        Some(bytes) if bytes < 1024 => write!(f, "{:>8} B   ", bytes),
+        Some(bytes) if bytes < 1024 * 1024 => write!(f, "{:>8.1} KiB", bytes as f64 / 1024.),
+        Some(bytes) => write!(f, "{:>8.1} MiB", bytes as f64 / (1024. * 1024.)),
+    }
+}
+
+fn fmt_module(f: &mut fmt::Formatter, timings: &ModuleTimings) -> fmt::Result {
+    writeln!(f, "{}", timings.module)?;
+    for (phase, timing) in timings.phases() {
+        let millis =
+            timing.time.as_secs() as f64 * 1000. + timing.time.subsec_nanos() as f64 / 1e6;
+        write!(f, "    {:<16}{:>12.3}ms  ", phase.to_string(), millis)?;
+        fmt_bytes(f, timing.peak_bytes)?;
+        writeln!(f, "")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<20}{:>14}  {:>12}", "phase", "wall time", "peak memory")?;
+        for timings in &self.modules {
+            fmt_module(f, timings)?;
+        }
+        fmt_module(f, &self.total())
+    }
+}
+
+/// A phase which is currently running
+struct Active {
+    module: String,
+    phase: Phase,
+    timing: PhaseTiming,
+    segment_start: Instant,
+    /// The resident set size when the current segment of the phase started
+    segment_memory: Option<usize>,
+}
+
+impl Active {
+    fn resume(&mut self) {
+        self.segment_start = Instant::now();
+        self.segment_memory = memory::reset_peak();
+    }
+
+    fn pause(&mut self) {
+        self.timing.time += self.segment_start.elapsed();
+        if let (Some(start), Some(peak)) = (self.segment_memory, memory::peak()) {
+            let peak = peak.saturating_sub(start);
+            self.timing.peak_bytes = Some(cmp::max(self.timing.peak_bytes.unwrap_or(0), peak));
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    timings: Timings,
+    active: Vec<Active>,
+}
+
+thread_local!(static STATE: RefCell<Option<State>> = RefCell::new(None));
+
+/// Starts collecting timings on the current thread
+pub fn enable() {
+    STATE.with(|state| *state.borrow_mut() = Some(State::default()));
+}
+
+/// Stops collecting timings on the current thread and returns what has been collected
+pub fn take() -> Option<Timings> {
+    let state = STATE.with(|state| state.borrow_mut().take());
+    state.map(|state| state.timings)
+}
+
+fn start(module: Option<&str>, phase: Phase) -> bool {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let state = match *state {
+            Some(ref mut state) => state,
+            None => return false,
+        };
+        let module = match module {
+            Some(module) => module.to_string(),
+            None => match state.active.last() {
+                Some(active) => active.module.clone(),
+                None => return false,
+            },
+        };
+        if let Some(active) = state.active.last_mut() {
+            active.pause();
+        }
+        let mut active = Active {
+            module,
+            phase,
+            timing: PhaseTiming {
+                runs: 1,
+                ..PhaseTiming::default()
+            },
+            segment_start: Instant::now(),
+            segment_memory: None,
+        };
+        active.resume();
+        state.active.push(active);
+        true
+    })
+}
+
+fn stop() {
+    STATE.with(|state| {
+        if let Some(ref mut state) = *state.borrow_mut() {
+            if let Some(mut active) = state.active.pop() {
+                active.pause();
+                state
+                    .timings
+                    .record(&active.module, active.phase, &active.timing);
+            }
+            if let Some(active) = state.active.last_mut() {
+                active.resume();
+            }
+        }
+    })
+}
+
+struct Guard(bool);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if self.0 {
+            stop();
+        }
+    }
+}
+
+/// Runs `f`, recording the time it takes as `phase` of `module`. Unnamed modules (such as the
+/// implicit prelude) are recorded as part of the module of the innermost running phase.
+pub fn time<F, R>(module: &str, phase: Phase, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let module = if module.is_empty() { None } else { Some(module) };
+    let _guard = Guard(start(module, phase));
+    f()
+}
+
+/// Runs `f`, recording the time it takes as `phase` of the module of the innermost running phase.
+/// Nothing is recorded if no phase is running.
+pub fn nested<F, R>(phase: Phase, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let _guard = Guard(start(None, phase));
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_phases_are_excluded_from_the_outer_phase() {
+        enable();
+        time("a", Phase::MacroExpansion, || {
+            time("b", Phase::Parse, || {
+                nested(Phase::Lex, || ::std::thread::sleep(Duration::from_millis(20)))
+            });
+        });
+        nested(Phase::Lex, || ());
+        let timings = take().unwrap();
+
+        assert_eq!(
+            timings
+                .modules
+                .iter()
+                .map(|m| &m.module[..])
+                .collect::<Vec<_>>(),
+            ["b", "a"]
+        );
+        let b = timings.module("b").unwrap();
+        assert!(b.phase(Phase::Lex).time >= Duration::from_millis(20));
+        assert!(b.phase(Phase::Parse).time < Duration::from_millis(20));
+        assert_eq!(b.phase(Phase::Typecheck).runs, 0);
+
+        let a = timings.module("a").unwrap();
+        assert!(a.phase(Phase::MacroExpansion).time < Duration::from_millis(20));
+        assert_eq!(a.phase(Phase::Lex).runs, 0);
+        assert_eq!(timings.total().phase(Phase::Lex).runs, 1);
+    }
+
+    #[test]
+    fn nothing_is_recorded_when_disabled() {
+        assert_eq!(time("a", Phase::Parse, || 1), 1);
+        assert_eq!(take(), None);
+    }
+}
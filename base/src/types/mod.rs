@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 use std::fmt;
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
+use std::mem;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 use std::marker::PhantomData;
@@ -44,8 +45,21 @@ pub trait TypeEnv: KindEnv {
         fields: &[Symbol],
         selector: RecordSelector,
     ) -> Option<(ArcType, ArcType)>;
+
+    /// Returns the names of the values in the environment, used to suggest alternatives to
+    /// undefined variables. Environments which can not list their values return no names.
+    fn value_names(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// Returns the names of the types in the environment, used to suggest alternatives to
+    /// undefined types. Environments which can not list their types return no names.
+    fn type_names(&self) -> Vec<&str> {
+        Vec::new()
+    }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum RecordSelector {
     // Selects a record which exactly has the fields
     Exact,
@@ -87,6 +101,14 @@ impl<'a, T: ?Sized + TypeEnv> TypeEnv for &'a T {
     ) -> Option<(ArcType, ArcType)> {
         (**self).find_record(fields, selector)
     }
+
+    fn value_names(&self) -> Vec<&str> {
+        (**self).value_names()
+    }
+
+    fn type_names(&self) -> Vec<&str> {
+        (**self).type_names()
+    }
 }
 
 /// Trait which is a `TypeEnv` which also provides access to the type representation of some
@@ -157,6 +179,8 @@ where
             BuiltinType::Float => self.float(),
             BuiltinType::Array => self.array_builtin(),
             BuiltinType::Function => self.function_builtin(),
+            BuiltinType::ImplicitFunction => Type::builtin(BuiltinType::ImplicitFunction),
+            BuiltinType::NatAdd => Type::builtin(BuiltinType::NatAdd),
         }
     }
 
@@ -183,6 +207,11 @@ pub enum BuiltinType {
     Array,
     /// Type constructor for functions, `(->) a b : Type -> Type -> Type`
     Function,
+    /// Type constructor for functions whose argument is implicit, `[a] -> b`. The argument is
+    /// filled in by the compiler with a binding marked `#[implicit]` at each use of the function
+    ImplicitFunction,
+    /// Addition of type-level naturals, `(+) n m : Nat -> Nat -> Nat`
+    NatAdd,
 }
 
 impl BuiltinType {
@@ -202,6 +231,7 @@ impl ::std::str::FromStr for BuiltinType {
             "Char" => BuiltinType::Char,
             "Array" => BuiltinType::Array,
             "->" => BuiltinType::Function,
+            "+" => BuiltinType::NatAdd,
             _ => return Err(()),
         };
         Ok(t)
@@ -218,6 +248,8 @@ impl BuiltinType {
             BuiltinType::Float => "Float",
             BuiltinType::Array => "Array",
             BuiltinType::Function => "->",
+            BuiltinType::ImplicitFunction => "[->]",
+            BuiltinType::NatAdd => "+",
         }
     }
 }
@@ -536,6 +568,108 @@ impl<Id, T> Field<Id, T> {
     }
 }
 
+/// Returns the name which the `index`th overload of `name` is stored under in a record type,
+/// `name@index`. Several functions can be registered from Rust under the same name, all but the
+/// first of them are renamed so that the fields of the record stay unique.
+pub fn overload_name(name: &str, index: usize) -> String {
+    format!("{}@{}", name, index)
+}
+
+/// Returns the name which `field` overloads along with the index of the overload if `field` were
+/// created by `overload_name`
+pub fn overloaded_name(field: &str) -> Option<(&str, usize)> {
+    let at = field.rfind('@')?;
+    let (name, index) = (&field[..at], &field[at + 1..]);
+    if name.is_empty() {
+        return None;
+    }
+    index.parse().ok().map(|index| (name, index))
+}
+
+/// Returns the name of `field` as it should be shown to the user, the overloads of a name are all
+/// shown under that name
+pub fn field_display_name(field: &str) -> &str {
+    overloaded_name(field).map_or(field, |(name, _)| name)
+}
+
+/// Returns true if `l` and `r` are the same type up to the names of their generic variables,
+/// `forall a . a -> Int` and `forall b . b -> Int` are equivalent
+pub fn alpha_equivalent<Id>(l: &ArcType<Id>, r: &ArcType<Id>) -> bool
+where
+    Id: Clone + Eq + Hash,
+{
+    AlphaEquivalence {
+        generics: FnvMap::default(),
+    }.equivalent(l, r)
+}
+
+struct AlphaEquivalence<Id> {
+    /// Maps the generic variables of the left type to the ones in the right type
+    generics: FnvMap<Id, Id>,
+}
+
+impl<Id> AlphaEquivalence<Id>
+where
+    Id: Clone + Eq + Hash,
+{
+    fn equivalent(&mut self, l: &ArcType<Id>, r: &ArcType<Id>) -> bool {
+        match (&**l, &**r) {
+            (&Type::Forall(ref l_params, ref l, _), &Type::Forall(ref r_params, ref r, _)) => {
+                l_params.len() == r_params.len()
+                    && l_params
+                        .iter()
+                        .zip(r_params)
+                        .all(|(l, r)| self.generic_equivalent(l, r))
+                    && self.equivalent(l, r)
+            }
+            (&Type::Generic(ref l), &Type::Generic(ref r)) => self.generic_equivalent(l, r),
+            (&Type::App(ref l, ref l_args), &Type::App(ref r, ref r_args)) => {
+                l_args.len() == r_args.len() && self.equivalent(l, r)
+                    && l_args
+                        .iter()
+                        .zip(r_args)
+                        .all(|(l, r)| self.equivalent(l, r))
+            }
+            (&Type::Record(ref l), &Type::Record(ref r))
+            | (&Type::Variant(ref l), &Type::Variant(ref r)) => self.equivalent(l, r),
+            (
+                &Type::ExtendRow {
+                    types: ref l_types,
+                    fields: ref l_fields,
+                    rest: ref l_rest,
+                },
+                &Type::ExtendRow {
+                    types: ref r_types,
+                    fields: ref r_fields,
+                    rest: ref r_rest,
+                },
+            ) => {
+                l_types == r_types && l_fields.len() == r_fields.len()
+                    && l_fields.iter().zip(r_fields).all(|(l, r)| {
+                        l.name == r.name && self.equivalent(&l.typ, &r.typ)
+                    })
+                    && self.equivalent(l_rest, r_rest)
+            }
+            (&Type::Alias(ref l), &Type::Alias(ref r)) => l.name == r.name,
+            _ => l == r,
+        }
+    }
+
+    fn generic_equivalent(&mut self, l: &Generic<Id>, r: &Generic<Id>) -> bool {
+        if l.kind != r.kind {
+            return false;
+        }
+        if let Some(id) = self.generics.get(&l.id) {
+            return *id == r.id;
+        }
+        if self.generics.values().any(|id| *id == r.id) {
+            return false;
+        }
+        self.generics.insert(l.id.clone(), r.id.clone());
+        true
+    }
+}
+
 /// The representation of gluon's types.
 ///
 /// For efficiency this enum is not stored directly but instead a pointer wrapper which derefs to
@@ -606,6 +740,8 @@ pub enum Type<Id, T = ArcType<Id>> {
     /// A variable that needs to be instantiated with a fresh type variable
     /// when the binding is referred to.
     Generic(#[cfg_attr(feature = "serde_derive", serde(state))] Generic<Id>),
+    /// A type-level natural number, of kind `Nat`
+    Nat(u64),
     Alias(#[cfg_attr(feature = "serde_derive", serde(state))] AliasRef<Id, T>),
     Skolem(#[cfg_attr(feature = "serde_derive", serde(state))] Skolem<Id>),
 }
@@ -617,6 +753,14 @@ impl<Id, T> Type<Id, T> {
             _ => None,
         }
     }
+
+    /// Returns true if `self` is the `(+)` constructor of type-level naturals
+    pub fn is_nat_add(&self) -> bool {
+        match *self {
+            Type::Builtin(BuiltinType::NatAdd) => true,
+            _ => false,
+        }
+    }
 }
 
 impl<Id, T> Type<Id, T>
@@ -635,6 +779,10 @@ where
         T::from(Type::Builtin(typ))
     }
 
+    pub fn nat(n: u64) -> T {
+        T::from(Type::Nat(n))
+    }
+
     pub fn forall(params: Vec<Generic<Id>>, typ: T) -> T {
         Type::forall_with_vars(params, typ, None)
     }
@@ -743,6 +891,16 @@ where
         })
     }
 
+    pub fn implicit_function(args: Vec<T>, ret: T) -> T
+    where
+        T: Clone,
+    {
+        let function: T = Type::builtin(BuiltinType::ImplicitFunction);
+        args.into_iter().rev().fold(ret, |body, arg| {
+            Type::app(function.clone(), collect![arg, body])
+        })
+    }
+
     pub fn generic(typ: Generic<Id>) -> T {
         T::from(Type::Generic(typ))
     }
@@ -805,15 +963,28 @@ where
     T: Deref<Target = Type<Id, T>>,
 {
     pub fn as_function(&self) -> Option<(&T, &T)> {
+        self.as_builtin_function(BuiltinType::Function)
+    }
+
+    /// Returns the argument and return type of `[a] -> b`
+    pub fn as_implicit_function(&self) -> Option<(&T, &T)> {
+        self.as_builtin_function(BuiltinType::ImplicitFunction)
+    }
+
+    fn as_builtin_function(&self, function: BuiltinType) -> Option<(&T, &T)> {
         if let Type::App(ref app, ref args) = *self {
             if args.len() == 2 {
-                if let Type::Builtin(BuiltinType::Function) = **app {
-                    return Some((&args[0], &args[1]));
+                if let Type::Builtin(b) = **app {
+                    if b == function {
+                        return Some((&args[0], &args[1]));
+                    }
                 }
             } else if args.len() == 1 {
                 if let Type::App(ref app, ref args2) = **app {
-                    if let Type::Builtin(BuiltinType::Function) = **app {
-                        return Some((&args2[0], &args[0]));
+                    if let Type::Builtin(b) = **app {
+                        if b == function {
+                            return Some((&args2[0], &args[0]));
+                        }
                     }
                 }
             }
@@ -879,6 +1050,11 @@ where
     fn kind_(&self, applied_args: usize) -> Cow<ArcKind> {
         let mut immediate_kind = match *self {
             Type::App(ref t, ref args) => t.kind_(args.len()),
+            Type::Nat(_) => Cow::Owned(Kind::nat()),
+            Type::Builtin(BuiltinType::NatAdd) => Cow::Owned(Kind::function(
+                Kind::nat(),
+                Kind::function(Kind::nat(), Kind::nat()),
+            )),
             Type::Hole | Type::Opaque | Type::Builtin(_) | Type::Record(_) | Type::Variant(_) => {
                 Cow::Owned(Kind::typ())
             }
@@ -1048,6 +1224,15 @@ impl<Id> ArcType<Id> {
         }
     }
 
+    /// Returns the type which remains after all implicit arguments have been applied,
+    /// `[a] -> [b] -> c` becomes `c`
+    pub fn remove_implicit_args(&self) -> &ArcType<Id> {
+        match self.as_implicit_function() {
+            Some((_, ret)) => ret.remove_implicit_args(),
+            None => self,
+        }
+    }
+
     pub fn skolemize(&self, named_variables: &mut FnvMap<Id, ArcType<Id>>) -> ArcType<Id>
     where
         Id: Clone + Eq + Hash,
@@ -1205,7 +1390,12 @@ impl ArcType {
     /// ```
     pub fn apply_args(&self, args: &[ArcType]) -> Option<ArcType> {
         let params = self.params();
-        let mut typ = self.remove_forall().clone();
+        // Only the `Forall` binding the parameters is removed, any `forall` inside the aliased
+        // type is kept (`type Id = forall a . a -> a`)
+        let mut typ = match **self {
+            Type::Forall(_, ref typ, _) => typ.clone(),
+            _ => self.clone(),
+        };
 
         // It is ok to take the type only if it is fully applied or if it
         // the missing argument only appears in order at the end, i.e:
@@ -1244,7 +1434,7 @@ impl ArcType {
             },
         }
 
-        Some(walk_move_type(typ.remove_forall().clone(), &mut |typ| {
+        Some(walk_move_type(typ, &mut |typ| {
             match **typ {
                 Type::Generic(ref generic) => {
                     // Replace the generic variable with the type from the list
@@ -1380,6 +1570,14 @@ pub struct RowIteratorMut<'a, Id: 'a, T: 'a> {
     rest: *mut T,
 }
 
+impl<'a, Id: 'a, T: 'a> RowIteratorMut<'a, Id, T> {
+    /// Returns the type the iterator is currently at. After the iterator has been exhausted this
+    /// is the rest of the row.
+    pub fn current_type(&mut self) -> &mut T {
+        unsafe { &mut *self.rest }
+    }
+}
+
 impl<'a, Id: 'a, T: 'a> Iterator for RowIteratorMut<'a, Id, T>
 where
     T: DerefMut<Target = Type<Id, T>>,
@@ -1574,15 +1772,15 @@ where
     where
         I: AsRef<str>,
     {
-        const INDENT: usize = 4;
-
         let arena = printer.arena;
+        let indent = printer.indent;
 
         let p = self.prec;
         let typ = self.typ;
         let doc = match **typ {
             Type::Hole => arena.text("_"),
             Type::Opaque => arena.text("<opaque>"),
+            Type::Nat(n) => arena.text(n.to_string()),
             Type::Forall(ref args, ref typ, _) => {
                 let doc = chain![arena;
                     chain![arena;
@@ -1605,8 +1803,19 @@ where
                     skolem.id.to_string()
                 ],
             Type::Generic(ref gen) => arena.text(gen.id.as_ref()),
-            Type::App(ref t, ref args) => match self.typ.as_function() {
-                Some(_) => self.pretty_function(printer).nest(INDENT),
+            Type::App(ref t, ref args) => match self.typ
+                .as_function()
+                .or_else(|| self.typ.as_implicit_function())
+            {
+                Some(_) => self.pretty_function(printer).nest(indent),
+                // `n + m` can only be written in parentheses
+                None if t.is_nat_add() && args.len() == 2 => chain![arena;
+                    "(",
+                    dt(Prec::Top, &args[0]).pretty(printer),
+                    " + ",
+                    dt(Prec::Top, &args[1]).pretty(printer),
+                    ")"
+                ],
                 None => {
                     let doc = dt(Prec::Top, t).pretty(printer);
                     let arg_doc = arena.concat(args.iter().map(|arg| {
@@ -1614,11 +1823,40 @@ where
                             .space()
                             .append(dt(Prec::Constructor, arg).pretty(printer))
                     }));
-                    let doc = doc.append(arg_doc.nest(INDENT));
+                    let doc = doc.append(arg_doc.nest(indent));
                     p.enclose(Prec::Constructor, arena, doc).group()
                 }
             },
             Type::Variant(ref row) => {
+                let mut iter = row_iter(row);
+                for _ in iter.by_ref() {}
+                match **iter.current_type() {
+                    Type::ExtendRow { .. } | Type::EmptyRow => (),
+                    // An open variant, `[| A Int, B | r |]`
+                    _ => {
+                        let mut doc = arena.text("[| ");
+                        for (i, field) in row_iter(row).enumerate() {
+                            if i != 0 {
+                                doc = doc.append(", ");
+                            }
+                            doc = doc.append(field.name.as_ref());
+                            for arg in arg_iter(&field.typ) {
+                                doc = chain![arena;
+                                    doc,
+                                    " ",
+                                    dt(Prec::Constructor, arg).pretty(printer)
+                                ];
+                            }
+                        }
+                        return chain![arena;
+                            doc,
+                            " | ",
+                            dt(Prec::Top, iter.current_type()).pretty(printer),
+                            " |]"
+                        ].group();
+                    }
+                }
+
                 let mut first = true;
                 let mut doc = arena.nil();
 
@@ -1644,7 +1882,7 @@ where
                 p.enclose(Prec::Constructor, arena, doc).group()
             }
             Type::Builtin(ref t) => match *t {
-                BuiltinType::Function => chain![arena; "(", t.to_str(), ")"],
+                BuiltinType::Function | BuiltinType::NatAdd => chain![arena; "(", t.to_str(), ")"],
                 _ => arena.text(t.to_str()),
             },
             Type::Record(ref row) => {
@@ -1673,11 +1911,11 @@ where
 
                 doc = match **row {
                     Type::EmptyRow => doc,
-                    Type::ExtendRow { .. } => doc.append(top(row).pretty(printer)).nest(INDENT),
+                    Type::ExtendRow { .. } => doc.append(top(row).pretty(printer)).nest(indent),
                     _ => doc.append(arena.space())
                         .append("| ")
                         .append(top(row).pretty(printer))
-                        .nest(INDENT),
+                        .nest(indent),
                 };
                 if !empty_fields {
                     doc = doc.append(newline);
@@ -1760,11 +1998,11 @@ where
                         match *field.typ {
                             // Records handle nesting on their own
                             Type::Record(_) => (),
-                            _ => rhs = rhs.nest(INDENT),
+                            _ => rhs = rhs.nest(indent),
                         }
                         let f = chain![arena;
                             pretty_print::doc_comment(arena, field.typ.comment()),
-                            pretty_print::ident(arena, field.name.as_ref()),
+                            pretty_print::ident(arena, field_display_name(field.name.as_ref())),
                             " : ",
                             rhs.group(),
                             if i + 1 != fields.len() {
@@ -1839,7 +2077,21 @@ where
 
                 p.enclose(Prec::Function, arena, doc)
             }
-            None => self.pretty(printer),
+            None => match self.typ.as_implicit_function() {
+                Some((arg, ret)) => {
+                    let doc = chain![arena;
+                        "[",
+                        top(arg).pretty(printer).group(),
+                        "]",
+                        printer.space_after(arg.span().end),
+                        "-> ",
+                        top(ret).pretty_function(printer)
+                    ];
+
+                    p.enclose(Prec::Function, arena, doc)
+                }
+                None => self.pretty(printer),
+            },
         }
     }
 }
@@ -1855,6 +2107,97 @@ where
     dt(Prec::Top, typ).pretty(printer)
 }
 
+/// Hashes the structure of `typ`. Unlike the `Hash` implementation of the type, identifiers are
+/// hashed by their name instead of by which symbol they are, so equal types hash the same way
+/// every time the compiler runs.
+pub fn hash_structure<I, T, H>(typ: &T, hasher: &mut H)
+where
+    I: AsRef<str>,
+    T: Deref<Target = Type<I, T>>,
+    H: Hasher,
+{
+    fn hash_generics<I, H>(generics: &[Generic<I>], hasher: &mut H)
+    where
+        I: AsRef<str>,
+        H: Hasher,
+    {
+        generics.len().hash(hasher);
+        for generic in generics {
+            generic.id.as_ref().hash(hasher);
+            generic.kind.hash(hasher);
+        }
+    }
+
+    fn hash_types<'a, I, T, H, Iter>(types: Iter, hasher: &mut H)
+    where
+        I: AsRef<str>,
+        T: Deref<Target = Type<I, T>> + 'a,
+        H: Hasher,
+        Iter: ExactSizeIterator<Item = &'a T>,
+    {
+        types.len().hash(hasher);
+        for typ in types {
+            hash_structure(typ, hasher);
+        }
+    }
+
+    mem::discriminant(&**typ).hash(hasher);
+    match **typ {
+        Type::Hole | Type::Opaque | Type::EmptyRow => (),
+        Type::Builtin(builtin) => builtin.hash(hasher),
+        Type::Nat(n) => n.hash(hasher),
+        Type::Forall(ref params, ref typ, ref vars) => {
+            hash_generics(params, hasher);
+            hash_structure(typ, hasher);
+            vars.as_ref().map(|vars| vars.len()).hash(hasher);
+            if let Some(ref vars) = *vars {
+                hash_types(vars.iter(), hasher);
+            }
+        }
+        Type::App(ref f, ref args) => {
+            hash_structure(f, hasher);
+            hash_types(args.iter(), hasher);
+        }
+        Type::Record(ref row) | Type::Variant(ref row) => hash_structure(row, hasher),
+        Type::ExtendRow {
+            ref types,
+            ref fields,
+            ref rest,
+        } => {
+            types.len().hash(hasher);
+            for field in types {
+                field.name.as_ref().hash(hasher);
+                hash_structure(&field.typ._typ, hasher);
+            }
+            fields.len().hash(hasher);
+            for field in fields {
+                field.name.as_ref().hash(hasher);
+                hash_structure(&field.typ, hasher);
+            }
+            hash_structure(rest, hasher);
+        }
+        Type::Ident(ref id) => id.as_ref().hash(hasher),
+        Type::Variable(ref var) => var.hash(hasher),
+        Type::Generic(ref generic) => {
+            generic.id.as_ref().hash(hasher);
+            generic.kind.hash(hasher);
+        }
+        Type::Alias(ref alias) => {
+            alias.index.hash(hasher);
+            alias.group.len().hash(hasher);
+            for data in alias.group.iter() {
+                data.name.as_ref().hash(hasher);
+                hash_structure(&data.typ, hasher);
+            }
+        }
+        Type::Skolem(ref skolem) => {
+            skolem.name.as_ref().hash(hasher);
+            skolem.id.hash(hasher);
+            skolem.kind.hash(hasher);
+        }
+    }
+}
+
 pub fn walk_type<'a, I, T, F>(typ: &'a T, mut f: F)
 where
     F: Walker<'a, T>,
@@ -1895,6 +2238,7 @@ where
         Type::Hole
         | Type::Opaque
         | Type::Builtin(_)
+        | Type::Nat(_)
         | Type::Variable(_)
         | Type::Generic(_)
         | Type::Skolem(_)
@@ -1932,6 +2276,7 @@ where
         Type::Hole
         | Type::Opaque
         | Type::Builtin(_)
+        | Type::Nat(_)
         | Type::Variable(_)
         | Type::Generic(_)
         | Type::Skolem(_)
@@ -2086,6 +2431,7 @@ where
         Type::Hole
         | Type::Opaque
         | Type::Builtin(_)
+        | Type::Nat(_)
         | Type::Variable(_)
         | Type::Skolem(_)
         | Type::Generic(_)
@@ -2209,6 +2555,7 @@ where
         Type::Hole => cache.hole(),
         Type::Opaque => cache.opaque(),
         Type::Builtin(ref builtin) => cache.builtin_type(builtin.clone()),
+        Type::Nat(n) => Type::nat(n),
         Type::Variable(ref var) => Type::variable(var.clone()),
         Type::Generic(ref gen) => Type::generic(gen.clone()),
         Type::Ident(ref id) => Type::ident(id.clone()),
@@ -117,6 +117,7 @@ impl<'a, I, T> TypeFormatter<'a, I, T> {
         top(self.typ).pretty(&Printer {
             arena,
             source: &Source::new(""),
+            indent: 4,
             filter: self.filter,
         })
     }
@@ -125,6 +126,7 @@ impl<'a, I, T> TypeFormatter<'a, I, T> {
         Printer {
             arena,
             source,
+            indent: 4,
             filter: self.filter,
         }
     }
@@ -153,6 +155,8 @@ where
 pub struct Printer<'a: 'e, 'e, I: 'a> {
     pub arena: &'a Arena<'a>,
     pub source: &'e Source<'a>,
+    /// The number of spaces which nested types are indented with
+    pub indent: usize,
     filter: &'a Fn(&I) -> Filter,
 }
 
@@ -161,6 +165,7 @@ impl<'a: 'e, 'e, I> Printer<'a, 'e, I> {
         Printer {
             arena,
             source,
+            indent: 4,
             filter: &|_| Filter::Retain,
         }
     }
@@ -170,11 +175,13 @@ impl<'a: 'e, 'e, I> Printer<'a, 'e, I> {
     }
 
     pub fn space_before(&self, pos: BytePos) -> DocBuilder<'a, Arena<'a>> {
-        let (doc, comments) = self.comments_before_(pos);
+        let (doc, comments, ends_with_newline) = self.comments_before_(pos);
         if doc.1 == self.arena.nil().1 {
             self.arena.space()
-        } else if comments {
+        } else if comments && !ends_with_newline {
             self.arena.space().append(doc).append(self.arena.space())
+        } else if comments {
+            self.arena.space().append(doc)
         } else {
             doc
         }
@@ -191,19 +198,25 @@ impl<'a: 'e, 'e, I> Printer<'a, 'e, I> {
     }
 
     pub fn comments_before(&self, pos: BytePos) -> DocBuilder<'a, Arena<'a>> {
-        let (doc, comments) = self.comments_before_(pos);
-        if comments {
+        let (doc, comments, ends_with_newline) = self.comments_before_(pos);
+        if comments && !ends_with_newline {
             doc.append(self.arena.space())
         } else {
             doc
         }
     }
 
-    fn comments_before_(&self, pos: BytePos) -> (DocBuilder<'a, Arena<'a>>, bool) {
+    /// Returns the comments before `pos`, whether any of them are block comments and whether
+    /// there is a line break directly before `pos`
+    fn comments_before_(&self, pos: BytePos) -> (DocBuilder<'a, Arena<'a>>, bool, bool) {
         let arena = self.arena;
         let mut doc = arena.nil();
         let mut comments = 0;
+        let mut ends_with_newline = None;
         for comment in self.source.comments_between(Span::new(0.into(), pos)).rev() {
+            if ends_with_newline.is_none() {
+                ends_with_newline = Some(comment.is_empty() || comment.starts_with("//"));
+            }
             let x = if comment.is_empty() {
                 arena.newline()
             } else if comment.starts_with("//") {
@@ -214,7 +227,7 @@ impl<'a: 'e, 'e, I> Printer<'a, 'e, I> {
             };
             doc = x.append(doc);
         }
-        (doc, comments != 0)
+        (doc, comments != 0, ends_with_newline.unwrap_or(false))
     }
 
     pub fn comments_after(&self, end: BytePos) -> DocBuilder<'a, Arena<'a>> {
@@ -0,0 +1,62 @@
+extern crate gluon_base as base;
+#[macro_use]
+extern crate pretty_assertions;
+
+use std::fmt;
+
+use base::error::{Errors, InFile, Label, Labels};
+use base::pos::{self, BytePos};
+
+#[derive(Debug, PartialEq)]
+struct Shadowed;
+
+impl fmt::Display for Shadowed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`x` shadows a previous binding")
+    }
+}
+
+impl Labels for Shadowed {
+    fn labels(&self) -> Vec<Label> {
+        vec![
+            Label::new(
+                pos::span(BytePos::from(4), BytePos::from(5)),
+                "previous binding introduced here",
+            ),
+        ]
+    }
+}
+
+fn shadowed_error() -> InFile<Shadowed> {
+    let source = "let x = 1\nlet x = 2\nx\n";
+    let errors: Errors<_> = vec![pos::spanned2(14.into(), 15.into(), Shadowed)]
+        .into_iter()
+        .collect();
+    InFile::new("test", source, errors)
+}
+
+#[test]
+fn display_error_with_label() {
+    assert_eq!(
+        shadowed_error().to_string(),
+        r#"test:Line: 2, Column: 5: `x` shadows a previous binding
+let x = 2
+    ^
+test:Line: 1, Column: 5: previous binding introduced here
+let x = 1
+    -
+"#
+    );
+}
+
+#[test]
+fn emit_colored_error() {
+    let mut output = String::new();
+    shadowed_error().emit(&mut output, true).unwrap();
+    assert!(output.contains("\x1b[1;31mtest:Line: 2, Column: 5\x1b[0m"));
+    assert!(output.contains("\x1b[1;34m-\x1b[0m"));
+
+    let mut output = String::new();
+    shadowed_error().emit(&mut output, false).unwrap();
+    assert_eq!(output, shadowed_error().to_string());
+}
@@ -0,0 +1,71 @@
+#[macro_use]
+extern crate collect_mac;
+extern crate gluon_base as base;
+
+use base::interface::{Interface, InterfaceEnv};
+use base::kind::{Kind, KindEnv};
+use base::symbol::{Symbol, SymbolRef};
+use base::types::{Alias, AliasData, ArcType, Field, Generic, Type, TypeEnv};
+
+/// The interface of a module `{ type Option a = | None | Some a, x : Int }`
+fn option_module() -> Interface {
+    let option = Symbol::from("Option");
+    let a = Generic::new(Symbol::from("a"), Kind::typ());
+    let option_a: ArcType = Type::app(
+        Type::ident(option.clone()),
+        collect![Type::generic(a.clone())],
+    );
+    let variant = Type::variant(vec![
+        Field::new(Symbol::from("None"), option_a.clone()),
+        Field::new(
+            Symbol::from("Some"),
+            Type::function(vec![Type::generic(a.clone())], option_a.clone()),
+        ),
+    ]);
+    let alias = Alias::from(AliasData::new(option.clone(), vec![a], variant));
+    let typ = Type::record(
+        vec![Field::new(option, alias)],
+        vec![Field::new(Symbol::from("x"), Type::int())],
+    );
+    Interface::new("std.option".to_string(), 123, typ)
+}
+
+#[test]
+fn interface_env_finds_module_and_types() {
+    let mut env = InterfaceEnv::new();
+    env.insert(option_module());
+
+    let module = env.find_type(SymbolRef::new("std.option"));
+    assert_eq!(
+        module.map(|typ| typ.to_string()),
+        Some(option_module().typ.to_string())
+    );
+
+    let option = env.find_type_info(SymbolRef::new("std.option.Option"));
+    assert_eq!(option.map(|alias| alias.name.as_ref()), Some("Option"));
+    assert_eq!(
+        env.find_kind(SymbolRef::new("std.option.Option")),
+        Some(Kind::function(Kind::typ(), Kind::typ()))
+    );
+
+    assert!(env.find_type(SymbolRef::new("Some")).is_some());
+    assert!(env.find_type_info(SymbolRef::new("std.option.Missing")).is_none());
+
+    assert!(env.is_up_to_date("std.option", 123));
+    assert!(!env.is_up_to_date("std.option", 124));
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn interface_roundtrip() {
+    let interface = option_module();
+
+    let mut buffer = Vec::new();
+    interface.write(&mut buffer).unwrap();
+    let read = Interface::read(&buffer[..]).unwrap();
+
+    assert_eq!(read.module, interface.module);
+    assert_eq!(read.source_hash, interface.source_hash);
+    assert_eq!(read.typ.to_string(), interface.typ.to_string());
+    assert!(read.find_type_info("Option").is_some());
+}
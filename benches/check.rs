@@ -51,5 +51,31 @@ fn clone_prelude(b: &mut Bencher) {
     b.iter(|| black_box(expr.clone()))
 }
 
-benchmark_group!(check, typecheck_prelude, clone_prelude);
+// Compares the cost of compiling a small script in a fresh VM with either implicit prelude
+fn fresh_vm(b: &mut Bencher, small_prelude: bool) {
+    b.iter(|| {
+        let vm = new_vm();
+        let result = Compiler::new()
+            .small_prelude(small_prelude)
+            .run_expr::<i32>(&vm, "<top>", "1 + 2")
+            .unwrap_or_else(|err| panic!("{}", err));
+        black_box(result)
+    })
+}
+
+fn fresh_vm_prelude(b: &mut Bencher) {
+    fresh_vm(b, false)
+}
+
+fn fresh_vm_small_prelude(b: &mut Bencher) {
+    fresh_vm(b, true)
+}
+
+benchmark_group!(
+    check,
+    typecheck_prelude,
+    clone_prelude,
+    fresh_vm_prelude,
+    fresh_vm_small_prelude
+);
 benchmark_main!(check);
@@ -0,0 +1,45 @@
+#[macro_use]
+extern crate bencher;
+
+extern crate gluon;
+
+use bencher::{black_box, Bencher};
+
+use gluon::{new_vm, Compiler};
+use gluon::vm::api::FunctionRef;
+use gluon::vm::gc::GcSettings;
+
+// Benchmarks allocation by building a list of a million cells. Most of the cells are still
+// reachable when a collection is run so this also measures how quickly live values are marked.
+fn build_list(b: &mut Bencher, allocation_buffer_size: usize) {
+    let vm = new_vm();
+    vm.set_gc_settings(GcSettings {
+        allocation_buffer_size: allocation_buffer_size,
+        ..GcSettings::default()
+    });
+    let text = r#"
+    type List a = | Nil | Cons a (List a)
+    let build n acc : Int -> List Int -> List Int =
+        if n #Int== 0 then acc else build (n #Int- 1) (Cons n acc)
+    \n -> match build n Nil with
+        | Nil -> 0
+        | Cons x _ -> x
+    "#;
+    Compiler::new().load_script(&vm, "build_list", text).unwrap();
+    let mut build_list: FunctionRef<fn(i32) -> i32> = vm.get_global("build_list").unwrap();
+    b.iter(|| {
+        let result = build_list.call(1_000_000).unwrap();
+        black_box(result)
+    })
+}
+
+fn build_list_buffered(b: &mut Bencher) {
+    build_list(b, GcSettings::default().allocation_buffer_size)
+}
+
+fn build_list_unbuffered(b: &mut Bencher) {
+    build_list(b, 0)
+}
+
+benchmark_group!(gc, build_list_buffered, build_list_unbuffered);
+benchmark_main!(gc);
@@ -0,0 +1,31 @@
+#[macro_use]
+extern crate bencher;
+
+extern crate gluon;
+
+use bencher::{black_box, Bencher};
+
+use gluon::{new_vm, Compiler};
+use gluon::vm::api::FunctionRef;
+
+// Benchmarks an integer loop written as a self tail call. Run with and without `--features jit`
+// to compare the interpreter with the compiled code.
+fn sum_tail_call(b: &mut Bencher) {
+    let vm = new_vm();
+    let text = r#"
+    let sum acc n : Int -> Int -> Int =
+        if n #Int== 0
+        then acc
+        else sum (acc #Int+ n) (n #Int- 1)
+    sum
+    "#;
+    Compiler::new().load_script(&vm, "sum", text).unwrap();
+    let mut sum: FunctionRef<fn(i32, i32) -> i32> = vm.get_global("sum").unwrap();
+    b.iter(|| {
+        let result = sum.call(0, 10000).unwrap();
+        black_box(result)
+    })
+}
+
+benchmark_group!(jit, sum_tail_call);
+benchmark_main!(jit);
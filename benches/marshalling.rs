@@ -0,0 +1,96 @@
+#[macro_use]
+extern crate bencher;
+
+extern crate gluon;
+
+use bencher::{black_box, Bencher};
+
+use gluon::{new_vm, Compiler};
+use gluon::vm::api::FunctionRef;
+use gluon::vm::thread::{RootStr, Thread};
+use gluon::vm::types::VmInt;
+
+fn load_strings(vm: &Thread) {
+    let strings = (0..1000)
+        .map(|i| format!("\"{}\"", i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Compiler::new()
+        .load_script(vm, "strings", &format!("[{}]", strings))
+        .unwrap();
+    Compiler::new()
+        .load_script(vm, "string", r#" "a string" "#)
+        .unwrap();
+    Compiler::new()
+        .load_script(
+            vm,
+            "string_at",
+            r#"
+                let array = import! std.array
+                let strings = import! strings
+                \i -> array.index strings i
+            "#,
+        )
+        .unwrap();
+}
+
+// Benchmarks retrieving strings by rooting each of them
+fn rooted_strings(b: &mut Bencher) {
+    let vm = new_vm();
+    load_strings(&vm);
+    b.iter(|| {
+        let len = (0..1000)
+            .map(|_| vm.get_global::<RootStr>("string").unwrap().len())
+            .sum::<usize>();
+        black_box(len)
+    })
+}
+
+// Benchmarks retrieving strings which borrow from a single root scope
+fn scoped_strings(b: &mut Bencher) {
+    let vm = new_vm();
+    load_strings(&vm);
+    b.iter(|| {
+        let scope = vm.root_scope();
+        let len = (0..1000)
+            .map(|_| scope.get_global::<&str>("string").unwrap().len())
+            .sum::<usize>();
+        black_box(len)
+    })
+}
+
+// Benchmarks calling a function whose string result is copied out of the vm
+fn call_copied_strings(b: &mut Bencher) {
+    let vm = new_vm();
+    load_strings(&vm);
+    let mut string_at: FunctionRef<fn(VmInt) -> String> = vm.get_global("string_at").unwrap();
+    b.iter(|| {
+        let len = (0..1000)
+            .map(|i| string_at.call(i).unwrap().len())
+            .sum::<usize>();
+        black_box(len)
+    })
+}
+
+// Benchmarks calling a function whose string result borrows from a single root scope
+fn call_scoped_strings(b: &mut Bencher) {
+    let vm = new_vm();
+    load_strings(&vm);
+    let mut string_at: FunctionRef<fn(VmInt) -> String> = vm.get_global("string_at").unwrap();
+    b.iter(|| {
+        let scope = vm.root_scope();
+        let len = (0..1000)
+            .map(|i| string_at.call_scoped::<&str>(&scope, i).unwrap().len())
+            .sum::<usize>();
+        black_box(len)
+    })
+}
+
+benchmark_group!(
+    marshalling,
+    rooted_strings,
+    scoped_strings,
+    call_copied_strings,
+    call_scoped_strings
+);
+benchmark_main!(marshalling);
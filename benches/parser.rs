@@ -6,6 +6,7 @@ extern crate gluon_parser as parser;
 
 use bencher::{black_box, Bencher};
 
+use base::ast::Arena;
 use base::symbol::{SymbolModule, Symbols};
 use base::types::TypeCache;
 
@@ -20,9 +21,10 @@ fn prelude(b: &mut Bencher) {
     b.iter(|| {
         let mut symbols = Symbols::new();
         let mut symbols = SymbolModule::new("".into(), &mut symbols);
-        let expr = parser::parse_expr(&mut symbols, &TypeCache::new(), &text)
+        let arena = Arena::new();
+        let expr = parser::parse_expr(&arena, &mut symbols, &TypeCache::new(), &text)
             .unwrap_or_else(|err| panic!("{:?}", err));
-        black_box(expr)
+        black_box(expr);
     })
 }
 
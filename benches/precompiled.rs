@@ -26,7 +26,7 @@ fn precompiled_prelude(b: &mut Bencher) {
     let mut serialized_prelude = Vec::new();
     {
         let env = thread.global_env().get_env();
-        let prelude = env.globals.get("std.prelude").unwrap();
+        let prelude = env.get_global("std.prelude").unwrap();
         prelude
             .serialize_state(
                 &mut bincode::Serializer::new(&mut serialized_prelude),
@@ -0,0 +1,204 @@
+//! Explanations of why an expression were inferred to have its type.
+//!
+//! When `Typecheck::record_provenance` has been called the substitution records every binding of
+//! a type variable together with the unification which made it. `explain` follows the bindings of
+//! the variables which were created while checking an expression, along with the variables in
+//! its type, to find the unifications which lead to the final type of the expression.
+use std::fmt;
+use std::ops::Range;
+
+use base::fnv::FnvSet;
+use base::pos::{BytePos, Location, Span};
+use base::source::Source;
+use base::types::{self, ArcType, Type};
+
+use messages;
+use substitution::{Origin, Substitution};
+
+/// The type which an expression were given when it were typechecked
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExprType {
+    pub span: Span<BytePos>,
+    pub typ: ArcType,
+    /// The type variables which were created while typechecking the expression
+    pub variables: Range<u32>,
+}
+
+/// A binding of a type variable which contributed to the type of an expression
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step<Pos> {
+    pub variable: ArcType,
+    pub typ: ArcType,
+    /// The unification which bound `variable`, if it is known
+    pub origin: Option<Origin<ArcType, Pos>>,
+}
+
+/// Why an expression has the type it has
+#[derive(Clone, Debug, PartialEq)]
+pub struct Explanation<Pos> {
+    /// The span of the explained expression
+    pub span: Span<Pos>,
+    /// The type which the expression were given when it were checked
+    pub inferred: ArcType,
+    /// The type of the expression once every variable in `inferred` has been replaced
+    pub typ: ArcType,
+    /// The bindings which lead from `inferred` to `typ`, in the order they were made
+    pub steps: Vec<Step<Pos>>,
+}
+
+impl Explanation<BytePos> {
+    /// Converts the byte positions of the explanation into line and column locations in `source`
+    pub fn in_source(self, source: &Source) -> Explanation<Location> {
+        let span = |span: Span<BytePos>| span.map(|pos| source.location(pos).unwrap());
+        Explanation {
+            span: span(self.span),
+            inferred: self.inferred,
+            typ: self.typ,
+            steps: self.steps
+                .into_iter()
+                .map(|step| Step {
+                    variable: step.variable,
+                    typ: step.typ,
+                    origin: step.origin.map(|origin| Origin {
+                        span: span(origin.span),
+                        expected: origin.expected,
+                        actual: origin.actual,
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<Pos> fmt::Display for Explanation<Pos>
+where
+    Pos: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}",
+            message!(
+                messages::WHY_INFERRED,
+                location = self.span.start,
+                typ = self.typ
+            )
+        )?;
+        if self.steps.is_empty() {
+            return write!(f, "{}", message!(messages::WHY_NO_STEPS));
+        }
+        write!(f, "{}", message!(messages::WHY_STEPS))?;
+        for step in &self.steps {
+            write!(f, "\n    ")?;
+            match step.origin {
+                Some(ref origin) => write!(
+                    f,
+                    "{}",
+                    message!(
+                        messages::WHY_UNIFICATION,
+                        location = origin.span.start,
+                        expected = origin.expected,
+                        actual = origin.actual,
+                        variable = step.variable,
+                        typ = step.typ
+                    )
+                )?,
+                None => write!(
+                    f,
+                    "{}",
+                    message!(
+                        messages::WHY_BINDING,
+                        variable = step.variable,
+                        typ = step.typ
+                    )
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn variable_id(typ: &ArcType) -> Option<u32> {
+    match **typ {
+        Type::Variable(ref var) => Some(var.id),
+        _ => None,
+    }
+}
+
+fn add_variables(typ: &ArcType, variables: &mut FnvSet<u32>) {
+    types::walk_type(typ, |typ: &ArcType| {
+        if let Some(id) = variable_id(typ) {
+            variables.insert(id);
+        }
+    });
+}
+
+/// Replaces every bound variable in `typ` with the type it is bound to
+fn resolve(subs: &Substitution<ArcType>, typ: &ArcType) -> ArcType {
+    types::walk_move_type(typ.clone(), &mut |typ: &ArcType| {
+        variable_id(typ)
+            .and_then(|id| subs.find_type_for_var(id))
+            .map(|typ| resolve(subs, typ))
+    })
+}
+
+/// Explains the type of the innermost expression in `expr_types` which contains `span`, using
+/// the bindings recorded in `subs`. Only expressions with the same expansion id as `span` are
+/// considered so that expressions inserted by macros (such as the implicit prelude) are skipped.
+pub fn explain(
+    subs: &Substitution<ArcType>,
+    expr_types: &[ExprType],
+    span: Span<BytePos>,
+) -> Option<Explanation<BytePos>> {
+    let expr_type = expr_types
+        .iter()
+        .filter(|expr_type| {
+            expr_type.span.expansion_id == span.expansion_id && expr_type.span.contains(span)
+        })
+        .min_by_key(|expr_type| expr_type.span.end.to_usize() - expr_type.span.start.to_usize())?;
+
+    // The type of an expression has usually been resolved already when it is recorded so the
+    // variables which were created while checking it are followed as well
+    let mut variables: FnvSet<u32> = expr_type.variables.clone().collect();
+    add_variables(&expr_type.typ, &mut variables);
+
+    let provenance = subs.provenance();
+    let mut relevant = vec![false; provenance.len()];
+    // A binding may only become relevant through a variable found in a later binding so the
+    // bindings are scanned until no more relevant bindings are found
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (relevant, binding) in relevant.iter_mut().zip(&*provenance) {
+            if *relevant {
+                continue;
+            }
+            let variable = variable_id(&binding.variable);
+            let bound_to_variable = variable_id(&binding.typ);
+            if variable.map_or(false, |id| variables.contains(&id))
+                || bound_to_variable.map_or(false, |id| variables.contains(&id))
+            {
+                *relevant = true;
+                changed = true;
+                variables.extend(variable);
+                add_variables(&binding.typ, &mut variables);
+            }
+        }
+    }
+
+    Some(Explanation {
+        span: expr_type.span,
+        inferred: expr_type.typ.clone(),
+        typ: resolve(subs, &expr_type.typ),
+        steps: provenance
+            .iter()
+            .zip(&relevant)
+            .filter(|&(_, &relevant)| relevant)
+            .map(|(binding, _)| Step {
+                variable: binding.variable.clone(),
+                typ: binding.typ.clone(),
+                origin: binding.origin.clone(),
+            })
+            .collect(),
+    })
+}
@@ -0,0 +1,74 @@
+//! Discovery of the instances of class-like type aliases.
+//!
+//! A type alias such as `type Functor f = { map : ... }` is used like a type class, with values
+//! of `Functor Option` etc. acting as its instances. `find_instances` searches the fields of
+//! modules and other records for the values whose type is equivalent to such an alias applied to
+//! some arguments.
+use base::symbol::Symbol;
+use base::types::{Alias, ArcType, Type, TypeEnv};
+
+use rename::equivalent;
+
+/// A value whose type is an instance of a type alias
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instance {
+    /// The path to the value (`std.option.functor`)
+    pub name: String,
+    pub typ: ArcType,
+}
+
+/// Returns `alias` applied to a generic for each of its parameters (`Functor f`)
+fn applied_alias(alias: &Alias<Symbol, ArcType>) -> ArcType {
+    Type::app(
+        alias.as_type().clone(),
+        alias.params().iter().cloned().map(Type::generic).collect(),
+    )
+}
+
+fn find_instances_in(
+    env: &TypeEnv,
+    pattern: &ArcType,
+    name: String,
+    typ: &ArcType,
+    instances: &mut Vec<Instance>,
+) {
+    let typ = typ.remove_forall();
+    if equivalent(env, pattern, typ) {
+        instances.push(Instance {
+            name,
+            typ: typ.clone(),
+        });
+        return;
+    }
+    // Only records which are written out are searched, the fields of an instance (such as the
+    // `functor` field of `Applicative Option`) are not instances in their own right
+    if let Type::Record(_) = **typ {
+        for field in typ.row_iter() {
+            let field_name = format!("{}.{}", name, field.name.declared_name());
+            find_instances_in(env, pattern, field_name, &field.typ, instances);
+        }
+    }
+}
+
+/// Searches `bindings`, and the fields of the records among them, for the values whose type is
+/// `alias` applied to some arguments. Each binding is given as its name and type, the instances
+/// are returned sorted by their path.
+///
+/// For the alias `Functor` and the binding `std.option` this finds `std.option.functor` which
+/// has the type `Functor Option`.
+pub fn find_instances<'a, I>(
+    env: &TypeEnv,
+    alias: &Alias<Symbol, ArcType>,
+    bindings: I,
+) -> Vec<Instance>
+where
+    I: IntoIterator<Item = (String, &'a ArcType)>,
+{
+    let pattern = applied_alias(alias);
+    let mut instances = Vec::new();
+    for (name, typ) in bindings {
+        find_instances_in(env, &pattern, name, typ, &mut instances);
+    }
+    instances.sort_by(|l, r| l.name.cmp(&r.name));
+    instances
+}
@@ -2,6 +2,7 @@ use std::fmt;
 use std::result::Result as StdResult;
 
 use base::ast::{self, AstType};
+use base::error::{Label, Labels};
 use base::fnv::FnvMap;
 use base::kind::{self, ArcKind, Kind, KindCache, KindEnv};
 use base::merge;
@@ -9,6 +10,7 @@ use base::symbol::Symbol;
 use base::types::{self, BuiltinType, Generic, Type, Walker};
 use base::pos::{self, BytePos, HasSpan, Span, Spanned};
 
+use messages;
 use substitution::{Constraints, Substitutable, Substitution};
 use unify::{self, Error as UnifyError, Unifiable, Unifier, UnifierState};
 
@@ -20,6 +22,10 @@ pub type Result<T> = StdResult<T, SpannedError<Symbol>>;
 /// Struct containing methods for kindchecking types
 pub struct KindCheck<'a> {
     variables: Vec<Generic<Symbol>>,
+    /// The variables in `variables` which were given an explicit kind, `(a : Type -> Type)`
+    declared_variables: Vec<Symbol>,
+    /// The location of the binding which declared `variables`
+    declared_at: Option<Span<BytePos>>,
     /// Type bindings local to the current kindcheck invocation
     locals: Vec<(Symbol, ArcKind)>,
     info: &'a (KindEnv + 'a),
@@ -30,6 +36,14 @@ pub struct KindCheck<'a> {
     function1_kind: ArcKind,
     /// A cached two argument kind function, `Type -> Type -> Type`
     function2_kind: ArcKind,
+    /// How deeply nested the type which is currently being kindchecked is
+    depth: usize,
+    /// The variables which the named kinds (`k` in `(a : k)`) of the type which is currently
+    /// being instantiated were replaced with
+    kind_variables: FnvMap<String, ArcKind>,
+    /// All named kinds which have been instantiated, used to keep their names when generalizing
+    named_kinds: Vec<(String, ArcKind)>,
+    max_depth: usize,
 }
 
 fn walk_move_kind<F>(kind: ArcKind, f: &mut F) -> ArcKind
@@ -54,7 +68,12 @@ where
                 let ret_new = walk_move_kind2(ret, f);
                 merge::merge(arg, arg_new, ret, ret_new, Kind::function)
             }
-            Kind::Hole | Kind::Type | Kind::Variable(_) | Kind::Row => None,
+            Kind::Hole
+            | Kind::Type
+            | Kind::Variable(_)
+            | Kind::Row
+            | Kind::Nat
+            | Kind::Generic(_) => None,
         }
     };
     new2.or(new)
@@ -70,6 +89,8 @@ impl<'a> KindCheck<'a> {
         let function1_kind = Kind::function(typ.clone(), typ.clone());
         KindCheck {
             variables: Vec::new(),
+            declared_variables: Vec::new(),
+            declared_at: None,
             locals: Vec::new(),
             info: info,
             idents: idents,
@@ -77,16 +98,46 @@ impl<'a> KindCheck<'a> {
             function1_kind: function1_kind.clone(),
             function2_kind: Kind::function(typ, function1_kind),
             kind_cache: kind_cache,
+            depth: 0,
+            max_depth: ::DEFAULT_MAX_DEPTH,
+            kind_variables: FnvMap::default(),
+            named_kinds: Vec::new(),
         }
     }
 
+    /// Sets how deeply types may be nested before `KindError::TypeTooDeeplyNested` is returned
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
     pub fn add_local(&mut self, name: Symbol, kind: ArcKind) {
         self.locals.push((name, kind));
     }
 
-    pub fn set_variables(&mut self, variables: &[Generic<Symbol>]) {
+    /// Returns the names of the type bindings local to this kindcheck, used to suggest
+    /// alternatives to undefined types
+    pub fn local_names(&self) -> Vec<&str> {
+        self.locals
+            .iter()
+            .map(|&(ref id, _)| id.declared_name())
+            .collect()
+    }
+
+    /// Sets the type variables which are in scope while kindchecking. Variables which have a
+    /// kind without any holes or kind variables in it (`(f : Type -> Type)`) are treated as being
+    /// declared with that kind and any mismatch reports the declared kind. `declared_at` is the
+    /// location of the type binding which declares the variables.
+    pub fn set_variables(&mut self, variables: &[Generic<Symbol>], declared_at: Span<BytePos>) {
+        self.declared_at = Some(declared_at);
         self.variables.clear();
         self.variables.extend(variables.iter().cloned());
+        self.declared_variables.clear();
+        self.declared_variables.extend(
+            variables
+                .iter()
+                .filter(|var| is_declared_kind(&var.kind))
+                .map(|var| var.id.clone()),
+        );
     }
 
     pub fn type_kind(&self) -> ArcKind {
@@ -105,20 +156,56 @@ impl<'a> KindCheck<'a> {
         self.kind_cache.row()
     }
 
+    pub fn nat_kind(&self) -> ArcKind {
+        self.kind_cache.nat()
+    }
+
+    /// Replaces the holes and named kinds in `kind` with new kind variables
     pub fn instantiate_kinds(&mut self, kind: &mut ArcKind) {
-        match *ArcKind::make_mut(kind) {
+        self.kind_variables.clear();
+        self.instantiate_kind(kind);
+    }
+
+    /// Instantiates the kinds of the parameters of a type binding, returning the kind of the
+    /// type itself. Parameters with the same named kind are given the same kind variable.
+    pub fn instantiate_params(&mut self, params: &mut [Generic<Symbol>]) -> ArcKind {
+        self.kind_variables.clear();
+        let mut kind = self.type_kind();
+        for param in params.iter_mut() {
+            self.instantiate_kind(&mut param.kind);
+        }
+        for param in params.iter().rev() {
+            kind = Kind::function(param.kind.clone(), kind);
+        }
+        kind
+    }
+
+    fn instantiate_kind(&mut self, kind: &mut ArcKind) {
+        let name = match *ArcKind::make_mut(kind) {
             // Can't assign a new var to `kind` here because it is borrowed mutably...
             // We'll rely on fall-through instead.
-            Kind::Hole => (),
+            Kind::Hole => None,
+            Kind::Generic(ref name) => Some(name.clone()),
             Kind::Variable(_) => ice!("Unexpected kind variable while instantiating"),
             Kind::Function(ref mut lhs, ref mut rhs) => {
-                self.instantiate_kinds(lhs);
-                self.instantiate_kinds(rhs);
+                self.instantiate_kind(lhs);
+                self.instantiate_kind(rhs);
                 return;
             }
-            Kind::Row | Kind::Type => return,
-        }
-        *kind = self.subs.new_var();
+            Kind::Row | Kind::Type | Kind::Nat => return,
+        };
+        *kind = match name {
+            Some(name) => match self.kind_variables.get(&name).cloned() {
+                Some(var) => var,
+                None => {
+                    let var = self.subs.new_var();
+                    self.kind_variables.insert(name.clone(), var.clone());
+                    self.named_kinds.push((name, var.clone()));
+                    var
+                }
+            },
+            None => self.subs.new_var(),
+        };
     }
 
     fn find(&mut self, span: Span<BytePos>, id: &Symbol) -> Result<ArcKind> {
@@ -127,7 +214,14 @@ impl<'a> KindCheck<'a> {
             .find(|var| var.id == *id)
             .map(|t| t.kind.clone())
             .or_else(|| self.locals.iter().find(|t| t.0 == *id).map(|t| t.1.clone()))
-            .or_else(|| self.info.find_kind(id))
+            .or_else(|| {
+                // Holes in the kinds of already defined types are generalized kinds so they
+                // are instantiated with fresh variables to accept any kind
+                self.info.find_kind(id).map(|mut kind| {
+                    self.instantiate_kinds(&mut kind);
+                    kind
+                })
+            })
             .map_or_else(
                 || {
                     let id_str = self.idents.string(id);
@@ -161,7 +255,7 @@ impl<'a> KindCheck<'a> {
         expected: &ArcKind,
     ) -> Result<ArcKind> {
         let kind = self.kindcheck(typ)?;
-        let kind = self.unify(typ.span(), expected, kind)?;
+        let kind = self.unify_type(typ, expected, kind)?;
         self.finalize_type(typ);
         Ok(kind)
     }
@@ -174,11 +268,28 @@ impl<'a> KindCheck<'a> {
             | BuiltinType::Int
             | BuiltinType::Float => self.type_kind(),
             BuiltinType::Array => self.function1_kind(),
-            BuiltinType::Function => self.function2_kind(),
+            BuiltinType::Function | BuiltinType::ImplicitFunction => self.function2_kind(),
+            BuiltinType::NatAdd => {
+                let nat = self.nat_kind();
+                Kind::function(nat.clone(), Kind::function(nat.clone(), nat))
+            }
         }
     }
 
     fn kindcheck(&mut self, typ: &mut AstType<Symbol>) -> Result<ArcKind> {
+        if self.depth >= self.max_depth {
+            return Err(pos::spanned(
+                typ.span(),
+                UnifyError::Other(KindError::TypeTooDeeplyNested),
+            ));
+        }
+        self.depth += 1;
+        let result = self.kindcheck_(typ);
+        self.depth -= 1;
+        result
+    }
+
+    fn kindcheck_(&mut self, typ: &mut AstType<Symbol>) -> Result<ArcKind> {
         let span = typ.span();
         match **typ {
             Type::Hole | Type::Opaque | Type::Variable(_) => Ok(self.subs.new_var()),
@@ -191,6 +302,7 @@ impl<'a> KindCheck<'a> {
                 Ok(gen.kind.clone())
             }
             Type::Builtin(builtin_typ) => Ok(self.builtin_kind(builtin_typ)),
+            Type::Nat(_) => Ok(self.nat_kind()),
             Type::Forall(ref mut params, ref mut typ, _) => {
                 for param in &mut *params {
                     param.kind = self.subs.new_var();
@@ -207,11 +319,12 @@ impl<'a> KindCheck<'a> {
                 let mut kind = self.kindcheck(ctor)?;
                 for arg in args {
                     let f = Kind::function(self.subs.new_var(), self.subs.new_var());
-                    kind = self.unify(arg.span(), &f, kind)?;
+                    kind = self.unify(arg.span(), &f, kind)
+                        .map_err(|err| self.declared_kind_error(ctor, err))?;
                     kind = match *kind {
                         Kind::Function(ref arg_kind, ref ret) => {
                             let actual = self.kindcheck(arg)?;
-                            self.unify(arg.span(), arg_kind, actual)?;
+                            self.unify_type(arg, arg_kind, actual)?;
                             ret.clone()
                         }
                         _ => {
@@ -225,12 +338,24 @@ impl<'a> KindCheck<'a> {
                 Ok(kind)
             }
             Type::Variant(ref mut row) => {
-                for field in types::row_iter_mut(row) {
+                let mut iter = types::row_iter_mut(row);
+                for field in iter.by_ref() {
                     let kind = self.kindcheck(&mut field.typ)?;
                     let type_kind = self.type_kind();
                     self.unify(field.typ.span(), &type_kind, kind)?;
                 }
 
+                // The rest of an open variant, `[| A | r |]`, must be a row
+                let rest = iter.current_type();
+                match **rest {
+                    Type::EmptyRow => (),
+                    _ => {
+                        let kind = self.kindcheck(rest)?;
+                        let row_kind = self.row_kind();
+                        self.unify(rest.span(), &row_kind, kind)?;
+                    }
+                }
+
                 Ok(self.type_kind())
             }
             Type::Record(ref mut row) => {
@@ -284,6 +409,43 @@ impl<'a> KindCheck<'a> {
         }
     }
 
+    /// Unifies the kind of `typ` with `expected`, reporting the declared kind if `typ` is a type
+    /// variable with an explicit kind
+    fn unify_type(
+        &mut self,
+        typ: &AstType<Symbol>,
+        expected: &ArcKind,
+        actual: ArcKind,
+    ) -> Result<ArcKind> {
+        self.unify(typ.span(), expected, actual)
+            .map_err(|err| self.declared_kind_error(typ, err))
+    }
+
+    fn declared_kind_error(
+        &self,
+        typ: &AstType<Symbol>,
+        err: SpannedError<Symbol>,
+    ) -> SpannedError<Symbol> {
+        let declared_name = match **typ {
+            Type::Generic(ref gen) if self.declared_variables.contains(&gen.id) => {
+                Some(gen.id.clone())
+            }
+            _ => None,
+        };
+        match (declared_name, err.value) {
+            (Some(name), UnifyError::TypeMismatch(expected, declared)) => pos::spanned(
+                err.span,
+                UnifyError::Other(KindError::DeclaredKindMismatch {
+                    name,
+                    declared,
+                    expected,
+                    declared_at: self.declared_at,
+                }),
+            ),
+            (_, value) => pos::spanned(err.span, value),
+        }
+    }
+
     pub fn finalize_type(&self, typ: &mut AstType<Symbol>) {
         let default = Some(&self.kind_cache.typ);
         types::walk_type_mut(typ, &mut |typ: &mut AstType<Symbol>| match **typ {
@@ -302,6 +464,68 @@ impl<'a> KindCheck<'a> {
         kind = update_kind(&self.subs, kind, Some(&self.kind_cache.typ));
         Generic::new(var.id.clone(), kind)
     }
+
+    /// Like `finalize_generic` for all parameters of a type binding, except that any kind
+    /// variables which could not be inferred are generalized instead of defaulting to `Type`.
+    /// A variable which occurs in the kind of a single parameter becomes a hole while variables
+    /// shared between parameters become named kinds, reusing the name of the named kind which
+    /// created it if there is one. Each use of the type instantiates these kinds with new
+    /// variables (see `find`) which lets, for instance, `type Proxy a = | Proxy` be applied to
+    /// types of any kind.
+    pub fn generalize_params(&self, params: &mut [Generic<Symbol>]) {
+        let mut occurrences = FnvMap::default();
+        for param in params.iter_mut() {
+            param.kind = update_kind(&self.subs, param.kind.clone(), None);
+            walk_move_kind(param.kind.clone(), &mut |kind| {
+                if let Kind::Variable(id) = *kind {
+                    *occurrences.entry(id).or_insert(0) += 1;
+                }
+                None
+            });
+        }
+
+        let mut names = FnvMap::default();
+        for &(ref name, ref var) in &self.named_kinds {
+            if let Kind::Variable(id) = *update_kind(&self.subs, var.clone(), None) {
+                if occurrences.contains_key(&id) && !names.values().any(|n| n == name) {
+                    names.entry(id).or_insert_with(|| name.clone());
+                }
+            }
+        }
+        let mut shared: Vec<_> = occurrences
+            .iter()
+            .filter(|&(id, &count)| count > 1 && !names.contains_key(id))
+            .map(|(&id, _)| id)
+            .collect();
+        shared.sort();
+        for id in shared {
+            let name = (0..)
+                .map(|i| if i == 0 { "k".to_string() } else { format!("k{}", i) })
+                .find(|name| !names.values().any(|n| n == name))
+                .unwrap();
+            names.insert(id, name);
+        }
+
+        for param in params {
+            param.kind = walk_move_kind(param.kind.clone(), &mut |kind| match *kind {
+                Kind::Variable(id) => Some(
+                    names
+                        .get(&id)
+                        .map_or_else(Kind::hole, |name| Kind::generic(name)),
+                ),
+                _ => None,
+            });
+        }
+    }
+}
+
+/// Returns true if `kind` is fully specified, that is, it does not contain any holes or variables
+fn is_declared_kind(kind: &ArcKind) -> bool {
+    match **kind {
+        Kind::Hole | Kind::Variable(_) | Kind::Generic(_) => false,
+        Kind::Function(ref arg, ref ret) => is_declared_kind(arg) && is_declared_kind(ret),
+        Kind::Type | Kind::Row | Kind::Nat => true,
+    }
 }
 
 fn update_kind(subs: &Substitution<ArcKind>, kind: ArcKind, default: Option<&ArcKind>) -> ArcKind {
@@ -318,6 +542,16 @@ fn update_kind(subs: &Substitution<ArcKind>, kind: ArcKind, default: Option<&Arc
 pub enum KindError<I> {
     /// The type is not defined in the current scope
     UndefinedType(I),
+    /// A type variable with an explicit kind is used with a different kind
+    DeclaredKindMismatch {
+        name: I,
+        declared: ArcKind,
+        expected: ArcKind,
+        /// The location of the binding which declares the variable, if it is known
+        declared_at: Option<Span<BytePos>>,
+    },
+    /// The type is nested deeper than the limit set with `KindCheck::set_max_depth`
+    TypeTooDeeplyNested,
 }
 
 impl<I> fmt::Display for KindError<I>
@@ -326,7 +560,48 @@ where
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            KindError::UndefinedType(ref name) => write!(f, "Type '{}' is not defined", name),
+            KindError::UndefinedType(ref name) => {
+                write!(f, "{}", message!(messages::KIND_UNDEFINED_TYPE, name = name))
+            }
+            KindError::DeclaredKindMismatch {
+                ref name,
+                ref declared,
+                ref expected,
+                ..
+            } => write!(
+                f,
+                "{}",
+                message!(
+                    messages::DECLARED_KIND_MISMATCH,
+                    name = name,
+                    declared = declared,
+                    expected = expected
+                )
+            ),
+            KindError::TypeTooDeeplyNested => {
+                write!(f, "{}", message!(messages::TYPE_TOO_DEEPLY_NESTED))
+            }
+        }
+    }
+}
+
+impl<I> Labels for KindError<I>
+where
+    I: fmt::Display,
+{
+    fn labels(&self) -> Vec<Label> {
+        match *self {
+            KindError::DeclaredKindMismatch {
+                ref name,
+                ref declared,
+                declared_at: Some(span),
+                ..
+            } => vec![Label::new(
+                span,
+                message!(messages::DECLARED_KIND_HERE, name = name, declared = declared)
+                    .to_string(),
+            )],
+            _ => Vec::new(),
         }
     }
 }
@@ -339,8 +614,12 @@ where
     match *error {
         TypeMismatch(ref expected, ref actual) => write!(
             f,
-            "Kind mismatch\nExpected: {}\nFound: {}",
-            expected, actual
+            "{}",
+            message!(
+                messages::KIND_MISMATCH,
+                expected = expected,
+                found = actual
+            )
         ),
         Substitution(ref err) => write!(f, "{}", err),
         Other(ref err) => write!(f, "{}", err),
@@ -1,5 +1,6 @@
 //! The `check` crate is responsible for ensuring that an AST expression is actually a valid
-//! program. This currently consits of three larger parts, typechecking, kindchecking and renaming.
+//! program. This currently consits of four larger parts, typechecking, kindchecking, renaming and
+//! checking the patterns of `match` expressions.
 //! If an AST passes the checks in `Typecheck::typecheck_expr` (which runs all of theses checks
 //! the expression is expected to compile succesfully (if it does not it should be considered an
 //! internal compiler error.
@@ -21,31 +22,80 @@ pub mod typecheck;
 pub mod unify_type;
 pub mod unify;
 pub mod kindcheck;
+pub mod messages;
 pub mod substitution;
 pub mod rename;
+pub mod pattern_match;
 pub mod metadata;
+pub mod warning;
+pub mod unused;
+pub mod explain;
+pub mod instances;
 
+use base::error::Errors;
+use base::symbol::Symbol;
 use base::types::{ArcType, TypeEnv};
 
+/// The default for how deeply types and expressions may be nested before the typechecker reports
+/// an error instead of risking an overflow of the native stack
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
 /// Checks if `actual` can be assigned to a binding with the type signature `signature`
 pub fn check_signature(env: &TypeEnv, signature: &ArcType, actual: &ArcType) -> bool {
     use base::kind::Kind;
-    use base::scoped_map::ScopedMap;
-    use base::fnv::FnvMap;
 
     use substitution::Substitution;
 
     let subs = Substitution::new(Kind::typ());
-    let state = unify_type::State::new(env, &subs);
-    let actual = unify_type::new_skolem_scope(&subs, &FnvMap::default(), actual);
-    let actual = actual.instantiate_generics(&mut FnvMap::default());
-    let result = unify_type::subsumes(&subs, &mut ScopedMap::new(), 0, state, signature, &actual);
+    let result = subsume_signature(env, &subs, signature, actual);
     if let Err(ref err) = result {
         debug!("Check signature error: {}", err);
     }
     result.is_ok()
 }
 
+/// Checks that the record `actual` has every field of the record `signature` and that the type of
+/// each field can be assigned to the type of the same field in `signature`. Unlike
+/// `check_signature`, `actual` may have fields which do not exist in `signature` so this can be
+/// used to check that a module implements an interface. Returns every mismatch that was found.
+pub fn check_record_signature(
+    env: &TypeEnv,
+    signature: &ArcType,
+    actual: &ArcType,
+) -> Result<(), Errors<unify_type::Error<Symbol>>> {
+    use base::kind::Kind;
+    use base::types::Type;
+
+    use substitution::Substitution;
+
+    let subs = Substitution::new(Kind::typ());
+    // The fields of `actual` which are not in `signature` are unified with the rest of the row
+    let signature = match **signature {
+        Type::Record(_) => Type::poly_record(
+            signature.type_field_iter().cloned().collect(),
+            signature.row_iter().cloned().collect(),
+            subs.new_var(),
+        ),
+        _ => signature.clone(),
+    };
+    subsume_signature(env, &subs, &signature, actual)
+}
+
+fn subsume_signature(
+    env: &TypeEnv,
+    subs: &substitution::Substitution<ArcType>,
+    signature: &ArcType,
+    actual: &ArcType,
+) -> Result<(), Errors<unify_type::Error<Symbol>>> {
+    use base::scoped_map::ScopedMap;
+    use base::fnv::FnvMap;
+
+    let state = unify_type::State::new(env, subs);
+    let actual = unify_type::new_skolem_scope(subs, &FnvMap::default(), actual);
+    let actual = actual.instantiate_generics(&mut FnvMap::default());
+    unify_type::subsumes(subs, &mut ScopedMap::new(), 0, state, signature, &actual).map(|_| ())
+}
+
 #[cfg(test)]
 mod tests {
     use std::cell::RefCell;
@@ -0,0 +1,112 @@
+//! The messages of the diagnostics reported by the checks in this crate. See `base::message` for
+//! how they can be translated.
+
+declare_messages! {
+    UNDEFINED_VARIABLE = "typecheck.undefined_variable": "Undefined variable `{name}`",
+    NOT_A_FUNCTION = "typecheck.not_a_function": "`{typ}` is not a function",
+    UNDEFINED_TYPE = "typecheck.undefined_type": "Type `{name}` is not defined",
+    UNDEFINED_FIELD = "typecheck.undefined_field":
+        "Type `{typ}` does not have the field `{field}`",
+    UNIFICATION = "typecheck.unification": "Expected the following types to be equal",
+    UNIFICATION_ERRORS = "typecheck.unification_errors":
+        "{count} errors were found during unification:",
+    PATTERN_ERROR = "typecheck.pattern_error": "Type {typ} has {count} to few arguments",
+    DUPLICATE_TYPE_DEFINITION = "typecheck.duplicate_type_definition":
+        "Type '{name}' has been already been defined in this module",
+    DUPLICATE_FIELD = "typecheck.duplicate_field":
+        "The record has more than one field named '{field}'",
+    INVALID_PROJECTION = "typecheck.invalid_projection":
+        "Type '{typ}' is not a type which allows field accesses",
+    RECORD_FIELD_MISMATCH = "typecheck.record_field_mismatch":
+        "The record does not have the same fields as `{alias}`",
+    MISSING_FIELDS = "typecheck.missing_fields": "Missing fields:",
+    UNEXPECTED_FIELDS = "typecheck.unexpected_fields": "Unexpected fields:",
+    UNDEFINED_RECORD = "typecheck.undefined_record":
+        "No type found with the following fields: {fields}",
+    EMPTY_CASE = "typecheck.empty_case": "`case` expression with no alternatives",
+    EXPR_TOO_DEEPLY_NESTED = "typecheck.expr_too_deeply_nested":
+        "Expression is too deeply nested",
+    INVALID_BUILTIN_OPERATOR = "typecheck.invalid_builtin_operator":
+        "Invalid builtin type for operator",
+    UNEXPECTED_TYPE_CONSTRUCTOR = "typecheck.unexpected_type_constructor":
+        "Unexpected type constructor `{name}`",
+    ALIAS_DEFINED_HERE = "typecheck.alias_defined_here": "`{alias}` is defined here",
+    PREVIOUS_DEFINITION = "typecheck.previous_definition": "previous definition is here",
+    FIELD_FIRST_DEFINED = "typecheck.field_first_defined": "field first defined here",
+    UNDEFINED_FLAT_MAP_IN_DO = "typecheck.help.undefined_flat_map_in_do":
+        "Try bringing the `flat_map` function found in the `Monad` instance for your type into \
+         scope",
+    DID_YOU_MEAN = "typecheck.help.did_you_mean": "did you mean `{name}`?",
+    INSTANTIATED_HERE = "typecheck.instantiated_here":
+        "`{generic}` was instantiated to `{typ}` here",
+
+    TYPE_MISMATCH = "unify.type_mismatch": "Types do not match:",
+    EXPECTED = "unify.expected": "Expected:",
+    FOUND = "unify.found": "Found:",
+    OCCURS = "unify.occurs": "Variable `{var}` occurs in `{typ}`.",
+    CONSTRAINT = "unify.constraint":
+        "Type `{typ}` could not fullfill a constraint.\nPossible resolves:",
+    FIELD_MISMATCH = "unify.field_mismatch":
+        "Field names in record do not match.\n\tExpected: {expected}\n\tFound: {found}",
+    TYPE_DOES_NOT_EXIST = "unify.undefined_type": "Type `{name}` does not exist.",
+    SELF_RECURSIVE = "unify.self_recursive":
+        "The use of self recursion in type `{name}` could not be unified.",
+    UNABLE_TO_GENERALIZE = "unify.unable_to_generalize":
+        "Could not generalize the variable bound to `{name}` as the variable was used outside \
+         its scope",
+    MISSING_TYPE_FIELDS = "unify.missing_fields":
+        "The type `{typ}` lacks the following fields: {fields}",
+    AND = "unify.and": " and ",
+
+    KIND_MISMATCH = "kindcheck.kind_mismatch":
+        "Kind mismatch\nExpected: {expected}\nFound: {found}",
+    KIND_UNDEFINED_TYPE = "kindcheck.undefined_type": "Type '{name}' is not defined",
+    DECLARED_KIND_MISMATCH = "kindcheck.declared_kind_mismatch":
+        "Kind mismatch for `{name}` which is declared with kind `{declared}`\nExpected: {expected}",
+    TYPE_TOO_DEEPLY_NESTED = "kindcheck.type_too_deeply_nested": "Type is too deeply nested",
+    DECLARED_KIND_HERE = "kindcheck.declared_kind_here":
+        "`{name}` is declared with kind `{declared}` in this type binding",
+
+    NO_MATCHING_TYPE = "rename.no_matching_type":
+        "Could not resolve a binding for `{symbol}` with type `{expected}`",
+    POSSIBILITIES = "rename.possibilities": "Possibilities:",
+    GLOBAL_POSSIBILITY = "rename.global_possibility": "{typ} at 'global'",
+    AMBIGUOUS_IMPLICIT = "rename.ambiguous_implicit":
+        "Multiple implicit bindings of type `{expected}` could be passed to `{symbol}`",
+    MISSING_IMPLICIT = "rename.missing_implicit":
+        "No implicit binding of type `{expected}` could be found for `{symbol}`",
+    IMPLICIT_RECURSION_LIMIT = "rename.implicit_recursion_limit":
+        "Reached the recursion limit while resolving an implicit binding of type `{expected}` \
+         for `{symbol}`",
+    BINDING_INTRODUCED_HERE = "rename.binding_introduced_here":
+        "binding of type `{typ}` introduced here",
+    IMPLICIT_DEFINED_HERE = "rename.implicit_defined_here":
+        "implicit binding of type `{typ}` is defined here",
+    RECURSIVE_VALUE = "rename.recursive_value":
+        "The value of `{name}` depends on itself: {path}\nOnly functions (bindings with \
+         arguments) may refer to themselves",
+    VALUE_DEFINED_HERE = "rename.value_defined_here": "`{name}` is defined here",
+
+    NON_EXHAUSTIVE = "pattern_match.non_exhaustive":
+        "Non-exhaustive `match` expression. The following patterns are not matched:",
+    UNREACHABLE_ALTERNATIVE = "pattern_match.unreachable_alternative":
+        "Unreachable alternative. All values it matches are matched by earlier alternatives",
+
+    UNUSED_BINDING = "unused.binding": "Unused binding `{name}`",
+    UNUSED_ARGUMENT = "unused.argument": "Unused argument `{name}`",
+    UNUSED_FIELD = "unused.field": "Unused record field `{name}`",
+
+    DEPRECATED = "metadata.deprecated": "`{name}` is deprecated",
+    DEPRECATED_WITH_MESSAGE = "metadata.deprecated_with_message":
+        "`{name}` is deprecated: {message}",
+
+    SHADOWED = "warning.shadowed": "`{name}` shadows an earlier binding with the same type",
+    FIRST_BOUND_HERE = "warning.first_bound_here": "`{name}` is first bound here",
+
+    WHY_INFERRED = "why.inferred": "The expression at {location} has the type `{typ}`",
+    WHY_NO_STEPS = "why.no_steps": "No unification changed the type of the expression",
+    WHY_STEPS = "why.steps": "It was inferred from the following unifications:",
+    WHY_UNIFICATION = "why.unification":
+        "{location}: unifying `{expected}` with `{actual}` bound `{variable}` to `{typ}`",
+    WHY_BINDING = "why.binding": "`{variable}` was bound to `{typ}`",
+}
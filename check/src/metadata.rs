@@ -1,217 +1,366 @@
 use std::collections::BTreeMap;
+use std::fmt;
 
-use base::ast::{self, Expr, Pattern, SpannedExpr, SpannedPattern, ValueBinding};
+use base::ast::{self, AstType, Expr, Pattern, SpannedExpr, SpannedPattern, TypeBinding,
+                ValueBinding};
 use base::ast::Visitor;
+use base::error::{Errors, Labels};
 use base::fnv::FnvMap;
 use base::metadata::{Metadata, MetadataEnv};
+use base::pos::{self, BytePos, HasSpan, Span, Spanned};
 use base::symbol::{Name, Symbol};
+use base::types::{walk_type, Type};
+
+use messages;
+
+/// Warning emitted when a binding marked with `#[deprecated]` is used
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deprecated {
+    pub name: String,
+    /// The argument given to the attribute (`#[deprecated("use foo instead")]`)
+    pub message: Option<String>,
+    /// The name given to a `#[replaced_by("foo")]` attribute on the deprecated binding, which
+    /// `gluon fix` renames each use to
+    pub replacement: Option<String>,
+}
+
+impl fmt::Display for Deprecated {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.message {
+            Some(ref message) => write!(
+                f,
+                "{}",
+                message!(
+                    messages::DEPRECATED_WITH_MESSAGE,
+                    name = self.name,
+                    message = message
+                )
+            ),
+            None => write!(f, "{}", message!(messages::DEPRECATED, name = self.name)),
+        }
+    }
+}
+
+impl Labels for Deprecated {}
+
+/// Returns the metadata declared directly on `bind` (its documentation comment and attributes)
+fn binding_metadata(bind: &ValueBinding<Symbol>) -> Option<Metadata> {
+    if bind.comment.is_none() && bind.attributes.is_empty() {
+        return None;
+    }
+    Some(Metadata {
+        comment: bind.comment.as_ref().map(|comment| comment.content.clone()),
+        attributes: bind.attributes
+            .iter()
+            .map(|attr| (attr.name.clone(), attr.arguments.clone()))
+            .collect(),
+        module: BTreeMap::new(),
+    })
+}
+
+/// Returns the metadata a binding receives from the expression it is bound to. A binding which
+/// merely refers to a deprecated binding is not deprecated itself.
+fn inherited(mut metadata: Metadata) -> Metadata {
+    metadata.attributes.remove("deprecated");
+    metadata
+}
+
+/// Returns the metadata declared directly on the type binding `bind`
+fn type_binding_metadata(bind: &TypeBinding<Symbol>) -> Option<Metadata> {
+    if bind.comment.is_none() && bind.attributes.is_empty() {
+        return None;
+    }
+    Some(Metadata {
+        comment: bind.comment.as_ref().map(|comment| comment.content.clone()),
+        attributes: bind.attributes
+            .iter()
+            .map(|attr| (attr.name.clone(), attr.arguments.clone()))
+            .collect(),
+        module: BTreeMap::new(),
+    })
+}
 
 struct Environment<'b> {
     env: &'b MetadataEnv,
     stack: FnvMap<Symbol, Metadata>,
 }
 
-/// Queries `expr` for the metadata which it contains.
-pub fn metadata(
-    env: &MetadataEnv,
-    expr: &SpannedExpr<Symbol>,
-) -> (Metadata, FnvMap<Symbol, Metadata>) {
-    struct MetadataVisitor<'b> {
-        env: Environment<'b>,
+struct MetadataVisitor<'b> {
+    env: Environment<'b>,
+    deprecations: Vec<Spanned<Deprecated, BytePos>>,
+}
+
+impl<'b> MetadataVisitor<'b> {
+    fn new(env: &'b MetadataEnv) -> MetadataVisitor<'b> {
+        MetadataVisitor {
+            env: Environment {
+                env: env,
+                stack: FnvMap::default(),
+            },
+            deprecations: Vec::new(),
+        }
     }
 
-    impl<'b> MetadataVisitor<'b> {
-        fn new_binding(&mut self, metadata: Metadata, bind: &ValueBinding<Symbol>) {
-            match bind.name.value {
-                Pattern::As(ref id, _) => {
-                    let metadata = bind.comment.as_ref().map_or(metadata, |comment| Metadata {
-                        comment: Some(comment.content.clone()),
-                        module: BTreeMap::new(),
-                    });
-                    self.stack_var(id.clone(), metadata.clone());
-                    self.new_pattern(metadata, &bind.name);
-                }
-                Pattern::Ident(ref id) => {
-                    let metadata = bind.comment.as_ref().map_or(metadata, |comment| Metadata {
-                        comment: Some(comment.content.clone()),
-                        module: BTreeMap::new(),
-                    });
-                    self.stack_var(id.name.clone(), metadata);
-                }
-                Pattern::Constructor(..)
-                | Pattern::Tuple { .. }
-                | Pattern::Record { .. }
-                | Pattern::Literal(_)
-                | Pattern::Error => self.new_pattern(metadata, &bind.name),
+    fn check_deprecated(&mut self, span: Span<BytePos>, name: &str, metadata: &Metadata) {
+        if let Some(message) = metadata.attributes.get("deprecated") {
+            let argument = |argument: &Option<String>| {
+                argument
+                    .as_ref()
+                    .map(|argument| argument.trim_matches('"').to_string())
+            };
+            let replacement = metadata
+                .attributes
+                .get("replaced_by")
+                .and_then(|replacement| argument(replacement));
+            self.deprecations.push(pos::spanned(
+                span,
+                Deprecated {
+                    name: name.to_string(),
+                    message: argument(message),
+                    replacement,
+                },
+            ));
+        }
+    }
+
+    fn check_deprecated_types(&mut self, typ: &AstType<Symbol>) {
+        let mut uses = Vec::new();
+        walk_type(typ, |typ: &AstType<Symbol>| {
+            if let Type::Ident(ref id) = **typ {
+                uses.push((typ.span(), id.clone()));
+            }
+        });
+        for (span, id) in uses {
+            if let Some(metadata) = self.metadata(&id).cloned() {
+                self.check_deprecated(span, id.declared_name(), &metadata);
             }
         }
+    }
 
-        fn new_pattern(&mut self, mut metadata: Metadata, pattern: &SpannedPattern<Symbol>) {
-            match pattern.value {
-                Pattern::Record {
-                    ref fields,
-                    ref types,
-                    ..
-                } => {
-                    for field in fields {
-                        if let Some(m) = metadata.module.remove(field.name.value.as_ref()) {
-                            let id = match field.value {
-                                Some(ref pat) => match pat.value {
-                                    Pattern::Ident(ref id) => &id.name,
-                                    _ => return self.new_pattern(m, pat),
-                                },
-                                None => &field.name.value,
-                            };
-                            self.stack_var(id.clone(), m);
-                        }
-                    }
-                    for field in types {
-                        if let Some(m) = metadata.module.remove(field.name.value.as_ref()) {
-                            let id = field
-                                .value
-                                .as_ref()
-                                .unwrap_or_else(|| &field.name.value)
-                                .clone();
-                            self.stack_var(id, m);
-                        }
+    fn new_binding(&mut self, metadata: Metadata, bind: &ValueBinding<Symbol>) {
+        match bind.name.value {
+            Pattern::As(ref id, _) => {
+                let metadata = binding_metadata(bind).unwrap_or_else(|| inherited(metadata));
+                self.stack_var(id.clone(), metadata.clone());
+                self.new_pattern(metadata, &bind.name);
+            }
+            Pattern::Ident(ref id) => {
+                let metadata = binding_metadata(bind).unwrap_or_else(|| inherited(metadata));
+                self.stack_var(id.name.clone(), metadata);
+            }
+            Pattern::Constructor(..)
+            | Pattern::Tuple { .. }
+            | Pattern::Record { .. }
+            | Pattern::Literal(_)
+            | Pattern::Error => self.new_pattern(metadata, &bind.name),
+        }
+    }
+
+    fn new_pattern(&mut self, mut metadata: Metadata, pattern: &SpannedPattern<Symbol>) {
+        match pattern.value {
+            Pattern::Record {
+                ref fields,
+                ref types,
+                ..
+            } => {
+                for field in fields {
+                    if let Some(m) = metadata.module.remove(field.name.value.as_ref()) {
+                        // Destructuring a deprecated field is a use of it
+                        self.check_deprecated(field.name.span, field.name.value.as_ref(), &m);
+                        let id = match field.value {
+                            Some(ref pat) => match pat.value {
+                                Pattern::Ident(ref id) => &id.name,
+                                _ => return self.new_pattern(m, pat),
+                            },
+                            None => &field.name.value,
+                        };
+                        self.stack_var(id.clone(), m);
                     }
                 }
-                Pattern::Ident(ref id) => self.stack_var(id.name.clone(), metadata),
-                Pattern::As(ref id, ref pat) => {
-                    self.stack_var(id.clone(), metadata.clone());
-                    self.new_pattern(metadata, pat);
+                for field in types {
+                    if let Some(m) = metadata.module.remove(field.name.value.as_ref()) {
+                        let id = field
+                            .value
+                            .as_ref()
+                            .unwrap_or_else(|| &field.name.value)
+                            .clone();
+                        self.stack_var(id, m);
+                    }
                 }
-                Pattern::Tuple { .. }
-                | Pattern::Constructor(..)
-                | Pattern::Literal(_)
-                | Pattern::Error => (),
             }
-        }
-
-        fn stack_var(&mut self, id: Symbol, metadata: Metadata) {
-            if metadata.has_data() {
-                debug!("Insert {}", id);
-                self.env.stack.insert(id, metadata);
+            Pattern::Ident(ref id) => self.stack_var(id.name.clone(), metadata),
+            Pattern::As(ref id, ref pat) => {
+                self.stack_var(id.clone(), metadata.clone());
+                self.new_pattern(metadata, pat);
             }
+            Pattern::Tuple { .. }
+            | Pattern::Constructor(..)
+            | Pattern::Literal(_)
+            | Pattern::Error => (),
         }
+    }
 
-        fn metadata(&self, id: &Symbol) -> Option<&Metadata> {
-            debug!("Lookup {}", id);
-            self.env
-                .stack
-                .get(id)
-                .or_else(|| self.env.env.get_metadata(id))
+    fn stack_var(&mut self, id: Symbol, metadata: Metadata) {
+        if metadata.has_data() {
+            debug!("Insert {}", id);
+            self.env.stack.insert(id, metadata);
         }
+    }
 
-        fn metadata_expr(&mut self, expr: &SpannedExpr<Symbol>) -> Metadata {
-            match expr.value {
-                Expr::Ident(ref id) => self.metadata(&id.name)
+    fn metadata(&self, id: &Symbol) -> Option<&Metadata> {
+        debug!("Lookup {}", id);
+        self.env
+            .stack
+            .get(id)
+            .or_else(|| self.env.env.get_metadata(id))
+    }
+
+    fn metadata_expr(&mut self, expr: &SpannedExpr<Symbol>) -> Metadata {
+        match expr.value {
+            Expr::Ident(ref id) | Expr::ImplicitApp(ref id, _) => {
+                let metadata = self.metadata(&id.name)
                     .cloned()
-                    .unwrap_or_else(Metadata::default),
-                Expr::Record {
-                    ref exprs,
-                    ref types,
-                    ..
-                } => {
-                    let mut module = BTreeMap::new();
-                    for field in exprs {
-                        let maybe_metadata = match field.value {
-                            Some(ref expr) => {
-                                let m = self.metadata_expr(expr);
-                                if m.has_data() {
-                                    Some(m)
-                                } else {
-                                    None
-                                }
+                    .unwrap_or_else(Metadata::default);
+                self.check_deprecated(expr.span, id.name.declared_name(), &metadata);
+                metadata
+            }
+            Expr::Infix(ref lhs, ref op, ref rhs) => {
+                if let Some(metadata) = self.metadata(&op.value.name).cloned() {
+                    self.check_deprecated(op.span, op.value.name.declared_name(), &metadata);
+                }
+                self.metadata_expr(lhs);
+                self.metadata_expr(rhs);
+                Metadata::default()
+            }
+            Expr::Record {
+                ref exprs,
+                ref types,
+                ..
+            } => {
+                let mut module = BTreeMap::new();
+                for field in exprs.iter() {
+                    let maybe_metadata = match field.value {
+                        // Exporting a deprecated binding is not a use of it
+                        Some(Spanned {
+                            value: Expr::Ident(ref id),
+                            ..
+                        }) => self.metadata(&id.name).cloned(),
+                        Some(ref expr) => {
+                            let m = self.metadata_expr(expr);
+                            if m.has_data() {
+                                Some(m)
+                            } else {
+                                None
                             }
-                            None => self.metadata(&field.name.value).cloned(),
-                        };
-                        let field_metadata = field.comment.clone().map(|comment| Metadata {
-                            comment: Some(comment.content),
-                            module: BTreeMap::new(),
-                        });
-                        let maybe_metadata = match (field_metadata, maybe_metadata) {
-                            (Some(l), Some(r)) => Some(l.merge(r)),
-                            (None, Some(x)) | (Some(x), None) => Some(x),
-                            (None, None) => None,
-                        };
-                        if let Some(metadata) = maybe_metadata {
-                            module.insert(String::from(field.name.value.as_ref()), metadata);
-                        }
-                    }
-                    for field in types {
-                        let maybe_metadata = self.metadata(&field.name.value).cloned();
-                        if let Some(metadata) = maybe_metadata {
-                            let name = Name::new(field.name.value.as_ref()).name().as_str();
-                            module.insert(String::from(name), metadata);
                         }
-                    }
-                    Metadata {
-                        comment: None,
-                        module: module,
+                        None => self.metadata(&field.name.value).cloned(),
+                    };
+                    let field_metadata = field.comment.clone().map(|comment| Metadata {
+                        comment: Some(comment.content),
+                        ..Metadata::default()
+                    });
+                    let maybe_metadata = match (field_metadata, maybe_metadata) {
+                        (Some(l), Some(r)) => Some(l.merge(r)),
+                        (None, Some(x)) | (Some(x), None) => Some(x),
+                        (None, None) => None,
+                    };
+                    if let Some(metadata) = maybe_metadata {
+                        module.insert(String::from(field.name.value.as_ref()), metadata);
                     }
                 }
-                Expr::LetBindings(ref bindings, ref expr) => {
-                    let is_recursive = bindings.iter().all(|bind| !bind.args.is_empty());
-                    if is_recursive {
-                        for bind in bindings {
-                            self.new_binding(Metadata::default(), bind);
-                        }
-                        for bind in bindings {
-                            self.metadata_expr(&bind.expr);
-                        }
-                    } else {
-                        for bind in bindings {
-                            let metadata = self.metadata_expr(&bind.expr);
-                            self.new_binding(metadata, bind);
-                        }
+                for field in types.iter() {
+                    let maybe_metadata = self.metadata(&field.name.value).cloned();
+                    if let Some(metadata) = maybe_metadata {
+                        let name = Name::new(field.name.value.as_ref()).name().as_str();
+                        module.insert(String::from(name), metadata);
                     }
-                    let result = self.metadata_expr(expr);
-                    result
                 }
-                Expr::TypeBindings(ref bindings, ref expr) => {
-                    for bind in bindings {
-                        let maybe_metadata = bind.comment.as_ref().map(|comment| Metadata {
-                            comment: Some(comment.content.clone()),
-                            module: BTreeMap::new(),
-                        });
-                        if let Some(metadata) = maybe_metadata {
-                            self.stack_var(bind.name.value.clone(), metadata);
-                        }
+                Metadata {
+                    module: module,
+                    ..Metadata::default()
+                }
+            }
+            Expr::LetBindings(ref bindings, ref expr) => {
+                for bind in bindings.iter() {
+                    if let Some(ref typ) = bind.typ {
+                        self.check_deprecated_types(typ);
                     }
-                    let result = self.metadata_expr(expr);
-                    result
                 }
-                Expr::Projection(ref expr, ref field, _) => {
-                    let metadata = self.metadata_expr(expr);
-                    metadata
-                        .module
-                        .get(field.as_ref())
-                        .cloned()
-                        .unwrap_or_default()
+                let is_recursive = bindings.iter().all(|bind| bind.is_function());
+                if is_recursive {
+                    for bind in bindings.iter() {
+                        self.new_binding(Metadata::default(), bind);
+                    }
+                    for bind in bindings.iter() {
+                        self.metadata_expr(&bind.expr);
+                    }
+                } else {
+                    for bind in bindings.iter() {
+                        let metadata = self.metadata_expr(&bind.expr);
+                        self.new_binding(metadata, bind);
+                    }
                 }
-                _ => {
-                    ast::walk_expr(self, expr);
-                    Metadata::default()
+                let result = self.metadata_expr(expr);
+                result
+            }
+            Expr::TypeBindings(ref bindings, ref expr) => {
+                for bind in bindings.iter() {
+                    self.check_deprecated_types(bind.alias.value.unresolved_type());
+                    if let Some(metadata) = type_binding_metadata(bind) {
+                        self.stack_var(bind.name.value.clone(), metadata);
+                    }
                 }
+                let result = self.metadata_expr(expr);
+                result
+            }
+            Expr::Projection(ref inner, ref field, _, _) => {
+                let metadata = self.metadata_expr(inner)
+                    .module
+                    .get(field.as_ref())
+                    .cloned()
+                    .unwrap_or_default();
+                // The field name is at the end of the projection
+                let field_span = Span::with_id(
+                    expr.span.end - BytePos::from(field.as_ref().len()),
+                    expr.span.end,
+                    expr.span.expansion_id,
+                );
+                self.check_deprecated(field_span, field.as_ref(), &metadata);
+                metadata
+            }
+            _ => {
+                ast::walk_expr(self, expr);
+                Metadata::default()
             }
         }
     }
+}
 
-    impl<'a, 'b> Visitor<'a> for MetadataVisitor<'b> {
-        type Ident = Symbol;
+impl<'a, 'b, 'ast> Visitor<'a, 'ast> for MetadataVisitor<'b> {
+    type Ident = Symbol;
 
-        fn visit_expr(&mut self, expr: &SpannedExpr<Symbol>) {
-            self.metadata_expr(expr);
-        }
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        self.metadata_expr(expr);
     }
+}
 
-    let mut visitor = MetadataVisitor {
-        env: Environment {
-            env: env,
-            stack: FnvMap::default(),
-        },
-    };
+/// Queries `expr` for the metadata which it contains.
+pub fn metadata(
+    env: &MetadataEnv,
+    expr: &SpannedExpr<Symbol>,
+) -> (Metadata, FnvMap<Symbol, Metadata>) {
+    let mut visitor = MetadataVisitor::new(env);
     let metadata = visitor.metadata_expr(expr);
     (metadata, visitor.env.stack)
 }
+
+/// Returns a warning for each use of a binding or type in `expr` which were marked with the
+/// `#[deprecated]` attribute. Exporting a deprecated binding from a module is not a use.
+pub fn deprecations(
+    env: &MetadataEnv,
+    expr: &SpannedExpr<Symbol>,
+) -> Errors<Spanned<Deprecated, BytePos>> {
+    let mut visitor = MetadataVisitor::new(env);
+    visitor.metadata_expr(expr);
+    visitor.deprecations.into_iter().collect()
+}
@@ -0,0 +1,506 @@
+//! Analysis of the patterns in `match` expressions. Reports `match` expressions which do not cover
+//! every possible value as errors and warns about alternatives which can never be reached as the
+//! alternatives before them already match every value they could match.
+//!
+//! The analysis is based on the "usefulness" algorithm described in
+//! "Warnings for pattern matching" by Luc Maranget.
+use std::fmt;
+
+use base::ast::{Expr, Literal, Pattern, SpannedExpr, SpannedPattern, Visitor};
+use base::error::Errors;
+use base::pos::{self, BytePos, Spanned};
+use base::resolve;
+use base::symbol::Symbol;
+use base::types::{arg_iter, ArcType, Type, TypeEnv};
+
+use messages;
+use warning::{Warning, Warnings};
+
+pub type Error = Errors<Spanned<MatchError, BytePos>>;
+
+/// The maximum number of uncovered patterns which are reported for a single `match` expression
+const MAX_MISSING_PATTERNS: usize = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatchError {
+    /// The alternatives do not match every possible value. Stores patterns describing the values
+    /// which are not matched.
+    NonExhaustive(Vec<String>),
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MatchError::NonExhaustive(ref missing) => {
+                write!(f, "{}", message!(messages::NON_EXHAUSTIVE))?;
+                for pattern in missing {
+                    write!(f, "\n    {}", pattern)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Simplified pattern which only contains the information necessary for the analysis
+#[derive(Clone, Debug)]
+enum Pat {
+    Wildcard,
+    Constructor {
+        name: String,
+        args: Vec<Pat>,
+        /// All constructors of the variant type (name and number of arguments) if they could be
+        /// determined
+        constructors: Option<Vec<(String, usize)>>,
+        /// `true` if the variant is open (`[| A | r |]`) and may contain other constructors
+        open: bool,
+    },
+    Record {
+        fields: Vec<(String, Pat)>,
+        tuple: bool,
+    },
+    Literal(Literal),
+}
+
+/// The outermost part of a pattern which a value is tested against
+#[derive(Clone, Debug, PartialEq)]
+enum Head {
+    Constructor(String, usize),
+    Record(Vec<String>, bool),
+    Literal(Literal),
+}
+
+impl Head {
+    fn arity(&self) -> usize {
+        match *self {
+            Head::Constructor(_, arity) => arity,
+            Head::Record(ref fields, _) => fields.len(),
+            Head::Literal(_) => 0,
+        }
+    }
+}
+
+/// Describes a value which is not matched by any alternative
+#[derive(Clone, Debug)]
+enum Witness {
+    Wildcard,
+    Constructor(String, Vec<Witness>),
+    Record(Vec<(String, Witness)>, bool),
+}
+
+impl Witness {
+    fn from_head(head: &Head, args: Vec<Witness>) -> Witness {
+        match *head {
+            Head::Constructor(ref name, _) => Witness::Constructor(name.clone(), args),
+            Head::Record(ref fields, tuple) => {
+                Witness::Record(fields.iter().cloned().zip(args).collect(), tuple)
+            }
+            // Literals are never reported as missing as there are (practically) infinitely many
+            Head::Literal(_) => Witness::Wildcard,
+        }
+    }
+
+    fn is_wildcard(&self) -> bool {
+        match *self {
+            Witness::Wildcard => true,
+            Witness::Record(ref fields, _) => fields.iter().all(|field| field.1.is_wildcard()),
+            Witness::Constructor(..) => false,
+        }
+    }
+}
+
+impl fmt::Display for Witness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Witness::Wildcard => write!(f, "_"),
+            Witness::Constructor(ref name, ref args) => {
+                write!(f, "{}", name)?;
+                for arg in args {
+                    match *arg {
+                        Witness::Constructor(_, ref args) if !args.is_empty() => {
+                            write!(f, " ({})", arg)?
+                        }
+                        _ => write!(f, " {}", arg)?,
+                    }
+                }
+                Ok(())
+            }
+            Witness::Record(ref fields, true) => {
+                write!(f, "(")?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", field.1)?;
+                }
+                write!(f, ")")
+            }
+            Witness::Record(ref fields, false) => {
+                if self.is_wildcard() {
+                    return write!(f, "_");
+                }
+                write!(f, "{{ ")?;
+                let fields = fields.iter().filter(|field| !field.1.is_wildcard());
+                for (i, &(ref name, ref witness)) in fields.enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} = {}", name, witness)?;
+                }
+                write!(f, ", .. }}")
+            }
+        }
+    }
+}
+
+/// Returns the constructors of the variant type which `ctor_type` constructs and whether the
+/// variant is open
+fn variant_constructors(
+    env: &TypeEnv,
+    ctor_type: &ArcType,
+) -> Option<(Vec<(String, usize)>, bool)> {
+    let mut args = arg_iter(ctor_type.remove_forall());
+    while args.next().is_some() {}
+    let variant = resolve::remove_aliases(env, args.typ.clone());
+    match **variant.remove_forall() {
+        Type::Variant(ref row) => {
+            let mut iter = row.row_iter();
+            let constructors = iter.by_ref()
+                .map(|field| {
+                    let arity = arg_iter(field.typ.remove_forall()).count();
+                    (field.name.declared_name().to_string(), arity)
+                })
+                .collect();
+            let open = match **iter.current_type() {
+                Type::EmptyRow => false,
+                _ => true,
+            };
+            Some((constructors, open))
+        }
+        _ => None,
+    }
+}
+
+fn simplify(env: &TypeEnv, pattern: &SpannedPattern<Symbol>) -> Pat {
+    match pattern.value {
+        Pattern::As(_, ref pattern) => simplify(env, pattern),
+        Pattern::Ident(_) | Pattern::Error => Pat::Wildcard,
+        Pattern::Constructor(ref id, ref args) => {
+            let (constructors, open) = match variant_constructors(env, &id.typ) {
+                Some((constructors, open)) => (Some(constructors), open),
+                None => (None, false),
+            };
+            Pat::Constructor {
+                name: id.name.declared_name().to_string(),
+                args: args.iter().map(|arg| simplify(env, arg)).collect(),
+                constructors,
+                open,
+            }
+        }
+        Pattern::Record { ref fields, .. } => Pat::Record {
+            fields: fields
+                .iter()
+                .map(|field| {
+                    let pattern = field
+                        .value
+                        .as_ref()
+                        .map_or(Pat::Wildcard, |pattern| simplify(env, pattern));
+                    (field.name.value.declared_name().to_string(), pattern)
+                })
+                .collect(),
+            tuple: false,
+        },
+        Pattern::Tuple { ref elems, .. } => Pat::Record {
+            fields: elems
+                .iter()
+                .enumerate()
+                .map(|(i, elem)| (i.to_string(), simplify(env, elem)))
+                .collect(),
+            tuple: true,
+        },
+        Pattern::Literal(ref literal) => Pat::Literal(literal.clone()),
+    }
+}
+
+/// The heads found in the first column of a pattern matrix
+struct Column {
+    heads: Vec<Head>,
+    /// The heads which are not in `heads`. `None` if there are infinitely many such heads or the
+    /// column only contains wildcards.
+    missing: Option<Vec<Head>>,
+}
+
+impl Column {
+    fn new<'p, I>(patterns: I) -> Column
+    where
+        I: IntoIterator<Item = &'p Pat>,
+    {
+        let mut heads = Vec::new();
+        let mut record: Option<(Vec<String>, bool)> = None;
+        let mut all_constructors = None;
+        let mut literal = false;
+        let mut open = false;
+        for pattern in patterns {
+            match *pattern {
+                Pat::Wildcard => (),
+                Pat::Constructor {
+                    ref name,
+                    ref args,
+                    ref constructors,
+                    open: open_variant,
+                } => {
+                    open |= open_variant;
+                    let head = Head::Constructor(name.clone(), args.len());
+                    if !heads.contains(&head) {
+                        heads.push(head);
+                    }
+                    if all_constructors.is_none() {
+                        all_constructors = constructors.clone();
+                    }
+                }
+                Pat::Record { ref fields, tuple } => {
+                    // Record patterns do not need to mention every field so the fields of each
+                    // pattern in the column are merged
+                    let names = &mut record.get_or_insert_with(|| (Vec::new(), tuple)).0;
+                    for &(ref name, _) in fields {
+                        if !names.contains(name) {
+                            names.push(name.clone());
+                        }
+                    }
+                }
+                Pat::Literal(ref literal_value) => {
+                    literal = true;
+                    let head = Head::Literal(literal_value.clone());
+                    if !heads.contains(&head) {
+                        heads.push(head);
+                    }
+                }
+            }
+        }
+
+        if let Some((fields, tuple)) = record {
+            return Column {
+                heads: vec![Head::Record(fields, tuple)],
+                missing: Some(Vec::new()),
+            };
+        }
+        if literal || open || heads.is_empty() {
+            return Column {
+                heads,
+                missing: None,
+            };
+        }
+        // If the constructors of the type could not be determined we assume that every
+        // constructor is matched so that no false errors are reported
+        let missing = all_constructors
+            .unwrap_or_else(Vec::new)
+            .into_iter()
+            .map(|(name, arity)| Head::Constructor(name, arity))
+            .filter(|head| !heads.contains(head))
+            .collect();
+        Column {
+            heads,
+            missing: Some(missing),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.missing
+            .as_ref()
+            .map_or(false, |missing| missing.is_empty())
+    }
+}
+
+/// Returns the row with its first pattern replaced by the arguments of `head` or `None` if the
+/// first pattern can't match values with that head
+fn specialize(row: &[Pat], head: &Head) -> Option<Vec<Pat>> {
+    let (first, rest) = row.split_first().expect("Non-empty row");
+    let mut new_row = match (first, head) {
+        (&Pat::Wildcard, _) => (0..head.arity()).map(|_| Pat::Wildcard).collect(),
+        (
+            &Pat::Constructor {
+                ref name, ref args, ..
+            },
+            &Head::Constructor(ref head_name, _),
+        ) => {
+            if name != head_name {
+                return None;
+            }
+            args.clone()
+        }
+        (&Pat::Record { ref fields, .. }, &Head::Record(ref names, _)) => names
+            .iter()
+            .map(|name| {
+                fields
+                    .iter()
+                    .find(|field| field.0 == *name)
+                    .map_or(Pat::Wildcard, |field| field.1.clone())
+            })
+            .collect(),
+        (&Pat::Literal(ref literal), &Head::Literal(ref head_literal)) => {
+            if literal != head_literal {
+                return None;
+            }
+            Vec::new()
+        }
+        _ => return None,
+    };
+    new_row.extend(rest.iter().cloned());
+    Some(new_row)
+}
+
+fn specialize_matrix(matrix: &[Vec<Pat>], head: &Head) -> Vec<Vec<Pat>> {
+    matrix
+        .iter()
+        .filter_map(|row| specialize(row, head))
+        .collect()
+}
+
+/// Returns the rows which start with a wildcard, with the wildcard removed
+fn default_matrix(matrix: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    matrix
+        .iter()
+        .filter_map(|row| match row[0] {
+            Pat::Wildcard => Some(row[1..].to_vec()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns true if there is a value which `row` matches but no row in `matrix` matches
+fn is_useful(matrix: &[Vec<Pat>], row: &[Pat]) -> bool {
+    if row.is_empty() {
+        return matrix.is_empty();
+    }
+    let column = Column::new(matrix.iter().map(|row| &row[0]).chain(Some(&row[0])));
+    match row[0] {
+        Pat::Wildcard => if column.is_complete() {
+            column.heads.iter().any(|head| {
+                is_useful(
+                    &specialize_matrix(matrix, head),
+                    &specialize(row, head).unwrap(),
+                )
+            })
+        } else {
+            is_useful(&default_matrix(matrix), &row[1..])
+        },
+        _ => column.heads.iter().any(|head| {
+            specialize(row, head).map_or(false, |new_row| {
+                is_useful(&specialize_matrix(matrix, head), &new_row)
+            })
+        }),
+    }
+}
+
+/// Returns rows of `width` patterns describing the values which are not matched by `matrix`
+fn missing_values(matrix: &[Vec<Pat>], width: usize) -> Vec<Vec<Witness>> {
+    if width == 0 {
+        return if matrix.is_empty() {
+            vec![Vec::new()]
+        } else {
+            Vec::new()
+        };
+    }
+    let column = Column::new(matrix.iter().map(|row| &row[0]));
+
+    let mut result = Vec::new();
+    if column.is_complete() {
+        for head in &column.heads {
+            let arity = head.arity();
+            let specialized = specialize_matrix(matrix, head);
+            for mut missing in missing_values(&specialized, arity + width - 1) {
+                let rest = missing.split_off(arity);
+                let mut row = vec![Witness::from_head(head, missing)];
+                row.extend(rest);
+                result.push(row);
+            }
+            if result.len() >= MAX_MISSING_PATTERNS {
+                break;
+            }
+        }
+    } else {
+        let rest = missing_values(&default_matrix(matrix), width - 1);
+        let firsts = match column.missing {
+            Some(ref missing) if !column.heads.is_empty() => missing
+                .iter()
+                .map(|head| {
+                    let args = (0..head.arity()).map(|_| Witness::Wildcard).collect();
+                    Witness::from_head(head, args)
+                })
+                .collect(),
+            _ => vec![Witness::Wildcard],
+        };
+        for first in firsts {
+            for rest in &rest {
+                let mut row = vec![first.clone()];
+                row.extend(rest.iter().cloned());
+                result.push(row);
+            }
+        }
+    }
+    result.truncate(MAX_MISSING_PATTERNS);
+    result
+}
+
+struct MatchChecker<'a> {
+    env: &'a TypeEnv,
+    errors: Error,
+    warnings: Warnings,
+}
+
+impl<'a> MatchChecker<'a> {
+    fn check_alternatives(&mut self, expr: &SpannedExpr<Symbol>) {
+        let alternatives = match expr.value {
+            Expr::Match(_, ref alternatives) => alternatives,
+            _ => return,
+        };
+
+        let mut matrix = Vec::with_capacity(alternatives.len());
+        for alt in alternatives.iter() {
+            let row = vec![simplify(self.env, &alt.pattern)];
+            if !is_useful(&matrix, &row) {
+                self.warnings.push(pos::spanned(
+                    alt.pattern.span,
+                    Warning::UnreachableAlternative,
+                ));
+            }
+            matrix.push(row);
+        }
+
+        let missing = missing_values(&matrix, 1);
+        if !missing.is_empty() {
+            let missing = missing
+                .into_iter()
+                .map(|mut row| row.pop().unwrap().to_string())
+                .collect();
+            self.errors
+                .push(pos::spanned(expr.span, MatchError::NonExhaustive(missing)));
+        }
+    }
+}
+
+impl<'a, 'b, 'ast> Visitor<'b, 'ast> for MatchChecker<'a> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'b SpannedExpr<'ast, Symbol>) {
+        self.check_alternatives(expr);
+        ::base::ast::walk_expr(self, expr);
+    }
+}
+
+/// Checks that every `match` expression in `expr` is exhaustive. Returns warnings for the
+/// alternatives which can not be reached if successful. `expr` is expected to have been
+/// typechecked successfully.
+pub fn check(env: &TypeEnv, expr: &SpannedExpr<Symbol>) -> Result<Warnings, Error> {
+    let mut checker = MatchChecker {
+        env,
+        errors: Errors::new(),
+        warnings: Errors::new(),
+    };
+    checker.visit_expr(expr);
+    if checker.errors.has_errors() {
+        Err(checker.errors)
+    } else {
+        Ok(checker.warnings)
+    }
+}
@@ -1,15 +1,18 @@
 use std::fmt;
 
-use base::ast::{self, DisplayEnv, Do, Expr, MutVisitor, Pattern, SpannedExpr, Typed, TypedIdent};
-use base::error::Errors;
-use base::fnv::FnvMap;
+use base::ast::{self, DisplayEnv, Do, Expr, MutVisitor, Pattern, SpannedExpr, SpannedPattern,
+                Typed, TypedIdent, ValueBinding, Visitor};
+use base::error::{Errors, Label, Labels};
+use base::fnv::{FnvMap, FnvSet};
 use base::kind::{ArcKind, Kind, KindEnv};
 use base::pos::{self, BytePos, Span, Spanned};
 use base::scoped_map::ScopedMap;
 use base::symbol::{Symbol, SymbolModule, SymbolRef};
 use base::types::{self, Alias, ArcType, RecordSelector, Type, TypeEnv};
+use messages;
 use unify_type::{State, TypeError};
 use unify::{Error as UnifyError, Unifiable, Unifier, UnifierState};
+use warning::{Warning, WarningSettings, Warnings};
 
 pub type Error = Errors<Spanned<RenameError, BytePos>>;
 
@@ -20,6 +23,20 @@ pub enum RenameError {
         expected: ArcType,
         possible_types: Vec<(Option<Span<BytePos>>, ArcType)>,
     },
+    /// More than one implicit binding matches an implicit argument of `symbol`
+    AmbiguousImplicit {
+        symbol: String,
+        expected: ArcType,
+        candidates: Vec<(Span<BytePos>, ArcType)>,
+    },
+    /// No implicit binding matches an implicit argument of `symbol`
+    MissingImplicit { symbol: String, expected: ArcType },
+    /// Resolving the implicit arguments of the implicit bindings passed to `symbol` did not
+    /// terminate within `IMPLICIT_RECURSION_LIMIT` steps
+    ImplicitRecursionLimit { symbol: String, expected: ArcType },
+    /// Bindings which are not functions but whose values depend on themselves, either directly
+    /// or through the other bindings of the same `let`
+    RecursiveValue { cycle: Vec<(String, Span<BytePos>)> },
 }
 
 impl fmt::Display for RenameError {
@@ -32,18 +49,119 @@ impl fmt::Display for RenameError {
             } => {
                 writeln!(
                     f,
-                    "Could not resolve a binding for `{}` with type `{}`",
-                    symbol, expected
+                    "{}",
+                    message!(
+                        messages::NO_MATCHING_TYPE,
+                        symbol = symbol,
+                        expected = expected
+                    )
                 )?;
-                writeln!(f, "Possibilities:")?;
+                // The locations of the possible bindings are reported as labels
+                writeln!(f, "{}", message!(messages::POSSIBILITIES))?;
                 for &(ref span, ref typ) in possible_types {
                     match *span {
-                        Some(ref span) => writeln!(f, "{} at {}", typ, span.start)?,
-                        None => writeln!(f, "{} at 'global'", typ)?,
+                        Some(_) => writeln!(f, "{}", typ)?,
+                        None => writeln!(
+                            f,
+                            "{}",
+                            message!(messages::GLOBAL_POSSIBILITY, typ = typ)
+                        )?,
                     }
                 }
                 Ok(())
             }
+            RenameError::AmbiguousImplicit {
+                ref symbol,
+                ref expected,
+                ..
+            } => write!(
+                f,
+                "{}",
+                message!(
+                    messages::AMBIGUOUS_IMPLICIT,
+                    symbol = symbol,
+                    expected = expected
+                )
+            ),
+            RenameError::MissingImplicit {
+                ref symbol,
+                ref expected,
+            } => write!(
+                f,
+                "{}",
+                message!(
+                    messages::MISSING_IMPLICIT,
+                    symbol = symbol,
+                    expected = expected
+                )
+            ),
+            RenameError::ImplicitRecursionLimit {
+                ref symbol,
+                ref expected,
+            } => write!(
+                f,
+                "{}",
+                message!(
+                    messages::IMPLICIT_RECURSION_LIMIT,
+                    symbol = symbol,
+                    expected = expected
+                )
+            ),
+            RenameError::RecursiveValue { ref cycle } => {
+                let mut path = String::new();
+                for &(ref name, _) in cycle {
+                    path.push_str(&format!("`{}` -> ", name));
+                }
+                path.push_str(&format!("`{}`", cycle[0].0));
+                write!(
+                    f,
+                    "{}",
+                    message!(messages::RECURSIVE_VALUE, name = cycle[0].0, path = path)
+                )
+            }
+        }
+    }
+}
+
+impl Labels for RenameError {
+    fn labels(&self) -> Vec<Label> {
+        match *self {
+            RenameError::NoMatchingType {
+                ref possible_types,
+                ..
+            } => possible_types
+                .iter()
+                .filter_map(|&(ref span, ref typ)| {
+                    span.map(|span| {
+                        Label::new(
+                            span,
+                            message!(messages::BINDING_INTRODUCED_HERE, typ = typ).to_string(),
+                        )
+                    })
+                })
+                .collect(),
+            RenameError::AmbiguousImplicit { ref candidates, .. } => candidates
+                .iter()
+                .map(|&(span, ref typ)| {
+                    Label::new(
+                        span,
+                        message!(messages::IMPLICIT_DEFINED_HERE, typ = typ).to_string(),
+                    )
+                })
+                .collect(),
+            RenameError::MissingImplicit { .. } | RenameError::ImplicitRecursionLimit { .. } => {
+                Vec::new()
+            }
+            RenameError::RecursiveValue { ref cycle } => cycle
+                .iter()
+                .skip(1)
+                .map(|&(ref name, span)| {
+                    Label::new(
+                        span,
+                        message!(messages::VALUE_DEFINED_HERE, name = name).to_string(),
+                    )
+                })
+                .collect(),
         }
     }
 }
@@ -83,17 +201,32 @@ impl<'a> TypeEnv for Environment<'a> {
     }
 }
 
+/// How deeply the implicit arguments of implicit bindings are resolved before giving up
+const IMPLICIT_RECURSION_LIMIT: usize = 32;
+
+/// Renames the bindings of `expr` so that each has a unique name, resolving overloaded names by
+/// their types. Returns the warnings enabled by `settings` if successful.
 pub fn rename(
     symbols: &mut SymbolModule,
     env: &TypeEnv,
+    settings: &WarningSettings,
     expr: &mut SpannedExpr<Symbol>,
-) -> Result<(), Error> {
+) -> Result<Warnings, Error> {
     use base::resolve;
 
     struct RenameVisitor<'a: 'b, 'b> {
         symbols: &'b mut SymbolModule<'a>,
         env: Environment<'b>,
         errors: Error,
+        settings: &'b WarningSettings,
+        warnings: Warnings,
+        /// Set while the bindings of a let binding's pattern are introduced
+        in_let_pattern: bool,
+        /// The renamed symbols of all bindings which may be passed as implicit arguments, the
+        /// bindings marked with `#[implicit]` and the implicit arguments, `?x`, of functions
+        implicits: FnvSet<Symbol>,
+        /// Set while introducing the variables of an implicit binding
+        in_implicit_binding: bool,
     }
 
     impl<'a, 'b> RenameVisitor<'a, 'b> {
@@ -113,19 +246,48 @@ pub fn rename(
                     let field_types = self.find_fields(typ);
                     for field in fields {
                         match field.value {
-                            Some(ref mut pat) => self.new_pattern(pat),
+                            Some(ref mut pat) => {
+                                let binding = match pat.value {
+                                    Pattern::Ident(ref id) => Some(id.name.clone()),
+                                    _ => None,
+                                };
+                                self.new_pattern(pat);
+                                if let (Some(binding), &Pattern::Ident(ref id)) =
+                                    (binding, &pat.value)
+                                {
+                                    self.stack_overloads(
+                                        &field_types,
+                                        &field.name.value,
+                                        binding,
+                                        &id.name,
+                                        pat.span,
+                                    );
+                                }
+                            }
                             None => {
                                 if let Some(field_type) = field_types
                                     .iter()
                                     .find(|field_type| field_type.name.name_eq(&field.name.value))
                                 {
                                     let id = field.name.value.clone();
+                                    if self.in_let_pattern {
+                                        self.check_shadowing(
+                                            &id,
+                                            field.name.span,
+                                            &field_type.typ,
+                                        );
+                                    }
+                                    let new_id =
+                                        self.stack_var(id.clone(), pattern.span, field_type.typ.clone());
+                                    self.stack_overloads(
+                                        &field_types,
+                                        &field.name.value,
+                                        id,
+                                        &new_id,
+                                        pattern.span,
+                                    );
                                     let pat = Pattern::Ident(TypedIdent {
-                                        name: self.stack_var(
-                                            id,
-                                            pattern.span,
-                                            field_type.typ.clone(),
-                                        ),
+                                        name: new_id,
                                         typ: field_type.typ.clone(),
                                     });
                                     field.value = Some(pos::spanned(field.name.span, pat));
@@ -154,11 +316,17 @@ pub fn rename(
                     }
                 }
                 Pattern::Ident(ref mut id) => {
+                    if self.in_let_pattern {
+                        self.check_shadowing(&id.name, pattern.span, &id.typ);
+                    }
                     let new_name = self.stack_var(id.name.clone(), pattern.span, id.typ.clone());
                     id.name = new_name;
                 }
                 Pattern::As(ref mut id, ref mut pat) => {
                     let typ = pat.env_type_of(&self.env);
+                    if self.in_let_pattern {
+                        self.check_shadowing(id, pattern.span, &typ);
+                    }
                     let new_name = self.stack_var(id.clone(), pattern.span, typ);
                     *id = new_name;
                     self.new_pattern(pat)
@@ -175,18 +343,69 @@ pub fn rename(
 
         fn stack_var(&mut self, id: Symbol, span: Span<BytePos>, typ: ArcType) -> Symbol {
             let old_id = id.clone();
+            // Numbered rather than named after the position of the binding so that the
+            // generated names do not change when code before the binding is edited or reformatted
             let name = self.symbols.string(&id).to_owned();
-            let new_id = self.symbols.symbol(format!("{}:{}", name, span.start));
+            let new_id = self.symbols.fresh_symbol(&name);
             debug!(
                 "Rename binding `{}` = `{}` `{}`",
                 self.symbols.string(&old_id),
                 self.symbols.string(&new_id),
                 typ
             );
+            if self.in_implicit_binding {
+                self.implicits.insert(new_id.clone());
+            }
             self.env.stack.insert(old_id, (new_id.clone(), span, typ));
             new_id
         }
 
+        /// Adds the overloads of `field` (`field@1`, `field@2`, ...) as candidates for the uses of
+        /// `id`, which the record pattern binds `field` to. Each overload is bound to a variable
+        /// derived from `new_id` so that they can be told apart after renaming.
+        fn stack_overloads(
+            &mut self,
+            field_types: &[types::Field<Symbol, ArcType>],
+            field: &Symbol,
+            id: Symbol,
+            new_id: &Symbol,
+            span: Span<BytePos>,
+        ) {
+            for overload in field_types {
+                if let Some((name, index)) = types::overloaded_name(overload.name.as_ref()) {
+                    if name == field.declared_name() {
+                        let overload_id =
+                            self.symbols.symbol(types::overload_name(new_id.as_ref(), index));
+                        self.env
+                            .stack
+                            .insert(id.clone(), (overload_id, span, overload.typ.clone()));
+                    }
+                }
+            }
+        }
+
+        /// Warns if `id` hides a local binding which has the same type, as that binding can no
+        /// longer be referred to (bindings of other types are overloads of `id`)
+        fn check_shadowing(&mut self, id: &Symbol, span: Span<BytePos>, typ: &ArcType) {
+            if !self.settings.shadowing {
+                return;
+            }
+            let previous = self.env.stack.get_all(id).and_then(|bindings| {
+                bindings
+                    .iter()
+                    .rev()
+                    .find(|bind| {
+                        equivalent(&self.env, bind.2.remove_forall(), typ.remove_forall())
+                    })
+                    .map(|bind| bind.1)
+            });
+            if let Some(previous) = previous {
+                let name = String::from(self.symbols.string(id));
+                self.warnings
+                    .push(pos::spanned(span, Warning::Shadowed { name, previous }));
+            }
+        }
+
         fn stack_type(&mut self, id: Symbol, span: Span<BytePos>, alias: &Alias<Symbol, ArcType>) {
             // Insert variant constructors into the local scope
             let aliased_type = alias.typ();
@@ -237,12 +456,165 @@ pub fn rename(
                 })
         }
 
+        /// Selects the binding which is passed as an implicit argument of type `expected` to
+        /// `function`. Every name in scope is searched for an implicit binding of that type (a
+        /// binding marked `#[implicit]` or an implicit argument, `?x`), it is an error if there
+        /// is not exactly one such binding. Ordinary bindings still shadow implicit bindings
+        /// which have the same name and type.
+        fn rename_implicit_args(
+            &self,
+            function: &Symbol,
+            args: &mut [ast::ImplicitArg<Symbol>],
+        ) -> Result<(), RenameError> {
+            for arg in args {
+                self.rename_implicit(function, arg, 0)?;
+                debug!("Implicit argument of {} = {}", function, arg.name.name);
+            }
+            Ok(())
+        }
+
+        /// Resolves the implicit binding passed as `arg` to `function`. If the binding takes
+        /// implicit arguments of its own (`Show (List a)` needing a `Show a`) those are resolved
+        /// as well and stored in `arg.args`
+        fn rename_implicit(
+            &self,
+            function: &Symbol,
+            arg: &mut ast::ImplicitArg<Symbol>,
+            depth: usize,
+        ) -> Result<(), RenameError> {
+            if depth > IMPLICIT_RECURSION_LIMIT {
+                return Err(RenameError::ImplicitRecursionLimit {
+                    symbol: String::from(self.symbols.string(function)),
+                    expected: arg.name.typ.clone(),
+                });
+            }
+            let mut candidates: Vec<_> = {
+                let expected = arg.name.typ.remove_forall();
+                self.env
+                    .stack
+                    .iter()
+                    .filter_map(|(name, _)| {
+                        self.env
+                            .stack
+                            .get_all(name)
+                            .into_iter()
+                            .flat_map(|bindings| bindings.iter().rev())
+                            .filter_map(|bind| {
+                                let candidate = bind.2.remove_forall().remove_implicit_args();
+                                equivalent_generics(&self.env, candidate, expected)
+                                    .map(|generics| (bind, generics))
+                            })
+                            .next()
+                            .and_then(|(bind, generics)| {
+                                if self.implicits.contains(&bind.0) {
+                                    Some((bind, generics))
+                                } else {
+                                    None
+                                }
+                            })
+                    })
+                    .collect()
+            };
+            candidates.sort_by_key(|&(bind, _)| bind.1.start);
+            match candidates.len() {
+                0 => Err(RenameError::MissingImplicit {
+                    symbol: String::from(self.symbols.string(function)),
+                    expected: arg.name.typ.clone(),
+                }),
+                1 => {
+                    let (bind, generics) = candidates.pop().unwrap();
+                    arg.name.name = bind.0.clone();
+                    arg.args.clear();
+                    let mut typ = bind.2.remove_forall();
+                    while let Some((arg_type, ret)) = typ.as_implicit_function() {
+                        let arg_type = arg_type
+                            .instantiate_generics_(&generics)
+                            .unwrap_or_else(|| arg_type.clone());
+                        let mut nested = ast::ImplicitArg::new(bind.0.clone(), arg_type);
+                        self.rename_implicit(function, &mut nested, depth + 1)?;
+                        arg.args.push(nested);
+                        typ = ret;
+                    }
+                    Ok(())
+                }
+                _ => Err(RenameError::AmbiguousImplicit {
+                    symbol: String::from(self.symbols.string(function)),
+                    expected: arg.name.typ.clone(),
+                    candidates: candidates
+                        .iter()
+                        .map(|&(bind, _)| (bind.1, bind.2.clone()))
+                        .collect(),
+                }),
+            }
+        }
+
+        /// Returns the field which a projection of `field` with the type `expected` refers to if
+        /// `field` is overloaded in `record_type`
+        fn rename_projection(
+            &self,
+            record_type: &ArcType,
+            field: &Symbol,
+            expected: &ArcType,
+        ) -> Result<Option<Symbol>, RenameError> {
+            let candidates: Vec<_> = self.find_fields(record_type)
+                .into_iter()
+                .filter(|candidate| {
+                    candidate.name.name_eq(field)
+                        || types::overloaded_name(candidate.name.as_ref())
+                            .map_or(false, |(name, _)| name == field.declared_name())
+                })
+                .collect();
+            if candidates.len() <= 1 {
+                return Ok(None);
+            }
+            candidates
+                .iter()
+                .find(|candidate| {
+                    equivalent(&self.env, candidate.typ.remove_forall(), expected.remove_forall())
+                })
+                .map(|candidate| Some(candidate.name.clone()))
+                .ok_or_else(|| RenameError::NoMatchingType {
+                    symbol: String::from(field.declared_name()),
+                    expected: expected.clone(),
+                    possible_types: candidates
+                        .iter()
+                        .map(|candidate| (None, candidate.typ.clone()))
+                        .collect(),
+                })
+        }
+
+        fn rename_projection_expr(
+            &self,
+            record_type: &ArcType,
+            field: &mut Symbol,
+            typ: &ArcType,
+            args: &mut [ast::ImplicitArg<Symbol>],
+        ) -> Result<(), RenameError> {
+            if let Some(new_field) = self.rename_projection(record_type, field, typ)? {
+                debug!("Rename projection {} = {}", field, new_field);
+                *field = new_field;
+            }
+            self.rename_implicit_args(field, args)
+        }
+
         fn rename_expr(&mut self, expr: &mut SpannedExpr<Symbol>) -> Result<(), RenameError> {
             match expr.value {
+                Expr::Projection(ref mut record, ref mut field, ref typ, ref mut args) => {
+                    let record_type = record.env_type_of(&self.env);
+                    self.rename_projection_expr(&record_type, field, typ, args)?;
+                    self.visit_expr(record);
+                }
                 Expr::Ident(ref mut id) => if let Some(new_id) = self.rename(&id.name, &id.typ)? {
                     debug!("Rename identifier {} = {}", id.name, new_id);
                     id.name = new_id;
                 },
+                Expr::ImplicitApp(ref mut id, ref mut args) => {
+                    self.rename_implicit_args(&id.name, args)?;
+                    if let Some(new_id) = self.rename(&id.name, &id.typ)? {
+                        debug!("Rename identifier {} = {}", id.name, new_id);
+                        id.name = new_id;
+                    }
+                }
                 Expr::Record {
                     ref mut typ,
                     ref mut exprs,
@@ -250,7 +622,7 @@ pub fn rename(
                     ..
                 } => {
                     let field_types = self.find_fields(typ);
-                    for (field, expr_field) in field_types.iter().zip(exprs) {
+                    for (field, expr_field) in field_types.iter().zip(exprs.iter_mut()) {
                         match expr_field.value {
                             Some(ref mut expr) => self.visit_expr(expr),
                             None => if let Some(new_id) =
@@ -286,7 +658,7 @@ pub fn rename(
                 }
                 Expr::Match(ref mut expr, ref mut alts) => {
                     self.visit_expr(expr);
-                    for alt in alts {
+                    for alt in alts.iter_mut() {
                         self.env.stack_types.enter_scope();
                         self.env.stack.enter_scope();
                         self.new_pattern(&mut alt.pattern);
@@ -298,19 +670,32 @@ pub fn rename(
                 Expr::LetBindings(ref mut bindings, ref mut expr) => {
                     self.env.stack_types.enter_scope();
                     self.env.stack.enter_scope();
-                    let is_recursive = bindings.iter().all(|bind| !bind.args.is_empty());
+                    let is_recursive = bindings.iter().all(|bind| bind.is_function());
                     for bind in bindings.iter_mut() {
                         if !is_recursive {
                             self.visit_expr(&mut bind.expr);
                         }
+                        self.in_let_pattern = true;
+                        self.in_implicit_binding = bind.is_implicit();
                         self.new_pattern(&mut bind.name);
+                        self.in_implicit_binding = false;
+                        self.in_let_pattern = false;
                     }
                     if is_recursive {
-                        for bind in bindings {
+                        for bind in bindings.iter_mut() {
                             self.env.stack.enter_scope();
-                            for (typ, arg) in types::arg_iter(bind.resolved_type.remove_forall())
-                                .zip(&mut bind.args)
-                            {
+                            let mut typ = bind.resolved_type.remove_forall();
+                            self.in_implicit_binding = true;
+                            for arg in &mut bind.implicit_args {
+                                let (arg_type, ret) = typ.as_implicit_function()
+                                    .unwrap_or_else(|| ice!("Expected an implicit argument"));
+                                let name = arg.value.name.clone();
+                                arg.value.name = self.stack_var(name, arg.span, arg_type.clone());
+                                typ = ret;
+                            }
+                            self.in_implicit_binding = false;
+                            for (typ, arg) in types::arg_iter(typ).zip(&mut bind.args) {
+                                self.check_shadowing(&arg.value.name, arg.span, typ);
                                 arg.value.name =
                                     self.stack_var(arg.value.name.clone(), expr.span, typ.clone());
                             }
@@ -325,6 +710,7 @@ pub fn rename(
                 Expr::Lambda(ref mut lambda) => {
                     self.env.stack.enter_scope();
                     for (typ, arg) in types::arg_iter(&lambda.id.typ).zip(&mut lambda.args) {
+                        self.check_shadowing(&arg.value.name, arg.span, typ);
                         arg.value.name =
                             self.stack_var(arg.value.name.clone(), expr.span, typ.clone());
                     }
@@ -333,7 +719,7 @@ pub fn rename(
                 }
                 Expr::TypeBindings(ref bindings, ref mut body) => {
                     self.env.stack_types.enter_scope();
-                    for bind in bindings {
+                    for bind in bindings.iter() {
                         self.stack_type(
                             bind.name.value.clone(),
                             expr.span,
@@ -377,10 +763,10 @@ pub fn rename(
         }
     }
 
-    impl<'a, 'b> MutVisitor for RenameVisitor<'a, 'b> {
+    impl<'a, 'b, 'ast> MutVisitor<'ast> for RenameVisitor<'a, 'b> {
         type Ident = Symbol;
 
-        fn visit_expr(&mut self, expr: &mut SpannedExpr<Self::Ident>) {
+        fn visit_expr(&mut self, expr: &mut SpannedExpr<'ast, Self::Ident>) {
             if let Err(err) = self.rename_expr(expr) {
                 self.errors.push(Spanned {
                     span: expr.span,
@@ -393,21 +779,261 @@ pub fn rename(
     let mut visitor = RenameVisitor {
         symbols: symbols,
         errors: Errors::new(),
+        implicits: FnvSet::default(),
+        in_implicit_binding: false,
         env: Environment {
             env: env,
             stack: ScopedMap::new(),
             stack_types: ScopedMap::new(),
         },
+        settings: settings,
+        warnings: Errors::new(),
+        in_let_pattern: false,
     };
     visitor.visit_expr(expr);
     if visitor.errors.has_errors() {
         Err(visitor.errors)
     } else {
-        Ok(())
+        Ok(visitor.warnings)
+    }
+}
+
+/// Pushes the variables bound by `pattern` to `names`
+fn pattern_names<'a>(pattern: &'a SpannedPattern<Symbol>, names: &mut Vec<&'a Symbol>) {
+    match pattern.value {
+        Pattern::Ident(ref id) => names.push(&id.name),
+        Pattern::As(ref id, ref pat) => {
+            names.push(id);
+            pattern_names(pat, names);
+        }
+        Pattern::Record { ref fields, .. } => for field in fields {
+            match field.value {
+                Some(ref pat) => pattern_names(pat, names),
+                None => names.push(&field.name.value),
+            }
+        },
+        Pattern::Tuple { ref elems, .. } => for elem in elems {
+            pattern_names(elem, names);
+        },
+        Pattern::Constructor(_, ref args) => for arg in args {
+            pattern_names(arg, names);
+        },
+        Pattern::Literal(_) | Pattern::Error => (),
+    }
+}
+
+/// Collects the uses of `names` in an expression, skipping the uses where a name has been rebound
+/// inside the expression
+struct References<'a: 'n, 'n> {
+    names: &'n [&'a Symbol],
+    shadowed: Vec<&'a Symbol>,
+    found: Vec<Symbol>,
+}
+
+impl<'a: 'n, 'n> References<'a, 'n> {
+    fn reference(&mut self, id: &'a Symbol) {
+        if self.names.contains(&id) && !self.shadowed.contains(&id) && !self.found.contains(id) {
+            self.found.push(id.clone());
+        }
+    }
+}
+
+impl<'a: 'n, 'ast, 'n> Visitor<'a, 'ast> for References<'a, 'n> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        let scope = self.shadowed.len();
+        match expr.value {
+            Expr::Ident(ref id) | Expr::ImplicitApp(ref id, _) => self.reference(&id.name),
+            Expr::Infix(ref lhs, ref op, ref rhs) => {
+                self.reference(&op.value.name);
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            Expr::Record {
+                ref exprs,
+                ref base,
+                ..
+            } => {
+                for field in exprs.iter() {
+                    match field.value {
+                        Some(ref expr) => self.visit_expr(expr),
+                        None => self.reference(&field.name.value),
+                    }
+                }
+                if let Some(ref base) = *base {
+                    self.visit_expr(base);
+                }
+            }
+            Expr::Lambda(ref lambda) => {
+                self.shadowed
+                    .extend(lambda.args.iter().map(|arg| &arg.value.name));
+                self.visit_expr(&lambda.body);
+            }
+            Expr::LetBindings(ref bindings, ref body) => {
+                let is_recursive = bindings.iter().all(|bind| bind.is_function());
+                if is_recursive {
+                    for bind in bindings.iter() {
+                        pattern_names(&bind.name, &mut self.shadowed);
+                    }
+                }
+                for bind in bindings.iter() {
+                    let bind_scope = self.shadowed.len();
+                    self.shadowed.extend(
+                        bind.implicit_args
+                            .iter()
+                            .chain(&bind.args)
+                            .map(|arg| &arg.value.name),
+                    );
+                    self.visit_expr(&bind.expr);
+                    self.shadowed.truncate(bind_scope);
+                    if !is_recursive {
+                        pattern_names(&bind.name, &mut self.shadowed);
+                    }
+                }
+                self.visit_expr(body);
+            }
+            Expr::Match(ref expr, ref alts) => {
+                self.visit_expr(expr);
+                for alt in alts.iter() {
+                    pattern_names(&alt.pattern, &mut self.shadowed);
+                    self.visit_expr(&alt.expr);
+                    self.shadowed.truncate(scope);
+                }
+            }
+            Expr::Do(Do {
+                ref id,
+                ref bound,
+                ref body,
+                ..
+            }) => {
+                self.visit_expr(bound);
+                self.shadowed.push(&id.value.name);
+                self.visit_expr(body);
+            }
+            _ => ast::walk_expr(self, expr),
+        }
+        self.shadowed.truncate(scope);
+    }
+}
+
+/// Finds the bindings of a `let` whose values depend on themselves. Only `let`s where every
+/// binding is a function (`let f x = ...`) may be recursive, the bindings of other `let`s are
+/// evaluated in order so a binding which refers to itself, or to a later binding which refers
+/// back to it, can't be evaluated.
+///
+/// `is_defined` returns whether a name refers to a binding outside of the `let`, a binding
+/// which uses such a name is not recursive (`let x = x + 1` where `x` is already defined).
+///
+/// The cycle is returned as the index of each binding in it along with the name it is referred
+/// to by.
+pub fn value_cycle<F>(
+    bindings: &[ValueBinding<Symbol>],
+    is_defined: F,
+) -> Option<Vec<(usize, Symbol)>>
+where
+    F: Fn(&Symbol) -> bool,
+{
+    let bound: Vec<Vec<&Symbol>> = bindings
+        .iter()
+        .map(|bind| {
+            let mut names = Vec::new();
+            pattern_names(&bind.name, &mut names);
+            names
+        })
+        .collect();
+    let all_names: Vec<&Symbol> = bound
+        .iter()
+        .flat_map(|names| names.iter().cloned())
+        .collect();
+
+    // The bindings which each binding refers to
+    let edges: Vec<Vec<(usize, Symbol)>> = bindings
+        .iter()
+        .enumerate()
+        .map(|(i, bind)| {
+            let mut references = References {
+                names: &all_names,
+                shadowed: bind.implicit_args
+                    .iter()
+                    .chain(&bind.args)
+                    .map(|arg| &arg.value.name)
+                    .collect(),
+                found: Vec::new(),
+            };
+            references.visit_expr(&bind.expr);
+            references
+                .found
+                .into_iter()
+                .filter_map(|name| {
+                    // A name refers to the last binding before this one which binds it, or if
+                    // there is none and it is not defined outside the `let`, to this binding or a
+                    // later one
+                    let earlier = (0..i).rev().find(|&j| bound[j].contains(&&name));
+                    let target = match earlier {
+                        Some(j) => Some(j),
+                        None if !is_defined(&name) => {
+                            (i..bindings.len()).find(|&j| bound[j].contains(&&name))
+                        }
+                        None => None,
+                    };
+                    target.map(|j| (j, name))
+                })
+                .collect()
+        })
+        .collect();
+
+    fn find_path(
+        edges: &[Vec<(usize, Symbol)>],
+        start: usize,
+        current: usize,
+        path: &mut Vec<(usize, Symbol)>,
+        visited: &mut Vec<bool>,
+    ) -> bool {
+        for &(next, ref name) in &edges[current] {
+            if next == start {
+                path.push((start, name.clone()));
+                return true;
+            }
+            if !visited[next] {
+                visited[next] = true;
+                path.push((next, name.clone()));
+                if find_path(edges, start, next, path, visited) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
     }
+
+    (0..bindings.len())
+        .filter_map(|start| {
+            let mut path = Vec::new();
+            let mut visited = vec![false; bindings.len()];
+            if find_path(&edges, start, start, &mut path, &mut visited) {
+                // `path` ends with the edge back to `start`, rotate it so that `start` is first
+                let last = path.pop().unwrap();
+                path.insert(0, last);
+                Some(path)
+            } else {
+                None
+            }
+        })
+        .next()
 }
 
 pub fn equivalent(env: &TypeEnv, actual: &ArcType, inferred: &ArcType) -> bool {
+    equivalent_generics(env, actual, inferred).is_some()
+}
+
+/// Like `equivalent` but also returns the types that the generic variables of `actual` were
+/// matched against
+fn equivalent_generics(
+    env: &TypeEnv,
+    actual: &ArcType,
+    inferred: &ArcType,
+) -> Option<FnvMap<Symbol, ArcType>> {
     use substitution::Substitution;
     // FIXME This Substitution is unnecessary for equivalence unification
     let subs = Substitution::new(Kind::typ());
@@ -419,7 +1045,11 @@ pub fn equivalent(env: &TypeEnv, actual: &ArcType, inferred: &ArcType) -> bool {
         },
     };
     unifier.try_match(actual, inferred);
-    unifier.unifier.equiv
+    if unifier.unifier.equiv {
+        Some(unifier.unifier.map)
+    } else {
+        None
+    }
 }
 
 struct Equivalent {
@@ -455,6 +1085,31 @@ impl<'a> Unifier<State<'a>, ArcType> for UnifierState<State<'a>, Equivalent> {
                     Ok(None)
                 }
             },
+            // A `forall` nested inside a type (`(forall a . a -> a) -> Int`) is equivalent to
+            // another if their bodies are equal after pairing up the parameters
+            (
+                &Type::Forall(ref l_params, ref l_inner, _),
+                &Type::Forall(ref r_params, ref r_inner, _),
+            ) if l_params.len() == r_params.len() =>
+            {
+                let previous: Vec<_> = l_params
+                    .iter()
+                    .zip(r_params)
+                    .map(|(l_param, r_param)| {
+                        self.unifier
+                            .map
+                            .insert(l_param.id.clone(), Type::generic(r_param.clone()))
+                    })
+                    .collect();
+                let result = self.try_match_res(l_inner, r_inner);
+                for (l_param, previous) in l_params.iter().zip(previous) {
+                    match previous {
+                        Some(typ) => self.unifier.map.insert(l_param.id.clone(), typ),
+                        None => self.unifier.map.remove(&l_param.id),
+                    };
+                }
+                result.map(|_| None)
+            }
             _ => l.zip_match(r, self),
         }
     }
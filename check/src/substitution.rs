@@ -1,6 +1,7 @@
-use std::cell::{RefCell, RefMut};
+use std::cell::{Ref, RefCell, RefMut};
 use std::default::Default;
 use std::fmt;
+use std::mem;
 use std::sync::Arc;
 
 use itertools::Itertools;
@@ -11,8 +12,11 @@ use base::fnv::FnvMap;
 use base::fixed::{FixedMap, FixedVec};
 use base::types;
 use base::types::{ArcType, Generic, Type, Walker};
+use base::pos::{BytePos, Span};
 use base::symbol::Symbol;
 
+use messages;
+
 #[derive(Debug, PartialEq)]
 pub enum Error<T> {
     Occurs(T, T),
@@ -28,13 +32,11 @@ where
         use self::Error::*;
 
         match *self {
-            Occurs(ref var, ref typ) => write!(f, "Variable `{}` occurs in `{}`.", var, typ),
+            Occurs(ref var, ref typ) => {
+                write!(f, "{}", message!(messages::OCCURS, var = var, typ = typ))
+            }
             Constraint(ref typ, ref constraints) => {
-                writeln!(
-                    f,
-                    "Type `{}` could not fullfill a constraint.\nPossible resolves:",
-                    typ,
-                )?;
+                writeln!(f, "{}", message!(messages::CONSTRAINT, typ = typ))?;
                 for constraint in &constraints[..] {
                     writeln!(f, "{}", constraint)?;
                 }
@@ -48,6 +50,23 @@ use typecheck::unroll_typ;
 
 pub type Constraints<T> = Arc<Vec<T>>;
 
+/// The unification of `expected` and `actual` at `span`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Origin<T, Pos = BytePos> {
+    pub span: Span<Pos>,
+    pub expected: T,
+    pub actual: T,
+}
+
+/// Records that `variable` were bound to `typ` while unifying the types in `origin`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Provenance<T> {
+    pub variable: T,
+    pub typ: T,
+    /// `None` if the binding were made outside of any unification set with `set_origin`
+    pub origin: Option<Origin<T>>,
+}
+
 pub struct Substitution<T>
 where
     T: Substitutable,
@@ -63,6 +82,11 @@ where
     /// `&T` from this map safely.
     types: FixedMap<u32, T>,
     factory: T::Factory,
+    /// Every binding made by `union` in the order they were made, `None` unless recording has
+    /// been enabled with `record_provenance`
+    provenance: RefCell<Option<Vec<Provenance<T>>>>,
+    /// The unification which bindings are currently made for
+    origin: RefCell<Option<Origin<T>>>,
 }
 
 impl<T> Default for Substitution<T>
@@ -248,6 +272,8 @@ impl<T: Substitutable> Substitution<T> {
             variables: FixedVec::new(),
             types: FixedMap::new(),
             factory: factory,
+            provenance: RefCell::new(None),
+            origin: RefCell::new(None),
         }
     }
 
@@ -258,6 +284,34 @@ impl<T: Substitutable> Substitution<T> {
     pub fn clear(&mut self) {
         self.types.clear();
         self.variables.clear();
+        if let Some(ref mut provenance) = *self.provenance.borrow_mut() {
+            provenance.clear();
+        }
+        *self.origin.borrow_mut() = None;
+    }
+
+    /// Enables or disables the recording of why each variable were bound. Enabling it discards
+    /// anything recorded previously.
+    pub fn record_provenance(&self, enable: bool) {
+        *self.provenance.borrow_mut() = if enable { Some(Vec::new()) } else { None };
+    }
+
+    pub fn is_recording_provenance(&self) -> bool {
+        self.provenance.borrow().is_some()
+    }
+
+    /// Sets the unification which the following bindings are made for, returning the previous
+    /// one so that it can be restored once the unification is done
+    pub fn set_origin(&self, origin: Option<Origin<T>>) -> Option<Origin<T>> {
+        mem::replace(&mut *self.origin.borrow_mut(), origin)
+    }
+
+    /// Returns the bindings which have been recorded since the substitution were last cleared
+    pub fn provenance(&self) -> Ref<[Provenance<T>]> {
+        Ref::map(self.provenance.borrow(), |provenance| match *provenance {
+            Some(ref provenance) => &provenance[..],
+            None => &[],
+        })
     }
 
     pub fn insert(&self, var: u32, t: T) {
@@ -504,6 +558,13 @@ impl<T: Substitutable + PartialEq + Clone> Substitution<T> {
                     self.insert(id.get_id(), typ.clone());
                 }
             }
+            if let Some(ref mut provenance) = *self.provenance.borrow_mut() {
+                provenance.push(Provenance {
+                    variable: T::from_variable(id.clone()),
+                    typ: typ.clone(),
+                    origin: self.origin.borrow().clone(),
+                });
+            }
         }
         Ok(resolved_type)
     }
@@ -5,29 +5,36 @@ use std::borrow::Cow;
 use std::fmt;
 use std::iter::once;
 use std::mem;
+use std::ptr;
 use std::sync::Arc;
 
 use itertools::Itertools;
 
 use base::scoped_map::ScopedMap;
 use base::ast::{DisplayEnv, Do, Expr, Literal, MutVisitor, Pattern, PatternField, SpannedExpr};
-use base::ast::{AstType, SpannedIdent, SpannedPattern, TypeBinding, Typed, TypedIdent,
-                ValueBinding};
-use base::error::Errors;
+use base::ast::{AstType, ImplicitArg, SpannedIdent, SpannedPattern, TypeBinding, Typed,
+                TypedIdent, ValueBinding};
+use base::error::{Errors, Label, Labels};
 use base::fnv::{FnvMap, FnvSet};
 use base::resolve;
+use base::suggest;
 use base::kind::{ArcKind, Kind, KindCache, KindEnv};
 use base::merge;
 use base::pos::{self, BytePos, Span, Spanned};
 use base::symbol::{Symbol, SymbolModule, SymbolRef, Symbols};
+use base::timings::{self, Phase};
 use base::types::{self, Alias, AliasRef, AppVec, ArcType, Field, Filter, Generic, PrimitiveEnv,
                   RecordSelector, Skolem, Type, TypeCache, TypeEnv, TypeFormatter, TypeVariable};
 
+use explain::{ExprType, Explanation};
 use kindcheck::{self, Error as KindCheckError, KindCheck, KindError};
-use substitution::{self, Constraints, Substitution};
+use messages;
+use substitution::{self, Constraints, Origin, Substitutable, Substitution};
+use pattern_match::MatchError;
 use rename::RenameError;
 use unify::{self, Error as UnifyError};
 use unify_type::{self, new_skolem_scope, Error as UnifyTypeError};
+use warning::{WarningSettings, Warnings};
 
 /// Type representing a single error when checking a type
 #[derive(Debug, PartialEq)]
@@ -42,27 +49,59 @@ pub enum TypeError<I> {
     UndefinedField(ArcType<I>, I),
     /// Constructor type was found in a pattern but did not have the expected number of arguments
     PatternError(ArcType<I>, usize),
-    /// Errors found when trying to unify two types
-    Unification(ArcType<I>, ArcType<I>, Vec<UnifyTypeError<I>>),
+    /// Errors found when trying to unify two types, along with how the generic variables which
+    /// lead to the mismatched types were instantiated
+    Unification(
+        ArcType<I>,
+        ArcType<I>,
+        Vec<UnifyTypeError<I>>,
+        Vec<Instantiation<I>>,
+    ),
     /// Error were found when trying to unify the kinds of two types
     KindError(KindCheckError<I>),
     /// Errors found during renaming (overload resolution)
     Rename(RenameError),
-    /// Multiple types were declared with the same name in the same expression
-    DuplicateTypeDefinition(I),
-    /// A field was defined more than once in a record constructor or pattern match
-    DuplicateField(String),
+    /// Non-exhaustive `match` expressions or unreachable alternatives
+    PatternMatch(MatchError),
+    /// Multiple types were declared with the same name in the same expression. Stores the location
+    /// of the previous definition if it is known
+    DuplicateTypeDefinition(I, Option<Span<BytePos>>),
+    /// A field was defined more than once in a record constructor or pattern match. Stores the
+    /// location of the first definition of the field
+    DuplicateField(String, Span<BytePos>),
     /// Type is not a type which has any fields
     InvalidProjection(ArcType<I>),
+    /// A record expression did not have the same fields as the record alias it was expected to
+    /// have the type of
+    RecordFieldMismatch {
+        alias: I,
+        /// Fields of the alias which are missing from the record expression
+        missing: Vec<(I, ArcType<I>)>,
+        /// Fields in the record expression which the alias does not have
+        extra: Vec<(I, ArcType<I>)>,
+        /// Where the alias was defined, if it was defined in the checked expression
+        definition: Option<Span<BytePos>>,
+    },
     /// Expected to find a record with the following fields
     UndefinedRecord {
         fields: Vec<I>,
     },
     /// Found a case expression without any alternatives
     EmptyCase,
+    /// The expression is nested deeper than the limit set with `Typecheck::set_max_depth`
+    ExprTooDeeplyNested,
     Message(String),
 }
 
+/// Records that the generic variable `generic` were instantiated to `typ` by the unification at
+/// `span`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instantiation<I> {
+    pub generic: I,
+    pub typ: ArcType<I>,
+    pub span: Span<BytePos>,
+}
+
 impl<I> From<KindCheckError<I>> for TypeError<I> {
     fn from(e: KindCheckError<I>) -> Self {
         match e {
@@ -78,18 +117,30 @@ impl<I> From<RenameError> for TypeError<I> {
     }
 }
 
+impl<I> From<MatchError> for TypeError<I> {
+    fn from(e: MatchError) -> TypeError<I> {
+        TypeError::PatternMatch(e)
+    }
+}
+
 impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use self::TypeError::*;
         use pretty::{Arena, DocAllocator};
         match *self {
-            UndefinedVariable(ref name) => write!(f, "Undefined variable `{}`", name),
-            NotAFunction(ref typ) => write!(f, "`{}` is not a function", typ),
-            UndefinedType(ref name) => write!(f, "Type `{}` is not defined", name),
-            UndefinedField(ref typ, ref field) => {
-                write!(f, "Type `{}` does not have the field `{}`", typ, field)
+            UndefinedVariable(ref name) => {
+                write!(f, "{}", message!(messages::UNDEFINED_VARIABLE, name = name))
+            }
+            NotAFunction(ref typ) => write!(f, "{}", message!(messages::NOT_A_FUNCTION, typ = typ)),
+            UndefinedType(ref name) => {
+                write!(f, "{}", message!(messages::UNDEFINED_TYPE, name = name))
             }
-            Unification(ref expected, ref actual, ref errors) => {
+            UndefinedField(ref typ, ref field) => write!(
+                f,
+                "{}",
+                message!(messages::UNDEFINED_FIELD, typ = typ, field = field)
+            ),
+            Unification(ref expected, ref actual, ref errors, _) => {
                 let filters = errors
                     .iter()
                     .filter_map(|err| match *err {
@@ -116,25 +167,26 @@ impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
 
                 let arena = Arena::new();
                 let types = chain![&arena;
-                    "Expected:",
+                    arena.text(message!(messages::EXPECTED).to_string()),
                     chain![&arena;
                         arena.space(),
                         TypeFormatter::new(expected).filter(&filter).pretty(&arena)
                     ].nest(4).group(),
                     arena.newline(),
-                    "Found:",
+                    arena.text(message!(messages::FOUND).to_string()),
                     chain![&arena;
                         arena.space(),
                         TypeFormatter::new(actual).filter(&filter).pretty(&arena)
                     ].nest(4).group()
                 ].group();
                 let doc = chain![&arena;
-                    "Expected the following types to be equal",
+                    arena.text(message!(messages::UNIFICATION).to_string()),
                     arena.newline(),
                     types,
                     arena.newline(),
-                    arena.as_string(errors.len()),
-                    " errors were found during unification:"
+                    arena.text(
+                        message!(messages::UNIFICATION_ERRORS, count = errors.len()).to_string()
+                    )
                 ];
                 writeln!(f, "{}", doc.1.pretty(80))?;
                 if errors.is_empty() {
@@ -148,51 +200,121 @@ impl<I: fmt::Display + AsRef<str>> fmt::Display for TypeError<I> {
                 }
                 write!(f, "{}", errors.last().unwrap())
             }
-            PatternError(ref typ, expected_len) => {
-                write!(f, "Type {} has {} to few arguments", typ, expected_len)
-            }
+            PatternError(ref typ, expected_len) => write!(
+                f,
+                "{}",
+                message!(messages::PATTERN_ERROR, typ = typ, count = expected_len)
+            ),
             KindError(ref err) => kindcheck::fmt_kind_error(err, f),
             Rename(ref err) => write!(f, "{}", err),
-            DuplicateTypeDefinition(ref id) => write!(
+            PatternMatch(ref err) => write!(f, "{}", err),
+            DuplicateTypeDefinition(ref id, _) => write!(
                 f,
-                "Type '{}' has been already been defined in this module",
-                id
+                "{}",
+                message!(messages::DUPLICATE_TYPE_DEFINITION, name = id)
             ),
-            DuplicateField(ref id) => {
-                write!(f, "The record has more than one field named '{}'", id)
+            DuplicateField(ref id, _) => {
+                write!(f, "{}", message!(messages::DUPLICATE_FIELD, field = id))
             }
-            InvalidProjection(ref typ) => write!(
-                f,
-                "Type '{}' is not a type which allows field accesses",
-                typ
-            ),
-            UndefinedRecord { ref fields } => {
-                write!(f, "No type found with the following fields: ")?;
-                write!(f, "{}", fields[0])?;
-                for field in &fields[1..] {
-                    write!(f, ", {}", field)?;
+            InvalidProjection(ref typ) => {
+                write!(f, "{}", message!(messages::INVALID_PROJECTION, typ = typ))
+            }
+            RecordFieldMismatch {
+                ref alias,
+                ref missing,
+                ref extra,
+                ..
+            } => {
+                write!(
+                    f,
+                    "{}",
+                    message!(messages::RECORD_FIELD_MISMATCH, alias = alias)
+                )?;
+                if !missing.is_empty() {
+                    write!(f, "\n{}", message!(messages::MISSING_FIELDS))?;
+                    for &(ref name, ref typ) in missing {
+                        write!(f, "\n    {} : {}", name, typ)?;
+                    }
+                }
+                if !extra.is_empty() {
+                    write!(f, "\n{}", message!(messages::UNEXPECTED_FIELDS))?;
+                    for &(ref name, ref typ) in extra {
+                        write!(f, "\n    {} : {}", name, typ)?;
+                    }
                 }
                 Ok(())
             }
-            EmptyCase => write!(f, "`case` expression with no alternatives"),
+            UndefinedRecord { ref fields } => write!(
+                f,
+                "{}",
+                message!(
+                    messages::UNDEFINED_RECORD,
+                    fields = fields.iter().join(", ")
+                )
+            ),
+            EmptyCase => write!(f, "{}", message!(messages::EMPTY_CASE)),
+            ExprTooDeeplyNested => write!(f, "{}", message!(messages::EXPR_TOO_DEEPLY_NESTED)),
             Message(ref msg) => write!(f, "{}", msg),
         }
     }
 }
 
+impl<I: fmt::Display + AsRef<str>> Labels for TypeError<I> {
+    fn labels(&self) -> Vec<Label> {
+        match *self {
+            TypeError::RecordFieldMismatch {
+                ref alias,
+                definition: Some(span),
+                ..
+            } => vec![Label::new(
+                span,
+                message!(messages::ALIAS_DEFINED_HERE, alias = alias).to_string(),
+            )],
+            TypeError::DuplicateTypeDefinition(_, Some(span)) => vec![Label::new(
+                span,
+                message!(messages::PREVIOUS_DEFINITION).to_string(),
+            )],
+            TypeError::DuplicateField(_, span) => vec![Label::new(
+                span,
+                message!(messages::FIELD_FIRST_DEFINED).to_string(),
+            )],
+            TypeError::Unification(_, _, ref errors, ref instantiations) => errors
+                .iter()
+                .flat_map(|err| err.labels())
+                .chain(instantiations.iter().map(|instantiation| {
+                    Label::new(
+                        instantiation.span,
+                        message!(
+                            messages::INSTANTIATED_HERE,
+                            generic = instantiation.generic,
+                            typ = instantiation.typ
+                        ).to_string(),
+                    )
+                }))
+                .collect(),
+            TypeError::KindError(ref err) => err.labels(),
+            TypeError::Rename(ref err) => err.labels(),
+            _ => Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Help {
     UndefinedFlatMapInDo,
+    /// A name similar to the undefined name which does exist
+    DidYouMean(String),
 }
 
 impl fmt::Display for Help {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            Help::UndefinedFlatMapInDo => write!(
-                f,
-                "Try bringing the `flat_map` function found in the `Monad`\
-                 instance for your type into scope"
-            ),
+            Help::UndefinedFlatMapInDo => {
+                write!(f, "{}", message!(messages::UNDEFINED_FLAT_MAP_IN_DO))
+            }
+            Help::DidYouMean(ref name) => {
+                write!(f, "{}", message!(messages::DID_YOU_MEAN, name = name))
+            }
         }
     }
 }
@@ -268,6 +390,21 @@ impl<'a> TypeEnv for Environment<'a> {
             .map(|t| ((t.1).0.clone(), (t.1).1.typ().into_owned()))
             .or_else(|| self.environment.find_record(fields, selector))
     }
+
+    fn value_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.stack.iter().map(|(id, _)| id.declared_name()).collect();
+        names.extend(self.environment.value_names());
+        names
+    }
+
+    fn type_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.stack_types
+            .iter()
+            .map(|(id, _)| id.declared_name())
+            .collect();
+        names.extend(self.environment.type_names());
+        names
+    }
 }
 
 impl<'a> PrimitiveEnv for Environment<'a> {
@@ -292,6 +429,9 @@ pub struct Typecheck<'a> {
     /// Mapping from the fresh symbol generated during typechecking to the symbol that was assigned
     /// during typechecking
     original_symbols: ScopedMap<Symbol, Symbol>,
+    /// Where the types in `stack_types` were defined (types from the global environment are not
+    /// included)
+    type_definitions: ScopedMap<Symbol, Span<BytePos>>,
     subs: Substitution<ArcType>,
     named_variables: FnvMap<Symbol, ArcType>,
     errors: Errors<SpannedTypeError<Symbol>>,
@@ -299,6 +439,24 @@ pub struct Typecheck<'a> {
     type_variables: ScopedMap<Symbol, ArcType>,
     type_cache: TypeCache<Symbol, ArcType>,
     kind_cache: KindCache,
+    warning_settings: WarningSettings,
+    /// Warnings of the last successfully typechecked expression
+    warnings: Warnings,
+    /// The span of the expression which is currently being typechecked
+    expr_span: Span<BytePos>,
+    /// The first type variable which were created while typechecking the current expression
+    expr_variables: u32,
+    /// The generic variables which have been instantiated along with the type variable each one
+    /// were replaced by
+    instantiations: Vec<(Symbol, u32)>,
+    /// Bindings of the `let` being checked which have been reported as depending on their own
+    /// value
+    recursive_values: FnvSet<Symbol>,
+    /// How deeply nested the expression which is currently being typechecked is
+    depth: usize,
+    max_depth: usize,
+    /// The type of each typechecked expression, `None` unless `record_provenance` has been called
+    expr_types: Option<Vec<ExprType>>,
 }
 
 /// Error returned when unsuccessfully typechecking an expression
@@ -314,6 +472,10 @@ impl<'a> Typecheck<'a> {
     ) -> Typecheck<'a> {
         let symbols = SymbolModule::new(module, symbols);
         let kind_cache = KindCache::new();
+        let subs = Substitution::new(kind_cache.typ());
+        // The bindings are recorded so that type errors can explain how generic variables were
+        // instantiated
+        subs.record_provenance(true);
         Typecheck {
             environment: Environment {
                 environment: environment,
@@ -322,26 +484,86 @@ impl<'a> Typecheck<'a> {
             },
             symbols: symbols,
             original_symbols: ScopedMap::new(),
-            subs: Substitution::new(kind_cache.typ()),
+            type_definitions: ScopedMap::new(),
+            subs,
             named_variables: FnvMap::default(),
             errors: Errors::new(),
             type_variables: ScopedMap::new(),
             type_cache: type_cache,
             kind_cache: kind_cache,
+            warning_settings: WarningSettings::default(),
+            warnings: Errors::new(),
+            expr_span: Span::new(0.into(), 0.into()),
+            expr_variables: 0,
+            instantiations: Vec::new(),
+            recursive_values: FnvSet::default(),
+            depth: 0,
+            max_depth: ::DEFAULT_MAX_DEPTH,
+            expr_types: None,
         }
     }
 
+    /// Sets which warnings are reported when typechecking
+    pub fn set_warning_settings(&mut self, settings: WarningSettings) {
+        self.warning_settings = settings;
+    }
+
+    /// Takes the warnings reported while typechecking the last expression
+    pub fn take_warnings(&mut self) -> Warnings {
+        mem::replace(&mut self.warnings, Errors::new())
+    }
+
+    /// Sets how deeply expressions and types may be nested before an error is reported instead of
+    /// checking them
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Makes the typechecker record the type of each expression and which unification bound each
+    /// type variable so that `explain` can be called once an expression has been typechecked
+    pub fn record_provenance(&mut self) {
+        self.subs.record_provenance(true);
+        self.expr_types = Some(Vec::new());
+    }
+
+    /// Explains why the innermost expression which contains `span` has the type it has. Returns
+    /// `None` if provenance were not recorded or if no expression contains `span`.
+    pub fn explain(&self, span: Span<BytePos>) -> Option<Explanation<BytePos>> {
+        self.expr_types
+            .as_ref()
+            .and_then(|expr_types| ::explain::explain(&self.subs, expr_types, span))
+    }
+
     fn error<E>(&mut self, span: Span<BytePos>, error: E) -> ArcType
     where
         E: Into<HelpError<Symbol>>,
     {
+        let mut error = error.into();
+        if error.help.is_none() {
+            error.help = self.suggest(&error.error, None).map(Help::DidYouMean);
+        }
         self.errors.push(Spanned {
             span: span,
-            value: error.into(),
+            value: error,
         });
         self.subs.new_var()
     }
 
+    /// Looks for a variable or type in scope, in the global environment (or in `extra`) with a
+    /// name similar to an undefined name
+    fn suggest<'s, I>(&'s self, error: &TypeError<Symbol>, extra: I) -> Option<String>
+    where
+        I: IntoIterator<Item = &'s str>,
+    {
+        let (name, mut candidates) = match *error {
+            TypeError::UndefinedVariable(ref id) => (id, self.environment.value_names()),
+            TypeError::UndefinedType(ref id) => (id, self.environment.type_names()),
+            _ => return None,
+        };
+        candidates.extend(extra);
+        suggest::closest_match(name.declared_name(), candidates).map(String::from)
+    }
+
     fn bool(&self) -> ArcType {
         self.environment.get_bool().clone()
     }
@@ -378,7 +600,8 @@ impl<'a> Typecheck<'a> {
             None => {
                 // Don't report global variables inserted by the `import!` macro as undefined
                 // (if they don't exist the error will already have been reported by the macro)
-                if id.is_global() {
+                // or bindings which have been reported as depending on their own value
+                if id.is_global() || self.recursive_values.contains(id) {
                     Ok(self.subs.new_var())
                 } else {
                     Err(TypeError::UndefinedVariable(id.clone()))
@@ -456,17 +679,23 @@ impl<'a> Typecheck<'a> {
         self.environment.stack.enter_scope();
         self.environment.stack_types.enter_scope();
         self.original_symbols.enter_scope();
+        self.type_definitions.enter_scope();
     }
 
     fn exit_scope(&mut self) {
         self.environment.stack.exit_scope();
         self.environment.stack_types.exit_scope();
         self.original_symbols.exit_scope();
+        self.type_definitions.exit_scope();
     }
 
     fn generalize_binding(&mut self, level: u32, binding: &mut ValueBinding<Symbol>) {
         self.generalize_type(level, &mut binding.resolved_type);
-        self.generalize_variables(level, &mut binding.args, &mut binding.expr);
+        self.generalize_variables(
+            level,
+            binding.implicit_args.iter_mut().chain(&mut binding.args),
+            &mut binding.expr,
+        );
     }
 
     /// Generalizing updates all variables which are above `level` into "generic variables". A
@@ -483,12 +712,14 @@ impl<'a> Typecheck<'a> {
     /// `let` basically infers that the variables in `id` does not refer to anything outside the
     /// `let` scope and can thus be "generalized" into `a -> a` which is instantiated with a fresh
     /// type variable in the `id 2` call.
-    fn generalize_variables(
+    fn generalize_variables<'i, I>(
         &mut self,
         level: u32,
-        args: &mut [SpannedIdent<Symbol>],
+        args: I,
         expr: &mut SpannedExpr<Symbol>,
-    ) {
+    ) where
+        I: IntoIterator<Item = &'i mut SpannedIdent<Symbol>>,
+    {
         self.type_variables.enter_scope();
 
         // Replaces all type variables with their inferred types
@@ -497,7 +728,7 @@ impl<'a> Typecheck<'a> {
             tc: &'a mut Typecheck<'b>,
         }
 
-        impl<'a, 'b> MutVisitor for ReplaceVisitor<'a, 'b> {
+        impl<'a, 'b, 'ast> MutVisitor<'ast> for ReplaceVisitor<'a, 'b> {
             type Ident = Symbol;
 
             fn visit_typ(&mut self, typ: &mut ArcType) {
@@ -537,11 +768,14 @@ impl<'a> Typecheck<'a> {
             match err.value.error {
                 UndefinedVariable(_)
                 | UndefinedType(_)
-                | DuplicateTypeDefinition(_)
-                | DuplicateField(_)
+                | DuplicateTypeDefinition(..)
+                | DuplicateField(..)
+                | RecordFieldMismatch { .. }
                 | UndefinedRecord { .. }
                 | EmptyCase
+                | ExprTooDeeplyNested
                 | Rename(_)
+                | PatternMatch(_)
                 | KindError(_)
                 | Message(_) => (),
                 NotAFunction(ref mut typ)
@@ -550,9 +784,17 @@ impl<'a> Typecheck<'a> {
                 | InvalidProjection(ref mut typ) => {
                     self.generalize_type(0, typ);
                 }
-                Unification(ref mut expected, ref mut actual, ref mut errors) => {
+                Unification(
+                    ref mut expected,
+                    ref mut actual,
+                    ref mut errors,
+                    ref mut instantiations,
+                ) => {
                     self.generalize_type_without_forall(0, expected);
                     self.generalize_type_without_forall(0, actual);
+                    for instantiation in instantiations {
+                        self.generalize_type_without_forall(0, &mut instantiation.typ);
+                    }
                     for err in errors {
                         match *err {
                             unify::Error::TypeMismatch(ref mut l, ref mut r) => {
@@ -574,7 +816,7 @@ impl<'a> Typecheck<'a> {
                                 }
                             },
                             unify::Error::Other(ref mut err) => {
-                                if let unify_type::TypeError::MissingFields(ref mut typ, _) = *err {
+                                if let unify_type::TypeError::MissingFields(ref mut typ, ..) = *err {
                                     self.generalize_type_without_forall(0, typ);
                                 }
                             }
@@ -598,14 +840,20 @@ impl<'a> Typecheck<'a> {
         expr: &mut SpannedExpr<Symbol>,
         expected_type: Option<&ArcType>,
     ) -> Result<ArcType, Error> {
-        fn tail_expr(e: &mut SpannedExpr<Symbol>) -> &mut SpannedExpr<Symbol> {
+        fn tail_expr<'a, 'ast>(
+            e: &'a mut SpannedExpr<'ast, Symbol>,
+        ) -> &'a mut SpannedExpr<'ast, Symbol> {
             match e.value {
                 Expr::LetBindings(_, ref mut b) | Expr::TypeBindings(_, ref mut b) => tail_expr(b),
                 _ => e,
             }
         }
         self.subs.clear();
+        self.instantiations.clear();
         self.environment.stack.clear();
+        if let Some(ref mut expr_types) = self.expr_types {
+            expr_types.clear();
+        }
 
         let mut typ = self.typecheck_opt(expr, expected_type);
         if let Some(expected) = expected_type {
@@ -624,8 +872,26 @@ impl<'a> Typecheck<'a> {
             self.generalize_type_errors(&mut errors);
             Err(errors)
         } else {
-            match ::rename::rename(&mut self.symbols, &self.environment, expr) {
-                Ok(()) => {
+            let (symbols, environment, settings) =
+                (&mut self.symbols, &self.environment, &self.warning_settings);
+            let renamed = timings::nested(Phase::Rename, || {
+                ::rename::rename(symbols, environment, settings, expr)
+            });
+            match renamed {
+                Ok(warnings) => self.warnings = warnings,
+                Err(errors) => {
+                    for Spanned { span, value } in errors {
+                        self.errors.push(Spanned {
+                            span: span,
+                            value: TypeError::from(value).into(),
+                        });
+                    }
+                    return Err(mem::replace(&mut self.errors, Errors::new()));
+                }
+            }
+            match ::pattern_match::check(&self.environment, expr) {
+                Ok(warnings) => {
+                    self.warnings.extend(warnings);
                     debug!("Typecheck result: {}", typ);
                     Ok(typ)
                 }
@@ -660,10 +926,27 @@ impl<'a> Typecheck<'a> {
         fn moving<T>(t: T) -> T {
             t
         }
+        if self.depth >= self.max_depth {
+            let typ = self.error(expr.span, TypeError::ExprTooDeeplyNested);
+            // Discard the expression so that the passes after typechecking do not recurse into it
+            expr.value = Expr::Error(Some(typ.clone()));
+            return typ;
+        }
+        self.depth += 1;
+        let outer_span = self.expr_span;
+        let outer_variables = self.expr_variables;
+        // The spans of the expressions in this "tailcall" loop which all have the returned type,
+        // along with the first variable created while checking them
+        let mut spans = Vec::new();
         // How many scopes that have been entered in this "tailcall" loop
         let mut scope_count = 0;
         let returned_type;
         loop {
+            self.expr_span = expr.span;
+            self.expr_variables = self.subs.var_id();
+            if self.expr_types.is_some() {
+                spans.push((expr.span, self.subs.var_id()));
+            }
             let expected_type = expected_type.map(|t| self.skolemize(t));
             let mut expected_type = expected_type.as_ref();
             match self.typecheck_(expr, &mut expected_type) {
@@ -677,7 +960,7 @@ impl<'a> Typecheck<'a> {
                                 | Expr::Do(Do {
                                     body: ref mut new_expr,
                                     ..
-                                }) => new_expr,
+                                }) => &mut **new_expr,
                                 _ => ice!("Only Let and Type expressions can tailcall"),
                             };
                             scope_count += 1;
@@ -695,11 +978,7 @@ impl<'a> Typecheck<'a> {
                     }
                 }
                 Err(err) => {
-                    returned_type = self.subs.new_var();
-                    self.errors.push(Spanned {
-                        span: expr_check_span(expr),
-                        value: err.into(),
-                    });
+                    returned_type = self.error(expr_check_span(expr), err);
                     break;
                 }
             }
@@ -707,6 +986,17 @@ impl<'a> Typecheck<'a> {
         for _ in 0..scope_count {
             self.exit_scope();
         }
+        if let Some(ref mut expr_types) = self.expr_types {
+            let end = self.subs.var_id();
+            expr_types.extend(spans.into_iter().map(|(span, start)| ExprType {
+                span,
+                typ: returned_type.clone(),
+                variables: start..end,
+            }));
+        }
+        self.expr_span = outer_span;
+        self.expr_variables = outer_variables;
+        self.depth -= 1;
         returned_type
     }
 
@@ -718,13 +1008,7 @@ impl<'a> Typecheck<'a> {
         expected_type: &mut Option<&ArcType<Symbol>>,
     ) -> Result<TailCall, TypeError<Symbol>> {
         match expr.value {
-            Expr::Ident(ref mut id) => {
-                if let Some(new) = self.original_symbols.get(&id.name) {
-                    id.name = new.clone();
-                }
-                id.typ = self.find(&id.name)?;
-                Ok(TailCall::Type(id.typ.clone()))
-            }
+            Expr::Ident(_) | Expr::ImplicitApp(..) => self.typecheck_ident(expr),
             Expr::Literal(ref lit) => Ok(TailCall::Type(match *lit {
                 Literal::Int(_) => self.type_cache.int(),
                 Literal::Byte(_) => self.type_cache.byte(),
@@ -734,7 +1018,7 @@ impl<'a> Typecheck<'a> {
             })),
             Expr::App(ref mut func, ref mut args) => {
                 let func_type = self.infer_expr(func);
-                self.typecheck_application(func_type, args)
+                self.typecheck_application(func_type, args.iter_mut())
             }
             Expr::IfElse(ref mut pred, ref mut if_true, ref mut if_false) => {
                 let bool_type = self.bool();
@@ -751,29 +1035,10 @@ impl<'a> Typecheck<'a> {
                 self.unify(&true_type, false_type).map(TailCall::Type)
             }
             Expr::Infix(ref mut lhs, ref mut op, ref mut rhs) => {
-                let op_name = String::from(self.symbols.string(&op.value.name));
-                let func_type = if op_name.starts_with('#') {
-                    // Handle primitives
-                    let op_type = op_name.trim_matches(|c: char| !c.is_alphabetic());
-                    let builtin_type = op_type.parse().map_err(|_| {
-                        TypeError::Message("Invalid builtin type for operator".to_string())
-                    })?;
-                    let prim_type = self.type_cache.builtin_type(builtin_type);
-                    let return_type = match &op_name[1 + op_type.len()..] {
-                        "+" | "-" | "*" | "/" => prim_type.clone(),
-                        "==" | "<" => self.bool(),
-                        _ => return Err(TypeError::UndefinedVariable(op.value.name.clone())),
-                    };
-                    self.type_cache.function(
-                        vec![prim_type.clone(), prim_type.clone()],
-                        return_type.clone(),
-                    )
-                } else {
-                    match &*op_name {
-                        "&&" | "||" => self.type_cache
-                            .function(vec![self.bool(), self.bool()], self.bool()),
-                        _ => self.find(&op.value.name)?,
-                    }
+                // Report an unknown operator but still check the operands
+                let func_type = match self.infix_type(&op.value.name) {
+                    Ok(func_type) => func_type,
+                    Err(err) => self.error(op.span, err),
                 };
 
                 op.value.typ = func_type.clone();
@@ -821,7 +1086,7 @@ impl<'a> Typecheck<'a> {
                     self.exit_scope();
                     // All alternatives must unify to the same type
                     if let Some(ref expected) = expected_alt_type {
-                        alt_type = self.unify(expected, alt_type)?;
+                        alt_type = self.unify_span(expr_check_span(&alt.expr), expected, alt_type);
                     }
                     expected_alt_type = Some(alt_type);
                 }
@@ -830,10 +1095,10 @@ impl<'a> Typecheck<'a> {
                     .map(TailCall::Type)
             }
             Expr::LetBindings(ref mut bindings, _) => {
-                self.typecheck_bindings(bindings)?;
+                self.typecheck_bindings(bindings);
                 Ok(TailCall::TailCall)
             }
-            Expr::Projection(ref mut expr, ref field_id, ref mut ast_field_typ) => {
+            Expr::Projection(ref mut expr, ref field_id, ref mut ast_field_typ, ref mut args) => {
                 let mut expr_typ = self.infer_expr(&mut **expr);
                 debug!(
                     "Projection {} . {:?}",
@@ -862,7 +1127,14 @@ impl<'a> Typecheck<'a> {
                             .find(|field| field.name.name_eq(field_id))
                             .map(|field| field.typ.clone());
                         *ast_field_typ = match field_type {
-                            Some(typ) => self.new_skolem_scope(&typ),
+                            Some(typ) => {
+                                let overloads = overloads(&record, field_id);
+                                if overloads.is_empty() {
+                                    self.new_skolem_scope(&typ)
+                                } else {
+                                    self.overloaded_field_type(typ, &overloads)
+                                }
+                            }
                             None => {
                                 // FIXME As the polymorphic `record_type` do not have the type
                                 // fields which `typ` this unification is only done after we
@@ -876,14 +1148,19 @@ impl<'a> Typecheck<'a> {
                                 field_var
                             }
                         };
-                        Ok(TailCall::Type(ast_field_typ.clone()))
+                        args.clear();
+                        if ast_field_typ.remove_forall().as_implicit_function().is_none() {
+                            return Ok(TailCall::Type(ast_field_typ.clone()));
+                        }
+                        *ast_field_typ = self.instantiate_generics(ast_field_typ);
+                        Ok(TailCall::Type(implicit_args(field_id, ast_field_typ, args)))
                     }
                     _ => Err(TypeError::InvalidProjection(record)),
                 }
             }
             Expr::Array(ref mut array) => {
                 let mut expected_type = self.subs.new_var();
-                for expr in &mut array.exprs {
+                for expr in array.exprs.iter_mut() {
                     let typ = self.typecheck(expr, &expected_type);
                     expected_type = self.unify_span(expr.span, &expected_type, typ);
                 }
@@ -891,14 +1168,29 @@ impl<'a> Typecheck<'a> {
                 Ok(TailCall::Type(array.typ.clone()))
             }
             Expr::Lambda(ref mut lambda) => {
-                let loc = format!("{}.lambda:{}", self.symbols.module(), expr.span.start);
-                lambda.id.name = self.symbols.symbol(loc);
+                let prefix = format!("{}.lambda", self.symbols.module());
+                lambda.id.name = self.symbols.fresh_symbol(&prefix);
+                let function_type = match *expected_type {
+                    Some(expected_type) => {
+                        // An alias of a higher rank type (`type Id = forall a . a -> a`) must be
+                        // skolemized before `level` so its variables are not generalized
+                        match forall_alias(&self.environment, expected_type) {
+                            Some(typ) => {
+                                let typ = self.new_skolem_scope(&typ);
+                                self.skolemize(&typ)
+                            }
+                            None => expected_type.clone(),
+                        }
+                    }
+                    None => self.subs.new_var(),
+                };
                 let level = self.subs.var_id();
-                let function_type = expected_type
-                    .cloned()
-                    .unwrap_or_else(|| self.subs.new_var());
-                let mut typ =
-                    self.typecheck_lambda(function_type, &mut lambda.args, &mut lambda.body);
+                let mut typ = self.typecheck_lambda(
+                    function_type,
+                    &mut [],
+                    &mut lambda.args,
+                    &mut lambda.body,
+                );
                 self.generalize_type(level, &mut typ);
                 lambda.id.typ = typ.clone();
                 Ok(TailCall::Type(typ))
@@ -913,6 +1205,12 @@ impl<'a> Typecheck<'a> {
                 exprs: ref mut fields,
                 ref mut base,
             } => {
+                let expected_alias = expected_type.and_then(|expected_type| {
+                    expected_type
+                        .remove_forall()
+                        .alias_ident()
+                        .map(|alias| (alias.clone(), expected_type.clone()))
+                });
                 let expected_type = expected_type.map(|expected_type| {
                     let typ = resolve::remove_aliases_cow(&self.environment, expected_type);
                     self.new_skolem_scope(&typ)
@@ -921,20 +1219,26 @@ impl<'a> Typecheck<'a> {
 
                 let mut new_types: Vec<Field<_, _>> = Vec::with_capacity(types.len());
 
-                let mut duplicated_fields = FnvSet::default();
-                for field in types {
+                let mut duplicated_fields = FnvMap::default();
+                for field in types.iter_mut() {
                     if let Some(ref mut typ) = field.value {
                         *typ = self.create_unifiable_signature(typ)
                             .unwrap_or_else(|| typ.clone());
                     }
-                    let alias = self.find_type_info(&field.name.value)?.clone();
+                    let alias = match self.find_type_info(&field.name.value).map(|a| a.clone()) {
+                        Ok(alias) => alias,
+                        Err(err) => {
+                            self.error(field.name.span, err);
+                            continue;
+                        }
+                    };
                     if self.error_on_duplicated_field(&mut duplicated_fields, field.name.clone()) {
                         new_types.push(Field::new(field.name.value.clone(), alias));
                     }
                 }
 
                 let mut new_fields: Vec<Field<_, _>> = Vec::with_capacity(fields.len());
-                for field in fields {
+                for field in fields.iter_mut() {
                     let level = self.subs.var_id();
 
                     let name = &field.name.value;
@@ -954,7 +1258,7 @@ impl<'a> Typecheck<'a> {
                             new_skolem_scope(&self.subs, &FnvMap::default(), &typ)
                         }
                         None => {
-                            let typ = self.find(&field.name.value)?;
+                            let typ = self.find_at(field.name.span, &field.name.value);
                             match expected_field_type {
                                 Some(expected_field_type) => {
                                     self.subsumes(field.name.span, level, &expected_field_type, typ)
@@ -978,17 +1282,55 @@ impl<'a> Typecheck<'a> {
                     new_types.extend(
                         base_type
                             .type_field_iter()
-                            .filter(|field| !duplicated_fields.contains(field.name.declared_name()))
+                            .filter(|field| !duplicated_fields.contains_key(field.name.declared_name()))
                             .cloned(),
                     );
                     new_fields.extend(
                         base_type
                             .row_iter()
-                            .filter(|field| !duplicated_fields.contains(field.name.declared_name()))
+                            .filter(|field| !duplicated_fields.contains_key(field.name.declared_name()))
                             .cloned(),
                     );
                 }
 
+                if let (Some((alias, alias_type)), Some(expected_type)) =
+                    (expected_alias, expected_type)
+                {
+                    if base.is_none() && expected_type.is_non_polymorphic_record() {
+                        let missing: Vec<_> = expected_type
+                            .row_iter()
+                            .filter(|field| {
+                                !new_fields.iter().any(|new| new.name.name_eq(&field.name))
+                            })
+                            .map(|field| (field.name.clone(), field.typ.clone()))
+                            .collect();
+                        let extra: Vec<_> = new_fields
+                            .iter()
+                            .filter(|new| {
+                                !expected_type
+                                    .row_iter()
+                                    .any(|field| field.name.name_eq(&new.name))
+                            })
+                            .map(|field| (field.name.clone(), field.typ.clone()))
+                            .collect();
+                        if !missing.is_empty() || !extra.is_empty() {
+                            let definition = self.type_definitions.get(&alias).cloned();
+                            self.error(
+                                expr.span,
+                                TypeError::RecordFieldMismatch {
+                                    alias,
+                                    missing,
+                                    extra,
+                                    definition,
+                                },
+                            );
+                            // Use the expected type so the mismatch is only reported once
+                            *typ = alias_type.clone();
+                            return Ok(TailCall::Type(alias_type));
+                        }
+                    }
+                }
+
                 let record_fields = new_fields
                     .iter()
                     .map(|f| f.name.clone())
@@ -1081,48 +1423,135 @@ impl<'a> Typecheck<'a> {
         }
     }
 
-    fn typecheck_application<'e, I>(
+    fn infix_type(&mut self, op: &Symbol) -> TcResult<ArcType> {
+        let op_name = String::from(self.symbols.string(op));
+        Ok(if op_name.starts_with('#') {
+            // Handle primitives
+            let op_type = op_name.trim_matches(|c: char| !c.is_alphabetic());
+            let builtin_type = op_type.parse().map_err(|_| {
+                TypeError::Message(message!(messages::INVALID_BUILTIN_OPERATOR).to_string())
+            })?;
+            let prim_type = self.type_cache.builtin_type(builtin_type);
+            let return_type = match &op_name[1 + op_type.len()..] {
+                "+" | "-" | "*" | "/" => prim_type.clone(),
+                "==" | "<" => self.bool(),
+                _ => return Err(TypeError::UndefinedVariable(op.clone())),
+            };
+            self.type_cache
+                .function(vec![prim_type.clone(), prim_type.clone()], return_type.clone())
+        } else {
+            match &*op_name {
+                "&&" | "||" => self.type_cache
+                    .function(vec![self.bool(), self.bool()], self.bool()),
+                _ => self.find(op)?,
+            }
+        })
+    }
+
+    fn typecheck_application<'e, 'ast: 'e, I>(
         &mut self,
         mut func_type: ArcType,
         args: I,
     ) -> Result<TailCall, TypeError<Symbol>>
     where
-        I: IntoIterator<Item = &'e mut SpannedExpr<Symbol>>,
+        I: IntoIterator<Item = &'e mut SpannedExpr<'ast, Symbol>>,
     {
         func_type = self.new_skolem_scope(&func_type);
-        for arg in args {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
             let f = self.type_cache
                 .function(once(self.subs.new_var()), self.subs.new_var());
             func_type = self.instantiate_generics(&func_type);
-            func_type = self.unify(&f, func_type)?;
+            let result = self.unify(&f, func_type).and_then(|func_type| {
+                match func_type.as_function() {
+                    Some((arg_ty, ret_ty)) => Ok((arg_ty.clone(), ret_ty.clone())),
+                    None => Err(TypeError::NotAFunction(func_type.clone())),
+                }
+            });
 
-            func_type = match func_type.as_function() {
-                Some((arg_ty, ret_ty)) => {
-                    let actual = self.typecheck(arg, arg_ty);
+            func_type = match result {
+                Ok((arg_ty, ret_ty)) => {
+                    let actual = self.typecheck(arg, &arg_ty);
                     let actual = self.instantiate_generics(&actual);
 
                     let level = self.subs.var_id();
-                    self.subsumes(expr_check_span(arg), level, arg_ty, actual);
+                    self.subsumes(expr_check_span(arg), level, &arg_ty, actual);
 
-                    ret_ty.clone()
+                    ret_ty
+                }
+                Err(err) => {
+                    // Still check the arguments so that any errors in them are reported as well
+                    for arg in once(arg).chain(args) {
+                        self.infer_expr(arg);
+                    }
+                    return Err(err);
                 }
-                None => return Err(TypeError::NotAFunction(func_type.clone())),
             };
         }
         Ok(TailCall::Type(func_type))
     }
 
+    /// Checks an identifier. If the type of the identifier takes implicit arguments,
+    /// `[a] -> b`, the identifier is replaced by `Expr::ImplicitApp` which holds one argument for
+    /// each implicit argument in the type. The values of those arguments are selected by
+    /// `rename` once the types are known.
+    fn typecheck_ident(
+        &mut self,
+        expr: &mut SpannedExpr<Symbol>,
+    ) -> Result<TailCall, TypeError<Symbol>> {
+        let mut id = match expr.value {
+            Expr::Ident(ref id) | Expr::ImplicitApp(ref id, _) => id.clone(),
+            _ => ice!("Expected an identifier"),
+        };
+        if let Some(new) = self.original_symbols.get(&id.name) {
+            id.name = new.clone();
+        }
+        id.typ = self.find(&id.name)?;
+        if id.typ.remove_forall().as_implicit_function().is_none() {
+            let typ = id.typ.clone();
+            expr.value = Expr::Ident(id);
+            return Ok(TailCall::Type(typ));
+        }
+
+        id.typ = self.instantiate_generics(&id.typ);
+        let mut args = Vec::new();
+        let typ = implicit_args(&id.name, &id.typ, &mut args);
+        expr.value = Expr::ImplicitApp(id, args);
+        Ok(TailCall::Type(typ))
+    }
+
     fn typecheck_lambda(
         &mut self,
         mut function_type: ArcType,
+        implicit_args: &mut [SpannedIdent<Symbol>],
         args: &mut [SpannedIdent<Symbol>],
         body: &mut SpannedExpr<Symbol>,
     ) -> ArcType {
         self.enter_scope();
         function_type = self.skolemize(&function_type);
+        let mut implicit_types = Vec::new();
+        for arg in implicit_args {
+            function_type = self.subs.real(&function_type).clone();
+            let (arg_type, ret) = match function_type.as_implicit_function() {
+                Some((arg_type, ret)) => (arg_type.clone(), ret.clone()),
+                None => {
+                    let arg_type = self.subs.new_var();
+                    let ret = self.subs.new_var();
+                    let f = Type::implicit_function(vec![arg_type.clone()], ret.clone());
+                    self.unify_span(arg.span, &function_type, f);
+                    (arg_type, ret)
+                }
+            };
+            arg.value.typ = arg_type.clone();
+            implicit_types.push(arg_type.clone());
+            self.stack_var(arg.value.name.clone(), arg_type);
+            function_type = ret;
+        }
         let mut arg_types = Vec::new();
         let body_type = {
-            let mut iter1 = function_arg_iter(self, args.last().unwrap().span, function_type);
+            // A binding may take only implicit arguments, `let f ?x = ...`
+            let span = args.last().map_or(body.span, |arg| arg.span);
+            let mut iter1 = function_arg_iter(self, span, function_type);
             for arg in args {
                 let arg_type = match iter1.next() {
                     Some(arg_type) => arg_type,
@@ -1132,13 +1561,22 @@ impl<'a> Typecheck<'a> {
 
                 arg.typ = arg_type;
                 arg_types.push(arg.typ.clone());
-                iter1.tc.stack_var(arg.name.clone(), arg.typ.clone());
+                // An argument with a higher rank type, `(forall a . a -> a) -> Int`, must be
+                // instantiated with fresh variables each time it is used
+                let forall = forall_alias(&iter1.tc.environment, &arg.typ);
+                let stack_type = match forall.as_ref().map(|typ| &**typ) {
+                    Some(&Type::Forall(ref params, ref typ, _)) => {
+                        Type::forall(params.clone(), typ.clone())
+                    }
+                    _ => arg.typ.clone(),
+                };
+                iter1.tc.stack_var(arg.name.clone(), stack_type);
             }
             iter1.typ
         };
         let body_type = self.typecheck(body, &body_type);
         self.exit_scope();
-        self.type_cache.function(arg_types, body_type)
+        Type::implicit_function(implicit_types, self.type_cache.function(arg_types, body_type))
     }
 
     fn typecheck_pattern(
@@ -1159,8 +1597,30 @@ impl<'a> Typecheck<'a> {
                 if let Some(new) = self.original_symbols.get(&id.name) {
                     id.name = new.clone();
                 }
+                // Matching on an open variant, `[| A Int | r |]`, uses the constructor from the
+                // variant as the constructor's declared type would close the variant
+                let open_ctor_type = match *match_type {
+                    Type::Variant(ref row) => {
+                        let mut iter = row.row_iter();
+                        let field = iter.by_ref()
+                            .find(|field| field.name.name_eq(&id.name))
+                            .map(|field| {
+                                let args = types::arg_iter(&field.typ).cloned().collect();
+                                Type::function(args, match_type.clone())
+                            });
+                        for _ in iter.by_ref() {}
+                        match **iter.current_type() {
+                            Type::EmptyRow => None,
+                            _ => field,
+                        }
+                    }
+                    _ => None,
+                };
                 // Find the enum constructor and return the types for its arguments
-                let ctor_type = self.find_at(span, &id.name);
+                let ctor_type = match open_ctor_type {
+                    Some(ctor_type) => ctor_type,
+                    None => self.find_at(span, &id.name),
+                };
                 id.typ = ctor_type.clone();
                 let return_type = match self.typecheck_pattern_rec(args, ctor_type) {
                     Ok(return_type) => return_type,
@@ -1179,7 +1639,7 @@ impl<'a> Typecheck<'a> {
 
                 let mut pattern_fields = Vec::with_capacity(associated_types.len() + fields.len());
 
-                let mut duplicated_fields = FnvSet::default();
+                let mut duplicated_fields = FnvMap::default();
                 {
                     let all_fields = associated_types
                         .iter()
@@ -1366,12 +1826,17 @@ impl<'a> Typecheck<'a> {
         }
     }
 
-    fn typecheck_bindings(&mut self, bindings: &mut [ValueBinding<Symbol>]) -> TcResult<()> {
+    fn typecheck_bindings(&mut self, bindings: &mut [ValueBinding<Symbol>]) {
         self.enter_scope();
         self.type_variables.enter_scope();
         let level = self.subs.var_id();
 
-        let is_recursive = bindings.iter().all(|bind| !bind.args.is_empty());
+        let is_recursive = bindings.iter().all(|bind| bind.is_function());
+        let recursive_values = if is_recursive {
+            Vec::new()
+        } else {
+            self.check_value_cycle(bindings)
+        };
         // When the definitions are allowed to be mutually recursive
         if is_recursive {
             for bind in bindings.iter_mut() {
@@ -1383,10 +1848,10 @@ impl<'a> Typecheck<'a> {
                     {
                         self.error(
                             bind.name.span,
-                            TypeError::Message(format!(
-                                "Unexpected type constructor `{}`",
-                                id.name
-                            )),
+                            TypeError::Message(
+                                message!(messages::UNEXPECTED_TYPE_CONSTRUCTOR, name = id.name)
+                                    .to_string(),
+                            ),
                         );
                     }
                     _ => (),
@@ -1423,7 +1888,7 @@ impl<'a> Typecheck<'a> {
 
             // Functions which are declared as `let f x = ...` are allowed to be self
             // recursive
-            let mut typ = if bind.args.is_empty() {
+            let mut typ = if !bind.is_function() {
                 if let Some(ref mut typ) = bind.typ {
                     self.kindcheck(typ);
 
@@ -1441,9 +1906,18 @@ impl<'a> Typecheck<'a> {
             } else {
                 let typ = self.new_skolem_scope_signature(&bind.resolved_type);
                 let function_type = self.skolemize(&typ);
-                self.typecheck_lambda(function_type, &mut bind.args, &mut bind.expr)
+                self.typecheck_lambda(
+                    function_type,
+                    &mut bind.implicit_args,
+                    &mut bind.args,
+                    &mut bind.expr,
+                )
             };
 
+            if let Some(global) = bind.intrinsic() {
+                self.typecheck_intrinsic(bind.expr.span, global, &typ);
+            }
+
             debug!("let {:?} : {}", bind.name, typ);
 
             if !is_recursive {
@@ -1460,6 +1934,9 @@ impl<'a> Typecheck<'a> {
 
             self.type_variables.exit_scope();
         }
+        for name in &recursive_values {
+            self.recursive_values.remove(name);
+        }
         // Once all variables inside the let has been unified we can quantify them
         debug!("Generalize at {}", level);
         for bind in bindings.iter_mut() {
@@ -1470,7 +1947,43 @@ impl<'a> Typecheck<'a> {
         }
         debug!("Typecheck `in`");
         self.type_variables.exit_scope();
-        Ok(())
+    }
+
+    /// Checks that the type of a binding marked with `#[intrinsic(name)]` agrees with the type of
+    /// the `@name` global which the binding is compiled to
+    fn typecheck_intrinsic(&mut self, span: Span<BytePos>, global: &str, typ: &ArcType) {
+        let intrinsic_type = self.find_at(span, &Symbol::from(format!("@{}", global)));
+        self.unify_span(span, &intrinsic_type, typ.clone());
+    }
+
+    /// Reports the bindings of a `let` which is not recursive and whose values depend on
+    /// themselves. Without this `let x = x + 1` would be reported as using an undefined variable.
+    /// Returns the names of the reported bindings, which are not reported as undefined until the
+    /// `let` has been checked.
+    fn check_value_cycle(&mut self, bindings: &[ValueBinding<Symbol>]) -> Vec<Symbol> {
+        let cycle = {
+            let environment = &self.environment;
+            ::rename::value_cycle(bindings, |id| environment.find_type(id).is_some())
+        };
+        let cycle = match cycle {
+            Some(cycle) => cycle,
+            None => return Vec::new(),
+        };
+        let names: Vec<_> = cycle.iter().map(|&(_, ref name)| name.clone()).collect();
+        self.recursive_values.extend(names.iter().cloned());
+
+        let cycle: Vec<_> = cycle
+            .into_iter()
+            .map(|(i, name)| {
+                (String::from(self.symbols.string(&name)), bindings[i].name.span)
+            })
+            .collect();
+        let span = cycle[0].1;
+        self.error(
+            span,
+            TypeError::Rename(RenameError::RecursiveValue { cycle }),
+        );
+        names
     }
 
     fn typecheck_type_bindings(
@@ -1492,6 +2005,7 @@ impl<'a> Typecheck<'a> {
         {
             let mut check =
                 KindCheck::new(&self.environment, &self.symbols, self.kind_cache.clone());
+            check.set_max_depth(self.max_depth);
 
             // Setup kind variables for all holes and insert the types in the
             // the type expression into the kindcheck environment
@@ -1500,41 +2014,37 @@ impl<'a> Typecheck<'a> {
                 // Test a b : 2 -> 1 -> Type
                 // and bind the same variables to the arguments of the type binding
                 // ('a' and 'b' in the example)
-                let mut id_kind = check.type_kind();
-                for generic in bind.alias
-                    .value
-                    .unresolved_type_mut()
-                    .params_mut()
-                    .iter_mut()
-                    .rev()
-                {
-                    check.instantiate_kinds(&mut generic.kind);
-                    id_kind = Kind::function(generic.kind.clone(), id_kind);
-                }
+                let id_kind =
+                    check.instantiate_params(bind.alias.value.unresolved_type_mut().params_mut());
                 check.add_local(bind.alias.value.name.clone(), id_kind);
             }
 
             // Kindcheck all the types in the environment
             for bind in &mut *bindings {
-                check.set_variables(bind.alias.value.params());
+                check.set_variables(bind.alias.value.params(), bind.name.span);
 
                 let typ = bind.alias
                     .value
                     .unresolved_type_mut()
                     .remove_single_forall();
                 if let Err(err) = check.kindcheck_type(typ) {
-                    self.errors
-                        .push(pos::spanned(err.span, TypeError::from(err.value).into()));
+                    discard_too_deep_type(&err, typ);
+                    let error = TypeError::from(err.value);
+                    let help = self.suggest(&error, check.local_names())
+                        .map(Help::DidYouMean);
+                    self.errors.push(pos::spanned(
+                        err.span,
+                        ::base::error::Help { error, help },
+                    ));
                 }
             }
 
             // All kinds are now inferred so replace the kinds store in the AST
             for bind in &mut *bindings {
                 let typ = bind.alias.value.unresolved_type_mut();
+                // Generalize the parameters first as `finalize_type` defaults any unresolved kind
+                check.generalize_params(typ.params_mut());
                 check.finalize_type(typ);
-                for arg in typ.params_mut() {
-                    *arg = check.finalize_generic(arg);
-                }
             }
         }
 
@@ -1562,10 +2072,18 @@ impl<'a> Typecheck<'a> {
                     .map(|param| (param.id.clone(), alias.unresolved_type().clone())),
             );
 
-            let replacement =
-                self.create_unifiable_signature2(alias.unresolved_type().remove_forall());
+            // Only the `Forall` holding the parameters is removed, a `forall` in the body of the
+            // alias (`type Id = forall a . a -> a`) must be kept
+            let replacement = self.create_unifiable_signature2(alias.aliased_type());
             if let Some(typ) = replacement {
-                *alias.unresolved_type_mut() = Type::forall(alias.params().to_owned(), typ);
+                let params = match **alias.unresolved_type() {
+                    Type::Forall(ref params, _, _) => Some(params.clone()),
+                    _ => None,
+                };
+                *alias.unresolved_type_mut() = match params {
+                    Some(params) => ArcType::from(Type::Forall(params, typ, None)),
+                    None => typ,
+                };
             }
             resolved_aliases.push(alias);
         }
@@ -1578,12 +2096,19 @@ impl<'a> Typecheck<'a> {
         // Finally insert the declared types into the global scope
         for bind in bindings {
             if self.environment.stack_types.get(&bind.name.value).is_some() {
+                let previous = self.type_definitions.get(&bind.name.value).cloned();
                 self.errors.push(Spanned {
                     span: expr_check_span(expr),
-                    // TODO Help to the position of the other field
-                    value: TypeError::DuplicateTypeDefinition(bind.name.value.clone()).into(),
+                    value: TypeError::DuplicateTypeDefinition(bind.name.value.clone(), previous)
+                        .into(),
                 });
             } else {
+                // Both the local and the global name of the type may be referred to
+                let alias = bind.finalized_alias.as_ref().unwrap();
+                self.type_definitions
+                    .insert(alias.name.clone(), bind.name.span);
+                self.type_definitions
+                    .insert(bind.name.value.clone(), bind.name.span);
                 self.stack_type(
                     bind.name.value.clone(),
                     &bind.finalized_alias.as_ref().unwrap(),
@@ -1596,11 +2121,12 @@ impl<'a> Typecheck<'a> {
         let result = {
             let mut check =
                 KindCheck::new(&self.environment, &self.symbols, self.kind_cache.clone());
+            check.set_max_depth(self.max_depth);
             check.kindcheck_type(typ)
         };
         if let Err(err) = result {
-            self.errors
-                .push(pos::spanned(err.span, TypeError::from(err.value).into()));
+            discard_too_deep_type(&err, typ);
+            self.error(err.span, TypeError::from(err.value));
         }
     }
 
@@ -1614,6 +2140,10 @@ impl<'a> Typecheck<'a> {
                 // Inside records variables are bound implicitly to the closest field
                 // so variables are allowed to be undefined/implicit
             }
+            Type::Forall(ref params, ref typ, _) => {
+                let args: Vec<_> = args.iter().chain(params).cloned().collect();
+                self.check_undefined_variables(&args, typ);
+            }
             _ => {
                 types::walk_move_type_opt(
                     typ,
@@ -1668,9 +2198,13 @@ impl<'a> Typecheck<'a> {
                 with_pattern_types(fields, &record_type, |field_name, binding, field_type| {
                     let mut field_type = field_type.clone();
                     self.generalize_type(level, &mut field_type);
-                    match *binding {
+                    let binding_name = match *binding {
                         Some(ref mut pat) => {
                             self.finish_pattern(level, pat, &field_type);
+                            match pat.value {
+                                Pattern::Ident(ref id) => Some(id.name.clone()),
+                                _ => None,
+                            }
                         }
                         None => {
                             self.environment
@@ -1681,7 +2215,11 @@ impl<'a> Typecheck<'a> {
                             debug!("{}: {}", field_name, field_type);
 
                             self.intersect_type(level, field_name, &field_type);
+                            Some(field_name.clone())
                         }
+                    };
+                    if let Some(binding_name) = binding_name {
+                        self.bind_overloads(level, &record_type, field_name, &binding_name);
                     }
                 });
             }
@@ -1716,8 +2254,44 @@ impl<'a> Typecheck<'a> {
         }
     }
 
+    /// Returns the type of a projection of a field which has `overloads`, the projection is
+    /// resolved to one of the fields once its type is known
+    fn overloaded_field_type(
+        &mut self,
+        field_type: ArcType,
+        overloads: &[Field<Symbol, ArcType>],
+    ) -> ArcType {
+        let level = self.subs.var_id();
+        let mut binding = StackBinding {
+            constraints: FnvMap::default(),
+            typ: field_type,
+        };
+        for overload in overloads {
+            binding = self.intersection(level, &binding, &overload.typ);
+        }
+        new_skolem_scope(&self.subs, &binding.constraints, &binding.typ)
+    }
+
+    /// Binds the overloads of `field` (`field@1`, `field@2`, ...) in `record_type` to `binding` as
+    /// well so that each use of `binding` can refer to any of them
+    fn bind_overloads(
+        &mut self,
+        level: u32,
+        record_type: &ArcType,
+        field: &Symbol,
+        binding: &Symbol,
+    ) {
+        for overload in overloads(record_type, field) {
+            let mut overload_type = overload.typ.clone();
+            self.generalize_type(level, &mut overload_type);
+            debug!("{}: {}", overload.name, overload_type);
+            self.stack_var(binding.clone(), overload_type.clone());
+            self.intersect_type(level, binding, &overload_type);
+        }
+    }
+
     fn intersect_type(&mut self, level: u32, symbol: &Symbol, symbol_type: &ArcType) {
-        let intersection_update = {
+        let existing_binding = {
             let existing_types = self.environment
                 .stack
                 .get_all(symbol)
@@ -1726,102 +2300,112 @@ impl<'a> Typecheck<'a> {
             // It might be possible to lift this restriction but currently it causes problems
             // which I am not sure how to solve
             if existing_types.len() >= 2 {
-                debug!("Looking for intersection `{}`", symbol_type);
-                let existing_binding = &existing_types[existing_types.len() - 2];
-                debug!(
-                    "Intersect `{}`\n{} ∩ {}",
-                    symbol,
-                    self.subs.real(&existing_binding.typ),
-                    self.subs.real(&symbol_type)
-                );
-                let existing_type = new_skolem_scope(
-                    &self.subs,
-                    &existing_binding.constraints,
-                    &existing_binding.typ,
-                );
-
-                let result = {
-                    let mut state = unify_type::State::new(&self.environment, &self.subs);
-                    state.in_alias = true;
-                    unify::intersection(
-                        &self.subs,
-                        self.symbols.symbols(),
-                        state,
-                        &existing_type,
-                        &new_skolem_scope(&self.subs, &FnvMap::default(), &symbol_type),
-                    )
-                };
-
-                // Avoid cloning existing_binding
-                Some((result.0, result.1, existing_type, existing_binding.clone()))
+                Some(existing_types[existing_types.len() - 2].clone())
             } else {
                 None
             }
         };
 
-        if let Some((intersection_constraints, mut typ, existing_type, existing_binding)) =
-            intersection_update
-        {
-            let constraints: FnvMap<_, _> = intersection_constraints
-                .into_iter()
-                .map(|((mut l, mut r), name)| {
-                    let constraints = match *l {
-                        Type::Generic(ref gen) => existing_binding.constraints.get(&gen.id),
-                        Type::Skolem(ref skolem) => existing_binding.constraints.get(&skolem.name),
-                        // Since we call `new_skolem_scope` we may find a variable as the constraint
-                        // but we really need to return the constraints bound by the generic
-                        // instantiating it
-                        Type::Variable(ref constraint_var) => match *existing_type {
-                            Type::Forall(ref params, _, Some(ref vars)) => vars.iter()
-                                .position(|var| match **var {
-                                    Type::Variable(ref var) => var.id == constraint_var.id,
-                                    _ => unreachable!(),
-                                })
-                                .and_then(|i| existing_binding.constraints.get(&params[i].id)),
-                            _ => None,
-                        },
+        if let Some(existing_binding) = existing_binding {
+            debug!("Intersect `{}`", symbol);
+            let intersection = self.intersection(level, &existing_binding, symbol_type);
+            let bind = self.environment.stack.get_mut(symbol).unwrap();
+            *bind = intersection;
+            debug!("Updated {} to `{}`", symbol, bind.typ);
+        }
+    }
+
+    /// Returns the type which a use of an overloaded name has if it may refer to either
+    /// `existing_binding` or a binding of type `symbol_type`
+    fn intersection(
+        &mut self,
+        level: u32,
+        existing_binding: &StackBinding,
+        symbol_type: &ArcType,
+    ) -> StackBinding {
+        debug!("Looking for intersection `{}`", symbol_type);
+        debug!(
+            "{} ∩ {}",
+            self.subs.real(&existing_binding.typ),
+            self.subs.real(&symbol_type)
+        );
+        let existing_type = new_skolem_scope(
+            &self.subs,
+            &existing_binding.constraints,
+            &existing_binding.typ,
+        );
+
+        let (intersection_constraints, mut typ) = {
+            let mut state = unify_type::State::new(&self.environment, &self.subs);
+            state.in_alias = true;
+            unify::intersection(
+                &self.subs,
+                self.symbols.symbols(),
+                state,
+                &existing_type,
+                &new_skolem_scope(&self.subs, &FnvMap::default(), &symbol_type),
+            )
+        };
+
+        let constraints: FnvMap<_, _> = intersection_constraints
+            .into_iter()
+            .map(|((mut l, mut r), name)| {
+                let constraints = match *l {
+                    Type::Generic(ref gen) => existing_binding.constraints.get(&gen.id),
+                    Type::Skolem(ref skolem) => existing_binding.constraints.get(&skolem.name),
+                    // Since we call `new_skolem_scope` we may find a variable as the constraint
+                    // but we really need to return the constraints bound by the generic
+                    // instantiating it
+                    Type::Variable(ref constraint_var) => match *existing_type {
+                        Type::Forall(ref params, _, Some(ref vars)) => vars.iter()
+                            .position(|var| match **var {
+                                Type::Variable(ref var) => var.id == constraint_var.id,
+                                _ => unreachable!(),
+                            })
+                            .and_then(|i| existing_binding.constraints.get(&params[i].id)),
                         _ => None,
-                    };
+                    },
+                    _ => None,
+                };
 
-                    self.generalize_type(level, &mut l);
-                    self.generalize_type(level, &mut r);
+                self.generalize_type(level, &mut l);
+                self.generalize_type(level, &mut r);
 
-                    (
-                        name,
-                        Arc::new(
-                            constraints
-                                .iter()
-                                .flat_map(|x| x.iter())
-                                .cloned()
-                                .chain(Some(r))
-                                .chain(if constraints.is_none() { Some(l) } else { None })
-                                .collect::<Vec<_>>(),
-                        ),
-                    )
-                })
-                .collect();
+                (
+                    name,
+                    Arc::new(
+                        constraints
+                            .iter()
+                            .flat_map(|x| x.iter())
+                            .cloned()
+                            .chain(Some(r))
+                            .chain(if constraints.is_none() { Some(l) } else { None })
+                            .collect::<Vec<_>>(),
+                    ),
+                )
+            })
+            .collect();
 
-            typ = Type::forall(
-                constraints
-                    .iter()
-                    .map(|constraint| {
-                        Generic::new(constraint.0.clone(), constraint.1[0].kind().into_owned())
-                    })
-                    .collect(),
-                typ,
-            );
-            debug!(
-                "Intersect result {}\n\t{}",
-                typ,
-                constraints
-                    .iter()
-                    .map(|t| format!("{}:{}", t.0, t.1.iter().format("\n\t\t")))
-                    .format("\n")
-            );
-            let bind = self.environment.stack.get_mut(symbol).unwrap();
-            bind.constraints = constraints;
-            bind.typ = typ;
-            debug!("Updated {} to `{}`", symbol, bind.typ);
+        typ = Type::forall(
+            constraints
+                .iter()
+                .map(|constraint| {
+                    Generic::new(constraint.0.clone(), constraint.1[0].kind().into_owned())
+                })
+                .collect(),
+            typ,
+        );
+        debug!(
+            "Intersect result {}\n\t{}",
+            typ,
+            constraints
+                .iter()
+                .map(|t| format!("{}:{}", t.0, t.1.iter().format("\n\t\t")))
+                .format("\n")
+        );
+        StackBinding {
+            constraints: constraints,
+            typ: typ,
         }
     }
 
@@ -1901,6 +2485,7 @@ impl<'a> Typecheck<'a> {
                                 typ.clone(),
                                 typ.clone(),
                                 vec![unify::Error::Substitution(err)],
+                                Vec::new(),
                             ),
                         ),
                     };
@@ -2086,6 +2671,28 @@ impl<'a> Typecheck<'a> {
                     })
             }
             Type::Variant(ref row) => {
+                // The constructors of an open variant, `[| A Int | r |]`, return the variant
+                // itself which the parser writes as a hole, give them all the same variable
+                let filled_row = if row.row_iter()
+                    .any(|field| **constructor_return(&field.typ) == Type::Hole)
+                {
+                    let variant_type = self.subs.new_var();
+                    let mut iter = row.row_iter();
+                    let fields = iter.by_ref()
+                        .map(|field| {
+                            let args = types::arg_iter(&field.typ).cloned().collect();
+                            let ret = match **constructor_return(&field.typ) {
+                                Type::Hole => variant_type.clone(),
+                                _ => constructor_return(&field.typ).clone(),
+                            };
+                            Field::new(field.name.clone(), Type::function(args, ret))
+                        })
+                        .collect();
+                    Some(Type::extend_row(vec![], fields, iter.current_type().clone()))
+                } else {
+                    None
+                };
+                let row = filled_row.as_ref().unwrap_or(row);
                 let replacement = types::visit_type_opt(
                     row,
                     &mut types::ControlVisitation(|typ: &ArcType| {
@@ -2100,17 +2707,18 @@ impl<'a> Typecheck<'a> {
                 if iter().any(|opt| opt.is_some()) {
                     // If any of the variants requires a symbol replacement
                     // we create a new type
-                    Some(
-                        self.type_cache.variant(
-                            iter()
-                                .zip(row.row_iter())
-                                .map(|(new, old)| match new {
-                                    Some(new) => Field::new(new.clone(), old.typ.clone()),
-                                    None => old.clone(),
-                                })
-                                .collect(),
-                        ),
-                    )
+                    let mut row_iter = row.row_iter();
+                    let fields = iter()
+                        .zip(row_iter.by_ref())
+                        .map(|(new, old)| match new {
+                            Some(new) => Field::new(new.clone(), old.typ.clone()),
+                            None => old.clone(),
+                        })
+                        .collect();
+                    for _ in row_iter.by_ref() {}
+                    Some(Type::poly_variant(fields, row_iter.current_type().clone()))
+                } else if replacement.is_none() && filled_row.is_some() {
+                    Some(ArcType::from(Type::Variant(row.clone())))
                 } else {
                     replacement
                         .clone()
@@ -2182,14 +2790,17 @@ impl<'a> Typecheck<'a> {
         debug!("Merge {} : {}", expected, actual);
         let expected = self.skolemize(&expected);
         let state = unify_type::State::new(&self.environment, &self.subs);
-        match unify_type::subsumes(
+        let outer_origin = self.set_origin(span, &expected, &actual);
+        let result = unify_type::subsumes(
             &self.subs,
             &mut self.type_variables,
             level,
             state,
             &expected,
             &actual,
-        ) {
+        );
+        self.subs.set_origin(outer_origin);
+        match result {
             Ok(typ) => self.subs.set_type(typ),
             Err(errors) => {
                 let mut expected = expected.clone();
@@ -2199,7 +2810,9 @@ impl<'a> Typecheck<'a> {
                     "Error '{:?}' between:\n>> {}\n>> {}",
                     errors, expected, actual
                 );
-                let err = TypeError::Unification(expected, actual, apply_subs(&self.subs, errors));
+                let errors = apply_subs(&self.subs, &self.type_definitions, errors);
+                let instantiations = self.explain_instantiations(&errors);
+                let err = TypeError::Unification(expected, actual, errors, instantiations);
                 self.errors.push(Spanned {
                     span: span,
                     // TODO Help what caused this unification failure
@@ -2211,7 +2824,7 @@ impl<'a> Typecheck<'a> {
     }
 
     fn unify_span(&mut self, span: Span<BytePos>, expected: &ArcType, actual: ArcType) -> ArcType {
-        match self.unify(expected, actual) {
+        match self.unify_at(span, expected, actual) {
             Ok(typ) => typ,
             Err(err) => {
                 self.errors.push(Spanned {
@@ -2224,10 +2837,41 @@ impl<'a> Typecheck<'a> {
         }
     }
 
-    fn unify(&self, expected: &ArcType, mut actual: ArcType) -> TcResult<ArcType> {
+    /// Sets the unification of `expected` and `actual` as the origin of the bindings made by the
+    /// substitution, returning the previous origin
+    fn set_origin(
+        &self,
+        span: Span<BytePos>,
+        expected: &ArcType,
+        actual: &ArcType,
+    ) -> Option<Origin<ArcType>> {
+        if self.subs.is_recording_provenance() {
+            self.subs.set_origin(Some(Origin {
+                span,
+                expected: expected.clone(),
+                actual: actual.clone(),
+            }))
+        } else {
+            None
+        }
+    }
+
+    fn unify(&self, expected: &ArcType, actual: ArcType) -> TcResult<ArcType> {
+        self.unify_at(self.expr_span, expected, actual)
+    }
+
+    fn unify_at(
+        &self,
+        span: Span<BytePos>,
+        expected: &ArcType,
+        mut actual: ArcType,
+    ) -> TcResult<ArcType> {
         debug!("Unify start {} <=> {}", expected, actual);
         let state = unify_type::State::new(&self.environment, &self.subs);
-        match unify::unify(&self.subs, state, expected, &actual) {
+        let outer_origin = self.set_origin(span, expected, &actual);
+        let result = unify::unify(&self.subs, state, expected, &actual);
+        self.subs.set_origin(outer_origin);
+        match result {
             Ok(typ) => Ok(self.subs.set_type(typ)),
             Err(errors) => {
                 let mut expected = expected.clone();
@@ -2237,10 +2881,13 @@ impl<'a> Typecheck<'a> {
                     "Error '{:?}' between:\n>> {}\n>> {}",
                     errors, expected, actual
                 );
+                let errors = apply_subs(&self.subs, &self.type_definitions, errors);
+                let instantiations = self.explain_instantiations(&errors);
                 Err(TypeError::Unification(
                     expected,
                     actual,
-                    apply_subs(&self.subs, errors),
+                    errors,
+                    instantiations,
                 ))
             }
         }
@@ -2268,7 +2915,77 @@ impl<'a> Typecheck<'a> {
 
     fn instantiate_generics(&mut self, typ: &ArcType) -> ArcType {
         self.named_variables.clear();
-        typ.instantiate_generics(&mut self.named_variables)
+        let typ = typ.instantiate_generics(&mut self.named_variables);
+        if self.subs.is_recording_provenance() {
+            self.instantiations.extend(
+                self.named_variables
+                    .iter()
+                    .filter_map(|(generic, var)| match **var {
+                        Type::Variable(ref var) => Some((generic.clone(), var.id)),
+                        _ => None,
+                    }),
+            );
+        }
+        typ
+    }
+
+    /// Finds the generic variables, instantiated while checking the current expression, which
+    /// were bound to one of the types that failed to unify in `errors` and where they were bound
+    fn explain_instantiations(
+        &self,
+        errors: &[UnifyTypeError<Symbol>],
+    ) -> Vec<Instantiation<Symbol>> {
+        let mismatched: Vec<&ArcType> = errors
+            .iter()
+            .flat_map(|err| match *err {
+                unify::Error::TypeMismatch(ref expected, ref actual) => vec![expected, actual],
+                _ => Vec::new(),
+            })
+            .collect();
+        if mismatched.is_empty() {
+            return Vec::new();
+        }
+
+        let provenance = self.subs.provenance();
+        let mut instantiations: Vec<Instantiation<Symbol>> = Vec::new();
+        for &(ref generic, var) in &self.instantiations {
+            if var < self.expr_variables {
+                continue;
+            }
+            let bound = match self.subs.find_type_for_var(var) {
+                Some(bound) if bound.get_var().is_none() => bound,
+                _ => continue,
+            };
+            // The first binding of a variable in the same set as `var` to a type which is not
+            // a variable is where `var` got its type
+            let origin = provenance
+                .iter()
+                .filter(|binding| binding.typ.get_var().is_none())
+                .find(|binding| {
+                    binding.variable.get_var().map_or(false, |other| {
+                        self.subs
+                            .find_type_for_var(other.id)
+                            .map_or(false, |other| ptr::eq(other, bound))
+                    })
+                })
+                .and_then(|binding| binding.origin.as_ref());
+            let origin = match origin {
+                Some(origin) => origin,
+                None => continue,
+            };
+            let typ = self.subs.set_type(bound.clone());
+            if mismatched.iter().any(|mismatched| **mismatched == typ)
+                && !instantiations.iter().any(|instantiation| {
+                    instantiation.generic == *generic && instantiation.span == origin.span
+                }) {
+                instantiations.push(Instantiation {
+                    generic: generic.clone(),
+                    typ,
+                    span: origin.span,
+                });
+            }
+        }
+        instantiations
     }
 
     fn new_skolem_scope(&mut self, typ: &ArcType) -> ArcType {
@@ -2281,21 +2998,49 @@ impl<'a> Typecheck<'a> {
 
     fn error_on_duplicated_field(
         &mut self,
-        duplicated_fields: &mut FnvSet<String>,
+        duplicated_fields: &mut FnvMap<String, Span<BytePos>>,
         new_name: Spanned<Symbol, BytePos>,
     ) -> bool {
+        use std::collections::hash_map::Entry;
+
         let span = new_name.span;
-        duplicated_fields
-            .replace(new_name.value.declared_name().to_string())
-            .map_or(true, |name| {
+        match duplicated_fields.entry(new_name.value.declared_name().to_string()) {
+            Entry::Occupied(entry) => {
                 self.errors.push(Spanned {
                     span: span,
-                    // TODO Help to the other fields location
-                    value: TypeError::DuplicateField(name).into(),
+                    value: TypeError::DuplicateField(entry.key().clone(), *entry.get()).into(),
                 });
                 false
-            })
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(span);
+                true
+            }
+        }
+    }
+}
+
+/// Returns the fields of `record_type` which overload `field`
+fn overloads(record_type: &ArcType, field: &Symbol) -> Vec<Field<Symbol, ArcType>> {
+    record_type
+        .row_iter()
+        .filter(|other| {
+            types::overloaded_name(other.name.as_ref())
+                .map_or(false, |(name, _)| name == field.declared_name())
+        })
+        .cloned()
+        .collect()
+}
+
+/// Pushes an argument to `args` for each implicit argument of `typ`, returning the type that
+/// remains once they are applied. The arguments are selected by `rename`
+fn implicit_args(name: &Symbol, typ: &ArcType, args: &mut Vec<ImplicitArg<Symbol>>) -> ArcType {
+    let mut typ = typ;
+    while let Some((arg, ret)) = typ.as_implicit_function() {
+        args.push(ImplicitArg::new(name.clone(), arg.clone()));
+        typ = ret;
     }
+    typ.clone()
 }
 
 fn with_pattern_types<F>(
@@ -2316,11 +3061,15 @@ fn with_pattern_types<F>(
     }
 }
 
+/// Applies the substitution to the types in `errors` and fills in the locations of the local
+/// type definitions which the errors refer to
 fn apply_subs(
     subs: &Substitution<ArcType>,
+    type_definitions: &ScopedMap<Symbol, Span<BytePos>>,
     errors: Errors<UnifyTypeError<Symbol>>,
 ) -> Vec<UnifyTypeError<Symbol>> {
     use unify::Error::*;
+    use unify_type::TypeError::{MissingFields, SelfRecursive};
     errors
         .into_iter()
         .map(|error| match error {
@@ -2338,6 +3087,16 @@ fn apply_subs(
                     substitution::Error::Constraint(subs.set_type(typ), constraints)
                 }
             }),
+            Other(SelfRecursive(id, None)) => {
+                let definition = type_definitions.get(&id).cloned();
+                Other(SelfRecursive(id, definition))
+            }
+            Other(MissingFields(typ, fields, None)) => {
+                let definition = typ.alias_ident()
+                    .and_then(|id| type_definitions.get(id))
+                    .cloned();
+                Other(MissingFields(typ, fields, definition))
+            }
             Other(err) => Other(err),
         })
         .collect()
@@ -2352,6 +3111,33 @@ pub fn extract_generics(args: &[ArcType]) -> Vec<Generic<Symbol>> {
         .collect()
 }
 
+/// Returns the `forall` type which `typ` is, possibly through aliases. Self recursive aliases
+/// (`type A a = A a`) are not expanded more than once as the unifier reports them.
+fn forall_alias(env: &TypeEnv, typ: &ArcType) -> Option<ArcType> {
+    let mut visited = Vec::new();
+    let mut typ = typ.clone();
+    loop {
+        if let Type::Forall(..) = *typ {
+            return Some(typ);
+        }
+        match typ.alias_ident() {
+            Some(id) if !visited.contains(id) => visited.push(id.clone()),
+            _ => return None,
+        }
+        typ = match resolve::remove_alias(env, &typ) {
+            Ok(Some(typ)) => typ,
+            _ => return None,
+        };
+    }
+}
+
+/// Returns the type returned by the constructor of type `typ`
+fn constructor_return(typ: &ArcType) -> &ArcType {
+    let mut iter = types::arg_iter(typ);
+    for _ in iter.by_ref() {}
+    iter.typ
+}
+
 fn get_alias_app<'a>(
     env: &'a TypeEnv,
     typ: &'a ArcType,
@@ -2385,11 +3171,11 @@ impl<'a, 'b> Iterator for FunctionArgIter<'a, 'b> {
             self.typ = self.tc.skolemize(&self.typ);
             let (arg, new) = match self.typ.as_function() {
                 Some((arg, ret)) => (Some(arg.clone()), ret.clone()),
-                None => match get_alias_app(&self.tc.environment, &self.typ) {
-                    Some((alias, args)) => match alias.typ().apply_args(&args) {
-                        Some(typ) => (None, typ.clone()),
-                        None => return None,
-                    },
+                None => match get_alias_app(&self.tc.environment, &self.typ)
+                    .map(|(alias, args)| alias.typ().apply_args(&args))
+                {
+                    Some(Some(typ)) => (None, self.tc.new_skolem_scope(&typ)),
+                    Some(None) => return None,
                     None => {
                         let arg = self.tc.subs.new_var();
                         let ret = self.tc.subs.new_var();
@@ -2439,6 +3225,19 @@ fn expr_check_span(e: &SpannedExpr<Symbol>) -> Span<BytePos> {
     }
 }
 
+/// Replaces `typ` with a hole if kindchecking stopped because it is too deeply nested so that the
+/// rest of the typechecker does not recurse into it
+fn discard_too_deep_type(
+    err: &Spanned<KindCheckError<Symbol>, BytePos>,
+    typ: &mut AstType<Symbol>,
+) {
+    if let UnifyError::Other(KindError::TypeTooDeeplyNested) = err.value {
+        use base::pos::HasSpan;
+        let span = typ.span();
+        *typ = pos::spanned(span, Type::Hole).into();
+    }
+}
+
 /// Removes layers of `Type::App` and `Type::Record` by packing them into a single `Type::App` or
 /// `Type::Record`
 ///
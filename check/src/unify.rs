@@ -3,11 +3,12 @@ use std::hash::Hash;
 
 use pretty::{Arena, DocAllocator};
 
-use base::error::Errors;
+use base::error::{Errors, Label, Labels};
 use base::fnv::FnvMap;
 use base::symbol::{Symbol, Symbols};
 use base::types::ToDoc;
 
+use messages;
 use substitution::{self, Substitutable, Substitution, Variable};
 
 #[derive(Debug, PartialEq)]
@@ -17,6 +18,18 @@ pub enum Error<T, E> {
     Other(E),
 }
 
+impl<T, E> Labels for Error<T, E>
+where
+    E: Labels,
+{
+    fn labels(&self) -> Vec<Label> {
+        match *self {
+            Error::Other(ref err) => err.labels(),
+            Error::TypeMismatch(..) | Error::Substitution(_) => Vec::new(),
+        }
+    }
+}
+
 impl<T, E> fmt::Display for Error<T, E>
 where
     T: fmt::Display + for<'a> ToDoc<'a, Arena<'a>, ()>,
@@ -30,16 +43,21 @@ where
                 let arena = Arena::new();
                 let doc = chain![&arena;
                     arena.newline(),
-                    "Expected:",
+                    arena.text(message!(messages::EXPECTED).to_string()),
                     arena.space(),
                     l.to_doc(&arena, ()).group(),
                     arena.newline(),
-                    "Found:",
+                    arena.text(message!(messages::FOUND).to_string()),
                     arena.space(),
                     r.to_doc(&arena, ()).group()
                 ].group()
                     .nest(4);
-                write!(f, "Types do not match:{}", doc.1.pretty(80))
+                write!(
+                    f,
+                    "{}{}",
+                    message!(messages::TYPE_MISMATCH),
+                    doc.1.pretty(80)
+                )
             }
             Substitution(ref err) => err.fmt(f),
             Other(ref err) => write!(f, "{}", err),
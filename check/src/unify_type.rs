@@ -1,16 +1,18 @@
 use std::fmt;
 use std::mem;
 
-use base::error::Errors;
+use base::error::{Errors, Label, Labels};
 use base::fnv::FnvMap;
 use base::merge;
 use base::kind::ArcKind;
+use base::pos::{BytePos, Span};
 use base::types::{self, AppVec, ArcType, Field, Filter, Generic, Skolem, Type, TypeEnv,
                   TypeFormatter, TypeVariable};
 use base::symbol::{Symbol, SymbolRef};
 use base::resolve::{self, Error as ResolveError};
 use base::scoped_map::ScopedMap;
 
+use messages;
 use unify;
 use unify::{Error as UnifyError, Fresh, GenericVariant, Unifiable, Unifier};
 use substitution::{Constraints, Substitutable, Substitution, Variable, VariableFactory};
@@ -71,7 +73,7 @@ impl<'a> State<'a> {
     ) -> Result<Option<ArcType>, TypeError<Symbol>> {
         if let Some(alias_id) = typ.alias_ident() {
             if self.reduced_aliases.iter().any(|name| name == alias_id) {
-                return Err(TypeError::SelfRecursive(alias_id.clone()));
+                return Err(TypeError::SelfRecursive(alias_id.clone(), None));
             }
             self.reduced_aliases.push(alias_id.clone());
         }
@@ -95,7 +97,7 @@ impl<'a> State<'a> {
                     });
                     if let Some(alias_id) = typ.alias_ident() {
                         if self.reduced_aliases.iter().any(|name| name == alias_id) {
-                            return Err(TypeError::SelfRecursive(alias_id.clone()));
+                            return Err(TypeError::SelfRecursive(alias_id.clone(), None));
                         }
                         self.reduced_aliases.push(alias_id.clone());
                     }
@@ -116,9 +118,33 @@ impl<'a> State<'a> {
 pub enum TypeError<I> {
     UndefinedType(I),
     FieldMismatch(I, I),
-    SelfRecursive(I),
+    /// The span is the definition of the alias, if it is known
+    SelfRecursive(I, Option<Span<BytePos>>),
     UnableToGeneralize(I),
-    MissingFields(ArcType<I>, Vec<I>),
+    /// The span is the definition of the alias which lacks the fields, if it is known
+    MissingFields(ArcType<I>, Vec<I>, Option<Span<BytePos>>),
+}
+
+impl<I> Labels for TypeError<I>
+where
+    I: fmt::Display + AsRef<str>,
+{
+    fn labels(&self) -> Vec<Label> {
+        let alias_label = |alias: &fmt::Display, span: Span<BytePos>| {
+            Label::new(
+                span,
+                message!(messages::ALIAS_DEFINED_HERE, alias = alias).to_string(),
+            )
+        };
+        match *self {
+            TypeError::SelfRecursive(ref id, Some(span)) => vec![alias_label(id, span)],
+            TypeError::MissingFields(ref typ, _, Some(span)) => match typ.alias_ident() {
+                Some(id) => vec![alias_label(id, span)],
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        }
+    }
 }
 
 impl From<ResolveError> for TypeError<Symbol> {
@@ -153,9 +179,9 @@ where
                 }
             }),
             TypeError::UndefinedType(_) => Box::new(|_| Filter::Retain),
-            TypeError::SelfRecursive(_) => Box::new(|_| Filter::Retain),
+            TypeError::SelfRecursive(..) => Box::new(|_| Filter::Retain),
             TypeError::UnableToGeneralize(_) => Box::new(|_| Filter::Retain),
-            TypeError::MissingFields(ref typ, ref fields) => {
+            TypeError::MissingFields(ref typ, ref fields, _) => {
                 let mut field_similarity = typ.type_field_iter()
                     .map(|field| &field.name)
                     .chain(typ.row_iter().map(|field| &field.name))
@@ -197,36 +223,39 @@ where
         match *self {
             TypeError::FieldMismatch(ref l, ref r) => write!(
                 f,
-                "Field names in record do not match.\n\tExpected: {}\n\tFound: {}",
-                l, r
-            ),
-            TypeError::UndefinedType(ref id) => write!(f, "Type `{}` does not exist.", id),
-            TypeError::SelfRecursive(ref id) => write!(
-                f,
-                "The use of self recursion in type `{}` could not be unified.",
-                id
+                "{}",
+                message!(messages::FIELD_MISMATCH, expected = l, found = r)
             ),
-            TypeError::UnableToGeneralize(ref id) => write!(
-                f,
-                "Could not generalize the variable bound to `{}` as the variable was used \
-                 outside its scope",
-                id
-            ),
-            TypeError::MissingFields(ref typ, ref fields) => {
-                write!(
-                    f,
-                    "The type `{}` lacks the following fields: ",
-                    TypeFormatter::new(typ).filter(filter)
-                )?;
+            TypeError::UndefinedType(ref id) => {
+                write!(f, "{}", message!(messages::TYPE_DOES_NOT_EXIST, name = id))
+            }
+            TypeError::SelfRecursive(ref id, _) => {
+                write!(f, "{}", message!(messages::SELF_RECURSIVE, name = id))
+            }
+            TypeError::UnableToGeneralize(ref id) => {
+                write!(f, "{}", message!(messages::UNABLE_TO_GENERALIZE, name = id))
+            }
+            TypeError::MissingFields(ref typ, ref fields, _) => {
+                let and = message!(messages::AND).to_string();
+                let mut field_list = String::new();
                 for (i, field) in fields.iter().enumerate() {
                     let sep = match i {
                         0 => "",
                         i if i < fields.len() - 1 => ", ",
-                        _ => " and ",
+                        _ => &and[..],
                     };
-                    write!(f, "{}{}", sep, field)?;
+                    field_list.push_str(sep);
+                    field_list.push_str(field.as_ref());
                 }
-                Ok(())
+                write!(
+                    f,
+                    "{}",
+                    message!(
+                        messages::MISSING_TYPE_FIELDS,
+                        typ = TypeFormatter::new(typ).filter(filter),
+                        fields = field_list
+                    )
+                )
             }
         }
     }
@@ -328,11 +357,25 @@ where
     UnifierState<'a, U>: Unifier<State<'a>, ArcType>,
 {
     debug!("Unifying:\n{} <=> {}", expected, actual);
+    // Type-level naturals are equal if they add up to the same number, `(1 + 2) == 3`
+    if let (Some(l), Some(r)) = (
+        eval_nat(unifier.state.subs, expected),
+        eval_nat(unifier.state.subs, actual),
+    ) {
+        return if l == r {
+            Ok(None)
+        } else {
+            Err(UnifyError::TypeMismatch(expected.clone(), actual.clone()))
+        };
+    }
     match (&**expected, &**actual) {
         (&Type::App(ref l, ref l_args), &Type::App(ref r, ref r_args)) => {
             Ok(unify_app(unifier, l, l_args, r, r_args))
         }
-        (&Type::Variant(ref l_row), &Type::Variant(ref r_row)) => match (&**l_row, &**r_row) {
+        (&Type::Variant(ref expected_row), &Type::Variant(ref actual_row)) => match (
+            &**expected_row,
+            &**actual_row,
+        ) {
             (
                 &Type::ExtendRow {
                     fields: ref l_row,
@@ -357,6 +400,11 @@ where
                         .map(|typ| Field::new(l.name.clone(), typ))
                 });
                 Ok(new_fields.map(|fields| Type::poly_variant(fields, l_rest.clone())))
+            } else if **l_rest != Type::EmptyRow || **r_rest != Type::EmptyRow {
+                // At least one of the variants is open (`[| A | r |]`) so unify them as rows
+                Ok(unifier
+                    .try_match(expected_row, actual_row)
+                    .map(|row| ArcType::from(Type::Variant(row))))
             } else {
                 Err(UnifyError::TypeMismatch(expected.clone(), actual.clone()))
             },
@@ -407,7 +455,9 @@ where
                     new_rest,
                     |fields, rest| Type::extend_row(l_types.clone(), fields, rest),
                 ))
-            } else if **l_rest == Type::EmptyRow && **r_rest == Type::EmptyRow {
+            } else if l_args.len() == r_args.len() && **l_rest == Type::EmptyRow
+                && **r_rest == Type::EmptyRow
+            {
                 for l_typ in expected.type_field_iter() {
                     if actual
                         .type_field_iter()
@@ -581,6 +631,20 @@ where
     }
 }
 
+/// Evaluates `typ` if it is a type-level natural, or a sum of type-level naturals which are all
+/// known
+fn eval_nat(subs: &Substitution<ArcType>, typ: &ArcType) -> Option<u64> {
+    match **subs.real(typ) {
+        Type::Nat(n) => Some(n),
+        Type::App(ref f, ref args) if f.is_nat_add() && args.len() == 2 => {
+            let l = eval_nat(subs, &args[0])?;
+            let r = eval_nat(subs, &args[1])?;
+            l.checked_add(r)
+        }
+        _ => None,
+    }
+}
+
 fn unify_app<'a, U>(
     unifier: &mut UnifierState<'a, U>,
     l: &ArcType,
@@ -732,6 +796,7 @@ where
                         .into_iter()
                         .map(|field| field.name.clone())
                         .collect(),
+                    None,
                 );
                 unifier.report_error(UnifyError::Other(err));
             }
@@ -765,6 +830,7 @@ where
                         .into_iter()
                         .map(|field| field.name.clone())
                         .collect(),
+                    None,
                 );
                 unifier.report_error(UnifyError::Other(err));
             }
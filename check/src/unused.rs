@@ -0,0 +1,188 @@
+//! Detection of bindings which are never referenced. Let bindings, function arguments and the
+//! fields bound by record patterns which are not used anywhere are reported as warnings.
+//!
+//! Bindings whose name start with `_` are never reported, neither are bindings introduced by macro
+//! expansions (such as the implicit prelude).
+use std::fmt;
+
+use base::ast::{self, Do, Expr, Pattern, SpannedExpr, SpannedIdent, SpannedPattern, Visitor};
+use base::error::{Errors, Labels};
+use base::fnv::FnvSet;
+use base::pos::{self, BytePos, Span, Spanned, NO_EXPANSION};
+use base::symbol::Symbol;
+use base::types;
+
+use messages;
+
+pub type Warnings = Errors<Spanned<Warning, BytePos>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Warning {
+    /// A variable bound by a `let` binding or a pattern is never used
+    UnusedBinding(String),
+    /// An argument of a function is never used
+    UnusedArgument(String),
+    /// A field bound by a record pattern is never used
+    UnusedField(String),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::UnusedBinding(ref name) => {
+                write!(f, "{}", message!(messages::UNUSED_BINDING, name = name))
+            }
+            Warning::UnusedArgument(ref name) => {
+                write!(f, "{}", message!(messages::UNUSED_ARGUMENT, name = name))
+            }
+            Warning::UnusedField(ref name) => {
+                write!(f, "{}", message!(messages::UNUSED_FIELD, name = name))
+            }
+        }
+    }
+}
+
+impl Labels for Warning {}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Binding,
+    Argument,
+    Field,
+}
+
+struct Liveness<'a> {
+    used: FnvSet<&'a Symbol>,
+    /// The names of the bindings whose overloads (`name@1`, `name@2`, ...) are used
+    used_overloads: FnvSet<&'a str>,
+    bindings: Vec<(&'a Symbol, Span<BytePos>, Kind)>,
+}
+
+impl<'a> Liveness<'a> {
+    fn use_variable(&mut self, id: &'a Symbol) {
+        self.used.insert(id);
+        // A use of an overload of a binding is a use of the binding itself
+        if let Some((name, _)) = types::overloaded_name(id.as_ref()) {
+            self.used_overloads.insert(name);
+        }
+    }
+
+    fn use_implicit_args(&mut self, args: &'a [ast::ImplicitArg<Symbol>]) {
+        for arg in args {
+            self.use_variable(&arg.name.name);
+            self.use_implicit_args(&arg.args);
+        }
+    }
+
+    fn new_binding(&mut self, id: &'a Symbol, span: Span<BytePos>, kind: Kind) {
+        if span.expansion_id == NO_EXPANSION && !id.declared_name().starts_with('_') {
+            self.bindings.push((id, span, kind));
+        }
+    }
+
+    fn new_args(&mut self, args: &'a [SpannedIdent<Symbol>]) {
+        for arg in args {
+            self.new_binding(&arg.value.name, arg.span, Kind::Argument);
+        }
+    }
+
+    fn new_pattern(&mut self, pattern: &'a SpannedPattern<Symbol>, kind: Kind) {
+        // Only the outermost span of a pattern is marked as expanded in expanded code (such as
+        // the implicit prelude) so none of the bindings within it are checked
+        if pattern.span.expansion_id != NO_EXPANSION {
+            return;
+        }
+        match pattern.value {
+            Pattern::As(ref id, ref pat) => {
+                self.new_binding(id, pattern.span, kind);
+                self.new_pattern(pat, kind);
+            }
+            Pattern::Ident(ref id) => self.new_binding(&id.name, pattern.span, kind),
+            Pattern::Record { ref fields, .. } => for field in fields {
+                match field.value {
+                    Some(ref pat) => self.new_pattern(pat, Kind::Field),
+                    None => self.new_binding(&field.name.value, field.name.span, Kind::Field),
+                }
+            },
+            Pattern::Constructor(_, ref args) => for arg in args {
+                self.new_pattern(arg, kind);
+            },
+            Pattern::Tuple { ref elems, .. } => for elem in elems {
+                self.new_pattern(elem, kind);
+            },
+            Pattern::Literal(_) | Pattern::Error => (),
+        }
+    }
+}
+
+impl<'a, 'ast> Visitor<'a, 'ast> for Liveness<'a> {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        match expr.value {
+            Expr::Ident(ref id) => self.use_variable(&id.name),
+            Expr::ImplicitApp(ref id, ref args) => {
+                self.use_variable(&id.name);
+                self.use_implicit_args(args);
+            }
+            Expr::Projection(_, _, _, ref args) => self.use_implicit_args(args),
+            Expr::Infix(_, ref op, _) => self.use_variable(&op.value.name),
+            Expr::LetBindings(ref bindings, _) => for bind in bindings.iter() {
+                self.new_pattern(&bind.name, Kind::Binding);
+                self.new_args(&bind.implicit_args);
+                self.new_args(&bind.args);
+            },
+            Expr::Lambda(ref lambda) => self.new_args(&lambda.args),
+            Expr::Match(_, ref alts) => for alt in alts.iter() {
+                self.new_pattern(&alt.pattern, Kind::Binding);
+            },
+            Expr::Record { ref exprs, .. } => for field in exprs.iter() {
+                if field.value.is_none() {
+                    self.use_variable(&field.name.value);
+                }
+            },
+            Expr::Do(Do {
+                ref id,
+                ref flat_map_id,
+                ..
+            }) => {
+                self.new_binding(&id.value.name, id.span, Kind::Binding);
+                if let Some(ref flat_map_id) = *flat_map_id {
+                    self.use_variable(&flat_map_id.name);
+                }
+            }
+            _ => (),
+        }
+        ast::walk_expr(self, expr);
+    }
+}
+
+/// Returns a warning for each binding in `expr` which is never referenced. `expr` is expected to
+/// have been renamed so that every binding has an unique name.
+pub fn check(expr: &SpannedExpr<Symbol>) -> Warnings {
+    let mut liveness = Liveness {
+        used: FnvSet::default(),
+        used_overloads: FnvSet::default(),
+        bindings: Vec::new(),
+    };
+    liveness.visit_expr(expr);
+
+    let Liveness {
+        used,
+        used_overloads,
+        bindings,
+    } = liveness;
+    bindings
+        .into_iter()
+        .filter(|&(id, _, _)| !used.contains(id) && !used_overloads.contains(id.as_ref()))
+        .map(|(id, span, kind)| {
+            let name = id.declared_name().to_string();
+            let warning = match kind {
+                Kind::Binding => Warning::UnusedBinding(name),
+                Kind::Argument => Warning::UnusedArgument(name),
+                Kind::Field => Warning::UnusedField(name),
+            };
+            pos::spanned(span, warning)
+        })
+        .collect()
+}
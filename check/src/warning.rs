@@ -0,0 +1,68 @@
+//! Non-fatal diagnostics which are reported alongside a successfully checked expression
+use std::fmt;
+
+use base::error::{Errors, Label, Labels};
+use base::pos::{BytePos, Span, Spanned};
+
+use messages;
+use metadata;
+use unused;
+
+pub type Warnings = Errors<Spanned<Warning, BytePos>>;
+
+/// Selects which warnings are reported
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WarningSettings {
+    /// Warn when a let binding or a function argument shadows a local binding of the same type
+    /// (default: false)
+    pub shadowing: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Warning {
+    /// A binding hides a binding with an equivalent type, which can no longer be referred to
+    Shadowed {
+        name: String,
+        previous: Span<BytePos>,
+    },
+    /// A binding which is never referenced
+    Unused(unused::Warning),
+    /// A use of a binding or type marked with `#[deprecated]`
+    Deprecated(metadata::Deprecated),
+    /// A `match` alternative which can never be matched as the alternatives before it match all
+    /// of its values
+    UnreachableAlternative,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Warning::Shadowed { ref name, .. } => {
+                write!(f, "{}", message!(messages::SHADOWED, name = name))
+            }
+            Warning::Unused(ref warning) => write!(f, "{}", warning),
+            Warning::Deprecated(ref warning) => write!(f, "{}", warning),
+            Warning::UnreachableAlternative => {
+                write!(f, "{}", message!(messages::UNREACHABLE_ALTERNATIVE))
+            }
+        }
+    }
+}
+
+impl Labels for Warning {
+    fn labels(&self) -> Vec<Label> {
+        match *self {
+            Warning::Shadowed { ref name, previous } => {
+                vec![
+                    Label::new(
+                        previous,
+                        message!(messages::FIRST_BOUND_HERE, name = name).to_string(),
+                    ),
+                ]
+            }
+            Warning::Unused(ref warning) => warning.labels(),
+            Warning::Deprecated(ref warning) => warning.labels(),
+            Warning::UnreachableAlternative => Vec::new(),
+        }
+    }
+}
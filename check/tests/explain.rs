@@ -0,0 +1,80 @@
+#[macro_use]
+extern crate collect_mac;
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_check as check;
+extern crate gluon_parser as parser;
+
+use base::pos::{BytePos, Span};
+use base::types::Type;
+
+use check::explain::Explanation;
+
+use support::typ;
+
+#[macro_use]
+#[allow(unused_macros)]
+mod support;
+
+fn span(text: &str, expr: &str) -> Span<BytePos> {
+    let start = text.find(expr).unwrap();
+    Span::new(BytePos::from(start), BytePos::from(start + expr.len()))
+}
+
+fn explain(text: &str, span: Span<BytePos>) -> Option<Explanation<BytePos>> {
+    support::typecheck_with(text, |tc| tc.record_provenance(), |tc| tc.explain(span))
+}
+
+#[test]
+fn explain_lambda_argument() {
+    let _ = env_logger::init();
+
+    let text = r"\x -> x #Int+ 1";
+    let explanation = explain(text, span(text, text)).unwrap();
+
+    assert_eq!(explanation.span, span(text, text));
+    assert_eq!(
+        explanation.typ,
+        Type::function(vec![typ("Int")], typ("Int"))
+    );
+    assert!(
+        explanation
+            .steps
+            .iter()
+            .any(|step| step.typ == typ("Int") && step.origin.is_some()),
+        "{:#?}",
+        explanation.steps
+    );
+    assert!(
+        explanation.to_string().contains("`Int -> Int`"),
+        "{}",
+        explanation
+    );
+}
+
+#[test]
+fn explain_innermost_expression() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let x = "abc"
+x
+"#;
+    let explanation = explain(text, span(text, r#""abc""#)).unwrap();
+
+    assert_eq!(explanation.span, span(text, r#""abc""#));
+    assert_eq!(explanation.typ, typ("String"));
+    assert!(explanation.steps.is_empty());
+}
+
+#[test]
+fn explain_outside_any_expression() {
+    let _ = env_logger::init();
+
+    let text = "1";
+    assert_eq!(
+        explain(text, Span::new(BytePos::from(5), BytePos::from(6))),
+        None
+    );
+}
@@ -8,10 +8,12 @@ extern crate gluon_base as base;
 extern crate gluon_check as check;
 extern crate gluon_parser as parser;
 
+use base::error::Labels;
 use base::symbol::Symbol;
 use base::types::{ArcType, Type};
 
-use check::typecheck::TypeError;
+use check::rename::RenameError;
+use check::typecheck::{Help, TypeError};
 
 #[macro_use]
 mod support;
@@ -44,6 +46,91 @@ in x
     assert_err!(result, UndefinedType(..));
 }
 
+#[test]
+fn undefined_variable_suggests_similar_name() {
+    let _ = env_logger::init();
+    let text = r#"
+let length = 1
+lenght
+"#;
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    let error = (&errors).into_iter().next().expect("error");
+    assert_eq!(
+        error.value.help,
+        Some(Help::DidYouMean("length".to_string()))
+    );
+    assert!(error.value.to_string().contains("did you mean `length`?"));
+}
+
+#[test]
+fn undefined_type_suggests_similar_name() {
+    let _ = env_logger::init();
+    let text = r#"
+type Tree = | Leaf | Node Tree Tree
+type Forest = Array Tre
+1
+"#;
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    let error = (&errors).into_iter().next().expect("error");
+    assert_eq!(error.value.help, Some(Help::DidYouMean("Tree".to_string())));
+}
+
+#[test]
+fn undefined_names_suggest_names_from_the_environment() {
+    let _ = env_logger::init();
+    let text = r#"
+let x : Bol = Tru
+x
+"#;
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    let helps: Vec<_> = (&errors)
+        .into_iter()
+        .map(|error| error.value.help.as_ref())
+        .collect();
+    assert_eq!(
+        helps,
+        [
+            Some(&Help::DidYouMean("Bool".to_string())),
+            Some(&Help::DidYouMean("True".to_string())),
+        ]
+    );
+}
+
+#[test]
+fn undefined_operator_does_not_suggest_identifiers() {
+    let _ = env_logger::init();
+    let text = r#"
+let _ = 1
+1 + 2
+"#;
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    let error = (&errors).into_iter().next().expect("error");
+    assert_eq!(error.value.help, None);
+}
+
+#[test]
+fn undefined_type_in_recursive_group_suggests_similar_name() {
+    let _ = env_logger::init();
+    let text = r#"
+type Tree = | Leaf | Node Forest
+and Forest = Array Tre
+1
+"#;
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    let error = (&errors).into_iter().next().expect("error");
+    assert_eq!(error.value.help, Some(Help::DidYouMean("Tree".to_string())));
+}
+
 #[test]
 fn undefined_variant() {
     let _ = env_logger::init();
@@ -145,6 +232,106 @@ let (++) x y = x #Float+ y
     );
 }
 
+#[test]
+fn ambiguous_implicit_argument() {
+    let _ = env_logger::init();
+    let text = r#"
+#[implicit]
+let x = 1
+#[implicit]
+let y = 2
+let f ?i _ : [Int] -> () -> Int = i
+f ()
+"#;
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    let error = (&errors).into_iter().next().expect("error");
+    match error.value.error {
+        TypeError::Rename(RenameError::AmbiguousImplicit { ref candidates, .. }) => {
+            assert_eq!(candidates.len(), 2)
+        }
+        ref err => panic!("Expected an ambiguous implicit argument, got `{}`", err),
+    }
+    assert_eq!(error.value.labels().len(), 2);
+}
+
+#[test]
+fn missing_implicit_argument() {
+    let _ = env_logger::init();
+    let text = r#"
+let x = 1
+#[implicit]
+let y = 2.0
+let f ?i _ : [Int] -> () -> Int = i
+f ()
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, Rename(RenameError::MissingImplicit { .. }));
+}
+
+#[test]
+fn implicit_recursion_limit() {
+    let _ = env_logger::init();
+    let text = r#"
+type Show a = { show : a -> String }
+#[implicit]
+let show_loop ?s : [Show a] -> Show a = s
+let show ?s x : [Show a] -> a -> String = s.show x
+show 1
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, Rename(RenameError::ImplicitRecursionLimit { .. }));
+}
+
+fn recursive_value_cycle(text: &str) -> Vec<String> {
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    assert_eq!(errors.len(), 1, "{}", errors);
+    let error = (&errors).into_iter().next().expect("error");
+    match error.value.error {
+        TypeError::Rename(RenameError::RecursiveValue { ref cycle }) => {
+            cycle.iter().map(|&(ref name, _)| name.clone()).collect()
+        }
+        ref err => panic!("Expected a recursive value, got `{}`", err),
+    }
+}
+
+#[test]
+fn value_refers_to_itself() {
+    let _ = env_logger::init();
+    let text = r#"
+let x = x #Int+ 1
+x
+"#;
+    assert_eq!(recursive_value_cycle(text), vec!["x"]);
+}
+
+#[test]
+fn values_refer_to_each_other() {
+    let _ = env_logger::init();
+    let text = r#"
+let x = y #Int+ 1
+and y = x
+and z = 2
+x
+"#;
+    assert_eq!(recursive_value_cycle(text), vec!["x", "y"]);
+}
+
+#[test]
+fn value_refers_to_itself_through_lambda() {
+    let _ = env_logger::init();
+    let text = r#"
+let f = \x -> f x
+f
+"#;
+    assert_eq!(recursive_value_cycle(text), vec!["f"]);
+}
+
 // TODO Determine what the correct semantics is for this case
 #[ignore]
 #[test]
@@ -257,6 +444,27 @@ let g x: A a -> () = x
     assert_unify_err!(result, Other(SelfRecursive(..)));
 }
 
+#[test]
+fn self_recursive_alias_points_to_definition() {
+    let _ = env_logger::init();
+    let text = r"
+type A a = A a
+
+let g x: A a -> () = x
+1
+";
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    let labels: Vec<_> = (&errors)
+        .into_iter()
+        .flat_map(|err| err.value.labels())
+        .collect();
+    assert_eq!(labels.len(), 1, "{:?}", labels);
+    assert_eq!(labels[0].span.start, 6.into());
+    assert!(labels[0].message.ends_with("A` is defined here"), "{}", labels[0].message);
+}
+
 #[test]
 fn declared_generic_variables_may_not_make_outer_bindings_more_general() {
     let _ = ::env_logger::init();
@@ -296,6 +504,129 @@ let { Test, Test, x = y, x } = { Test, x = 1 }
     assert_err!(result, DuplicateField(..), DuplicateField(..));
 }
 
+#[test]
+fn duplicate_field_points_to_first_definition() {
+    let _ = ::env_logger::init();
+    let text = r#"
+{ x = 1, x = 2 }
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(
+        &*format!("{}", result.unwrap_err()),
+        r#"test:Line: 2, Column: 10: The record has more than one field named 'x'
+{ x = 1, x = 2 }
+         ^
+test:Line: 2, Column: 3: field first defined here
+{ x = 1, x = 2 }
+  -
+"#
+    );
+}
+
+#[test]
+fn duplicate_type_definition_points_to_previous_definition() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Test = Int
+in
+type Test = Float
+in 1
+"#;
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    let labels: Vec<_> = (&errors)
+        .into_iter()
+        .flat_map(|err| err.value.labels())
+        .map(|label| (label.span.start, label.message))
+        .collect();
+    assert_eq!(
+        labels,
+        vec![(6.into(), "previous definition is here".to_string())]
+    );
+}
+
+#[test]
+fn type_mismatch_explains_instantiation() {
+    let _ = ::env_logger::init();
+    let text = r#"
+let id x = x
+let y : String = id 1
+y
+"#;
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    let labels: Vec<_> = (&errors)
+        .into_iter()
+        .flat_map(|err| err.value.labels())
+        .map(|label| (label.span.start, label.message))
+        .collect();
+    assert_eq!(
+        labels,
+        vec![(34.into(), "`a` was instantiated to `Int` here".to_string())]
+    );
+}
+
+#[test]
+fn record_alias_field_mismatch() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Point = { x : Int, y : Int }
+let p : Point = { x = 1, z = "" }
+p
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(
+        &*format!("{}", result.unwrap_err()),
+        r#"test:Line: 3, Column: 17: The record does not have the same fields as `test.Point`
+Missing fields:
+    y : Int
+Unexpected fields:
+    z : String
+let p : Point = { x = 1, z = "" }
+                ^~~~~~~~~~~~~~~~~
+test:Line: 2, Column: 6: `test.Point` is defined here
+type Point = { x : Int, y : Int }
+     -----
+"#
+    );
+}
+
+#[test]
+fn non_exhaustive_match() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Option a = | None | Some a
+type List a = | Nil | Cons a (List a)
+let f l : List (Option Int) -> Int =
+    match l with
+    | Cons (Some x) _ -> x
+    | Nil -> 3
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, PatternMatch(..));
+    let message = format!("{}", support::typecheck(text).unwrap_err());
+    assert!(message.contains("\n    Cons None _\n"), "{}", message);
+}
+
+#[test]
+fn non_exhaustive_match_on_literals() {
+    let _ = ::env_logger::init();
+    let text = r#"
+match 1 with
+| 0 -> "zero"
+| 1 -> "one"
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, PatternMatch(..));
+}
+
 #[test]
 fn type_alias_with_explicit_type_kind() {
     let _ = ::env_logger::init();
@@ -309,6 +640,19 @@ type Bar = Test Foo
     assert_err!(result, KindError(TypeMismatch(..)));
 }
 
+#[test]
+fn type_alias_with_kind_variable_applied_to_different_kinds() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Pair (a : k) (b : k) = | Pair
+type Test a = | Test a
+let x : Pair Int Test = Pair
+()
+"#;
+    let result = support::typecheck(text);
+    assert_err!(result, KindError(TypeMismatch(..)));
+}
+
 #[test]
 fn type_alias_with_explicit_row_kind() {
     let _ = ::env_logger::init();
@@ -320,11 +664,59 @@ type Bar = Test Int
     let result = support::typecheck(text);
     assert_err!(
         result,
-        KindError(TypeMismatch(..)),
+        KindError(Other(check::kindcheck::KindError::DeclaredKindMismatch { .. })),
         KindError(TypeMismatch(..))
     );
 }
 
+#[test]
+fn type_alias_with_explicit_kind_reports_declared_kind() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Functor (f : Type -> Type) = { map : f -> f }
+()
+"#;
+    let result = support::typecheck(text);
+    assert_err!(
+        result,
+        KindError(Other(check::kindcheck::KindError::DeclaredKindMismatch { .. }))
+    );
+
+    let result = support::typecheck(text);
+    let message = result.unwrap_err().to_string();
+    assert!(
+        message.contains("`f` which is declared with kind `Type -> Type`"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn declared_kind_mismatch_points_to_declaration() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Functor (f : Type -> Type) = { map : f -> f }
+()
+"#;
+    let result = support::typecheck(text);
+
+    let errors = result.unwrap_err().errors();
+    let labels: Vec<_> = (&errors)
+        .into_iter()
+        .flat_map(|err| err.value.labels())
+        .map(|label| (label.span.start, label.message))
+        .collect();
+    assert_eq!(
+        labels,
+        vec![
+            (
+                6.into(),
+                "`f` is declared with kind `Type -> Type` in this type binding".to_string(),
+            ),
+        ]
+    );
+}
+
 #[test]
 fn type_alias_with_explicit_function_kind() {
     let _ = ::env_logger::init();
@@ -355,6 +747,28 @@ y
     );
 }
 
+#[test]
+fn type_error_span_after_raw_and_multi_line_strings() {
+    use base::pos::Span;
+
+    let _ = ::env_logger::init();
+    let text = r##"
+let x = """a
+"b" """
+let y = r#"c"d"#
+x #Int+ 1
+"##;
+    let result = support::typecheck(text);
+    let errors: Vec<_> = result.unwrap_err().errors().into();
+    assert_eq!(errors.len(), 1);
+    let start = text.rfind("x #Int+").unwrap();
+    assert_eq!(
+        errors[0].span.map(|loc| loc.absolute),
+        Span::new(start.into(), (start + 1).into())
+    );
+    assert_eq!(errors[0].span.start.line.to_usize(), 4);
+}
+
 #[test]
 fn issue_286() {
     let _ = ::env_logger::init();
@@ -447,7 +861,7 @@ fn long_type_error_format() {
             "loooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooooong",
         )),
     );
-    let err = TypeError::Unification(Type::int(), long_type.clone(), vec![]);
+    let err = TypeError::Unification(Type::int(), long_type.clone(), vec![], vec![]);
     assert_eq!(
         &*err.to_string(),
         r#"Expected the following types to be equal
@@ -632,3 +1046,87 @@ let test x : () = () in 1
 
     assert_unify_err!(result, TypeMismatch(..));
 }
+
+#[test]
+fn errors_in_operands_of_undefined_operator_are_reported() {
+    let _ = env_logger::init();
+    let text = r#"
+let f x : Int -> Int = x
+f "" +++ undefined
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(
+        result,
+        UndefinedVariable(..),
+        Unification(..),
+        UndefinedVariable(..)
+    );
+}
+
+#[test]
+fn errors_in_arguments_to_non_function_are_reported() {
+    let _ = env_logger::init();
+    let text = r#"
+1 undefined
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, UndefinedVariable(..), Unification(..));
+}
+
+#[test]
+fn errors_in_later_match_alternatives_are_reported() {
+    let _ = env_logger::init();
+    let text = r#"
+match 1 with
+| 0 -> ""
+| 1 -> 2
+| _ -> undefined
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, Unification(..), UndefinedVariable(..));
+}
+
+#[test]
+fn errors_in_record_fields_after_undefined_type_are_reported() {
+    let _ = env_logger::init();
+    let text = r#"
+{ Undefined, x = undefined }
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, UndefinedType(..), UndefinedVariable(..));
+}
+
+#[test]
+fn type_level_naturals_must_be_equal() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Vec (n : Nat) a = | Vec (Array a)
+let empty : Vec 0 a = Vec []
+let push xs x : Vec n a -> a -> Vec (n + 1) a =
+    match xs with
+    | Vec xs -> Vec xs
+let pair : Vec 3 Int = push (push empty 1) 2
+pair
+"#;
+    let result = support::typecheck(text);
+
+    assert_unify_err!(result, TypeMismatch(..));
+}
+
+#[test]
+fn type_level_natural_is_not_a_type() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Bad = Array 1
+()
+"#;
+    let result = support::typecheck(text);
+
+    assert_err!(result, KindError(TypeMismatch(..)));
+}
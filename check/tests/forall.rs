@@ -78,7 +78,7 @@ test 1
     let (expr, result) = support::typecheck_expr(text);
 
     assert!(result.is_ok());
-    let (bind, call) = match expr.value {
+    let (bind, call) = match expr.expr().value {
         Expr::TypeBindings(_, ref body) => match body.value {
             Expr::LetBindings(_, ref body) => match body.value {
                 Expr::LetBindings(ref binds, ref body) => (&binds[0], body),
@@ -111,9 +111,9 @@ let a = { id = \x -> x, z = 1 #Int== 2.0 }
 a.id
 "#;
     let (expr, _result) = support::typecheck_expr(text);
-    let t = match expr.value {
+    let t = match expr.expr().value {
         Expr::LetBindings(_, ref body) => match body.value {
-            Expr::Projection(_, _, ref typ) => typ,
+            Expr::Projection(_, _, ref typ, _) => typ,
             _ => panic!(),
         },
         _ => panic!(),
@@ -243,6 +243,38 @@ type Bar = Test Foo
     assert!(result.is_ok(), "{}", result.unwrap_err());
 }
 
+#[test]
+fn type_alias_with_polymorphic_kind() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Proxy a = | Proxy
+type Test a = | Test a
+let x : Proxy Int = Proxy
+let y : Proxy Test = Proxy
+let z : Proxy Proxy = Proxy
+()
+"#;
+    let result = support::typecheck(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+}
+
+#[test]
+fn type_alias_with_kind_variable() {
+    let _ = ::env_logger::init();
+    let text = r#"
+type Proxy k (a : k) = | Proxy
+type Pair (a : k) (b : k) = | Pair
+type Test a = | Test a
+type Test2 a = | Test2 a
+let x : Proxy Int Test = Proxy
+let y : Pair Int String = Pair
+let z : Pair Test Test2 = Pair
+()
+"#;
+    let result = support::typecheck(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+}
+
 /// Check that after typechecking, the resulting types are `Alias`, not `Ident`. This is necessary
 /// so that when the type is later propagated it knows what its internal representation are without
 /// any extra information
@@ -584,10 +616,10 @@ let traversable : Traversable Option = {
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
     struct Visitor;
-    impl<'a> base::ast::Visitor<'a> for Visitor {
+    impl<'a, 'ast> base::ast::Visitor<'a, 'ast> for Visitor {
         type Ident = Symbol;
 
-        fn visit_expr(&mut self, expr: &'a SpannedExpr<Symbol>) {
+        fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
             match expr.value {
                 Expr::Ident(ref id) if id.name.declared_name() == "Some" => {
                     assert_eq!(id.typ.to_string(), "b -> test.Option b");
@@ -596,7 +628,7 @@ let traversable : Traversable Option = {
             }
         }
     }
-    base::ast::Visitor::visit_expr(&mut Visitor, &expr)
+    base::ast::Visitor::visit_expr(&mut Visitor, expr.expr())
 }
 
 #[test]
@@ -1065,3 +1097,47 @@ append (Cons "" Nil) Nil
 
     assert!(result.is_ok(), "{}", result.unwrap_err());
 }
+
+#[test]
+fn higher_rank_argument_is_polymorphic() {
+    let _ = ::env_logger::init();
+
+    let text = r#"
+let f g : (forall a . a -> a) -> { x : Int, y : String } = { x = g 1, y = g "" }
+f (\x -> x)
+"#;
+    let result = support::typecheck(text);
+
+    assert_req!(
+        result.map(|t| t.to_string()),
+        Ok("{ x : Int, y : String }".to_string())
+    );
+}
+
+#[test]
+fn higher_rank_argument_rejects_monomorphic_function() {
+    let _ = ::env_logger::init();
+
+    let text = r#"
+let f g : (forall a . a -> a) -> Int = g 1
+f (\x -> x #Int+ 1)
+"#;
+    let result = support::typecheck(text);
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn higher_rank_alias() {
+    let _ = ::env_logger::init();
+
+    let text = r#"
+type Id = forall a . a -> a
+let f g : Id -> (Int, String) = (g 1, g "")
+let id : Id = \x -> x
+f id
+"#;
+    let result = support::typecheck(text);
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+}
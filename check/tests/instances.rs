@@ -0,0 +1,93 @@
+#[macro_use]
+extern crate collect_mac;
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_check as check;
+extern crate gluon_parser as parser;
+
+use base::types::ArcType;
+
+use check::instances::find_instances;
+
+use support::MockEnv;
+
+#[macro_use]
+#[allow(unused_macros)]
+mod support;
+
+fn instance_names(text: &str, alias: &str) -> Vec<String> {
+    let typ = support::typecheck(text).unwrap_or_else(|err| panic!("{}", err));
+    let alias = typ.type_field_iter()
+        .find(|field| field.name.declared_name() == alias)
+        .unwrap_or_else(|| panic!("Missing type `{}` in `{}`", alias, typ))
+        .typ
+        .clone();
+
+    let env = MockEnv::new();
+    let bindings: Vec<(String, &ArcType)> = vec![("test".to_string(), &typ)];
+    find_instances(&env, &alias, bindings)
+        .into_iter()
+        .map(|instance| instance.name)
+        .collect()
+}
+
+#[test]
+fn find_instance_fields() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Functor f = { map : forall a b . (a -> b) -> f a -> f b }
+type Option a = | None | Some a
+type List a = | Nil | Cons a (List a)
+
+let option_functor : Functor Option = {
+    map = \f x ->
+        match x with
+        | None -> None
+        | Some y -> Some (f y)
+}
+let list_functor : Functor List =
+    let map f xs =
+        match xs with
+        | Nil -> Nil
+        | Cons y ys -> Cons (f y) (map f ys)
+    { map }
+
+{ Functor, Option, List, option_functor, nested = { list_functor }, x = 1 }
+"#;
+    assert_eq!(
+        instance_names(text, "Functor"),
+        vec!["test.nested.list_functor", "test.option_functor"]
+    );
+}
+
+#[test]
+fn fields_of_instances_are_not_instances() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Semigroup a = { append : a -> a -> a }
+type Monoid a = { semigroup : Semigroup a, empty : a }
+
+let semigroup : Semigroup Int = { append = \x y -> x #Int+ y }
+let monoid : Monoid Int = { semigroup, empty = 0 }
+
+{ Semigroup, Monoid, semigroup, monoid }
+"#;
+    assert_eq!(instance_names(text, "Semigroup"), vec!["test.semigroup"]);
+    assert_eq!(instance_names(text, "Monoid"), vec!["test.monoid"]);
+}
+
+#[test]
+fn records_of_types_are_not_instances() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Functor f = { map : forall a b . (a -> b) -> f a -> f b }
+type Option a = | None | Some a
+
+{ Functor, types = { Option } }
+"#;
+    assert_eq!(instance_names(text, "Functor"), Vec::<String>::new());
+}
@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate collect_mac;
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_check as check;
+extern crate gluon_parser as parser;
+
+use std::collections::{HashMap, HashSet};
+
+use base::message;
+
+use check::messages;
+
+mod support;
+
+#[test]
+fn message_ids_are_unique() {
+    let mut ids = HashSet::new();
+    for message in messages::MESSAGES {
+        assert!(ids.insert(message.id), "Duplicate message id `{}`", message.id);
+    }
+}
+
+#[test]
+fn translated_messages() {
+    let _ = env_logger::init();
+
+    let text = "undefined_x";
+    let result = support::typecheck(text);
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("Undefined variable `undefined_x`")
+    );
+
+    let mut catalog = HashMap::new();
+    catalog.insert(
+        messages::UNDEFINED_VARIABLE.id.to_string(),
+        "Variable indéfinie `{name}`".to_string(),
+    );
+    message::set_catalog(Some(Box::new(catalog)));
+    // Messages are translated when they are displayed so the catalog must still be installed
+    let err = support::typecheck(text).unwrap_err().to_string();
+    message::set_catalog(None);
+
+    assert!(err.contains("Variable indéfinie `undefined_x`"), "{}", err);
+}
@@ -10,7 +10,7 @@ use base::ast::SpannedExpr;
 use base::metadata::{Metadata, MetadataEnv};
 use base::symbol::Symbol;
 
-fn metadata(env: &MetadataEnv, expr: &mut SpannedExpr<Symbol>) -> Metadata {
+fn metadata(env: &MetadataEnv, expr: &SpannedExpr<Symbol>) -> Metadata {
     check::metadata::metadata(env, expr).0
 }
 
@@ -33,16 +33,16 @@ fn propagate_metadata_let_in() {
 let id x = x
 id
 "#;
-    let (mut expr, result) = support::typecheck_expr(text);
+    let (expr, result) = support::typecheck_expr(text);
 
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    let metadata = metadata(&MockEnv, &mut expr);
+    let metadata = metadata(&MockEnv, expr.expr());
     assert_eq!(
         metadata,
         Metadata {
             comment: Some("The identity function".into()),
-            module: Default::default(),
+            ..Metadata::default()
         }
     );
 }
@@ -56,16 +56,16 @@ fn propagate_metadata_let_record() {
 let id x = x
 { id }
 "#;
-    let (mut expr, result) = support::typecheck_expr(text);
+    let (expr, result) = support::typecheck_expr(text);
 
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    let metadata = metadata(&MockEnv, &mut expr);
+    let metadata = metadata(&MockEnv, expr.expr());
     assert_eq!(
         metadata.module.get("id"),
         Some(&Metadata {
             comment: Some("The identity function".into()),
-            module: Default::default(),
+            ..Metadata::default()
         })
     );
 }
@@ -79,16 +79,16 @@ fn propagate_metadata_type_record() {
 type Test = Int
 { Test }
 "#;
-    let (mut expr, result) = support::typecheck_expr(text);
+    let (expr, result) = support::typecheck_expr(text);
 
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    let metadata = metadata(&MockEnv, &mut expr);
+    let metadata = metadata(&MockEnv, expr.expr());
     assert_eq!(
         metadata.module.get("Test"),
         Some(&Metadata {
             comment: Some("A test type".into()),
-            module: Default::default(),
+            ..Metadata::default()
         })
     );
 }
@@ -103,16 +103,16 @@ fn propagate_metadata_record_field_comment() {
     id = \x -> x
 }
 "#;
-    let (mut expr, result) = support::typecheck_expr(text);
+    let (expr, result) = support::typecheck_expr(text);
 
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    let metadata = metadata(&MockEnv, &mut expr);
+    let metadata = metadata(&MockEnv, expr.expr());
     assert_eq!(
         metadata.module.get("id"),
         Some(&Metadata {
             comment: Some("The identity function".into()),
-            module: Default::default(),
+            ..Metadata::default()
         })
     );
 }
@@ -128,16 +128,135 @@ let x = {
 }
 x.id
 "#;
-    let (mut expr, result) = support::typecheck_expr(text);
+    let (expr, result) = support::typecheck_expr(text);
 
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    let metadata = metadata(&MockEnv, &mut expr);
+    let metadata = metadata(&MockEnv, expr.expr());
     assert_eq!(
         metadata,
         Metadata {
             comment: Some("The identity function".into()),
-            module: Default::default(),
+            ..Metadata::default()
         }
     );
 }
+
+#[test]
+fn propagate_attributes_let_record() {
+    let _ = env_logger::init();
+
+    let text = r#"
+/// The identity function
+#[implicit]
+let id x = x
+{ id }
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let metadata = metadata(&MockEnv, expr.expr());
+    assert_eq!(
+        metadata.module.get("id"),
+        Some(&Metadata {
+            comment: Some("The identity function".into()),
+            attributes: vec![("implicit".to_string(), None)].into_iter().collect(),
+            ..Metadata::default()
+        })
+    );
+}
+
+fn deprecations(text: &str) -> Vec<String> {
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    check::metadata::deprecations(&MockEnv, expr.expr())
+        .into_iter()
+        .map(|warning| warning.value.to_string())
+        .collect()
+}
+
+#[test]
+fn deprecated_binding_is_reported_at_each_use() {
+    let _ = env_logger::init();
+
+    let text = r#"
+#[deprecated("use new_id instead")]
+let old_id x = x
+let new_id x = x
+let y = old_id 1
+old_id y
+"#;
+    assert_eq!(
+        deprecations(text),
+        vec![
+            "`old_id` is deprecated: use new_id instead".to_string(),
+            "`old_id` is deprecated: use new_id instead".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn exporting_deprecated_binding_is_not_reported() {
+    let _ = env_logger::init();
+
+    let text = r#"
+#[deprecated]
+let x = 1
+let y = x
+{ x, y }
+"#;
+    // Only the use in `y` is reported and `y` itself is not deprecated
+    assert_eq!(deprecations(text), vec!["`x` is deprecated".to_string()]);
+}
+
+#[test]
+fn deprecated_type_is_reported() {
+    let _ = env_logger::init();
+
+    let text = r#"
+#[deprecated("use Int")]
+type Old = Int
+let x : Old = 1
+x
+"#;
+    assert_eq!(
+        deprecations(text),
+        vec!["`Old` is deprecated: use Int".to_string()]
+    );
+}
+
+#[test]
+fn deprecated_field_is_reported_with_replacement() {
+    let _ = env_logger::init();
+
+    let text = r#"
+#[deprecated("use new_id instead")]
+#[replaced_by("new_id")]
+let old_id x = x
+let new_id x = x
+let m = { old_id, new_id }
+let { old_id } = m
+m.old_id (old_id 1)
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let replaced = check::metadata::deprecations(&MockEnv, expr.expr())
+        .into_iter()
+        .map(|warning| {
+            let start = warning.span.start.to_usize();
+            let end = warning.span.end.to_usize();
+            (&text[start..end], warning.value.replacement)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        replaced,
+        vec![
+            ("old_id", Some("new_id".to_string())),
+            ("old_id", Some("new_id".to_string())),
+            ("old_id", Some("new_id".to_string())),
+        ]
+    );
+}
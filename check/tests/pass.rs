@@ -162,7 +162,7 @@ let f: a -> b -> a = \x y -> x in f 1.0 ()
     let expected = Ok(typ("Float"));
 
     assert_req!(result, expected);
-    match expr.value {
+    match expr.expr().value {
         ast::Expr::LetBindings(ref bindings, _) => {
             assert_eq!(
                 bindings[0].expr.env_type_of(&env).to_string(),
@@ -222,7 +222,7 @@ in test2 1";
     let expected = Ok(typ("Int"));
 
     assert_req!(result, expected);
-    assert_match!(expr.value, ast::Expr::LetBindings(ref binds, _) => {
+    assert_match!(expr.expr().value, ast::Expr::LetBindings(ref binds, _) => {
         assert_eq!(binds.len(), 2);
         assert_match!(**binds[0].resolved_type.remove_forall(), Type::App(_, ref args) => {
             assert_match!(*args[0], Type::Generic(_) => ())
@@ -751,6 +751,75 @@ let (+) x y : () -> () -> () = y
     assert_req!(result.map(support::close_record), expected);
 }
 
+#[test]
+fn implicit_arguments() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Show a = { show : a -> String }
+#[implicit]
+let show_int : Show Int = { show = \_ -> "Int" }
+#[implicit]
+let show_float : Show Float = { show = \_ -> "Float" }
+let show ?s x : [Show a] -> a -> String = s.show x
+let show_twice ?s x : [Show a] -> a -> String = show x
+
+{ x = show 1, y = show 1.0, z = show_twice 2 }
+"#;
+    let result = support::typecheck(text);
+    let fields = vec![
+        Field::new(intern("x"), typ("String")),
+        Field::new(intern("y"), typ("String")),
+        Field::new(intern("z"), typ("String")),
+    ];
+    let expected = Ok(Type::record(vec![], fields));
+
+    assert_req!(result.map(support::close_record), expected);
+}
+
+#[test]
+fn implicit_arguments_of_implicit_bindings() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Show a = { show : a -> String }
+type List a = | Nil | Cons a (List a)
+#[implicit]
+let show_int : Show Int = { show = \_ -> "Int" }
+#[implicit]
+let show_list ?s : [Show a] -> Show (List a) =
+    let show_elems xs =
+        match xs with
+        | Nil -> "Nil"
+        | Cons x _ -> s.show x
+    { show = show_elems }
+let show ?s x : [Show a] -> a -> String = s.show x
+
+show (Cons (Cons 1 Nil) Nil)
+"#;
+    let result = support::typecheck(text);
+
+    assert_req!(result, Ok(typ("String")));
+}
+
+#[test]
+fn implicit_arguments_of_projections() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Show a = { show : a -> String }
+#[implicit]
+let show_int : Show Int = { show = \_ -> "Int" }
+let show ?s x : [Show a] -> a -> String = s.show x
+let m = { show }
+
+m.show 1
+"#;
+    let result = support::typecheck(text);
+
+    assert_req!(result, Ok(typ("String")));
+}
+
 #[test]
 fn as_pattern() {
     let _ = env_logger::init();
@@ -830,6 +899,108 @@ test (x #Int+ 2)
     assert_req!(result.map(support::close_record), expected);
 }
 
+#[test]
+fn loop_expression() {
+    let _ = env_logger::init();
+
+    let text = r#"
+loop state = { i = 0, sum = 0 }
+if state.i #Int< 10 then
+    continue { i = state.i #Int+ 1, sum = state.sum #Int+ state.i }
+else
+    break state.sum
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(Type::int()));
+}
+
+#[test]
+fn labeled_block() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let validate x =
+    check: {
+        if x #Int< 0 then exit check "negative" else ()
+        let y = x #Int* 2
+        if 100 #Int< y then exit check "too large" else ()
+        "ok"
+    }
+validate 3
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(Type::string()));
+}
+
+#[test]
+fn pattern_arguments() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let add { x, y } = x #Int+ y
+let first = \(a, _) -> a
+add { x = first (1, "a"), y = 2 }
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(Type::int()));
+}
+
+#[test]
+fn function_clauses() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let fact 0 = 1
+let fact n = n #Int* fact (n #Int- 1)
+fact 5
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(Type::int()));
+}
+
+#[test]
+fn operator_sections() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let apply f x : (a -> b) -> a -> b = f x
+let get_x = (.x)
+apply (#Int- 1) (apply (10 #Int-) (get_x { x = 3 }))
+"#;
+    let result = support::typecheck(text);
+
+    assert_eq!(result, Ok(Type::int()));
+}
+
+#[test]
+fn for_expression() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Test a = { x : a }
+let flat_map f x : (a -> Test b) -> Test a -> Test b = f x.x
+let test x : a -> Test a = { x }
+
+for x in test 1 do
+    test (x #Int+ 2)
+"#;
+    let result = support::typecheck(text);
+    let expected = Ok(Type::app(
+        alias(
+            "Test",
+            &["a"],
+            Type::record(vec![], vec![Field::new(intern("x"), typ("a"))]),
+        ),
+        collect![typ("Int")],
+    ));
+
+    assert_req!(result.map(support::close_record), expected);
+}
+
 #[test]
 fn eq_unresolved_constraint_bug() {
     let _ = env_logger::init();
@@ -866,3 +1037,99 @@ match Test { x = 1 } with
 
     assert!(result.is_ok(), "{}", result.unwrap_err());
 }
+
+#[test]
+fn exhaustive_nested_match() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Option a = | None | Some a
+type List a = | Nil | Cons a (List a)
+let f l : List (Option Int) -> Int =
+    match l with
+    | Cons (Some x) _ -> x
+    | Cons None Nil -> 1
+    | Cons None (Cons _ _) -> 2
+    | Nil -> 3
+f
+"#;
+    let result = support::typecheck(text);
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+}
+
+#[test]
+fn type_level_naturals_are_added() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Vec (n : Nat) a = | Vec (Array a)
+let empty : Vec 0 a = Vec []
+let push xs x : Vec n a -> a -> Vec (n + 1) a =
+    match xs with
+    | Vec xs -> Vec xs
+let pair : Vec 2 Int = push (push empty 1) 2
+pair
+"#;
+    let result = support::typecheck(text);
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+}
+
+#[test]
+fn lambdas_are_given_fresh_names() {
+    let _ = env_logger::init();
+
+    let text = r"
+let f = \x -> x
+let g = \y -> y
+f
+";
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    fn lambda_name<'a>(expr: &'a ast::SpannedExpr<base::symbol::Symbol>) -> &'a str {
+        match expr.value {
+            ast::Expr::Lambda(ref lambda) => lambda.id.name.as_ref(),
+            _ => panic!("Expected a lambda"),
+        }
+    }
+    match expr.expr().value {
+        ast::Expr::LetBindings(ref f, ref body) => match body.value {
+            ast::Expr::LetBindings(ref g, _) => {
+                let (f, g) = (lambda_name(&f[0].expr), lambda_name(&g[0].expr));
+                assert!(f.starts_with("test.lambda:"), "{}", f);
+                assert!(g.starts_with("test.lambda:"), "{}", g);
+                assert_ne!(f, g);
+            }
+            _ => assert!(false),
+        },
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn unreachable_alternative_is_a_warning() {
+    use check::warning::Warning;
+
+    let _ = env_logger::init();
+    let text = r#"
+type Test = | A | B Int
+match B 1 with
+| B _ -> 1
+| A -> 2
+| B 3 -> 3
+| _ -> 4
+"#;
+    let warnings: Vec<_> = support::typecheck_with(
+        text,
+        |_| (),
+        |tc| {
+            tc.take_warnings()
+                .into_iter()
+                .filter(|warning| warning.value == Warning::UnreachableAlternative)
+                .collect()
+        },
+    );
+    assert_eq!(warnings.len(), 2, "{:?}", warnings);
+}
@@ -262,3 +262,78 @@ fn row_kinds_error() {
     let result = kindcheck.kindcheck_expected(&mut typ, &Kind::row());
     assert!(result.is_err());
 }
+
+#[test]
+fn open_variant_type() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let f x : forall r . [| A Int, B | r |] -> Int = 0
+f
+"#;
+    let result = support::typecheck(text);
+    assert_req!(
+        result.map(|t| t.to_string()),
+        Ok("forall a . forall r . [| A Int, B | r |] -> Int".to_string())
+    );
+}
+
+#[test]
+fn open_variant_accepts_variants_with_more_constructors() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type T = | A Int | B | C String
+type U = | A Int | B
+let f x : forall r . [| A Int, B | r |] -> Int = 0
+f (C "")
+f B
+"#;
+    let result = support::typecheck(text);
+    assert_eq!(result, Ok(typ("Int")));
+}
+
+#[test]
+fn open_variant_rejects_variant_missing_constructor() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type T = | A Int | C String
+let f x : forall r . [| A Int, B | r |] -> Int = 0
+f (C "")
+"#;
+    let result = support::typecheck(text);
+    assert_unify_err!(result, Other(MissingFields(..)));
+}
+
+#[test]
+fn match_on_open_variant() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type T = | A Int | B | C String
+let f x : forall r . [| A Int, B | r |] -> Int =
+    match x with
+    | A i -> i
+    | B -> 1
+    | _ -> 0
+f (C "")
+"#;
+    let result = support::typecheck(text);
+    assert_eq!(result, Ok(typ("Int")));
+}
+
+#[test]
+fn match_on_open_variant_requires_wildcard() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let f x : forall r . [| A Int, B | r |] -> Int =
+    match x with
+    | A i -> i
+    | B -> 1
+f
+"#;
+    let result = support::typecheck(text);
+    assert_err!(result, PatternMatch(..));
+}
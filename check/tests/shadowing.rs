@@ -0,0 +1,78 @@
+#[macro_use]
+extern crate collect_mac;
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_check as check;
+extern crate gluon_parser as parser;
+
+use check::warning::{Warning, WarningSettings};
+
+#[macro_use]
+#[allow(unused_macros)]
+mod support;
+
+fn shadowed(text: &str) -> Vec<String> {
+    support::typecheck_with(
+        text,
+        |tc| tc.set_warning_settings(WarningSettings { shadowing: true }),
+        |tc| {
+            tc.take_warnings()
+                .into_iter()
+                .filter_map(|warning| match warning.value {
+                    Warning::Shadowed { name, .. } => Some(name),
+                    _ => None,
+                })
+                .collect()
+        },
+    )
+}
+
+#[test]
+fn let_binding_shadows_binding() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let x = 1
+let x = 2
+x
+"#;
+    assert_eq!(shadowed(text), vec!["x"]);
+}
+
+#[test]
+fn argument_shadows_binding() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let x = 1
+let f x = x #Int+ 1
+let g = \x -> x #Int+ 2
+f (g x)
+"#;
+    assert_eq!(shadowed(text), vec!["x", "x"]);
+}
+
+#[test]
+fn record_pattern_shadows_binding() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let x = 1
+let { x } = { x = 2 }
+x
+"#;
+    assert_eq!(shadowed(text), vec!["x"]);
+}
+
+#[test]
+fn overloads_are_not_shadowing() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let x = 1
+let x = 1.0
+x #Float+ 2.0
+"#;
+    assert_eq!(shadowed(text), Vec::<String>::new());
+}
@@ -5,6 +5,7 @@ extern crate gluon_base as base;
 extern crate gluon_check as check;
 extern crate gluon_parser as parser;
 
+#[macro_use]
 mod support;
 
 #[test]
@@ -738,3 +739,42 @@ in 1
 "#;
     support::typecheck(text).unwrap();
 }
+
+#[test]
+fn deeply_nested_type_is_an_error() {
+    let text = format!(
+        "type Nested = {}Int{}\n1",
+        "Array (".repeat(1000),
+        ")".repeat(1000)
+    );
+    let result = support::typecheck(&text);
+    assert_err!(
+        result,
+        KindError(Other(check::kindcheck::KindError::TypeTooDeeplyNested))
+    );
+}
+
+#[test]
+fn deeply_nested_type_annotation_is_an_error() {
+    let text = format!(
+        "let x : {}Int{} = []\nx",
+        "Array (".repeat(1000),
+        ")".repeat(1000)
+    );
+    let result = support::typecheck(&text);
+    assert_err!(
+        result,
+        KindError(Other(check::kindcheck::KindError::TypeTooDeeplyNested))
+    );
+}
+
+#[test]
+fn deeply_nested_expression_is_an_error() {
+    let text = format!("{}1{}", "[".repeat(100), "]".repeat(100));
+    support::typecheck(&text).unwrap();
+
+    // Deeper than `DEFAULT_MAX_DEPTH` but not so deep that the parser would overflow the stack
+    let text = format!("{}1{}", "[".repeat(200), "]".repeat(200));
+    let result = support::typecheck(&text);
+    assert_err!(result, ExprTooDeeplyNested);
+}
@@ -1,6 +1,6 @@
 #![allow(unused_macros)]
 
-use base::ast::{DisplayEnv, IdentEnv, SpannedExpr};
+use base::ast::{DisplayEnv, IdentEnv, RootExpr};
 use base::error::InFile;
 use base::kind::{ArcKind, Kind, KindEnv};
 use base::metadata::{Metadata, MetadataEnv};
@@ -12,6 +12,7 @@ use check::typecheck::{self, Typecheck};
 use parser::{parse_partial_expr, ParseErrors};
 
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::rc::Rc;
 
@@ -42,13 +43,11 @@ pub fn intern(s: &str) -> Symbol {
     }
 }
 
-pub fn parse_new(
-    s: &str,
-) -> Result<SpannedExpr<Symbol>, (Option<SpannedExpr<Symbol>>, ParseErrors)> {
+pub fn parse_new(s: &str) -> Result<RootExpr<Symbol>, (Option<RootExpr<Symbol>>, ParseErrors)> {
     let symbols = get_local_interner();
     let mut symbols = symbols.borrow_mut();
     let mut module = SymbolModule::new("test".into(), &mut symbols);
-    parse_partial_expr(&mut module, &TypeCache::new(), &s)
+    RootExpr::try_new(|arena| parse_partial_expr(arena, &mut module, &TypeCache::new(), &s))
 }
 
 #[allow(dead_code)]
@@ -106,6 +105,14 @@ impl TypeEnv for MockEnv {
     ) -> Option<(ArcType, ArcType)> {
         None
     }
+
+    fn value_names(&self) -> Vec<&str> {
+        vec!["False", "True"]
+    }
+
+    fn type_names(&self) -> Vec<&str> {
+        vec!["Bool"]
+    }
 }
 
 impl PrimitiveEnv for MockEnv {
@@ -121,12 +128,12 @@ impl MetadataEnv for MockEnv {
 }
 
 #[allow(dead_code)]
-pub struct MockIdentEnv<T>(PhantomData<T>);
+pub struct MockIdentEnv<T>(HashMap<String, usize>, PhantomData<T>);
 
 #[allow(dead_code)]
 impl<T> MockIdentEnv<T> {
     pub fn new() -> MockIdentEnv<T> {
-        MockIdentEnv(PhantomData)
+        MockIdentEnv(HashMap::new(), PhantomData)
     }
 }
 
@@ -145,15 +152,18 @@ where
     fn from_str(&mut self, s: &str) -> Self::Ident {
         T::from(s)
     }
+
+    fn fresh_ident(&mut self, prefix: &str) -> Self::Ident {
+        let count = self.0.entry(prefix.to_string()).or_insert(0);
+        *count += 1;
+        T::from(&*format!("{}:{}", prefix, count))
+    }
 }
 
 pub fn typecheck_expr_expected(
     text: &str,
     expected: Option<&ArcType>,
-) -> (
-    SpannedExpr<Symbol>,
-    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
-) {
+) -> (RootExpr<Symbol>, Result<ArcType, InFile<typecheck::HelpError<Symbol>>>) {
     let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
 
     let env = MockEnv::new();
@@ -161,27 +171,42 @@ pub fn typecheck_expr_expected(
     let mut interner = interner.borrow_mut();
     let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
 
-    let result = tc.typecheck_expr_expected(&mut expr, expected);
+    let result = expr.with_mut(|_, expr| tc.typecheck_expr_expected(expr, expected));
 
     (expr, result.map_err(|err| InFile::new("test", text, err)))
 }
 
 pub fn typecheck_expr(
     text: &str,
-) -> (
-    SpannedExpr<Symbol>,
-    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
-) {
+) -> (RootExpr<Symbol>, Result<ArcType, InFile<typecheck::HelpError<Symbol>>>) {
     typecheck_expr_expected(text, None)
 }
 
+/// Typechecks `text` with a typechecker which is first configured by `configure`, returning what
+/// `inspect` reads from the typechecker after `text` has been checked
+#[allow(dead_code)]
+pub fn typecheck_with<C, I, R>(text: &str, configure: C, inspect: I) -> R
+where
+    C: FnOnce(&mut Typecheck),
+    I: FnOnce(&mut Typecheck) -> R,
+{
+    let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
+
+    let env = MockEnv::new();
+    let interner = get_local_interner();
+    let mut interner = interner.borrow_mut();
+    let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
+    configure(&mut tc);
+
+    expr.with_mut(|_, expr| tc.typecheck_expr(expr))
+        .unwrap_or_else(|err| panic!("{}", InFile::new("test", text, err)));
+    inspect(&mut tc)
+}
+
 #[allow(dead_code)]
 pub fn typecheck_partial_expr(
     text: &str,
-) -> (
-    SpannedExpr<Symbol>,
-    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
-) {
+) -> (RootExpr<Symbol>, Result<ArcType, InFile<typecheck::HelpError<Symbol>>>) {
     let mut expr = match parse_new(text) {
         Ok(e) => e,
         Err((Some(e), _)) => e,
@@ -193,7 +218,7 @@ pub fn typecheck_partial_expr(
     let mut interner = interner.borrow_mut();
     let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
 
-    let result = tc.typecheck_expr(&mut expr);
+    let result = expr.with_mut(|_, expr| tc.typecheck_expr(expr));
 
     (expr, result.map_err(|err| InFile::new("test", text, err)))
 }
@@ -320,6 +345,8 @@ macro_rules! assert_err {
         use check::substitution::Error::{Occurs, Constraint};
         #[allow(unused_imports)]
         use check::unify_type::TypeError::FieldMismatch;
+        #[allow(unused_imports)]
+        use check::pattern_match::MatchError::*;
 
         match $e {
             Ok(x) => assert!(false, "Expected error, got {}", x),
@@ -373,7 +400,7 @@ macro_rules! assert_multi_unify_err {
                 match errors_iter.next() {
                     Some((i, error)) => {
                         match error.value.error {
-                            Unification(_, _, ref errors) => {
+                            Unification(_, _, ref errors, _) => {
                                 let mut iter = errors.iter();
                                 let expected_count = count!($($id),+);
                                 $(
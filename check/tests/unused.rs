@@ -0,0 +1,88 @@
+#[macro_use]
+extern crate collect_mac;
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_check as check;
+extern crate gluon_parser as parser;
+
+use check::unused::Warning;
+
+mod support;
+
+fn unused(text: &str) -> Vec<Warning> {
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    check::unused::check(expr.expr())
+        .into_iter()
+        .map(|warning| warning.value)
+        .collect()
+}
+
+#[test]
+fn unused_let_binding() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let x = 1
+let y = 2
+y
+"#;
+    assert_eq!(unused(text), vec![Warning::UnusedBinding("x".into())]);
+}
+
+#[test]
+fn unused_argument() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let f x y = x
+let g = \a b -> b
+f (g 1 2) 3
+"#;
+    assert_eq!(
+        unused(text),
+        vec![
+            Warning::UnusedArgument("y".into()),
+            Warning::UnusedArgument("a".into()),
+        ]
+    );
+}
+
+#[test]
+fn unused_record_pattern_field() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let { x, y = z } = { x = 1, y = 2 }
+x
+"#;
+    assert_eq!(unused(text), vec![Warning::UnusedField("z".into())]);
+}
+
+#[test]
+fn underscore_bindings_are_not_reported() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let _x = 1
+let f _y = 2
+let { x = _z } = { x = 1 }
+f 3
+"#;
+    assert_eq!(unused(text), vec![]);
+}
+
+#[test]
+fn bindings_used_in_records_and_operators_are_not_reported() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let (+++) x y : Int -> Int -> Int = x
+let x = 1
+let y = 2
+{ x, z = 1 +++ y }
+"#;
+    assert_eq!(unused(text), vec![Warning::UnusedArgument("y".into())]);
+}
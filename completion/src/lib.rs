@@ -8,9 +8,12 @@ extern crate walkdir;
 extern crate gluon_base as base;
 
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
 use std::iter::once;
 use std::cmp::Ordering;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use either::Either;
 
@@ -20,36 +23,38 @@ use base::filename_to_module;
 use base::ast::{walk_expr, walk_pattern, AstType, Expr, Pattern, PatternField, SpannedExpr,
                 SpannedIdent, SpannedPattern, Typed, TypedIdent, Visitor};
 use base::fnv::{FnvMap, FnvSet};
-use base::kind::{ArcKind, Kind};
+use base::kind::{ArcKind, Kind, KindEnv};
 use base::metadata::Metadata;
 use base::resolve;
-use base::pos::{self, BytePos, HasSpan, Span, Spanned, NO_EXPANSION};
+use base::pos::{self, BytePos, HasSpan, Line, Span, Spanned, NO_EXPANSION};
 use base::scoped_map::ScopedMap;
+use base::source::Source;
 use base::symbol::{Name, Symbol, SymbolRef};
-use base::types::{walk_type_, AliasData, ArcType, ControlVisitation, Generic, Type, TypeEnv};
+use base::types::{walk_type_, Alias, AliasData, ArcType, ControlVisitation, Generic, RecordSelector,
+                  Type, TypeEnv};
 
 #[derive(Clone, Debug)]
-pub struct Found<'a> {
-    pub match_: Option<Match<'a>>,
-    pub near_matches: Vec<Match<'a>>,
-    pub enclosing_matches: Vec<Match<'a>>,
+pub struct Found<'a, 'ast: 'a> {
+    pub match_: Option<Match<'a, 'ast>>,
+    pub near_matches: Vec<Match<'a, 'ast>>,
+    pub enclosing_matches: Vec<Match<'a, 'ast>>,
 }
 
-impl<'a> Found<'a> {
-    fn enclosing_match(&self) -> &Match<'a> {
+impl<'a, 'ast> Found<'a, 'ast> {
+    fn enclosing_match(&self) -> &Match<'a, 'ast> {
         self.enclosing_matches.last().unwrap()
     }
 }
 
 #[derive(Clone, Debug)]
-pub enum Match<'a> {
-    Expr(&'a SpannedExpr<Symbol>),
+pub enum Match<'a, 'ast: 'a> {
+    Expr(&'a SpannedExpr<'ast, Symbol>),
     Pattern(&'a SpannedPattern<Symbol>),
     Ident(Span<BytePos>, &'a Symbol, &'a ArcType),
     Type(Span<BytePos>, &'a Symbol, ArcKind),
 }
 
-impl<'a> Match<'a> {
+impl<'a, 'ast> Match<'a, 'ast> {
     pub fn span(&self) -> Span<BytePos> {
         match *self {
             Match::Expr(expr) => expr.span,
@@ -202,21 +207,21 @@ impl<E: TypeEnv> OnFound for Suggest<E> {
     }
 }
 
-enum MatchState<'a> {
+enum MatchState<'a, 'ast: 'a> {
     NotFound,
     Empty,
-    Found(Match<'a>),
+    Found(Match<'a, 'ast>),
 }
 
-struct FindVisitor<'a, F> {
+struct FindVisitor<'a, 'ast: 'a, F> {
     pos: BytePos,
     on_found: F,
-    found: MatchState<'a>,
-    enclosing_matches: Vec<Match<'a>>,
-    near_matches: Vec<Match<'a>>,
+    found: MatchState<'a, 'ast>,
+    enclosing_matches: Vec<Match<'a, 'ast>>,
+    near_matches: Vec<Match<'a, 'ast>>,
 }
 
-impl<'a, F> FindVisitor<'a, F> {
+impl<'a, 'ast, F> FindVisitor<'a, 'ast, F> {
     fn select_spanned<I, S, T>(&self, iter: I, mut span: S) -> (bool, Option<T>)
     where
         I: IntoIterator<Item = T>,
@@ -246,22 +251,22 @@ impl<'a, F> FindVisitor<'a, F> {
     }
 }
 
-struct VisitUnExpanded<'a: 'e, 'e, F: 'e>(&'e mut FindVisitor<'a, F>);
+struct VisitUnExpanded<'a: 'e, 'ast: 'a, 'e, F: 'e>(&'e mut FindVisitor<'a, 'ast, F>);
 
-impl<'a, 'e, F> Visitor<'a> for VisitUnExpanded<'a, 'e, F>
+impl<'a, 'ast, 'e, F> Visitor<'a, 'ast> for VisitUnExpanded<'a, 'ast, 'e, F>
 where
     F: OnFound,
 {
     type Ident = Symbol;
 
-    fn visit_expr(&mut self, e: &'a SpannedExpr<Self::Ident>) {
+    fn visit_expr(&mut self, e: &'a SpannedExpr<'ast, Self::Ident>) {
         if let MatchState::NotFound = self.0.found {
             if e.span.expansion_id == NO_EXPANSION {
                 self.0.visit_expr(e);
             } else {
                 match e.value {
                     Expr::TypeBindings(ref type_bindings, ref e) => {
-                        for type_binding in type_bindings {
+                        for type_binding in type_bindings.iter() {
                             self.0.on_found.on_alias(
                                 type_binding
                                     .finalized_alias
@@ -272,7 +277,7 @@ where
                         self.0.visit_expr(e);
                     }
                     Expr::LetBindings(ref bindings, ref e) => {
-                        for binding in bindings {
+                        for binding in bindings.iter() {
                             self.0.on_found.on_pattern(&binding.name);
                         }
                         self.0.visit_expr(e);
@@ -294,13 +299,13 @@ where
     }
 }
 
-impl<'a, F> FindVisitor<'a, F>
+impl<'a, 'ast, F> FindVisitor<'a, 'ast, F>
 where
     F: OnFound,
 {
     fn visit_one<I>(&mut self, iter: I)
     where
-        I: IntoIterator<Item = &'a SpannedExpr<Symbol>>,
+        I: IntoIterator<Item = &'a SpannedExpr<'ast, Symbol>>,
     {
         let (_, expr) = self.select_spanned(iter, |e| e.span);
         self.visit_expr(expr.unwrap());
@@ -413,7 +418,7 @@ where
         }
     }
 
-    fn visit_expr(&mut self, current: &'a SpannedExpr<Symbol>) {
+    fn visit_expr(&mut self, current: &'a SpannedExpr<'ast, Symbol>) {
         // When inside a macro expanded expression we do a exhaustive search for an unexpanded
         // expression
         if current.span.expansion_id != NO_EXPANSION {
@@ -428,7 +433,7 @@ where
         }
 
         match current.value {
-            Expr::Ident(_) | Expr::Literal(_) => {
+            Expr::Ident(_) | Expr::ImplicitApp(..) | Expr::Literal(_) => {
                 self.found = if current.span.containment(&self.pos) == Ordering::Equal {
                     MatchState::Found(Match::Expr(current))
                 } else {
@@ -436,7 +441,7 @@ where
                 };
             }
             Expr::App(ref func, ref args) => {
-                self.visit_one(once(&**func).chain(args));
+                self.visit_one(once(&**func).chain(args.iter()));
             }
             Expr::IfElse(ref pred, ref if_true, ref if_false) => {
                 self.visit_one([pred, if_true, if_false].iter().map(|x| &***x))
@@ -477,24 +482,25 @@ where
                 }
             }
             Expr::LetBindings(ref bindings, ref expr) => {
-                for bind in bindings {
+                for bind in bindings.iter() {
                     self.on_found.on_pattern(&bind.name);
                 }
-                match self.select_spanned(bindings, |b| {
+                match self.select_spanned(bindings.iter(), |b| {
                     Span::new(b.name.span.start, b.expr.span.end)
                 }) {
                     (false, Some(bind)) => {
-                        for arg in &bind.args {
+                        for arg in bind.implicit_args.iter().chain(&bind.args) {
                             self.on_found.on_ident(&arg.value);
                         }
 
-                        enum Variant<'a> {
+                        enum Variant<'a, 'ast: 'a> {
                             Pattern(&'a SpannedPattern<Symbol>),
                             Ident(&'a SpannedIdent<Symbol>),
                             Type(&'a AstType<Symbol>),
-                            Expr(&'a SpannedExpr<Symbol>),
+                            Expr(&'a SpannedExpr<'ast, Symbol>),
                         }
                         let iter = once(Variant::Pattern(&bind.name))
+                            .chain(bind.implicit_args.iter().map(Variant::Ident))
                             .chain(bind.args.iter().map(Variant::Ident))
                             .chain(bind.typ.iter().map(Variant::Type))
                             .chain(once(Variant::Expr(&bind.expr)));
@@ -522,7 +528,7 @@ where
                 }
             }
             Expr::TypeBindings(ref type_bindings, ref expr) => {
-                for type_binding in type_bindings {
+                for type_binding in type_bindings.iter() {
                     self.on_found.on_alias(
                         type_binding
                             .finalized_alias
@@ -555,7 +561,7 @@ where
                     _ => unreachable!(),
                 }
             }
-            Expr::Projection(ref expr, ref id, ref typ) => {
+            Expr::Projection(ref expr, ref id, ref typ, _) => {
                 if expr.span.containment(&self.pos) <= Ordering::Equal {
                     self.visit_expr(expr);
                 } else {
@@ -563,7 +569,7 @@ where
                     self.found = MatchState::Found(Match::Ident(current.span, id, typ));
                 }
             }
-            Expr::Array(ref array) => self.visit_one(&array.exprs),
+            Expr::Array(ref array) => self.visit_one(array.exprs.iter()),
             Expr::Record {
                 ref exprs,
                 ref base,
@@ -600,7 +606,7 @@ where
             | Expr::Block(ref exprs) => if exprs.is_empty() {
                 self.found = MatchState::Found(Match::Expr(current));
             } else {
-                self.visit_one(exprs)
+                self.visit_one(exprs.iter())
             },
             Expr::Do(ref do_expr) => {
                 let iter = once(Either::Left(&do_expr.id))
@@ -664,7 +670,11 @@ where
     }
 }
 
-fn complete_at<F>(on_found: F, expr: &SpannedExpr<Symbol>, pos: BytePos) -> Result<Found, ()>
+fn complete_at<'a, 'ast, F>(
+    on_found: F,
+    expr: &'a SpannedExpr<'ast, Symbol>,
+    pos: BytePos,
+) -> Result<Found<'a, 'ast>, ()>
 where
     F: OnFound,
 {
@@ -714,14 +724,33 @@ impl<'a> Extract for TypeAt<'a> {
 
     fn match_extract(self, found: &Match) -> Result<Self::Output, ()> {
         Ok(match *found {
-            Match::Expr(expr) => expr.env_type_of(self.env),
+            Match::Expr(expr) => expr.try_type_of(self.env).map_err(|_| ())?,
             Match::Ident(_, _, typ) => typ.clone(),
             Match::Type(..) => return Err(()),
-            Match::Pattern(pattern) => pattern.env_type_of(self.env),
+            Match::Pattern(pattern) => pattern.try_type_of(self.env).map_err(|_| ())?,
         })
     }
 }
 
+#[derive(Clone, Copy)]
+pub struct KindAt;
+impl Extract for KindAt {
+    type Output = ArcKind;
+    fn extract(self, found: &Found) -> Result<Self::Output, ()> {
+        match found.match_ {
+            Some(ref match_) => self.match_extract(match_),
+            None => self.match_extract(found.enclosing_match()),
+        }
+    }
+
+    fn match_extract(self, found: &Match) -> Result<Self::Output, ()> {
+        match *found {
+            Match::Type(_, _, ref kind) if **kind != Kind::Hole => Ok(kind.clone()),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct IdentAt;
 impl Extract for IdentAt {
@@ -813,6 +842,49 @@ where
     completion(extract, expr, pos)
 }
 
+/// Type environment which knows of no types. Types which can only be determined by resolving an
+/// alias can't be found with it
+struct EmptyEnv;
+
+impl KindEnv for EmptyEnv {
+    fn find_kind(&self, _type_name: &SymbolRef) -> Option<ArcKind> {
+        None
+    }
+}
+
+impl TypeEnv for EmptyEnv {
+    fn find_type(&self, _id: &SymbolRef) -> Option<&ArcType> {
+        None
+    }
+
+    fn find_type_info(&self, _id: &SymbolRef) -> Option<&Alias<Symbol, ArcType>> {
+        None
+    }
+
+    fn find_record(
+        &self,
+        _fields: &[Symbol],
+        _selector: RecordSelector,
+    ) -> Option<(ArcType, ArcType)> {
+        None
+    }
+}
+
+/// Returns the type of the expression, pattern or binding at `pos` in a typechecked expression.
+///
+/// Unlike `find` this does not need an environment. `None` is returned if nothing is found at
+/// `pos` or if the type could only be determined by looking up an alias, such as the return type
+/// of a function whose type is an alias.
+pub fn type_at(expr: &SpannedExpr<Symbol>, pos: BytePos) -> Option<ArcType> {
+    completion(TypeAt { env: &EmptyEnv }, expr, pos).ok()
+}
+
+/// Returns the kind of the type at `pos` in a typechecked expression. `None` is returned if `pos`
+/// is not on a type or if the kind of the type is not recorded in the AST.
+pub fn kind_at(expr: &SpannedExpr<Symbol>, pos: BytePos) -> Option<ArcKind> {
+    completion(KindAt, expr, pos).ok()
+}
+
 pub fn find_all_symbols(
     expr: &SpannedExpr<Symbol>,
     pos: BytePos,
@@ -823,10 +895,10 @@ pub fn find_all_symbols(
             result: Vec<Span<BytePos>>,
             symbol: Symbol,
         }
-        impl<'a> Visitor<'a> for ExtractIdents {
+        impl<'a, 'ast> Visitor<'a, 'ast> for ExtractIdents {
             type Ident = Symbol;
 
-            fn visit_expr(&mut self, e: &'a SpannedExpr<Self::Ident>) {
+            fn visit_expr(&mut self, e: &'a SpannedExpr<'ast, Self::Ident>) {
                 match e.value {
                     Expr::Ident(ref id) if id.name == self.symbol => {
                         self.result.push(e.span);
@@ -857,12 +929,62 @@ pub fn find_all_symbols(
     })
 }
 
+/// Returns the span of the binding which introduces the identifier at `pos`, if it is bound in
+/// `expr`
+pub fn find_definition(expr: &SpannedExpr<Symbol>, pos: BytePos) -> Option<Span<BytePos>> {
+    struct FindDefinition {
+        result: Option<Span<BytePos>>,
+        symbol: Symbol,
+    }
+    impl FindDefinition {
+        fn visit_args(&mut self, args: &[SpannedIdent<Symbol>]) {
+            if let Some(arg) = args.iter().find(|arg| arg.value.name == self.symbol) {
+                self.result = Some(arg.span);
+            }
+        }
+    }
+    impl<'a, 'ast> Visitor<'a, 'ast> for FindDefinition {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, e: &'a SpannedExpr<'ast, Self::Ident>) {
+            if self.result.is_some() {
+                return;
+            }
+            match e.value {
+                Expr::Lambda(ref lambda) => self.visit_args(&lambda.args),
+                Expr::LetBindings(ref binds, _) => for bind in binds.iter() {
+                    self.visit_args(&bind.implicit_args);
+                    self.visit_args(&bind.args);
+                },
+                _ => (),
+            }
+            walk_expr(self, e)
+        }
+
+        fn visit_pattern(&mut self, p: &'a SpannedPattern<Self::Ident>) {
+            match p.value {
+                Pattern::As(ref id, _) if id == &self.symbol => self.result = Some(p.span),
+                Pattern::Ident(ref id) if id.name == self.symbol => self.result = Some(p.span),
+                _ => walk_pattern(self, &p.value),
+            }
+        }
+    }
+
+    let symbol = completion(IdentAt, expr, pos).ok()?;
+    let mut visitor = FindDefinition {
+        result: None,
+        symbol,
+    };
+    visitor.visit_expr(expr);
+    visitor.result
+}
+
 #[derive(Debug, PartialEq)]
-pub enum CompletionSymbol<'a> {
+pub enum CompletionSymbol<'a, 'ast: 'a> {
     Value {
         name: &'a Symbol,
         typ: &'a ArcType,
-        expr: &'a SpannedExpr<Symbol>,
+        expr: &'a SpannedExpr<'ast, Symbol>,
     },
     Type {
         name: &'a Symbol,
@@ -870,14 +992,16 @@ pub enum CompletionSymbol<'a> {
     },
 }
 
-pub fn all_symbols(expr: &SpannedExpr<Symbol>) -> Vec<Spanned<CompletionSymbol, BytePos>> {
-    struct AllIdents<'a> {
-        result: Vec<Spanned<CompletionSymbol<'a>, BytePos>>,
+pub fn all_symbols<'a, 'ast>(
+    expr: &'a SpannedExpr<'ast, Symbol>,
+) -> Vec<Spanned<CompletionSymbol<'a, 'ast>, BytePos>> {
+    struct AllIdents<'a, 'ast: 'a> {
+        result: Vec<Spanned<CompletionSymbol<'a, 'ast>, BytePos>>,
     }
-    impl<'a> Visitor<'a> for AllIdents<'a> {
+    impl<'a, 'ast> Visitor<'a, 'ast> for AllIdents<'a, 'ast> {
         type Ident = Symbol;
 
-        fn visit_expr(&mut self, e: &'a SpannedExpr<Self::Ident>) {
+        fn visit_expr(&mut self, e: &'a SpannedExpr<'ast, Self::Ident>) {
             match e.value {
                 Expr::TypeBindings(ref binds, _) => {
                     self.result.extend(binds.iter().map(|bind| {
@@ -913,6 +1037,684 @@ pub fn all_symbols(expr: &SpannedExpr<Symbol>) -> Vec<Spanned<CompletionSymbol,
     visitor.result
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Type,
+    Function,
+    Value,
+}
+
+/// A symbol in the outline of a module as returned by `symbols`
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutlineSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The span of the entire binding
+    pub span: Span<BytePos>,
+    /// The span of the name which introduced the symbol
+    pub name_span: Span<BytePos>,
+    pub typ: Either<ArcKind, ArcType>,
+    /// Symbols which are declared inside the binding of this symbol
+    pub children: Vec<OutlineSymbol>,
+}
+
+/// Returns a hierarchical outline of all types and values bound in `expr`. Bindings which are
+/// declared inside the expression of another binding are returned as children of that binding.
+///
+/// Bindings originating from macro expansions (such as the implicit prelude) are not included.
+pub fn symbols(expr: &SpannedExpr<Symbol>) -> Vec<OutlineSymbol> {
+    struct Outline {
+        result: Vec<OutlineSymbol>,
+    }
+
+    impl Outline {
+        fn nested(expr: &SpannedExpr<Symbol>) -> Vec<OutlineSymbol> {
+            let mut outline = Outline { result: Vec::new() };
+            outline.visit_expr(expr);
+            outline.result
+        }
+
+        fn pattern_symbols(&mut self, pattern: &SpannedPattern<Symbol>) {
+            if pattern.span.expansion_id != NO_EXPANSION {
+                return;
+            }
+            match pattern.value {
+                Pattern::As(ref id, ref pat) => {
+                    self.result.push(OutlineSymbol {
+                        name: id.declared_name().to_string(),
+                        kind: SymbolKind::Value,
+                        span: pattern.span,
+                        name_span: pattern.span,
+                        typ: Either::Right(match pat.value {
+                            Pattern::Ident(ref id) => id.typ.clone(),
+                            Pattern::Record { ref typ, .. } | Pattern::Tuple { ref typ, .. } => {
+                                typ.clone()
+                            }
+                            _ => Type::hole(),
+                        }),
+                        children: Vec::new(),
+                    });
+                    self.pattern_symbols(pat);
+                }
+                Pattern::Ident(ref id) => self.result.push(OutlineSymbol {
+                    name: id.name.declared_name().to_string(),
+                    kind: SymbolKind::Value,
+                    span: pattern.span,
+                    name_span: pattern.span,
+                    typ: Either::Right(id.typ.clone()),
+                    children: Vec::new(),
+                }),
+                Pattern::Record {
+                    ref typ,
+                    ref types,
+                    ref fields,
+                } => {
+                    for field in types {
+                        let kind = typ.type_field_iter()
+                            .find(|it| it.name.name_eq(&field.name.value))
+                            .map(|it| it.typ.unresolved_type().kind().into_owned())
+                            .unwrap_or_else(Kind::hole);
+                        self.result.push(OutlineSymbol {
+                            name: field.name.value.declared_name().to_string(),
+                            kind: SymbolKind::Type,
+                            span: field.name.span,
+                            name_span: field.name.span,
+                            typ: Either::Left(kind),
+                            children: Vec::new(),
+                        });
+                    }
+                    for field in fields {
+                        match field.value {
+                            Some(ref pattern) => self.pattern_symbols(pattern),
+                            None => self.result.push(OutlineSymbol {
+                                name: field.name.value.declared_name().to_string(),
+                                kind: SymbolKind::Value,
+                                span: field.name.span,
+                                name_span: field.name.span,
+                                typ: Either::Right(
+                                    typ.row_iter()
+                                        .find(|it| it.name.name_eq(&field.name.value))
+                                        .map(|it| it.typ.clone())
+                                        .unwrap_or_else(Type::hole),
+                                ),
+                                children: Vec::new(),
+                            }),
+                        }
+                    }
+                }
+                Pattern::Tuple { ref elems, .. } | Pattern::Constructor(_, ref elems) => {
+                    for elem in elems {
+                        self.pattern_symbols(elem);
+                    }
+                }
+                Pattern::Literal(_) | Pattern::Error => (),
+            }
+        }
+    }
+
+    impl<'a, 'ast> Visitor<'a, 'ast> for Outline {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, e: &'a SpannedExpr<'ast, Self::Ident>) {
+            match e.value {
+                Expr::TypeBindings(ref binds, ref body) => {
+                    self.result.extend(
+                        binds
+                            .iter()
+                            .filter(|bind| bind.name.span.expansion_id == NO_EXPANSION)
+                            .map(|bind| OutlineSymbol {
+                                name: bind.name.value.declared_name().to_string(),
+                                kind: SymbolKind::Type,
+                                span: bind.span(),
+                                name_span: bind.name.span,
+                                typ: Either::Left(
+                                    bind.alias.value.unresolved_type().kind().into_owned(),
+                                ),
+                                children: Vec::new(),
+                            }),
+                    );
+                    self.visit_expr(body);
+                }
+                Expr::LetBindings(ref binds, ref body) => {
+                    for bind in binds.iter() {
+                        match bind.name.value {
+                            Pattern::Ident(ref id)
+                                if bind.name.span.expansion_id == NO_EXPANSION =>
+                            {
+                                let is_function = bind.is_function() || match bind.expr.value {
+                                    Expr::Lambda(_) => true,
+                                    _ => false,
+                                };
+                                self.result.push(OutlineSymbol {
+                                    name: id.name.declared_name().to_string(),
+                                    kind: if is_function {
+                                        SymbolKind::Function
+                                    } else {
+                                        SymbolKind::Value
+                                    },
+                                    span: bind.span(),
+                                    name_span: bind.name.span,
+                                    typ: Either::Right(id.typ.clone()),
+                                    children: Outline::nested(&bind.expr),
+                                });
+                            }
+                            _ => {
+                                self.pattern_symbols(&bind.name);
+                                self.visit_expr(&bind.expr);
+                            }
+                        }
+                    }
+                    self.visit_expr(body);
+                }
+                _ => walk_expr(self, e),
+            }
+        }
+    }
+
+    Outline::nested(expr)
+}
+
+/// The file, relative to the root of a project, in which `gluon check` and the language server
+/// keep the `SymbolIndex` of the project
+pub const SYMBOL_INDEX_FILE: &str = ".gluon/symbols";
+
+/// A symbol exported from a module as stored in a `SymbolIndex`
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub module: String,
+    /// The file which the module was loaded from
+    pub file: String,
+    /// The span of the name which introduced the symbol
+    pub span: Span<BytePos>,
+    /// The type of a value or the kind of a type
+    pub typ: String,
+}
+
+impl IndexedSymbol {
+    fn to_line(&self) -> String {
+        let kind = match self.kind {
+            SymbolKind::Type => "type",
+            SymbolKind::Function => "function",
+            SymbolKind::Value => "value",
+        };
+        [
+            escape_field(&self.module),
+            escape_field(&self.file),
+            escape_field(&self.name),
+            kind.to_string(),
+            self.span.start.to_usize().to_string(),
+            self.span.end.to_usize().to_string(),
+            escape_field(&self.typ),
+        ].join("\t")
+    }
+
+    fn from_line(line: &str) -> Option<IndexedSymbol> {
+        let mut fields = line.split('\t');
+        let module = unescape_field(fields.next()?);
+        let file = unescape_field(fields.next()?);
+        let name = unescape_field(fields.next()?);
+        let kind = match fields.next()? {
+            "type" => SymbolKind::Type,
+            "function" => SymbolKind::Function,
+            "value" => SymbolKind::Value,
+            _ => return None,
+        };
+        let start = fields.next()?.parse::<usize>().ok()?;
+        let end = fields.next()?.parse::<usize>().ok()?;
+        let typ = unescape_field(fields.next()?);
+        Some(IndexedSymbol {
+            name,
+            kind,
+            module,
+            file,
+            span: Span::new(BytePos::from(start), BytePos::from(end)),
+            typ,
+        })
+    }
+}
+
+/// Escapes the characters which separate fields and entries in the index
+fn escape_field(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape_field(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => result.push('\t'),
+                Some('n') => result.push('\n'),
+                Some(c) => result.push(c),
+                None => (),
+            },
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// An index of the symbols exported by each module of a project. The index can be stored on disk
+/// so that editors can search through the entire project without typechecking every module.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SymbolIndex {
+    modules: BTreeMap<String, Vec<IndexedSymbol>>,
+}
+
+impl SymbolIndex {
+    pub fn new() -> SymbolIndex {
+        SymbolIndex::default()
+    }
+
+    /// Loads the index stored at `path`, returning an empty index if the file does not exist
+    pub fn load(path: &Path) -> io::Result<SymbolIndex> {
+        let mut contents = String::new();
+        match File::open(path) {
+            Ok(mut file) => file.read_to_string(&mut contents)?,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(SymbolIndex::new()),
+            Err(err) => return Err(err),
+        };
+        let mut index = SymbolIndex::new();
+        for line in contents.lines() {
+            let symbol = IndexedSymbol::from_line(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid symbol index entry `{}`", line),
+                )
+            })?;
+            index
+                .modules
+                .entry(symbol.module.clone())
+                .or_insert_with(Vec::new)
+                .push(symbol);
+        }
+        Ok(index)
+    }
+
+    /// Writes the index to `path`, creating the directory containing it if necessary
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(path)?;
+        for symbol in self.symbols() {
+            writeln!(file, "{}", symbol.to_line())?;
+        }
+        Ok(())
+    }
+
+    /// Returns all symbols in the index, ordered by module
+    pub fn symbols<'s>(&'s self) -> Box<Iterator<Item = &'s IndexedSymbol> + 's> {
+        Box::new(self.modules.values().flat_map(|symbols| symbols.iter()))
+    }
+
+    /// Replaces the symbols of `module` with the symbols exported by `expr`, the typechecked
+    /// contents of `file`. `typ` is the type of `expr` and determines which symbols are exported.
+    pub fn update(
+        &mut self,
+        module: &str,
+        file: &str,
+        expr: &SpannedExpr<Symbol>,
+        typ: &ArcType,
+    ) {
+        // Exported symbols are located at the top level binding which defines them or, for
+        // fields which are defined directly in the exported record, at the field itself
+        let mut spans = FnvMap::default();
+        for symbol in symbols(expr) {
+            spans.insert(symbol.name, symbol.name_span);
+        }
+        let mut body = expr;
+        loop {
+            match body.value {
+                Expr::LetBindings(_, ref next) | Expr::TypeBindings(_, ref next) => body = next,
+                Expr::Record { ref exprs, .. } => {
+                    for field in exprs.iter() {
+                        let name = field.name.value.declared_name().to_string();
+                        match field.value {
+                            // Punned fields (which the renamer turns into identifiers) and fields
+                            // which refer to other bindings are only located at the field if
+                            // there is no top level binding with the same name
+                            None
+                            | Some(Spanned {
+                                value: Expr::Ident(_),
+                                ..
+                            }) => {
+                                spans.entry(name).or_insert(field.name.span);
+                            }
+                            Some(_) => {
+                                spans.insert(name, field.name.span);
+                            }
+                        }
+                    }
+                    break;
+                }
+                _ => break,
+            }
+        }
+        let span_of = |name: &str| spans.get(name).cloned().unwrap_or(body.span);
+
+        let types = typ.type_field_iter().map(|field| {
+            let name = field.name.declared_name();
+            IndexedSymbol {
+                name: name.to_string(),
+                kind: SymbolKind::Type,
+                module: module.to_string(),
+                file: file.to_string(),
+                span: span_of(name),
+                typ: field.typ.unresolved_type().kind().to_string(),
+            }
+        });
+        let values = typ.row_iter().map(|field| {
+            let name = field.name.declared_name();
+            IndexedSymbol {
+                name: name.to_string(),
+                kind: if field.typ.remove_forall().as_function().is_some() {
+                    SymbolKind::Function
+                } else {
+                    SymbolKind::Value
+                },
+                module: module.to_string(),
+                file: file.to_string(),
+                span: span_of(name),
+                typ: field.typ.to_string(),
+            }
+        });
+        self.modules
+            .insert(module.to_string(), types.chain(values).collect());
+    }
+
+    /// Removes the symbols of `module` from the index
+    pub fn remove(&mut self, module: &str) {
+        self.modules.remove(module);
+    }
+}
+
+/// Returns how well `name` matches `query` where lower is better, or `None` if `name` does not
+/// contain the characters of `query` in order. Matching ignores case.
+fn fuzzy_match(name: &str, query: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+    let query = query.to_lowercase();
+    if name == query {
+        Some(0)
+    } else if name.starts_with(&query) {
+        Some(1)
+    } else if name.contains(&query) {
+        Some(2)
+    } else {
+        let mut name_chars = name.chars();
+        if query.chars().all(|q| name_chars.any(|c| c == q)) {
+            Some(3)
+        } else {
+            None
+        }
+    }
+}
+
+/// Searches `index` for symbols matching `query`. A symbol matches if its name contains the
+/// characters of `query` in order, ignoring case. Exact matches are returned first followed by
+/// prefix matches, substring matches and the remaining matches.
+pub fn workspace_symbols<'i>(index: &'i SymbolIndex, query: &str) -> Vec<&'i IndexedSymbol> {
+    let mut matches = index
+        .symbols()
+        .filter_map(|symbol| fuzzy_match(&symbol.name, query).map(|score| (score, symbol)))
+        .collect::<Vec<_>>();
+    matches.sort_by(|&(l_score, l), &(r_score, r)| {
+        (l_score, l.name.len(), &l.name, &l.module).cmp(&(
+            r_score,
+            r.name.len(),
+            &r.name,
+            &r.module,
+        ))
+    });
+    matches.into_iter().map(|(_, symbol)| symbol).collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FoldingRangeKind {
+    /// A group of bindings such as `let x = 1 and y = 2` or a single multi-line binding
+    Bindings,
+    /// An alternative of a `match` expression
+    Alternative,
+    /// A record expression
+    Record,
+    /// Consecutive comments preceding a binding
+    Comment,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FoldingRange {
+    pub start: Line,
+    pub end: Line,
+    pub kind: FoldingRangeKind,
+}
+
+/// Computes the ranges which an editor may fold in `expr`. Only constructs which span multiple
+/// lines in `source` produce a range.
+pub fn folding_ranges(source: &Source, expr: &SpannedExpr<Symbol>) -> Vec<FoldingRange> {
+    struct Folding<'a: 'b, 'b> {
+        source: &'b Source<'a>,
+        result: Vec<FoldingRange>,
+    }
+
+    impl<'a, 'b> Folding<'a, 'b> {
+        fn add(&mut self, span: Span<BytePos>, kind: FoldingRangeKind) {
+            if span.expansion_id != NO_EXPANSION {
+                return;
+            }
+            let start = self.source.line_number_at_byte(span.start);
+            let end = self.source.line_number_at_byte(span.end);
+            if start < end {
+                self.result.push(FoldingRange { start, end, kind });
+            }
+        }
+
+        fn add_comments_before(&mut self, pos: BytePos) {
+            let comment_start = self.source.comment_start_before(pos);
+            // Only fold the comments themselves, not the line of the binding
+            let end = self.source.line_number_at_byte(pos);
+            let start = self.source.line_number_at_byte(comment_start);
+            if start.to_usize() + 1 < end.to_usize() {
+                self.result.push(FoldingRange {
+                    start,
+                    end: Line::from(end.to_usize() - 1),
+                    kind: FoldingRangeKind::Comment,
+                });
+            }
+        }
+
+        fn add_group<I>(&mut self, expr: &SpannedExpr<Symbol>, iter: I)
+        where
+            I: IntoIterator<Item = Span<BytePos>>,
+        {
+            if expr.span.expansion_id != NO_EXPANSION {
+                return;
+            }
+            let mut iter = iter.into_iter();
+            if let Some(first) = iter.next() {
+                if let Some(last) = iter.last() {
+                    self.add(Span::new(first.start, last.end), FoldingRangeKind::Bindings);
+                }
+                // The expression starts at the `let` or `type` keyword
+                self.add_comments_before(expr.span.start);
+            }
+        }
+    }
+
+    impl<'a, 'b, 'e, 'ast> Visitor<'e, 'ast> for Folding<'a, 'b> {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, e: &'e SpannedExpr<'ast, Self::Ident>) {
+            match e.value {
+                Expr::LetBindings(ref binds, _) => {
+                    self.add_group(e, binds.iter().map(|bind| bind.span()));
+                    for bind in binds.iter() {
+                        self.add(bind.span(), FoldingRangeKind::Bindings);
+                    }
+                }
+                Expr::TypeBindings(ref binds, _) => {
+                    self.add_group(e, binds.iter().map(|bind| bind.span()));
+                    for bind in binds.iter() {
+                        self.add(bind.span(), FoldingRangeKind::Bindings);
+                    }
+                }
+                Expr::Match(_, ref alts) => for alt in alts.iter() {
+                    self.add(
+                        Span::with_id(
+                            alt.pattern.span.start,
+                            alt.expr.span.end,
+                            alt.pattern.span.expansion_id,
+                        ),
+                        FoldingRangeKind::Alternative,
+                    );
+                },
+                Expr::Record { .. } => self.add(e.span, FoldingRangeKind::Record),
+                _ => (),
+            }
+            walk_expr(self, e)
+        }
+    }
+
+    let mut folding = Folding {
+        source,
+        result: Vec::new(),
+    };
+    folding.visit_expr(expr);
+    folding.result.sort_by_key(|range| (range.start, range.end));
+    folding.result.dedup();
+    folding.result
+}
+
+/// A call from the binding `caller` to `callee`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallSite {
+    pub caller: Symbol,
+    pub callee: Symbol,
+    /// The span of the `callee` identifier at the call site
+    pub span: Span<BytePos>,
+}
+
+/// The calls between bindings in a typechecked expression.
+///
+/// Since typechecked expressions have all their identifiers renamed to unique symbols, calls to
+/// shadowed bindings are distinguished from each other.
+#[derive(Clone, Debug, Default)]
+pub struct CallHierarchy {
+    pub calls: Vec<CallSite>,
+}
+
+impl CallHierarchy {
+    /// Collects every call in `expr`. A call is attributed to the innermost named binding that
+    /// encloses it, calls which are not inside any binding are ignored.
+    pub fn new(expr: &SpannedExpr<Symbol>) -> CallHierarchy {
+        struct Calls {
+            callers: Vec<Symbol>,
+            calls: Vec<CallSite>,
+        }
+
+        impl Calls {
+            fn add(&mut self, callee: &Symbol, span: Span<BytePos>) {
+                if let Some(caller) = self.callers.last() {
+                    self.calls.push(CallSite {
+                        caller: caller.clone(),
+                        callee: callee.clone(),
+                        span,
+                    });
+                }
+            }
+        }
+
+        impl<'a, 'ast> Visitor<'a, 'ast> for Calls {
+            type Ident = Symbol;
+
+            fn visit_expr(&mut self, e: &'a SpannedExpr<'ast, Self::Ident>) {
+                match e.value {
+                    Expr::App(ref func, _) => {
+                        if let Expr::Ident(ref id) = func.value {
+                            self.add(&id.name, func.span);
+                        }
+                        walk_expr(self, e);
+                    }
+                    Expr::Infix(_, ref op, _) => {
+                        self.add(&op.value.name, op.span);
+                        walk_expr(self, e);
+                    }
+                    Expr::LetBindings(ref binds, ref body) => {
+                        for bind in binds.iter() {
+                            match bind.name.value {
+                                Pattern::Ident(ref id) => {
+                                    self.callers.push(id.name.clone());
+                                    self.visit_expr(&bind.expr);
+                                    self.callers.pop();
+                                }
+                                _ => self.visit_expr(&bind.expr),
+                            }
+                        }
+                        self.visit_expr(body);
+                    }
+                    _ => walk_expr(self, e),
+                }
+            }
+        }
+
+        let mut visitor = Calls {
+            callers: Vec::new(),
+            calls: Vec::new(),
+        };
+        visitor.visit_expr(expr);
+        CallHierarchy {
+            calls: visitor.calls,
+        }
+    }
+
+    /// Returns all calls made to `callee`
+    pub fn incoming_calls(&self, callee: &SymbolRef) -> Vec<&CallSite> {
+        self.calls
+            .iter()
+            .filter(|call| *call.callee == *callee)
+            .collect()
+    }
+
+    /// Returns all calls made from the body of `caller`
+    pub fn outgoing_calls(&self, caller: &SymbolRef) -> Vec<&CallSite> {
+        self.calls
+            .iter()
+            .filter(|call| *call.caller == *caller)
+            .collect()
+    }
+}
+
+/// Returns the spans of all expressions, patterns and types enclosing `pos`, ordered from the
+/// innermost to the outermost span. Suitable for implementing "expand selection" in an editor.
+pub fn selection_ranges(expr: &SpannedExpr<Symbol>, pos: BytePos) -> Vec<Span<BytePos>> {
+    let found = match complete_at((), expr, pos) {
+        Ok(found) => found,
+        Err(()) => return Vec::new(),
+    };
+    let mut spans: Vec<Span<BytePos>> = found
+        .match_
+        .iter()
+        .chain(found.enclosing_matches.iter().rev())
+        .map(|match_| match_.span())
+        .filter(|span| {
+            span.expansion_id == NO_EXPANSION && span.containment(&pos) == Ordering::Equal
+        })
+        .collect();
+    // Each span must strictly contain the previous one
+    spans.dedup();
+    let mut result: Vec<Span<BytePos>> = Vec::with_capacity(spans.len());
+    for span in spans {
+        if result.last().map_or(true, |last| span.contains(*last)) {
+            result.push(span);
+        }
+    }
+    result
+}
+
 pub fn suggest<T>(env: &T, expr: &SpannedExpr<Symbol>, pos: BytePos) -> Vec<Suggestion>
 where
     T: TypeEnv,
@@ -1055,7 +1857,7 @@ impl SuggestionQuery {
                 }
                 Match::Ident(_, ident, _) => match *enclosing_match {
                     Match::Expr(context) => match context.value {
-                        Expr::Projection(ref expr, _, _) => {
+                        Expr::Projection(ref expr, _, _, _) => {
                             let typ = resolve::remove_aliases(&env, expr.env_type_of(&env));
                             let id = ident.as_ref();
 
@@ -1186,19 +1988,27 @@ impl SuggestionQuery {
                 .chain(self.modules.iter().map(|s| &s[..]))
                 .filter(|module| self.filter(module, path.as_str()))
                 .map(|module| {
-                    let name = module[path.module().as_str().len()..]
+                    let prefix = path.module().as_str();
+                    let name = module[prefix.len()..]
                         .trim_left_matches('.')
                         .split('.')
                         .next()
                         .unwrap()
                         .to_string();
+                    // `std.prelude.small` is suggested as `prelude` so it has the type of
+                    // `std.prelude`
+                    let suggested_module = if prefix.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{}.{}", prefix, name)
+                    };
                     Suggestion {
-                        name,
                         typ: Either::Right(
-                            env.find_type(SymbolRef::new(module))
+                            env.find_type(SymbolRef::new(&suggested_module[..]))
                                 .cloned()
                                 .unwrap_or_else(Type::hole),
                         ),
+                        name,
                     }
                 }),
         );
@@ -1235,7 +2045,7 @@ impl SuggestionQuery {
 
                     Match::Ident(_, _, _) => match *enclosing_match {
                         Match::Expr(&Spanned {
-                            value: Expr::Projection(ref expr, _, _),
+                            value: Expr::Projection(ref expr, _, _, _),
                             ..
                         }) => if let Expr::Ident(ref expr_ident) = expr.value {
                             env.get(&expr_ident.name)
@@ -1299,7 +2109,7 @@ pub fn signature_help(
                     Expr::App(ref f, ref args) => f.try_type_of(env).ok().map(|typ| {
                         let name = match f.value {
                             Expr::Ident(ref id) => id.name.declared_name().to_string(),
-                            Expr::Projection(_, ref name, _) => name.declared_name().to_string(),
+                            Expr::Projection(_, ref name, _, _) => name.declared_name().to_string(),
                             _ => "".to_string(),
                         };
                         let index = if args.first().map_or(false, |arg| pos >= arg.span.start) {
@@ -1324,7 +2134,7 @@ pub fn signature_help(
                 Match::Expr(expr) => {
                     let name = match expr.value {
                         Expr::Ident(ref id) => id.name.declared_name().to_string(),
-                        Expr::Projection(_, ref name, _) => name.declared_name().to_string(),
+                        Expr::Projection(_, ref name, _, _) => name.declared_name().to_string(),
                         _ => "".to_string(),
                     };
 
@@ -1359,7 +2169,7 @@ pub fn get_metadata<'a>(
             },
             Match::Ident(_, id, _typ) => match enclosing_match {
                 Match::Expr(&Spanned {
-                    value: Expr::Projection(ref expr, _, _),
+                    value: Expr::Projection(ref expr, _, _, _),
                     ..
                 }) => if let Expr::Ident(ref expr_id) = expr.value {
                     env.get(&expr_id.name)
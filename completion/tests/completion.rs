@@ -8,11 +8,18 @@ extern crate gluon_check as check;
 extern crate gluon_completion as completion;
 extern crate gluon_parser as parser;
 
+use std::env;
+
+use base::kind::Kind;
 use base::metadata::Metadata;
 use base::pos::{BytePos, Span};
 use base::types::{ArcType, Field, Type};
 use base::source::Source;
 
+use completion::{FoldingRangeKind, SymbolKind};
+
+use either::Either;
+
 #[allow(unused)]
 mod support;
 use support::{intern, typ, MockEnv};
@@ -24,14 +31,14 @@ fn find_span_type(s: &str, pos: BytePos) -> Result<(Span<BytePos>, ArcType), ()>
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
     let extract = (completion::SpanAt, completion::TypeAt { env: &env });
-    completion::completion(extract, &expr, pos)
+    completion::completion(extract, expr.expr(), pos)
 }
 
 fn find_all_symbols(s: &str, pos: BytePos) -> Result<(String, Vec<Span<BytePos>>), ()> {
     let (expr, result) = support::typecheck_expr(s);
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    completion::find_all_symbols(&expr, pos)
+    completion::find_all_symbols(expr.expr(), pos)
 }
 
 fn find_type(s: &str, pos: BytePos) -> Result<ArcType, ()> {
@@ -52,8 +59,8 @@ fn get_metadata(s: &str, pos: BytePos) -> Option<Metadata> {
     let (expr, result) = support::typecheck_expr(s);
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    let (_, metadata_map) = check::metadata::metadata(&env, &expr);
-    completion::get_metadata(&metadata_map, &expr, pos).cloned()
+    let (_, metadata_map) = check::metadata::metadata(&env, expr.expr());
+    completion::get_metadata(&metadata_map, expr.expr(), pos).cloned()
 }
 
 fn suggest_metadata(s: &str, pos: BytePos, name: &str) -> Option<Metadata> {
@@ -61,8 +68,8 @@ fn suggest_metadata(s: &str, pos: BytePos, name: &str) -> Option<Metadata> {
 
     let (expr, _result) = support::typecheck_expr(s);
 
-    let (_, metadata_map) = check::metadata::metadata(&env, &expr);
-    completion::suggest_metadata(&metadata_map, &env, &expr, pos, name).cloned()
+    let (_, metadata_map) = check::metadata::metadata(&env, expr.expr());
+    completion::suggest_metadata(&metadata_map, &env, expr.expr(), pos, name).cloned()
 }
 
 #[test]
@@ -72,19 +79,19 @@ fn identifier() {
     let (expr, result) = support::typecheck_expr("let abc = 1 in abc");
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    let result = completion::find(&env, &expr, BytePos::from(15));
+    let result = completion::find(&env, expr.expr(), BytePos::from(15));
     let expected = Ok(typ("Int"));
     assert_eq!(result, expected);
 
-    let result = completion::find(&env, &expr, BytePos::from(16));
+    let result = completion::find(&env, expr.expr(), BytePos::from(16));
     let expected = Ok(typ("Int"));
     assert_eq!(result, expected);
 
-    let result = completion::find(&env, &expr, BytePos::from(17));
+    let result = completion::find(&env, expr.expr(), BytePos::from(17));
     let expected = Ok(typ("Int"));
     assert_eq!(result, expected);
 
-    let result = completion::find(&env, &expr, BytePos::from(18));
+    let result = completion::find(&env, expr.expr(), BytePos::from(18));
     let expected = Ok(typ("Int"));
     assert_eq!(result, expected);
 }
@@ -97,6 +104,45 @@ fn literal_string() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn type_at_without_env() {
+    let text = r#"
+let f x : Int -> Int = x
+f 1
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let pos = Source::new(text).lines().offset(2.into(), 0.into()).unwrap();
+    assert_eq!(
+        completion::type_at(expr.expr(), pos),
+        Some(Type::function(vec![typ("Int")], typ("Int")))
+    );
+
+    let pos = Source::new(text).lines().offset(2.into(), 2.into()).unwrap();
+    assert_eq!(completion::type_at(expr.expr(), pos), Some(typ("Int")));
+
+}
+
+#[test]
+fn kind_at_type() {
+    let text = r#"
+type Abc a = a Int
+1
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let pos = Source::new(text).lines().offset(1.into(), 13.into()).unwrap();
+    assert_eq!(
+        completion::kind_at(expr.expr(), pos),
+        Some(Kind::function(Kind::typ(), Kind::typ()))
+    );
+
+    let pos = Source::new(text).lines().offset(2.into(), 0.into()).unwrap();
+    assert_eq!(completion::kind_at(expr.expr(), pos), None);
+}
+
 #[test]
 fn in_let() {
     let result = find_type(
@@ -162,15 +208,15 @@ let (++) l r =
     );
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    let result = completion::find(&env, &expr, BytePos::from(57));
+    let result = completion::find(&env, expr.expr(), BytePos::from(57));
     let expected = Ok(Type::function(vec![typ("Int"), typ("Float")], typ("Int")));
     assert_eq!(result, expected);
 
-    let result = completion::find(&env, &expr, BytePos::from(54));
+    let result = completion::find(&env, expr.expr(), BytePos::from(54));
     let expected = Ok(typ("Int"));
     assert_eq!(result, expected);
 
-    let result = completion::find(&env, &expr, BytePos::from(59));
+    let result = completion::find(&env, expr.expr(), BytePos::from(59));
     let expected = Ok(typ("Float"));
     assert_eq!(result, expected);
 }
@@ -189,14 +235,14 @@ r.x
     );
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    let result = completion::find(&typ_env, &expr, BytePos::from(19));
+    let result = completion::find(&typ_env, expr.expr(), BytePos::from(19));
     let expected = Ok(Type::record(
         vec![],
         vec![Field::new(intern("x"), typ("Int"))],
     ));
     assert_eq!(result.map(support::close_record), expected);
 
-    let result = completion::find(&typ_env, &expr, BytePos::from(22));
+    let result = completion::find(&typ_env, expr.expr(), BytePos::from(22));
     let expected = Ok(typ("Int"));
     assert_eq!(result, expected);
 }
@@ -238,11 +284,11 @@ let id x = x
     let env = MockEnv::new();
     let extract = (completion::SpanAt, completion::TypeAt { env: &env });
 
-    let result = completion::completion(extract, &expr, BytePos::from(14));
+    let result = completion::completion(extract, expr.expr(), BytePos::from(14));
     let expected = Ok((Span::new(14.into(), 20.into()), Type::int()));
     assert_eq!(result, expected);
 
-    let result = completion::completion(extract, &expr, BytePos::from(15));
+    let result = completion::completion(extract, expr.expr(), BytePos::from(15));
     let expected = Ok((
         Span::new(15.into(), 17.into()),
         Type::function(vec![Type::int()], Type::int()),
@@ -264,7 +310,7 @@ x
     let env = MockEnv::new();
     let extract = (completion::SpanAt, completion::TypeAt { env: &env });
 
-    let result = completion::completion(extract, &expr, BytePos::from(5));
+    let result = completion::completion(extract, expr.expr(), BytePos::from(5));
     let expected = Ok((
         Span::new(5.into(), 10.into()),
         Type::record(
@@ -470,6 +516,28 @@ test #Int+ test #Int+ dummy
     );
 }
 
+#[test]
+fn find_definition() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let test = 1
+let f x = x #Int+ test
+f test
+"#;
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    assert_eq!(
+        completion::find_definition(expr.expr(), 39.into()),
+        Some(Span::new(5.into(), 9.into()))
+    );
+    assert_eq!(
+        completion::find_definition(expr.expr(), 24.into()),
+        Some(Span::new(20.into(), 21.into()))
+    );
+}
+
 #[test]
 fn all_symbols_test() {
     let _ = env_logger::init();
@@ -488,7 +556,205 @@ let { x, y } = { x = 1, y = 2 }
     let (expr, result) = support::typecheck_expr(text);
     assert!(result.is_ok(), "{}", result.unwrap_err());
 
-    let symbols = completion::all_symbols(&expr);
+    let symbols = completion::all_symbols(expr.expr());
 
     assert_eq!(symbols.len(), 4);
 }
+
+#[test]
+fn symbols_outline() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let test = 1
+let dummy x =
+    let inner = 3
+    inner
+type Abc a = a Int
+let { x, y } = { x = 1, y = 2 }
+1
+"#;
+
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let outline = completion::symbols(expr.expr());
+
+    let names = outline
+        .iter()
+        .map(|symbol| (symbol.name.as_str(), symbol.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            ("test", SymbolKind::Value),
+            ("dummy", SymbolKind::Function),
+            ("Abc", SymbolKind::Type),
+            ("x", SymbolKind::Value),
+            ("y", SymbolKind::Value),
+        ]
+    );
+
+    let dummy = &outline[1];
+    assert_eq!(dummy.children.len(), 1);
+    assert_eq!(dummy.children[0].name, "inner");
+    assert_eq!(dummy.children[0].typ, Either::Right(typ("Int")));
+    assert_eq!(dummy.name_span, Span::new(18.into(), 23.into()));
+}
+
+#[test]
+fn workspace_symbols() {
+    let _ = env_logger::init();
+
+    let text = r#"
+type Shape = | Circle Float | Square Float
+let area shape =
+    match shape with
+    | Circle r -> r
+    | Square s -> s
+let unit_square = Square 1.0
+let hidden = 1
+{ Shape, area, unit_square, scaled_area = \s -> area s }
+"#;
+
+    let (expr, result) = support::typecheck_expr(text);
+    let typ = result.unwrap_or_else(|err| panic!("{}", err));
+
+    let mut index = completion::SymbolIndex::new();
+    index.update("shapes", "shapes.glu", expr.expr(), &typ);
+
+    let path = env::temp_dir().join("gluon_completion_symbol_index");
+    index.save(&path).unwrap();
+    let index = completion::SymbolIndex::load(&path).unwrap();
+
+    let names = |query| {
+        completion::workspace_symbols(&index, query)
+            .into_iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.kind))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        names("area"),
+        [
+            ("area", SymbolKind::Function),
+            ("scaled_area", SymbolKind::Function),
+        ]
+    );
+    assert_eq!(names("usq"), [("unit_square", SymbolKind::Value)]);
+    assert_eq!(names("hidden"), []);
+
+    let shape = completion::workspace_symbols(&index, "Shape")[0];
+    assert_eq!(shape.module, "shapes");
+    assert_eq!(shape.file, "shapes.glu");
+    assert_eq!(shape.kind, SymbolKind::Type);
+    assert_eq!(shape.span, Span::new(6.into(), 11.into()));
+    assert_eq!(shape.typ, "Type");
+
+    let area = completion::workspace_symbols(&index, "area")[0];
+    assert_eq!(area.span, Span::new(48.into(), 52.into()));
+    assert_eq!(area.typ, "test.Shape -> Float");
+
+    let unit_square = completion::workspace_symbols(&index, "unit_square")[0];
+    assert_eq!(unit_square.typ, "test.Shape");
+}
+
+#[test]
+fn folding_ranges() {
+    let _ = env_logger::init();
+
+    let text = r#"
+// A comment
+// spanning two lines
+let test =
+    1
+let record = {
+    x = 1,
+    y = 2,
+}
+match record with
+| { x, y } ->
+    x
+"#;
+
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let ranges = completion::folding_ranges(&Source::new(text), expr.expr())
+        .into_iter()
+        .map(|range| (range.start.to_usize(), range.end.to_usize(), range.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ranges,
+        [
+            (1, 2, FoldingRangeKind::Comment),
+            (3, 4, FoldingRangeKind::Bindings),
+            (5, 8, FoldingRangeKind::Bindings),
+            (5, 8, FoldingRangeKind::Record),
+            (10, 11, FoldingRangeKind::Alternative),
+        ]
+    );
+}
+
+#[test]
+fn selection_ranges() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let f x = x
+f (f 2)
+"#;
+
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let ranges = completion::selection_ranges(expr.expr(), 16.into());
+    assert_eq!(
+        ranges,
+        [
+            Span::new(16.into(), 17.into()),
+            Span::new(16.into(), 19.into()),
+            Span::new(15.into(), 20.into()),
+            Span::new(13.into(), 20.into()),
+            Span::new(1.into(), 20.into()),
+        ]
+    );
+}
+
+#[test]
+fn call_hierarchy() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let f x = x
+let g y = f y
+let h z =
+    let f x = g x
+    f (g z)
+h 1
+"#;
+
+    let (expr, result) = support::typecheck_expr(text);
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let calls = completion::CallHierarchy::new(expr.expr());
+    let g = calls
+        .calls
+        .iter()
+        .find(|call| call.caller.declared_name() == "g")
+        .map(|call| call.caller.clone())
+        .unwrap();
+
+    let incoming = calls
+        .incoming_calls(&g)
+        .into_iter()
+        .map(|call| call.caller.declared_name())
+        .collect::<Vec<_>>();
+    // The inner `f` is a separate symbol from the outer `f`
+    assert_eq!(incoming, ["f", "h"]);
+
+    let outgoing = calls.outgoing_calls(&g);
+    assert_eq!(outgoing.len(), 1);
+    assert_eq!(outgoing[0].callee.declared_name(), "f");
+    assert_eq!(outgoing[0].span, Span::new(23.into(), 24.into()));
+    assert!(calls.incoming_calls(&outgoing[0].callee).len() == 1);
+}
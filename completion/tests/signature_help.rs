@@ -22,7 +22,7 @@ fn signature_help(expr_str: &str, row: usize, column: usize) -> Option<Signature
         .offset(row.into(), column.into())
         .expect("Position is not in source");
     let (expr, _result) = support::typecheck_partial_expr(expr_str);
-    completion::signature_help(&support::MockEnv::new(), &expr, offset)
+    completion::signature_help(&support::MockEnv::new(), expr.expr(), offset)
 }
 
 #[test]
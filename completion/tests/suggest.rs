@@ -32,10 +32,10 @@ fn suggest_query(query: &SuggestionQuery, s: &str, pos: BytePos) -> Result<Vec<S
 
     struct ReplaceImport;
 
-    impl MutVisitor for ReplaceImport {
+    impl<'ast> MutVisitor<'ast> for ReplaceImport {
         type Ident = Symbol;
 
-        fn visit_expr(&mut self, expr: &mut SpannedExpr<Symbol>) {
+        fn visit_expr(&mut self, expr: &mut SpannedExpr<'ast, Symbol>) {
             let replacement = match expr.value {
                 Expr::App(ref id, ref args) => match id.value {
                     Expr::Ident(ref id) if id.name.declared_name() == "import!" => {
@@ -59,9 +59,9 @@ fn suggest_query(query: &SuggestionQuery, s: &str, pos: BytePos) -> Result<Vec<S
 
     let (mut expr, _result) = support::typecheck_partial_expr(s);
 
-    ReplaceImport.visit_expr(&mut expr);
+    expr.with_mut(|_, expr| ReplaceImport.visit_expr(expr));
 
-    let mut vec = query.suggest(&env, &expr, pos);
+    let mut vec = query.suggest(&env, expr.expr(), pos);
     vec.sort_by(|l, r| l.name.cmp(&r.name));
     Ok(vec)
 }
@@ -130,6 +130,22 @@ let f test =
     assert_eq!(result, expected);
 }
 
+#[test]
+fn suggest_after_syntax_error_in_binding() {
+    let _ = env_logger::init();
+
+    let text = r#"
+let test = 1
+let x : = 2
+let tes = ""
+te
+"#;
+    let result = suggest_loc(text, 4, 2);
+    let expected = Ok(vec!["tes".into(), "test".into()]);
+
+    assert_eq!(result, expected);
+}
+
 #[test]
 fn suggest_after_unrelated_type_error() {
     let _ = env_logger::init();
@@ -702,8 +718,8 @@ match Abc 1 with
     let env = MockEnv::new();
 
     let (mut expr, _result) = support::typecheck_partial_expr(text);
-    expr.span.expansion_id = pos::UNKNOWN_EXPANSION;
-    let result: Vec<_> = completion::suggest(&env, &expr, 42.into())
+    expr.with_mut(|_, expr| expr.span.expansion_id = pos::UNKNOWN_EXPANSION);
+    let result: Vec<_> = completion::suggest(&env, expr.expr(), 42.into())
         .into_iter()
         .map(|s| s.name)
         .collect();
@@ -726,8 +742,8 @@ match Abc 1 with
     let env = MockEnv::new();
 
     let (mut expr, _result) = support::typecheck_partial_expr(text);
-    expr.span.expansion_id = pos::UNKNOWN_EXPANSION;
-    let result: Vec<_> = completion::suggest(&env, &expr, 74.into())
+    expr.with_mut(|_, expr| expr.span.expansion_id = pos::UNKNOWN_EXPANSION);
+    let result: Vec<_> = completion::suggest(&env, expr.expr(), 74.into())
         .into_iter()
         .map(|s| s.name)
         .collect();
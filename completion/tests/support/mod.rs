@@ -1,6 +1,6 @@
 #![allow(unused)]
 
-use base::ast::SpannedExpr;
+use base::ast::RootExpr;
 use base::error::InFile;
 use base::kind::{ArcKind, Kind, KindEnv};
 use base::metadata::{Metadata, MetadataEnv};
@@ -32,13 +32,11 @@ pub fn intern(s: &str) -> Symbol {
     }
 }
 
-pub fn parse_new(
-    s: &str,
-) -> Result<SpannedExpr<Symbol>, (Option<SpannedExpr<Symbol>>, ParseErrors)> {
+pub fn parse_new(s: &str) -> Result<RootExpr<Symbol>, (Option<RootExpr<Symbol>>, ParseErrors)> {
     let symbols = get_local_interner();
     let mut symbols = symbols.borrow_mut();
     let mut module = SymbolModule::new("test".into(), &mut symbols);
-    parse_partial_expr(&mut module, &TypeCache::new(), s)
+    RootExpr::try_new(|arena| parse_partial_expr(arena, &mut module, &TypeCache::new(), s))
 }
 
 pub struct MockEnv {
@@ -111,10 +109,7 @@ impl MetadataEnv for MockEnv {
 pub fn typecheck_expr_expected(
     text: &str,
     expected: Option<&ArcType>,
-) -> (
-    SpannedExpr<Symbol>,
-    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
-) {
+) -> (RootExpr<Symbol>, Result<ArcType, InFile<typecheck::HelpError<Symbol>>>) {
     let mut expr = parse_new(text).unwrap_or_else(|(_, err)| panic!("{}", err));
 
     let env = MockEnv::new();
@@ -122,26 +117,20 @@ pub fn typecheck_expr_expected(
     let mut interner = interner.borrow_mut();
     let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
 
-    let result = tc.typecheck_expr_expected(&mut expr, expected);
+    let result = expr.with_mut(|_, expr| tc.typecheck_expr_expected(expr, expected));
 
     (expr, result.map_err(|err| InFile::new("test", text, err)))
 }
 
 pub fn typecheck_expr(
     text: &str,
-) -> (
-    SpannedExpr<Symbol>,
-    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
-) {
+) -> (RootExpr<Symbol>, Result<ArcType, InFile<typecheck::HelpError<Symbol>>>) {
     typecheck_expr_expected(text, None)
 }
 
 pub fn typecheck_partial_expr(
     text: &str,
-) -> (
-    SpannedExpr<Symbol>,
-    Result<ArcType, InFile<typecheck::HelpError<Symbol>>>,
-) {
+) -> (RootExpr<Symbol>, Result<ArcType, InFile<typecheck::HelpError<Symbol>>>) {
     let mut expr = match parse_new(text) {
         Ok(e) | Err((Some(e), _)) => e,
         Err((None, err)) => panic!("{}", err),
@@ -152,7 +141,7 @@ pub fn typecheck_partial_expr(
     let mut interner = interner.borrow_mut();
     let mut tc = Typecheck::new("test".into(), &mut interner, &env, TypeCache::new());
 
-    let result = tc.typecheck_expr(&mut expr);
+    let result = expr.with_mut(|_, expr| tc.typecheck_expr(expr));
 
     (expr, result.map_err(|err| InFile::new("test", text, err)))
 }
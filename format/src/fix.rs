@@ -0,0 +1,62 @@
+//! Span accurate rewrites of source code, used to migrate code away from deprecated bindings and
+//! syntax. Only the text covered by each edit is changed so the rest of the input keeps its
+//! layout and comments.
+
+use base::ast::Arena;
+use base::pos::{BytePos, Span, NO_EXPANSION};
+use base::symbol::Symbols;
+use base::types::TypeCache;
+
+use Error;
+
+/// Replaces the text of the input within `span` with `replacement`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub span: Span<BytePos>,
+    pub replacement: String,
+}
+
+/// Returns an edit which renames `old` to `new` at `span`. `None` is returned if `span` does not
+/// cover the text `old` in `input`, for instance because it points into a macro expansion or at a
+/// binding which was given another name when it was destructured.
+pub fn rename(input: &str, span: Span<BytePos>, old: &str, new: &str) -> Option<Edit> {
+    if span.expansion_id != NO_EXPANSION {
+        return None;
+    }
+    let (start, end) = (span.start.to_usize(), span.end.to_usize());
+    match input.get(start..end) {
+        Some(text) if text == old => Some(Edit {
+            span,
+            replacement: new.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Applies `edits` to `input`. Duplicated edits are only applied once and the resulting code is
+/// checked to still parse.
+pub fn apply_edits(input: &str, mut edits: Vec<Edit>) -> Result<String, Error> {
+    edits.sort_by_key(|edit| (edit.span.start, edit.span.end));
+    edits.dedup();
+
+    let mut output = String::with_capacity(input.len());
+    let mut last_end = 0;
+    for edit in &edits {
+        let (start, end) = (edit.span.start.to_usize(), edit.span.end.to_usize());
+        if start < last_end {
+            return Err(Error::OverlappingEdits);
+        }
+        output.push_str(&input[last_end..start]);
+        output.push_str(&edit.replacement);
+        last_end = end;
+    }
+    output.push_str(&input[last_end..]);
+
+    if !edits.is_empty() {
+        let type_cache = TypeCache::new();
+        let arena = Arena::new();
+        ::parser::parse_expr(&arena, &mut Symbols::new(), &type_cache, &output)
+            .map_err(Error::InvalidOutput)?;
+    }
+    Ok(output)
+}
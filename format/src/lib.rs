@@ -6,15 +6,85 @@ extern crate gluon_base as base;
 extern crate gluon_parser as parser;
 extern crate itertools;
 extern crate pretty;
+#[macro_use]
+extern crate quick_error;
+
+use itertools::Itertools;
 
-use base::ast::SpannedExpr;
+use base::ast::{walk_expr, Arena, Comment, Expr, SpannedComment, SpannedExpr, Visitor};
 use base::source::Source;
 use base::symbol::{Symbol, Symbols};
 use base::types::TypeCache;
 
+pub mod fix;
 mod pretty_print;
 
+quick_error! {
+    /// Error type for the formatter
+    #[derive(Debug)]
+    pub enum Error {
+        /// The input could not be parsed
+        Parse(err: parser::ParseErrors) {
+            description("parse error")
+            display("{}", err)
+            from()
+        }
+        /// The formatted code could not be parsed
+        InvalidOutput(err: parser::ParseErrors) {
+            description("the formatted code could not be parsed")
+            display("the formatted code could not be parsed: {}", err)
+        }
+        /// Formatting the formatted code changed it again
+        NotIdempotent {
+            description("formatting the formatted code changed it")
+            display("formatting the formatted code changed it")
+        }
+        /// Two edits given to `fix::apply_edits` replace the same part of the input
+        OverlappingEdits {
+            description("edits overlap")
+            display("two edits replace the same part of the input")
+        }
+        /// Comments of the input are missing from the formatted code
+        LostComments(comments: Vec<String>) {
+            description("comments were lost while formatting")
+            display(
+                "the formatted code is missing the comments: {}",
+                comments.iter().map(|comment| format!("`{}`", comment)).format(", ")
+            )
+        }
+    }
+}
+
+/// Settings which control the layout of formatted code
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatSettings {
+    /// The width which the formatter tries to fit each line within
+    pub max_width: usize,
+    /// The number of spaces which nested code is indented with
+    pub indent: usize,
+    /// Whether records which are written over several lines end with a comma after the last field
+    pub record_trailing_comma: bool,
+}
+
+impl Default for FormatSettings {
+    fn default() -> FormatSettings {
+        FormatSettings {
+            max_width: 100,
+            indent: 4,
+            record_trailing_comma: true,
+        }
+    }
+}
+
 pub fn pretty_expr(expr: &SpannedExpr<Symbol>, input: &str) -> String {
+    pretty_expr_with(&FormatSettings::default(), expr, input)
+}
+
+pub fn pretty_expr_with(
+    settings: &FormatSettings,
+    expr: &SpannedExpr<Symbol>,
+    input: &str,
+) -> String {
     let newline = match input.find(|c: char| c == '\n' || c == '\r') {
         Some(i) => {
             if input[i..].starts_with("\r\n") {
@@ -30,13 +100,91 @@ pub fn pretty_expr(expr: &SpannedExpr<Symbol>, input: &str) -> String {
 
     let source = Source::new(input);
     let arena = pretty::Arena::new();
-    let printer = pretty_print::Printer::new(&arena, &source);
-    printer.format(100, newline, &expr)
+    let printer = pretty_print::Printer::new(&arena, &source, settings);
+    printer.format(newline, &expr)
+}
+
+pub fn format_expr(input: &str) -> Result<String, Error> {
+    format_expr_with(&FormatSettings::default(), input)
 }
 
-pub fn format_expr(input: &str) -> Result<String, parser::ParseErrors> {
+/// Formats `input` according to `settings`. The formatted code is checked to contain every
+/// comment which the parser attached to the input and to be left unchanged if it is formatted
+/// again.
+pub fn format_expr_with(settings: &FormatSettings, input: &str) -> Result<String, Error> {
     let type_cache = TypeCache::new();
-    let expr = parser::parse_expr(&mut Symbols::new(), &type_cache, input)?;
+    let arena = Arena::new();
+    let expr = parser::parse_expr(&arena, &mut Symbols::new(), &type_cache, input)?;
+    let output = pretty_expr_with(settings, &expr, input);
+
+    let formatted_expr = parser::parse_expr(&arena, &mut Symbols::new(), &type_cache, &output)
+        .map_err(Error::InvalidOutput)?;
+    // Formatting may change which node a comment is attached to but it must not drop it
+    let mut lost_comments = comments(&expr);
+    for comment in comments(&formatted_expr) {
+        if let Some(i) = lost_comments.iter().position(|lost| *lost == comment) {
+            lost_comments.swap_remove(i);
+        }
+    }
+    if !lost_comments.is_empty() {
+        return Err(Error::LostComments(
+            lost_comments.into_iter().map(String::from).collect(),
+        ));
+    }
+
+    if pretty_expr_with(settings, &formatted_expr, &output) != output {
+        return Err(Error::NotIdempotent);
+    }
+    Ok(output)
+}
+
+/// Returns the contents of the comments of every binding, record field and match alternative in
+/// `expr`
+fn comments<'a>(expr: &'a SpannedExpr<Symbol>) -> Vec<&'a str> {
+    struct Comments<'a>(Vec<&'a str>);
+
+    impl<'a> Comments<'a> {
+        fn add(&mut self, doc_comment: &'a Option<Comment>, comments: &'a [SpannedComment]) {
+            self.0.extend(doc_comment.iter().map(|comment| &comment.content[..]));
+            self.0
+                .extend(comments.iter().map(|comment| &comment.value.content[..]));
+        }
+    }
+
+    impl<'a, 'ast> Visitor<'a, 'ast> for Comments<'a> {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+            match expr.value {
+                Expr::LetBindings(ref binds, _) => for bind in binds.iter() {
+                    self.add(&bind.comment, &bind.comments);
+                },
+                Expr::TypeBindings(ref binds, _) => for bind in binds.iter() {
+                    self.add(&bind.comment, &bind.comments);
+                },
+                Expr::Record {
+                    ref types,
+                    ref exprs,
+                    ..
+                } => {
+                    for field in types.iter() {
+                        self.add(&field.comment, &field.comments);
+                    }
+                    for field in exprs.iter() {
+                        self.add(&field.comment, &field.comments);
+                    }
+                }
+                Expr::Match(_, ref alts) => for alt in alts.iter() {
+                    self.0
+                        .extend(alt.comments.iter().map(|comment| &comment.value.content[..]));
+                },
+                _ => (),
+            }
+            walk_expr(self, expr);
+        }
+    }
 
-    Ok(pretty_expr(&expr, input))
+    let mut comments = Comments(Vec::new());
+    comments.visit_expr(expr);
+    comments.0
 }
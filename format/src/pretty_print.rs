@@ -3,52 +3,72 @@ use std::{iter, ops};
 use itertools::{Either, Itertools};
 use pretty::{Arena, DocAllocator, DocBuilder};
 
-use base::ast::{Do, Expr, Pattern, SpannedExpr, SpannedPattern, ValueBinding};
+use base::ast::{Attribute, Do, Expr, Lambda, Pattern, SpannedExpr, SpannedIdent,
+                SpannedPattern, ValueBinding};
 use base::kind::Kind;
 use base::pos::{self, BytePos, HasSpan, Span, Spanned};
 use base::source;
 use base::types::{self, Prec, Type};
 use self::types::pretty_print as pretty_types;
 
-const INDENT: usize = 4;
+use FormatSettings;
+
+/// The name of the function which `loop` expressions are translated into by the parser
+const LOOP_ID: &str = "loop#";
+
+/// Prefix of the arguments which the parser introduces in place of pattern arguments
+const PATTERN_ARG_PREFIX: &str = "arg:";
+
+/// Prefix of the argument of the lambdas which operator sections are translated into by the parser
+const SECTION_PREFIX: &str = "section:";
 
 macro_rules! newlines_iter {
     ($self_: ident, $iterable: expr) => {
         $iterable
             .into_iter()
             .tuple_windows()
-            .map(|(prev, next)| $self_.comments(Span::new(prev.end, next.start)))
+            .map(|(prev, next)| $self_.comments_after_code(Span::new(prev.end, next.start)))
     }
 }
 
-macro_rules! rev_newlines_iter {
-    ($self_: ident, $iterable: expr) => {
-        $iterable
-            .into_iter()
-            .tuple_windows()
-            .map(|(prev, next)| $self_.rev_comments(Span::new(prev.end, next.start)))
-    }
+fn attributes<'a>(arena: &'a Arena<'a>, attributes: &'a [Attribute]) -> DocBuilder<'a, Arena<'a>> {
+    arena.concat(attributes.iter().map(|attr| {
+        chain![arena;
+            "#[",
+            attr.name.as_str(),
+            match attr.arguments {
+                Some(ref arguments) => chain![arena; "(", arguments.as_str(), ")"],
+                None => arena.nil(),
+            },
+            "]",
+            arena.newline()
+        ]
+    }))
 }
 
-pub(super) struct Printer<'a: 'e, 'e, I: 'a>(pretty_types::Printer<'a, 'e, I>);
+pub(super) struct Printer<'a: 'e, 'e, I: 'a> {
+    printer: pretty_types::Printer<'a, 'e, I>,
+    settings: &'e FormatSettings,
+}
 
 impl<'a: 'e, 'e, I> Printer<'a, 'e, I>
 where
     I: AsRef<str>,
 {
-    pub(super) fn new(arena: &'a Arena<'a>, source: &'e source::Source<'a>) -> Self {
-        Printer(pretty_types::Printer::new(arena, source))
+    pub(super) fn new(
+        arena: &'a Arena<'a>,
+        source: &'e source::Source<'a>,
+        settings: &'e FormatSettings,
+    ) -> Self {
+        let mut printer = pretty_types::Printer::new(arena, source);
+        printer.indent = settings.indent;
+        Printer { printer, settings }
     }
 
-    pub(super) fn format(
-        &self,
-        width: usize,
-        newline: &'a str,
-        expr: &'a SpannedExpr<I>,
-    ) -> String {
+    pub(super) fn format(&self, newline: &'a str, expr: &'a SpannedExpr<I>) -> String {
         self.pretty_expr(expr)
             .1
-            .pretty(width)
+            .pretty(self.settings.max_width)
             .to_string()
             .lines()
             .map(|s| format!("{}{}", s.trim_right(), newline))
@@ -65,20 +85,31 @@ where
 
     fn pretty_expr_with_shebang_line(&self, expr: &'a SpannedExpr<I>) -> DocBuilder<'a, Arena<'a>> {
         let arena = self.arena;
-        match self.find_shebang_line() {
-            Some(shebang_line) => arena
-                .text(shebang_line)
-                .append(self.pretty_expr_(BytePos::from(shebang_line.len()), expr)),
+        match self.find_header() {
+            Some(header) => arena
+                .concat(header.lines().map(|line| arena.text(line)).intersperse(arena.newline()))
+                .append(self.pretty_expr_(BytePos::from(header.len()), expr)),
             None => self.pretty_expr_(BytePos::from(0), expr),
         }
     }
 
-    fn find_shebang_line(&self) -> Option<&'a str> {
+    /// Returns the shebang line and the `#![name]` module attributes at the start of the source
+    fn find_header(&self) -> Option<&'a str> {
         let src = self.source.src();
-        if src.starts_with("#!") {
-            src.lines().next()
-        } else {
+        let mut end = 0;
+        let mut offset = 0;
+        for line in src.lines() {
+            if line.starts_with("#!") {
+                end = offset + line.len();
+            } else if !line.trim().is_empty() {
+                break;
+            }
+            offset += line.len() + 1;
+        }
+        if end == 0 {
             None
+        } else {
+            Some(&src[..end])
         }
     }
 
@@ -91,17 +122,17 @@ where
 
         let pretty = |next: &'a SpannedExpr<_>| self.pretty_expr_(next.span.start, next);
 
-        let comments = self.comments(Span::new(previous_end, expr.span.start));
+        let comments = self.comments_after_code(Span::new(previous_end, expr.span.start));
         let doc = match expr.value {
             Expr::App(ref func, ref args) => {
-                let arg_iter = iter::once(&**func).chain(args).tuple_windows().map(
+                let arg_iter = iter::once(&**func).chain(args.iter()).tuple_windows().map(
                     |(prev, arg)| {
                         self.space(Span::new(prev.span.end, arg.span.start))
                             .append(pretty(arg))
                     },
                 );
                 pretty(func)
-                    .append(arena.concat(arg_iter).nest(INDENT))
+                    .append(arena.concat(arg_iter).nest(self.settings.indent))
                     .group()
             }
             Expr::Array(ref array) => arena
@@ -134,14 +165,17 @@ where
                     }
                 }))
             },
-            Expr::Ident(ref id) => pretty_types::ident(arena, id.name.as_ref()),
+            Expr::Ident(ref id) | Expr::ImplicitApp(ref id, _) => match id.name.as_ref() {
+                LOOP_ID => arena.text("continue"),
+                name => pretty_types::ident(arena, name),
+            },
             Expr::IfElse(ref body, ref if_true, ref if_false) => {
                 let space = newline(arena, expr);
                 chain![arena;
                     arena.text("if ").append(pretty(body)).group(),
                     arena.space(),
                     "then",
-                    space.clone().append(pretty(if_true)).nest(INDENT).group(),
+                    space.clone().append(pretty(if_true)).nest(self.settings.indent).group(),
                     space.clone(),
                     "else",
                     self.pretty_else_expr(space, if_false)
@@ -154,39 +188,93 @@ where
                         op.value.name.as_ref(),
                         " ",
                         pretty(r).group()
-                    ].nest(INDENT)
+                    ].nest(self.settings.indent)
                 ],
+            Expr::Lambda(ref lambda) if section(lambda).is_some() => {
+                match section(lambda).unwrap() {
+                    Section::Left(lhs, op) => chain![arena;
+                        "(",
+                        pretty(lhs),
+                        " ",
+                        op,
+                        ")"
+                    ],
+                    Section::Right(op, rhs) => chain![arena;
+                        "(",
+                        op,
+                        " ",
+                        pretty(rhs),
+                        ")"
+                    ],
+                    Section::Projection(field) => chain![arena;
+                        "(.",
+                        pretty_types::ident(arena, field),
+                        ")"
+                    ],
+                }
+            }
             Expr::Lambda(_) => {
                 let (arguments, body) = self.pretty_lambda(previous_end, expr);
                 arguments.group().append(body)
             }
+            Expr::LetBindings(ref binds, ref body) if loop_init(binds, body).is_some() => {
+                let init = loop_init(binds, body).unwrap();
+                let bind = &binds[0];
+                chain![arena;
+                    chain![arena;
+                        "loop ",
+                        bind.args[0].value.name.as_ref(),
+                        " =",
+                        self.hang(arena.nil(), init).group()
+                    ].group(),
+                    self.pretty_expr_(init.span.end, &bind.expr).group()
+                ]
+            }
             Expr::LetBindings(ref binds, ref body) => {
                 let binding = |prefix: &'a str, bind: &'a ValueBinding<I>| {
-                    let decl = chain![arena;
-                        prefix,
-                        chain![arena;
-                            self.pretty_pattern(&bind.name),
-                            " ",
-                            arena.concat(bind.args.iter().map(|arg| {
-                                arena.text(arg.value.name.as_ref()).append(" ")
-                            }))
-                        ].group(),
-                        match bind.typ {
-                            None => arena.nil(),
-                            Some(ref typ) => arena.text(": ")
-                                .append(types::pretty_print(self, typ))
-                                .append(self.space_after(typ.span().end)),
-                        },
-                        "="
-                    ];
+                    let clauses = function_clauses(&bind.args, &bind.expr);
+                    let clauses = clauses.into_iter().enumerate().map(|(i, (args, body))| {
+                        // Only the first equation of a function has the `and` prefix and the type
+                        let (prefix, typ) = if i == 0 {
+                            (prefix, bind.typ.as_ref())
+                        } else {
+                            ("let ", None)
+                        };
+                        let decl = chain![arena;
+                            prefix,
+                            chain![arena;
+                                self.pretty_pattern(&bind.name),
+                                " ",
+                                arena.concat(bind.implicit_args.iter().map(|arg| {
+                                    chain![arena;
+                                        "?",
+                                        pretty_types::ident(arena, arg.value.name.as_ref()),
+                                        " "
+                                    ]
+                                })),
+                                arena.concat(args.into_iter().map(|arg| {
+                                    self.pretty_argument(arg).append(" ")
+                                }))
+                            ].group(),
+                            match typ {
+                                None => arena.nil(),
+                                Some(typ) => arena.text(": ")
+                                    .append(types::pretty_print(self, typ))
+                                    .append(self.space_after(typ.span().end)),
+                            },
+                            "="
+                        ];
+                        self.hang(decl, body).group()
+                    });
                     chain![arena;
                         pretty_types::doc_comment(arena, bind.comment.as_ref()),
-                        self.hang(decl, &bind.expr).group()
+                        attributes(arena, &bind.attributes),
+                        arena.concat(clauses.intersperse(arena.newline()))
                     ]
                 };
                 let prefixes = iter::once("let ").chain(iter::repeat("and "));
                 chain![arena;
-                    arena.concat(prefixes.zip(binds).map(|(prefix, bind)| {
+                    arena.concat(prefixes.zip(binds.iter()).map(|(prefix, bind)| {
                         binding(prefix, bind)
                     }).interleave(newlines_iter!(self, binds.iter().map(|bind| bind.span())))),
                     self.pretty_expr_(binds.last().unwrap().span().end, body).group()
@@ -211,7 +299,7 @@ where
                         ]
                     }).intersperse(arena.newline()))
                 ],
-            Expr::Projection(ref expr, ref field, _) => chain![arena;
+            Expr::Projection(ref expr, ref field, _, _) => chain![arena;
                     pretty(expr),
                     ".",
                     pretty_types::ident(arena, field.as_ref())
@@ -236,6 +324,7 @@ where
                 let prefixes = iter::once("type").chain(iter::repeat("and"));
                 chain![arena;
                     pretty_types::doc_comment(arena, binds.first().unwrap().comment.as_ref()),
+                    attributes(arena, &binds.first().unwrap().attributes),
                     arena.concat(binds.iter().zip(prefixes).map(|(bind, prefix)| {
                         let typ = bind.alias.value.unresolved_type();
                         let typ = match **typ {
@@ -246,7 +335,7 @@ where
                         let mut type_doc = types::pretty_print(self, typ);
                         match **typ {
                             Type::Record(_) => (),
-                            _ => type_doc = type_doc.nest(INDENT),
+                            _ => type_doc = type_doc.nest(self.settings.indent),
                         }
                         chain![arena;
                             prefix,
@@ -335,12 +424,12 @@ where
                     arena.text(" if ").append(pretty(body)).group(),
                     arena.space(),
                     "then",
-                    space.clone().append(pretty(if_true)).nest(INDENT).group(),
+                    space.clone().append(pretty(if_true)).nest(self.settings.indent).group(),
                     space.clone(),
                     "else",
                     self.pretty_else_expr(space, if_false)
                 ],
-            _ => space.append(pretty(if_false)).nest(INDENT).group(),
+            _ => space.append(pretty(if_false)).nest(self.settings.indent).group(),
         }
     }
 
@@ -352,14 +441,15 @@ where
         let arena = self.arena;
         match expr.value {
             Expr::Lambda(ref lambda) => {
+                let (args, body) = pattern_arguments(&lambda.args, &lambda.body);
                 let decl = chain![arena;
                     "\\",
-                    arena.concat(lambda.args.iter().map(|arg| {
-                        arena.text(arg.value.name.as_ref()).append(" ")
+                    arena.concat(args.into_iter().map(|arg| {
+                        self.pretty_argument(arg).append(" ")
                     })),
                     "->"
                 ];
-                let (next_lambda, body) = self.pretty_lambda(lambda.body.span.start, &lambda.body);
+                let (next_lambda, body) = self.pretty_lambda(body.span.start, body);
                 if next_lambda.1 == arena.nil().1 {
                     let decl = decl.append(self.space_before(lambda.body.span.start));
                     (decl, body)
@@ -398,17 +488,12 @@ where
                     })
                 };
 
-                // Preserve comments before and after the comma
-                let newlines = newlines_iter!(self, spans())
-                    .zip(rev_newlines_iter!(self, spans()))
-                    .collect::<Vec<_>>();
-
                 let mut line = newline(arena, expr);
                 // If there are any explicit line breaks then we need put each field on a separate
                 // line
-                let newline_in_fields = newlines
-                    .iter()
-                    .any(|&(ref l, ref r)| l.1 != arena.nil().1 || r.1 != arena.nil().1);
+                let newline_in_fields = spans().tuple_windows().any(|(prev, next)| {
+                    self.source.src()[prev.end.to_usize()..next.start.to_usize()].contains('\n')
+                });
                 let newline_in_base = base.as_ref().map_or(false, |base| {
                     self.space_before(base.span.start).1 != arena.nil().1
                 });
@@ -428,6 +513,7 @@ where
                             ),
                             Either::Right(r) => {
                                 let id = pretty_types::ident(arena, r.name.value.as_ref());
+                                let id = if r.strict { arena.text("!").append(id) } else { id };
                                 pos::spanned(
                                     r.name.span,
                                     match r.value {
@@ -448,6 +534,7 @@ where
                     ))
                     .append(if (!exprs.is_empty() || !types.is_empty())
                         && line.1 == arena.newline().1
+                        && self.settings.record_trailing_comma
                     {
                         arena.text(",")
                     } else {
@@ -469,7 +556,7 @@ where
                         }
                         None => arena.nil(),
                     })
-                    .nest(INDENT)
+                    .nest(self.settings.indent)
                     .append(self.whitespace(
                         Span::new(last_element_end, expr.span.end),
                         line.clone(),
@@ -502,6 +589,16 @@ where
         }
     }
 
+    fn pretty_argument(
+        &self,
+        arg: Either<&'a SpannedIdent<I>, &'a SpannedPattern<I>>,
+    ) -> DocBuilder<'a, Arena<'a>> {
+        match arg {
+            Either::Left(arg) => self.arena.text(arg.value.name.as_ref()),
+            Either::Right(pattern) => self.pretty_pattern_(pattern, Prec::Constructor),
+        }
+    }
+
     fn pretty_pattern(&self, pattern: &'a SpannedPattern<I>) -> DocBuilder<'a, Arena<'a>> {
         self.pretty_pattern_(pattern, Prec::Top)
     }
@@ -564,7 +661,7 @@ where
                         })),
                     |spanned| spanned.value,
                 );
-                let doc = arena.concat(iter).nest(INDENT);
+                let doc = arena.concat(iter).nest(self.settings.indent);
                 chain![arena;
                     "{",
                     doc,
@@ -624,7 +721,7 @@ where
                     body
                 ].group();
                 if needs_indent {
-                    doc.nest(INDENT)
+                    doc.nest(self.settings.indent)
                 } else {
                     doc
                 }
@@ -635,7 +732,7 @@ where
                             arguments
                         ].group()
                     .append(body)
-                    .nest(INDENT),
+                    .nest(self.settings.indent),
             ).group(),
         }
     }
@@ -664,21 +761,21 @@ where
         self.comments_count(span).0
     }
 
-    fn rev_comments(&self, span: Span<BytePos>) -> DocBuilder<'a, Arena<'a>> {
-        let arena = self.arena;
-        self.source
-            .comments_between(span)
-            .rev()
-            .map(|comment| {
-                if comment.is_empty() {
-                    arena.newline()
-                } else if comment.starts_with("//") {
-                    arena.text(comment).append(arena.newline())
-                } else {
-                    arena.text(comment)
-                }
-            })
-            .fold(arena.nil(), |acc, doc| doc.append(acc))
+    /// Returns the comments in `span`, separated by a space from the code before `span` if the
+    /// first comment is on the same line as that code
+    fn comments_after_code(&self, span: Span<BytePos>) -> DocBuilder<'a, Arena<'a>> {
+        let doc = self.comments(span);
+        let (before, src) = self.source.src().split_at(span.start.to_usize());
+        let src = &src[..(span.end - span.start).to_usize()];
+        let line_before = before.rsplit('\n').next().unwrap_or("");
+        if !line_before.trim().is_empty()
+            && src.trim_left_matches(|c: char| c.is_whitespace() && c != '\n')
+                .starts_with('/')
+        {
+            self.arena.space().append(doc)
+        } else {
+            doc
+        }
     }
 }
 
@@ -686,7 +783,7 @@ impl<'a: 'e, 'e, I> ops::Deref for Printer<'a, 'e, I> {
     type Target = pretty_types::Printer<'a, 'e, I>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.printer
     }
 }
 
@@ -741,8 +838,10 @@ fn pretty_kind<'a>(arena: &'a Arena<'a>, prec: Prec, kind: &'a Kind) -> DocBuild
     match *kind {
         Kind::Type => arena.text("Type"),
         Kind::Row => arena.text("Row"),
+        Kind::Nat => arena.text("Nat"),
         Kind::Hole => arena.text("_"),
         Kind::Variable(ref id) => arena.text(id.to_string()),
+        Kind::Generic(ref name) => arena.text(&name[..]),
         Kind::Function(ref a, ref r) => {
             let doc = chain![arena;
                 pretty_kind(arena, Prec::Function, a),
@@ -755,6 +854,155 @@ fn pretty_kind<'a>(arena: &'a Arena<'a>, prec: Prec, kind: &'a Kind) -> DocBuild
     }
 }
 
+/// Returns the initial value of the loop if `binds` and `body` were produced by a
+/// `loop x = init in body` expression. Uses of `break` in the loop are not preserved as they have
+/// been replaced by the value they return.
+fn loop_init<'a, 'ast, I>(
+    binds: &'a [ValueBinding<'ast, I>],
+    body: &'a SpannedExpr<'ast, I>,
+) -> Option<&'a SpannedExpr<'ast, I>>
+where
+    I: AsRef<str>,
+{
+    let is_loop_id = |expr: &SpannedExpr<I>| match expr.value {
+        Expr::Ident(ref id) => id.name.as_ref() == LOOP_ID,
+        _ => false,
+    };
+    if binds.len() != 1 || binds[0].args.len() != 1 {
+        return None;
+    }
+    match (&binds[0].name.value, &body.value) {
+        (&Pattern::Ident(ref id), &Expr::App(ref f, ref args))
+            if id.name.as_ref() == LOOP_ID && is_loop_id(f) && args.len() == 1 =>
+        {
+            Some(&args[0])
+        }
+        _ => None,
+    }
+}
+
+/// Returns the arguments of a function as they were written along with the body of the function.
+/// The parser translates `\{ x } -> body` into `\arg:1 -> match arg:1 with | { x } -> body` so
+/// the `match` is removed and its pattern is returned in place of `arg:1`.
+fn pattern_arguments<'a, 'ast, I>(
+    args: &'a [SpannedIdent<I>],
+    mut body: &'a SpannedExpr<'ast, I>,
+) -> (
+    Vec<Either<&'a SpannedIdent<I>, &'a SpannedPattern<I>>>,
+    &'a SpannedExpr<'ast, I>,
+)
+where
+    I: AsRef<str>,
+{
+    let args = args.iter()
+        .map(|arg| {
+            let name = arg.value.name.as_ref();
+            if name.starts_with(PATTERN_ARG_PREFIX) {
+                if let Expr::Match(ref scrutinee, ref alts) = body.value {
+                    match scrutinee.value {
+                        Expr::Ident(ref id) if id.name.as_ref() == name && alts.len() == 1 => {
+                            body = &alts[0].expr;
+                            return Either::Right(&alts[0].pattern);
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            Either::Left(arg)
+        })
+        .collect();
+    (args, body)
+}
+
+enum Section<'a, 'ast: 'a, I: 'ast> {
+    Left(&'a SpannedExpr<'ast, I>, &'a str),
+    Right(&'a str, &'a SpannedExpr<'ast, I>),
+    Projection(&'a str),
+}
+
+/// Returns the operator section which `lambda` was translated from by the parser, `(+ 1)` is
+/// translated into `\section:1 -> section:1 + 1`
+fn section<'a, 'ast, I>(lambda: &'a Lambda<'ast, I>) -> Option<Section<'a, 'ast, I>>
+where
+    I: AsRef<str>,
+{
+    if lambda.args.len() != 1 || !lambda.args[0].value.name.as_ref().starts_with(SECTION_PREFIX) {
+        return None;
+    }
+    let arg = lambda.args[0].value.name.as_ref();
+    let is_section_arg = |expr: &SpannedExpr<I>| match expr.value {
+        Expr::Ident(ref id) => id.name.as_ref() == arg,
+        _ => false,
+    };
+    match lambda.body.value {
+        Expr::Infix(ref lhs, ref op, ref rhs) if is_section_arg(lhs) => {
+            // The parser wraps an infix operand in parentheses
+            let rhs = match rhs.value {
+                Expr::Tuple { ref elems, .. } if elems.len() == 1 => match elems[0].value {
+                    Expr::Infix(..) => &elems[0],
+                    _ => rhs,
+                },
+                _ => rhs,
+            };
+            Some(Section::Right(op.value.name.as_ref(), rhs))
+        }
+        Expr::Infix(ref lhs, ref op, ref rhs) if is_section_arg(rhs) => {
+            Some(Section::Left(lhs, op.value.name.as_ref()))
+        }
+        Expr::Projection(ref expr, ref field, _, _) if is_section_arg(expr) => {
+            Some(Section::Projection(field.as_ref()))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the equations which a function was defined with. The parser merges the equations of
+/// a function into a single `match` on all of its arguments, `let f 0 = 1` followed by
+/// `let f n = n` becomes `let f arg:1 = match arg:1 with | 0 -> 1 | n -> n`.
+fn function_clauses<'a, 'ast, I>(
+    args: &'a [SpannedIdent<I>],
+    body: &'a SpannedExpr<'ast, I>,
+) -> Vec<
+    (
+        Vec<Either<&'a SpannedIdent<I>, &'a SpannedPattern<I>>>,
+        &'a SpannedExpr<'ast, I>,
+    ),
+>
+where
+    I: AsRef<str>,
+{
+    let is_argument = |expr: &SpannedExpr<I>, arg: &SpannedIdent<I>| match expr.value {
+        Expr::Ident(ref id) => {
+            let name = arg.value.name.as_ref();
+            name.starts_with(PATTERN_ARG_PREFIX) && id.name.as_ref() == name
+        }
+        _ => false,
+    };
+    if let Expr::Match(ref scrutinee, ref alts) = body.value {
+        let is_merged = alts.len() > 1 && match scrutinee.value {
+            Expr::Tuple { ref elems, .. } => {
+                elems.len() == args.len()
+                    && elems.iter().zip(args).all(|(elem, arg)| is_argument(elem, arg))
+            }
+            _ => args.len() == 1 && is_argument(scrutinee, &args[0]),
+        };
+        if is_merged {
+            return alts.iter()
+                .map(|alt| {
+                    let patterns = match alt.pattern.value {
+                        Pattern::Tuple { ref elems, .. } if args.len() > 1 => {
+                            elems.iter().map(Either::Right).collect()
+                        }
+                        _ => vec![Either::Right(&alt.pattern)],
+                    };
+                    (patterns, &alt.expr)
+                })
+                .collect();
+        }
+    }
+    vec![pattern_arguments(args, body)]
+}
+
 fn newline<'a, Id>(arena: &'a Arena<'a>, expr: &'a SpannedExpr<Id>) -> DocBuilder<'a, Arena<'a>> {
     if forced_new_line(expr) {
         arena.newline()
@@ -0,0 +1,61 @@
+extern crate gluon_base as base;
+extern crate gluon_format as format;
+
+use base::pos::{BytePos, Span};
+
+use format::fix::{apply_edits, rename, Edit};
+use format::Error;
+
+fn span(start: usize, end: usize) -> Span<BytePos> {
+    Span::new(BytePos::from(start), BytePos::from(end))
+}
+
+#[test]
+fn rename_keeps_layout_and_comments() {
+    let input = r#"
+// Comment
+let x =   old 1
+old   x
+"#;
+    let edits = vec![
+        rename(input, span(22, 25), "old", "new").unwrap(),
+        rename(input, span(28, 31), "old", "new").unwrap(),
+    ];
+    assert_eq!(
+        apply_edits(input, edits).unwrap(),
+        r#"
+// Comment
+let x =   new 1
+new   x
+"#
+    );
+}
+
+#[test]
+fn rename_requires_the_old_name_at_the_span() {
+    assert_eq!(rename("let y = x in y", span(8, 9), "old", "new"), None);
+}
+
+#[test]
+fn overlapping_edits_are_rejected() {
+    let edit = |start, end| Edit {
+        span: span(start, end),
+        replacement: "y".to_string(),
+    };
+    match apply_edits("x + x", vec![edit(0, 3), edit(2, 5)]) {
+        Err(Error::OverlappingEdits) => (),
+        result => panic!("Expected overlapping edits, got {:?}", result),
+    }
+}
+
+#[test]
+fn edits_which_break_the_syntax_are_rejected() {
+    let edit = Edit {
+        span: span(0, 3),
+        replacement: "let".to_string(),
+    };
+    match apply_edits("old 1", vec![edit]) {
+        Err(Error::InvalidOutput(_)) => (),
+        result => panic!("Expected invalid output, got {:?}", result),
+    }
+}
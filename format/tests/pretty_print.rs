@@ -12,7 +12,7 @@ use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
 
-use format::format_expr;
+use format::{format_expr, format_expr_with, FormatSettings};
 
 fn test_format(name: &str) {
     let _ = env_logger::init();
@@ -97,6 +97,16 @@ fn test() {
     test_format("std/test.glu");
 }
 
+#[test]
+fn test_assert() {
+    test_format("std/test/assert.glu");
+}
+
+#[test]
+fn test_expect() {
+    test_format("std/test/expect.glu");
+}
+
 #[test]
 fn types() {
     test_format("std/types.glu");
@@ -150,6 +160,14 @@ fn dont_lose_information_in_literals() {
     assert_eq!(&format_expr(expr).unwrap(), expr);
 }
 
+#[test]
+fn dont_lose_information_in_numeric_literals() {
+    let expr = r#"
+f 0xFF 0b1010 0o755 1_000_000 1_0.2_5 0b
+"#;
+    assert_eq!(&format_expr(expr).unwrap(), expr);
+}
+
 #[test]
 fn preserve_comment_between_let_in() {
     let expr = r#"
@@ -164,6 +182,14 @@ type Test = Int
     assert_diff!(&format_expr(expr).unwrap(), expr, " ", 0);
 }
 
+#[test]
+fn preserve_strict_record_fields() {
+    let expr = r#"
+{ !sum = sum + x, !count, x }
+"#;
+    assert_eq!(&format_expr(expr).unwrap(), expr);
+}
+
 #[test]
 fn preserve_whitespace_in_record() {
     let expr = r#"
@@ -208,6 +234,17 @@ x
     assert_eq!(&format_expr(expr).unwrap(), expr);
 }
 
+#[test]
+fn preserve_module_attributes() {
+    let expr = r#"#!/usr/bin/env gluon
+#![no_implicit_prelude]
+// test
+let x = 1
+x
+"#;
+    assert_eq!(&format_expr(expr).unwrap(), expr);
+}
+
 #[test]
 fn nested_constructor_pattern() {
     let expr = r#"
@@ -341,3 +378,102 @@ abc ""
 "#;
     assert_diff!(&format_expr(expr).unwrap(), expr, " ", 0);
 }
+
+#[test]
+fn preserve_attributes() {
+    let expr = r#"
+/// Test
+#[implicit]
+let x = 1
+#[doc(hidden)]
+let y = 2
+x
+"#;
+    assert_diff!(&format_expr(expr).unwrap(), expr, " ", 0);
+}
+
+#[test]
+fn loop_expression() {
+    let expr = r#"
+loop i = 0
+if i < 10 then continue (i + 1) else i
+"#;
+    assert_diff!(&format_expr(expr).unwrap(), expr, " ", 0);
+}
+
+#[test]
+fn pattern_arguments() {
+    let expr = r#"
+let add { x, y } (Some z) = x + y + z
+\(a, b) c -> a
+"#;
+    assert_diff!(&format_expr(expr).unwrap(), expr, " ", 0);
+}
+
+#[test]
+fn function_clauses() {
+    let expr = r#"
+let f 0 y = y
+let f x (Some y) = x + y
+let f x y = x
+f
+"#;
+    assert_diff!(&format_expr(expr).unwrap(), expr, " ", 0);
+}
+
+#[test]
+fn operator_sections() {
+    let expr = r#"
+map (+ 1) (map (2 *) (map (.x) xs))
+(* 1 + 2)
+"#;
+    assert_diff!(&format_expr(expr).unwrap(), expr, " ", 0);
+}
+
+#[test]
+fn format_settings() {
+    let expr = r#"
+let f x =
+    let y = x
+    { aaaaaaaaaaaaaaaaaaaaaa = y, bbbbbbbbbbbbbbbbbbbbbbbb = y }
+f
+"#;
+    let settings = FormatSettings {
+        max_width: 40,
+        indent: 2,
+        record_trailing_comma: false,
+    };
+    let expected = r#"
+let f x =
+  let y = x
+  {
+    aaaaaaaaaaaaaaaaaaaaaa = y,
+    bbbbbbbbbbbbbbbbbbbbbbbb = y
+  }
+f
+"#;
+    assert_diff!(&format_expr_with(&settings, expr).unwrap(), expected, " ", 0);
+    assert_diff!(&format_expr_with(&settings, expected).unwrap(), expected, " ", 0);
+}
+
+#[test]
+fn preserve_comments_attached_to_bindings() {
+    let expr = r#"
+// leading
+let x = 1 // trailing
+let f y =
+    /* inside */
+    y
+{ x, /* field */ f }
+"#;
+    assert_diff!(&format_expr(expr).unwrap(), expr, " ", 0);
+}
+
+#[test]
+fn preserve_implicit_arguments() {
+    let expr = r#"
+let show ?s x : [Show a] -> a -> String = s.show x
+show 1
+"#;
+    assert_diff!(&format_expr(expr).unwrap(), expr, " ", 0);
+}
@@ -0,0 +1,212 @@
+//! Attaches the comments which the tokenizer skipped to the nearest binding, record field or match
+//! alternative
+use base::ast::{walk_mut_expr, Expr, MutVisitor, SpannedComment, SpannedExpr, ValueBinding};
+use base::pos::{BytePos, Span};
+
+use token::{Token, Tokenizer};
+
+/// Calls a function with the span and the comments of each binding, record field and match
+/// alternative in an expression. The nodes are always visited in the same order.
+struct Nodes<'f, Id> {
+    f: &'f mut FnMut(Span<BytePos>, &mut Vec<SpannedComment>),
+    _marker: ::std::marker::PhantomData<Id>,
+}
+
+impl<'f, Id> Nodes<'f, Id> {
+    fn visit_binding(&mut self, bind: &mut ValueBinding<Id>) {
+        (self.f)(bind.span(), &mut bind.comments);
+        self.visit_expr(&mut bind.expr);
+    }
+}
+
+impl<'f, 'ast, Id: 'ast> MutVisitor<'ast> for Nodes<'f, Id> {
+    type Ident = Id;
+
+    fn visit_expr(&mut self, expr: &mut SpannedExpr<'ast, Id>) {
+        match expr.value {
+            Expr::LetBindings(ref mut binds, ref mut body) => {
+                for bind in binds.iter_mut() {
+                    self.visit_binding(bind);
+                }
+                self.visit_expr(body);
+            }
+            Expr::TypeBindings(ref mut binds, ref mut body) => {
+                for bind in binds.iter_mut() {
+                    (self.f)(bind.span(), &mut bind.comments);
+                }
+                self.visit_expr(body);
+            }
+            Expr::Record {
+                ref mut types,
+                ref mut exprs,
+                ref mut base,
+                ..
+            } => {
+                for field in types.iter_mut() {
+                    (self.f)(field.name.span, &mut field.comments);
+                }
+                for field in exprs.iter_mut() {
+                    let span = match field.value {
+                        Some(ref value) => Span::new(field.name.span.start, value.span.end),
+                        None => field.name.span,
+                    };
+                    (self.f)(span, &mut field.comments);
+                    if let Some(ref mut value) = field.value {
+                        self.visit_expr(value);
+                    }
+                }
+                if let Some(ref mut base) = *base {
+                    self.visit_expr(base);
+                }
+            }
+            Expr::Match(ref mut scrutinee, ref mut alts) => {
+                self.visit_expr(scrutinee);
+                for alt in alts.iter_mut() {
+                    let span = Span::new(alt.pattern.span.start, alt.expr.span.end);
+                    (self.f)(span, &mut alt.comments);
+                    self.visit_expr(&mut alt.expr);
+                }
+            }
+            _ => walk_mut_expr(self, expr),
+        }
+    }
+}
+
+/// The expression or let binding which comments are attached to
+pub enum Root<'a, 'ast: 'a, Id: 'ast> {
+    Expr(&'a mut SpannedExpr<'ast, Id>),
+    Binding(&'a mut ValueBinding<'ast, Id>),
+}
+
+impl<'a, 'ast, Id> Root<'a, 'ast, Id> {
+    fn visit(&mut self, f: &mut FnMut(Span<BytePos>, &mut Vec<SpannedComment>)) {
+        let mut nodes = Nodes {
+            f,
+            _marker: ::std::marker::PhantomData,
+        };
+        match *self {
+            Root::Expr(ref mut expr) => nodes.visit_expr(expr),
+            Root::Binding(ref mut bind) => nodes.visit_binding(bind),
+        }
+    }
+}
+
+/// Returns true if `input` only contains tokens which may appear between a comment and the start
+/// of the binding, field or alternative which follows it
+fn only_binding_prefix(input: &str) -> bool {
+    // The tokenizer returns `EOF` indefinitely once the input is exhausted
+    Tokenizer::new(input)
+        .map(|token| token.map(|token| token.value))
+        .take_while(|token| match *token {
+            Ok(Token::EOF) => false,
+            _ => true,
+        })
+        .all(|token| match token {
+            Ok(Token::DocComment(_))
+            | Ok(Token::Attribute(_))
+            | Ok(Token::Let)
+            | Ok(Token::And)
+            | Ok(Token::Type)
+            | Ok(Token::Comma)
+            | Ok(Token::LBrace)
+            | Ok(Token::Pipe) => true,
+            _ => false,
+        })
+}
+
+/// Returns the index of the node in `spans` which `comment` should be attached to
+fn nearest(input: &str, spans: &[Span<BytePos>], comment: Span<BytePos>) -> Option<usize> {
+    let slice = |start: BytePos, end: BytePos| &input[start.to_usize()..end.to_usize()];
+
+    // The innermost node containing the comment
+    let container = spans
+        .iter()
+        .enumerate()
+        .filter(|&(_, span)| span.start <= comment.start && comment.end <= span.end)
+        .min_by_key(|&(_, span)| span.end - span.start)
+        .map(|(i, _)| i);
+    let within_container =
+        |span: &Span<BytePos>| container.map_or(true, |i| spans[i].contains(*span));
+
+    // A comment placed before a node, only separated by keywords, attributes or other comments
+    let leading = || {
+        spans
+            .iter()
+            .enumerate()
+            .filter(|&(_, span)| comment.end <= span.start && within_container(span))
+            .min_by_key(|&(_, span)| span.start)
+            .and_then(|(i, span)| {
+                if only_binding_prefix(slice(comment.end, span.start)) {
+                    Some(i)
+                } else {
+                    None
+                }
+            })
+    };
+    // A comment which ends the line on which a node ends
+    let rest_of_line = input[comment.end.to_usize()..].split('\n').next().unwrap_or("");
+    let trailing = if rest_of_line.trim().is_empty() {
+        spans
+            .iter()
+            .enumerate()
+            .filter(|&(_, span)| span.end <= comment.start && within_container(span))
+            .max_by_key(|&(_, span)| span.end)
+            .and_then(|(i, span)| {
+                let between = slice(span.end, comment.start);
+                if !between.contains('\n') && between.trim().trim_left_matches(',').is_empty() {
+                    Some(i)
+                } else {
+                    None
+                }
+            })
+    } else {
+        None
+    };
+    // Otherwise the closest node by distance
+    let closest = || {
+        spans
+            .iter()
+            .enumerate()
+            .min_by_key(|&(_, span)| {
+                if span.end <= comment.start {
+                    comment.start - span.end
+                } else {
+                    span.start - comment.end
+                }
+            })
+            .map(|(i, _)| i)
+    };
+    trailing
+        .or_else(leading)
+        .or(container)
+        .or_else(closest)
+}
+
+/// Attaches each comment to the binding, record field or match alternative in `root` which it is
+/// closest to. A
+/// comment at the end of the line on which a node ends is attached to that node, a comment
+/// directly before a binding or field is attached to that node and any other comment is attached
+/// to the innermost node containing it. If no node contains the comment it is attached to the
+/// closest node instead.
+///
+/// Comments are dropped only if there are no bindings, record fields or alternatives to attach them
+/// to.
+pub fn attach_comments<Id>(input: &str, mut root: Root<Id>, comments: Vec<SpannedComment>) {
+    if comments.is_empty() {
+        return;
+    }
+    let mut spans = Vec::new();
+    root.visit(&mut |span, _| spans.push(span));
+
+    let mut attached = vec![Vec::new(); spans.len()];
+    for comment in comments {
+        if let Some(i) = nearest(input, &spans, comment.span) {
+            attached[i].push(comment);
+        }
+    }
+
+    let mut attached = attached.into_iter();
+    root.visit(&mut |_, comments| {
+        comments.extend(attached.next().into_iter().flat_map(|x| x))
+    });
+}
@@ -0,0 +1,387 @@
+//! Parses a module again after it has been edited by only parsing the top level bindings which
+//! were affected by the edit
+use std::marker::PhantomData;
+use std::mem;
+
+use base::ast::{walk_mut_ast_type, walk_mut_expr, walk_mut_pattern, Arena, Expr, IdentEnv,
+                MutVisitor, SpannedAlias, SpannedAstType, SpannedComment, SpannedExpr,
+                SpannedIdent, SpannedPattern, ValueBinding};
+use base::pos::{BytePos, Span, Spanned};
+use base::symbol::Symbol;
+use base::types::{ArcType, TypeCache};
+
+use {is_next_clause, parse_partial_expr, ParseErrors};
+
+/// An edit of the source text of a module which replaces the text in `span` with `text`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TextEdit {
+    pub span: Span<BytePos>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new<S>(span: Span<BytePos>, text: S) -> TextEdit
+    where
+        S: Into<String>,
+    {
+        TextEdit {
+            span,
+            text: text.into(),
+        }
+    }
+
+    /// Moves `pos`, which must not be before the end of the edit, to where it is after the edit
+    fn move_pos(&self, pos: BytePos) -> BytePos {
+        pos + BytePos::from(self.text.len()) - (self.span.end - self.span.start)
+    }
+}
+
+/// Moves every span in an expression with `f`
+struct MoveSpans<F, Id> {
+    f: F,
+    _marker: PhantomData<Id>,
+}
+
+impl<F, Id> MoveSpans<F, Id>
+where
+    F: FnMut(BytePos) -> BytePos,
+{
+    fn new(f: F) -> Self {
+        MoveSpans {
+            f,
+            _marker: PhantomData,
+        }
+    }
+
+    fn span(&mut self, span: &mut Span<BytePos>) {
+        // Nodes which the parser creates without a position in the input have the default span
+        if *span != Span::default() {
+            *span = span.map(&mut self.f);
+        }
+    }
+
+    fn comments(&mut self, comments: &mut [SpannedComment]) {
+        for comment in comments {
+            self.span(&mut comment.span);
+        }
+    }
+}
+
+impl<'ast, F, Id> MutVisitor<'ast> for MoveSpans<F, Id>
+where
+    F: FnMut(BytePos) -> BytePos,
+    Id: 'ast,
+{
+    type Ident = Id;
+
+    fn visit_expr(&mut self, expr: &mut SpannedExpr<'ast, Id>) {
+        self.span(&mut expr.span);
+        match expr.value {
+            Expr::LetBindings(ref mut binds, _) => for bind in binds.iter_mut() {
+                self.comments(&mut bind.comments);
+            },
+            Expr::TypeBindings(ref mut binds, _) => for bind in binds.iter_mut() {
+                self.comments(&mut bind.comments);
+            },
+            Expr::Record {
+                ref mut types,
+                ref mut exprs,
+                ..
+            } => {
+                for field in types.iter_mut() {
+                    self.comments(&mut field.comments);
+                }
+                for field in exprs.iter_mut() {
+                    self.comments(&mut field.comments);
+                }
+            }
+            Expr::Match(_, ref mut alts) => for alt in alts.iter_mut() {
+                self.comments(&mut alt.comments);
+            },
+            _ => (),
+        }
+        walk_mut_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &mut SpannedPattern<Id>) {
+        self.span(&mut pattern.span);
+        walk_mut_pattern(self, &mut pattern.value);
+    }
+
+    fn visit_spanned_typed_ident(&mut self, id: &mut SpannedIdent<Id>) {
+        self.span(&mut id.span);
+        self.visit_ident(&mut id.value)
+    }
+
+    fn visit_spanned_ident(&mut self, id: &mut Spanned<Id, BytePos>) {
+        self.span(&mut id.span);
+    }
+
+    fn visit_alias(&mut self, alias: &mut SpannedAlias<Id>) {
+        self.span(&mut alias.span);
+    }
+
+    fn visit_ast_type(&mut self, typ: &mut SpannedAstType<Id>) {
+        self.span(&mut typ.span);
+        walk_mut_ast_type(self, typ);
+    }
+}
+
+/// Returns the body of `expr` if it is a top level `let` or `type` expression
+fn top_level_body<'a, 'ast, Id>(
+    expr: &'a SpannedExpr<'ast, Id>,
+) -> Option<&'a SpannedExpr<'ast, Id>> {
+    match expr.value {
+        Expr::LetBindings(_, ref body) | Expr::TypeBindings(_, ref body) => Some(&**body),
+        _ => None,
+    }
+}
+
+fn top_level_body_mut<'a, 'ast, Id>(
+    expr: &'a mut SpannedExpr<'ast, Id>,
+) -> Option<&'a mut SpannedExpr<'ast, Id>> {
+    match expr.value {
+        Expr::LetBindings(_, ref mut body) | Expr::TypeBindings(_, ref mut body) => {
+            Some(&mut **body)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the span of the bindings of each top level `let` and `type` expression in `expr`
+fn top_level_spans<Id>(mut expr: &SpannedExpr<Id>) -> Vec<Span<BytePos>> {
+    let mut spans = Vec::new();
+    loop {
+        let end = match expr.value {
+            Expr::LetBindings(ref binds, _) => binds.last().map(|bind| bind.span().end),
+            Expr::TypeBindings(ref binds, _) => binds.last().map(|bind| bind.span().end),
+            _ => None,
+        };
+        match (end, top_level_body(expr)) {
+            (Some(end), Some(body)) => {
+                spans.push(Span::new(expr.span.start, end));
+                expr = body;
+            }
+            _ => return spans,
+        }
+    }
+}
+
+/// Returns true if any of the top level bindings declares the fixity of an operator, which
+/// changes how the bindings after it are parsed
+fn declares_fixity<Id>(mut expr: &SpannedExpr<Id>) -> bool {
+    loop {
+        if let Expr::LetBindings(ref binds, _) = expr.value {
+            let is_infix = binds
+                .iter()
+                .any(|bind| bind.attributes.iter().any(|attr| attr.name == "infix"));
+            if is_infix {
+                return true;
+            }
+        }
+        match top_level_body(expr) {
+            Some(body) => expr = body,
+            None => return false,
+        }
+    }
+}
+
+/// Returns the comments which are attached to the top level bindings of `expr`
+fn top_level_comments<'a, Id>(
+    expr: &'a mut SpannedExpr<Id>,
+) -> Vec<&'a mut Vec<SpannedComment>> {
+    match expr.value {
+        Expr::LetBindings(ref mut binds, _) => {
+            binds.iter_mut().map(|bind| &mut bind.comments).collect()
+        }
+        Expr::TypeBindings(ref mut binds, _) => {
+            binds.iter_mut().map(|bind| &mut bind.comments).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn single_binding<'a, 'ast, Id>(
+    expr: &'a SpannedExpr<'ast, Id>,
+) -> Option<&'a ValueBinding<'ast, Id>> {
+    match expr.value {
+        Expr::LetBindings(ref binds, _) if binds.len() == 1 => binds.first(),
+        _ => None,
+    }
+}
+
+/// Returns true if `first` and `second` would be merged into a single function if they were
+/// parsed together (see `let_bindings`)
+fn is_merged<Id>(
+    symbols: &IdentEnv<Ident = Id>,
+    first: Option<&SpannedExpr<Id>>,
+    second: Option<&SpannedExpr<Id>>,
+) -> bool {
+    match (first.and_then(single_binding), second.and_then(single_binding)) {
+        (Some(first), Some(second)) => is_next_clause(symbols, first, second),
+        _ => false,
+    }
+}
+
+/// Replaces the top level bindings of `expr` which contain `edit` with bindings parsed from
+/// `input`. Returns `false` without modifying `expr` if the bindings could not be parsed on
+/// their own.
+fn reparse_top_level<'ast, Id>(
+    arena: &'ast Arena<'ast, Id>,
+    symbols: &mut IdentEnv<Ident = Id>,
+    type_cache: &TypeCache<Id, ArcType<Id>>,
+    expr: &mut SpannedExpr<'ast, Id>,
+    edit: &TextEdit,
+    input: &str,
+) -> bool
+where
+    Id: Clone,
+{
+    if declares_fixity(expr) {
+        return false;
+    }
+
+    // The text between two top level bindings (comments, `in`) is parsed along with the
+    // bindings after it
+    let spans = top_level_spans(expr);
+    let index = match spans.iter().enumerate().position(|(i, span)| {
+        let is_after_previous = i == 0 || spans[i - 1].end < edit.span.start;
+        is_after_previous && edit.span.end <= span.end
+    }) {
+        Some(index) => index,
+        None => return false,
+    };
+    let start = if index == 0 {
+        0
+    } else {
+        spans[index - 1].end.to_usize()
+    };
+    // An edit on the line where the previous bindings end may change which of the bindings the
+    // comments on that line are attached to
+    if index != 0 && !input[start..edit.span.start.to_usize()].contains('\n') {
+        return false;
+    }
+    let end = edit.move_pos(spans[index].end).to_usize();
+    // The bindings start at the same position after the edit unless the edit is before them
+    let bindings_start = if edit.span.end <= spans[index].start {
+        edit.move_pos(spans[index].start).to_usize()
+    } else if spans[index].start <= edit.span.start {
+        spans[index].start.to_usize()
+    } else {
+        return false;
+    };
+    if end > input.len() || !input.is_char_boundary(end) || bindings_start > end {
+        return false;
+    }
+
+    // Pad the bindings so that they start at the same column as they do in `input`, which the
+    // layout depends on, and end them with `()` so that they can be parsed on their own
+    let column = |pos: usize| {
+        let line_start = input[..pos].rfind('\n').map_or(0, |i| i + 1);
+        input[line_start..pos].chars().count()
+    };
+    let indent = |column: usize| ::std::iter::repeat(' ').take(column).collect::<String>();
+    let prefix = indent(column(start));
+    let fragment = format!(
+        "{}{}\n{}()",
+        prefix,
+        &input[start..end],
+        indent(column(bindings_start))
+    );
+    let unit_start = BytePos::from(fragment.len() - 2);
+
+    let mut new_expr = match parse_partial_expr(arena, symbols, type_cache, &fragment) {
+        Ok(new_expr) => new_expr,
+        Err(_) => return false,
+    };
+    let is_unit = |expr: &SpannedExpr<Id>| match expr.value {
+        Expr::Tuple { ref elems, .. } => elems.is_empty() && expr.span.start == unit_start,
+        _ => false,
+    };
+    if !top_level_body(&new_expr).map_or(false, is_unit) || declares_fixity(&new_expr) {
+        return false;
+    }
+
+    let offset = BytePos::from(start - prefix.len());
+    MoveSpans::new(|pos| pos + offset).visit_expr(&mut new_expr);
+
+    {
+        let mut previous = None;
+        let mut node = &*expr;
+        for _ in 0..index {
+            previous = Some(node);
+            node = top_level_body(node).unwrap();
+        }
+        if is_merged(&*symbols, previous, Some(&new_expr))
+            || is_merged(&*symbols, Some(&new_expr), top_level_body(node))
+        {
+            return false;
+        }
+    }
+
+    let mut node = expr;
+    for i in 0..index {
+        node.span.end = edit.move_pos(node.span.end);
+        if i + 1 == index {
+            // Comments after the previous bindings which are attached to them were parsed again
+            // along with the new bindings
+            for previous in top_level_comments(node) {
+                for comment in previous.iter().filter(|c| c.span.start >= spans[i].end) {
+                    for comments in top_level_comments(&mut new_expr) {
+                        comments.retain(|c| c.span != comment.span);
+                    }
+                }
+            }
+        }
+        let current = node;
+        node = top_level_body_mut(current).unwrap();
+    }
+    {
+        let body = top_level_body_mut(&mut new_expr).unwrap();
+        mem::swap(body, top_level_body_mut(node).unwrap());
+        MoveSpans::new(|pos| edit.move_pos(pos)).visit_expr(body);
+    }
+    new_expr.span.end = top_level_body(&new_expr).unwrap().span.end;
+    *node = new_expr;
+    true
+}
+
+/// Parses `input`, which is the source text of `previous` after `edit` has been applied to it.
+///
+/// Only the top level `let` or `type` bindings which contain the edit are parsed again, the rest
+/// of `previous` is reused after moving its spans to their position in `input`. If the edit can't
+/// be confined to a single group of top level bindings, or if it may change how the other
+/// bindings are parsed (such as when an operator has its fixity declared), the entire input is
+/// parsed instead.
+///
+/// The new bindings are allocated in `arena` which must be the arena that `previous` was
+/// allocated in.
+pub fn reparse_partial_expr<'ast, Id>(
+    arena: &'ast Arena<'ast, Id>,
+    symbols: &mut IdentEnv<Ident = Id>,
+    type_cache: &TypeCache<Id, ArcType<Id>>,
+    mut previous: SpannedExpr<'ast, Id>,
+    edit: &TextEdit,
+    input: &str,
+) -> Result<SpannedExpr<'ast, Id>, (Option<SpannedExpr<'ast, Id>>, ParseErrors)>
+where
+    Id: Clone,
+{
+    if reparse_top_level(arena, symbols, type_cache, &mut previous, edit, input) {
+        Ok(previous)
+    } else {
+        debug!("Unable to reparse {:?} incrementally", edit);
+        parse_partial_expr(arena, symbols, type_cache, input)
+    }
+}
+
+pub fn reparse_expr<'ast>(
+    arena: &'ast Arena<'ast, Symbol>,
+    symbols: &mut IdentEnv<Ident = Symbol>,
+    type_cache: &TypeCache<Symbol, ArcType>,
+    previous: SpannedExpr<'ast, Symbol>,
+    edit: &TextEdit,
+    input: &str,
+) -> Result<SpannedExpr<'ast, Symbol>, ParseErrors> {
+    reparse_partial_expr(arena, symbols, type_cache, previous, edit, input).map_err(|t| t.1)
+}
@@ -2,10 +2,13 @@
 //! associative with the same precedence. Therefore we need to rebalance them
 //! after the fact.
 
-use base::ast::{walk_mut_expr, Expr, IdentEnv, Literal, MutVisitor, SpannedExpr, SpannedIdent};
+use base::ast::{self, is_operator_char, walk_mut_expr, Arena, Attribute, DisplayEnv, Expr,
+                IdentEnv, MutVisitor, Pattern, SpannedExpr, SpannedIdent, TypedIdent,
+                ValueBinding};
 use base::error::Errors;
-use base::fnv::FnvMap;
 use base::pos::{self, BytePos, Spanned};
+use base::scoped_map::ScopedMap;
+use base::types::Type;
 use std::cmp::Ordering;
 use std::error::Error as StdError;
 use std::fmt;
@@ -59,6 +62,22 @@ impl OpMeta {
             fixity: fixity,
         }
     }
+
+    /// Parses the arguments of an `#[infix(left, 6)]` attribute
+    pub fn from_attribute(attribute: &Attribute) -> Option<OpMeta> {
+        let arguments = attribute.arguments.as_ref()?;
+        let mut iter = arguments.split(',').map(|arg| arg.trim());
+        let fixity = match iter.next()? {
+            "left" => Fixity::Left,
+            "right" => Fixity::Right,
+            _ => return None,
+        };
+        let precedence = iter.next()?.parse().ok()?;
+        if iter.next().is_some() {
+            return None;
+        }
+        Some(OpMeta::new(precedence, fixity))
+    }
 }
 
 impl fmt::Display for OpMeta {
@@ -67,9 +86,10 @@ impl fmt::Display for OpMeta {
     }
 }
 
-/// A table of operator metadata
+/// A table of operator metadata. Operators declared with an `#[infix(..)]` attribute are added in
+/// a new scope for each `let` so that they only apply where the declared operator is visible.
 pub struct OpTable {
-    pub operators: FnvMap<String, OpMeta>,
+    pub operators: ScopedMap<String, OpMeta>,
     pub default_meta: OpMeta,
 }
 
@@ -78,10 +98,12 @@ impl OpTable {
     where
         I: IntoIterator<Item = (&'static str, OpMeta)>,
     {
+        let mut operators = ScopedMap::new();
+        for (name, op) in ops {
+            operators.insert(name.to_string(), op);
+        }
         OpTable {
-            operators: ops.into_iter()
-                .map(|(name, op)| (name.to_string(), op))
-                .collect(),
+            operators: operators,
             default_meta: OpMeta::new(9, Fixity::Left),
         }
     }
@@ -129,27 +151,72 @@ impl<'a> Index<&'a str> for OpTable {
     }
 }
 
-pub struct Reparser<'s, Id: 's> {
+pub struct Reparser<'s, 'ast, Id: 's + 'ast> {
     operators: OpTable,
-    symbols: &'s IdentEnv<Ident = Id>,
+    symbols: &'s mut IdentEnv<Ident = Id>,
+    /// The arena which the rebalanced infix expressions are allocated in
+    arena: &'ast Arena<'ast, Id>,
+    /// The `&&` operator which chained comparisons are translated into
+    and_op: Id,
     errors: Errors<Spanned<Error, BytePos>>,
     _marker: PhantomData<Id>,
 }
 
-impl<'s, Id> Reparser<'s, Id> {
-    pub fn new(operators: OpTable, symbols: &'s IdentEnv<Ident = Id>) -> Reparser<'s, Id> {
+impl<'s, 'ast, Id> Reparser<'s, 'ast, Id> {
+    pub fn new(
+        operators: OpTable,
+        symbols: &'s mut IdentEnv<Ident = Id>,
+        arena: &'ast Arena<'ast, Id>,
+    ) -> Reparser<'s, 'ast, Id> {
+        let and_op = symbols.from_str("&&");
         Reparser {
             operators: operators,
             symbols: symbols,
+            arena,
+            and_op: and_op,
             errors: Errors::new(),
             _marker: PhantomData,
         }
     }
 
+    /// Enters a new scope containing the fixities which `binds` declare with `#[infix(..)]`
+    fn declare_fixities(&mut self, binds: &[ValueBinding<Id>]) {
+        self.operators.operators.enter_scope();
+        for bind in binds {
+            let attribute = match bind.attribute("infix") {
+                Some(attribute) => attribute,
+                None => continue,
+            };
+            let name = match bind.name.value {
+                Pattern::Ident(ref id) => self.symbols.string(&id.name),
+                _ => "",
+            };
+            if name.is_empty() || !name.chars().all(is_operator_char) {
+                self.errors.push(pos::spanned(
+                    bind.name.span,
+                    Error::InfixAttributeOnNonOperator(name.to_string()),
+                ));
+                continue;
+            }
+            match OpMeta::from_attribute(attribute) {
+                Some(meta) => {
+                    self.operators.operators.insert(name.to_string(), meta);
+                }
+                None => self.errors.push(pos::spanned(
+                    bind.name.span,
+                    Error::InvalidInfixAttribute(attribute.arguments.clone()),
+                )),
+            }
+        }
+    }
+
     pub fn reparse(
         &mut self,
-        expr: &mut SpannedExpr<Id>,
-    ) -> Result<(), Errors<Spanned<Error, BytePos>>> {
+        expr: &mut SpannedExpr<'ast, Id>,
+    ) -> Result<(), Errors<Spanned<Error, BytePos>>>
+    where
+        Id: Clone,
+    {
         self.visit_expr(expr);
         if self.errors.has_errors() {
             Err(mem::replace(&mut self.errors, Errors::new()))
@@ -159,31 +226,41 @@ impl<'s, Id> Reparser<'s, Id> {
     }
 }
 
-impl<'s, Id> MutVisitor for Reparser<'s, Id> {
+impl<'s, 'ast, Id> MutVisitor<'ast> for Reparser<'s, 'ast, Id>
+where
+    Id: Clone,
+{
     type Ident = Id;
 
-    fn visit_expr(&mut self, e: &mut SpannedExpr<Self::Ident>) {
+    fn visit_expr(&mut self, e: &mut SpannedExpr<'ast, Self::Ident>) {
+        let is_let = match e.value {
+            Expr::LetBindings(ref binds, _) => {
+                self.declare_fixities(binds);
+                true
+            }
+            _ => false,
+        };
         if let Expr::Infix(..) = e.value {
-            let dummy = pos::spanned2(
-                BytePos::from(0),
-                BytePos::from(0),
-                Expr::Literal(Literal::Int(0)),
-            );
-            let expr = mem::replace(e, dummy);
-            match reparse(expr, self.symbols, &self.operators) {
+            let expr = ast::take_expr(e);
+            match reparse(self.arena, expr, &*self.symbols, &self.operators) {
                 Ok(expr) => {
-                    *e = expr;
+                    *e = chain_comparisons(self.arena, expr, &mut *self.symbols, &self.and_op);
                 }
                 Err(err) => self.errors.push(err),
             }
         }
         walk_mut_expr(self, e);
+        if is_let {
+            self.operators.operators.exit_scope();
+        }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Error {
     ConflictingFixities((String, OpMeta), (String, OpMeta)),
+    InvalidInfixAttribute(Option<String>),
+    InfixAttributeOnNonOperator(String),
 }
 
 impl fmt::Display for Error {
@@ -202,13 +279,28 @@ impl fmt::Display for Error {
                     rhs_name
                 )
             }
+            InvalidInfixAttribute(ref arguments) => write!(
+                f,
+                "Invalid infix attribute `#[infix({})]`, expected `#[infix(left, <precedence>)]` \
+                 or `#[infix(right, <precedence>)]`",
+                arguments.as_ref().map_or("", |s| &s[..])
+            ),
+            InfixAttributeOnNonOperator(ref name) => write!(
+                f,
+                "The infix attribute can only be used on operators, not `{}`",
+                name
+            ),
         }
     }
 }
 
 impl StdError for Error {
     fn description(&self) -> &str {
-        "Conflicting fixities at the same precedence level"
+        match *self {
+            Error::ConflictingFixities(..) => "Conflicting fixities at the same precedence level",
+            Error::InvalidInfixAttribute(..) => "Invalid infix attribute",
+            Error::InfixAttributeOnNonOperator(..) => "Infix attribute on a non-operator",
+        }
     }
 }
 
@@ -218,20 +310,22 @@ impl StdError for Error {
 /// Inspired by [`Language.Haskell.Infix`].
 ///
 /// [`Language.Haskell.Infix`]: https://hackage.haskell.org/package/infix-0.1.1/docs/src/Language-Haskell-Infix.html
-pub fn reparse<Id>(
-    expr: SpannedExpr<Id>,
+pub fn reparse<'ast, Id>(
+    arena: &'ast Arena<'ast, Id>,
+    expr: SpannedExpr<'ast, Id>,
     symbols: &IdentEnv<Ident = Id>,
     operators: &OpTable,
-) -> Result<SpannedExpr<Id>, Spanned<Error, BytePos>> {
+) -> Result<SpannedExpr<'ast, Id>, Spanned<Error, BytePos>> {
     use base::pos;
     use self::Error::*;
 
-    let make_op = |lhs: Box<SpannedExpr<Id>>, op, rhs: Box<SpannedExpr<Id>>| {
-        let span = pos::span(lhs.span.start, rhs.span.end);
-        Box::new(pos::spanned(span, Expr::Infix(lhs, op, rhs)))
-    };
+    let make_op =
+        |lhs: &'ast mut SpannedExpr<'ast, Id>, op, rhs: &'ast mut SpannedExpr<'ast, Id>| {
+            let span = pos::span(lhs.span.start, rhs.span.end);
+            arena.alloc(pos::spanned(span, Expr::Infix(lhs, op, rhs)))
+        };
 
-    let mut infixes = Infixes::new(expr);
+    let mut infixes = Infixes::new(arena.alloc(expr));
     let mut arg_stack = Vec::new();
     let mut op_stack = Vec::new();
 
@@ -307,12 +401,150 @@ pub fn reparse<Id>(
 
     assert_eq!(arg_stack.len(), 1);
 
-    Ok(*arg_stack.pop().unwrap())
+    Ok(ast::take_expr(arg_stack.pop().unwrap()))
+}
+
+/// Returns true if `op` is one of the ordering operators which may be chained, `a < b <= c`
+fn is_chainable(op: &str) -> bool {
+    let op = if op.starts_with('#') {
+        op[1..].trim_left_matches(char::is_alphanumeric)
+    } else {
+        op
+    };
+    match op {
+        "<" | "<=" | ">" | ">=" => true,
+        _ => false,
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum InfixToken<Id> {
-    Arg(Box<SpannedExpr<Id>>),
+/// Returns true if `expr` is a variable or a literal, which are as cheap to evaluate again as
+/// they would be to bind to a variable
+fn is_trivial<Id>(expr: &SpannedExpr<Id>) -> bool {
+    match expr.value {
+        Expr::Ident(_) | Expr::Literal(_) => true,
+        _ => false,
+    }
+}
+
+/// Translates chained comparisons such as `a < b <= c` into `a < b && b <= c` so that the second
+/// comparison is only evaluated if the first one succeeds. Operands in the middle which are not
+/// variables or literals are bound to a fresh variable first so that they are only evaluated
+/// once, `a < f x <= c` becomes `let comparison:1 = f x in a < comparison:1 && comparison:1 <= c`.
+///
+/// Only the ordering operators are chained since `a < b == c` is already a valid comparison
+/// between booleans.
+fn chain_comparisons<'ast, Id>(
+    arena: &'ast Arena<'ast, Id>,
+    mut expr: SpannedExpr<'ast, Id>,
+    symbols: &mut IdentEnv<Ident = Id>,
+    and_op: &Id,
+) -> SpannedExpr<'ast, Id>
+where
+    Id: Clone,
+{
+    let span = expr.span;
+
+    // Collect the operands and operators of `((a < b) <= c) < d`, starting from the right
+    let mut operands = Vec::new();
+    let mut ops = Vec::new();
+    let first = loop {
+        let span = expr.span;
+        expr = match expr.value {
+            Expr::Infix(lhs, op, rhs) => {
+                if !is_chainable(symbols.string(&op.value.name)) {
+                    break pos::spanned(span, Expr::Infix(lhs, op, rhs));
+                }
+                operands.push(ast::take_expr(rhs));
+                ops.push(op);
+                ast::take_expr(lhs)
+            }
+            value => break pos::spanned(span, value),
+        };
+    };
+    operands.push(first);
+    operands.reverse();
+    ops.reverse();
+
+    if ops.len() < 2 {
+        return match ops.pop() {
+            Some(op) => {
+                let rhs = operands.pop().unwrap();
+                let lhs = operands.pop().unwrap();
+                pos::spanned(span, Expr::Infix(arena.alloc(lhs), op, arena.alloc(rhs)))
+            }
+            None => operands.pop().unwrap(),
+        };
+    }
+
+    // Each operand in the middle is used by two comparisons
+    let mut bindings = Vec::new();
+    for operand in operands.iter_mut().skip(1).take(ops.len() - 1) {
+        if is_trivial(operand) {
+            bindings.push(None);
+        } else {
+            let id = TypedIdent::new(symbols.fresh_ident("comparison"));
+            let operand_span = operand.span;
+            let value = mem::replace(
+                operand,
+                pos::spanned(operand_span, Expr::Ident(id.clone())),
+            );
+            bindings.push(Some((id, value)));
+        }
+    }
+
+    // a < b && (b < c && c < d)
+    let last_op = ops.pop().unwrap();
+    let last_rhs = operands.pop().unwrap();
+    let last_lhs = ast::clone_expr(arena, operands.last().unwrap());
+    let rhs_span = pos::span(last_lhs.span.start, last_rhs.span.end);
+    let mut result = pos::spanned(
+        rhs_span,
+        Expr::Infix(arena.alloc(last_lhs), last_op, arena.alloc(last_rhs)),
+    );
+    while let Some(op) = ops.pop() {
+        let rhs = operands.pop().unwrap();
+        let lhs = if ops.is_empty() {
+            operands.pop().unwrap()
+        } else {
+            ast::clone_expr(arena, operands.last().unwrap())
+        };
+        let and = pos::spanned(op.span, TypedIdent::new(and_op.clone()));
+        let comparison_span = pos::span(lhs.span.start, rhs.span.end);
+        let comparison = pos::spanned(
+            comparison_span,
+            Expr::Infix(arena.alloc(lhs), op, arena.alloc(rhs)),
+        );
+        let and_span = pos::span(comparison_span.start, result.span.end);
+        result = pos::spanned(
+            and_span,
+            Expr::Infix(arena.alloc(comparison), and, arena.alloc(result)),
+        );
+        // The operand on the right of `op` is used by the comparisons in `result`
+        if let Some((id, value)) = bindings.pop().unwrap() {
+            let binding = ValueBinding {
+                comment: None,
+                comments: Vec::new(),
+                attributes: Vec::new(),
+                name: pos::spanned(value.span, Pattern::Ident(id)),
+                typ: None,
+                resolved_type: Type::hole(),
+                implicit_args: Vec::new(),
+                args: Vec::new(),
+                expr: value,
+            };
+            result = pos::spanned(
+                and_span,
+                Expr::LetBindings(arena.alloc_extend(Some(binding)), arena.alloc(result)),
+            );
+        }
+    }
+    result.span = span;
+    result
+}
+
+#[derive(Debug, PartialEq)]
+enum InfixToken<'ast, Id: 'ast> {
+    Arg(&'ast mut SpannedExpr<'ast, Id>),
     // TODO: Make this spanned to allow for accurate error reporting
     Op(SpannedIdent<Id>),
 }
@@ -341,58 +573,61 @@ enum InfixToken<Id> {
 /// Op:   -
 /// Arg:  8
 /// ```
-struct Infixes<Id> {
+struct Infixes<'ast, Id: 'ast> {
     /// The next part of the expression that we need to flatten
-    remaining_expr: Option<Box<SpannedExpr<Id>>>,
+    remaining_expr: Option<&'ast mut SpannedExpr<'ast, Id>>,
     /// Cached operator from a previous iteration
     next_op: Option<SpannedIdent<Id>>,
 }
 
-impl<Id> Infixes<Id> {
-    fn new(expr: SpannedExpr<Id>) -> Infixes<Id> {
+impl<'ast, Id> Infixes<'ast, Id> {
+    fn new(expr: &'ast mut SpannedExpr<'ast, Id>) -> Infixes<'ast, Id> {
         Infixes {
-            remaining_expr: Some(Box::new(expr)),
+            remaining_expr: Some(expr),
             next_op: None,
         }
     }
 }
 
-impl<Id> Iterator for Infixes<Id> {
-    type Item = InfixToken<Id>;
+impl<'ast, Id> Iterator for Infixes<'ast, Id> {
+    type Item = InfixToken<'ast, Id>;
 
-    fn next(&mut self) -> Option<InfixToken<Id>> {
+    fn next(&mut self) -> Option<InfixToken<'ast, Id>> {
         if let Some(op) = self.next_op.take() {
             return Some(InfixToken::Op(op));
         }
 
-        self.remaining_expr.take().map(|expr| {
-            let expr = *expr; // Workaround for http://stackoverflow.com/questions/28466809/
-            match expr.value {
+        self.remaining_expr.take().map(|expr| match expr.value {
+            Expr::Infix(..) => match ast::take_expr(expr).value {
                 Expr::Infix(lhs, op, rhs) => {
                     self.remaining_expr = Some(rhs);
                     self.next_op = Some(op);
                     InfixToken::Arg(lhs)
                 }
-                _ => InfixToken::Arg(Box::new(expr)), // FIXME: remove reallocation?
-            }
+                _ => unreachable!(),
+            },
+            _ => InfixToken::Arg(expr),
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use base::ast::{DisplayEnv, Expr, IdentEnv, Literal, SpannedExpr, TypedIdent};
+    use base::ast::{clone_expr, take_expr, Arena, DisplayEnv, Expr, IdentEnv, Literal, Pattern,
+                    SpannedExpr, TypedIdent, ValueBinding};
     use base::pos::{self, BytePos, Spanned};
+    use base::types::Type;
+    use std::collections::HashMap;
     use std::marker::PhantomData;
 
-    use super::{reparse, Fixity, InfixToken, Infixes, OpMeta, OpTable};
+    use super::{chain_comparisons, reparse, Fixity, InfixToken, Infixes, OpMeta, OpTable};
     use super::Error::*;
 
-    pub struct MockEnv<T>(PhantomData<T>);
+    pub struct MockEnv<T>(HashMap<String, usize>, PhantomData<T>);
 
     impl<T> MockEnv<T> {
         pub fn new() -> MockEnv<T> {
-            MockEnv(PhantomData)
+            MockEnv(HashMap::new(), PhantomData)
         }
     }
 
@@ -411,6 +646,12 @@ mod tests {
         fn from_str(&mut self, s: &str) -> Self::Ident {
             T::from(s)
         }
+
+        fn fresh_ident(&mut self, prefix: &str) -> Self::Ident {
+            let count = self.0.entry(prefix.to_string()).or_insert(0);
+            *count += 1;
+            T::from(&*format!("{}:{}", prefix, count))
+        }
     }
 
 
@@ -422,37 +663,52 @@ mod tests {
         TypedIdent::new(name.to_string())
     }
 
-    fn op(
-        lhs: Box<SpannedExpr<String>>,
+    type ExprRef<'ast> = &'ast mut SpannedExpr<'ast, String>;
+
+    fn op<'ast>(
+        arena: &'ast Arena<'ast, String>,
+        lhs: ExprRef<'ast>,
         op_str: &str,
-        rhs: Box<SpannedExpr<String>>,
-    ) -> Box<SpannedExpr<String>> {
-        Box::new(no_loc(Expr::Infix(lhs, no_loc(ident(op_str)), rhs)))
+        rhs: ExprRef<'ast>,
+    ) -> ExprRef<'ast> {
+        arena.alloc(no_loc(Expr::Infix(lhs, no_loc(ident(op_str)), rhs)))
     }
 
-    fn int(value: i64) -> Box<SpannedExpr<String>> {
-        Box::new(no_loc(Expr::Literal(Literal::Int(value))))
+    fn int<'ast>(arena: &'ast Arena<'ast, String>, value: i64) -> ExprRef<'ast> {
+        arena.alloc(no_loc(Expr::Literal(Literal::Int(value))))
     }
 
     #[test]
     fn infixes() {
+        let arena = &Arena::new();
         let expr = op(
-            int(1),
+            arena,
+            int(arena, 1),
             "+",
-            op(int(2), "^", op(int(4), "*", op(int(6), "-", int(8)))),
+            op(
+                arena,
+                int(arena, 2),
+                "^",
+                op(
+                    arena,
+                    int(arena, 4),
+                    "*",
+                    op(arena, int(arena, 6), "-", int(arena, 8)),
+                ),
+            ),
         );
 
-        let result: Vec<_> = Infixes::new(*expr).collect();
+        let result: Vec<_> = Infixes::new(expr).collect();
         let expected = vec![
-            InfixToken::Arg(int(1)),
+            InfixToken::Arg(int(arena, 1)),
             InfixToken::Op(no_loc(ident("+"))),
-            InfixToken::Arg(int(2)),
+            InfixToken::Arg(int(arena, 2)),
             InfixToken::Op(no_loc(ident("^"))),
-            InfixToken::Arg(int(4)),
+            InfixToken::Arg(int(arena, 4)),
             InfixToken::Op(no_loc(ident("*"))),
-            InfixToken::Arg(int(6)),
+            InfixToken::Arg(int(arena, 6)),
             InfixToken::Op(no_loc(ident("-"))),
-            InfixToken::Arg(int(8)),
+            InfixToken::Arg(int(arena, 8)),
         ];
 
         assert_eq!(result, expected);
@@ -460,17 +716,19 @@ mod tests {
 
     #[test]
     fn reparse_single() {
+        let arena = &Arena::new();
         let env = MockEnv::new();
         let ops = OpTable::new(vec![]);
 
-        let expr = *op(int(1), "+", int(2));
-        let expected = Ok(expr.clone());
+        let expr = take_expr(op(arena, int(arena, 1), "+", int(arena, 2)));
+        let expected = Ok(clone_expr(arena, &expr));
 
-        assert_eq!(reparse(expr, &env, &ops), expected);
+        assert_eq!(reparse(arena, expr, &env, &ops), expected);
     }
 
     #[test]
     fn reparse_less_precedence() {
+        let arena = &Arena::new();
         let env = MockEnv::new();
         let ops = OpTable::new(vec![
             ("*", OpMeta::new(7, Fixity::Left)),
@@ -478,14 +736,20 @@ mod tests {
         ]);
 
         // 1 + (2 * 8)
-        let expr = *op(int(1), "+", op(int(2), "*", int(8)));
-        let expected = Ok(expr.clone());
+        let expr = take_expr(op(
+            arena,
+            int(arena, 1),
+            "+",
+            op(arena, int(arena, 2), "*", int(arena, 8)),
+        ));
+        let expected = Ok(clone_expr(arena, &expr));
 
-        assert_eq!(reparse(expr, &env, &ops), expected);
+        assert_eq!(reparse(arena, expr, &env, &ops), expected);
     }
 
     #[test]
     fn reparse_greater_precedence() {
+        let arena = &Arena::new();
         let env = MockEnv::new();
         let ops = OpTable::new(vec![
             ("*", OpMeta::new(7, Fixity::Left)),
@@ -493,15 +757,26 @@ mod tests {
         ]);
 
         // 1 * (2 + 8)
-        let expr = *op(int(1), "*", op(int(2), "+", int(8)));
+        let expr = take_expr(op(
+            arena,
+            int(arena, 1),
+            "*",
+            op(arena, int(arena, 2), "+", int(arena, 8)),
+        ));
         // (1 * 2) + 8
-        let expected = Ok(*op(op(int(1), "*", int(2)), "+", int(8)));
+        let expected = Ok(take_expr(op(
+            arena,
+            op(arena, int(arena, 1), "*", int(arena, 2)),
+            "+",
+            int(arena, 8),
+        )));
 
-        assert_eq!(reparse(expr, &env, &ops), expected);
+        assert_eq!(reparse(arena, expr, &env, &ops), expected);
     }
 
     #[test]
     fn reparse_equal_precedence_left_fixity() {
+        let arena = &Arena::new();
         let env = MockEnv::new();
         let ops = OpTable::new(vec![
             ("-", OpMeta::new(6, Fixity::Left)),
@@ -509,15 +784,26 @@ mod tests {
         ]);
 
         // 1 + (2 - 8)
-        let expr = *op(int(1), "+", op(int(2), "-", int(8)));
+        let expr = take_expr(op(
+            arena,
+            int(arena, 1),
+            "+",
+            op(arena, int(arena, 2), "-", int(arena, 8)),
+        ));
         // (1 + 2) - 8
-        let expected = Ok(*op(op(int(1), "+", int(2)), "-", int(8)));
-
-        assert_eq!(reparse(expr, &env, &ops), expected);
+        let expected = Ok(take_expr(op(
+            arena,
+            op(arena, int(arena, 1), "+", int(arena, 2)),
+            "-",
+            int(arena, 8),
+        )));
+
+        assert_eq!(reparse(arena, expr, &env, &ops), expected);
     }
 
     #[test]
     fn reparse_equal_precedence_right_fixity() {
+        let arena = &Arena::new();
         let env = MockEnv::new();
         let ops = OpTable::new(vec![
             ("-", OpMeta::new(6, Fixity::Right)),
@@ -525,14 +811,20 @@ mod tests {
         ]);
 
         // 1 + (2 - 8)
-        let expr = *op(int(1), "+", op(int(2), "-", int(8)));
-        let expected = Ok(expr.clone());
+        let expr = take_expr(op(
+            arena,
+            int(arena, 1),
+            "+",
+            op(arena, int(arena, 2), "-", int(arena, 8)),
+        ));
+        let expected = Ok(clone_expr(arena, &expr));
 
-        assert_eq!(reparse(expr, &env, &ops), expected);
+        assert_eq!(reparse(arena, expr, &env, &ops), expected);
     }
 
     #[test]
     fn reparse_mixed_precedences_mixed_fixities() {
+        let arena = &Arena::new();
         let env = MockEnv::new();
         let ops = OpTable::new(vec![
             ("*", OpMeta::new(7, Fixity::Left)),
@@ -541,15 +833,36 @@ mod tests {
         ]);
 
         //  1  + (2  * (6   -  8))
-        let expr = *op(int(1), "+", op(int(2), "*", op(int(6), "-", int(8))));
+        let expr = take_expr(op(
+            arena,
+            int(arena, 1),
+            "+",
+            op(
+                arena,
+                int(arena, 2),
+                "*",
+                op(arena, int(arena, 6), "-", int(arena, 8)),
+            ),
+        ));
         // (1  + (2  *  6)) -  8
-        let expected = Ok(*op(op(int(1), "+", op(int(2), "*", int(6))), "-", int(8)));
-
-        assert_eq!(reparse(expr, &env, &ops), expected);
+        let expected = Ok(take_expr(op(
+            arena,
+            op(
+                arena,
+                int(arena, 1),
+                "+",
+                op(arena, int(arena, 2), "*", int(arena, 6)),
+            ),
+            "-",
+            int(arena, 8),
+        )));
+
+        assert_eq!(reparse(arena, expr, &env, &ops), expected);
     }
 
     #[test]
     fn reparse_equal_precedence_conflicting_fixities() {
+        let arena = &Arena::new();
         let env = MockEnv::new();
         let ops = OpTable::new(vec![
             ("|>", OpMeta::new(5, Fixity::Left)),
@@ -557,18 +870,24 @@ mod tests {
         ]);
 
         // 1 |> (2 <| 8)
-        let expr = *op(int(1), "|>", op(int(2), "<|", int(8)));
+        let expr = take_expr(op(
+            arena,
+            int(arena, 1),
+            "|>",
+            op(arena, int(arena, 2), "<|", int(arena, 8)),
+        ));
         let error = ConflictingFixities(
             ("|>".to_string(), OpMeta::new(5, Fixity::Left)),
             ("<|".to_string(), OpMeta::new(5, Fixity::Right)),
         );
         let expected = Err(no_loc(error));
 
-        assert_eq!(reparse(expr, &env, &ops), expected);
+        assert_eq!(reparse(arena, expr, &env, &ops), expected);
     }
 
     #[test]
     fn reparse_equal_precedence_conflicting_fixities_nested() {
+        let arena = &Arena::new();
         let env = MockEnv::new();
         let ops = OpTable::new(vec![
             ("|>", OpMeta::new(5, Fixity::Left)),
@@ -576,13 +895,117 @@ mod tests {
         ]);
 
         // 1 + (1 |> (2 <| 8))
-        let expr = *op(int(1), "+", op(int(1), "|>", op(int(2), "<|", int(8))));
+        let expr = take_expr(op(
+            arena,
+            int(arena, 1),
+            "+",
+            op(
+                arena,
+                int(arena, 1),
+                "|>",
+                op(arena, int(arena, 2), "<|", int(arena, 8)),
+            ),
+        ));
         let error = ConflictingFixities(
             ("|>".to_string(), OpMeta::new(5, Fixity::Left)),
             ("<|".to_string(), OpMeta::new(5, Fixity::Right)),
         );
         let expected = Err(no_loc(error));
 
-        assert_eq!(reparse(expr, &env, &ops), expected);
+        assert_eq!(reparse(arena, expr, &env, &ops), expected);
+    }
+
+    #[test]
+    fn chain_ordering_comparisons() {
+        let arena = &Arena::new();
+        let mut env = MockEnv::new();
+        let and = "&&".to_string();
+
+        // 0 <= 1 < 2 < 3
+        let expr = take_expr(op(
+            arena,
+            op(
+                arena,
+                op(arena, int(arena, 0), "<=", int(arena, 1)),
+                "<",
+                int(arena, 2),
+            ),
+            "<",
+            int(arena, 3),
+        ));
+        // 0 <= 1 && (1 < 2 && 2 < 3)
+        let expected = take_expr(op(
+            arena,
+            op(arena, int(arena, 0), "<=", int(arena, 1)),
+            "&&",
+            op(
+                arena,
+                op(arena, int(arena, 1), "<", int(arena, 2)),
+                "&&",
+                op(arena, int(arena, 2), "<", int(arena, 3)),
+            ),
+        ));
+
+        assert_eq!(chain_comparisons(arena, expr, &mut env, &and), expected);
+    }
+
+    #[test]
+    fn chain_binds_middle_operand() {
+        let arena = &Arena::new();
+        let mut env = MockEnv::new();
+        let and = "&&".to_string();
+
+        // 0 < (1 + 2) < 3
+        let expr = take_expr(op(
+            arena,
+            op(
+                arena,
+                int(arena, 0),
+                "<",
+                op(arena, int(arena, 1), "+", int(arena, 2)),
+            ),
+            "<",
+            int(arena, 3),
+        ));
+        // let comparison:1 = 1 + 2 in 0 < comparison:1 && comparison:1 < 3
+        let middle = || arena.alloc(no_loc(Expr::Ident(ident("comparison:1"))));
+        let binding = ValueBinding {
+            comment: None,
+            comments: Vec::new(),
+            attributes: Vec::new(),
+            name: no_loc(Pattern::Ident(ident("comparison:1"))),
+            typ: None,
+            resolved_type: Type::hole(),
+            implicit_args: Vec::new(),
+            args: Vec::new(),
+            expr: take_expr(op(arena, int(arena, 1), "+", int(arena, 2))),
+        };
+        let body = op(
+            arena,
+            op(arena, int(arena, 0), "<", middle()),
+            "&&",
+            op(arena, middle(), "<", int(arena, 3)),
+        );
+        let expected = no_loc(Expr::LetBindings(arena.alloc_extend(Some(binding)), body));
+
+        assert_eq!(chain_comparisons(arena, expr, &mut env, &and), expected);
+    }
+
+    #[test]
+    fn do_not_chain_equality() {
+        let arena = &Arena::new();
+        let mut env = MockEnv::new();
+        let and = "&&".to_string();
+
+        // (0 < 1) == 2
+        let expr = take_expr(op(
+            arena,
+            op(arena, int(arena, 0), "<", int(arena, 1)),
+            "==",
+            int(arena, 2),
+        ));
+        let expected = clone_expr(arena, &expr);
+
+        assert_eq!(chain_comparisons(arena, expr, &mut env, &and), expected);
     }
 }
@@ -48,6 +48,12 @@ enum Context {
     MatchClause,
     /// In a lambda function
     Lambda,
+    /// Between `for` and `in` in a for expression
+    For,
+    /// Between `in` and `do` in a for expression
+    ForIn,
+    /// Between the `:` and `{` of a labeled block
+    Label,
 }
 
 #[derive(Debug)]
@@ -220,7 +226,7 @@ where
         loop {
             // Retrieve the current indentation level if one exists
             let offside = match (&token.value, self.indent_levels.last().cloned()) {
-                (&Token::ShebangLine(_), _) => return Ok(token),
+                (&Token::ShebangLine(_), _) | (&Token::InnerAttribute(_), _) => return Ok(token),
                 (_, Some(offside)) => offside,
                 (_, None) => {
                     let offside =
@@ -262,19 +268,14 @@ where
                     if token_closes_context(&token.value, offside.context) {
                         match offside.context {
                             Context::If => (),
+                            Context::For => {
+                                let offside = Offside::new(offside.location, Context::ForIn);
+                                self.indent_levels.push(offside)?;
+                                return Ok(token);
+                            }
                             Context::Brace | Context::Bracket | Context::Paren => return Ok(token),
                             Context::Block { .. } if token.value == Token::CloseBlock => {
-                                if let Some(offside) = self.indent_levels.last_mut() {
-                                    // The enclosing block should not emit a block separator for the next
-                                    // expression
-                                    if let Context::Block {
-                                        ref mut emit_semi, ..
-                                    } = offside.context
-                                    {
-                                        *emit_semi = false;
-                                    }
-                                }
-                                return Ok(token);
+                                return Ok(token)
                             }
                             Context::Let | Context::Type => {
                                 let location = {
@@ -345,7 +346,7 @@ where
                 }
                 (Context::Block { emit_semi: false }, Ordering::Equal) => {
                     match token.value {
-                        Token::DocComment { .. } | Token::OpenBlock => (),
+                        Token::DocComment { .. } | Token::Attribute(_) | Token::OpenBlock => (),
                         _ => {
                             // If it is the first token in a sequence we dont want to emit a
                             // separator
@@ -420,8 +421,21 @@ where
 
             // Some tokens directly insert a new context when emitted
             let push_context = match token.value {
-                Token::Let | Token::Do => Some(Context::Let),
+                Token::Do if offside.context == Context::ForIn => {
+                    self.indent_levels.pop();
+                    self.scan_for_next_block(Context::Block { emit_semi: false })?;
+                    return Ok(token);
+                }
+                Token::LBrace if offside.context == Context::Label => {
+                    self.indent_levels.pop();
+                    let offside = Offside::new(token.span.start, Context::Brace);
+                    self.indent_levels.push(offside)?;
+                    self.scan_for_next_block(Context::Block { emit_semi: false })?;
+                    return Ok(token);
+                }
+                Token::Let | Token::Do | Token::Loop => Some(Context::Let),
                 Token::If => Some(Context::If),
+                Token::For => Some(Context::For),
                 Token::Type => Some(Context::Type),
                 Token::Match => Some(Context::Expr),
                 Token::Lambda => Some(Context::Lambda),
@@ -437,6 +451,13 @@ where
 
             // For other tokens we need to scan for the next token to get its position
             match (&token.value, offside.context) {
+                // Type annotations never appear directly in a block so this must be a label
+                (&Token::Colon, Context::Block { .. }) => {
+                    if self.peek_token().value == Token::LBrace {
+                        let offside = Offside::new(token.span.start, Context::Label);
+                        self.indent_levels.push(offside)?;
+                    }
+                }
                 (&Token::In, context) => {
                     self.indent_levels.pop();
                     if let Context::Block { .. } = context {
@@ -500,6 +521,7 @@ fn token_closes_context(token: &Token, context: Context) -> bool {
         | (&Token::CloseBlock, Context::Block { .. })
         | (&Token::In, Context::Let)
         | (&Token::In, Context::Type)
+        | (&Token::In, Context::For)
         | (_, Context::Block { .. }) => true,
         (_, _) => false,
     }
@@ -16,24 +16,32 @@ extern crate pretty;
 extern crate quick_error;
 
 use std::cell::RefCell;
-use std::fmt;
+use std::iter::once;
+use std::{fmt, mem};
 
-use base::ast::{Comment, Do, Expr, IdentEnv, SpannedExpr, SpannedPattern, TypedIdent, ValueBinding};
-use base::error::Errors;
+use base::ast::{self, Attribute, Comment, DisplayEnv, Do, Expr, IdentEnv, Pattern, SpannedExpr,
+                SpannedIdent, SpannedPattern, TypedIdent, ValueBinding, Visitor};
+use base::error::{Errors, Labels};
 use base::pos::{self, BytePos, Span, Spanned};
 use base::symbol::Symbol;
-use base::types::{ArcType, TypeCache};
+use base::timings::{self, Phase};
+use base::types::{ArcType, Type, TypeCache};
 
+use comments::{attach_comments, Root};
 use infix::{OpTable, Reparser};
 use layout::Layout;
 use token::{Token, Tokenizer};
 
+pub use incremental::{reparse_expr, reparse_partial_expr, TextEdit};
 pub use infix::Error as InfixError;
 pub use layout::Error as LayoutError;
 pub use token::Error as TokenizeError;
+pub use token::{KEYWORDS, RESERVED_OPERATORS};
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
+mod comments;
 mod grammar;
+mod incremental;
 mod infix;
 mod layout;
 mod token;
@@ -52,7 +60,7 @@ type LalrpopError<'input> = lalrpop_util::ParseError<
 >;
 
 /// Shrink hidden spans to fit the visible expressions and flatten singleton blocks.
-fn shrink_hidden_spans<Id>(mut expr: SpannedExpr<Id>) -> SpannedExpr<Id> {
+fn shrink_hidden_spans<'ast, Id>(mut expr: SpannedExpr<'ast, Id>) -> SpannedExpr<'ast, Id> {
     match expr.value {
         Expr::Infix(_, _, ref last)
         | Expr::IfElse(_, _, ref last)
@@ -62,7 +70,7 @@ fn shrink_hidden_spans<Id>(mut expr: SpannedExpr<Id>) -> SpannedExpr<Id> {
         Expr::Lambda(ref lambda) => expr.span.end = lambda.body.span.end,
         Expr::Block(ref mut exprs) => match exprs.len() {
             0 => (),
-            1 => return exprs.pop().unwrap(),
+            1 => return ast::take_expr(&mut exprs[0]),
             _ => expr.span.end = exprs.last().unwrap().span.end,
         },
         Expr::Match(_, ref alts) => if let Some(last_alt) = alts.last() {
@@ -71,8 +79,9 @@ fn shrink_hidden_spans<Id>(mut expr: SpannedExpr<Id>) -> SpannedExpr<Id> {
         },
         Expr::App(_, _)
         | Expr::Ident(_)
+        | Expr::ImplicitApp(..)
         | Expr::Literal(_)
-        | Expr::Projection(_, _, _)
+        | Expr::Projection(..)
         | Expr::Array(_)
         | Expr::Record { .. }
         | Expr::Tuple { .. }
@@ -81,6 +90,652 @@ fn shrink_hidden_spans<Id>(mut expr: SpannedExpr<Id>) -> SpannedExpr<Id> {
     expr
 }
 
+/// Reports every use of `continue` or `break` in an expression where they can't be translated
+struct LoopControlChecker<'a, 'input: 'a, Id: 'a> {
+    env: &'a IdentEnv<Ident = Id>,
+    errors: &'a mut Errors<LalrpopError<'input>>,
+}
+
+impl<'a, 'b, 'ast, 'input, Id> Visitor<'b, 'ast> for LoopControlChecker<'a, 'input, Id>
+where
+    Id: 'b + 'ast,
+{
+    type Ident = Id;
+
+    fn visit_expr(&mut self, expr: &'b SpannedExpr<'ast, Id>) {
+        if let Expr::Ident(ref id) = expr.value {
+            let name = self.env.string(&id.name);
+            if name == "continue" || name == "break" {
+                self.errors.push(lalrpop_util::ParseError::User {
+                    error: pos::spanned(expr.span, Error::LoopControl(name.to_string())),
+                });
+            }
+        }
+        ast::walk_expr(self, expr);
+    }
+}
+
+/// Replaces `continue x` in tail position of `expr` with a call to `loop_id` and `break x` with
+/// `x`.
+fn translate_loop_control<'input, Id>(
+    env: &IdentEnv<Ident = Id>,
+    errors: &mut Errors<LalrpopError<'input>>,
+    loop_id: &Id,
+    expr: &mut SpannedExpr<Id>,
+) where
+    Id: Clone,
+{
+    let control = match expr.value {
+        Expr::App(ref mut f, ref mut args) if args.len() == 1 => match f.value {
+            Expr::Ident(ref mut id) => match env.string(&id.name) {
+                "continue" => {
+                    id.name = loop_id.clone();
+                    None
+                }
+                "break" => Some(ast::take_expr(&mut args[0])),
+                _ => None,
+            },
+            _ => None,
+        },
+        Expr::IfElse(_, ref mut if_true, ref mut if_false) => {
+            translate_loop_control(env, errors, loop_id, if_true);
+            translate_loop_control(env, errors, loop_id, if_false);
+            None
+        }
+        Expr::Match(_, ref mut alts) => {
+            for alt in alts.iter_mut() {
+                translate_loop_control(env, errors, loop_id, &mut alt.expr);
+            }
+            None
+        }
+        Expr::LetBindings(_, ref mut body) | Expr::TypeBindings(_, ref mut body) => {
+            translate_loop_control(env, errors, loop_id, body);
+            None
+        }
+        Expr::Block(ref mut exprs) => {
+            if let Some(last) = exprs.last_mut() {
+                translate_loop_control(env, errors, loop_id, last);
+            }
+            None
+        }
+        _ => None,
+    };
+    if let Some(value) = control {
+        *expr = value;
+    }
+
+    // Any `continue` or `break` which were not translated above are not in tail position
+    let mut checker = LoopControlChecker { env, errors };
+    match expr.value {
+        Expr::App(ref f, ref args) => {
+            checker.visit_expr(f);
+            for arg in args.iter() {
+                checker.visit_expr(arg);
+            }
+        }
+        Expr::IfElse(ref pred, _, _) => checker.visit_expr(pred),
+        Expr::Match(ref scrutinee, _) => checker.visit_expr(scrutinee),
+        Expr::LetBindings(ref binds, _) => for bind in binds.iter() {
+            checker.visit_expr(&bind.expr);
+        },
+        Expr::Block(ref exprs) => for expr in exprs.iter().rev().skip(1) {
+            checker.visit_expr(expr);
+        },
+        Expr::TypeBindings(..) => (),
+        _ => checker.visit_expr(expr),
+    }
+}
+
+/// Translates `loop x = init in body` into a tail recursive function
+/// `let loop x = body in loop init` where each `continue y` in tail position of `body` calls
+/// `loop y` and each `break y` returns `y`.
+fn loop_expr<'input, 'ast, Id>(
+    env: MutIdentEnv<Id>,
+    type_cache: &TypeCache<Id, ArcType<Id>>,
+    errors: &mut Errors<LalrpopError<'input>>,
+    arena: ArenaRef<'ast, Id>,
+    id: SpannedIdent<Id>,
+    init: SpannedExpr<'ast, Id>,
+    mut body: SpannedExpr<'ast, Id>,
+) -> Expr<'ast, Id>
+where
+    Id: Clone,
+{
+    // `#` can't appear in identifiers so the function can't be referred to from `body`
+    let loop_id = env.from_str("loop#");
+    translate_loop_control(&*env, errors, &loop_id, &mut body);
+
+    let loop_span = id.span;
+    let init_span = init.span;
+    Expr::LetBindings(
+        arena.alloc_extend(once(ValueBinding {
+            comment: None,
+            comments: Vec::new(),
+            attributes: Vec::new(),
+            name: pos::spanned(
+                loop_span,
+                Pattern::Ident(new_ident(type_cache, loop_id.clone())),
+            ),
+            typ: None,
+            resolved_type: type_cache.hole(),
+            implicit_args: Vec::new(),
+            args: vec![id],
+            expr: body,
+        })),
+        arena.alloc(pos::spanned(
+            init_span,
+            Expr::App(
+                arena.alloc(pos::spanned(
+                    init_span,
+                    Expr::Ident(new_ident(type_cache, loop_id)),
+                )),
+                arena.alloc_extend(once(init)),
+            ),
+        )),
+    )
+}
+
+/// Translates the patterns in the arguments of a function into identifiers which are matched on
+/// at the start of `body`, `\{ x, y } (Some z) -> body` becomes
+/// `\arg:1 arg:2 -> match arg:1 with | { x, y } -> match arg:2 with | Some z -> body`.
+fn pattern_arguments<'ast, Id>(
+    env: MutIdentEnv<Id>,
+    type_cache: &TypeCache<Id, ArcType<Id>>,
+    arena: ArenaRef<'ast, Id>,
+    args: Vec<SpannedPattern<Id>>,
+    body: SpannedExpr<'ast, Id>,
+) -> (Vec<SpannedIdent<Id>>, SpannedExpr<'ast, Id>)
+where
+    Id: Clone,
+{
+    let mut matches = Vec::new();
+    let args = args.into_iter()
+        .map(|arg| match arg.value {
+            Pattern::Ident(id) => pos::spanned(arg.span, id),
+            _ => {
+                let name = env.fresh_ident("arg");
+                let id = pos::spanned(arg.span, new_ident(type_cache, name));
+                matches.push((id.clone(), arg));
+                id
+            }
+        })
+        .collect();
+
+    let body = matches
+        .into_iter()
+        .rev()
+        .fold(body, |body, (id, pattern)| {
+            pos::spanned(
+                body.span,
+                Expr::Match(
+                    arena.alloc(pos::spanned(id.span, Expr::Ident(id.value))),
+                    arena.alloc_extend(once(ast::Alternative {
+                        comments: Vec::new(),
+                        pattern,
+                        expr: body,
+                    })),
+                ),
+            )
+        });
+    (args, body)
+}
+
+/// Creates the lambda of an operator section, `(+ 1)`, `(1 +)` or `(.field)`. `body` is given
+/// the expression which refers to the argument of the lambda, `(+ 1)` becomes
+/// `\section:1 -> section:1 + 1`.
+fn section<'ast, Id, F>(
+    env: MutIdentEnv<Id>,
+    type_cache: &TypeCache<Id, ArcType<Id>>,
+    arena: ArenaRef<'ast, Id>,
+    span: Span<BytePos>,
+    body: F,
+) -> Expr<'ast, Id>
+where
+    Id: Clone,
+    F: FnOnce(SpannedExpr<'ast, Id>) -> Expr<'ast, Id>,
+{
+    let arg = pos::spanned(span, new_ident(type_cache, env.fresh_ident("section")));
+    let arg_expr = pos::spanned(span, Expr::Ident(arg.value.clone()));
+    Expr::Lambda(ast::Lambda {
+        id: new_ident(type_cache, env.from_str("")),
+        args: vec![arg],
+        body: arena.alloc(pos::spanned(span, body(arg_expr))),
+    })
+}
+
+/// Wraps infix expressions in parentheses so that the operand of a section is not reassociated
+/// with the operator of the section, `(* 1 + 2)` is `\x -> x * (1 + 2)`
+fn section_operand<'ast, Id>(
+    arena: ArenaRef<'ast, Id>,
+    expr: SpannedExpr<'ast, Id>,
+) -> SpannedExpr<'ast, Id> {
+    match expr.value {
+        Expr::Infix(..) => pos::spanned(
+            expr.span,
+            Expr::Tuple {
+                typ: Type::hole(),
+                elems: arena.alloc_extend(once(expr)),
+            },
+        ),
+        _ => expr,
+    }
+}
+
+fn is_pattern_argument<Id>(env: &IdentEnv<Ident = Id>, arg: &SpannedIdent<Id>) -> bool {
+    env.string(&arg.value.name).starts_with("arg:")
+}
+
+/// Returns true if `expr` refers to the argument `arg` which was introduced in place of a pattern
+fn is_pattern_argument_expr<Id>(
+    env: &IdentEnv<Ident = Id>,
+    expr: &SpannedExpr<Id>,
+    arg: &SpannedIdent<Id>,
+) -> bool {
+    match expr.value {
+        Expr::Ident(ref id) => {
+            is_pattern_argument(env, arg) && env.string(&id.name) == env.string(&arg.value.name)
+        }
+        _ => false,
+    }
+}
+
+/// Splits a function back into the equations it was defined with. The patterns of each equation
+/// are returned along with its body.
+fn function_clauses<'ast, Id>(
+    env: &IdentEnv<Ident = Id>,
+    args: Vec<SpannedIdent<Id>>,
+    body: SpannedExpr<'ast, Id>,
+) -> Vec<(Vec<SpannedPattern<Id>>, SpannedExpr<'ast, Id>)> {
+    // A function defined by several equations matches on all of its arguments at once,
+    // `match (arg:1, arg:2) with | (p0, p1) -> body0 | ...`
+    let is_merged = match body.value {
+        Expr::Match(ref scrutinee, _) => match scrutinee.value {
+            Expr::Tuple { ref elems, .. } => {
+                elems.len() == args.len()
+                    && elems
+                        .iter()
+                        .zip(&args)
+                        .all(|(elem, arg)| is_pattern_argument_expr(env, elem, arg))
+            }
+            _ => args.len() == 1 && is_pattern_argument_expr(env, scrutinee, &args[0]),
+        },
+        _ => false,
+    };
+    if is_merged {
+        let arity = args.len();
+        return match body.value {
+            Expr::Match(_, alts) => alts.iter_mut()
+                .map(|alt| {
+                    let pattern = take_pattern(&mut alt.pattern);
+                    let expr = ast::take_expr(&mut alt.expr);
+                    match (arity > 1, pattern.value) {
+                        (true, Pattern::Tuple { elems, .. }) => (elems, expr),
+                        (_, value) => (vec![pos::spanned(pattern.span, value)], expr),
+                    }
+                })
+                .collect(),
+            _ => unreachable!(),
+        };
+    }
+
+    // Each pattern argument of a single equation is matched on in turn,
+    // `match arg:1 with | p0 -> match arg:2 with | p1 -> body`
+    let mut body = body;
+    let mut patterns = Vec::with_capacity(args.len());
+    for arg in args {
+        let is_match = match body.value {
+            Expr::Match(ref scrutinee, ref alts) => {
+                alts.len() == 1 && is_pattern_argument_expr(env, scrutinee, &arg)
+            }
+            _ => false,
+        };
+        if is_match {
+            match body.value {
+                Expr::Match(_, alts) => {
+                    patterns.push(take_pattern(&mut alts[0].pattern));
+                    body = ast::take_expr(&mut alts[0].expr);
+                }
+                _ => unreachable!(),
+            }
+        } else {
+            patterns.push(arg.map(Pattern::Ident));
+        }
+    }
+    vec![(patterns, body)]
+}
+
+fn take_pattern<Id>(pattern: &mut SpannedPattern<Id>) -> SpannedPattern<Id> {
+    let span = pattern.span;
+    mem::replace(pattern, pos::spanned(span, Pattern::Error))
+}
+
+/// Returns true if `pattern` may fail to match
+fn is_refutable<Id>(pattern: &SpannedPattern<Id>) -> bool {
+    match pattern.value {
+        Pattern::Constructor(..) | Pattern::Literal(_) => true,
+        Pattern::As(_, ref pattern) => is_refutable(pattern),
+        Pattern::Tuple { ref elems, .. } => elems.iter().any(is_refutable),
+        Pattern::Record { ref fields, .. } => fields
+            .iter()
+            .any(|field| field.value.as_ref().map_or(false, is_refutable)),
+        Pattern::Ident(_) | Pattern::Error => false,
+    }
+}
+
+/// Returns true if `bind` is an equation of a function with a pattern argument which may fail to
+/// match
+fn is_refutable_clause<Id>(env: &IdentEnv<Ident = Id>, bind: &ValueBinding<Id>) -> bool {
+    let mut body = &bind.expr;
+    for arg in &bind.args {
+        match body.value {
+            Expr::Match(ref scrutinee, ref alts)
+                if alts.len() == 1 && is_pattern_argument_expr(env, scrutinee, arg) =>
+            {
+                if is_refutable(&alts[0].pattern) {
+                    return true;
+                }
+                body = &alts[0].expr;
+            }
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Returns true if `next` is an equation which continues the function defined by `bind`.
+///
+/// A function may be defined again with the same name to overload it, so `next` is only an
+/// equation of the same function if `bind` has a pattern which may fail to match. Functions with
+/// implicit arguments are not merged.
+fn is_next_clause<Id>(
+    env: &IdentEnv<Ident = Id>,
+    bind: &ValueBinding<Id>,
+    next: &ValueBinding<Id>,
+) -> bool {
+    match (&bind.name.value, &next.name.value) {
+        (&Pattern::Ident(ref l), &Pattern::Ident(ref r)) => {
+            !bind.args.is_empty() && bind.args.len() == next.args.len()
+                && bind.implicit_args.is_empty() && next.implicit_args.is_empty()
+                && env.string(&l.name) == env.string(&r.name)
+                && is_refutable_clause(env, bind)
+        }
+        _ => false,
+    }
+}
+
+/// Creates a `let` expression. A function defined by several equations in consecutive `let`
+/// bindings, `let f 0 = 1` followed by `let f n = n * f (n - 1)`, is merged into a single
+/// function which tries each equation in turn,
+/// `let f arg:1 = match arg:1 with | 0 -> 1 | n -> n * f (n - 1)` (see `is_next_clause`).
+fn let_bindings<'ast, Id>(
+    env: MutIdentEnv<Id>,
+    type_cache: &TypeCache<Id, ArcType<Id>>,
+    arena: ArenaRef<'ast, Id>,
+    mut binds: Vec<ValueBinding<'ast, Id>>,
+    body: SpannedExpr<'ast, Id>,
+) -> Expr<'ast, Id>
+where
+    Id: Clone,
+{
+    let is_clause = binds.len() == 1 && match body.value {
+        Expr::LetBindings(ref next, _) => {
+            next.len() == 1 && is_next_clause(&*env, &binds[0], &next[0])
+        }
+        _ => false,
+    };
+    if !is_clause {
+        return Expr::LetBindings(arena.alloc_extend(binds), arena.alloc(body));
+    }
+    let (next, body) = match body.value {
+        Expr::LetBindings(next, body) => (&mut next[0], body),
+        _ => unreachable!(),
+    };
+    let bind = binds.pop().unwrap();
+
+    let args: Vec<_> = bind.args
+        .iter()
+        .map(|arg| pos::spanned(arg.span, new_ident(type_cache, env.fresh_ident("arg"))))
+        .collect();
+    let arg_expr = |arg: &SpannedIdent<Id>| pos::spanned(arg.span, Expr::Ident(arg.value.clone()));
+    let scrutinee = if args.len() == 1 {
+        arg_expr(&args[0])
+    } else {
+        pos::spanned(
+            Span::new(args[0].span.start, args.last().unwrap().span.end),
+            Expr::Tuple {
+                typ: type_cache.hole(),
+                elems: arena.alloc_extend(args.iter().map(arg_expr)),
+            },
+        )
+    };
+
+    let mut clauses = function_clauses(&*env, bind.args, bind.expr);
+    clauses.extend(function_clauses(
+        &*env,
+        mem::replace(&mut next.args, Vec::new()),
+        ast::take_expr(&mut next.expr),
+    ));
+    let span = Span::new(clauses[0].1.span.start, clauses.last().unwrap().1.span.end);
+    let alts = clauses
+        .into_iter()
+        .map(|(mut patterns, expr)| {
+            let pattern = if patterns.len() == 1 {
+                patterns.pop().unwrap()
+            } else {
+                pos::spanned(
+                    Span::new(patterns[0].span.start, patterns.last().unwrap().span.end),
+                    Pattern::Tuple {
+                        typ: type_cache.hole(),
+                        elems: patterns,
+                    },
+                )
+            };
+            ast::Alternative {
+                comments: Vec::new(),
+                pattern,
+                expr,
+            }
+        });
+
+    Expr::LetBindings(
+        arena.alloc_extend(once(ValueBinding {
+            comment: bind.comment,
+            comments: Vec::new(),
+            attributes: bind.attributes,
+            name: bind.name,
+            typ: bind.typ.or(next.typ.take()),
+            resolved_type: bind.resolved_type,
+            implicit_args: Vec::new(),
+            args,
+            expr: pos::spanned(
+                span,
+                Expr::Match(arena.alloc(scrutinee), arena.alloc_extend(alts)),
+            ),
+        })),
+        body,
+    )
+}
+
+/// Returns true if `expr` is `exit label ...`
+fn is_exit<Id>(env: &IdentEnv<Ident = Id>, label: &str, expr: &SpannedExpr<Id>) -> bool {
+    match expr.value {
+        Expr::App(ref f, ref args) => match (&f.value, args.first().map(|arg| &arg.value)) {
+            (&Expr::Ident(ref f), Some(&Expr::Ident(ref id))) => {
+                env.string(&f.name) == "exit" && env.string(&id.name) == label
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Searches an expression for `exit label` expressions
+struct ExitFinder<'a, Id: 'a> {
+    env: &'a IdentEnv<Ident = Id>,
+    label: &'a str,
+    spans: Vec<Span<BytePos>>,
+}
+
+impl<'a, 'b, 'ast, Id> Visitor<'b, 'ast> for ExitFinder<'a, Id>
+where
+    Id: 'b + 'ast,
+{
+    type Ident = Id;
+
+    fn visit_expr(&mut self, expr: &'b SpannedExpr<'ast, Id>) {
+        if is_exit(self.env, self.label, expr) {
+            self.spans.push(expr.span);
+        }
+        ast::walk_expr(self, expr);
+    }
+}
+
+fn find_exits<Id>(
+    env: &IdentEnv<Ident = Id>,
+    label: &str,
+    expr: &SpannedExpr<Id>,
+) -> Vec<Span<BytePos>> {
+    let mut finder = ExitFinder {
+        env,
+        label,
+        spans: Vec::new(),
+    };
+    finder.visit_expr(expr);
+    finder.spans
+}
+
+fn is_unit<Id>(expr: &SpannedExpr<Id>) -> bool {
+    match expr.value {
+        Expr::Tuple { ref elems, .. } => elems.is_empty(),
+        _ => false,
+    }
+}
+
+/// Evaluates `expr` followed by `rest`
+fn sequence<'ast, Id>(
+    arena: ArenaRef<'ast, Id>,
+    expr: SpannedExpr<'ast, Id>,
+    rest: Option<SpannedExpr<'ast, Id>>,
+) -> SpannedExpr<'ast, Id> {
+    let unit = is_unit(&expr);
+    match rest {
+        None => expr,
+        // `()` has no effect so there is no need to evaluate it
+        Some(rest) => if unit {
+            rest
+        } else {
+            let span = Span::new(expr.span.start, rest.span.end);
+            let exprs = match rest.value {
+                Expr::Block(exprs) => {
+                    arena.alloc_extend(once(expr).chain(exprs.iter_mut().map(ast::take_expr)))
+                }
+                rest_expr => {
+                    arena.alloc_extend(vec![expr, pos::spanned(rest.span, rest_expr)])
+                }
+            };
+            pos::spanned(span, Expr::Block(exprs))
+        }
+    }
+}
+
+/// Replaces each `exit label value` in `expr` with `value`. `rest` is the continuation of `expr`,
+/// the expression which is evaluated after `expr` unless `expr` exits, and is moved into every
+/// branch of `expr` which does not exit.
+fn translate_exits<'ast, Id>(
+    env: &IdentEnv<Ident = Id>,
+    arena: ArenaRef<'ast, Id>,
+    label: &str,
+    mut expr: SpannedExpr<'ast, Id>,
+    rest: Option<SpannedExpr<'ast, Id>>,
+) -> SpannedExpr<'ast, Id>
+where
+    Id: Clone,
+{
+    if find_exits(env, label, &expr).is_empty() {
+        return sequence(arena, expr, rest);
+    }
+    if is_exit(env, label, &expr) {
+        if let Expr::App(_, ref mut args) = expr.value {
+            if args.len() == 2 {
+                return ast::take_expr(&mut args[1]);
+            }
+        }
+    }
+    let clone_rest = |rest: &Option<SpannedExpr<Id>>| {
+        rest.as_ref().map(|rest| ast::clone_expr(arena, rest))
+    };
+    let span = Span::new(
+        expr.span.start,
+        rest.as_ref().map_or(expr.span.end, |rest| rest.span.end),
+    );
+    let value = match expr.value {
+        Expr::IfElse(pred, if_true, if_false) => {
+            let if_true_rest = clone_rest(&rest);
+            *if_true = translate_exits(env, arena, label, ast::take_expr(if_true), if_true_rest);
+            *if_false = translate_exits(env, arena, label, ast::take_expr(if_false), rest);
+            Expr::IfElse(pred, if_true, if_false)
+        }
+        Expr::Match(scrutinee, alts) => {
+            for alt in alts.iter_mut() {
+                let alt_expr = ast::take_expr(&mut alt.expr);
+                alt.expr = translate_exits(env, arena, label, alt_expr, clone_rest(&rest));
+            }
+            Expr::Match(scrutinee, alts)
+        }
+        Expr::LetBindings(binds, body) => {
+            *body = translate_exits(env, arena, label, ast::take_expr(body), rest);
+            Expr::LetBindings(binds, body)
+        }
+        Expr::TypeBindings(binds, body) => {
+            *body = translate_exits(env, arena, label, ast::take_expr(body), rest);
+            Expr::TypeBindings(binds, body)
+        }
+        Expr::Block(exprs) => {
+            return exprs
+                .iter_mut()
+                .rev()
+                .fold(rest, |rest, expr| {
+                    Some(translate_exits(env, arena, label, ast::take_expr(expr), rest))
+                })
+                .expect("Empty block");
+        }
+        // The `exit` is nested in a position where it can't be translated, it will be reported
+        // as an error by `labeled_block`
+        value => return sequence(arena, pos::spanned(expr.span, value), rest),
+    };
+    pos::spanned(span, value)
+}
+
+/// Translates `label: { body }` by replacing each `exit label value` in `body` with `value` and
+/// moving the expressions which follow the `exit` into the branches which do not exit.
+/// ```gluon
+/// label: {
+///     if x < 0 then exit label "negative" else ()
+///     "positive"
+/// }
+/// // becomes
+/// if x < 0 then "negative" else "positive"
+/// ```
+fn labeled_block<'input, 'ast, Id>(
+    env: MutIdentEnv<Id>,
+    errors: &mut Errors<LalrpopError<'input>>,
+    arena: ArenaRef<'ast, Id>,
+    label: Id,
+    body: SpannedExpr<'ast, Id>,
+) -> Expr<'ast, Id>
+where
+    Id: Clone,
+{
+    let label = env.string(&label).to_string();
+    let body = translate_exits(&*env, arena, &label, body, None);
+    for span in find_exits(&*env, &label, &body) {
+        errors.push(lalrpop_util::ParseError::User {
+            error: pos::spanned(span, Error::Exit(label.clone())),
+        });
+    }
+    body.value
+}
+
 fn transform_errors<'a, Iter>(errors: Iter) -> Errors<Spanned<Error, BytePos>>
 where
     Iter: IntoIterator<Item = LalrpopError<'a>>,
@@ -143,9 +798,21 @@ quick_error! {
             display("{}", err)
             from()
         }
+        LoopControl(name: String) {
+            description("loop control outside of tail position")
+            display("`{}` can only be used in tail position of a `loop` expression and must be \
+                     applied to a single argument", name)
+        }
+        Exit(label: String) {
+            description("exit outside of a labeled block")
+            display("`exit {}` can only be used in tail position of the block labeled `{}` or \
+                     directly followed by the rest of the block", label, label)
+        }
     }
 }
 
+impl Labels for Error {}
+
 /// LALRPOP currently has an unnecessary set of `"` around each expected token
 fn remove_extra_quotes(tokens: &mut [String]) {
     for token in tokens {
@@ -219,7 +886,8 @@ where
     type Item = T;
 
     fn next(&mut self) -> Option<T> {
-        match self.iter.next() {
+        let iter = &mut self.iter;
+        match timings::nested(Phase::Lex, || iter.next()) {
             Some(Ok(t)) => Some(t),
             Some(Err(err)) => {
                 self.error = Some(err);
@@ -263,18 +931,20 @@ pub enum FieldPattern<Id> {
     Value(Spanned<Id, BytePos>, Option<SpannedPattern<Id>>),
 }
 
-pub enum FieldExpr<Id> {
+pub enum FieldExpr<'ast, Id: 'ast> {
     Type(Option<Comment>, Spanned<Id, BytePos>, Option<ArcType<Id>>),
     Value(
         Option<Comment>,
+        bool,
         Spanned<Id, BytePos>,
-        Option<SpannedExpr<Id>>,
+        Option<SpannedExpr<'ast, Id>>,
     ),
 }
 
 // Hack around LALRPOP's limited type syntax
 type MutIdentEnv<'env, Id> = &'env mut IdentEnv<Ident = Id>;
 type ErrorEnv<'err, 'input> = &'err mut Errors<LalrpopError<'input>>;
+type ArenaRef<'ast, Id> = &'ast ast::Arena<'ast, Id>;
 
 pub type ParseErrors = Errors<Spanned<Error, BytePos>>;
 
@@ -300,11 +970,13 @@ macro_rules! layout {
     } }
 }
 
-pub fn parse_partial_expr<Id>(
+/// Parses `input` into an expression whose nodes are allocated in `arena`
+pub fn parse_partial_expr<'ast, Id>(
+    arena: &'ast ast::Arena<'ast, Id>,
     symbols: &mut IdentEnv<Ident = Id>,
     type_cache: &TypeCache<Id, ArcType<Id>>,
     input: &str,
-) -> Result<SpannedExpr<Id>, (Option<SpannedExpr<Id>>, ParseErrors)>
+) -> Result<SpannedExpr<'ast, Id>, (Option<SpannedExpr<'ast, Id>>, ParseErrors)>
 where
     Id: Clone,
 {
@@ -313,7 +985,15 @@ where
 
     let mut parse_errors = Errors::new();
 
-    let result = grammar::parse_TopExpr(input, type_cache, symbols, &mut parse_errors, layout);
+    let result = grammar::parse_TopExpr(
+        input,
+        type_cache,
+        symbols,
+        &mut parse_errors,
+        arena,
+        layout,
+    );
+    let comments = result_ok_iter.borrow_mut().iter.take_comments();
 
     // If there is a tokenizer error it may still exist in the result iterator wrapper.
     // If that is the case we return that error instead of the unexpected EOF error that lalrpop
@@ -332,10 +1012,11 @@ where
     match result {
         Ok(mut expr) => {
             let mut errors = transform_errors(parse_errors);
-            let mut reparser = Reparser::new(OpTable::default(), symbols);
+            let mut reparser = Reparser::new(OpTable::default(), symbols, arena);
             if let Err(reparse_errors) = reparser.reparse(&mut expr) {
                 errors.extend(reparse_errors.into_iter().map(|err| err.map(Error::Infix)));
             }
+            attach_comments(input, Root::Expr(&mut expr), comments);
 
             if errors.has_errors() {
                 Err((Some(expr), errors))
@@ -350,20 +1031,36 @@ where
     }
 }
 
-pub fn parse_expr(
+pub fn parse_expr<'ast>(
+    arena: &'ast ast::Arena<'ast, Symbol>,
     symbols: &mut IdentEnv<Ident = Symbol>,
     type_cache: &TypeCache<Symbol, ArcType>,
     input: &str,
-) -> Result<SpannedExpr<Symbol>, ParseErrors> {
-    parse_partial_expr(symbols, type_cache, input).map_err(|t| t.1)
+) -> Result<SpannedExpr<'ast, Symbol>, ParseErrors> {
+    parse_partial_expr(arena, symbols, type_cache, input).map_err(|t| t.1)
 }
 
-pub type LetOrExpr<Id> = Result<SpannedExpr<Id>, ValueBinding<Id>>;
+/// Returns the `#![name]` attributes which apply to the module in `input`. Module attributes
+/// must come before the expression of the module, after the shebang line if there is one.
+pub fn module_attributes(input: &str) -> Vec<Attribute> {
+    let mut attributes = Vec::new();
+    for token in Tokenizer::new(input) {
+        match token.map(|token| token.value) {
+            Ok(Token::ShebangLine(_)) => (),
+            Ok(Token::InnerAttribute(attribute)) => attributes.push(attribute),
+            _ => break,
+        }
+    }
+    attributes
+}
 
-pub fn parse_partial_let_or_expr<Id>(
+pub type LetOrExpr<'ast, Id> = Result<SpannedExpr<'ast, Id>, ValueBinding<'ast, Id>>;
+
+pub fn parse_partial_let_or_expr<'ast, Id>(
+    arena: &'ast ast::Arena<'ast, Id>,
     symbols: &mut IdentEnv<Ident = Id>,
     input: &str,
-) -> Result<LetOrExpr<Id>, (Option<LetOrExpr<Id>>, ParseErrors)>
+) -> Result<LetOrExpr<'ast, Id>, (Option<LetOrExpr<'ast, Id>>, ParseErrors)>
 where
     Id: Clone,
 {
@@ -374,7 +1071,15 @@ where
 
     let type_cache = TypeCache::new();
 
-    let result = grammar::parse_LetOrExpr(input, &type_cache, symbols, &mut parse_errors, layout);
+    let result = grammar::parse_LetOrExpr(
+        input,
+        &type_cache,
+        symbols,
+        &mut parse_errors,
+        arena,
+        layout,
+    );
+    let comments = result_ok_iter.borrow_mut().iter.take_comments();
 
     // If there is a tokenizer error it may still exist in the result iterator wrapper.
     // If that is the case we return that error instead of the unexpected EOF error that lalrpop
@@ -393,7 +1098,7 @@ where
     match result {
         Ok(mut let_or_expr) => {
             let mut errors = transform_errors(parse_errors);
-            let mut reparser = Reparser::new(OpTable::default(), symbols);
+            let mut reparser = Reparser::new(OpTable::default(), symbols, arena);
             let result = match let_or_expr {
                 Ok(ref mut expr) => reparser.reparse(expr),
                 Err(ref mut let_binding) => reparser.reparse(&mut let_binding.expr),
@@ -401,6 +1106,13 @@ where
             if let Err(reparse_errors) = result {
                 errors.extend(reparse_errors.into_iter().map(|err| err.map(Error::Infix)));
             }
+            {
+                let root = match let_or_expr {
+                    Ok(ref mut expr) => Root::Expr(expr),
+                    Err(ref mut let_binding) => Root::Binding(let_binding),
+                };
+                attach_comments(input, root, comments);
+            }
 
             if errors.has_errors() {
                 Err((Some(let_or_expr), errors))
@@ -416,9 +1128,10 @@ where
 }
 
 #[cfg(feature = "test")]
-pub fn parse_string<'env, 'input>(
+pub fn parse_string<'env, 'input, 'ast>(
+    arena: &'ast ast::Arena<'ast, String>,
     symbols: &'env mut IdentEnv<Ident = String>,
     input: &'input str,
-) -> Result<SpannedExpr<String>, (Option<SpannedExpr<String>>, ParseErrors)> {
-    parse_partial_expr(symbols, &TypeCache::new(), input)
+) -> Result<SpannedExpr<'ast, String>, (Option<SpannedExpr<'ast, String>>, ParseErrors)> {
+    parse_partial_expr(arena, symbols, &TypeCache::new(), input)
 }
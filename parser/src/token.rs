@@ -1,4 +1,4 @@
-use base::ast::{is_operator_char, Comment, CommentType};
+use base::ast::{is_operator_char, Attribute, Comment, CommentType, SpannedComment};
 use base::pos::{self, BytePos, Column, Line, Location, Spanned};
 use std::fmt;
 use std::str::Chars;
@@ -17,13 +17,18 @@ pub enum Token<'input> {
     ByteLiteral(u8),
     FloatLiteral(f64),
     DocComment(Comment),
+    Attribute(Attribute),
+    /// A `#![name]` attribute which applies to the whole module
+    InnerAttribute(Attribute),
 
     And,
     Else,
+    For,
     Forall,
     If,
     In,
     Let,
+    Loop,
     Do,
     Match,
     Then,
@@ -38,6 +43,7 @@ pub enum Token<'input> {
     Equals,
     Lambda,
     Pipe,
+    Question,
     RArrow,
 
     LBrace,
@@ -68,13 +74,17 @@ impl<'input> fmt::Display for Token<'input> {
             ByteLiteral(_) => "ByteLiteral",
             FloatLiteral(_) => "FloatLiteral",
             DocComment { .. } => "DocComment",
+            Attribute(_) => "Attribute",
+            InnerAttribute(_) => "InnerAttribute",
 
             And => "And",
             Else => "Else",
+            For => "For",
             Forall => "Forall",
             If => "If",
             In => "In",
             Let => "Let",
+            Loop => "Loop",
             Do => "Do",
             Match => "Match",
             Then => "Then",
@@ -97,6 +107,7 @@ impl<'input> fmt::Display for Token<'input> {
             Equals => "Equal",
             Lambda => "Lambda",
             Pipe => "Pipe",
+            Question => "Question",
             RArrow => "RArrow",
 
             OpenBlock => "OpenBlock",
@@ -118,6 +129,16 @@ impl<'input> Token<'input> {
     }
 }
 
+/// The keywords recognized by the lexer. `for` and `loop` are only keywords when they start a
+/// `for x in xs do ...` or `loop x = init in ...` expression.
+pub const KEYWORDS: &[&str] = &[
+    "and", "do", "else", "for", "forall", "if", "in", "let", "loop", "match", "then", "type",
+    "with",
+];
+
+/// Operators which are part of the syntax instead of being defined in gluon code
+pub const RESERVED_OPERATORS: &[&str] = &["@", ".", "..", ":", "=", "|", "->", "\\"];
+
 pub type SpannedToken<'input> = Spanned<Token<'input>, Location>;
 
 pub type SpError = Spanned<Error, Location>;
@@ -158,6 +179,15 @@ quick_error! {
         HexLiteralIncomplete {
             description("cannot parse hex literal, incomplete")
         }
+        IntLiteralIncomplete {
+            description("cannot parse integer literal, incomplete")
+        }
+        UnterminatedAttribute {
+            description("unterminated attribute")
+        }
+        InvalidAttribute {
+            description("invalid attribute, expected `#[name]` or `#[name(arguments)]`")
+        }
     }
 }
 
@@ -181,12 +211,40 @@ fn is_ident_continue(ch: char) -> bool {
     }
 }
 
-fn is_digit(ch: char) -> bool {
-    ch.is_digit(10)
+/// Returns `true` if `rest`, the input directly after a `for` identifier, has the form `x in ...`
+fn starts_for_expression(rest: &str) -> bool {
+    let rest = rest.trim_left();
+    if !rest.starts_with(is_ident_start) {
+        return false;
+    }
+    let rest = rest.trim_left_matches(is_ident_continue);
+    let rest = rest.trim_left();
+    rest.starts_with("in") && !rest[2..].starts_with(is_ident_continue)
+}
+
+/// Returns `true` if a `loop` identifier, preceded by `before` and followed by `rest`, has the form
+/// `loop x = ...` and is not the name of a function being defined (`let loop x = ...`)
+fn starts_loop_expression(before: &str, rest: &str) -> bool {
+    let defines_loop = {
+        let before = before.trim_right();
+        ["let", "and"].iter().any(|keyword| {
+            before.ends_with(keyword)
+                && !before[..before.len() - keyword.len()].ends_with(is_ident_continue)
+        })
+    };
+    if defines_loop {
+        return false;
+    }
+    let rest = rest.trim_left();
+    if !rest.starts_with(is_ident_start) {
+        return false;
+    }
+    let rest = rest.trim_left_matches(is_ident_continue).trim_left();
+    rest.starts_with('=') && !rest[1..].starts_with(is_operator_char)
 }
 
-fn is_hex(ch: char) -> bool {
-    ch.is_digit(16)
+fn is_digit(ch: char) -> bool {
+    ch.is_digit(10)
 }
 
 struct CharLocations<'input> {
@@ -229,6 +287,8 @@ pub struct Tokenizer<'input> {
     chars: CharLocations<'input>,
     eof_location: Location,
     lookahead: Option<(Location, char)>,
+    /// The comments which are not documentation comments and therefore not emitted as tokens
+    comments: Vec<SpannedComment>,
 }
 
 impl<'input> Tokenizer<'input> {
@@ -241,9 +301,15 @@ impl<'input> Tokenizer<'input> {
             eof_location: eof_location,
             lookahead: chars.next(),
             chars: chars,
+            comments: Vec::new(),
         }
     }
 
+    /// Returns the comments which have been skipped so far
+    pub fn take_comments(&mut self) -> Vec<SpannedComment> {
+        ::std::mem::replace(&mut self.comments, Vec::new())
+    }
+
     fn bump(&mut self) -> Option<(Location, char)> {
         match self.lookahead {
             Some((location, ch)) => {
@@ -315,6 +381,15 @@ impl<'input> Tokenizer<'input> {
             });
             Some(pos::spanned2(start, end, doc))
         } else {
+            let skip = if comment.starts_with("// ") { 3 } else { 2 };
+            self.comments.push(pos::spanned2(
+                start.absolute,
+                end.absolute,
+                Comment {
+                    typ: CommentType::Line,
+                    content: comment[skip..].to_string(),
+                },
+            ));
             None
         }
     }
@@ -336,6 +411,14 @@ impl<'input> Tokenizer<'input> {
                         });
                         return Ok(Some(pos::spanned2(start, end.shift('/'), doc)));
                     } else {
+                        self.comments.push(pos::spanned2(
+                            start.absolute,
+                            end.shift('/').absolute,
+                            Comment {
+                                typ: CommentType::Block,
+                                content: comment[2..].trim().to_string(),
+                            },
+                        ));
                         return Ok(None);
                     }
                 }
@@ -345,6 +428,53 @@ impl<'input> Tokenizer<'input> {
         }
     }
 
+    /// Lexes a `#[name(arguments)]` attribute or, if `inner` is set, a `#![name(arguments)]`
+    /// attribute
+    fn attribute(
+        &mut self,
+        start: Location,
+        inner: bool,
+    ) -> Result<SpannedToken<'input>, SpError> {
+        if inner {
+            self.bump(); // Skip '!'
+        }
+        self.bump(); // Skip '['
+
+        let (end, text) = self.take_until(start, |ch| ch == ']' || ch == '\n');
+        match self.lookahead {
+            Some((_, ']')) => {
+                self.bump();
+            }
+            _ => return self.error(end, UnterminatedAttribute),
+        }
+
+        let text = text[if inner { 3 } else { 2 }..].trim();
+        let (name, arguments) = match text.find('(') {
+            Some(i) if text.ends_with(')') => (
+                text[..i].trim(),
+                Some(text[i + 1..text.len() - 1].trim().to_string()),
+            ),
+            Some(_) => return self.error(start, InvalidAttribute),
+            None => (text, None),
+        };
+        let valid_name = name.chars().next().map_or(false, is_ident_start)
+            && name.chars().all(is_ident_continue);
+        if !valid_name {
+            return self.error(start, InvalidAttribute);
+        }
+
+        let attribute = Attribute {
+            name: name.to_string(),
+            arguments,
+        };
+        let token = if inner {
+            Token::InnerAttribute(attribute)
+        } else {
+            Token::Attribute(attribute)
+        };
+        Ok(pos::spanned2(start, end.shift(']'), token))
+    }
+
     fn operator(&mut self, start: Location) -> SpannedToken<'input> {
         let (end, op) = self.take_while(start, is_operator_char);
 
@@ -355,6 +485,7 @@ impl<'input> Tokenizer<'input> {
             ":" => Token::Colon,
             "=" => Token::Equals,
             "|" => Token::Pipe,
+            "?" => Token::Question,
             "->" => Token::RArrow,
             "#" => {
                 // Is this too permissive?
@@ -401,6 +532,58 @@ impl<'input> Tokenizer<'input> {
         self.error(start, UnterminatedStringLiteral)
     }
 
+    /// Lexes a string delimited by `"""` which may contain unescaped `"` characters
+    fn multi_line_string_literal(
+        &mut self,
+        start: Location,
+    ) -> Result<SpannedToken<'input>, SpError> {
+        self.bump(); // Skip the second '"'
+        self.bump(); // Skip the third '"'
+
+        let mut string = String::new();
+        while let Some((next, ch)) = self.bump() {
+            match ch {
+                '\\' => string.push(self.escape_code()?),
+                '"' if self.input[next.absolute.to_usize()..].starts_with(r#"""""#) => {
+                    self.bump();
+                    let end = self.bump().expect("'\"'").0.shift('"');
+                    let token = Token::StringLiteral(string);
+                    return Ok(pos::spanned2(start, end, token));
+                }
+                ch => string.push(ch),
+            }
+        }
+
+        self.error(start, UnterminatedStringLiteral)
+    }
+
+    /// Lexes a string of the form `r"..."` or `r#"..."#` in which escape codes are not processed.
+    /// Any number of `#` can be used so that the string can contain `"#`.
+    fn raw_string_literal(&mut self, start: Location) -> Result<SpannedToken<'input>, SpError> {
+        let mut hashes = 0;
+        while let Some((_, '#')) = self.lookahead {
+            self.bump();
+            hashes += 1;
+        }
+        self.bump(); // Skip '"'
+        let terminator = format!("\"{}", "#".repeat(hashes));
+
+        let mut string = String::new();
+        while let Some((next, ch)) = self.bump() {
+            if ch == '"' && self.input[next.absolute.to_usize()..].starts_with(&terminator[..]) {
+                let mut end = next.shift(ch);
+                for _ in 0..hashes {
+                    end = self.bump().expect("'#'").0.shift('#');
+                }
+                let token = Token::StringLiteral(string);
+                return Ok(pos::spanned2(start, end, token));
+            }
+            string.push(ch);
+        }
+
+        self.error(start, UnterminatedStringLiteral)
+    }
+
     fn shebang_line(&mut self, start: Location) -> Option<SpannedToken<'input>> {
         let (end, line) = self.take_until(start, |ch| ch == '\n');
 
@@ -433,41 +616,95 @@ impl<'input> Tokenizer<'input> {
         }
     }
 
+    /// Takes the digits of `radix` from the input. A `_` may be used to separate digits but only
+    /// counts as part of the literal if it is directly followed by another digit.
+    fn take_digits(&mut self, start: Location, radix: u32) -> (Location, &'input str) {
+        loop {
+            match self.lookahead {
+                Some((_, ch)) if ch.is_digit(radix) => {
+                    self.bump();
+                }
+                Some((next, '_'))
+                    if self.input[next.absolute.to_usize() + 1..]
+                        .starts_with(|ch: char| ch.is_digit(radix)) =>
+                {
+                    self.bump();
+                }
+                Some((end, _)) => return (end, self.slice(start, end)),
+                None => return (self.eof_location, self.slice(start, self.eof_location)),
+            }
+        }
+    }
+
+    /// Lexes the digits of a `0x`, `0o` or `0b` literal whose prefix ends at `prefix_end`
+    fn radix_literal(
+        &mut self,
+        start: Location,
+        prefix_end: Location,
+        radix: u32,
+        is_positive: bool,
+    ) -> Result<(Location, Location, Token<'input>), SpError> {
+        let (end, digits) = self.take_digits(prefix_end, radix);
+        match self.lookahead {
+            Some((_, ch)) if is_ident_start(ch) || ch.is_digit(10) => {
+                return self.error(end, UnexpectedChar(ch))
+            }
+            _ => (),
+        }
+        if digits.is_empty() {
+            let err = if radix == 16 {
+                HexLiteralIncomplete
+            } else {
+                IntLiteralIncomplete
+            };
+            return self.error(start, err);
+        }
+        match i64_from_radix(&digits.replace('_', ""), radix, is_positive) {
+            Ok(val) => Ok((start, end, Token::IntLiteral(val))),
+            Err(err) => self.error(start, err),
+        }
+    }
+
     fn numeric_literal(&mut self, start: Location) -> Result<SpannedToken<'input>, SpError> {
-        let (end, int) = self.take_while(start, is_digit);
+        let (end, int) = self.take_digits(start, 10);
+        let is_radix_prefix = int == "0" || int == "-0";
+        let is_positive = int == "0";
 
         let (start, end, token) = match self.lookahead {
             Some((_, '.')) => {
                 self.bump(); // Skip '.'
-                let (end, float) = self.take_while(start, is_digit);
+                let (end, float) = self.take_digits(start, 10);
                 match self.lookahead {
                     Some((_, ch)) if is_ident_start(ch) => {
                         return self.error(end, UnexpectedChar(ch))
                     }
-                    _ => (start, end, Token::FloatLiteral(float.parse().unwrap())),
+                    _ => (
+                        start,
+                        end,
+                        Token::FloatLiteral(float.replace('_', "").parse().unwrap()),
+                    ),
                 }
             }
             Some((end, 'x')) => {
                 self.bump(); // Skip 'x'
-                let (end, hex) = self.take_while(end.shift('x'), is_hex);
-                match int {
-                    "0" | "-0" => match self.lookahead {
-                        Some((_, ch)) if is_ident_start(ch) => {
-                            return self.error(end, UnexpectedChar(ch))
-                        }
-                        _ => {
-                            if hex.is_empty() {
-                                return self.error(start, HexLiteralIncomplete);
-                            }
-                            let is_positive = int == "0";
-                            match i64_from_hex(hex, is_positive) {
-                                Ok(val) => (start, end, Token::IntLiteral(val)),
-                                Err(err) => return self.error(start, err),
-                            }
-                        }
-                    },
-                    _ => return self.error(start, HexLiteralWrongPrefix),
+                if !is_radix_prefix {
+                    return self.error(start, HexLiteralWrongPrefix);
                 }
+                self.radix_literal(start, end.shift('x'), 16, is_positive)?
+            }
+            Some((end, 'o')) if is_radix_prefix => {
+                self.bump(); // Skip 'o'
+                self.radix_literal(start, end.shift('o'), 8, is_positive)?
+            }
+            // `0b` on its own is the byte `0`
+            Some((end, 'b'))
+                if is_radix_prefix
+                    && self.input[end.absolute.to_usize() + 1..].starts_with(|ch| {
+                        ch == '0' || ch == '1'
+                    }) =>
+            {
+                self.bump(); // Skip 'b'
+                self.radix_literal(start, end.shift('b'), 2, is_positive)?
             }
             Some((end, 'b')) => {
                 self.bump(); // Skip 'b'
@@ -475,7 +712,7 @@ impl<'input> Tokenizer<'input> {
                     Some((pos, ch)) if is_ident_start(ch) => {
                         return self.error(pos, UnexpectedChar(ch))
                     }
-                    _ => if let Ok(val) = int.parse() {
+                    _ => if let Ok(val) = int.replace('_', "").parse() {
                         (start, end.shift('b'), Token::ByteLiteral(val))
                     } else {
                         return self.error(start, NonParseableInt);
@@ -483,7 +720,7 @@ impl<'input> Tokenizer<'input> {
                 }
             }
             Some((start, ch)) if is_ident_start(ch) => return self.error(start, UnexpectedChar(ch)),
-            None | Some(_) => if let Ok(val) = int.parse() {
+            None | Some(_) => if let Ok(val) = int.replace('_', "").parse() {
                 (start, end, Token::IntLiteral(val))
             } else {
                 return self.error(start, NonParseableInt);
@@ -513,6 +750,18 @@ impl<'input> Tokenizer<'input> {
             "in" => Token::In,
             "let" => Token::Let,
             "do" => Token::Do,
+            // `for` is only a keyword when it starts a `for x in xs do ...` expression so that it
+            // can still be used as an ordinary identifier
+            "for" if starts_for_expression(&self.input[end.absolute.to_usize()..]) => Token::For,
+            // Likewise `loop` is only a keyword when it starts a `loop x = init in ...` expression
+            "loop"
+                if starts_loop_expression(
+                    &self.input[..start.absolute.to_usize()],
+                    &self.input[end.absolute.to_usize()..],
+                ) =>
+            {
+                Token::Loop
+            }
             "match" => Token::Match,
             "then" => Token::Then,
             "type" => Token::Type,
@@ -539,7 +788,16 @@ impl<'input> Iterator for Tokenizer<'input> {
                 ']' => Some(Ok(pos::spanned2(start, start.shift(ch), Token::RBracket))),
                 ')' => Some(Ok(pos::spanned2(start, start.shift(ch), Token::RParen))),
 
+                '"' if self.input[start.absolute.to_usize()..].starts_with(r#"""""#) => {
+                    Some(self.multi_line_string_literal(start))
+                }
                 '"' => Some(self.string_literal(start)),
+                'r' if self.input[start.absolute.to_usize() + 1..]
+                    .trim_left_matches('#')
+                    .starts_with('"') =>
+                {
+                    Some(self.raw_string_literal(start))
+                }
                 '\'' => Some(self.char_literal(start)),
 
                 '/' if self.test_lookahead(|ch| ch == '/') => match self.line_comment(start) {
@@ -551,6 +809,9 @@ impl<'input> Iterator for Tokenizer<'input> {
                     Ok(None) => continue,
                     Err(err) => Some(Err(err)),
                 },
+                '#' if self.input[start.absolute.to_usize()..].starts_with("#![") => {
+                    Some(self.attribute(start, true))
+                }
                 '#' if start.absolute == BytePos::from(0) && self.test_lookahead(|ch| {
                     ch == '!'
                 }) =>
@@ -561,6 +822,8 @@ impl<'input> Iterator for Tokenizer<'input> {
                     }
                 }
 
+                '#' if self.test_lookahead(|ch| ch == '[') => Some(self.attribute(start, false)),
+
                 ch if is_ident_start(ch) => Some(Ok(self.identifier(start))),
                 ch if is_digit(ch) || (ch == '-' && self.test_lookahead(is_digit)) => {
                     Some(self.numeric_literal(start))
@@ -580,25 +843,22 @@ impl<'input> Iterator for Tokenizer<'input> {
     }
 }
 
-/// Converts partial hex literal (i.e. part after `0x` or `-0x`) to 64 bit signed integer.
+/// Converts the digits of a literal in `radix` (i.e. part after `0x` or `-0x`) to 64 bit signed
+/// integer.
 ///
 /// This is basically a copy and adaptation of `std::num::from_str_radix`.
-fn i64_from_hex(hex: &str, is_positive: bool) -> Result<i64, Error> {
-    const RADIX: u32 = 16;
-    let digits = hex.as_bytes();
+fn i64_from_radix(digits: &str, radix: u32, is_positive: bool) -> Result<i64, Error> {
     let sign: i64 = if is_positive { 1 } else { -1 };
     let mut result = 0i64;
-    for &c in digits {
-        let x = (c as char).to_digit(RADIX).expect("valid hex literal");
+    for c in digits.chars() {
+        let x = c.to_digit(radix).expect("valid digit");
         result = result
-            .checked_mul(RADIX as i64)
+            .checked_mul(radix as i64)
             .and_then(|result| result.checked_add((x as i64) * sign))
-            .ok_or_else(|| {
-                if is_positive {
-                    HexLiteralOverflow
-                } else {
-                    HexLiteralUnderflow
-                }
+            .ok_or_else(|| match (radix, is_positive) {
+                (16, true) => HexLiteralOverflow,
+                (16, false) => HexLiteralUnderflow,
+                _ => NonParseableInt,
             })?;
     }
     Ok(result)
@@ -606,7 +866,7 @@ fn i64_from_hex(hex: &str, is_positive: bool) -> Result<i64, Error> {
 
 #[cfg(test)]
 mod test {
-    use base::ast::Comment;
+    use base::ast::{Attribute, Comment};
     use base::pos::{self, BytePos, Column, Line, Location, Spanned};
     use base::source;
 
@@ -660,6 +920,38 @@ mod test {
         assert_eq!(None, tokenizer.next());
     }
 
+    #[test]
+    fn keywords_and_reserved_operators() {
+        // Syntax highlighting grammars are generated from these definitions so they must agree
+        // with the lexer
+        for keyword in KEYWORDS {
+            let input = match *keyword {
+                "for" => "for x in xs",
+                "loop" => "loop x = 1",
+                keyword => keyword,
+            };
+            match tokenizer(input).next() {
+                Some(Ok(Spanned {
+                    value: Identifier(_),
+                    ..
+                }))
+                | Some(Err(_))
+                | None => panic!("`{}` is not lexed as a keyword", keyword),
+                Some(Ok(_)) => (),
+            }
+        }
+        for op in RESERVED_OPERATORS {
+            match tokenizer(op).next() {
+                Some(Ok(Spanned {
+                    value: Operator(_), ..
+                }))
+                | Some(Err(_))
+                | None => panic!("`{}` is not lexed as a reserved operator", op),
+                Some(Ok(_)) => (),
+            }
+        }
+    }
+
     #[test]
     fn sample_lambda_expr() {
         test(
@@ -760,6 +1052,45 @@ mod test {
         );
     }
 
+    #[test]
+    fn raw_string_literals() {
+        test(
+            r###"r"a\n" r#"b"c"# rx"###,
+            vec![
+                (r#"~~~~~~            "#, StringLiteral("a\\n".to_string())),
+                (r#"       ~~~~~~~~   "#, StringLiteral("b\"c".to_string())),
+                (r#"                ~~"#, Identifier("rx")),
+            ],
+        );
+    }
+
+    #[test]
+    fn raw_string_literal_unterminated() {
+        assert_eq!(
+            tokenizer(r###"foo r#"bar"baz"###).last(),
+            Some(error(loc(4), UnterminatedStringLiteral))
+        );
+    }
+
+    #[test]
+    fn multi_line_string_literals() {
+        test(
+            r##""""a"b\t""" """""""##,
+            vec![
+                (r#"~~~~~~~~~~~       "#, StringLiteral("a\"b\t".to_string())),
+                (r#"            ~~~~~~"#, StringLiteral("".to_string())),
+            ],
+        );
+    }
+
+    #[test]
+    fn multi_line_string_literal_unterminated() {
+        assert_eq!(
+            tokenizer(r#"foo """bar""baz"#).last(),
+            Some(error(loc(4), UnterminatedStringLiteral))
+        );
+    }
+
     #[test]
     fn string_literal_unexpected_escape_code() {
         assert_eq!(
@@ -933,6 +1264,62 @@ mod test {
         );
     }
 
+    #[test]
+    fn binary_and_octal_literals() {
+        test(
+            r#"0b1010 -0b1 0o755 -0o17 0b"#,
+            vec![
+                (r#"~~~~~~                    "#, IntLiteral(10)),
+                (r#"       ~~~~               "#, IntLiteral(-1)),
+                (r#"            ~~~~~         "#, IntLiteral(493)),
+                (r#"                  ~~~~~   "#, IntLiteral(-15)),
+                (r#"                        ~~"#, ByteLiteral(0)),
+            ],
+        );
+    }
+
+    #[test]
+    fn binary_and_octal_literals_errors() {
+        assert_eq!(
+            tokenizer(r#"0b102"#).last(),
+            Some(error(loc(4), UnexpectedChar('2')))
+        );
+        assert_eq!(
+            tokenizer(r#"0o"#).last(),
+            Some(error(loc(0), IntLiteralIncomplete))
+        );
+        assert_eq!(
+            tokenizer(r#"0o1000000000000000000000"#).last(),
+            Some(error(loc(0), NonParseableInt))
+        );
+    }
+
+    #[test]
+    fn digit_separators() {
+        test(
+            r#"1_000_000 0xff_ff 0b1_0 1_0.2_5 1_0b"#,
+            vec![
+                (r#"~~~~~~~~~                           "#, IntLiteral(1_000_000)),
+                (r#"          ~~~~~~~                   "#, IntLiteral(0xffff)),
+                (r#"                  ~~~~~             "#, IntLiteral(2)),
+                (r#"                        ~~~~~~~     "#, FloatLiteral(10.25)),
+                (r#"                                ~~~~"#, ByteLiteral(10)),
+            ],
+        );
+    }
+
+    #[test]
+    fn digit_separators_unexpected_char() {
+        assert_eq!(
+            tokenizer(r#"1_"#).last(),
+            Some(error(loc(1), UnexpectedChar('_')))
+        );
+        assert_eq!(
+            tokenizer(r#"1__0"#).last(),
+            Some(error(loc(1), UnexpectedChar('_')))
+        );
+    }
+
     #[test]
     fn int_literal_overflow() {
         assert_eq!(
@@ -989,6 +1376,60 @@ mod test {
         );
     }
 
+    #[test]
+    fn attributes() {
+        test(
+            r#"#[implicit] #[doc(hidden)]"#,
+            vec![
+                (
+                    r#"~~~~~~~~~~~               "#,
+                    Token::Attribute(Attribute {
+                        name: "implicit".to_string(),
+                        arguments: None,
+                    }),
+                ),
+                (
+                    r#"            ~~~~~~~~~~~~~~"#,
+                    Token::Attribute(Attribute {
+                        name: "doc".to_string(),
+                        arguments: Some("hidden".to_string()),
+                    }),
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn inner_attributes() {
+        test(
+            r#"#![no_implicit_prelude] #[implicit]"#,
+            vec![
+                (
+                    r#"~~~~~~~~~~~~~~~~~~~~~~~            "#,
+                    Token::InnerAttribute(Attribute {
+                        name: "no_implicit_prelude".to_string(),
+                        arguments: None,
+                    }),
+                ),
+                (
+                    r#"                        ~~~~~~~~~~~"#,
+                    Token::Attribute(Attribute {
+                        name: "implicit".to_string(),
+                        arguments: None,
+                    }),
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn unterminated_attribute() {
+        assert_eq!(
+            tokenizer(r#"#[implicit"#).last(),
+            Some(error(loc(10), UnterminatedAttribute))
+        );
+    }
+
     #[test]
     fn line_doc_comments() {
         test(
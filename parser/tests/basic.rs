@@ -11,7 +11,8 @@ mod support;
 
 use base::ast::*;
 use base::pos::{self, BytePos, Span, Spanned};
-use base::types::{Field, Type};
+use base::kind::Kind;
+use base::types::{Field, Generic, Type};
 use support::*;
 
 #[test]
@@ -125,12 +126,16 @@ fn type_mutually_recursive() {
     let binds = vec![
         TypeBinding {
             comment: None,
+            comments: Vec::new(),
+            attributes: Vec::new(),
             name: no_loc(intern("Test")),
             alias: alias(intern("Test"), Vec::new(), test),
             finalized_alias: None,
         },
         TypeBinding {
             comment: None,
+            comments: Vec::new(),
+            attributes: Vec::new(),
             name: no_loc(intern("Test2")),
             alias: alias(intern("Test2"), Vec::new(), test2),
             finalized_alias: None,
@@ -273,6 +278,32 @@ fn record_trailing_comma() {
     );
 }
 
+#[test]
+fn record_strict_fields() {
+    let _ = ::env_logger::init();
+    let e = parse_clear_span!("{ !y, x = z, !w = 1 }");
+    let field = |name: &str, value, strict| ExprField {
+        comment: None,
+        comments: Vec::new(),
+        name: no_loc(name.into()),
+        value: value,
+        strict: strict,
+    };
+    assert_eq!(
+        e,
+        no_loc(Expr::Record {
+            typ: Type::hole(),
+            types: &mut [],
+            exprs: arena().alloc_extend(vec![
+                field("y", None, true),
+                field("x", Some(id("z")), false),
+                field("w", Some(int(1)), true),
+            ]),
+            base: None,
+        })
+    );
+}
+
 #[test]
 fn array_trailing_comma() {
     let _ = ::env_logger::init();
@@ -308,9 +339,11 @@ fn let_pattern() {
     assert_eq!(
         e,
         no_loc(Expr::LetBindings(
-            vec![
+            arena().alloc_extend(vec![
                 ValueBinding {
                     comment: None,
+                    comments: Vec::new(),
+                    attributes: Vec::new(),
                     name: no_loc(Pattern::Record {
                         typ: Type::hole(),
                         types: Vec::new(),
@@ -327,11 +360,12 @@ fn let_pattern() {
                     }),
                     typ: None,
                     resolved_type: Type::hole(),
+                    implicit_args: Vec::new(),
                     args: vec![],
                     expr: id("test"),
                 },
-            ],
-            Box::new(id("x")),
+            ]),
+            arena().alloc(id("x")),
         ),)
     );
 }
@@ -394,6 +428,47 @@ fn span_string_literal() {
     assert_eq!(e.span, Span::new(BytePos::from(1), BytePos::from(7)));
 }
 
+#[test]
+fn span_raw_and_multi_line_string_literals() {
+    let _ = ::env_logger::init();
+
+    let text = r##"
+let x = r#"a\n"b"#
+let y = """
+c "d"
+"""
+y
+"##;
+    let e = parse_new!(text);
+    let (bind, body) = match e.value {
+        Expr::LetBindings(ref binds, ref body) => (&binds[0], body),
+        _ => panic!("Expected a let binding, found {:?}", e),
+    };
+    assert_eq!(bind.expr.value, Expr::Literal(Literal::String(r#"a\n"b"#.to_string())));
+    let start = text.find("r#").unwrap();
+    assert_eq!(
+        bind.expr.span,
+        Span::new(BytePos::from(start), BytePos::from(start + 10))
+    );
+
+    let (bind, body) = match body.value {
+        Expr::LetBindings(ref binds, ref body) => (&binds[0], body),
+        _ => panic!("Expected a let binding, found {:?}", body),
+    };
+    assert_eq!(bind.expr.value, Expr::Literal(Literal::String("\nc \"d\"\n".to_string())));
+    let start = text.find(r#"""""#).unwrap();
+    assert_eq!(
+        bind.expr.span,
+        Span::new(BytePos::from(start), BytePos::from(start + 13))
+    );
+
+    let start = text.rfind('y').unwrap();
+    assert_eq!(
+        body.span,
+        Span::new(BytePos::from(start), BytePos::from(start + 1))
+    );
+}
+
 #[test]
 fn span_app() {
     let _ = ::env_logger::init();
@@ -445,13 +520,45 @@ fn span_field_access() {
     let expr = parse_new!("record.x");
     assert_eq!(expr.span, Span::new(BytePos::from(0), BytePos::from(8)));
     match expr.value {
-        Expr::Projection(ref e, _, _) => {
+        Expr::Projection(ref e, _, _, _) => {
             assert_eq!(e.span, Span::new(BytePos::from(0), BytePos::from(6)));
         }
         _ => panic!(),
     }
 }
 
+#[test]
+fn type_params_with_kinds() {
+    let _ = ::env_logger::init();
+    let e = parse_clear_span!("type Functor (f : Type -> Type) a = f a in 1");
+    match e.value {
+        Expr::TypeBindings(ref bindings, _) => assert_eq!(
+            bindings[0].alias.value.params(),
+            &[
+                Generic::new(intern("f"), Kind::function(Kind::typ(), Kind::typ())),
+                Generic::new(intern("a"), Kind::hole()),
+            ][..]
+        ),
+        _ => panic!("Expected type bindings, got {:?}", e),
+    }
+}
+
+#[test]
+fn type_params_with_kind_variables() {
+    let _ = ::env_logger::init();
+    let e = parse_clear_span!("type Proxy k (a : k) = | Proxy in 1");
+    match e.value {
+        Expr::TypeBindings(ref bindings, _) => assert_eq!(
+            bindings[0].alias.value.params(),
+            &[
+                Generic::new(intern("k"), Kind::hole()),
+                Generic::new(intern("a"), Kind::generic("k")),
+            ][..]
+        ),
+        _ => panic!("Expected type bindings, got {:?}", e),
+    }
+}
+
 #[test]
 fn comment_on_let() {
     let _ = ::env_logger::init();
@@ -464,24 +571,74 @@ id
     assert_eq!(
         e,
         no_loc(Expr::LetBindings(
-            vec![
+            arena().alloc_extend(vec![
                 ValueBinding {
                     comment: Some(Comment {
                         typ: CommentType::Line,
                         content: "The identity function".into(),
                     }),
+                    comments: Vec::new(),
+                    attributes: Vec::new(),
                     name: no_loc(Pattern::Ident(TypedIdent::new(intern("id")))),
                     typ: None,
                     resolved_type: Type::hole(),
+                    implicit_args: Vec::new(),
                     args: vec![no_loc(TypedIdent::new(intern("x")))],
                     expr: id("x"),
                 },
-            ],
-            Box::new(id("id")),
+            ]),
+            arena().alloc(id("id")),
         ),)
     );
 }
 
+#[test]
+fn attribute_on_let() {
+    let _ = ::env_logger::init();
+    let text = r#"
+/// The identity function
+#[implicit]
+let id x = x
+id
+"#;
+    let e = parse_clear_span!(text);
+    match e.value {
+        Expr::LetBindings(ref bindings, _) => {
+            assert_eq!(
+                bindings[0].attributes,
+                vec![
+                    Attribute {
+                        name: "implicit".into(),
+                        arguments: None,
+                    },
+                ]
+            );
+            assert!(bindings[0].is_implicit());
+            assert!(bindings[0].comment.is_some());
+        }
+        _ => panic!("Expected let bindings, got {:?}", e),
+    }
+}
+
+#[test]
+fn implicit_argument() {
+    let _ = ::env_logger::init();
+    let text = r#"
+let show ?s x : [Show a] -> a -> String = s.show x
+show
+"#;
+    let e = parse_clear_span!(text);
+    match e.value {
+        Expr::LetBindings(ref bindings, _) => {
+            assert_eq!(bindings[0].implicit_args, vec![no_loc(TypedIdent::new(intern("s")))]);
+            assert_eq!(bindings[0].args, vec![no_loc(TypedIdent::new(intern("x")))]);
+            let typ = bindings[0].typ.as_ref().expect("type");
+            assert!(typ.as_implicit_function().is_some(), "{:?}", typ);
+        }
+        _ => panic!("Expected let bindings, got {:?}", e),
+    }
+}
+
 #[test]
 fn comment_on_and() {
     let _ = ::env_logger::init();
@@ -495,12 +652,15 @@ id
     assert_eq!(
         e,
         no_loc(Expr::LetBindings(
-            vec![
+            arena().alloc_extend(vec![
                 ValueBinding {
                     comment: None,
+                    comments: Vec::new(),
+                    attributes: Vec::new(),
                     name: no_loc(Pattern::Ident(TypedIdent::new(intern("id")))),
                     typ: None,
                     resolved_type: Type::hole(),
+                    implicit_args: Vec::new(),
                     args: vec![no_loc(TypedIdent::new(intern("x")))],
                     expr: id("x"),
                 },
@@ -509,14 +669,17 @@ id
                         typ: CommentType::Line,
                         content: "The identity function".into(),
                     }),
+                    comments: Vec::new(),
+                    attributes: Vec::new(),
                     name: no_loc(Pattern::Ident(TypedIdent::new(intern("id2")))),
                     typ: None,
                     resolved_type: Type::hole(),
+                    implicit_args: Vec::new(),
                     args: vec![no_loc(TypedIdent::new(intern("y")))],
                     expr: id("y"),
                 },
-            ],
-            Box::new(id("id")),
+            ]),
+            arena().alloc(id("id")),
         ),)
     );
 }
@@ -539,6 +702,8 @@ id
                         typ: CommentType::Block,
                         content: "Test type".into(),
                     }),
+                    comments: Vec::new(),
+                    attributes: Vec::new(),
                     name: no_loc(intern("Test")),
                     alias: alias(intern("Test"), Vec::new(), typ("Int")),
                     finalized_alias: None,
@@ -573,6 +738,8 @@ id
                             typ: CommentType::Block,
                             content: "Test type".into(),
                         }),
+                        comments: Vec::new(),
+                        attributes: Vec::new(),
                         name: no_loc(intern("Test")),
                         alias: alias(intern("Test"), Vec::new(), typ("Int")),
                         finalized_alias: None,
@@ -604,6 +771,8 @@ id
                         typ: CommentType::Line,
                         content: "Merge\nconsecutive\nline comments.".into(),
                     }),
+                    comments: Vec::new(),
+                    attributes: Vec::new(),
                     name: no_loc(intern("Test")),
                     alias: alias(intern("Test"), Vec::new(), typ("Int")),
                     finalized_alias: None,
@@ -624,7 +793,12 @@ fn partial_field_access_simple() {
         clear_span(e.unwrap_err().0.unwrap()),
         Spanned {
             span: Span::new(BytePos::from(0), BytePos::from(0)),
-            value: Expr::Projection(Box::new(id("test")), intern(""), Type::hole()),
+            value: Expr::Projection(
+                arena().alloc(id("test")),
+                intern(""),
+                Type::hole(),
+                Vec::new(),
+            ),
         }
     );
 }
@@ -642,13 +816,18 @@ test
         clear_span(e.unwrap_err().0.unwrap()),
         Spanned {
             span: Span::default(),
-            value: Expr::Block(vec![
+            value: Expr::Block(arena().alloc_extend(vec![
                 Spanned {
                     span: Span::new(BytePos::from(0), BytePos::from(0)),
-                    value: Expr::Projection(Box::new(id("test")), intern(""), Type::hole()),
+                    value: Expr::Projection(
+                        arena().alloc(id("test")),
+                        intern(""),
+                        Type::hole(),
+                        Vec::new(),
+                    ),
                 },
                 id("test"),
-            ]),
+            ])),
         }
     );
 }
@@ -664,17 +843,20 @@ x
     assert_eq!(
         e,
         no_loc(Expr::LetBindings(
-            vec![
+            arena().alloc_extend(vec![
                 ValueBinding {
                     comment: None,
+                    comments: Vec::new(),
+                    attributes: Vec::new(),
                     name: no_loc(Pattern::Ident(TypedIdent::new(intern("x")))),
                     typ: Some(Type::app(typ("->"), collect![typ("Int"), typ("Int")])),
                     resolved_type: Type::hole(),
+                    implicit_args: Vec::new(),
                     args: vec![],
                     expr: id("x"),
                 },
-            ],
-            Box::new(id("x")),
+            ]),
+            arena().alloc(id("x")),
         ),)
     );
 }
@@ -746,6 +928,76 @@ fn parse_macro() {
     assert_eq!(e, id("import!"));
 }
 
+#[test]
+fn comments_are_attached_to_the_nearest_binding() {
+    let _ = ::env_logger::init();
+    let text = r#"
+// leading
+let x = 1 // trailing
+let f y =
+    /* inside */
+    y
+{ x, /* field */ f }
+"#;
+    let e = parse_new!(text);
+
+    fn contents(comments: &[SpannedComment]) -> Vec<&str> {
+        comments
+            .iter()
+            .map(|comment| comment.value.content.as_str())
+            .collect()
+    }
+    let (x, rest) = match e.value {
+        Expr::LetBindings(ref binds, ref rest) => (&binds[0], rest),
+        _ => panic!("Expected let bindings, found {:?}", e),
+    };
+    assert_eq!(contents(&x.comments), ["leading", "trailing"]);
+    assert_eq!(x.comments[0].span, Span::new(BytePos::from(1), BytePos::from(11)));
+
+    let (f, rest) = match rest.value {
+        Expr::LetBindings(ref binds, ref rest) => (&binds[0], rest),
+        _ => panic!("Expected let bindings, found {:?}", rest),
+    };
+    assert_eq!(contents(&f.comments), ["inside"]);
+    assert_eq!(f.comments[0].value.typ, CommentType::Block);
+
+    match rest.value {
+        Expr::Record { ref exprs, .. } => {
+            assert_eq!(contents(&exprs[0].comments), Vec::<&str>::new());
+            assert_eq!(contents(&exprs[1].comments), ["field"]);
+        }
+        _ => panic!("Expected a record, found {:?}", rest),
+    }
+}
+
+#[test]
+fn comments_are_attached_to_the_nearest_alternative() {
+    let _ = ::env_logger::init();
+    let text = r#"
+match x with
+// first
+| Some y -> y // trailing
+| None ->
+    /* inside */
+    0
+"#;
+    let e = parse_new!(text);
+
+    fn contents(comments: &[SpannedComment]) -> Vec<&str> {
+        comments
+            .iter()
+            .map(|comment| comment.value.content.as_str())
+            .collect()
+    }
+    match e.value {
+        Expr::Match(_, ref alts) => {
+            assert_eq!(contents(&alts[0].comments), ["first", "trailing"]);
+            assert_eq!(contents(&alts[1].comments), ["inside"]);
+        }
+        _ => panic!("Expected a match, found {:?}", e),
+    }
+}
+
 #[test]
 fn doc_comment_on_record_field() {
     let _ = ::env_logger::init();
@@ -757,26 +1009,30 @@ fn doc_comment_on_record_field() {
         e,
         no_loc(Expr::Record {
             typ: Type::hole(),
-            types: vec![
+            types: arena().alloc_extend(vec![
                 ExprField {
                     comment: Some(Comment {
                         typ: CommentType::Block,
                         content: "test".into(),
                     }),
+                    comments: Vec::new(),
                     name: no_loc("Test".into()),
                     value: None,
+                    strict: false,
                 },
-            ],
-            exprs: vec![
+            ]),
+            exprs: arena().alloc_extend(vec![
                 ExprField {
                     comment: Some(Comment {
                         typ: CommentType::Line,
                         content: "x binding".into(),
                     }),
+                    comments: Vec::new(),
                     name: no_loc("x".into()),
                     value: Some(int(1)),
+                    strict: false,
                 },
-            ],
+            ]),
             base: None,
         })
     )
@@ -794,6 +1050,34 @@ fn shebang_at_top_is_ignored() {
     )
 }
 
+#[test]
+fn module_attributes_after_shebang() {
+    let _ = ::env_logger::init();
+    let text = r"#!/usr/bin/env gluon
+#![no_implicit_prelude]
+#![doc(hidden)]
+{ Test, x }";
+    let e = parse_clear_span!(text);
+    assert_eq!(
+        e,
+        record_a(vec![("Test".into(), None)], vec![("x".into(), None)])
+    );
+    assert_eq!(
+        parser::module_attributes(text),
+        vec![
+            Attribute {
+                name: "no_implicit_prelude".into(),
+                arguments: None,
+            },
+            Attribute {
+                name: "doc".into(),
+                arguments: Some("hidden".into()),
+            },
+        ]
+    );
+    assert_eq!(parser::module_attributes("#[implicit]\nlet x = 1\nx"), vec![]);
+}
+
 #[test]
 fn do_in_parens() {
     let _ = ::env_logger::init();
@@ -806,6 +1090,141 @@ fn do_in_parens() {
     parse_clear_span!(text);
 }
 
+#[test]
+fn for_expression() {
+    let _ = ::env_logger::init();
+    let e = parse_clear_span!("for x in xs do f x");
+    assert_eq!(e, parse_clear_span!("do x = xs in f x"));
+}
+
+#[test]
+fn for_expression_block() {
+    let _ = ::env_logger::init();
+    let text = r"
+let y = 1
+for x in xs do
+    let z = f x
+    g z y
+";
+    let expected = r"
+let y = 1
+do x = xs in
+    let z = f x
+    g z y
+";
+    assert_eq!(parse_clear_span!(text), parse_clear_span!(expected));
+}
+
+#[test]
+fn for_identifier_is_not_a_keyword() {
+    let _ = ::env_logger::init();
+    let e = parse_clear_span!("for app xs f");
+    assert_eq!(e, app(id("for"), vec![id("app"), id("xs"), id("f")]));
+}
+
+#[test]
+fn loop_expression() {
+    let _ = ::env_logger::init();
+    let text = r"
+loop i = 0
+if i < 10 then continue (i + 1) else break i
+";
+    let e = parse_clear_span!(text);
+    assert_eq!(
+        e,
+        let_a(
+            "loop#",
+            &["i"],
+            if_else(
+                binop(id("i"), "<", int(10)),
+                app(
+                    id("loop#"),
+                    vec![
+                        no_loc(Expr::Tuple {
+                            typ: Type::hole(),
+                            elems: arena().alloc_extend(vec![
+                                binop(id("i"), "+", int(1)),
+                            ]),
+                        }),
+                    ],
+                ),
+                id("i"),
+            ),
+            app(id("loop#"), vec![int(0)]),
+        )
+    );
+}
+
+#[test]
+fn loop_identifier_is_not_a_keyword() {
+    let _ = ::env_logger::init();
+    let e = parse_clear_span!("let loop x = x in loop 1");
+    assert_eq!(e, let_a("loop", &["x"], id("x"), app(id("loop"), vec![int(1)])));
+}
+
+#[test]
+fn labeled_block() {
+    let _ = ::env_logger::init();
+    let text = r#"
+check: {
+    if x < 0 then exit check "negative" else ()
+    let y = x + 1
+    if y > 10 then exit check "large" else ()
+    "ok"
+}
+"#;
+    let expected = r#"
+if x < 0 then "negative"
+else
+    let y = x + 1
+    if y > 10 then "large" else "ok"
+"#;
+    assert_eq!(parse_clear_span!(text), parse_clear_span!(expected));
+}
+
+#[test]
+fn labeled_block_nested_exit() {
+    let _ = ::env_logger::init();
+    let text = r#"
+outer: {
+    inner: {
+        if x then exit outer 1 else ()
+        exit inner 2
+    }
+}
+"#;
+    let expected = r#"
+if x then 1 else 2
+"#;
+    assert_eq!(parse_clear_span!(text), parse_clear_span!(expected));
+}
+
+#[test]
+fn chained_comparison() {
+    let _ = ::env_logger::init();
+    assert_eq!(
+        parse_clear_span!("0 <= x < 10"),
+        parse_clear_span!("0 <= x && x < 10")
+    );
+}
+
+#[test]
+fn parenthesized_comparison_is_not_chained() {
+    let _ = ::env_logger::init();
+    let e = parse_clear_span!("(a < b) < c");
+    assert_eq!(
+        e,
+        binop(
+            no_loc(Expr::Tuple {
+                typ: Type::hole(),
+                elems: arena().alloc_extend(vec![binop(id("a"), "<", id("b"))]),
+            }),
+            "<",
+            id("c"),
+        )
+    );
+}
+
 #[test]
 fn parse_let_or_expr() {
     let _ = ::env_logger::init();
@@ -813,11 +1232,13 @@ fn parse_let_or_expr() {
     let mut module = MockEnv::new();
 
     let line = "let x = test";
-    match parser::parse_partial_let_or_expr(&mut module, line) {
+    match parser::parse_partial_let_or_expr(arena(), &mut module, line) {
         Ok(x) => assert_eq!(
             x,
             Err(ValueBinding {
                 comment: None,
+                comments: Vec::new(),
+                attributes: Vec::new(),
                 name: pos::spanned2(
                     4.into(),
                     5.into(),
@@ -825,6 +1246,7 @@ fn parse_let_or_expr() {
                 ),
                 typ: None,
                 resolved_type: Type::hole(),
+                implicit_args: Vec::new(),
                 args: Vec::new(),
                 expr: pos::spanned2(
                     8.into(),
@@ -836,3 +1258,260 @@ fn parse_let_or_expr() {
         Err((_, err)) => panic!("{}", err),
     }
 }
+
+#[test]
+fn lambda_pattern_argument() {
+    let _ = ::env_logger::init();
+    let e = parse_clear_span!(r"\x { y } -> y");
+    let pattern = Pattern::Record {
+        typ: Type::hole(),
+        types: Vec::new(),
+        fields: vec![
+            PatternField {
+                name: no_loc(intern("y")),
+                value: None,
+            },
+        ],
+    };
+    assert_eq!(
+        e,
+        lambda(
+            "",
+            vec![intern("x"), intern("arg:1")],
+            case(id("arg:1"), vec![(pattern, id("y"))])
+        )
+    );
+}
+
+#[test]
+fn let_pattern_arguments() {
+    let _ = ::env_logger::init();
+    let e = parse_clear_span!("let f (Some x) (a, b) = x in f");
+    let some = Pattern::Constructor(
+        TypedIdent::new(intern("Some")),
+        vec![no_loc(Pattern::Ident(TypedIdent::new(intern("x"))))],
+    );
+    let tuple = Pattern::Tuple {
+        typ: Type::hole(),
+        elems: vec![
+            no_loc(Pattern::Ident(TypedIdent::new(intern("a")))),
+            no_loc(Pattern::Ident(TypedIdent::new(intern("b")))),
+        ],
+    };
+    assert_eq!(
+        e,
+        let_a(
+            "f",
+            &["arg:1", "arg:2"],
+            case(
+                id("arg:1"),
+                vec![(some, case(id("arg:2"), vec![(tuple, id("x"))]))],
+            ),
+            id("f")
+        )
+    );
+}
+
+#[test]
+fn pattern_argument_spans() {
+    let _ = ::env_logger::init();
+    let e = parse_new!(r"\{ y } -> y");
+    match e.value {
+        Expr::Lambda(ref lambda) => {
+            assert_eq!(lambda.args[0].span, Span::new(1.into(), 6.into()));
+            assert_eq!(lambda.body.span, Span::new(10.into(), 11.into()));
+            match lambda.body.value {
+                Expr::Match(ref scrutinee, ref alts) => {
+                    assert_eq!(scrutinee.span, lambda.args[0].span);
+                    assert_eq!(alts[0].pattern.span, lambda.args[0].span);
+                }
+                _ => panic!("Expected match, got {:?}", lambda.body),
+            }
+        }
+        _ => panic!("Expected lambda, got {:?}", e),
+    }
+}
+
+#[test]
+fn function_clauses() {
+    let _ = ::env_logger::init();
+    let text = r#"
+let f 0 = 1
+let f n = n
+f
+"#;
+    let ident = |s: &str| Pattern::Ident(TypedIdent::new(intern(s)));
+    // `arg:1` is used by the pattern argument `0` before the equations are merged
+    assert_eq!(
+        parse_clear_span!(text),
+        let_a(
+            "f",
+            &["arg:2"],
+            case(
+                id("arg:2"),
+                vec![
+                    (Pattern::Literal(Literal::Int(0)), int(1)),
+                    (ident("n"), id("n")),
+                ],
+            ),
+            id("f")
+        )
+    );
+}
+
+#[test]
+fn function_clauses_multiple_arguments() {
+    let _ = ::env_logger::init();
+    let text = r#"
+let f 0 y = y
+let f x (Some y) = x
+let f x y = x
+f
+"#;
+    let ident = |s: &str| no_loc(Pattern::Ident(TypedIdent::new(intern(s))));
+    let tuple = |elems| Pattern::Tuple {
+        typ: Type::hole(),
+        elems,
+    };
+    let some_y = no_loc(Pattern::Constructor(
+        TypedIdent::new(intern("Some")),
+        vec![ident("y")],
+    ));
+    // `arg:1` to `arg:4` are used by the pattern arguments and by merging the last two equations
+    assert_eq!(
+        parse_clear_span!(text),
+        let_a(
+            "f",
+            &["arg:5", "arg:6"],
+            case(
+                no_loc(Expr::Tuple {
+                    typ: Type::hole(),
+                    elems: arena().alloc_extend(vec![id("arg:5"), id("arg:6")]),
+                }),
+                vec![
+                    (
+                        tuple(vec![no_loc(Pattern::Literal(Literal::Int(0))), ident("y")]),
+                        id("y"),
+                    ),
+                    (tuple(vec![ident("x"), some_y]), id("x")),
+                    (tuple(vec![ident("x"), ident("y")]), id("x")),
+                ],
+            ),
+            id("f")
+        )
+    );
+}
+
+#[test]
+fn function_with_different_arity_is_not_a_clause() {
+    let _ = ::env_logger::init();
+    let text = r#"
+let f 0 = 0
+let f x y = y
+f
+"#;
+    assert_eq!(
+        parse_clear_span!(text),
+        let_a(
+            "f",
+            &["arg:1"],
+            case(
+                id("arg:1"),
+                vec![(Pattern::Literal(Literal::Int(0)), int(0))]
+            ),
+            let_a("f", &["x", "y"], id("y"), id("f"))
+        )
+    );
+}
+
+#[test]
+fn overloaded_function_is_not_a_clause() {
+    let _ = ::env_logger::init();
+    // `f x` always matches so the second `f` is an overload and not another equation
+    let text = r#"
+let f x = x
+let f y = y
+f
+"#;
+    assert_eq!(
+        parse_clear_span!(text),
+        let_a("f", &["x"], id("x"), let_a("f", &["y"], id("y"), id("f")))
+    );
+}
+
+#[test]
+fn operator_sections() {
+    let _ = ::env_logger::init();
+    let section = |body| lambda("", vec![intern("section:1")], body);
+    assert_eq!(
+        parse_clear_span!("(+ 1)"),
+        section(binop(id("section:1"), "+", int(1)))
+    );
+    assert_eq!(
+        parse_clear_span!("(1 -)"),
+        section(binop(int(1), "-", id("section:1")))
+    );
+    assert_eq!(
+        parse_clear_span!("(.x)"),
+        section(field_access(id("section:1"), "x"))
+    );
+    assert_eq!(parse_clear_span!("(+)"), id("+"));
+}
+
+#[test]
+fn operator_section_operand_is_not_reassociated() {
+    let _ = ::env_logger::init();
+    assert_eq!(
+        parse_clear_span!("(* 1 + 2)"),
+        lambda(
+            "",
+            vec![intern("section:1")],
+            binop(
+                id("section:1"),
+                "*",
+                no_loc(Expr::Tuple {
+                    typ: Type::hole(),
+                    elems: arena().alloc_extend(vec![binop(int(1), "+", int(2))]),
+                })
+            )
+        )
+    );
+}
+
+#[test]
+fn infix_attribute_declares_fixity() {
+    let _ = ::env_logger::init();
+    let text = r#"
+let x =
+    #[infix(right, 8)]
+    let (<+>) l r = l
+    1 <+> 2 <+> 3 * 4
+1 <+> 2 <+> 3 * 4
+"#;
+    let e = parse_clear_span!(text);
+    match e.value {
+        Expr::LetBindings(ref bindings, ref body) => {
+            match bindings[0].expr.value {
+                Expr::LetBindings(_, ref body) => assert_eq!(
+                    **body,
+                    binop(
+                        binop(int(1), "<+>", binop(int(2), "<+>", int(3))),
+                        "*",
+                        int(4)
+                    )
+                ),
+                _ => panic!("Expected let bindings, got {:?}", bindings[0].expr),
+            }
+            // The declaration only applies inside the `let` which defines the operator
+            assert_eq!(
+                **body,
+                binop(
+                    binop(binop(int(1), "<+>", int(2)), "<+>", int(3)),
+                    "*",
+                    int(4)
+                )
+            );
+        }
+        _ => panic!("Expected let bindings, got {:?}", e),
+    }
+}
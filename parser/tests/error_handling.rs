@@ -7,11 +7,11 @@ extern crate gluon_parser as parser;
 
 mod support;
 
-use base::ast::{Expr, Pattern, PatternField, TypedIdent};
+use base::ast::{Comment, CommentType, Expr, Pattern, PatternField, SpannedComment, TypedIdent};
 use base::pos::{self, BytePos, Span, Spanned};
 use base::types::Type;
 
-use parser::{Error, ParseErrors, TokenizeError};
+use parser::{Error, InfixError, ParseErrors, TokenizeError};
 
 use support::*;
 
@@ -29,6 +29,22 @@ fn remove_expected(errors: ParseErrors) -> ParseErrors {
     ParseErrors::from(errors.into_iter().map(f).collect::<Vec<_>>())
 }
 
+// Attaches the `//` comment which follows the `|` of the first alternative to it
+fn with_alternative_comment(mut expr: SpExpr) -> SpExpr {
+    if let Expr::Match(_, ref mut alts) = expr.value {
+        let comment: SpannedComment = pos::spanned2(
+            BytePos::from(24),
+            BytePos::from(26),
+            Comment {
+                typ: CommentType::Line,
+                content: String::new(),
+            },
+        );
+        alts[0].comments.push(comment);
+    }
+    expr
+}
+
 #[test]
 fn empty_input() {
     let _ = ::env_logger::init();
@@ -192,7 +208,7 @@ fn incomplete_alternative() {
     let (expr, err) = result.unwrap_err();
     assert_eq!(
         clear_span(expr.unwrap()),
-        case(int(1), vec![(Pattern::Error, error())])
+        with_alternative_comment(case(int(1), vec![(Pattern::Error, error())]))
     );
 
     let error = Error::UnexpectedToken("CloseBlock".into(), vec![]);
@@ -217,13 +233,13 @@ fn incomplete_alternative_before_complete_alternative() {
     let (expr, err) = result.unwrap_err();
     assert_eq!(
         clear_span(expr.unwrap()),
-        case(
+        with_alternative_comment(case(
             int(1),
             vec![
                 (Pattern::Error, error()),
                 (Pattern::Ident(TypedIdent::new(intern("x"))), id("x")),
             ],
-        )
+        ))
     );
 
     let error = Error::UnexpectedToken("Pipe".into(), vec![]);
@@ -316,6 +332,76 @@ fn incomplete_let_binding_2() {
     assert_eq!(remove_expected(err), ParseErrors::from(errors));
 }
 
+#[test]
+fn missing_let_binding_name() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+    let = 1
+    let y = 2
+    y
+    "#;
+    let result = parse(expr);
+    assert!(result.is_err());
+    let (expr, err) = result.unwrap_err();
+
+    let mut expected = let_("", int(1), let_("y", int(2), id("y")));
+    if let Expr::LetBindings(ref mut binds, _) = expected.value {
+        binds[0].name = no_loc(Pattern::Error);
+    }
+    assert_eq!(clear_span(expr.unwrap()), expected);
+
+    let errors = vec![no_loc(Error::UnexpectedToken("Equal".into(), vec![]))];
+    assert_eq!(remove_expected(err), ParseErrors::from(errors));
+}
+
+#[test]
+fn invalid_let_binding_type() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+    let x : = 1
+    let y = x
+    y
+    "#;
+    let result = parse(expr);
+    assert!(result.is_err());
+    let (expr, err) = result.unwrap_err();
+    assert_eq!(
+        clear_span(expr.unwrap()),
+        let_("x", int(1), let_("y", id("x"), id("y")))
+    );
+
+    let errors = vec![no_loc(Error::UnexpectedToken("Equal".into(), vec![]))];
+    assert_eq!(remove_expected(err), ParseErrors::from(errors));
+}
+
+#[test]
+fn incomplete_type_binding() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+    type Test =
+    let y = 2
+    y
+    "#;
+    let result = parse(expr);
+    assert!(result.is_err());
+    let (expr, err) = result.unwrap_err();
+    assert_eq!(
+        clear_span(expr.unwrap()),
+        type_decl(
+            intern("Test"),
+            vec![],
+            no_loc(Type::Hole).into(),
+            let_("y", int(2), id("y"))
+        )
+    );
+
+    let errors = vec![no_loc(Error::UnexpectedToken("In".into(), vec![]))];
+    assert_eq!(remove_expected(err), ParseErrors::from(errors));
+}
+
 #[test]
 fn unterminated_char_literal() {
     let _ = ::env_logger::init();
@@ -331,3 +417,64 @@ fn unterminated_char_literal() {
     let span = pos::span(BytePos::from(5), BytePos::from(5));
     assert_eq!(err, ParseErrors::from(vec![pos::spanned(span, error)]));
 }
+
+#[test]
+fn loop_control_outside_tail_position() {
+    let _ = ::env_logger::init();
+
+    let result = parse(
+        r#"
+loop i = 0
+continue i + 1
+"#,
+    );
+
+    let error = Error::LoopControl("continue".into());
+    let errors = ParseErrors::from(vec![no_loc(error)]);
+
+    assert_eq!(remove_expected(result.unwrap_err().1), errors);
+}
+
+#[test]
+fn exit_outside_of_labeled_block_tail_position() {
+    let _ = ::env_logger::init();
+
+    let result = parse(
+        r#"
+check: {
+    f (exit check 1)
+}
+"#,
+    );
+
+    let error = Error::Exit("check".into());
+    let errors = ParseErrors::from(vec![no_loc(error)]);
+
+    assert_eq!(remove_expected(result.unwrap_err().1), errors);
+}
+
+#[test]
+fn invalid_infix_attribute() {
+    let _ = ::env_logger::init();
+
+    let result = parse(
+        r#"
+#[infix(up, 6)]
+let (+++) l r = l
+#[infix(left, 6)]
+let f l r = l
+1 +++ 2
+"#,
+    );
+
+    let errors = ParseErrors::from(vec![
+        no_loc(Error::Infix(InfixError::InvalidInfixAttribute(Some(
+            "up, 6".into(),
+        )))),
+        no_loc(Error::Infix(InfixError::InfixAttributeOnNonOperator(
+            "f".into(),
+        ))),
+    ]);
+
+    assert_eq!(remove_expected(result.unwrap_err().1), errors);
+}
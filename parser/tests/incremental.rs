@@ -0,0 +1,253 @@
+extern crate env_logger;
+
+extern crate gluon_base as base;
+extern crate gluon_parser as parser;
+
+mod support;
+
+use base::ast::*;
+use base::pos::{BytePos, Span};
+use base::types::{Type, TypeCache};
+use parser::{parse_string, reparse_partial_expr, TextEdit};
+use support::{arena, MockEnv};
+
+fn parse(text: &str) -> SpannedExpr<'static, String> {
+    parse_string(arena(), &mut MockEnv::new(), text).unwrap_or_else(|(_, err)| panic!("{}", err))
+}
+
+/// Replaces the first `old` in `before` with `new`, returning the edit and the edited text
+fn edit(before: &str, old: &str, new: &str) -> (TextEdit, String) {
+    let start = before.find(old).expect("Text to replace");
+    let span = Span::new(BytePos::from(start), BytePos::from(start + old.len()));
+    (TextEdit::new(span, new), before.replacen(old, new, 1))
+}
+
+fn reparse(
+    previous: SpannedExpr<'static, String>,
+    edit: &TextEdit,
+    after: &str,
+) -> SpannedExpr<'static, String> {
+    reparse_partial_expr(arena(), &mut MockEnv::new(), &TypeCache::new(), previous, edit, after)
+        .unwrap_or_else(|(_, err)| panic!("{}", err))
+}
+
+/// Checks that reparsing `before` after replacing `old` with `new` gives the same expression as
+/// parsing the edited text from scratch
+fn check_reparse(before: &str, old: &str, new: &str) {
+    let _ = ::env_logger::init();
+
+    let (edit, after) = edit(before, old, new);
+    assert_eq!(reparse(parse(before), &edit, &after), parse(&after));
+}
+
+fn binding_types(mut expr: &SpannedExpr<String>) -> Vec<(String, bool)> {
+    let mut types = Vec::new();
+    while let Expr::LetBindings(ref binds, ref body) = expr.value {
+        for bind in binds.iter() {
+            if let Pattern::Ident(ref id) = bind.name.value {
+                types.push((id.name.clone(), bind.resolved_type == Type::int()));
+            }
+        }
+        expr = body;
+    }
+    types
+}
+
+#[test]
+fn reparse_only_edited_binding() {
+    let _ = ::env_logger::init();
+
+    let before = r#"
+let x = 1
+let y = 2
+let z = 3
+x + y + z
+"#;
+    let mut previous = parse(before);
+    // Mark every binding so that it is possible to tell which ones were parsed again
+    {
+        let mut expr = &mut previous;
+        while let Expr::LetBindings(ref mut binds, ref mut body) = { expr }.value {
+            binds[0].resolved_type = Type::int();
+            expr = body;
+        }
+    }
+
+    let (edit, after) = edit(before, "2", "20 + 1");
+    let expr = reparse(previous, &edit, &after);
+    assert_eq!(
+        binding_types(&expr),
+        vec![
+            ("x".to_string(), true),
+            ("y".to_string(), false),
+            ("z".to_string(), true),
+        ]
+    );
+}
+
+#[test]
+fn edit_value_binding() {
+    check_reparse(
+        r#"
+let x = 1
+let f a b =
+    a + b
+let z = { a = 1, b = "" }
+f x z.a
+"#,
+        "a + b",
+        "let c = a * b\n    c + b",
+    );
+}
+
+#[test]
+fn edit_type_binding() {
+    check_reparse(
+        r#"
+type Test = Int
+let x : Test = 1
+type Other = { x : Test, y : Float }
+let y : Other = { x, y = 1.0 }
+y
+"#,
+        "Int",
+        "String",
+    );
+}
+
+#[test]
+fn edit_binding_with_comments() {
+    check_reparse(
+        r#"
+/// The first binding
+let x = 1 // one
+// A comment between the bindings
+let y = {
+    // The field
+    a = "abc",
+}
+/// Doc
+let z = 3
+y
+"#,
+        "\"abc\"",
+        "\"abcdef\"",
+    );
+}
+
+#[test]
+fn edit_first_binding_after_shebang() {
+    check_reparse(
+        r#"#!/bin/gluon
+let x = 1
+let y = x
+y
+"#,
+        "1",
+        "123",
+    );
+}
+
+#[test]
+fn edit_indented_bindings() {
+    check_reparse(
+        r#"
+    let x = 1
+    let y =
+        let z = x
+        z
+    y
+"#,
+        "let z = x",
+        "let z = x + 1",
+    );
+}
+
+#[test]
+fn edit_between_bindings() {
+    check_reparse(
+        r#"
+let x = 1
+
+// A comment
+let y = x
+y
+"#,
+        "A comment",
+        "A comment about y",
+    );
+}
+
+#[test]
+fn edit_adding_binding() {
+    check_reparse(
+        r#"
+let x = 1
+let y = x
+y
+"#,
+        "let y = x",
+        "let y = x\nlet w = y",
+    );
+}
+
+#[test]
+fn edit_body() {
+    check_reparse(
+        r#"
+let x = 1
+let y = x
+y
+"#,
+        "\ny\n",
+        "\nx + y\n",
+    );
+}
+
+#[test]
+fn edit_merging_function_equations() {
+    check_reparse(
+        r#"
+let f 0 = 1
+let g n = n
+f 2
+"#,
+        "g",
+        "f",
+    );
+}
+
+#[test]
+fn edit_after_fixity_declaration() {
+    check_reparse(
+        r#"
+#[infix(right, 5)]
+let (<->) x y = x
+let x = 1 <-> 2 <-> 3
+x
+"#,
+        "3",
+        "4 <-> 5",
+    );
+}
+
+#[test]
+fn edit_with_syntax_error() {
+    let _ = ::env_logger::init();
+
+    let before = r#"
+let x = 1
+let y = x
+y
+"#;
+    let (edit, after) = edit(before, "x\n", "(x\n");
+    let result = reparse_partial_expr(
+        arena(),
+        &mut MockEnv::new(),
+        &TypeCache::new(),
+        parse(before),
+        &edit,
+        &after,
+    );
+    assert!(result.is_err());
+}
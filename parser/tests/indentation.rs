@@ -7,10 +7,10 @@ mod support;
 
 use base::ast::*;
 use parser::{parse_string, ParseErrors};
-use support::MockEnv;
+use support::{arena, MockEnv};
 
-fn parse(text: &str) -> Result<SpannedExpr<String>, ParseErrors> {
-    parse_string(&mut MockEnv::new(), text).map_err(|(_, err)| err)
+fn parse(text: &str) -> Result<SpannedExpr<'static, String>, ParseErrors> {
+    parse_string(arena(), &mut MockEnv::new(), text).map_err(|(_, err)| err)
 }
 
 #[test]
@@ -212,7 +212,7 @@ else
 
     if let Expr::LetBindings(_, ref expr) = result.as_ref().unwrap().value {
         if let Expr::IfElse(_, _, ref if_false) = expr.value {
-            if let Expr::Block(_) = if_false.as_ref().value {
+            if let Expr::Block(_) = if_false.value {
                 return;
             }
         }
@@ -261,3 +261,24 @@ match True with
 
     assert!(false, "{:?}", result.unwrap());
 }
+
+#[test]
+fn block_if_else() {
+    let _ = ::env_logger::init();
+
+    let result = parse(
+        r#"
+if True then 1 else 0
+2
+"#,
+    );
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    if let Expr::Block(ref exprs) = result.as_ref().unwrap().value {
+        assert_eq!(2, exprs.len());
+        return;
+    }
+
+    assert!(false, "{:?}", result.unwrap());
+}
@@ -5,12 +5,12 @@ extern crate gluon_parser as parser;
 
 use base::ast::SpannedExpr;
 use parser::{parse_string, ParseErrors};
-use support::MockEnv;
+use support::{arena, MockEnv};
 
 mod support;
 
-fn parse(text: &str) -> Result<SpannedExpr<String>, ParseErrors> {
-    parse_string(&mut MockEnv::new(), text).map_err(|(_, err)| err)
+fn parse(text: &str) -> Result<SpannedExpr<'static, String>, ParseErrors> {
+    parse_string(arena(), &mut MockEnv::new(), text).map_err(|(_, err)| err)
 }
 
 #[test]
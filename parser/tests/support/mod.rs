@@ -1,21 +1,22 @@
 #![allow(unused)]
 
-use base::ast::{walk_mut_ast_type, walk_mut_expr, walk_mut_pattern, Alternative, Array, AstType,
-                DisplayEnv, Expr, ExprField, IdentEnv, Lambda, Literal, MutVisitor, Pattern,
-                SpannedAlias, SpannedAstType, SpannedExpr, SpannedIdent, SpannedPattern,
+use base::ast::{walk_mut_ast_type, walk_mut_expr, walk_mut_pattern, Alternative, Arena, Array,
+                AstType, DisplayEnv, Expr, ExprField, IdentEnv, Lambda, Literal, MutVisitor,
+                Pattern, SpannedAlias, SpannedAstType, SpannedExpr, SpannedIdent, SpannedPattern,
                 TypeBinding, TypedIdent, ValueBinding};
 use base::error::Errors;
 use base::pos::{self, BytePos, Span, Spanned};
 use base::kind::Kind;
 use base::types::{Alias, AliasData, ArcType, Field, Generic, Type};
 use parser::{parse_string, Error, ParseErrors};
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
-pub struct MockEnv<T>(PhantomData<T>);
+pub struct MockEnv<T>(HashMap<String, usize>, PhantomData<T>);
 
 impl<T> MockEnv<T> {
     pub fn new() -> MockEnv<T> {
-        MockEnv(PhantomData)
+        MockEnv(HashMap::new(), PhantomData)
     }
 }
 
@@ -34,15 +35,21 @@ where
     fn from_str(&mut self, s: &str) -> Self::Ident {
         T::from(s)
     }
+
+    fn fresh_ident(&mut self, prefix: &str) -> Self::Ident {
+        let count = self.0.entry(prefix.to_string()).or_insert(0);
+        *count += 1;
+        T::from(&*format!("{}:{}", prefix, count))
+    }
 }
 
 /// MutVisitor that clears spans.
 pub struct NoSpan;
 
-impl MutVisitor for NoSpan {
+impl<'ast> MutVisitor<'ast> for NoSpan {
     type Ident = String;
 
-    fn visit_expr(&mut self, e: &mut SpannedExpr<Self::Ident>) {
+    fn visit_expr(&mut self, e: &mut SpannedExpr<'ast, Self::Ident>) {
         e.span = Span::default();
         walk_mut_expr(self, e);
     }
@@ -71,10 +78,17 @@ impl MutVisitor for NoSpan {
     }
 }
 
-pub fn parse(
-    input: &str,
-) -> Result<SpannedExpr<String>, (Option<SpannedExpr<String>>, ParseErrors)> {
-    parse_string(&mut MockEnv::new(), input)
+/// Returns the arena which the expressions of the current test are allocated in. It is leaked so
+/// that the expressions can be built without passing an arena to every helper.
+pub fn arena() -> &'static Arena<'static, String> {
+    thread_local! {
+        static ARENA: &'static Arena<'static, String> = Box::leak(Box::new(Arena::new()));
+    }
+    ARENA.with(|arena| *arena)
+}
+
+pub fn parse(input: &str) -> Result<SpExpr, (Option<SpExpr>, ParseErrors)> {
+    parse_string(arena(), &mut MockEnv::new(), input)
 }
 
 /// Clears spans of the expression.
@@ -102,7 +116,7 @@ pub fn intern(s: &str) -> String {
     String::from(s)
 }
 
-pub type SpExpr = SpannedExpr<String>;
+pub type SpExpr = SpannedExpr<'static, String>;
 
 pub fn no_loc<T>(value: T) -> Spanned<T, BytePos> {
     pos::spanned(Span::default(), value)
@@ -110,9 +124,9 @@ pub fn no_loc<T>(value: T) -> Spanned<T, BytePos> {
 
 pub fn binop(l: SpExpr, s: &str, r: SpExpr) -> SpExpr {
     no_loc(Expr::Infix(
-        Box::new(l),
+        arena().alloc(l),
         no_loc(TypedIdent::new(intern(s))),
-        Box::new(r),
+        arena().alloc(r),
     ))
 }
 
@@ -126,19 +140,22 @@ pub fn let_(s: &str, e: SpExpr, b: SpExpr) -> SpExpr {
 
 pub fn let_a(s: &str, args: &[&str], e: SpExpr, b: SpExpr) -> SpExpr {
     no_loc(Expr::LetBindings(
-        vec![
+        arena().alloc_extend(vec![
             ValueBinding {
                 comment: None,
+                comments: Vec::new(),
+                attributes: Vec::new(),
                 name: no_loc(Pattern::Ident(TypedIdent::new(intern(s)))),
                 typ: None,
                 resolved_type: Type::hole(),
+                implicit_args: Vec::new(),
                 args: args.iter()
                     .map(|i| no_loc(TypedIdent::new(intern(i))))
                     .collect(),
                 expr: e,
             },
-        ],
-        Box::new(b),
+        ]),
+        arena().alloc(b),
     ))
 }
 
@@ -164,26 +181,25 @@ pub fn generic(s: &str) -> Generic<String> {
 }
 
 pub fn app(e: SpExpr, args: Vec<SpExpr>) -> SpExpr {
-    no_loc(Expr::App(Box::new(e), args))
+    no_loc(Expr::App(arena().alloc(e), arena().alloc_extend(args)))
 }
 
 pub fn if_else(p: SpExpr, if_true: SpExpr, if_false: SpExpr) -> SpExpr {
     no_loc(Expr::IfElse(
-        Box::new(p),
-        Box::new(if_true),
-        Box::new(if_false),
+        arena().alloc(p),
+        arena().alloc(if_true),
+        arena().alloc(if_false),
     ))
 }
 
 pub fn case(e: SpExpr, alts: Vec<(Pattern<String>, SpExpr)>) -> SpExpr {
     no_loc(Expr::Match(
-        Box::new(e),
-        alts.into_iter()
-            .map(|(p, e)| Alternative {
-                pattern: no_loc(p),
-                expr: e,
-            })
-            .collect(),
+        arena().alloc(e),
+        arena().alloc_extend(alts.into_iter().map(|(p, e)| Alternative {
+            comments: Vec::new(),
+            pattern: no_loc(p),
+            expr: e,
+        })),
     ))
 }
 
@@ -193,7 +209,7 @@ pub fn lambda(name: &str, args: Vec<String>, body: SpExpr) -> SpExpr {
         args: args.into_iter()
             .map(|id| no_loc(TypedIdent::new(id)))
             .collect(),
-        body: Box::new(body),
+        body: arena().alloc(body),
     }))
 }
 
@@ -207,6 +223,8 @@ pub fn type_decl(
         vec![
             TypeBinding {
                 comment: None,
+                comments: Vec::new(),
+                attributes: Vec::new(),
                 name: no_loc(name.clone()),
                 alias: no_loc(AliasData::new(name, args, typ)),
                 finalized_alias: None,
@@ -217,7 +235,10 @@ pub fn type_decl(
 }
 
 pub fn type_decls(binds: Vec<TypeBinding<String>>, body: SpExpr) -> SpExpr {
-    no_loc(Expr::TypeBindings(binds, Box::new(body)))
+    no_loc(Expr::TypeBindings(
+        arena().alloc_extend(binds),
+        arena().alloc(body),
+    ))
 }
 
 pub fn record(fields: Vec<(String, Option<SpExpr>)>) -> SpExpr {
@@ -230,38 +251,37 @@ pub fn record_a(
 ) -> SpExpr {
     no_loc(Expr::Record {
         typ: Type::hole(),
-        types: types
-            .into_iter()
-            .map(|(name, value)| ExprField {
-                comment: None,
-                name: no_loc(name),
-                value: value,
-            })
-            .collect(),
-        exprs: fields
-            .into_iter()
-            .map(|(name, value)| ExprField {
-                comment: None,
-                name: no_loc(name),
-                value: value,
-            })
-            .collect(),
+        types: arena().alloc_extend(types.into_iter().map(|(name, value)| ExprField {
+            comment: None,
+            comments: Vec::new(),
+            name: no_loc(name),
+            value: value,
+            strict: false,
+        })),
+        exprs: arena().alloc_extend(fields.into_iter().map(|(name, value)| ExprField {
+            comment: None,
+            comments: Vec::new(),
+            name: no_loc(name),
+            value: value,
+            strict: false,
+        })),
         base: None,
     })
 }
 
 pub fn field_access(expr: SpExpr, field: &str) -> SpExpr {
     no_loc(Expr::Projection(
-        Box::new(expr),
+        arena().alloc(expr),
         intern(field),
         Type::hole(),
+        Vec::new(),
     ))
 }
 
 pub fn array(fields: Vec<SpExpr>) -> SpExpr {
     no_loc(Expr::Array(Array {
         typ: Type::hole(),
-        exprs: fields,
+        exprs: arena().alloc_extend(fields),
     }))
 }
 
@@ -4,8 +4,8 @@ extern crate gluon_base as base;
 extern crate gluon_parser as parser;
 
 use base::ast::Expr;
-use base::types::Type;
-use support::{parse, typ, clear_span};
+use base::types::{Field, Type};
+use support::{clear_span, intern, parse, typ};
 
 mod support;
 
@@ -28,3 +28,27 @@ fn function_type() {
         _ => panic!("Expected let"),
     }
 }
+
+#[test]
+fn open_variant_type() {
+    let _ = env_logger::init();
+
+    let input = "let _ : [| A Int, B | r |] -> Int = 1 in 1";
+    let expr = parse(input).unwrap_or_else(|err| panic!("{}", err.1));
+    match clear_span(expr).value {
+        Expr::LetBindings(ref bindings, _) => {
+            let variant = Type::poly_variant(
+                vec![
+                    Field::new(intern("A"), Type::function(vec![typ("Int")], Type::hole())),
+                    Field::new(intern("B"), Type::function(vec![], Type::hole())),
+                ],
+                typ("r"),
+            );
+            assert_eq!(
+                bindings[0].typ,
+                Some(Type::function(vec![variant], typ("Int")))
+            );
+        }
+        _ => panic!("Expected let"),
+    }
+}
@@ -0,0 +1,56 @@
+//! Implementation of `gluon build` which precompiles gluon modules to bytecode
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use base::error::EmitOptions;
+use base::filename_to_module;
+
+use gluon::import::{Import, PRECOMPILED_EXTENSION};
+use gluon::{Compiler, Result, Thread};
+
+/// Writes the precompiled module of the file at `path` next to it, returning the path of the
+/// precompiled module
+fn build_file(vm: &Thread, path: &Path) -> Result<PathBuf> {
+    let mut source = String::new();
+    File::open(path)?.read_to_string(&mut source)?;
+
+    let module = filename_to_module(&path.to_string_lossy());
+    let precompiled = {
+        let import = vm.get_macros().get("import");
+        let import = import
+            .as_ref()
+            .and_then(|import| import.downcast_ref::<Import>())
+            .expect("Import macro");
+        import.precompile(&mut Compiler::new(), vm, &module, &source)?
+    };
+
+    let output = path.with_extension(PRECOMPILED_EXTENSION);
+    precompiled.write(io::BufWriter::new(File::create(&output)?))?;
+    Ok(output)
+}
+
+/// Precompiles each file, printing any errors to stderr. Returns the number of files which failed
+/// to compile.
+///
+/// A file is only loaded from its precompiled module when it is imported from a directory in the
+/// import paths, so the files should be given relative to such a directory.
+pub fn build_files(vm: &Thread, files: &[PathBuf], emit_options: &EmitOptions) -> usize {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    let errmsg = "Error writing to stderr";
+
+    files
+        .iter()
+        .filter(|file| match build_file(vm, file) {
+            Ok(output) => {
+                println!("Compiled {} to {}", file.display(), output.display());
+                false
+            }
+            Err(err) => {
+                writeln!(stderr, "error: {}", err.emit_string(emit_options)).expect(errmsg);
+                true
+            }
+        })
+        .count()
+}
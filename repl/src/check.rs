@@ -0,0 +1,214 @@
+//! Implementation of the `check` subcommand which typechecks files without running them
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use base::ast::{Arena, RootExpr};
+use base::error::EmitOptions;
+use base::filename_to_module;
+use base::symbol::Symbol;
+use base::types::{ArcType, TypeCache};
+
+use completion::{SymbolIndex, SYMBOL_INDEX_FILE};
+
+use gluon::{new_vm, Compiler, Result, RootedThread, Thread};
+use gluon::import::{imported_modules, CheckImporter, Import};
+
+/// How often the watched files are polled for changes (in milliseconds)
+const POLL_INTERVAL: u64 = 300;
+
+fn read_file(path: &Path) -> Result<String> {
+    let mut buffer = String::new();
+    File::open(path)?.read_to_string(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn module_name(path: &Path) -> String {
+    filename_to_module(&path.to_string_lossy())
+}
+
+/// Creates a vm which only typechecks the modules that are imported instead of running them
+pub fn check_vm() -> RootedThread {
+    let vm = new_vm();
+    let import = {
+        let import = vm.get_macros().get("import");
+        import
+            .as_ref()
+            .and_then(|import| import.downcast_ref::<Import>())
+            .expect("Import macro")
+            .with_importer(CheckImporter)
+    };
+    vm.get_macros().insert(String::from("import"), import);
+    vm
+}
+
+fn check_file(vm: &Thread, path: &Path) -> Result<(RootExpr<Symbol>, ArcType)> {
+    let source = read_file(path)?;
+    Compiler::new().typecheck_str(vm, &module_name(path), &source, None)
+}
+
+/// Typechecks each file, printing any errors to stderr. Returns the number of files which failed
+/// to typecheck.
+///
+/// The symbols exported by each file which typechecks are recorded in the symbol index of the
+/// current directory.
+pub fn check_files(vm: &Thread, files: &[PathBuf], emit_options: &EmitOptions) -> usize {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    let errmsg = "Error writing to stderr";
+
+    let index_path = Path::new(SYMBOL_INDEX_FILE);
+    let mut index = SymbolIndex::load(index_path).unwrap_or_else(|err| {
+        writeln!(stderr, "warning: Unable to load the symbol index: {}", err).expect(errmsg);
+        SymbolIndex::new()
+    });
+    let failed = files
+        .iter()
+        .filter(|file| match check_file(vm, file) {
+            Ok((expr, typ)) => {
+                index.update(&module_name(file), &file.to_string_lossy(), expr.expr(), &typ);
+                false
+            }
+            Err(err) => {
+                writeln!(stderr, "error: {}", err.emit_string(emit_options)).expect(errmsg);
+                true
+            }
+        })
+        .count();
+    if let Err(err) = index.save(index_path) {
+        writeln!(stderr, "warning: Unable to save the symbol index: {}", err).expect(errmsg);
+    }
+    failed
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    module: String,
+    modified: Option<SystemTime>,
+    imports: Vec<String>,
+}
+
+/// The import dependencies between the watched files
+struct ModuleGraph {
+    files: Vec<WatchedFile>,
+    type_cache: TypeCache<Symbol, ArcType>,
+}
+
+impl ModuleGraph {
+    fn new(files: Vec<PathBuf>) -> ModuleGraph {
+        ModuleGraph {
+            files: files
+                .into_iter()
+                .map(|path| WatchedFile {
+                    module: module_name(&path),
+                    path,
+                    modified: None,
+                    imports: Vec::new(),
+                })
+                .collect(),
+            type_cache: TypeCache::new(),
+        }
+    }
+
+    /// Updates the modification times and imports of all files, returning the indices of the
+    /// files which have changed since the last call
+    fn update(&mut self, compiler: &mut Compiler) -> Vec<usize> {
+        let type_cache = &self.type_cache;
+        let mut changed = Vec::new();
+        for (i, file) in self.files.iter_mut().enumerate() {
+            let modified = fs::metadata(&file.path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            if modified == file.modified {
+                continue;
+            }
+            file.modified = modified;
+            changed.push(i);
+
+            // If the file fails to parse we keep the old imports around as the typechecker
+            // will report the parse error anyway
+            let arena = Arena::new();
+            let imports = read_file(&file.path).ok().and_then(|source| {
+                compiler
+                    .parse_expr(&arena, type_cache, &file.module, &source)
+                    .ok()
+                    .map(|expr| imported_modules(&expr))
+            });
+            if let Some(imports) = imports {
+                file.imports = imports;
+            }
+        }
+        changed
+    }
+
+    /// Writes the imports of each file as a graph in the dot format
+    fn write_dot<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "digraph imports {{")?;
+        for file in &self.files {
+            writeln!(writer, "    \"{}\";", file.module)?;
+            for import in &file.imports {
+                writeln!(writer, "    \"{}\" -> \"{}\";", file.module, import)?;
+            }
+        }
+        writeln!(writer, "}}")
+    }
+
+    /// Returns the indices of `changed` and all files which transitively import them
+    fn with_dependents(&self, changed: &[usize]) -> BTreeSet<usize> {
+        let mut result: BTreeSet<usize> = changed.iter().cloned().collect();
+        let mut stack = changed.to_vec();
+        while let Some(i) = stack.pop() {
+            let module = &self.files[i].module;
+            for (j, file) in self.files.iter().enumerate() {
+                if file.imports.iter().any(|import| import == module) && result.insert(j) {
+                    stack.push(j);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Typechecks `files` and then keeps rechecking the files which change, along with the files
+/// depending on them, until the process is killed
+pub fn watch(files: Vec<PathBuf>, emit_options: &EmitOptions) -> Result<()> {
+    let mut compiler = Compiler::new();
+    let mut graph = ModuleGraph::new(files);
+    loop {
+        let changed = graph.update(&mut compiler);
+        if !changed.is_empty() {
+            let to_check: Vec<PathBuf> = graph
+                .with_dependents(&changed)
+                .into_iter()
+                .map(|i| graph.files[i].path.clone())
+                .collect();
+
+            // Loaded modules are cached in the vm so we need a fresh vm to observe any changes
+            let vm = check_vm();
+            let failed = check_files(&vm, &to_check, emit_options);
+            println!(
+                "Checked {} file(s): {} with errors, {} ok",
+                to_check.len(),
+                failed,
+                to_check.len() - failed
+            );
+        }
+        thread::sleep(Duration::from_millis(POLL_INTERVAL));
+    }
+}
+
+/// Prints the graph of the imports between `files` in the dot format
+pub fn print_import_graph(files: Vec<PathBuf>) -> Result<()> {
+    let mut graph = ModuleGraph::new(files);
+    graph.update(&mut Compiler::new());
+
+    let stdout = io::stdout();
+    graph.write_dot(&mut stdout.lock())?;
+    Ok(())
+}
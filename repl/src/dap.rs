@@ -0,0 +1,465 @@
+//! Implementation of the `debug` subcommand, a debugger which communicates with an editor over
+//! stdin and stdout using the Debug Adapter Protocol.
+//!
+//! The program runs on a thread of its own with a line hook installed. When the hook decides to
+//! stop, at a breakpoint or after a step, it records the frames and variables of the stack, sends
+//! a `stopped` event and blocks until the editor asks the program to continue.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+use futures::Async;
+use serde_json::Value;
+
+use base::filename_to_module;
+use base::pos::Line;
+use base::types::{ArcType, TypeEnv};
+
+use gluon::vm::internal::ValuePrinter;
+use gluon::vm::thread::{DebugInfo, HookFlags, ThreadInternal};
+use gluon::vm::Variants;
+use gluon::{new_vm, Compiler, Thread};
+
+use lsp::{read_message, write_message};
+
+/// The program only runs on a single thread which is reported with this id
+const THREAD_ID: i64 = 1;
+
+/// How the program continues after it has been stopped
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Step {
+    Continue,
+    /// Stop at the next line
+    Into,
+    /// Stop at the next line in a frame at most `depth` frames deep
+    Over(usize),
+    /// Stop at the next line in a frame less than `depth` frames deep
+    Out(usize),
+}
+
+struct Variable {
+    name: String,
+    typ: String,
+    value: String,
+}
+
+struct StackFrame {
+    name: String,
+    source: String,
+    line: Option<Line>,
+    variables: Vec<Variable>,
+}
+
+enum Run {
+    NotStarted,
+    Running,
+    Stopped {
+        /// The frames of the stack, innermost frame first
+        frames: Vec<StackFrame>,
+        /// The number of frames of the stack, including frames which are not reported
+        depth: usize,
+    },
+    Exited,
+}
+
+struct Output {
+    seq: i64,
+    writer: io::Stdout,
+}
+
+impl Output {
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.writer, &message)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+}
+
+/// State shared between the adapter and the thread running the program
+struct Shared {
+    output: Mutex<Output>,
+    /// The lines which have breakpoints, keyed by the module of the file they were set in
+    breakpoints: Mutex<HashMap<String, Vec<Line>>>,
+    run: Mutex<Run>,
+    run_changed: Condvar,
+}
+
+impl Shared {
+    fn set_run(&self, run: Run) {
+        *self.run.lock().unwrap() = run;
+        self.run_changed.notify_all();
+    }
+
+    fn has_breakpoint(&self, source: &str, line: Line) -> bool {
+        // Imported modules are named relative to the import path while breakpoints are keyed by
+        // the module of their full path so a suffix is enough for them to match
+        let suffix = format!(".{}", source);
+        self.breakpoints
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(module, lines)| {
+                (*module == source || module.ends_with(&suffix)) && lines.contains(&line)
+            })
+    }
+}
+
+fn print_value(env: &TypeEnv, typ: &ArcType, value: Variants) -> String {
+    ValuePrinter::new(env, typ, value)
+        .width(80)
+        .max_level(3)
+        .to_string()
+}
+
+fn stack_frames(thread: &Thread, debug_info: &DebugInfo) -> Vec<StackFrame> {
+    let env = thread.global_env().get_env();
+    (0..debug_info.stack_info_len())
+        .filter_map(|level| debug_info.stack_info(level))
+        .filter_map(|info| {
+            let name = info.function_name()?.to_string();
+            let line = info.line();
+            let mut variables: Vec<_> = info.locals()
+                .filter_map(|local| {
+                    let value = info.local_value(local)?;
+                    Some(Variable {
+                        name: local.name.declared_name().to_string(),
+                        typ: local.typ.to_string(),
+                        value: print_value(&env, &local.typ, value),
+                    })
+                })
+                .collect();
+            // Only closures have a line and upvariables
+            if line.is_some() {
+                variables.extend(info.upvars().iter().enumerate().filter_map(|(i, upvar)| {
+                    let value = info.upvar_value(i)?;
+                    Some(Variable {
+                        name: upvar.name.clone(),
+                        typ: upvar.typ.to_string(),
+                        value: print_value(&env, &upvar.typ, value),
+                    })
+                }));
+            }
+            Some(StackFrame {
+                name,
+                source: info.source_name().to_string(),
+                line,
+                variables,
+            })
+        })
+        .collect()
+}
+
+/// Runs `program` on a new thread, stopping it as decided by `step` and the breakpoints
+fn start(shared: Arc<Shared>, program: String, step: Step, steps: mpsc::Receiver<Step>) {
+    thread::spawn(move || {
+        let vm = new_vm();
+        {
+            let shared = shared.clone();
+            let steps = Mutex::new(steps);
+            let mut step = step;
+            let mut context = vm.context();
+            context.set_hook(Some(Box::new(move |thread, debug_info| {
+                let (source, line, depth) = match debug_info.stack_info(0) {
+                    Some(info) => match info.line() {
+                        Some(line) => (
+                            info.source_name().to_string(),
+                            line,
+                            debug_info.stack_info_len(),
+                        ),
+                        None => return Ok(Async::Ready(())),
+                    },
+                    None => return Ok(Async::Ready(())),
+                };
+                let reason = if shared.has_breakpoint(&source, line) {
+                    "breakpoint"
+                } else {
+                    match step {
+                        Step::Into => "step",
+                        Step::Over(max) if depth <= max => "step",
+                        Step::Out(max) if depth < max => "step",
+                        _ => return Ok(Async::Ready(())),
+                    }
+                };
+
+                let frames = stack_frames(thread, &debug_info);
+                // The event is sent first so that it precedes the responses to the requests which
+                // were waiting for the program to stop
+                let _ = shared.output.lock().unwrap().event(
+                    "stopped",
+                    json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+                );
+                shared.set_run(Run::Stopped { frames, depth });
+                // The adapter is gone if the channel is closed so let the program finish
+                step = steps.lock().unwrap().recv().unwrap_or(Step::Continue);
+                Ok(Async::Ready(()))
+            })));
+            context.set_hook_mask(HookFlags::LINE_FLAG);
+        }
+
+        let module = filename_to_module(&program);
+        let result = File::open(&program)
+            .and_then(|mut file| {
+                let mut text = String::new();
+                file.read_to_string(&mut text).map(|_| text)
+            })
+            .map_err(|err| err.into())
+            .and_then(|text| {
+                Compiler::new()
+                    .run_io(true)
+                    .load_script(&vm, &module, &text)
+            });
+
+        shared.set_run(Run::Exited);
+        let mut output = shared.output.lock().unwrap();
+        let exit_code = match result {
+            Ok(()) => 0,
+            Err(err) => {
+                let _ = output.event(
+                    "output",
+                    json!({ "category": "stderr", "output": format!("{}\n", err) }),
+                );
+                1
+            }
+        };
+        let _ = output.event("exited", json!({ "exitCode": exit_code }));
+        let _ = output.event("terminated", json!({}));
+    });
+}
+
+struct Server {
+    shared: Arc<Shared>,
+    program: Option<String>,
+    stop_on_entry: bool,
+    steps: Option<mpsc::Sender<Step>>,
+}
+
+impl Server {
+    fn new() -> Server {
+        Server {
+            shared: Arc::new(Shared {
+                output: Mutex::new(Output {
+                    seq: 0,
+                    writer: io::stdout(),
+                }),
+                breakpoints: Mutex::new(HashMap::new()),
+                run: Mutex::new(Run::NotStarted),
+                run_changed: Condvar::new(),
+            }),
+            program: None,
+            stop_on_entry: false,
+            steps: None,
+        }
+    }
+
+    /// Waits until the program has stopped or exited. Editors only ask about the stack after a
+    /// `stopped` event but waiting lets requests be sent ahead of it.
+    fn stopped(&self) -> Result<MutexGuard<Run>, String> {
+        let mut run = self.shared.run.lock().unwrap();
+        loop {
+            let running = match *run {
+                Run::NotStarted | Run::Running => true,
+                Run::Stopped { .. } => false,
+                Run::Exited => return Err("The program is not running".to_string()),
+            };
+            if !running {
+                return Ok(run);
+            }
+            run = self.shared.run_changed.wait(run).unwrap();
+        }
+    }
+
+    fn frame(&self, arguments: &Value, key: &str) -> Result<MutexGuard<Run>, String> {
+        let run = self.stopped()?;
+        let index = arguments[key].as_u64().unwrap_or(0) as usize;
+        match *run {
+            Run::Stopped { ref frames, .. } if 0 < index && index <= frames.len() => (),
+            _ => return Err(format!("Unknown {} {}", key, index)),
+        }
+        Ok(run)
+    }
+
+    fn resume(&self, step: fn(usize) -> Step) -> Result<Value, String> {
+        let mut run = self.stopped()?;
+        let depth = match *run {
+            Run::Stopped { depth, .. } => depth,
+            _ => unreachable!(),
+        };
+        *run = Run::Running;
+        if let Some(ref steps) = self.steps {
+            let _ = steps.send(step(depth));
+        }
+        Ok(json!({ "allThreadsContinued": true }))
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, String> {
+        let program = arguments["program"]
+            .as_str()
+            .ok_or_else(|| "Expected a `program` to debug".to_string())?;
+        self.program = Some(program.to_string());
+        self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+        Ok(Value::Null)
+    }
+
+    fn set_breakpoints(&self, arguments: &Value) -> Result<Value, String> {
+        let path = arguments["source"]["path"]
+            .as_str()
+            .ok_or_else(|| "Expected the `path` of the source".to_string())?;
+        let lines: Vec<_> = arguments["breakpoints"]
+            .as_array()
+            .map(|breakpoints| {
+                breakpoints
+                    .iter()
+                    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.shared.breakpoints.lock().unwrap().insert(
+            filename_to_module(path),
+            lines
+                .iter()
+                .map(|&line| Line::from(line.saturating_sub(1) as usize))
+                .collect(),
+        );
+        let breakpoints: Vec<_> = lines
+            .iter()
+            .map(|line| json!({ "verified": true, "line": line }))
+            .collect();
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn configuration_done(&mut self) -> Result<Value, String> {
+        let program = self.program
+            .clone()
+            .ok_or_else(|| "Expected `launch` before `configurationDone`".to_string())?;
+        let (sender, receiver) = mpsc::channel();
+        self.steps = Some(sender);
+        self.shared.set_run(Run::Running);
+        let step = if self.stop_on_entry {
+            Step::Into
+        } else {
+            Step::Continue
+        };
+        start(self.shared.clone(), program, step, receiver);
+        Ok(Value::Null)
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let run = self.stopped()?;
+        let frames = match *run {
+            Run::Stopped { ref frames, .. } => frames,
+            _ => unreachable!(),
+        };
+        let program_module = self.program.as_ref().map(|program| filename_to_module(program));
+        let stack_frames: Vec<_> = frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                let mut source = json!({ "name": frame.source });
+                if program_module.as_ref() == Some(&frame.source) {
+                    source["path"] = json!(self.program);
+                }
+                json!({
+                    "id": i + 1,
+                    "name": frame.name,
+                    "source": source,
+                    "line": frame.line.map_or(0, |line| line.to_usize() + 1),
+                    "column": 1,
+                })
+            })
+            .collect();
+        Ok(json!({ "stackFrames": stack_frames, "totalFrames": frames.len() }))
+    }
+
+    fn scopes(&self, arguments: &Value) -> Result<Value, String> {
+        // Only checks that the frame exists, the variables are looked up in `variables`
+        drop(self.frame(arguments, "frameId")?);
+        Ok(json!({
+            "scopes": [{
+                "name": "Locals",
+                "variablesReference": arguments["frameId"],
+                "expensive": false,
+            }]
+        }))
+    }
+
+    fn variables(&self, arguments: &Value) -> Result<Value, String> {
+        let run = self.frame(arguments, "variablesReference")?;
+        let index = arguments["variablesReference"].as_u64().unwrap_or(0) as usize;
+        let frame = match *run {
+            Run::Stopped { ref frames, .. } => &frames[index - 1],
+            _ => unreachable!(),
+        };
+        let variables: Vec<_> = frame
+            .variables
+            .iter()
+            .map(|variable| {
+                json!({
+                    "name": variable.name,
+                    "value": variable.value,
+                    "type": variable.typ,
+                    "variablesReference": 0,
+                })
+            })
+            .collect();
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// Handles a single request. Returns `false` once the editor has disconnected
+    fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let arguments = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or("");
+        let result = match command {
+            "initialize" => Ok(json!({ "supportsConfigurationDoneRequest": true })),
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "configurationDone" => self.configuration_done(),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => self.scopes(arguments),
+            "variables" => self.variables(arguments),
+            "continue" => self.resume(|_| Step::Continue),
+            "next" => self.resume(Step::Over),
+            "stepIn" => self.resume(|_| Step::Into),
+            "stepOut" => self.resume(Step::Out),
+            "disconnect" => Ok(Value::Null),
+            command => Err(format!("Unknown command `{}`", command)),
+        };
+
+        let mut output = self.shared.output.lock().unwrap();
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        output.send(response)?;
+        if command == "initialize" {
+            output.event("initialized", json!({}))?;
+        }
+        Ok(command != "disconnect")
+    }
+}
+
+/// Runs the debug adapter until the editor disconnects or closes stdin
+pub fn run() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+
+    let mut server = Server::new();
+    while let Some(request) = read_message(&mut stdin)? {
+        if !server.handle(&request)? {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,404 @@
+//! Implementation of the `doc` subcommand which generates documentation for the bindings exported
+//! by gluon modules
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use base::error::EmitOptions;
+use base::filename_to_module;
+use base::pos::{BytePos, Span};
+use base::source::Source;
+
+use completion::{SymbolIndex, SymbolKind};
+
+use gluon::check::metadata;
+use gluon::compiler_pipeline::Executable;
+use gluon::{Compiler, Result, Thread};
+
+/// The formats which documentation can be generated in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A page for each module and a page for its source code, similar to rustdoc
+    Html,
+    /// A single `index.json` file which contains every module
+    Json,
+}
+
+/// The documentation of a binding exported from a module
+#[derive(Debug, Serialize)]
+pub struct Item {
+    pub name: String,
+    /// One of `type`, `function` or `value`
+    pub kind: &'static str,
+    /// The type of a value or the definition of a type
+    #[serde(rename = "type")]
+    pub typ: String,
+    /// The contents of the documentation comment of the binding
+    pub comment: Option<String>,
+    /// The line, starting from 1, at which the binding is defined
+    pub line: usize,
+    /// The fenced gluon code blocks of the documentation comment which `gluon doc --test` runs
+    pub examples: Vec<String>,
+    #[serde(skip)]
+    span: Span<BytePos>,
+}
+
+/// The documentation of a module
+#[derive(Debug, Serialize)]
+pub struct Module {
+    pub name: String,
+    pub file: String,
+    pub items: Vec<Item>,
+    #[serde(skip)]
+    source: String,
+}
+
+/// Typechecks `path` and collects the documentation of everything it exports
+pub fn document_file(vm: &Thread, path: &Path) -> Result<Module> {
+    let mut source = String::new();
+    File::open(path)?.read_to_string(&mut source)?;
+
+    let file = path.to_string_lossy().into_owned();
+    let name = filename_to_module(&file);
+    let (expr, typ) = Compiler::new().typecheck_str(vm, &name, &source, None)?;
+
+    let (metadata, _) = metadata::metadata(&vm.get_env(), expr.expr());
+    let mut index = SymbolIndex::new();
+    index.update(&name, &file, expr.expr(), &typ);
+
+    let items = {
+        let lines = Source::new(&source);
+        index
+            .symbols()
+            .map(|symbol| {
+                let typ = match symbol.kind {
+                    // Show the definition of types instead of their kinds
+                    SymbolKind::Type => typ.type_field_iter()
+                        .find(|field| field.name.declared_name() == symbol.name)
+                        .map(|field| {
+                            let alias = &field.typ;
+                            let params = alias
+                                .params()
+                                .iter()
+                                .map(|param| format!(" {}", param.id))
+                                .collect::<String>();
+                            let definition = alias.unresolved_type().remove_forall();
+                            format!("{}{} = {}", symbol.name, params, definition)
+                        })
+                        .unwrap_or_else(|| symbol.typ.clone()),
+                    SymbolKind::Function | SymbolKind::Value => symbol.typ.clone(),
+                };
+                let comment = metadata
+                    .module
+                    .get(&symbol.name)
+                    .and_then(|metadata| metadata.comment.clone());
+                let examples = comment
+                    .as_ref()
+                    .map(|comment| {
+                        sections(comment)
+                            .into_iter()
+                            .filter_map(|section| match section {
+                                Section::Code { ref lang, ref code } if is_gluon(lang) => {
+                                    Some(code.clone())
+                                }
+                                _ => None,
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Item {
+                    name: symbol.name.clone(),
+                    kind: match symbol.kind {
+                        SymbolKind::Type => "type",
+                        SymbolKind::Function => "function",
+                        SymbolKind::Value => "value",
+                    },
+                    typ,
+                    comment,
+                    line: lines.line_number_at_byte(symbol.span.start).to_usize() + 1,
+                    examples,
+                    span: symbol.span,
+                }
+            })
+            .collect()
+    };
+    Ok(Module {
+        name,
+        file,
+        items,
+        source,
+    })
+}
+
+/// Writes the documentation of each file in `files` to the directory `output`
+pub fn generate(vm: &Thread, files: &[PathBuf], output: &Path, format: Format) -> Result<()> {
+    let modules = files
+        .iter()
+        .map(|file| document_file(vm, file))
+        .collect::<Result<Vec<_>>>()?;
+
+    fs::create_dir_all(output)?;
+    match format {
+        Format::Json => {
+            let file = File::create(output.join("index.json"))?;
+            serde_json::to_writer_pretty(file, &modules)
+                .map_err(|err| ::gluon::Error::Other(Box::new(err)))?;
+        }
+        Format::Html => {
+            fs::create_dir_all(output.join("src"))?;
+            write_index_html(&mut File::create(output.join("index.html"))?, &modules)?;
+            for module in &modules {
+                let path = output.join(format!("{}.html", module.name));
+                write_module_html(&mut File::create(path)?, module)?;
+                let path = output.join("src").join(format!("{}.html", module.name));
+                write_source_html(&mut File::create(path)?, module)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A part of a documentation comment
+#[derive(Debug, PartialEq)]
+enum Section {
+    Text(String),
+    /// A code block fenced by three backticks
+    Code { lang: String, code: String },
+}
+
+/// Code blocks without a language are assumed to be gluon code
+fn is_gluon(lang: &str) -> bool {
+    lang.is_empty() || lang == "gluon"
+}
+
+/// Splits a documentation comment into text and fenced code blocks
+fn sections(comment: &str) -> Vec<Section> {
+    let mut sections = Vec::new();
+    let mut text = String::new();
+    let mut lines = comment.lines();
+    while let Some(line) = lines.next() {
+        let fence = line.trim_left();
+        if !fence.starts_with("```") {
+            text.push_str(line);
+            text.push('\n');
+            continue;
+        }
+        if !text.trim().is_empty() {
+            sections.push(Section::Text(mem::replace(&mut text, String::new())));
+        }
+        text.clear();
+
+        let lang = fence.trim_left_matches('`').trim().to_string();
+        let mut code = String::new();
+        for line in lines.by_ref() {
+            if line.trim_left().starts_with("```") {
+                break;
+            }
+            code.push_str(line);
+            code.push('\n');
+        }
+        sections.push(Section::Code { lang, code });
+    }
+    if !text.trim().is_empty() {
+        sections.push(Section::Text(text));
+    }
+    sections
+}
+
+/// Runs each example of the documentation in `files`, printing the result of each example to
+/// stdout. Returns the number of examples which failed, counting each file which failed to compile
+/// as a failed example.
+pub fn run_tests(vm: &Thread, files: &[PathBuf], emit_options: &EmitOptions) -> usize {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let errmsg = "Error writing to stdout";
+
+    let mut passed = 0;
+    let mut failures = Vec::new();
+    for file in files {
+        let module = match document_file(vm, file) {
+            Ok(module) => module,
+            Err(err) => {
+                failures.push((file.display().to_string(), err.emit_string(emit_options)));
+                continue;
+            }
+        };
+        let examples = module
+            .items
+            .iter()
+            .map(|item| item.examples.len())
+            .sum::<usize>();
+        writeln!(
+            stdout,
+            "running {} doctest(s) in {}",
+            examples,
+            file.display()
+        ).expect(errmsg);
+
+        let lines = Source::new(&module.source);
+        for item in &module.items {
+            for (i, example) in item.examples.iter().enumerate() {
+                let name = format!("{} ({})", item.name, i);
+                // Run each example on its own thread so that a failing example does not affect
+                // the others
+                let result = vm.new_thread()
+                    .map_err(|err| err.into())
+                    .and_then(|thread| {
+                        let mut compiler = Compiler::new().run_io(true);
+                        let module_name = format!("{}.{}", module.name, item.name);
+                        example
+                            .run_expr(&mut compiler, &*thread, &module_name, example, None)
+                            .sync_or_error()
+                            .map(|_| ())
+                    });
+                let status = if result.is_ok() { "ok" } else { "FAILED" };
+                writeln!(stdout, "test {} ... {}", name, status).expect(errmsg);
+                match result {
+                    Ok(()) => passed += 1,
+                    Err(err) => {
+                        let location = lines.location(item.span.start).map_or(
+                            module.file.clone(),
+                            |location| {
+                                format!(
+                                    "{}:{}:{}",
+                                    module.file,
+                                    location.line.to_usize() + 1,
+                                    location.column.to_usize() + 1
+                                )
+                            },
+                        );
+                        failures.push((
+                            format!("{} ({})", name, location),
+                            err.emit_string(emit_options),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        writeln!(stdout, "\nfailures:").expect(errmsg);
+        for &(ref name, ref message) in &failures {
+            writeln!(stdout, "\n---- {} ----\n{}", name, message).expect(errmsg);
+        }
+    }
+    writeln!(
+        stdout,
+        "\ntest result: {}. {} passed; {} failed",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        passed,
+        failures.len()
+    ).expect(errmsg);
+    failures.len()
+}
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: auto; }
+code, pre { font-family: monospace; }
+.item { border-top: 1px solid #ddd; padding: 0.5em 0; }
+.src { float: right; font-size: small; }
+.line-number { color: #999; display: inline-block; width: 4em; text-align: right; \
+margin-right: 1em; }";
+
+fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn write_header<W: ?Sized + Write>(out: &mut W, title: &str) -> Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", escape(title))?;
+    writeln!(out, "<style>\n{}\n</style>", STYLE)?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    Ok(())
+}
+
+fn write_footer<W: ?Sized + Write>(out: &mut W) -> Result<()> {
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")?;
+    Ok(())
+}
+
+fn write_index_html<W: ?Sized + Write>(out: &mut W, modules: &[Module]) -> Result<()> {
+    write_header(out, "Modules")?;
+    writeln!(out, "<h1>Modules</h1>")?;
+    writeln!(out, "<ul>")?;
+    for module in modules {
+        let name = escape(&module.name);
+        writeln!(out, "<li><a href=\"{0}.html\">{0}</a></li>", name)?;
+    }
+    writeln!(out, "</ul>")?;
+    write_footer(out)
+}
+
+fn write_module_html<W: ?Sized + Write>(out: &mut W, module: &Module) -> Result<()> {
+    write_header(out, &module.name)?;
+    writeln!(out, "<h1>Module <code>{}</code></h1>", escape(&module.name))?;
+    for item in &module.items {
+        let keyword = match item.kind {
+            "type" => "type",
+            _ => "let",
+        };
+        let signature = match item.kind {
+            "type" => escape(&item.typ),
+            _ => format!("{} : {}", escape(&item.name), escape(&item.typ)),
+        };
+        writeln!(out, "<div class=\"item\" id=\"{}\">", escape(&item.name))?;
+        writeln!(
+            out,
+            "<a class=\"src\" href=\"src/{}.html#L{}\">[src]</a>",
+            escape(&module.name),
+            item.line
+        )?;
+        writeln!(out, "<pre><code>{} {}</code></pre>", keyword, signature)?;
+        if let Some(ref comment) = item.comment {
+            for section in sections(comment) {
+                match section {
+                    // Paragraphs are separated by empty lines
+                    Section::Text(text) => for paragraph in
+                        text.split("\n\n").filter(|p| !p.trim().is_empty())
+                    {
+                        writeln!(out, "<p>{}</p>", escape(paragraph.trim()))?;
+                    },
+                    Section::Code { code, .. } => {
+                        writeln!(out, "<pre><code>{}</code></pre>", escape(code.trim_right()))?;
+                    }
+                }
+            }
+        }
+        writeln!(out, "</div>")?;
+    }
+    write_footer(out)
+}
+
+fn write_source_html<W: ?Sized + Write>(out: &mut W, module: &Module) -> Result<()> {
+    write_header(out, &module.file)?;
+    writeln!(out, "<h1><code>{}</code></h1>", escape(&module.file))?;
+    writeln!(out, "<pre>")?;
+    for (i, line) in module.source.lines().enumerate() {
+        writeln!(
+            out,
+            "<span id=\"L{0}\"><span class=\"line-number\">{0}</span>{1}</span>",
+            i + 1,
+            escape(line)
+        )?;
+    }
+    writeln!(out, "</pre>")?;
+    write_footer(out)
+}
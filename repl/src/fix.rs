@@ -0,0 +1,88 @@
+//! Implementation of `gluon fix` which migrates gluon code away from deprecated bindings
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use base::error::EmitOptions;
+use base::filename_to_module;
+
+use gluon::check::metadata;
+use gluon::{Compiler, Error, Result, Thread};
+use gluon_format::fix::{self, Edit};
+use gluon_format::FormatSettings;
+
+/// Returns the code of `path` with each use of a deprecated binding which has a
+/// `#[replaced_by(..)]` attribute renamed to its replacement
+fn fix_source(vm: &Thread, path: &Path, source: &str) -> Result<String> {
+    let module = filename_to_module(&path.to_string_lossy());
+    let (expr, _) = Compiler::new().typecheck_str(vm, &module, source, None)?;
+
+    let edits: Vec<Edit> = metadata::deprecations(&vm.get_env(), expr.expr())
+        .into_iter()
+        .filter_map(|deprecation| {
+            let replacement = deprecation.value.replacement.as_ref()?;
+            fix::rename(source, deprecation.span, &deprecation.value.name, replacement)
+        })
+        .collect();
+    fix::apply_edits(source, edits).map_err(|err| Error::Other(Box::new(err)))
+}
+
+/// Fixes each file, printing any errors to stderr. If `check` is true the files are left
+/// unchanged and the files which need fixing are printed instead. If `settings` is given the fixed
+/// files are formatted as well. Returns the number of files which failed to be fixed or, if
+/// `check` is true, need fixing.
+pub fn fix_files(
+    vm: &Thread,
+    files: &[PathBuf],
+    settings: Option<&FormatSettings>,
+    check: bool,
+    emit_options: &EmitOptions,
+) -> usize {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    let errmsg = "Error writing to stderr";
+
+    files
+        .iter()
+        .filter(|file| match fix_file(vm, file, settings, check) {
+            Ok(false) => false,
+            Ok(true) if check => {
+                println!("{} needs to be fixed", file.display());
+                true
+            }
+            Ok(true) => {
+                println!("Fixed {}", file.display());
+                false
+            }
+            Err(err) => {
+                writeln!(stderr, "error: {}", err.emit_string(emit_options)).expect(errmsg);
+                true
+            }
+        })
+        .count()
+}
+
+/// Fixes the file at `path`, returning `true` if it was changed. A backup of the original file is
+/// written to `<path>.bk`. If `check` is true the file is left unchanged.
+fn fix_file(
+    vm: &Thread,
+    path: &Path,
+    settings: Option<&FormatSettings>,
+    check: bool,
+) -> Result<bool> {
+    let mut source = String::new();
+    File::open(path)?.read_to_string(&mut source)?;
+
+    let mut output = fix_source(vm, path, &source)?;
+    if let Some(settings) = settings {
+        output = ::format(settings, &output)?;
+    }
+    if output == source {
+        return Ok(false);
+    }
+    if !check {
+        File::create(path.with_extension("glu.bk"))?.write_all(source.as_bytes())?;
+        File::create(path)?.write_all(output.as_bytes())?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,300 @@
+//! Generation of syntax highlighting grammars for editors. The keywords and operators are taken
+//! from the definitions which the lexer uses so that the grammars only need to be generated again
+//! when the syntax changes.
+
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use gluon::base::ast::OPERATOR_CHARS;
+use gluon::parser::{KEYWORDS, RESERVED_OPERATORS};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// A TextMate grammar (`gluon.tmLanguage.json`), understood by VS Code, Sublime Text and Atom
+    TextMate,
+    /// A `grammar.js` for Tree-sitter which recognizes the tokens of the language
+    TreeSitter,
+}
+
+/// A token which is described by a regex, together with the TextMate scope it is highlighted as
+struct TokenRule {
+    name: &'static str,
+    scope: &'static str,
+    regex: &'static str,
+}
+
+// The rules are tried in order so longer tokens must come before their prefixes
+const COMMENTS: &[TokenRule] = &[
+    TokenRule {
+        name: "doc_comment",
+        scope: "comment.line.documentation.gluon",
+        regex: r"///.*",
+    },
+    TokenRule {
+        name: "line_comment",
+        scope: "comment.line.double-slash.gluon",
+        regex: r"//.*",
+    },
+    TokenRule {
+        name: "block_comment",
+        scope: "comment.block.gluon",
+        regex: r"/\*[^*]*\*+(?:[^/*][^*]*\*+)*/",
+    },
+];
+
+const LITERALS: &[TokenRule] = &[
+    TokenRule {
+        name: "attribute",
+        scope: "meta.attribute.gluon",
+        regex: r"#!?\[[^\]]*\]",
+    },
+    TokenRule {
+        name: "multi_line_string_literal",
+        scope: "string.quoted.triple.gluon",
+        regex: r#""""(?:[^"]|"[^"]|""[^"])*""""#,
+    },
+    TokenRule {
+        name: "raw_string_literal",
+        scope: "string.quoted.other.raw.gluon",
+        regex: r##"r#*"[^"]*"#*"##,
+    },
+    TokenRule {
+        name: "string_literal",
+        scope: "string.quoted.double.gluon",
+        regex: r#""(?:[^"\\]|\\.)*""#,
+    },
+    TokenRule {
+        name: "char_literal",
+        scope: "constant.character.gluon",
+        regex: r"'(?:[^'\\]|\\.)'",
+    },
+    TokenRule {
+        name: "float_literal",
+        scope: "constant.numeric.float.gluon",
+        regex: r"-?[0-9][0-9_]*\.[0-9_]*",
+    },
+    TokenRule {
+        name: "int_literal",
+        scope: "constant.numeric.integer.gluon",
+        regex: r"-?(?:0x[0-9a-fA-F_]+|0o[0-7_]+|0b[01_]+|[0-9][0-9_]*b?)",
+    },
+];
+
+const CONSTRUCTOR: TokenRule = TokenRule {
+    name: "constructor",
+    scope: "entity.name.type.gluon",
+    regex: r"[A-Z][_a-zA-Z0-9']*",
+};
+
+const MACRO: TokenRule = TokenRule {
+    name: "macro",
+    scope: "entity.name.function.macro.gluon",
+    regex: r"[_a-zA-Z][_a-zA-Z0-9']*!",
+};
+
+const IDENTIFIER: TokenRule = TokenRule {
+    name: "identifier",
+    scope: "variable.other.gluon",
+    regex: r"[_a-zA-Z][_a-zA-Z0-9']*",
+};
+
+/// Returns a regex character class matching each of `chars`
+fn char_class(chars: &str) -> String {
+    let mut class = String::from("[");
+    for c in chars.chars() {
+        if "\\^-[]".contains(c) {
+            class.push('\\');
+        }
+        class.push(c);
+    }
+    class.push(']');
+    class
+}
+
+fn escape_regex(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        if "\\.^$|?*+()[]{}/".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Returns the reserved operators with the longest operators first so that they are matched
+/// before their prefixes
+fn reserved_operators() -> Vec<&'static str> {
+    let mut operators = RESERVED_OPERATORS.to_vec();
+    operators.sort_by(|l, r| r.len().cmp(&l.len()).then(l.cmp(r)));
+    operators
+}
+
+/// Quotes `s` as a javascript string
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn textmate_rule(rule: &TokenRule) -> Value {
+    json!({ "name": rule.scope, "match": rule.regex })
+}
+
+/// Generates a TextMate grammar
+pub fn textmate() -> Value {
+    let operator_chars = char_class(OPERATOR_CHARS);
+    let reserved_operators = reserved_operators()
+        .into_iter()
+        .map(escape_regex)
+        .collect::<Vec<_>>()
+        .join("|");
+
+    json!({
+        "name": "Gluon",
+        "scopeName": "source.gluon",
+        "fileTypes": ["glu"],
+        "patterns": [
+            { "include": "#comments" },
+            { "include": "#literals" },
+            { "include": "#keywords" },
+            { "include": "#operators" },
+            { "include": "#identifiers" },
+        ],
+        "repository": {
+            "comments": {
+                "patterns": COMMENTS.iter().map(textmate_rule).collect::<Vec<_>>(),
+            },
+            "literals": {
+                "patterns": LITERALS.iter().map(textmate_rule).collect::<Vec<_>>(),
+            },
+            "keywords": {
+                "patterns": [{
+                    "name": "keyword.control.gluon",
+                    "match": format!(r"\b(?:{})\b", KEYWORDS.join("|")),
+                }],
+            },
+            "operators": {
+                "patterns": [
+                    {
+                        "name": "keyword.operator.reserved.gluon",
+                        "match": format!(
+                            "(?<!{0})(?:{1})(?!{0})",
+                            operator_chars,
+                            reserved_operators
+                        ),
+                    },
+                    {
+                        "name": "keyword.operator.gluon",
+                        "match": format!("{}+", operator_chars),
+                    },
+                ],
+            },
+            "identifiers": {
+                "patterns": [
+                    textmate_rule(&MACRO),
+                    {
+                        "name": CONSTRUCTOR.scope,
+                        "match": format!(r"\b{}\b", CONSTRUCTOR.regex),
+                    },
+                    textmate_rule(&IDENTIFIER),
+                ],
+            },
+        },
+    })
+}
+
+/// Generates a Tree-sitter grammar which recognizes the tokens of gluon. The grammar is meant as
+/// the starting point of a full grammar and parses a file as a flat sequence of tokens.
+pub fn tree_sitter() -> String {
+    let mut rules = Vec::new();
+    rules.push((
+        "keyword",
+        format!(
+            "choice({})",
+            KEYWORDS.iter().map(|s| quote(s)).collect::<Vec<_>>().join(", ")
+        ),
+    ));
+    rules.push((
+        "reserved_operator",
+        format!(
+            "choice({})",
+            reserved_operators()
+                .into_iter()
+                .map(|s| quote(s))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    ));
+    rules.push(("operator", format!("/{}+/", char_class(OPERATOR_CHARS))));
+    for rule in LITERALS.iter().chain(&[MACRO, CONSTRUCTOR, IDENTIFIER]) {
+        rules.push((rule.name, format!("/{}/", rule.regex)));
+    }
+
+    let mut out = String::new();
+    writeln!(out, "// Generated by `gluon grammar --format tree-sitter`").unwrap();
+    writeln!(out, "module.exports = grammar({{").unwrap();
+    writeln!(out, "  name: 'gluon',").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "  extras: $ => [").unwrap();
+    writeln!(out, "    /\\s/,").unwrap();
+    for rule in COMMENTS {
+        writeln!(out, "    $.{},", rule.name).unwrap();
+    }
+    writeln!(out, "  ],").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "  word: $ => $.identifier,").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "  rules: {{").unwrap();
+    writeln!(out, "    source_file: $ => repeat($._token),").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    _token: $ => choice(").unwrap();
+    for &(name, _) in &rules {
+        writeln!(out, "      $.{},", name).unwrap();
+    }
+    writeln!(out, "    ),").unwrap();
+    let comment_rules = COMMENTS
+        .iter()
+        .map(|rule| (rule.name, format!("/{}/", rule.regex)))
+        .collect::<Vec<_>>();
+    for &(name, ref definition) in comment_rules.iter().chain(&rules) {
+        writeln!(out).unwrap();
+        writeln!(out, "    {}: $ => {},", name, definition).unwrap();
+    }
+    writeln!(out, "  }}").unwrap();
+    writeln!(out, "}});").unwrap();
+    out
+}
+
+pub fn generate(format: Format) -> String {
+    match format {
+        Format::TextMate => {
+            let mut grammar = ::serde_json::to_string_pretty(&textmate()).unwrap();
+            grammar.push('\n');
+            grammar
+        }
+        Format::TreeSitter => tree_sitter(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn textmate_grammar_contains_keywords() {
+        let grammar = textmate();
+        let keywords = grammar["repository"]["keywords"]["patterns"][0]["match"]
+            .as_str()
+            .unwrap();
+        for keyword in KEYWORDS {
+            assert!(keywords.contains(keyword), "{}", keyword);
+        }
+    }
+
+    #[test]
+    fn tree_sitter_grammar_contains_reserved_operators() {
+        let grammar = tree_sitter();
+        assert!(grammar.contains("reserved_operator: $ => choice('->', '..', "));
+        assert!(grammar.contains("'\\\\'"));
+    }
+}
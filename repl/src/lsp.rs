@@ -0,0 +1,528 @@
+//! Implementation of the `lsp` subcommand, a language server which communicates with an editor
+//! over stdin and stdout using the Language Server Protocol
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+use base::ast::{Expr, RootExpr, SpannedExpr};
+use base::error::InFile;
+use base::filename_to_module;
+use base::pos::{self, BytePos, Line, Span};
+use base::source::Source;
+use base::symbol::Symbol;
+use base::types::{Alias, ArcType, Type};
+
+use completion::{self, SymbolIndex, SymbolKind, SYMBOL_INDEX_FILE};
+use gluon::check::{instances, metadata};
+use gluon::check::warning::Warnings;
+use gluon::compiler_pipeline::Typecheckable;
+use gluon::either::Either;
+use gluon::vm::thread::ThreadInternal;
+use gluon::{new_vm, Compiler, Error, RootedThread};
+use gluon_format::format_expr;
+
+/// Error code for requests which the server does not implement
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// `CompletionItemKind` values
+const COMPLETION_VARIABLE: i64 = 6;
+const COMPLETION_CLASS: i64 = 7;
+
+/// `DiagnosticSeverity` values
+const SEVERITY_ERROR: i64 = 1;
+const SEVERITY_WARNING: i64 = 2;
+
+/// `SymbolKind` values
+const SYMBOL_CLASS: i64 = 5;
+const SYMBOL_FUNCTION: i64 = 12;
+const SYMBOL_VARIABLE: i64 = 13;
+
+/// Reads a message, returning `None` if the input was closed
+pub fn read_message<R>(reader: &mut R) -> io::Result<Option<Value>>
+where
+    R: BufRead,
+{
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        let mut parts = header.splitn(2, ':');
+        if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let content_length = content_length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length header")
+    })?;
+    let mut content = vec![0; content_length];
+    reader.read_exact(&mut content)?;
+    ::serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn write_message<W>(writer: &mut W, message: &Value) -> io::Result<()>
+where
+    W: Write,
+{
+    let content = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    writer.flush()
+}
+
+/// Converts a `Position` (with the character offset counted in UTF-16 code units) to a byte
+/// offset in `source`
+fn byte_pos(source: &Source, position: &Value) -> Option<BytePos> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+    let (start, text) = source.line(Line::from(line))?;
+    let mut utf16_offset = 0;
+    let offset = text.char_indices()
+        .find(|&(_, c)| {
+            utf16_offset += c.len_utf16();
+            utf16_offset > character
+        })
+        .map_or(text.len(), |(i, _)| i);
+    Some(start + BytePos::from(offset))
+}
+
+/// Converts a byte offset in `source` to a `Position`
+fn position(source: &Source, pos: BytePos) -> Value {
+    let line = source.line_number_at_byte(pos);
+    let character = source.line(line).map_or(0, |(start, text)| {
+        let offset = (pos.to_usize() - start.to_usize()).min(text.len());
+        text[..offset].chars().map(char::len_utf16).sum()
+    });
+    json!({ "line": line.to_usize(), "character": character })
+}
+
+fn range(source: &Source, span: Span<BytePos>) -> Value {
+    json!({ "start": position(source, span.start), "end": position(source, span.end) })
+}
+
+fn diagnostic(range: Value, severity: i64, message: &str) -> Value {
+    json!({ "range": range, "severity": severity, "source": "gluon", "message": message })
+}
+
+/// Appends a diagnostic for each error in `err` which occurred in `module`. Errors in other
+/// modules are reported at the start of the file.
+fn diagnostics(source: &Source, module: &str, err: Error, output: &mut Vec<Value>) {
+    fn in_file<E>(source: &Source, module: &str, err: InFile<E>, output: &mut Vec<Value>)
+    where
+        E: fmt::Display,
+    {
+        let in_module = err.source_name == module;
+        for err in err.errors() {
+            let span = if in_module {
+                Span::new(err.span.start.absolute, err.span.end.absolute)
+            } else {
+                Span::new(BytePos::from(0), BytePos::from(0))
+            };
+            output.push(diagnostic(
+                range(source, span),
+                SEVERITY_ERROR,
+                &err.value.to_string(),
+            ));
+        }
+    }
+
+    match err {
+        Error::Parse(err) => in_file(source, module, err, output),
+        Error::Typecheck(err) => in_file(source, module, err, output),
+        Error::Macro(err) => in_file(source, module, err, output),
+        Error::Multiple(errors) => for err in errors {
+            diagnostics(source, module, err, output);
+        },
+        err => output.push(diagnostic(
+            range(source, Span::new(BytePos::from(0), BytePos::from(0))),
+            SEVERITY_ERROR,
+            &err.to_string(),
+        )),
+    }
+}
+
+fn file_path(uri: &str) -> &str {
+    uri.trim_left_matches("file://")
+}
+
+fn module_name(uri: &str) -> String {
+    filename_to_module(file_path(uri))
+}
+
+/// Returns the name and the alias of each record type defined at the top level of `expr`, the
+/// types which may be used like a type class
+fn record_types<'a, 'ast>(
+    mut expr: &'a SpannedExpr<'ast, Symbol>,
+) -> Vec<(Span<BytePos>, &'a Alias<Symbol, ArcType>)> {
+    let mut types = Vec::new();
+    loop {
+        match expr.value {
+            Expr::TypeBindings(ref binds, ref body) => {
+                types.extend(binds.iter().filter_map(|bind| {
+                    let alias = bind.finalized_alias.as_ref()?;
+                    match **alias.aliased_type() {
+                        Type::Record(_) => Some((bind.name.span, alias)),
+                        _ => None,
+                    }
+                }));
+                expr = &**body;
+            }
+            Expr::LetBindings(_, ref body) => expr = &**body,
+            _ => return types,
+        }
+    }
+}
+
+/// A document which has been parsed and typechecked
+struct Checked {
+    expr: RootExpr<Symbol>,
+    /// The type of the document if it typechecked without errors
+    typ: Option<ArcType>,
+    error: Option<Error>,
+    warnings: Warnings,
+}
+
+struct Server {
+    vm: RootedThread,
+    /// The contents of each open document
+    documents: HashMap<String, String>,
+    /// The directory of the project which the editor opened
+    root: PathBuf,
+    /// The symbols exported by the modules of the project
+    index: SymbolIndex,
+}
+
+impl Server {
+    fn new() -> Server {
+        Server {
+            vm: new_vm(),
+            documents: HashMap::new(),
+            root: env::current_dir().unwrap_or_default(),
+            index: SymbolIndex::new(),
+        }
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        let root = params["rootUri"]
+            .as_str()
+            .map(file_path)
+            .or_else(|| params["rootPath"].as_str());
+        if let Some(root) = root {
+            self.root = PathBuf::from(root);
+        }
+        self.index = SymbolIndex::load(&self.root.join(SYMBOL_INDEX_FILE)).unwrap_or_else(|err| {
+            eprintln!("Unable to load the symbol index: {}", err);
+            SymbolIndex::new()
+        });
+        json!({
+            "capabilities": {
+                // Full document synchronization
+                "textDocumentSync": 1,
+                "hoverProvider": true,
+                "completionProvider": { "triggerCharacters": ["."] },
+                "definitionProvider": true,
+                "documentFormattingProvider": true,
+                "workspaceSymbolProvider": true,
+                "codeLensProvider": { "resolveProvider": false },
+            }
+        })
+    }
+
+    /// Parses and typechecks the document at `uri`. The expression is returned even if errors
+    /// were found so long as the parser could recover
+    fn check(&self, uri: &str) -> Option<Checked> {
+        let text = self.documents.get(uri)?;
+        let module = module_name(uri);
+        let mut compiler = Compiler::new();
+        let type_cache = self.vm.global_env().type_cache();
+        let parsed = RootExpr::try_new(|arena| {
+            compiler.parse_partial_expr(arena, type_cache, &module, text)
+        });
+        let (mut expr, error) = match parsed {
+            Ok(expr) => (expr, None),
+            Err((Some(expr), err)) => (expr, Some(Error::from(err))),
+            Err((None, err)) => {
+                return Some(Checked {
+                    expr: RootExpr::new(|_| {
+                        pos::spanned2(BytePos::from(0), BytePos::from(0), Expr::Error(None))
+                    }),
+                    typ: None,
+                    error: Some(Error::from(err)),
+                    warnings: Warnings::new(),
+                })
+            }
+        };
+        let (typ, error, warnings) = match error {
+            Some(error) => (None, Some(error), Warnings::new()),
+            None => match (&mut expr).typecheck(&mut compiler, &self.vm, &module, text) {
+                Ok(value) => (Some(value.typ), None, value.warnings),
+                Err(error) => (None, Some(error), Warnings::new()),
+            },
+        };
+        Some(Checked {
+            expr,
+            typ,
+            error,
+            warnings,
+        })
+    }
+
+    /// Publishes the errors of the document at `uri` and, if it has none, updates the symbols
+    /// which it exports in the symbol index
+    fn publish_diagnostics<W>(&mut self, writer: &mut W, uri: &str) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut output = Vec::new();
+        if let Some(checked) = self.check(uri) {
+            if let Some(error) = checked.error {
+                let text = &self.documents[uri];
+                diagnostics(&Source::new(text), &module_name(uri), error, &mut output);
+            } else if let Some(typ) = checked.typ {
+                let source = Source::new(&self.documents[uri]);
+                for warning in checked.warnings {
+                    output.push(diagnostic(
+                        range(&source, warning.span),
+                        SEVERITY_WARNING,
+                        &warning.value.to_string(),
+                    ));
+                }
+                self.index
+                    .update(&module_name(uri), file_path(uri), checked.expr.expr(), &typ);
+                if let Err(err) = self.index.save(&self.root.join(SYMBOL_INDEX_FILE)) {
+                    eprintln!("Unable to save the symbol index: {}", err);
+                }
+            }
+        }
+        write_message(
+            writer,
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": { "uri": uri, "diagnostics": output },
+            }),
+        )
+    }
+
+    /// Returns the checked document and the byte offset referred to by a
+    /// `TextDocumentPositionParams`
+    fn document_position(&self, params: &Value) -> Option<(Checked, Source, BytePos)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let checked = self.check(uri)?;
+        let source = Source::new(&self.documents[uri]);
+        let pos = byte_pos(&source, &params["position"])?;
+        Some((checked, source, pos))
+    }
+
+    fn hover(&self, params: &Value) -> Option<Value> {
+        let (checked, _, pos) = self.document_position(params)?;
+        let env = self.vm.get_env();
+        let typ = completion::find(&env, checked.expr.expr(), pos).ok()?;
+        let (_, metadata_env) = metadata::metadata(&env, checked.expr.expr());
+        let comment = completion::get_metadata(&metadata_env, checked.expr.expr(), pos)
+            .and_then(|metadata| metadata.comment.clone());
+        let mut contents = format!("```gluon\n{}\n```", typ);
+        if let Some(comment) = comment {
+            contents.push_str("\n\n");
+            contents.push_str(&comment);
+        }
+        Some(json!({ "contents": { "kind": "markdown", "value": contents } }))
+    }
+
+    fn completion(&self, params: &Value) -> Option<Value> {
+        let (checked, _, pos) = self.document_position(params)?;
+        let env = self.vm.get_env();
+        let items = completion::suggest(&env, checked.expr.expr(), pos)
+            .into_iter()
+            .map(|suggestion| {
+                let (kind, detail) = match suggestion.typ {
+                    Either::Left(kind) => (COMPLETION_CLASS, kind.to_string()),
+                    Either::Right(typ) => (COMPLETION_VARIABLE, typ.to_string()),
+                };
+                json!({ "label": suggestion.name, "kind": kind, "detail": detail })
+            })
+            .collect::<Vec<_>>();
+        Some(Value::Array(items))
+    }
+
+    fn definition(&self, params: &Value) -> Option<Value> {
+        let (checked, source, pos) = self.document_position(params)?;
+        let span = completion::find_definition(checked.expr.expr(), pos)?;
+        Some(json!({
+            "uri": params["textDocument"]["uri"],
+            "range": range(&source, span),
+        }))
+    }
+
+    fn workspace_symbols(&self, params: &Value) -> Option<Value> {
+        let query = params["query"].as_str()?;
+        // Symbols are converted to positions using the open document if there is one or
+        // otherwise the file on disk
+        let mut files = HashMap::new();
+        let symbols = completion::workspace_symbols(&self.index, query)
+            .into_iter()
+            .filter_map(|symbol| {
+                let uri = format!("file://{}", self.root.join(&symbol.file).display());
+                if !files.contains_key(&uri) {
+                    let text = match self.documents.get(&uri) {
+                        Some(text) => Some(text.clone()),
+                        None => {
+                            let mut text = String::new();
+                            File::open(file_path(&uri))
+                                .and_then(|mut file| file.read_to_string(&mut text))
+                                .ok()
+                                .map(|_| text)
+                        }
+                    };
+                    files.insert(uri.clone(), text);
+                }
+                let source = Source::new(files[&uri].as_ref()?);
+                let kind = match symbol.kind {
+                    SymbolKind::Type => SYMBOL_CLASS,
+                    SymbolKind::Function => SYMBOL_FUNCTION,
+                    SymbolKind::Value => SYMBOL_VARIABLE,
+                };
+                Some(json!({
+                    "name": symbol.name,
+                    "kind": kind,
+                    "location": { "uri": uri, "range": range(&source, symbol.span) },
+                    "containerName": symbol.module,
+                }))
+            })
+            .collect::<Vec<_>>();
+        Some(Value::Array(symbols))
+    }
+
+    /// Shows how many instances each record type of the document has. Instances are searched for
+    /// in the modules loaded by the server and in what the document itself exports.
+    fn code_lens(&self, params: &Value) -> Option<Value> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let checked = self.check(uri)?;
+        let source = Source::new(&self.documents[uri]);
+        let module = module_name(uri);
+        let env = self.vm.get_env();
+        // A loaded version of the document would be out of date so it is replaced by the checked
+        // version
+        let mut bindings: Vec<_> = env.globals()
+            .into_iter()
+            .filter(|&(name, _)| name != module)
+            .map(|(name, global)| (name.to_string(), &global.typ))
+            .collect();
+        bindings.extend(checked.typ.as_ref().map(|typ| (module.clone(), typ)));
+
+        let lenses = record_types(checked.expr.expr())
+            .into_iter()
+            .map(|(span, alias)| {
+                let found = instances::find_instances(&env, alias, bindings.iter().cloned());
+                let title = match found.len() {
+                    1 => "1 instance".to_string(),
+                    count => format!("{} instances", count),
+                };
+                json!({
+                    "range": range(&source, span),
+                    "command": { "title": title, "command": "" },
+                })
+            })
+            .collect::<Vec<_>>();
+        Some(Value::Array(lenses))
+    }
+
+    fn formatting(&self, params: &Value) -> Option<Value> {
+        let text = self.documents.get(params["textDocument"]["uri"].as_str()?)?;
+        let formatted = format_expr(text).ok()?;
+        let source = Source::new(text);
+        let whole_document = Span::new(BytePos::from(0), BytePos::from(text.len()));
+        Some(json!([{ "range": range(&source, whole_document), "newText": formatted }]))
+    }
+
+    /// Handles a single message. Returns `false` once the client has asked the server to exit
+    fn handle<W>(&mut self, writer: &mut W, message: &Value) -> io::Result<bool>
+    where
+        W: Write,
+    {
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().map(String::from);
+        let result = match message["method"].as_str().unwrap_or("") {
+            "initialize" => Ok(self.initialize(params)),
+            "shutdown" => Ok(Value::Null),
+            "exit" => return Ok(false),
+            "textDocument/didOpen" => {
+                if let (Some(uri), Some(text)) = (uri, params["textDocument"]["text"].as_str()) {
+                    self.documents.insert(uri.clone(), text.to_string());
+                    self.publish_diagnostics(writer, &uri)?;
+                }
+                return Ok(true);
+            }
+            "textDocument/didChange" => {
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    self.documents.insert(uri.clone(), text.to_string());
+                    self.publish_diagnostics(writer, &uri)?;
+                }
+                return Ok(true);
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = uri {
+                    self.documents.remove(&uri);
+                    self.publish_diagnostics(writer, &uri)?;
+                }
+                return Ok(true);
+            }
+            "textDocument/hover" => Ok(self.hover(params).unwrap_or(Value::Null)),
+            "textDocument/completion" => Ok(self.completion(params).unwrap_or(Value::Null)),
+            "textDocument/definition" => Ok(self.definition(params).unwrap_or(Value::Null)),
+            "textDocument/formatting" => Ok(self.formatting(params).unwrap_or(Value::Null)),
+            "textDocument/codeLens" => Ok(self.code_lens(params).unwrap_or(Value::Null)),
+            "workspace/symbol" => Ok(self.workspace_symbols(params).unwrap_or(Value::Null)),
+            method => Err(format!("Unknown method `{}`", method)),
+        };
+
+        // Notifications do not have an id and must not be responded to
+        let id = match message.get("id") {
+            Some(id) => id,
+            None => return Ok(true),
+        };
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(message) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": METHOD_NOT_FOUND, "message": message },
+            }),
+        };
+        write_message(writer, &response)?;
+        Ok(true)
+    }
+}
+
+/// Runs the language server until the client asks it to exit or closes stdin
+pub fn run() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    let mut server = Server::new();
+    while let Some(message) = read_message(&mut stdin)? {
+        if !server.handle(&mut stdout, &message)? {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -2,6 +2,7 @@
 #![doc(html_root_url = "https://docs.rs/gluon_repl/0.4.1")] // # GLUON
 
 extern crate app_dirs;
+extern crate atty;
 #[macro_use]
 extern crate clap;
 #[cfg(feature = "env_logger")]
@@ -11,18 +12,24 @@ extern crate futures;
 extern crate log;
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "async")]
 extern crate tokio_core;
+#[cfg(feature = "async")]
 extern crate tokio_signal;
 extern crate walkdir;
 
 extern crate gluon;
+extern crate gluon_completion as completion;
 extern crate gluon_format;
 #[macro_use]
 extern crate gluon_vm;
 
 use std::io::{self, Write};
 use std::ffi::OsStr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use walkdir::WalkDir;
 
@@ -30,30 +37,140 @@ use gluon::base;
 use gluon::parser;
 use gluon::vm;
 
-use base::error::InFile;
+use base::ast::RootExpr;
+use base::error::{EmitOptions, Errors, InFile};
+use base::symbol::Symbol;
+use base::timings;
 
 use gluon::{new_vm, Compiler, Error, Result, Thread};
+use gluon::compiler_pipeline::TypecheckValue;
+use gluon_format::FormatSettings;
+use gluon::vm::profile;
 use gluon::vm::thread::ThreadInternal;
 use gluon::vm::Error as VMError;
 
+mod build;
+mod check;
+mod dap;
+mod doc;
+mod fix;
+mod grammar;
+mod lsp;
 mod repl;
+mod test;
 
 const APP_INFO: app_dirs::AppInfo = app_dirs::AppInfo {
     name: "gluon-repl",
     author: "gluon-lang",
 };
 
-fn run_files<'s, I>(vm: &Thread, files: I) -> Result<()>
+/// How many milliseconds `gluon run --profile` waits between each sample of the call stack
+const PROFILE_INTERVAL_MS: u64 = 1;
+
+/// Process exit code used when compiling a program fails
+const EXIT_COMPILE_ERROR: i32 = 2;
+/// Process exit code used when a program fails while running
+const EXIT_RUNTIME_ERROR: i32 = 3;
+
+fn exit_code(err: &Error) -> i32 {
+    match *err {
+        Error::Parse(_) | Error::Typecheck(_) | Error::Macro(_) => EXIT_COMPILE_ERROR,
+        Error::VM(_) => EXIT_RUNTIME_ERROR,
+        Error::Multiple(ref errors) => errors.into_iter().map(exit_code).max().unwrap_or(1),
+        Error::IO(_) | Error::Other(_) => 1,
+    }
+}
+
+/// Runs each file as a module. If `print_results` is set the value of each file is printed as
+/// well (see `eval`)
+fn run_files<'s, I>(vm: &Thread, files: I, print_results: bool) -> Result<()>
 where
     I: Iterator<Item = &'s str>,
 {
+    use std::fs::File;
+    use std::io::Read;
+
     let mut compiler = Compiler::new().run_io(true);
     for file in files {
-        compiler.load_file(&vm, file)?;
+        let mut source = String::new();
+        File::open(file)?.read_to_string(&mut source)?;
+        let name = base::filename_to_module(file);
+        if print_results {
+            eval(&mut compiler, vm, &name, &source)?;
+        } else {
+            use gluon::compiler_pipeline::Executable;
+
+            typecheck(&mut compiler, vm, &name, &source)?
+                .load_script(&mut compiler, vm, &name, &source, ())
+                .sync_or_error()?;
+        }
     }
     Ok(())
 }
 
+/// Typechecks `source`, printing any warnings found in it to stderr
+fn typecheck(
+    compiler: &mut Compiler,
+    vm: &Thread,
+    name: &str,
+    source: &str,
+) -> Result<TypecheckValue<RootExpr<Symbol>>> {
+    use std::mem;
+    use gluon::compiler_pipeline::Typecheckable;
+
+    let mut value = source.typecheck(compiler, vm, name, source)?;
+    let warnings = mem::replace(&mut value.warnings, Errors::new());
+    if warnings.has_errors() {
+        eprintln!("{}", InFile::new(name, source, warnings));
+    }
+    Ok(value)
+}
+
+/// Compiles and runs `source`, printing the resulting value (unless it is `()`) to stdout
+fn eval(compiler: &mut Compiler, vm: &Thread, name: &str, source: &str) -> Result<()> {
+    use gluon::base::types::Type;
+    use gluon::compiler_pipeline::{Executable, ExecuteValue};
+    use gluon::vm::internal::ValuePrinter;
+
+    let ExecuteValue { value, typ, .. } = typecheck(compiler, vm, name, source)?
+        .run_expr(compiler, vm, name, source, ())
+        .sync_or_error()?;
+
+    if typ != Type::unit() {
+        let env = vm.global_env().get_env();
+        let output = ValuePrinter::new(&env, &typ, value.get_variant())
+            .width(80)
+            .max_level(5)
+            .to_string();
+        println!("{}", output);
+    }
+    Ok(())
+}
+
+fn eval_stdin(vm: &Thread) -> Result<()> {
+    use std::io::{stdin, Read};
+
+    let mut buffer = String::new();
+    stdin().read_to_string(&mut buffer)?;
+    eval(&mut Compiler::new().run_io(true), vm, "<stdin>", &buffer)
+}
+
+fn run_files_with_stacktrace<'s, I>(vm: &Thread, files: I, print_results: bool) -> Result<()>
+where
+    I: Iterator<Item = &'s str>,
+{
+    match run_files(vm, files, print_results) {
+        Err(Error::VM(VMError::Message(msg))) | Err(Error::VM(VMError::Panic(msg))) => {
+            Err(Error::VM(VMError::Message(format!(
+                "{}\n{}",
+                msg,
+                vm.context().stack.stacktrace(0)
+            ))))
+        }
+        result => result,
+    }
+}
+
 #[cfg(feature = "env_logger")]
 fn init_env_logger() {
     let _ = ::env_logger::init();
@@ -62,47 +179,212 @@ fn init_env_logger() {
 #[cfg(not(feature = "env_logger"))]
 fn init_env_logger() {}
 
-fn format(writer: &mut Write, buffer: &str) -> Result<usize> {
-    use gluon_format::format_expr;
+fn format(settings: &FormatSettings, buffer: &str) -> Result<String> {
+    use gluon_format::{format_expr_with, Error as FormatError};
 
-    let output = format_expr(buffer).map_err(|err| InFile::new("", buffer, err))?;
-    writer.write_all(output.as_bytes())?;
-    Ok(output.len())
+    format_expr_with(settings, buffer).map_err(|err| match err {
+        FormatError::Parse(err) => InFile::new("", buffer, err).into(),
+        err => Error::Other(Box::new(err)),
+    })
 }
 
-fn fmt_file(name: &Path) -> Result<()> {
+/// Formats the file at `name`, returning `false` if it was not already formatted. If `check` is
+/// true the file is left unchanged.
+fn fmt_file(name: &Path, settings: &FormatSettings, check: bool) -> Result<bool> {
     use std::io::{Read, Seek, SeekFrom};
     use std::fs::{File, OpenOptions};
 
-    let mut input_file = OpenOptions::new().read(true).write(true).open(name)?;
+    let mut input_file = OpenOptions::new().read(true).write(!check).open(name)?;
 
     let mut buffer = String::new();
     input_file.read_to_string(&mut buffer)?;
 
+    let output = format(settings, &buffer)?;
+    if check {
+        return Ok(output == buffer);
+    }
+
     {
         let mut backup = File::create(name.with_extension("glu.bk"))?;
         backup.write_all(buffer.as_bytes())?;
     }
 
     input_file.seek(SeekFrom::Start(0))?;
-    let written = format(&mut input_file, &buffer)?;
+    input_file.write_all(output.as_bytes())?;
     // Truncate the file to remove any data that were there before
-    input_file.set_len(written as u64)?;
-    Ok(())
+    input_file.set_len(output.len() as u64)?;
+    Ok(output == buffer)
 }
 
-fn fmt_stdio() -> Result<()> {
+/// Formats stdin and writes it to stdout, returning `false` if stdin was not already formatted.
+/// If `check` is true nothing is written.
+fn fmt_stdio(settings: &FormatSettings, check: bool) -> Result<bool> {
     use std::io::{stdin, stdout, Read};
 
     let mut buffer = String::new();
     stdin().read_to_string(&mut buffer)?;
 
-    format(&mut stdout(), &buffer)?;
+    let output = format(settings, &buffer)?;
+    if !check {
+        stdout().write_all(output.as_bytes())?;
+    }
+    Ok(output == buffer)
+}
+
+/// Writes the calls between the bindings in each file as a graph in the `dot` format
+fn call_graph<'s, I>(vm: &Thread, files: I) -> Result<()>
+where
+    I: Iterator<Item = &'s str>,
+{
+    use std::fs::File;
+    use std::io::Read;
+
+    use base::filename_to_module;
+    use completion::CallHierarchy;
+
+    let mut edges = Vec::new();
+    for file in files {
+        let mut buffer = String::new();
+        File::open(file)?.read_to_string(&mut buffer)?;
+
+        let module = filename_to_module(file);
+        let (expr, _) = Compiler::new().typecheck_str(vm, &module, &buffer, None)?;
+
+        edges.extend(CallHierarchy::new(expr.expr()).calls.into_iter().map(|call| {
+            (
+                format!("{}.{}", module, call.caller.declared_name()),
+                format!("{}.{}", module, call.callee.declared_name()),
+            )
+        }));
+    }
+    edges.sort();
+    edges.dedup();
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    writeln!(stdout, "digraph calls {{")?;
+    for (caller, callee) in edges {
+        writeln!(stdout, "    \"{}\" -> \"{}\";", caller, callee)?;
+    }
+    writeln!(stdout, "}}")?;
+    Ok(())
+}
+
+/// Translates the functions in `file` into Rust source code and writes it to stdout
+fn compile_to_rust(vm: &Thread, file: &str) -> Result<()> {
+    use std::fs::File;
+    use std::io::Read;
+
+    use base::filename_to_module;
+    use vm::core::{self, rust_backend};
+
+    let mut buffer = String::new();
+    File::open(file)?.read_to_string(&mut buffer)?;
+
+    let module = filename_to_module(file);
+    let (expr, _) = Compiler::new().typecheck_str(vm, &module, &buffer, None)?;
+
+    let env = vm.get_env();
+    let core_expr = core::translate(&env, expr.expr());
+    print!("{}", rust_backend::emit_rust(core_expr.expr()));
+    Ok(())
+}
+
+/// Writes the AST of `file` to stdout in the versioned JSON format of `gluon::ast_json`
+fn emit_ast_json(vm: &Thread, file: &str) -> Result<()> {
+    use std::fs::File;
+    use std::io::Read;
+
+    use base::ast::RootExpr;
+    use base::filename_to_module;
+    use gluon::ast_json;
+
+    let mut buffer = String::new();
+    File::open(file)?.read_to_string(&mut buffer)?;
+
+    let module = filename_to_module(file);
+    let type_cache = vm.global_env().type_cache();
+    let expr = RootExpr::try_new(|arena| {
+        Compiler::new().parse_partial_expr(arena, type_cache, &module, &buffer)
+    }).map_err(|(_, err)| err)?;
+
+    let stdout = io::stdout();
+    ast_json::write(stdout.lock(), &expr)?;
+    println!();
     Ok(())
 }
 
-fn run() -> std::result::Result<(), Box<std::error::Error + Send + Sync>> {
-    let matches = clap_app!(gluon =>
+/// Runs each file as a gluon program whose AST is stored in the JSON format of `gluon::ast_json`
+fn run_ast_json_files<'s, I>(vm: &Thread, files: I) -> Result<()>
+where
+    I: Iterator<Item = &'s str>,
+{
+    use std::fs::File;
+
+    use base::filename_to_module;
+    use gluon::ast_json;
+    use gluon::compiler_pipeline::Executable;
+
+    let mut compiler = Compiler::new().run_io(true);
+    for file in files {
+        let expr = ast_json::read(io::BufReader::new(File::open(file)?))?;
+        let module = filename_to_module(file);
+        expr.load_script(&mut compiler, vm, &module, "", None)
+            .sync_or_error()?;
+    }
+    Ok(())
+}
+
+/// Translates the functions in `file` into a WebAssembly module which is written next to `file`
+fn compile_to_wasm(vm: &Thread, file: &str) -> Result<()> {
+    use std::fs::File;
+    use std::io::Read;
+
+    use base::filename_to_module;
+    use vm::core::{self, wasm_backend};
+
+    let mut buffer = String::new();
+    File::open(file)?.read_to_string(&mut buffer)?;
+
+    let module = filename_to_module(file);
+    let (expr, _) = Compiler::new().typecheck_str(vm, &module, &buffer, None)?;
+
+    let env = vm.get_env();
+    let core_expr = core::translate(&env, expr.expr());
+    let wasm = wasm_backend::emit_wasm(core_expr.expr());
+    for (name, reason) in wasm.skipped {
+        eprintln!("Skipped `{}`: {}", name, reason);
+    }
+    File::create(Path::new(file).with_extension("wasm"))?.write_all(&wasm.bytes)?;
+    Ok(())
+}
+
+fn gluon_files<'s, I>(args: I) -> Vec<PathBuf>
+where
+    I: IntoIterator<Item = &'s str>,
+{
+    let mut gluon_files = args.into_iter()
+        .flat_map(|arg| {
+            WalkDir::new(arg).into_iter().filter_map(|entry| {
+                entry.ok().and_then(|entry| {
+                    if entry.file_type().is_file()
+                        && entry.path().extension() == Some(OsStr::new("glu"))
+                    {
+                        Some(entry.path().to_owned())
+                    } else {
+                        None
+                    }
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+    gluon_files.sort();
+    gluon_files.dedup();
+    gluon_files
+}
+
+fn app() -> clap::App<'static, 'static> {
+    clap_app!(gluon =>
         (version: crate_version!())
         (long_version:
             concat!(
@@ -112,65 +394,339 @@ fn run() -> std::result::Result<(), Box<std::error::Error + Send + Sync>> {
         )
         (about: "executes gluon programs")
         (@arg REPL: -i --interactive "Starts the repl")
+        (@arg COLOR: --color +global +takes_value possible_value[auto always never]
+            "Whether errors are colored (default: auto)")
+        (@arg MAX_ERRORS: --("max-errors") +global +takes_value {is_number}
+            "Stops reporting errors after this many errors have been reported")
+        (@arg TIMINGS: --timings +global
+            "Prints the wall time and peak memory usage of each compiler phase to stderr")
         (@subcommand fmt =>
             (about: "Formats gluon source code")
+            (@arg CHECK: --check
+                "Fails if any file is not formatted instead of formatting it")
+            (@arg MAX_WIDTH: --("max-width") +takes_value {is_number}
+                "The width which lines are kept within (default: 100)")
+            (@arg INDENT: --indent +takes_value {is_number}
+                "The number of spaces which nested code is indented with (default: 4)")
+            (@arg NO_TRAILING_COMMA: --("no-trailing-comma")
+                "Omits the comma after the last field of records which span several lines")
             (@arg INPUT: ... "Formats each file")
         )
+        (@subcommand fix =>
+            (about: "Renames the uses of deprecated bindings to their replacements")
+            (@arg CHECK: --check
+                "Fails if any file needs to be fixed instead of fixing it")
+            (@arg FORMAT: --format "Formats each file after fixing it")
+            (@arg INPUT: +required ... "Fixes each file and each `.glu` file in directories")
+        )
+        (@subcommand eval =>
+            (about: "Evaluates an expression and prints the resulting value")
+            (@arg EXPR: -e --expr +takes_value +required "The expression to evaluate")
+        )
+        (@subcommand run =>
+            (about: "Runs a gluon program")
+            (@arg STDIN: --stdin conflicts_with[INPUT] "Reads the program from stdin")
+            (@arg AST_JSON: --("ast-json")
+                "Reads each file as an AST written by `gluon compile --emit ast-json`")
+            (@arg PROFILE: --profile +takes_value
+                "Samples the call stack while the program runs and writes the samples to this \
+                 file in the folded stack format read by flame graph tools")
+            (@arg INPUT: required_unless[STDIN] ...
+                "Executes each file as a gluon program and prints its value")
+        )
+        (@subcommand check =>
+            (about: "Typechecks gluon source code without running it")
+            (@arg WATCH: -w --watch "Rechecks files and their dependents when they change")
+            (@arg GRAPH: --graph conflicts_with[WATCH]
+                "Prints the import graph of the files in the dot format after checking them")
+            (@arg INPUT: +required ... "Typechecks each file and each `.glu` file in directories")
+        )
+        (@subcommand lsp =>
+            (about: "Starts a language server which communicates over stdin and stdout")
+        )
+        (@subcommand debug =>
+            (about: "Starts a debugger which communicates over stdin and stdout using the Debug \
+                     Adapter Protocol")
+        )
+        (@subcommand test =>
+            (about: "Runs the bindings marked with `#[test]` in gluon source code")
+            (@arg BLESS: --bless
+                "Updates the expected strings of failed `expect!` tests instead of failing")
+            (@arg INPUT: +required ... "Runs the tests in each file")
+        )
+        (@subcommand build =>
+            (about: "Precompiles gluon modules into `.gluc` files which are imported instead")
+            (@arg INPUT: +required ... "Compiles each file and each `.glu` file in directories")
+        )
+        (@subcommand compile =>
+            (about: "Compiles gluon source code into another language (experimental)")
+            (@arg EMIT: --emit +takes_value +required
+                "What to emit: `rust`, `wasm` (written next to the input file) or `ast-json`, \
+                 the parsed AST as versioned JSON")
+            (@arg INPUT: +required "The file to compile")
+        )
+        (@subcommand doc =>
+            (about: "Generates documentation for the bindings which gluon modules export")
+            (@arg FORMAT: --format +takes_value possible_value[html json]
+                "The format of the documentation (default: html)")
+            (@arg OUTPUT: -o --output +takes_value
+                "The directory which the documentation is written to (default: doc)")
+            (@arg TEST: --test
+                "Runs the fenced gluon code blocks of the documentation comments instead")
+            (@arg INPUT: +required ... "Documents each file")
+        )
+        (@subcommand graph =>
+            (about: "Prints the call graph of gluon source code in the dot format")
+            (@arg INPUT: +required ... "The files to include in the graph")
+        )
+        (@subcommand grammar =>
+            (about: "Generates syntax highlighting grammars from the tokens of the lexer")
+            // `possible_value` only accepts identifiers so `tree-sitter` is checked in `run`
+            (@arg FORMAT: --format +takes_value +required
+                "The kind of grammar to generate (`textmate` or `tree-sitter`)")
+            (@arg OUTPUT: -o --output +takes_value
+                "The file to write the grammar to (default: stdout)")
+        )
         (@arg INPUT: ... "Executes each file as a gluon program")
-    ).get_matches();
+    )
+}
+
+fn is_number(value: String) -> std::result::Result<(), String> {
+    value
+        .parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| format!("Expected a number, found `{}`", value))
+}
+
+/// Returns how errors should be emitted according to the `--color` and `--max-errors` flags
+fn emit_options(matches: &clap::ArgMatches) -> EmitOptions {
+    // Global arguments may be given to either the subcommand or the main command
+    let value_of = |name| {
+        matches
+            .subcommand()
+            .1
+            .and_then(|matches| matches.value_of(name))
+            .or_else(|| matches.value_of(name))
+    };
+    let color = match value_of("COLOR") {
+        Some("always") => true,
+        Some("never") => false,
+        _ => atty::is(atty::Stream::Stderr),
+    };
+    EmitOptions {
+        color,
+        max_errors: value_of("MAX_ERRORS").and_then(|max_errors| max_errors.parse().ok()),
+    }
+}
+
+/// Returns true if the `--timings` flag was given to either the subcommand or the main command
+fn timings_enabled(matches: &clap::ArgMatches) -> bool {
+    matches.is_present("TIMINGS") || matches
+        .subcommand()
+        .1
+        .map_or(false, |matches| matches.is_present("TIMINGS"))
+}
+
+/// Returns the settings given to the `fmt` subcommand
+fn format_settings(matches: &clap::ArgMatches) -> FormatSettings {
+    let default = FormatSettings::default();
+    let number = |name, default| {
+        matches
+            .value_of(name)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    };
+    FormatSettings {
+        max_width: number("MAX_WIDTH", default.max_width),
+        indent: number("INDENT", default.indent),
+        record_trailing_comma: !matches.is_present("NO_TRAILING_COMMA"),
+    }
+}
+
+fn run(
+    matches: &clap::ArgMatches,
+    emit_options: &EmitOptions,
+) -> std::result::Result<(), Box<std::error::Error + Send + Sync>> {
     if let Some(fmt_matches) = matches.subcommand_matches("fmt") {
+        let settings = format_settings(fmt_matches);
+        let check = fmt_matches.is_present("CHECK");
         if let Some(args) = fmt_matches.values_of("INPUT") {
-            let mut gluon_files = args.into_iter()
-                .flat_map(|arg| {
-                    WalkDir::new(arg).into_iter().filter_map(|entry| {
-                        entry.ok().and_then(|entry| {
-                            if entry.file_type().is_file()
-                                && entry.path().extension() == Some(OsStr::new("glu"))
-                            {
-                                Some(entry.path().to_owned())
-                            } else {
-                                None
-                            }
-                        })
-                    })
-                })
-                .collect::<Vec<_>>();
-            gluon_files.sort();
-            gluon_files.dedup();
-
-            for file in gluon_files {
-                fmt_file(&file)?;
+            let mut unformatted = 0;
+            for file in gluon_files(args) {
+                if !fmt_file(&file, &settings, check)? && check {
+                    println!("{} is not formatted", file.display());
+                    unformatted += 1;
+                }
+            }
+            if unformatted != 0 {
+                return Err(format!("{} file(s) are not formatted", unformatted).into());
             }
+        } else if !fmt_stdio(&settings, check)? && check {
+            return Err("stdin is not formatted".into());
+        }
+    } else if let Some(fix_matches) = matches.subcommand_matches("fix") {
+        let files = gluon_files(fix_matches.values_of("INPUT").into_iter().flat_map(|x| x));
+        let settings = FormatSettings::default();
+        let settings = if fix_matches.is_present("FORMAT") {
+            Some(&settings)
         } else {
-            fmt_stdio()?;
+            None
+        };
+        let check = fix_matches.is_present("CHECK");
+        let failed = fix::fix_files(&check::check_vm(), &files, settings, check, emit_options);
+        if failed != 0 {
+            return Err(format!("{} file(s) could not be fixed or need fixing", failed).into());
         }
-    } else if matches.is_present("REPL") {
-        repl::run()?;
-    } else if let Some(args) = matches.values_of("INPUT") {
+    } else if let Some(eval_matches) = matches.subcommand_matches("eval") {
+        let expr = eval_matches.value_of("EXPR").expect("EXPR");
+        eval(&mut Compiler::new().run_io(true), &new_vm(), "<eval>", expr)?;
+    } else if let Some(run_matches) = matches.subcommand_matches("run") {
         let vm = new_vm();
-        match run_files(&vm, args) {
-            Ok(()) => (),
-            Err(err @ Error::VM(VMError::Message(_))) => {
-                return Err(format!("{}\n{}", err, vm.context().stack.stacktrace(0)).into())
+        let profiler = run_matches.value_of("PROFILE").map(|path| {
+            let interval = Duration::from_millis(PROFILE_INTERVAL_MS);
+            (path, profile::start_sampling(&vm, interval))
+        });
+        let result = if run_matches.is_present("STDIN") {
+            eval_stdin(&vm)
+        } else if run_matches.is_present("AST_JSON") {
+            let args = run_matches.values_of("INPUT").into_iter().flat_map(|x| x);
+            run_ast_json_files(&vm, args)
+        } else if let Some(args) = run_matches.values_of("INPUT") {
+            run_files_with_stacktrace(&vm, args, true)
+        } else {
+            Ok(())
+        };
+        // The samples are written even if the program failed
+        if let Some((path, profiler)) = profiler {
+            profiler
+                .stop()
+                .write_folded(std::fs::File::create(path)?)?;
+        }
+        result?;
+    } else if let Some(check_matches) = matches.subcommand_matches("check") {
+        let files = gluon_files(check_matches.values_of("INPUT").into_iter().flat_map(|x| x));
+        if check_matches.is_present("WATCH") {
+            check::watch(files, emit_options)?;
+        } else {
+            let failed = check::check_files(&check::check_vm(), &files, emit_options);
+            if check_matches.is_present("GRAPH") {
+                check::print_import_graph(files)?;
+            }
+            if failed != 0 {
+                return Err(format!("{} file(s) failed to typecheck", failed).into());
+            }
+        }
+    } else if matches.subcommand_matches("lsp").is_some() {
+        lsp::run()?;
+    } else if matches.subcommand_matches("debug").is_some() {
+        dap::run()?;
+    } else if let Some(test_matches) = matches.subcommand_matches("test") {
+        let files = gluon_files(test_matches.values_of("INPUT").into_iter().flat_map(|x| x));
+        let bless = test_matches.is_present("BLESS");
+        let failed = test::run_files(&new_vm(), &files, bless, emit_options);
+        if failed != 0 {
+            return Err(format!("{} test(s) failed", failed).into());
+        }
+    } else if let Some(build_matches) = matches.subcommand_matches("build") {
+        let files = gluon_files(build_matches.values_of("INPUT").into_iter().flat_map(|x| x));
+        let failed = build::build_files(&new_vm(), &files, emit_options);
+        if failed != 0 {
+            return Err(format!("{} file(s) failed to compile", failed).into());
+        }
+    } else if let Some(compile_matches) = matches.subcommand_matches("compile") {
+        let file = compile_matches.value_of("INPUT").expect("INPUT");
+        match compile_matches.value_of("EMIT") {
+            Some("rust") => compile_to_rust(&new_vm(), file)?,
+            Some("wasm") => compile_to_wasm(&new_vm(), file)?,
+            Some("ast-json") => emit_ast_json(&new_vm(), file)?,
+            emit => return Err(format!("Unknown --emit target {:?}", emit).into()),
+        }
+    } else if let Some(doc_matches) = matches.subcommand_matches("doc") {
+        let files = gluon_files(doc_matches.values_of("INPUT").into_iter().flat_map(|x| x));
+        if doc_matches.is_present("TEST") {
+            let failed = doc::run_tests(&new_vm(), &files, emit_options);
+            if failed != 0 {
+                return Err(format!("{} doctest(s) failed", failed).into());
             }
-            Err(err) => return Err(err.into()),
+        } else {
+            let format = match doc_matches.value_of("FORMAT") {
+                Some("json") => doc::Format::Json,
+                _ => doc::Format::Html,
+            };
+            let output = Path::new(doc_matches.value_of("OUTPUT").unwrap_or("doc"));
+            doc::generate(&new_vm(), &files, output, format)?;
         }
+    } else if let Some(graph_matches) = matches.subcommand_matches("graph") {
+        let vm = new_vm();
+        call_graph(&vm, graph_matches.values_of("INPUT").into_iter().flat_map(|args| args))?;
+    } else if let Some(grammar_matches) = matches.subcommand_matches("grammar") {
+        let format = match grammar_matches.value_of("FORMAT") {
+            Some("textmate") => grammar::Format::TextMate,
+            Some("tree-sitter") => grammar::Format::TreeSitter,
+            format => return Err(format!("Unknown grammar format {:?}", format).into()),
+        };
+        let grammar = grammar::generate(format);
+        match grammar_matches.value_of("OUTPUT") {
+            Some(output) => std::fs::File::create(output)?.write_all(grammar.as_bytes())?,
+            None => io::stdout().write_all(grammar.as_bytes())?,
+        }
+    } else if matches.is_present("REPL") {
+        repl::run(emit_options)?;
+    } else if let Some(args) = matches.values_of("INPUT") {
+        let vm = new_vm();
+        run_files_with_stacktrace(&vm, args, false)?;
     } else {
         write!(io::stderr(), "{}", matches.usage()).expect("Error writing help to stderr");
     }
     Ok(())
 }
 
+/// Parses the command line arguments. clap rejects files such as `tests/main.glu` as misspellings
+/// of the `test` subcommand (even after `--`), so if an existing file is rejected the arguments are
+/// parsed again with the misspelling check disabled. That is only done through
+/// `InferSubcommands` which is otherwise left off so that prefixes are not accepted as subcommands.
+fn get_matches() -> clap::ArgMatches<'static> {
+    use std::env;
+
+    let args = env::args_os().collect::<Vec<_>>();
+    match app().get_matches_from_safe(args.clone()) {
+        Ok(matches) => matches,
+        Err(err) => {
+            let has_file = args.iter().skip(1).any(|arg| Path::new(arg).is_file());
+            if has_file && err.kind == clap::ErrorKind::InvalidSubcommand {
+                app()
+                    .setting(clap::AppSettings::InferSubcommands)
+                    .get_matches_from(args)
+            } else {
+                err.exit()
+            }
+        }
+    }
+}
+
 fn main() {
     init_env_logger();
 
-    if let Err(err) = run() {
-        let stderr = &mut io::stderr();
-        let errmsg = "Error writing to stderr";
-
-        write!(stderr, "error: {}", err).expect(errmsg);
+    let matches = get_matches();
+    let emit_options = emit_options(&matches);
+    if timings_enabled(&matches) {
+        timings::enable();
+    }
+    let result = run(&matches, &emit_options);
+    let stderr = &mut io::stderr();
+    let errmsg = "Error writing to stderr";
+    if let Some(timings) = timings::take() {
+        write!(stderr, "{}", timings).expect(errmsg);
+    }
+    if let Err(err) = result {
+        let message = match err.downcast_ref::<Error>() {
+            Some(err) => err.emit_string(&emit_options),
+            None => err.to_string(),
+        };
+        write!(stderr, "error: {}", message).expect(errmsg);
 
-        ::std::process::exit(1);
+        let code = err.downcast_ref::<Error>().map_or(1, exit_code);
+        ::std::process::exit(code);
     }
 }
 
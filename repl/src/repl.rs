@@ -1,17 +1,25 @@
+#[cfg(feature = "async")]
 extern crate futures_cpupool;
 extern crate rustyline;
 
-extern crate gluon_completion as completion;
-
 use std::error::Error as StdError;
+use std::fmt;
+use std::mem;
 use std::path::PathBuf;
 use std::sync::Mutex;
 
-use futures::{Future, Sink, Stream};
+use futures::Future;
+#[cfg(feature = "async")]
+use futures::{Sink, Stream};
+#[cfg(feature = "async")]
 use futures::sync::mpsc;
 
-use base::ast::{Expr, Pattern, SpannedPattern, Typed};
-use base::error::InFile;
+use self::rustyline::config::{Configurer, EditMode};
+
+use completion;
+
+use base::ast::{Expr, Pattern, RootExpr, SpannedPattern, Typed};
+use base::error::{EmitOptions, Errors, InFile};
 use base::kind::Kind;
 use base::pos;
 use base::symbol::{Symbol, SymbolModule};
@@ -26,8 +34,9 @@ use vm::internal::ValuePrinter;
 use vm::thread::{Context, RootStr, RootedValue, Thread, ThreadInternal};
 
 use gluon::{Compiler, Error as GluonError, Result as GluonResult, RootedThread};
-use gluon::import::add_extern_module;
-use gluon::compiler_pipeline::{Executable, ExecuteValue};
+use gluon::either::Either;
+use gluon::import::{add_extern_module, Import};
+use gluon::compiler_pipeline::{Executable, ExecuteValue, Typecheckable};
 
 fn type_of_expr(args: WithVM<RootStr>) -> IO<Result<String, String>> {
     let WithVM { vm, value: args } = args;
@@ -35,12 +44,28 @@ fn type_of_expr(args: WithVM<RootStr>) -> IO<Result<String, String>> {
     IO::Value(match compiler.typecheck_str(vm, "<repl>", &args, None) {
         Ok((expr, _)) => {
             let env = vm.get_env();
-            Ok(format!("{}", expr.env_type_of(&*env)))
+            Ok(format!("{}", expr.expr().env_type_of(&env)))
         }
         Err(msg) => Err(format!("{}", msg)),
     })
 }
 
+fn explain_type(args: WithVM<RootStr>) -> IO<Result<String, String>> {
+    use base::pos::{BytePos, Span};
+
+    let WithVM { vm, value: args } = args;
+    // Explain the whole expression, not counting any surrounding whitespace
+    let start = args.len() - args.trim_left().len();
+    let end = args.trim_right().len();
+    let span = Span::new(BytePos::from(start), BytePos::from(end));
+    let mut compiler = Compiler::new();
+    IO::Value(match compiler.explain_type(vm, "<repl>", &args, span) {
+        Ok(Some(explanation)) => Ok(explanation.to_string()),
+        Ok(None) => Err("Expected an expression".to_string()),
+        Err(msg) => Err(format!("{}", msg)),
+    })
+}
+
 fn find_kind(args: WithVM<RootStr>) -> IO<Result<String, String>> {
     let vm = args.vm;
     let args = args.value.trim();
@@ -94,6 +119,104 @@ fn find_info(args: WithVM<RootStr>) -> IO<Result<String, String>> {
     IO::Value(Ok(buffer))
 }
 
+fn find_instances(args: WithVM<RootStr>) -> IO<Result<String, String>> {
+    use std::fmt::Write;
+
+    use gluon::check::instances;
+
+    let vm = args.vm;
+    let args = args.value.trim();
+    let env = vm.get_env();
+    let mut globals = env.globals();
+    globals.sort_by(|l, r| l.0.cmp(r.0));
+
+    // Names without a module refer to a type exported by any of the loaded modules
+    // (`Functor` is found through `std.prelude`)
+    let alias = match env.find_type_info(args) {
+        Ok(alias) => alias.into_owned(),
+        Err(err) => {
+            let exported = if args.contains('.') {
+                None
+            } else {
+                globals
+                    .iter()
+                    .filter_map(|&(_, global)| {
+                        global
+                            .typ
+                            .type_field_iter()
+                            .find(|field| field.name.declared_name() == args)
+                    })
+                    .map(|field| field.typ.clone())
+                    .next()
+            };
+            match exported {
+                Some(alias) => alias,
+                None => return IO::Value(Err(format!("{}", err))),
+            }
+        }
+    };
+
+    let instances = instances::find_instances(
+        &env,
+        &alias,
+        globals
+            .iter()
+            .map(|&(name, global)| (name.to_string(), &global.typ)),
+    );
+    if instances.is_empty() {
+        return IO::Value(Ok(format!("No instances of `{}` were found", args)));
+    }
+    let mut buffer = String::new();
+    for instance in instances {
+        writeln!(buffer, "{} : {}", instance.name, instance.typ).unwrap();
+    }
+    IO::Value(Ok(buffer.trim_right().to_string()))
+}
+
+fn browse(args: WithVM<RootStr>) -> IO<Result<String, String>> {
+    use std::fmt::Write;
+
+    fn write_symbols(buffer: &mut String, symbols: &[completion::OutlineSymbol], indent: usize) {
+        for symbol in symbols {
+            match symbol.typ {
+                Either::Left(ref kind) => {
+                    writeln!(buffer, "{:3$}type {} : {}", "", symbol.name, kind, indent).unwrap()
+                }
+                Either::Right(ref typ) => {
+                    writeln!(buffer, "{:3$}{} : {}", "", symbol.name, typ, indent).unwrap()
+                }
+            }
+            write_symbols(buffer, &symbol.children, indent + 4);
+        }
+    }
+
+    let WithVM { vm, value: module } = args;
+    let module = module.trim();
+
+    let opt_macro = vm.get_macros().get("import");
+    let import = match opt_macro
+        .as_ref()
+        .and_then(|mac| mac.downcast_ref::<Import>())
+    {
+        Some(import) => import,
+        None => return IO::Value(Err("The import macro is not available".into())),
+    };
+    let source = match import.module_source(vm, module) {
+        Ok(source) => source,
+        Err(err) => return IO::Value(Err(format!("{}", err))),
+    };
+
+    let mut compiler = Compiler::new();
+    IO::Value(match compiler.typecheck_str(vm, module, &source, None) {
+        Ok((expr, _)) => {
+            let mut buffer = String::new();
+            write_symbols(&mut buffer, &completion::symbols(expr.expr()), 0);
+            Ok(buffer.trim_right().to_string())
+        }
+        Err(msg) => Err(format!("{}", msg)),
+    })
+}
+
 fn complete(thread: &Thread, name: &str, fileinput: &str, pos: usize) -> GluonResult<Vec<String>> {
     use base::pos::BytePos;
     use gluon::compiler_pipeline::*;
@@ -102,16 +225,19 @@ fn complete(thread: &Thread, name: &str, fileinput: &str, pos: usize) -> GluonRe
 
     // The parser may find parse errors but still produce an expression
     // For that case still typecheck the expression but return the parse error afterwards
-    let (mut expr, _parse_result): (_, GluonResult<()>) =
-        match compiler.parse_partial_expr(thread.global_env().type_cache(), &name, fileinput) {
-            Ok(expr) => (expr, Ok(())),
-            Err((None, err)) => return Err(err.into()),
-            Err((Some(expr), err)) => (expr, Err(err.into())),
-        };
+    let type_cache = thread.global_env().type_cache();
+    let parsed = RootExpr::try_new(|arena| {
+        compiler.parse_partial_expr(arena, type_cache, &name, fileinput)
+    });
+    let (mut expr, _parse_result): (_, GluonResult<()>) = match parsed {
+        Ok(expr) => (expr, Ok(())),
+        Err((None, err)) => return Err(err.into()),
+        Err((Some(expr), err)) => (expr, Err(err.into())),
+    };
 
     // Only need the typechecker to fill infer the types as best it can regardless of errors
     let _ = (&mut expr).typecheck(&mut compiler, thread, &name, fileinput);
-    let suggestions = completion::suggest(&*thread.get_env(), &expr, BytePos::from(pos));
+    let suggestions = completion::suggest(&thread.get_env(), expr.expr(), BytePos::from(pos));
     Ok(suggestions
         .into_iter()
         .map(|ident| {
@@ -124,7 +250,14 @@ fn complete(thread: &Thread, name: &str, fileinput: &str, pos: usize) -> GluonRe
 struct Completer(RootedThread);
 
 impl rustyline::completion::Completer for Completer {
-    fn complete(&self, line: &str, pos: usize) -> rustyline::Result<(usize, Vec<String>)> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
         let result = complete(&self.0, "<repl>", line, pos);
 
         // Get the start of the completed identifier
@@ -135,6 +268,16 @@ impl rustyline::completion::Completer for Completer {
     }
 }
 
+impl rustyline::hint::Hinter for Completer {
+    type Hint = String;
+}
+
+impl rustyline::highlight::Highlighter for Completer {}
+
+impl rustyline::validate::Validator for Completer {}
+
+impl rustyline::Helper for Completer {}
+
 macro_rules! impl_userdata {
     ($name : ident) => {
         impl ::gluon::vm::api::Userdata for $name {}
@@ -159,10 +302,105 @@ struct Editor(Mutex<rustyline::Editor<Completer>>);
 
 impl_userdata!{ Editor }
 
+#[cfg(feature = "async")]
 struct CpuPool(self::futures_cpupool::CpuPool);
 
+/// Without the `async` feature actions are run on the repl's thread and can't be interrupted
+#[cfg(not(feature = "async"))]
+struct CpuPool;
+
 impl_userdata!{ CpuPool }
 
+/// Options which can be changed while the repl is running using the `:set` command
+struct Options {
+    prompt: String,
+    /// The key bindings used to edit the input
+    keymap: EditMode,
+    emit: EmitOptions,
+    implicit_prelude: bool,
+    emit_debug_info: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            prompt: "> ".into(),
+            keymap: EditMode::Emacs,
+            emit: EmitOptions::default(),
+            implicit_prelude: true,
+            emit_debug_info: true,
+        }
+    }
+}
+
+impl fmt::Display for Options {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "prompt = {:?}", self.prompt)?;
+        let keymap = match self.keymap {
+            EditMode::Vi => "vi",
+            _ => "emacs",
+        };
+        writeln!(f, "keymap = {}", keymap)?;
+        writeln!(f, "color = {}", self.emit.color)?;
+        match self.emit.max_errors {
+            Some(max_errors) => writeln!(f, "max_errors = {}", max_errors)?,
+            None => writeln!(f, "max_errors = none")?,
+        }
+        writeln!(f, "implicit_prelude = {}", self.implicit_prelude)?;
+        write!(f, "emit_debug_info = {}", self.emit_debug_info)
+    }
+}
+
+impl Options {
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        fn parse_bool(value: &str) -> Result<bool, String> {
+            match value.trim() {
+                "true" | "on" => Ok(true),
+                "false" | "off" => Ok(false),
+                value => Err(format!("Expected `true` or `false`, found `{}`", value)),
+            }
+        }
+        match name {
+            "prompt" => self.prompt = value.to_string(),
+            "keymap" => {
+                self.keymap = match value.trim() {
+                    "emacs" => EditMode::Emacs,
+                    "vi" => EditMode::Vi,
+                    value => return Err(format!("Expected `emacs` or `vi`, found `{}`", value)),
+                }
+            }
+            "color" => self.emit.color = parse_bool(value)?,
+            "max_errors" => {
+                self.emit.max_errors = match value.trim() {
+                    "none" => None,
+                    value => Some(value.parse().map_err(|_| {
+                        format!("Expected a number or `none`, found `{}`", value)
+                    })?),
+                }
+            }
+            "implicit_prelude" => self.implicit_prelude = parse_bool(value)?,
+            "emit_debug_info" => self.emit_debug_info = parse_bool(value)?,
+            _ => return Err(format!("Unknown option `{}`", name)),
+        }
+        Ok(())
+    }
+
+    /// Applies the options which affect how the input is edited
+    fn configure_editor<C: Configurer>(&self, editor: &mut C) {
+        editor.set_edit_mode(self.keymap);
+    }
+
+    fn compiler(&self) -> Compiler {
+        Compiler::new()
+            .implicit_prelude(self.implicit_prelude)
+            .emit_debug_info(self.emit_debug_info)
+    }
+}
+
+struct Settings(Mutex<Options>);
+
+impl_userdata!{ Settings }
+
 #[derive(Serialize, Deserialize)]
 pub enum ReadlineError {
     Eof,
@@ -199,8 +437,18 @@ fn app_dir_root() -> Result<PathBuf, Box<StdError>> {
     )?)
 }
 
+/// The number of lines of history which are kept between sessions
+const HISTORY_MAX_LEN: usize = 1000;
+
 fn new_editor(vm: WithVM<()>) -> IO<Editor> {
-    let mut editor = rustyline::Editor::new();
+    // Bracketed paste lets a pasted block of several lines be evaluated as a whole instead of
+    // line by line
+    let config = rustyline::Config::builder()
+        .history_ignore_dups(true)
+        .max_history_size(HISTORY_MAX_LEN)
+        .bracketed_paste(true)
+        .build();
+    let mut editor = rustyline::Editor::with_config(config);
 
     let history_result =
         app_dir_root().and_then(|path| Ok(editor.load_history(&*path.join("history"))?));
@@ -208,13 +456,18 @@ fn new_editor(vm: WithVM<()>) -> IO<Editor> {
     if let Err(err) = history_result {
         warn!("Unable to load history: {}", err);
     }
-    editor.set_completer(Some(Completer(vm.vm.root_thread())));
+    editor.set_helper(Some(Completer(vm.vm.root_thread())));
     IO::Value(Editor(Mutex::new(editor)))
 }
 
-fn readline(editor: &Editor, prompt: &str) -> IO<Result<String, ReadlineError>> {
+fn readline(editor: &Editor, settings: &Settings) -> IO<Result<String, ReadlineError>> {
     let mut editor = editor.0.lock().unwrap();
-    let input = match editor.readline(prompt) {
+    let prompt = {
+        let options = settings.0.lock().unwrap();
+        options.configure_editor(&mut *editor);
+        options.prompt.clone()
+    };
+    let input = match editor.readline(&prompt) {
         Ok(input) => input,
         Err(rustyline::error::ReadlineError::Eof) => return IO::Value(Err(ReadlineError::Eof)),
         Err(rustyline::error::ReadlineError::Interrupted) => {
@@ -223,22 +476,52 @@ fn readline(editor: &Editor, prompt: &str) -> IO<Result<String, ReadlineError>>
         Err(err) => return IO::Exception(format!("{}", err)),
     };
     if !input.trim().is_empty() {
-        editor.add_history_entry(&input);
+        editor.add_history_entry(&*input);
     }
 
     IO::Value(Ok(input))
 }
 
+fn set_option(settings: &Settings, arg: &str) -> IO<Result<String, String>> {
+    let mut options = settings.0.lock().unwrap();
+    let arg = arg.trim_left();
+    if arg.trim().is_empty() {
+        return IO::Value(Ok(options.to_string()));
+    }
+    let (name, value) = match arg.find(char::is_whitespace) {
+        Some(i) => (&arg[..i], arg[i..].trim_left()),
+        None => (arg, ""),
+    };
+    IO::Value(
+        options
+            .set(name, value)
+            .map(|()| format!("{} has been set", name)),
+    )
+}
+
+#[cfg(feature = "async")]
 fn new_cpu_pool(size: usize) -> IO<CpuPool> {
     IO::Value(CpuPool(self::futures_cpupool::CpuPool::new(size)))
 }
 
-fn eval_line(WithVM { vm, value: line }: WithVM<&str>) -> PrimitiveFuture<IO<String>> {
-    eval_line_(vm.root_thread(), line)
-        .then(|result| {
+#[cfg(not(feature = "async"))]
+fn new_cpu_pool(_size: usize) -> IO<CpuPool> {
+    IO::Value(CpuPool)
+}
+
+fn eval_line(
+    WithVM { vm, value: settings }: WithVM<&Settings>,
+    line: &str,
+) -> PrimitiveFuture<IO<String>> {
+    let (compiler, emit_options) = {
+        let options = settings.0.lock().unwrap();
+        (options.compiler(), options.emit)
+    };
+    eval_line_(vm.root_thread(), compiler, line)
+        .then(move |result| {
             FutureValue::sync(Ok(match result {
                 Ok(x) => IO::Value(x),
-                Err(x) => IO::Exception(x.to_string()),
+                Err(x) => IO::Exception(x.emit_string(&emit_options)),
             }))
         })
         .boxed()
@@ -246,38 +529,70 @@ fn eval_line(WithVM { vm, value: line }: WithVM<&str>) -> PrimitiveFuture<IO<Str
 
 fn eval_line_(
     vm: RootedThread,
+    mut compiler: Compiler,
     line: &str,
 ) -> FutureValue<Box<Future<Item = String, Error = GluonError> + Send>> {
-    let mut compiler = Compiler::new();
-    let let_or_expr = {
+    // The pattern of the binding if `line` is a `let` binding instead of an expression
+    let mut unpack_pattern = None;
+    let eval_expr = {
         let mut module = SymbolModule::new("<line>".into(), compiler.mut_symbols());
-        match parse_partial_let_or_expr(&mut module, line) {
-            Ok(x) => x,
+        module.reset_fresh_symbols();
+        let result = RootExpr::try_new(|arena| {
+            let let_or_expr = match parse_partial_let_or_expr(arena, &mut module, line) {
+                Ok(let_or_expr) => let_or_expr,
+                Err((_, err)) => return Err((None, err)),
+            };
+            Ok(match let_or_expr {
+                Ok(expr) => expr,
+                Err(let_binding) => {
+                    let pattern = let_binding.name.clone();
+                    let expr = match pattern.value {
+                        Pattern::Ident(ref id) if !let_binding.args.is_empty() => {
+                            // We can't compile function bindings by only looking at
+                            // `let_binding.expr` so rewrite `let f x y = <expr>` into
+                            // `let f x y = <expr> in f`
+                            let id = pos::spanned2(0.into(), 0.into(), Expr::Ident(id.clone()));
+                            let expr = Expr::LetBindings(
+                                arena.alloc_extend(Some(let_binding)),
+                                arena.alloc(id),
+                            );
+                            pos::spanned2(0.into(), 0.into(), expr)
+                        }
+                        _ => let_binding.expr,
+                    };
+                    unpack_pattern = Some(pattern);
+                    expr
+                }
+            })
+        });
+        match result {
+            Ok(expr) => expr,
             Err((_, err)) => {
                 return FutureValue::sync(Err(InFile::new("<line>", line, err).into())).boxed()
             }
         }
     };
-    let future = match let_or_expr {
-        Ok(expr) => {
+    let mut eval_expr = match eval_expr.typecheck(&mut compiler, &vm, "<line>", line) {
+        Ok(eval_expr) => eval_expr,
+        Err(err) => return FutureValue::sync(Err(err)).boxed(),
+    };
+    // Warnings are printed before the value of the line
+    let warnings = mem::replace(&mut eval_expr.warnings, Errors::new());
+    let warnings = if warnings.has_errors() {
+        format!("{}\n", InFile::new("<line>", line, warnings))
+    } else {
+        String::new()
+    };
+    let future = match unpack_pattern {
+        None => {
             compiler = compiler.run_io(true);
-            expr.run_expr(&mut compiler, vm, "<line>", line, None)
+            eval_expr
+                .run_expr(&mut compiler, vm, "<line>", line, ())
                 .boxed()
         }
-        Err(let_binding) => {
-            let unpack_pattern = let_binding.name.clone();
-            let eval_expr = match unpack_pattern.value {
-                Pattern::Ident(ref id) if !let_binding.args.is_empty() => {
-                    // We can't compile function bindings by only looking at `let_binding.expr`
-                    // so rewrite `let f x y = <expr>` into `let f x y = <expr> in f`
-                    let id = pos::spanned2(0.into(), 0.into(), Expr::Ident(id.clone()));
-                    let expr = Expr::LetBindings(vec![let_binding], Box::new(id));
-                    pos::spanned2(0.into(), 0.into(), expr)
-                }
-                _ => let_binding.expr,
-            };
+        Some(unpack_pattern) => {
             eval_expr
-                .run_expr(&mut compiler, vm.clone(), "<line>", line, None)
+                .run_expr(&mut compiler, vm.clone(), "<line>", line, ())
                 .and_then(move |value| {
                     if let Err(err) =
                         set_globals(&vm, &unpack_pattern, &value.typ, &value.value.as_ref())
@@ -293,10 +608,11 @@ fn eval_line_(
         .map(move |ExecuteValue { value, typ, .. }| {
             let vm = value.vm();
             let env = vm.global_env().get_env();
-            ValuePrinter::new(&*env, &typ, value.get_variant())
+            let value = ValuePrinter::new(&env, &typ, value.get_variant())
                 .width(80)
                 .max_level(5)
-                .to_string()
+                .to_string();
+            warnings + &value
         })
         .boxed()
 }
@@ -358,6 +674,7 @@ fn set_globals(
     }
 }
 
+#[cfg(feature = "async")]
 fn finish_or_interrupt(
     cpu_pool: &CpuPool,
     thread: RootedThread,
@@ -402,6 +719,17 @@ fn finish_or_interrupt(
     ))
 }
 
+#[cfg(not(feature = "async"))]
+fn finish_or_interrupt(
+    _cpu_pool: &CpuPool,
+    thread: RootedThread,
+    action: OpaqueValue<&Thread, IO<Generic<A>>>,
+) -> FutureResult<Box<Future<Item = IO<Generic<A>>, Error = VMError> + Send>> {
+    let mut action =
+        OwnedFunction::<fn() -> IO<Generic<A>>>::from_value(&thread, action.get_variant());
+    FutureResult(action.call_async())
+}
+
 fn save_history(editor: &Editor) -> IO<()> {
     let history_result = app_dir_root().and_then(|path| {
         editor
@@ -416,7 +744,7 @@ fn save_history(editor: &Editor) -> IO<()> {
     });
 
     if let Err(err) = history_result {
-        warn!("Unable to load history: {}", err);
+        warn!("Unable to save history: {}", err);
     }
     IO::Value(())
 }
@@ -424,6 +752,7 @@ fn save_history(editor: &Editor) -> IO<()> {
 fn load_rustyline(vm: &Thread) -> vm::Result<vm::ExternModule> {
     vm.register_type::<Editor>("Editor", &[])?;
     vm.register_type::<CpuPool>("CpuPool", &[])?;
+    vm.register_type::<Settings>("Settings", &[])?;
 
     vm::ExternModule::new(
         vm,
@@ -440,9 +769,13 @@ fn load_repl(vm: &Thread) -> vm::Result<vm::ExternModule> {
         vm,
         record!(
             type_of_expr => primitive!(1 type_of_expr),
+            explain_type => primitive!(1 explain_type),
             find_info => primitive!(1 find_info),
             find_kind => primitive!(1 find_kind),
-            eval_line => primitive!(1 eval_line),
+            find_instances => primitive!(1 find_instances),
+            browse => primitive!(1 browse),
+            eval_line => primitive!(2 eval_line),
+            set_option => primitive!(2 set_option),
             finish_or_interrupt => primitive!(3 finish_or_interrupt),
             new_cpu_pool => primitive!(1 new_cpu_pool)
         ),
@@ -470,18 +803,30 @@ fn compile_repl(vm: &Thread) -> Result<(), Box<StdError + Send + Sync>> {
 }
 
 #[allow(dead_code)]
-pub fn run() -> Result<(), Box<StdError + Send + Sync>> {
+pub fn run(emit_options: &EmitOptions) -> Result<(), Box<StdError + Send + Sync>> {
+    #[cfg(feature = "async")]
     let mut core = ::tokio_core::reactor::Core::new()?;
 
+    #[cfg(feature = "async")]
     let vm = ::gluon::VmBuilder::new()
         .event_loop(Some(core.remote()))
         .build();
+    #[cfg(not(feature = "async"))]
+    let vm = ::gluon::VmBuilder::new().build();
 
     compile_repl(&vm)?;
 
-    let mut repl: OwnedFunction<fn(()) -> IO<()>> = vm.get_global("repl")?;
+    let settings = Settings(Mutex::new(Options {
+        emit: *emit_options,
+        ..Options::default()
+    }));
+
+    let mut repl: OwnedFunction<fn(Settings) -> IO<()>> = vm.get_global("repl")?;
     debug!("Starting repl");
-    core.run(repl.call_async(()))?;
+    #[cfg(feature = "async")]
+    core.run(repl.call_async(settings))?;
+    #[cfg(not(feature = "async"))]
+    repl.call_async(settings).wait()?;
 
     Ok(())
 }
@@ -510,7 +855,7 @@ mod tests {
         let _ = ::env_logger::init();
         let vm = new_vm();
         compile_repl(&vm).unwrap_or_else(|err| panic!("{}", err));
-        let repl: Result<FunctionRef<fn(()) -> IO<()>>, _> = vm.get_global("repl");
+        let repl: Result<FunctionRef<fn(Settings) -> IO<()>>, _> = vm.get_global("repl");
         assert!(repl.is_ok(), "{}", repl.err().unwrap());
     }
 
@@ -525,6 +870,18 @@ mod tests {
         assert_eq!(type_of.call("123"), Ok(IO::Value(Ok("Int".into()))));
     }
 
+    #[test]
+    fn explain_type() {
+        let _ = ::env_logger::init();
+        let vm = new_vm();
+        compile_repl(&vm).unwrap_or_else(|err| panic!("{}", err));
+        let mut explain: FunctionRef<QueryFn> = vm.get_global("repl.prim.explain_type").unwrap();
+        match explain.call(r" \x -> x #Int+ 1 ") {
+            Ok(IO::Value(Ok(ref explanation))) if explanation.contains("`Int -> Int`") => (),
+            x => assert!(false, "{:?}", x),
+        }
+    }
+
     #[test]
     fn find_kind() {
         let _ = ::env_logger::init();
@@ -557,6 +914,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn find_instances() {
+        let _ = ::env_logger::init();
+        let vm = new_vm();
+        compile_repl(&vm).unwrap_or_else(|err| panic!("{}", err));
+        let mut find_instances: FunctionRef<QueryFn> =
+            vm.get_global("repl.prim.find_instances").unwrap();
+        // Types are displayed with the full names of their aliases
+        let option_functor = "std.option.functor : std.prelude.Functor std.types.Option";
+        match find_instances.call("Functor") {
+            Ok(IO::Value(Ok(ref instances)))
+                if instances.contains(option_functor) && !instances.contains("std.types :") =>
+            {
+                ()
+            }
+            x => assert!(false, "{:?}", x),
+        }
+        match find_instances.call("std.prelude.Functor") {
+            Ok(IO::Value(Ok(ref instances))) if instances.contains(option_functor) => (),
+            x => assert!(false, "{:?}", x),
+        }
+        match find_instances.call("NotAType") {
+            Ok(IO::Value(Err(_))) => (),
+            x => assert!(false, "{:?}", x),
+        }
+    }
+
+    #[test]
+    fn browse() {
+        let _ = ::env_logger::init();
+        let vm = new_vm();
+        compile_repl(&vm).unwrap_or_else(|err| panic!("{}", err));
+        let mut browse: FunctionRef<QueryFn> = vm.get_global("repl.prim.browse").unwrap();
+        match browse.call("std.option") {
+            Ok(IO::Value(Ok(ref outline))) if outline.contains("type Option") => (),
+            x => assert!(false, "{:?}", x),
+        }
+    }
+
+    #[test]
+    fn set_option() {
+        let settings = Settings(Mutex::new(Options::default()));
+        assert_eq!(
+            super::set_option(&settings, "implicit_prelude false"),
+            IO::Value(Ok("implicit_prelude has been set".into()))
+        );
+        assert_eq!(
+            super::set_option(&settings, " prompt gluon> "),
+            IO::Value(Ok("prompt has been set".into()))
+        );
+        assert!(match super::set_option(&settings, "emit_debug_info 1") {
+            IO::Value(Err(_)) => true,
+            _ => false,
+        });
+        assert_eq!(
+            super::set_option(&settings, "max_errors 10"),
+            IO::Value(Ok("max_errors has been set".into()))
+        );
+
+        let options = settings.0.lock().unwrap();
+        assert_eq!(options.prompt, "gluon> ");
+        assert!(!options.implicit_prelude);
+        assert!(options.emit_debug_info);
+        assert_eq!(options.emit.max_errors, Some(10));
+    }
+
+    #[test]
+    fn set_keymap() {
+        let settings = Settings(Mutex::new(Options::default()));
+        assert_eq!(
+            super::set_option(&settings, "keymap vi"),
+            IO::Value(Ok("keymap has been set".into()))
+        );
+        assert!(match super::set_option(&settings, "keymap nano") {
+            IO::Value(Err(_)) => true,
+            _ => false,
+        });
+
+        let options = settings.0.lock().unwrap();
+        assert!(options.to_string().contains("keymap = vi"));
+        let mut config = rustyline::Config::builder();
+        options.configure_editor(&mut config);
+        assert_eq!(config.build().edit_mode(), EditMode::Vi);
+    }
+
     #[test]
     fn complete_repl_empty() {
         let _ = ::env_logger::init();
@@ -0,0 +1,394 @@
+//! Implementation of the `test` subcommand which runs the bindings marked with `#[test]`
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use base::ast::{walk_expr, Arena, Expr, ExprField, Literal, Pattern, RootExpr, SpannedExpr,
+                Visitor};
+use base::error::EmitOptions;
+use base::filename_to_module;
+use base::pos::{self, BytePos, Span};
+use base::resolve::remove_aliases;
+use base::source::Source;
+use base::symbol::Symbol;
+use base::types::{ArcType, Type};
+
+use gluon::check::check_signature;
+use gluon::compiler_pipeline::{Executable, ExecuteValue};
+use gluon::io::capture_output;
+use gluon::vm::api::{FunctionRef, Getable, ValueRef, VmType, IO};
+use gluon::vm::thread::{RootedValue, ThreadInternal};
+use gluon::vm::Variants;
+use gluon::{Compiler, Result, Thread};
+
+/// A top level binding marked with `#[test]`
+struct TestBinding {
+    name: Symbol,
+    span: Span<BytePos>,
+}
+
+/// The result of running a single test
+struct TestResult {
+    name: String,
+    location: String,
+    /// The messages of each failed assertion
+    result: ::std::result::Result<(), Vec<String>>,
+    /// The output which the test printed
+    output: String,
+}
+
+/// A failed expectation from `std.test.expect` which `--bless` can update
+struct Expectation {
+    /// The span of the expected string literal
+    span: Span<BytePos>,
+    /// The string literal which replaces the expected string
+    actual: String,
+}
+
+impl Expectation {
+    /// Parses the message written by `std.test.expect.expect_at`
+    fn from_message(message: &str) -> Option<Expectation> {
+        let mut lines = message.lines();
+        let header = lines.next()?;
+        if !header.starts_with("Expectation failed at line ") {
+            return None;
+        }
+        let start = header.find("(bytes ")? + "(bytes ".len();
+        let mut bounds = header[start..header.rfind(')')?].splitn(2, "..");
+        let start = bounds.next()?.parse::<usize>().ok()?;
+        let end = bounds.next()?.parse::<usize>().ok()?;
+        let actual = lines
+            .map(str::trim_left)
+            .find(|line| line.starts_with("actual: "))?;
+        Some(Expectation {
+            span: Span::new(BytePos::from(start), BytePos::from(end)),
+            actual: actual["actual: ".len()..].to_string(),
+        })
+    }
+}
+
+/// Collects the spans of the string literals in an expression, as only those are rewritten by
+/// `--bless`
+struct StringLiterals(Vec<Span<BytePos>>);
+
+impl<'a, 'ast> Visitor<'a, 'ast> for StringLiterals {
+    type Ident = Symbol;
+
+    fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+        if let Expr::Literal(Literal::String(_)) = expr.value {
+            self.0.push(expr.span);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// Collects the top level bindings of `expr` which are marked with `#[test]` and replaces the
+/// value of the module with a record containing each test
+fn collect_tests<'ast>(
+    arena: &'ast Arena<'ast, Symbol>,
+    expr: &mut SpannedExpr<'ast, Symbol>,
+    tests: &mut Vec<TestBinding>,
+) {
+    match expr.value {
+        Expr::LetBindings(ref binds, ref mut body) => {
+            tests.extend(
+                binds
+                    .iter()
+                    .filter(|bind| bind.attribute("test").is_some())
+                    .filter_map(|bind| match bind.name.value {
+                        Pattern::Ident(ref id) => Some(TestBinding {
+                            name: id.name.clone(),
+                            span: bind.name.span,
+                        }),
+                        _ => None,
+                    }),
+            );
+            return collect_tests(arena, body, tests);
+        }
+        Expr::TypeBindings(_, ref mut body) => return collect_tests(arena, body, tests),
+        _ => (),
+    }
+    let span = expr.span;
+    expr.value = Expr::Record {
+        typ: Type::hole(),
+        types: &mut [],
+        exprs: arena.alloc_extend(tests.iter().map(|test| ExprField {
+            comment: None,
+            comments: Vec::new(),
+            name: pos::spanned(span, test.name.clone()),
+            value: None,
+            strict: false,
+        })),
+        base: None,
+    };
+}
+
+/// Returns the messages of the failed assertions written to a `Test a` value
+fn failed_assertions(writer: Variants) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut list = writer.as_ref();
+    // `List String` is either `Nil` or `Cons String (List String)`
+    while let ValueRef::Data(data) = list {
+        match (data.get(0), data.get_variant(1)) {
+            (Some(ValueRef::String(message)), Some(rest)) => {
+                messages.push(message.to_string());
+                list = rest.as_ref();
+            }
+            _ => break,
+        }
+    }
+    messages
+}
+
+/// Runs a test of type `() -> ()` or `IO ()`, or checks that a test of type `Test a` did not fail
+/// any assertions. The output printed by the test is returned as well.
+fn run_test(
+    vm: &Thread,
+    typ: &ArcType,
+    value: RootedValue<&Thread>,
+) -> (::std::result::Result<(), Vec<String>>, String) {
+    let typ = remove_aliases(&vm.get_env(), typ.remove_forall().clone());
+    let is_io = check_signature(&vm.get_env(), &typ, &IO::<()>::make_type(vm));
+    let is_function = typ.as_function()
+        .map_or(false, |(arg, ret)| *arg == Type::unit() && *ret == Type::unit());
+    if is_io || is_function {
+        // Run each test on its own thread so that a failing test does not affect the others
+        let thread = match vm.new_thread() {
+            Ok(thread) => thread,
+            Err(err) => return (Err(vec![err.to_string()]), String::new()),
+        };
+        let (result, output) = capture_output(|| {
+            if is_io {
+                thread
+                    .execute_io(value.get_value())
+                    .sync_or_error()
+                    .map(|_| ())
+            } else {
+                let mut test: FunctionRef<fn(()) -> ()> =
+                    FunctionRef::from_value(&thread, value.get_variant());
+                test.call(())
+            }
+        });
+        return (result.map_err(|err| vec![err.to_string()]), output);
+    }
+    let has_value = typ.row_iter()
+        .any(|field| field.name.declared_name() == "value");
+    let writer = typ.row_iter()
+        .position(|field| field.name.declared_name() == "writer")
+        .and_then(|index| if has_value { value.get(index) } else { None });
+    let result = match writer {
+        Some(writer) => {
+            let messages = failed_assertions(writer.get_variant());
+            if messages.is_empty() {
+                Ok(())
+            } else {
+                Err(messages)
+            }
+        }
+        None => Err(vec![format!(
+            "Expected a test of type `() -> ()`, `IO ()` or `Test a`, found `{}`",
+            typ
+        )]),
+    };
+    (result, String::new())
+}
+
+/// Replaces the expected strings of the failed expectations in `results` with the actual strings,
+/// writing the updated source to `path`. The expectations are removed from the failures and the
+/// number of updated strings is returned.
+fn bless(
+    path: &Path,
+    source: &str,
+    string_literals: &[Span<BytePos>],
+    results: &mut [TestResult],
+) -> io::Result<usize> {
+    // Ordered by position so that the updated source can be built in one pass
+    let mut edits = BTreeMap::new();
+    for test in results.iter_mut() {
+        let remaining = match test.result {
+            Ok(()) => continue,
+            Err(ref mut messages) => {
+                messages.retain(|message| {
+                    let expectation = Expectation::from_message(message).and_then(|expectation| {
+                        if string_literals.contains(&expectation.span) {
+                            Some(expectation)
+                        } else {
+                            None
+                        }
+                    });
+                    match expectation {
+                        Some(expectation) => {
+                            edits
+                                .entry(expectation.span.start)
+                                .or_insert((expectation.span.end, expectation.actual));
+                            false
+                        }
+                        None => true,
+                    }
+                });
+                messages.len()
+            }
+        };
+        if remaining == 0 {
+            test.result = Ok(());
+        }
+    }
+    if edits.is_empty() {
+        return Ok(0);
+    }
+
+    let mut updated = String::with_capacity(source.len());
+    let mut previous_end = 0;
+    for (start, &(end, ref actual)) in &edits {
+        updated.push_str(&source[previous_end..start.to_usize()]);
+        updated.push_str(actual);
+        previous_end = end.to_usize();
+    }
+    updated.push_str(&source[previous_end..]);
+    File::create(path)?.write_all(updated.as_bytes())?;
+    Ok(edits.len())
+}
+
+/// Runs the tests in the file at `path`, returning the result of each test and the number of
+/// expectations which were updated by `--bless`. Errors are returned if the file could not be
+/// compiled.
+fn run_file(
+    vm: &Thread,
+    path: &Path,
+    bless_expectations: bool,
+) -> Result<(Vec<TestResult>, usize)> {
+    let mut source = String::new();
+    File::open(path)?.read_to_string(&mut source)?;
+    let module = filename_to_module(&path.to_string_lossy());
+
+    let mut compiler = Compiler::new();
+    let type_cache = vm.global_env().type_cache();
+    let mut expr = RootExpr::try_new(|arena| {
+        compiler.parse_partial_expr(arena, type_cache, &module, &source)
+    }).map_err(|(_, err)| err)?;
+    let mut string_literals = StringLiterals(Vec::new());
+    string_literals.visit_expr(expr.expr());
+    let mut tests = Vec::new();
+    expr.with_mut(|arena, expr| collect_tests(arena, expr, &mut tests));
+
+    let ExecuteValue { typ, value, .. } = expr.run_expr(&mut compiler, vm, &module, &source, None)
+        .sync_or_error()?;
+
+    let record = remove_aliases(&vm.get_env(), typ);
+    let lines = Source::new(&source);
+    let mut results = tests
+        .iter()
+        .map(|test| {
+            let (result, output) = record
+                .row_iter()
+                .enumerate()
+                .find(|&(_, field)| field.name.declared_name() == test.name.declared_name())
+                .and_then(|(index, field)| {
+                    value.get(index).map(|value| run_test(vm, &field.typ, value))
+                })
+                .unwrap_or_else(|| {
+                    (
+                        Err(vec!["Test was not found in the module".to_string()]),
+                        String::new(),
+                    )
+                });
+            let location = lines.location(test.span.start).map_or(
+                path.display().to_string(),
+                |location| {
+                    format!(
+                        "{}:{}:{}",
+                        path.display(),
+                        location.line.to_usize() + 1,
+                        location.column.to_usize() + 1
+                    )
+                },
+            );
+            TestResult {
+                name: test.name.declared_name().to_string(),
+                location,
+                result,
+                output,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let blessed = if bless_expectations {
+        bless(path, &source, &string_literals.0, &mut results)?
+    } else {
+        0
+    };
+    Ok((results, blessed))
+}
+
+/// Runs the tests in each file, printing the result of each test to stdout. Returns the number
+/// of tests which failed, counting each file which failed to compile as a failed test. If
+/// `bless_expectations` is set, failed expectations are updated in the source instead of failing.
+pub fn run_files(
+    vm: &Thread,
+    files: &[PathBuf],
+    bless_expectations: bool,
+    emit_options: &EmitOptions,
+) -> usize {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let errmsg = "Error writing to stdout";
+
+    let mut passed = 0;
+    let mut failures = Vec::new();
+    for file in files {
+        match run_file(vm, file, bless_expectations) {
+            Ok((results, blessed)) => {
+                writeln!(
+                    stdout,
+                    "running {} test(s) in {}",
+                    results.len(),
+                    file.display()
+                ).expect(errmsg);
+                for test in results {
+                    let status = if test.result.is_ok() { "ok" } else { "FAILED" };
+                    writeln!(stdout, "test {} ... {}", test.name, status).expect(errmsg);
+                    match test.result {
+                        Ok(()) => passed += 1,
+                        Err(messages) => {
+                            // Only the output of the failed tests is shown
+                            let mut message = messages.join("\n");
+                            if !test.output.is_empty() {
+                                message.push_str("\n\n---- output ----\n");
+                                message.push_str(&test.output);
+                            }
+                            failures.push((format!("{} ({})", test.name, test.location), message))
+                        }
+                    }
+                }
+                if blessed != 0 {
+                    writeln!(
+                        stdout,
+                        "updated {} expectation(s) in {}",
+                        blessed,
+                        file.display()
+                    ).expect(errmsg);
+                }
+            }
+            Err(err) => failures.push((
+                file.display().to_string(),
+                err.emit_string(emit_options),
+            )),
+        }
+    }
+
+    if !failures.is_empty() {
+        writeln!(stdout, "\nfailures:").expect(errmsg);
+        for &(ref name, ref message) in &failures {
+            writeln!(stdout, "\n---- {} ----\n{}", name, message).expect(errmsg);
+        }
+    }
+    writeln!(
+        stdout,
+        "\ntest result: {}. {} passed; {} failed",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        passed,
+        failures.len()
+    ).expect(errmsg);
+    failures.len()
+}
@@ -34,6 +34,169 @@ fn fmt_repl() {
     assert_eq!(before, after);
 }
 
+#[test]
+fn check_watch() {
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::thread;
+    use std::time::Duration;
+
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let project = env::temp_dir().join("gluon_check_watch");
+    let _ = fs::remove_dir_all(&project);
+    fs::create_dir_all(&project).unwrap();
+    let write = |file: &str, source: &str| {
+        File::create(project.join(file))
+            .unwrap()
+            .write_all(source.as_bytes())
+            .unwrap();
+    };
+    write("lib.glu", "let x = 1\n{ x }\n");
+    write("main.glu", "let lib = import! gluon_check_watch.lib\nlib.x #Int+ 1\n");
+
+    let mut child = Command::new(&*gluon_path)
+        .args(&["check", "--watch", "gluon_check_watch"])
+        .current_dir(env::temp_dir())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut next_line = || lines.next().expect("line").unwrap();
+
+    assert_eq!(next_line(), "Checked 2 file(s): 0 with errors, 2 ok");
+
+    // Wait so that the modification time is guaranteed to differ on coarse file systems
+    thread::sleep(Duration::from_millis(1100));
+    // Changing `lib` breaks `main` which must be rechecked along with it
+    write("lib.glu", "let x = \"\"\n{ x }\n");
+    assert_eq!(next_line(), "Checked 2 file(s): 1 with errors, 1 ok");
+
+    thread::sleep(Duration::from_millis(1100));
+    write("main.glu", "let lib = import! gluon_check_watch.lib\nlib.x\n");
+    assert_eq!(next_line(), "Checked 1 file(s): 0 with errors, 1 ok");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn run_prints_value() {
+    use std::io::Write;
+
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let program = env::temp_dir().join("gluon_run_prints_value.glu");
+    File::create(&program)
+        .unwrap()
+        .write_all(b"let x = 1 #Int+ 2\n{ x, y = \"abc\" }\n")
+        .unwrap();
+
+    let output = Command::new(&*gluon_path)
+        .arg("run")
+        .arg(&program)
+        .output()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    let stdout = String::from_utf8(output.stdout.clone()).unwrap();
+    assert!(stdout.contains("x: 3"), "{}", stdout);
+    assert!(stdout.contains("y: \"abc\""), "{}", stdout);
+
+    // The value is printed the same way as when the program is read from stdin
+    let mut child = Command::new(&*gluon_path)
+        .args(&["run", "--stdin"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    child
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all(b"let x = 1 #Int+ 2\n{ x, y = \"abc\" }\n")
+        .unwrap();
+    let stdin_output = child.wait_with_output().unwrap();
+    assert_eq!(stdin_output.stdout, output.stdout);
+}
+
+#[test]
+fn run_prints_warnings() {
+    use std::io::Write;
+
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let program = env::temp_dir().join("gluon_run_prints_warnings.glu");
+    File::create(&program)
+        .unwrap()
+        .write_all(b"#[deprecated]\nlet old = 1\nlet unused = old\n2\n")
+        .unwrap();
+
+    let output = Command::new(&*gluon_path)
+        .arg("run")
+        .arg(&program)
+        .output()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("Unused binding `unused`"), "{}", stderr);
+    assert!(stderr.contains("`old` is deprecated"), "{}", stderr);
+}
+
+#[test]
+fn fmt_check() {
+    use std::io::Write;
+
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let file = env::temp_dir().join("gluon_fmt_check.glu");
+    let check = |source: &str| {
+        File::create(&file)
+            .unwrap()
+            .write_all(source.as_bytes())
+            .unwrap();
+        let output = Command::new(&*gluon_path)
+            .arg("fmt")
+            .arg("--check")
+            .arg(&file)
+            .output()
+            .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+
+        let mut after = String::new();
+        File::open(&file)
+            .unwrap()
+            .read_to_string(&mut after)
+            .unwrap();
+        assert_eq!(after, source);
+        output
+    };
+
+    let output = check("let   x = 1\nx\n");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("gluon_fmt_check.glu is not formatted"), "{}", stdout);
+
+    let output = check("let x = 1\nx\n");
+    assert!(output.status.success());
+}
+
 #[test]
 fn issue_365_run_io_from_command_line() {
     let path = env::args().next().unwrap();
@@ -53,3 +216,503 @@ fn issue_365_run_io_from_command_line() {
     assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
     assert_eq!(String::from_utf8(output.stdout).unwrap(), "123\n");
 }
+
+#[test]
+fn run_unit_tests() {
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let output = Command::new(&*gluon_path)
+        .args(&["test", "tests/unit_test.glu"])
+        .output()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("test addition ... ok\n"), "{}", stdout);
+    assert!(stdout.contains("test assertions ... ok\n"), "{}", stdout);
+    assert!(stdout.contains("test failed_assertion ... FAILED\n"), "{}", stdout);
+    assert!(stdout.contains("test failed_assert ... FAILED\n"), "{}", stdout);
+    assert!(stdout.contains("test printing ... ok\n"), "{}", stdout);
+    assert!(stdout.contains("test failed_printing ... FAILED\n"), "{}", stdout);
+    assert!(
+        stdout.contains("---- failed_assertion (tests/unit_test.glu:13:5) ----\nAssertion failed: 1 != 2\n"),
+        "{}",
+        stdout
+    );
+    // Only the output of failed tests is shown
+    assert!(!stdout.contains("printed by a passing test"), "{}", stdout);
+    assert!(
+        stdout.contains("---- output ----\nprinted by a failing test\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("test result: FAILED. 3 passed; 3 failed"), "{}", stdout);
+}
+
+#[test]
+fn run_file_named_like_a_subcommand() {
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    // `tests/...` is close enough to `test` to be suggested as a misspelling of it
+    let output = Command::new(&*gluon_path)
+        .arg("tests/unit_test.glu")
+        .output()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+}
+
+#[test]
+fn bless_expectations() {
+    use std::io::Write;
+
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let test_file = env::temp_dir().join("gluon_bless_expectations.glu");
+    let source = r#"let int = import! std.int
+let string = import! std.string
+
+#[test]
+let int_expectation = expect! int.show 12 "13"
+
+#[test]
+let string_expectation = expect! string.show "a\"b\nc" "abc"
+
+{}
+"#;
+    File::create(&test_file)
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+
+    let output = Command::new(&*gluon_path)
+        .arg("test")
+        .arg(&test_file)
+        .output()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    assert!(!output.status.success());
+
+    let output = Command::new(&*gluon_path)
+        .arg("test")
+        .arg("--bless")
+        .arg(&test_file)
+        .output()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("updated 2 expectation(s)"), "{}", stdout);
+
+    let mut blessed = String::new();
+    File::open(&test_file)
+        .unwrap()
+        .read_to_string(&mut blessed)
+        .unwrap();
+    assert_eq!(
+        blessed,
+        source
+            .replace(r#"12 "13""#, r#"12 "12""#)
+            .replace(r#""abc""#, r#""a\"b\nc""#)
+    );
+}
+
+#[test]
+fn timings() {
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let output = Command::new(&*gluon_path)
+        .args(&["check", "--timings", "tests/unit_test.glu"])
+        .output()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("\ntests.unit_test\n    lexing "), "{}", stderr);
+    assert!(stderr.contains("\nstd.prelude\n"), "{}", stderr);
+    for phase in &["parsing", "macro expansion", "typechecking", "renaming"] {
+        assert!(stderr.contains(&format!("\n    {} ", phase)), "{}", stderr);
+    }
+    assert!(stderr.contains("\ntotal\n"), "{}", stderr);
+    // The executable counts allocations so the peak memory usage is always known
+    assert!(!stderr.contains(" -\n"), "{}", stderr);
+}
+
+#[test]
+fn check_directory_graph() {
+    use std::fs;
+    use std::io::Write;
+
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let project = env::temp_dir().join("gluon_check_graph");
+    fs::create_dir_all(&project).unwrap();
+    // Checking must not run `lib` or its error would be reported
+    File::create(project.join("lib.glu"))
+        .unwrap()
+        .write_all(b"let x : Int = error \"executed\"\n{ x }\n")
+        .unwrap();
+    File::create(project.join("main.glu"))
+        .unwrap()
+        .write_all(b"let lib = import! gluon_check_graph.lib\nlib.x #Int+ 1\n")
+        .unwrap();
+
+    let output = Command::new(&*gluon_path)
+        .args(&["check", "--graph", "gluon_check_graph"])
+        .current_dir(env::temp_dir())
+        .output()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("digraph imports {\n"), "{}", stdout);
+    assert!(stdout.contains("    \"gluon_check_graph.lib\";\n"), "{}", stdout);
+    assert!(
+        stdout.contains("    \"gluon_check_graph.main\" -> \"gluon_check_graph.lib\";\n"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn doc() {
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let output_dir = env::temp_dir().join("gluon_doc");
+    let run = |format: &str| {
+        let output = Command::new(&*gluon_path)
+            .args(&["doc", "--format", format, "-o"])
+            .arg(&output_dir)
+            .arg("../std/option.glu")
+            .output()
+            .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(output.status.success(), "{}", stderr);
+    };
+    let read = |file: &str| {
+        let mut contents = String::new();
+        File::open(output_dir.join(file))
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        contents
+    };
+
+    run("json");
+    let json = read("index.json");
+    assert!(json.contains(r#""name": "Option""#), "{}", json);
+    assert!(json.contains(r#""type": "Option a = | None | Some a""#), "{}", json);
+    assert!(
+        json.contains(r#""comment": "`Option` represents a value which may not exist.""#),
+        "{}",
+        json
+    );
+
+    run("html");
+    let index = read("index.html");
+    let module = index
+        .split("<a href=\"")
+        .nth(1)
+        .and_then(|link| link.split('"').next())
+        .expect("link to the module")
+        .to_string();
+    let html = read(&module);
+    assert!(html.contains("<pre><code>type Option a = | None | Some a</code></pre>"), "{}", html);
+    assert!(html.contains("<p>`Option` represents a value which may not exist.</p>"), "{}", html);
+    assert!(html.contains(&format!("href=\"src/{}#L8\"", module)), "{}", html);
+    let source = read(&format!("src/{}", module));
+    assert!(source.contains("<span id=\"L8\">"), "{}", source);
+}
+
+/// Sends each message in `input` to a language server and returns everything that it wrote to
+/// stdout
+#[test]
+fn doc_tests() {
+    use std::io::Write;
+
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let test_file = env::temp_dir().join("gluon_doc_tests.glu");
+    let source = r#"/// Adds one to an `Int`
+///
+/// ```
+/// let { succ } = import! gluon_doc_tests
+/// succ 1
+/// ```
+let succ x : Int -> Int = x #Int+ 1
+
+/// ```gluon
+/// let { pred } = import! gluon_doc_tests
+/// pred "1"
+/// ```
+///
+/// ```rust
+/// not gluon code
+/// ```
+let pred x : Int -> Int = x #Int- 1
+
+{ succ, pred }
+"#;
+    File::create(&test_file)
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+
+    let output = Command::new(&*gluon_path)
+        .args(&["doc", "--test"])
+        .arg(&test_file)
+        .current_dir(env::temp_dir())
+        .output()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!output.status.success(), "{}", stdout);
+    assert!(stdout.contains("running 2 doctest(s)"), "{}", stdout);
+    assert!(stdout.contains("test succ (0) ... ok\n"), "{}", stdout);
+    assert!(stdout.contains("test pred (0) ... FAILED\n"), "{}", stdout);
+    assert!(
+        stdout.contains(&format!("---- pred (0) ({}:17:5) ----", test_file.display())),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("test result: FAILED. 1 passed; 1 failed"), "{}", stdout);
+}
+
+fn run_language_server(input: &[&str]) -> String {
+    use std::io::Write;
+
+    fn message(content: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", content.len(), content)
+    }
+
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let mut child = Command::new(&*gluon_path)
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    {
+        let stdin = child.stdin.as_mut().unwrap();
+        for content in input {
+            stdin.write_all(message(content).as_bytes()).unwrap();
+        }
+    }
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn language_server() {
+    let stdout = run_language_server(&[
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+        r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///test.glu","text":"let inc x = x #Int+ 1\ninc \"\"\n"}}}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///test.glu"},"position":{"line":1,"character":1}}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///test.glu"},"position":{"line":1,"character":1}}}"#,
+        r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///warnings.glu","text":"let unused = 1\n2\n"}}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"shutdown"}"#,
+        r#"{"jsonrpc":"2.0","method":"exit"}"#,
+    ]);
+
+    assert!(stdout.contains(r#""hoverProvider":true"#), "{}", stdout);
+    assert!(
+        stdout.contains(r#""range":{"end":{"character":6,"line":1},"start":{"character":4,"line":1}}"#),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(r#""value":"```gluon\nInt -> Int\n```""#), "{}", stdout);
+    assert!(
+        stdout.contains(r#""id":3,"jsonrpc":"2.0","result":{"range":{"end":{"character":7,"line":0},"start":{"character":4,"line":0}}"#),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(r#""message":"Unused binding `unused`","range":{"end":{"character":10,"line":0},"start":{"character":4,"line":0}},"severity":2"#),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(r#"{"id":4,"jsonrpc":"2.0","result":null}"#), "{}", stdout);
+}
+
+#[test]
+fn language_server_workspace_symbols() {
+    use std::fs;
+    use std::io::Write;
+
+    let root = env::temp_dir().join("gluon_language_server_workspace_symbols");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let text = "let area x = x #Int* x\n{ area }\n";
+    File::create(root.join("shapes.glu"))
+        .unwrap()
+        .write_all(text.as_bytes())
+        .unwrap();
+
+    let initialize = format!(
+        r#"{{"jsonrpc":"2.0","id":1,"method":"initialize","params":{{"rootUri":"file://{}"}}}}"#,
+        root.display()
+    );
+    let did_open = format!(
+        r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file://{}","text":{:?}}}}}}}"#,
+        root.join("shapes.glu").display(),
+        text
+    );
+    run_language_server(&[
+        &initialize,
+        &did_open,
+        r#"{"jsonrpc":"2.0","method":"exit"}"#,
+    ]);
+
+    // The symbols are found from the index on disk without opening the document again
+    let stdout = run_language_server(&[
+        &initialize,
+        r#"{"jsonrpc":"2.0","id":2,"method":"workspace/symbol","params":{"query":"ara"}}"#,
+        r#"{"jsonrpc":"2.0","method":"exit"}"#,
+    ]);
+    assert!(stdout.contains(r#""workspaceSymbolProvider":true"#), "{}", stdout);
+    assert!(
+        stdout.contains(r#""kind":12,"location":{"range":{"end":{"character":8,"line":0},"start":{"character":4,"line":0}}"#),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains(r#""name":"area""#), "{}", stdout);
+}
+
+#[test]
+fn debug_adapter() {
+    use std::io::Write;
+
+    fn message(content: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", content.len(), content)
+    }
+
+    let program = env::temp_dir().join("gluon_debug_adapter.glu");
+    File::create(&program)
+        .unwrap()
+        .write_all(b"let add x y =\n    let sum = x #Int+ y\n    sum\nadd 1 2\n")
+        .unwrap();
+
+    let launch = format!(
+        r#"{{"seq":2,"type":"request","command":"launch","arguments":{{"program":"{}"}}}}"#,
+        program.display()
+    );
+    let set_breakpoints = format!(
+        r#"{{"seq":3,"type":"request","command":"setBreakpoints","arguments":{{"source":{{"path":"{}"}},"breakpoints":[{{"line":3}}]}}}}"#,
+        program.display()
+    );
+    let input = [
+        r#"{"seq":1,"type":"request","command":"initialize","arguments":{}}"#,
+        &launch,
+        &set_breakpoints,
+        r#"{"seq":4,"type":"request","command":"configurationDone"}"#,
+        r#"{"seq":5,"type":"request","command":"stackTrace","arguments":{"threadId":1}}"#,
+        r#"{"seq":6,"type":"request","command":"variables","arguments":{"variablesReference":1}}"#,
+        r#"{"seq":7,"type":"request","command":"continue","arguments":{"threadId":1}}"#,
+        r#"{"seq":8,"type":"request","command":"disconnect"}"#,
+    ];
+
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let mut child = Command::new(&*gluon_path)
+        .arg("debug")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    {
+        let stdin = child.stdin.as_mut().unwrap();
+        for content in &input {
+            stdin.write_all(message(content).as_bytes()).unwrap();
+        }
+    }
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(stdout.contains(r#""event":"initialized""#), "{}", stdout);
+    assert!(stdout.contains(r#""reason":"breakpoint""#), "{}", stdout);
+    assert!(
+        stdout.contains(r#""column":1,"id":1,"line":3,"name":"add""#),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(r#"{"name":"sum","type":"Int","value":"3","variablesReference":0}"#),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn run_with_profile() {
+    use std::io::Write;
+
+    let path = env::args().next().unwrap();
+    let gluon_path = Path::new(&path[..])
+        .parent()
+        .and_then(|p| p.parent())
+        .expect("folder")
+        .join("gluon");
+    let program = env::temp_dir().join("gluon_profile.glu");
+    let profile = env::temp_dir().join("gluon_profile.folded");
+    File::create(&program)
+        .unwrap()
+        .write_all(b"let loop n : Int -> Int = if n == 0 then 0 else loop (n - 1)\nloop 300000\n")
+        .unwrap();
+    let output = Command::new(&*gluon_path)
+        .arg("run")
+        .arg("--profile")
+        .arg(&profile)
+        .arg(&program)
+        .output()
+        .unwrap_or_else(|err| panic!("{}\nWhen opening `{}`", err, gluon_path.display()));
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let mut folded = String::new();
+    File::open(&profile)
+        .unwrap()
+        .read_to_string(&mut folded)
+        .unwrap();
+    for line in folded.lines() {
+        let (stack, count) = line.split_at(line.rfind(' ').expect("count"));
+        assert!(!stack.is_empty(), "{}", folded);
+        assert!(count.trim().parse::<usize>().is_ok(), "{}", folded);
+    }
+    assert!(folded.contains(".loop"), "{}", folded);
+}
@@ -4,6 +4,8 @@ set -ex
 export RUST_BACKTRACE=1
 
 cargo test --features test --all
+cargo test --features "test register_vm" -p gluon
+cargo test --features "test wasm" -p gluon
 cargo check --benches --features test
 cargo check --all --no-default-features
 
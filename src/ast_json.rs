@@ -0,0 +1,90 @@
+//! A versioned JSON representation of the AST which lets tools written in other languages analyze
+//! gluon code without linking to the gluon crates, and produce ASTs which gluon can compile.
+//!
+//! A document has the form `{ "version": 2, "expr": <expr> }` where `<expr>` is the serialized
+//! `SpannedExpr`. Each variant of an AST node is written as an object with the name of the variant
+//! as its only key and every node carries the `span` of the source code it were parsed from.
+//! Identifiers may be shared between several nodes, in which case the first occurrence is
+//! `{ "Marked": [<id>, <name>] }` and the later occurrences are `{ "Reference": <id> }`. An
+//! identifier which is only used once can also be written as `{ "Plain": <name> }`.
+//!
+//! `VERSION` is increased whenever the representation changes and `read` rejects documents of any
+//! other version.
+
+use std::io;
+
+use serde::de::DeserializeState;
+use serde::ser::{Seeded, SerializeStruct, Serializer};
+use serde_json::{self, Value};
+
+use base::ast::RootExpr;
+use base::serialization::{SeSeed, Seed};
+use base::symbol::Symbol;
+use base::types::ArcType;
+
+use {Error, Result};
+
+/// The version of the JSON representation of the AST
+pub const VERSION: u64 = 2;
+
+fn json_error(err: serde_json::Error) -> Error {
+    Error::Other(Box::new(err))
+}
+
+// The document is (de)serialized directly instead of through a `serde_json::Value` as the
+// objects of a `Value` do not keep the order of their keys, which would let a `Reference` to an
+// identifier precede the identifier it refers to.
+#[derive(DeserializeState)]
+#[serde(deserialize_state = "Seed<Symbol, ArcType<Symbol>>")]
+struct Document {
+    #[serde(state)]
+    expr: RootExpr<Symbol>,
+}
+
+/// Writes `expr` to `writer` as a JSON document
+pub fn write<W>(writer: W, expr: &RootExpr<Symbol>) -> Result<()>
+where
+    W: io::Write,
+{
+    let mut serializer = serde_json::Serializer::pretty(writer);
+    let mut document = serializer
+        .serialize_struct("Document", 2)
+        .map_err(json_error)?;
+    document
+        .serialize_field("version", &VERSION)
+        .map_err(json_error)?;
+    document
+        .serialize_field("expr", &Seeded::new(&SeSeed::default(), expr))
+        .map_err(json_error)?;
+    document.end().map_err(json_error)
+}
+
+/// Reads an expression from a JSON document written by `write`, or by a tool following the same
+/// format
+pub fn read<R>(mut reader: R) -> Result<RootExpr<Symbol>>
+where
+    R: io::Read,
+{
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+
+    let version = match serde_json::from_str(&text).map_err(json_error)? {
+        Value::Object(document) => document.get("version").and_then(|version| version.as_u64()),
+        _ => return Err("Expected the AST JSON to be an object".to_string().into()),
+    };
+    match version {
+        Some(VERSION) => (),
+        Some(version) => {
+            return Err(format!(
+                "Unsupported AST JSON version {}, expected version {}",
+                version, VERSION
+            ).into())
+        }
+        None => return Err("The AST JSON is missing its `version`".to_string().into()),
+    }
+    let document = Document::deserialize_state(
+        &mut Seed::default(),
+        &mut serde_json::Deserializer::from_str(&text),
+    ).map_err(json_error)?;
+    Ok(document.expr)
+}
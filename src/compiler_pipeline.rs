@@ -11,24 +11,29 @@ use std::borrow::{Borrow, BorrowMut};
 use std::mem;
 use std::ops::Deref;
 use std::result::Result as StdResult;
+use std::sync::Arc;
 
 #[cfg(feature = "serde")]
 use either::Either;
 
-use base::ast::SpannedExpr;
+use base::ast::RootExpr;
 use base::error::{Errors, InFile};
 use base::metadata::Metadata;
 use base::types::{ArcType, Type};
 use base::source::Source;
 use base::symbol::{Name, NameBuf, Symbol, SymbolModule};
+use base::timings::{self, Phase};
 use base::resolve;
 
+use check::warning::Warnings;
+
 use vm::core;
 use vm::compiler::CompiledModule;
 use vm::future::{BoxFutureValue, FutureValue};
 use vm::macros::MacroExpander;
 use vm::thread::{Execute, RootedValue, Thread, ThreadInternal, VmRoot};
 
+use line;
 use {Compiler, Error, Result};
 
 fn execute<T, F>(vm: T, f: F) -> FutureValue<Execute<T>>
@@ -47,6 +52,14 @@ where
     }
 }
 
+/// Returns `true` if the module in `expr_str` opts out of the implicit prelude with
+/// `#![no_implicit_prelude]`
+fn has_no_implicit_prelude(expr_str: &str) -> bool {
+    ::parser::module_attributes(expr_str)
+        .iter()
+        .any(|attribute| attribute.name == "no_implicit_prelude")
+}
+
 pub type SalvageResult<T> = StdResult<T, (Option<T>, Error)>;
 
 /// Result type of successful macro expansion
@@ -55,7 +68,7 @@ pub struct MacroValue<E> {
 }
 
 pub trait MacroExpandable {
-    type Expr: BorrowMut<SpannedExpr<Symbol>>;
+    type Expr: BorrowMut<RootExpr<Symbol>>;
 
     fn expand_macro(
         self,
@@ -85,7 +98,7 @@ pub trait MacroExpandable {
 }
 
 impl<'s> MacroExpandable for &'s str {
-    type Expr = SpannedExpr<Symbol>;
+    type Expr = RootExpr<Symbol>;
 
     fn expand_macro_with(
         self,
@@ -94,9 +107,9 @@ impl<'s> MacroExpandable for &'s str {
         file: &str,
         expr_str: &str,
     ) -> SalvageResult<MacroValue<Self::Expr>> {
-        compiler
-            .parse_expr(macros.vm.global_env().type_cache(), file, self)
-            .map_err(|err| (None, err.into()))
+        let type_cache = macros.vm.global_env().type_cache();
+        RootExpr::try_new(|arena| compiler.parse_partial_expr(arena, type_cache, file, self))
+            .map_err(|(_, err)| (None, err.into()))
             .and_then(|mut expr| {
                 let result = (&mut expr)
                     .expand_macro_with(compiler, macros, file, expr_str)
@@ -110,26 +123,31 @@ impl<'s> MacroExpandable for &'s str {
     }
 }
 
-impl<'s> MacroExpandable for &'s mut SpannedExpr<Symbol> {
-    type Expr = &'s mut SpannedExpr<Symbol>;
+impl<'s> MacroExpandable for &'s mut RootExpr<Symbol> {
+    type Expr = &'s mut RootExpr<Symbol>;
 
     fn expand_macro_with(
         self,
         compiler: &mut Compiler,
         macros: &mut MacroExpander,
         file: &str,
-        _expr_str: &str,
+        expr_str: &str,
     ) -> SalvageResult<MacroValue<Self::Expr>> {
-        if compiler.implicit_prelude {
-            compiler.include_implicit_prelude(macros.vm.global_env().type_cache(), file, self);
-        }
-        macros.run(self);
+        timings::time(file, Phase::MacroExpansion, || {
+            self.with_mut(|arena, expr| {
+                if compiler.implicit_prelude && !has_no_implicit_prelude(expr_str) {
+                    let type_cache = macros.vm.global_env().type_cache();
+                    compiler.include_implicit_prelude(arena, type_cache, file, expr);
+                }
+                line::with_source(macros, expr_str, |macros| macros.run(arena, expr))
+            })
+        });
         Ok(MacroValue { expr: self })
     }
 }
 
-impl MacroExpandable for SpannedExpr<Symbol> {
-    type Expr = SpannedExpr<Symbol>;
+impl MacroExpandable for RootExpr<Symbol> {
+    type Expr = RootExpr<Symbol>;
 
     fn expand_macro_with(
         mut self,
@@ -138,11 +156,16 @@ impl MacroExpandable for SpannedExpr<Symbol> {
         file: &str,
         expr_str: &str,
     ) -> SalvageResult<MacroValue<Self::Expr>> {
-        if compiler.implicit_prelude {
-            compiler.include_implicit_prelude(macros.vm.global_env().type_cache(), file, &mut self);
-        }
         let prev_errors = mem::replace(&mut macros.errors, Errors::new());
-        macros.run(&mut self);
+        timings::time(file, Phase::MacroExpansion, || {
+            self.with_mut(|arena, expr| {
+                if compiler.implicit_prelude && !has_no_implicit_prelude(expr_str) {
+                    let type_cache = macros.vm.global_env().type_cache();
+                    compiler.include_implicit_prelude(arena, type_cache, file, expr);
+                }
+                line::with_source(macros, expr_str, |macros| macros.run(arena, expr))
+            })
+        });
         let errors = mem::replace(&mut macros.errors, prev_errors);
         if errors.has_errors() {
             Err((None, InFile::new(file, expr_str, errors).into()))
@@ -156,10 +179,12 @@ impl MacroExpandable for SpannedExpr<Symbol> {
 pub struct TypecheckValue<E> {
     pub expr: E,
     pub typ: ArcType,
+    /// The warnings enabled in the `Compiler` which were found in `expr`
+    pub warnings: Warnings,
 }
 
 pub trait Typecheckable: Sized {
-    type Expr: BorrowMut<SpannedExpr<Symbol>>;
+    type Expr: BorrowMut<RootExpr<Symbol>>;
 
     fn typecheck(
         self,
@@ -217,7 +242,7 @@ where
 
 impl<E> Typecheckable for MacroValue<E>
 where
-    E: BorrowMut<SpannedExpr<Symbol>>,
+    E: BorrowMut<RootExpr<Symbol>>,
 {
     type Expr = E;
 
@@ -229,22 +254,48 @@ where
         expr_str: &str,
         expected_type: Option<&ArcType>,
     ) -> Result<TypecheckValue<Self::Expr>> {
+        use base::pos;
+        use check::metadata;
         use check::typecheck::Typecheck;
+        use check::warning::{Warning, WarningSettings};
+        use check::unused;
 
         let env = thread.get_env();
         let mut tc = Typecheck::new(
             file.into(),
             &mut compiler.symbols,
-            &*env,
+            &env,
             thread.global_env().type_cache().clone(),
         );
+        tc.set_max_depth(compiler.max_nesting_depth);
+
+        tc.set_warning_settings(WarningSettings {
+            shadowing: compiler.warn_shadowing,
+        });
+
+        let typ = {
+            let root = self.expr.borrow_mut();
+            timings::time(file, Phase::Typecheck, || {
+                root.with_mut(|_, expr| tc.typecheck_expr_expected(expr, expected_type))
+            })
+        }.map_err(|err| InFile::new(file, expr_str, err))?;
 
-        let typ = tc.typecheck_expr_expected(self.expr.borrow_mut(), expected_type)
-            .map_err(|err| InFile::new(file, expr_str, err))?;
+        let mut warnings = tc.take_warnings();
+        warnings.extend(
+            unused::check(self.expr.borrow().expr())
+                .into_iter()
+                .map(|warning| pos::spanned(warning.span, Warning::Unused(warning.value))),
+        );
+        warnings.extend(
+            metadata::deprecations(&env, self.expr.borrow().expr())
+                .into_iter()
+                .map(|warning| pos::spanned(warning.span, Warning::Deprecated(warning.value))),
+        );
 
         Ok(TypecheckValue {
             expr: self.expr,
             typ: typ,
+            warnings: warnings,
         })
     }
 }
@@ -254,6 +305,8 @@ pub struct CompileValue<E> {
     pub expr: E,
     pub typ: ArcType,
     pub module: CompiledModule,
+    /// The optimized core expression which `module` was compiled from
+    pub core: Arc<core::CoreExpr>,
 }
 
 pub trait Compileable<Extra> {
@@ -288,7 +341,7 @@ where
 }
 impl<E, Extra> Compileable<Extra> for TypecheckValue<E>
 where
-    E: Borrow<SpannedExpr<Symbol>>,
+    E: Borrow<RootExpr<Symbol>>,
 {
     type Expr = E;
 
@@ -302,19 +355,24 @@ where
     ) -> Result<CompileValue<Self::Expr>> {
         use vm::compiler::Compiler;
         debug!("Compile `{}`", filename);
-        let mut module = {
+        let (mut module, core_expr) = timings::time(filename, Phase::Codegen, || -> Result<_> {
             let env = thread.get_env();
 
-            let translator = core::Translator::new(&*env);
-            let expr = {
-                let expr = translator
-                    .allocator
-                    .arena
-                    .alloc(translator.translate(self.expr.borrow()));
-
-                debug!("Translation returned: {}", expr);
-
-                core::optimize::optimize(&translator.allocator, expr)
+            let core_expr = core::translate(&env, self.expr.borrow().expr());
+            debug!("Translation returned: {}", core_expr.expr());
+
+            let core_expr = {
+                let env = &env;
+                // The `#[inline]` functions of the modules which have already been loaded may be
+                // inlined into this module
+                let globals = |id: &Symbol| {
+                    let global = env.get_global(id.definition_name())?;
+                    let core = global.core.as_ref()?;
+                    Some((core.expr(), &global.metadata))
+                };
+                core_expr.map(|allocator, expr| {
+                    core::optimize::optimize(allocator, expr, &globals)
+                })
             };
 
             let name = Name::new(filename);
@@ -326,20 +384,22 @@ where
             let source = Source::new(expr_str);
 
             let mut compiler = Compiler::new(
-                &*env,
+                &env,
                 thread.global_env(),
                 symbols,
                 &source,
                 filename.to_string(),
                 compiler.emit_debug_info,
             );
-            compiler.compile_expr(expr)?
-        };
+            let module = compiler.compile_expr(core_expr.expr())?;
+            Ok((module, core_expr))
+        })?;
         module.function.id = Symbol::from(filename);
         Ok(CompileValue {
             expr: self.expr,
             typ: self.typ,
             module,
+            core: Arc::new(core_expr),
         })
     }
 }
@@ -383,7 +443,7 @@ pub trait Executable<'vm, Extra> {
 impl<'vm, C, Extra> Executable<'vm, Extra> for C
 where
     C: Compileable<Extra>,
-    C::Expr: BorrowMut<SpannedExpr<Symbol>> + Send + 'vm,
+    C::Expr: BorrowMut<RootExpr<Symbol>> + Send + 'vm,
 {
     type Expr = C::Expr;
 
@@ -422,7 +482,7 @@ where
 }
 impl<'vm, E> Executable<'vm, ()> for CompileValue<E>
 where
-    E: BorrowMut<SpannedExpr<Symbol>> + Send + 'vm,
+    E: BorrowMut<RootExpr<Symbol>> + Send + 'vm,
 {
     type Expr = E;
 
@@ -441,11 +501,12 @@ where
             expr,
             typ,
             mut module,
+            ..
         } = self;
         let run_io = compiler.run_io;
         let module_id = Symbol::from(format!("@{}", name));
         module.function.id = module_id.clone();
-        let closure = try_future!(vm.global_env().new_global_thunk(module));
+        let closure = try_future!(vm.new_global_thunk(module));
 
         let vm1 = vm.clone();
         execute(vm1, |vm| vm.call_thunk(closure))
@@ -480,6 +541,7 @@ where
 
         let run_io = compiler.run_io;
         let filename = filename.to_string();
+        let core = self.core.clone();
 
         let vm1 = vm.clone();
         let vm2 = vm.clone();
@@ -491,14 +553,25 @@ where
                     FutureValue::sync(Ok(v)).boxed()
                 }
             })
-            .and_then(move |mut value| {
-                let (metadata, _) = metadata::metadata(&*vm.get_env(), value.expr.borrow_mut());
+            .and_then(move |value| {
+                let (metadata, _) = timings::time(&filename, Phase::Metadata, || {
+                    metadata::metadata(&vm.get_env(), value.expr.borrow().expr())
+                });
+                // Only the functions marked `#[inline]` are inlined into other modules so the core
+                // expression is only needed if the module exports such a function
+                let exports_inline_functions = metadata
+                    .module
+                    .values()
+                    .any(|field| field.attributes.contains_key("inline"));
                 try_future!(vm.set_global(
                     value.id.clone(),
                     value.typ,
                     metadata,
                     value.value.get_value(),
                 ));
+                if exports_inline_functions {
+                    vm.set_global_core(&value.id, core);
+                }
                 info!("Loaded module `{}` filename", filename);
                 FutureValue::sync(Ok(()))
             })
@@ -514,7 +587,7 @@ pub struct Precompiled<D>(pub D);
            serde(deserialize_state = "::vm::serialization::DeSeed"))]
 #[cfg_attr(feature = "serde_derive_state", serde(serialize_state = "::vm::serialization::SeSeed"))]
 pub struct Module {
-    #[cfg_attr(feature = "serde_derive_state", serde(state_with = "::vm::serialization::borrow"))]
+    #[cfg_attr(feature = "serde_derive_state", serde(state_with = "::vm::serialization::typ"))]
     pub typ: ArcType,
 
     pub metadata: Metadata,
@@ -523,6 +596,73 @@ pub struct Module {
     pub module: CompiledModule,
 }
 
+/// Runs a module which has already been compiled, such as a module read from a precompiled file
+impl<'vm> Executable<'vm, ()> for Module {
+    type Expr = ();
+
+    fn run_expr<T>(
+        self,
+        _compiler: &mut Compiler,
+        vm: T,
+        name: &str,
+        _expr_str: &str,
+        _: (),
+    ) -> BoxFutureValue<'vm, ExecuteValue<T, Self::Expr>, Error>
+    where
+        T: Send + VmRoot<'vm>,
+    {
+        let Module {
+            typ, mut module, ..
+        } = self;
+        let module_id = Symbol::from(format!("@{}", name));
+        module.function.id = module_id.clone();
+        let closure = try_future!(vm.new_global_thunk(module));
+
+        let vm1 = vm.clone();
+        execute(vm1, |vm| vm.call_thunk(closure))
+            .map(|(vm, value)| ExecuteValue {
+                id: module_id,
+                expr: (),
+                typ: typ,
+                value: vm.root_value_with_self(value),
+            })
+            .map_err(Error::from)
+            .boxed()
+    }
+
+    fn load_script<T>(
+        self,
+        compiler: &mut Compiler,
+        vm: T,
+        name: &str,
+        _expr_str: &str,
+        _: (),
+    ) -> BoxFutureValue<'vm, (), Error>
+    where
+        T: Send + VmRoot<'vm>,
+    {
+        let Module {
+            typ,
+            metadata,
+            mut module,
+        } = self;
+        let module_id = compiler.symbols.symbol(format!("@{}", name));
+        module.function.id = module_id.clone();
+        let closure = try_future!(vm.new_global_thunk(module));
+
+        let name = name.to_string();
+        let vm1 = vm.clone();
+        execute(vm1, |vm| vm.call_thunk(closure))
+            .map_err(Error::from)
+            .and_then(move |(_, value)| {
+                try_future!(vm.set_global(module_id, typ, metadata, value));
+                info!("Loaded module `{}` from bytecode", name);
+                FutureValue::sync(Ok(()))
+            })
+            .boxed()
+    }
+}
+
 #[cfg(feature = "serde")]
 impl<'vm, 'de, D> Executable<'vm, ()> for Precompiled<D>
 where
@@ -558,7 +698,7 @@ where
         }
         let typ = module.typ;
         let vm1 = vm.clone();
-        let closure = try_future!(vm.global_env().new_global_thunk(module.module));
+        let closure = try_future!(vm.new_global_thunk(module.module));
         execute(vm1, |vm| vm.call_thunk(closure))
             .map(|(vm, value)| ExecuteValue {
                 id: module_id,
@@ -587,7 +727,7 @@ where
             metadata,
             typ,
             value,
-            id: _,
+            ..
         } = try_future!(
             DeSeed::new(&vm)
                 .deserialize(self.0)
@@ -621,6 +761,7 @@ where
         expr: _,
         typ,
         module,
+        ..
     } = self_
         .compile(compiler, thread, file, expr_str, arg)
         .map_err(Error::from)
@@ -647,7 +788,7 @@ where
     use vm::api::{VmType, IO};
     use vm::api::generic::A;
 
-    if check_signature(&*vm.get_env(), &v.typ, &IO::<A>::make_forall_type(&vm)) {
+    if check_signature(&vm.get_env(), &v.typ, &IO::<A>::make_forall_type(&vm)) {
         let ExecuteValue {
             id,
             expr,
@@ -659,7 +800,7 @@ where
         execute(vm1, |vm| vm.execute_io(value.get_value()))
             .map(move |(_, value)| {
                 // The type of the new value will be `a` instead of `IO a`
-                let actual = resolve::remove_aliases_cow(&*vm.get_env(), &typ);
+                let actual = resolve::remove_aliases_cow(&vm.get_env(), &typ);
                 let actual = match **actual {
                     Type::App(_, ref arg) => arg[0].clone(),
                     _ => ice!("ICE: Expected IO type found: `{}`", actual),
@@ -0,0 +1,70 @@
+//! The `expect!` macro which passes the location of the expected string to
+//! `std.test.expect.expect_at` so that `gluon test --bless` can update it
+use base::ast::{self, Arena, Expr, Literal, SpannedExpr, TypedIdent};
+use base::pos;
+use base::symbol::Symbol;
+use base::types::Type;
+
+use vm::macros::{Error as MacroError, Macro, MacroExpander};
+
+use line;
+
+/// `expect! show value "expected"` expands to
+/// `(import! std.test.expect).expect_at line start end show value "expected"` where `line`,
+/// `start` and `end` are the location of `"expected"`
+pub struct Expect;
+
+impl Macro for Expect {
+    fn expand<'ast>(
+        &self,
+        macros: &mut MacroExpander,
+        arena: &'ast Arena<'ast, Symbol>,
+        args: &mut [SpannedExpr<'ast, Symbol>],
+    ) -> Result<SpannedExpr<'ast, Symbol>, MacroError> {
+        let span = match args.last() {
+            Some(&pos::Spanned {
+                span,
+                value: Expr::Literal(Literal::String(_)),
+            }) => span,
+            _ => {
+                return Err("`expect!` expects a string literal as its last argument, \
+                            for example `expect! int.show 1 \"1\"`"
+                    .into())
+            }
+        };
+        let line = line::line_number(macros, span.start)
+            .ok_or_else(|| MacroError::from("`expect!` was used outside of a module"))?;
+
+        let spanned = |value| pos::spanned(span, value);
+        let ident = |name: &str| spanned(Expr::Ident(TypedIdent::new(Symbol::from(name))));
+        let int = |i| spanned(Expr::Literal(Literal::Int(i)));
+
+        let path = ["test", "expect"].iter().fold(ident("std"), |expr, name| {
+            spanned(Expr::Projection(
+                arena.alloc(expr),
+                Symbol::from(*name),
+                Type::hole(),
+                Vec::new(),
+            ))
+        });
+        let module = spanned(Expr::App(
+            arena.alloc(ident("import!")),
+            arena.alloc_extend(Some(path)),
+        ));
+        let expect_at = spanned(Expr::Projection(
+            arena.alloc(module),
+            Symbol::from("expect_at"),
+            Type::hole(),
+            Vec::new(),
+        ));
+
+        let location = vec![
+            int(line),
+            int(span.start.to_usize() as i64),
+            int(span.end.to_usize() as i64),
+        ];
+        let expect_args =
+            arena.alloc_extend(location.into_iter().chain(args.iter_mut().map(ast::take_expr)));
+        Ok(spanned(Expr::App(arena.alloc(expect_at), expect_args)))
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::any::Any;
 use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
 use std::sync::{Arc, Mutex, RwLock};
 use std::fs::File;
 use std::mem;
@@ -13,17 +14,23 @@ use itertools::Itertools;
 
 use base::filename_to_module;
 use base::error::{Errors, InFile};
-use base::ast::{expr_to_path, Expr, Literal, SpannedExpr, Typed, TypedIdent};
-use base::fnv::FnvMap;
+use base::ast::{expr_to_path, Arena, Expr, Literal, RootExpr, SpannedExpr, Typed, TypedIdent};
+use base::fnv::{FnvHasher, FnvMap};
+use base::interface::Interface;
+#[cfg(feature = "serialization")]
+use base::interface;
 use base::pos::{self, BytePos, Span};
 use base::symbol::Symbol;
-use base::types::ArcType;
+use base::types::{self, ArcType};
+#[cfg(feature = "serialization")]
+use base::serialization::{SeSeed, Seed};
 
 use vm::{ExternLoader, ExternModule};
 use vm::macros::{Error as MacroError, Macro, MacroExpander};
 use vm::thread::{Thread, ThreadInternal};
 
 use super::Compiler;
+use compiler_pipeline::Module;
 
 quick_error! {
     /// Error type for the import macro
@@ -53,8 +60,11 @@ quick_error! {
 }
 
 macro_rules! std_libs {
-    ($($file: expr),*) => {
-        [$((concat!("std.", $file), include_str!(concat!("../std/", $file, ".glu")))),*]
+    ($($file: expr),*; $($module: expr => $path: expr),*) => {
+        [
+            $((concat!("std.", $file), include_str!(concat!("../std/", $file, ".glu"))),)*
+            $((concat!("std.", $module), include_str!(concat!("../std/", $path, ".glu")))),*
+        ]
     }
 }
 // Include the standard library distribution in the binary
@@ -69,6 +79,7 @@ static STD_LIBS: &[(&str, &str)] = &std_libs!(
     "io",
     "list",
     "map",
+    "meta",
     "option",
     "parser",
     "result",
@@ -79,7 +90,13 @@ static STD_LIBS: &[(&str, &str)] = &std_libs!(
     "test",
     "unit",
     "writer",
-    "array"
+    "array",
+    "iter";
+    "array.sized" => "array/sized",
+    "math.linear" => "math/linear",
+    "prelude.small" => "prelude/small",
+    "test.assert" => "test/assert",
+    "test.expect" => "test/expect"
 );
 
 pub trait Importer: Any + Clone + Sync + Send {
@@ -90,8 +107,53 @@ pub trait Importer: Any + Clone + Sync + Send {
         earlier_errors_exist: bool,
         modulename: &str,
         input: &str,
-        expr: SpannedExpr<Symbol>,
+        expr: &mut RootExpr<Symbol>,
     ) -> Result<(), (Option<ArcType>, MacroError)>;
+
+    /// Imports a module which has already been typechecked to `typ`, such as a module read from
+    /// the cache directory of `Import`
+    fn import_typechecked(
+        &self,
+        compiler: &mut Compiler,
+        vm: &Thread,
+        modulename: &str,
+        input: &str,
+        expr: &mut RootExpr<Symbol>,
+        typ: ArcType,
+    ) -> Result<(), MacroError> {
+        use compiler_pipeline::*;
+
+        TypecheckValue {
+            expr,
+            typ,
+            warnings: Errors::new(),
+        }.load_script(compiler, vm, modulename, input, ())
+            .sync_or_error()
+            .map_err(|err| err.into())
+    }
+
+    /// Imports a module which has already been compiled, such as a module read from a
+    /// precompiled `.gluc` file
+    fn import_precompiled(
+        &self,
+        compiler: &mut Compiler,
+        vm: &Thread,
+        modulename: &str,
+        module: Module,
+    ) -> Result<(), MacroError> {
+        use compiler_pipeline::*;
+
+        module
+            .load_script(compiler, vm, modulename, "", ())
+            .sync_or_error()
+            .map_err(|err| err.into())
+    }
+
+    /// Whether the modules loaded by this importer are run. The `__init` action of a module is
+    /// only run if it is.
+    fn runs_modules(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Clone)]
@@ -104,12 +166,12 @@ impl Importer for DefaultImporter {
         earlier_errors_exist: bool,
         modulename: &str,
         input: &str,
-        mut expr: SpannedExpr<Symbol>,
+        expr: &mut RootExpr<Symbol>,
     ) -> Result<(), (Option<ArcType>, MacroError)> {
         use compiler_pipeline::*;
 
         let result = {
-            let expr = &mut expr;
+            let expr = &mut *expr;
             let result = MacroValue { expr }
                 .typecheck(compiler, vm, modulename, input)
                 .map_err(|err| err.into());
@@ -127,8 +189,326 @@ impl Importer for DefaultImporter {
             })
         };
 
-        result.map_err(|err| (Some(expr.env_type_of(&*vm.get_env())), err.into()))
+        result.map_err(|err| (Some(expr.expr().env_type_of(&vm.get_env())), err.into()))
+    }
+}
+
+/// Importer which typechecks the imported modules without compiling or running them. The modules
+/// are still added to the vm with their types so that the modules importing them can be
+/// typechecked, but their values are only placeholders which must not be used.
+#[derive(Clone)]
+pub struct CheckImporter;
+impl Importer for CheckImporter {
+    fn import(
+        &self,
+        compiler: &mut Compiler,
+        vm: &Thread,
+        earlier_errors_exist: bool,
+        modulename: &str,
+        input: &str,
+        expr: &mut RootExpr<Symbol>,
+    ) -> Result<(), (Option<ArcType>, MacroError)> {
+        use compiler_pipeline::*;
+
+        let result = MacroValue { expr: &mut *expr }
+            .typecheck(compiler, vm, modulename, input)
+            .map(|value| value.typ);
+        let typ = match result {
+            Ok(typ) => typ,
+            Err(err) => return Err((Some(expr.expr().env_type_of(&vm.get_env())), err.into())),
+        };
+        if earlier_errors_exist {
+            return Ok(());
+        }
+
+        self.import_typechecked(compiler, vm, modulename, input, expr, typ)
+            .map_err(|err| (None, err))
+    }
+
+    fn import_typechecked(
+        &self,
+        _compiler: &mut Compiler,
+        vm: &Thread,
+        modulename: &str,
+        _input: &str,
+        expr: &mut RootExpr<Symbol>,
+        typ: ArcType,
+    ) -> Result<(), MacroError> {
+        use check::metadata;
+        use vm::internal::Value;
+
+        let (metadata, _) = metadata::metadata(&vm.get_env(), expr.expr());
+        let id = Symbol::from(format!("@{}", modulename));
+        vm.set_global(id, typ, metadata, Value::tag(0))
+            .map_err(|err| err.into())
+    }
+
+    fn import_precompiled(
+        &self,
+        _compiler: &mut Compiler,
+        vm: &Thread,
+        modulename: &str,
+        module: Module,
+    ) -> Result<(), MacroError> {
+        use vm::internal::Value;
+
+        let id = Symbol::from(format!("@{}", modulename));
+        vm.set_global(id, module.typ, module.metadata, Value::tag(0))
+            .map_err(|err| err.into())
+    }
+
+    fn runs_modules(&self) -> bool {
+        false
+    }
+}
+
+/// Runs the `IO ()` action which `module` exports as `hook`, if it exports one
+fn run_module_hook(vm: &Thread, module: &str, hook: &str) -> Result<(), ::Error> {
+    use futures::Future;
+
+    use check::check_signature;
+    use vm::api::{VmType, IO};
+
+    let (value, typ) = match vm.get_env().get_binding(&format!("{}.{}", module, hook)) {
+        Ok((value, typ)) => (value, typ.into_owned()),
+        Err(_) => return Ok(()),
+    };
+    if !check_signature(&vm.get_env(), &IO::<()>::make_type(vm), &typ) {
+        return Err(format!(
+            "`{}` in module '{}' must have type `IO ()` but has type `{}`",
+            hook, module, typ
+        ).into());
+    }
+    vm.execute_io(value).wait()?;
+    Ok(())
+}
+
+/// Hashes the contents of `value`. Unlike `DefaultHasher` the result of `FnvHasher` is specified,
+/// so the same source gives the same hash with every build of gluon, letting precompiled and
+/// cached modules be shared between machines.
+fn hash<T: ?Sized + Hash>(value: &T) -> u64 {
+    let mut hasher = FnvHasher::default();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Summary of a module loaded through the import macro which is used to determine whether the
+/// module needs to be typechecked again
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleFingerprint {
+    /// Hash of the source code of the module (`None` for modules defined in Rust)
+    pub source_hash: Option<u64>,
+    /// The modules imported by this module together with the `interface_hash` each of them had
+    /// when this module were loaded
+    pub dependencies: Vec<(String, u64)>,
+    /// The type of the module, the interface which the modules importing it are checked against
+    pub typ: ArcType,
+}
+
+impl ModuleFingerprint {
+    /// Hash of the module's interface. Modules importing this module only need to be checked again
+    /// if this changes. The type is hashed structurally rather than through its pretty printed
+    /// form which leaves out the definitions of aliases and depends on the line width.
+    pub fn interface_hash(&self) -> u64 {
+        let mut hasher = FnvHasher::default();
+        types::hash_structure(&self.typ, &mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Returns the path of the interface file of `module` (`std/option.gli` for `std.option`)
+#[cfg(feature = "serialization")]
+fn interface_filename(module: &str) -> PathBuf {
+    let mut filename = PathBuf::from(module.replace(".", "/"));
+    filename.set_extension(interface::EXTENSION);
+    filename
+}
+
+/// The file extension used for the typechecked modules written to the cache directory
+pub const CACHE_EXTENSION: &str = "glucache";
+
+/// The bytes which every cached module starts with, followed by `CACHE_VERSION`
+#[cfg(feature = "serialization")]
+const CACHE_MAGIC: &[u8] = b"GLUCACHE";
+
+/// The version of the cached module format. It must be increased whenever the serialized
+/// representation of typechecked expressions or the way they are checked changes.
+#[cfg(feature = "serialization")]
+const CACHE_VERSION: u8 = 1;
+
+/// Hash of the options of `compiler` which change how a module is typechecked. A module cached
+/// with different options must be checked again.
+#[cfg(feature = "serialization")]
+fn compiler_options_hash(compiler: &Compiler) -> u64 {
+    hash(&(
+        compiler.implicit_prelude,
+        compiler.small_prelude,
+        compiler.max_nesting_depth,
+    ))
+}
+
+/// A typechecked module stored in the cache directory of `Import`
+#[cfg(feature = "serialization")]
+#[derive(DeserializeState, SerializeState)]
+#[serde(deserialize_state = "Seed<Symbol, ArcType>")]
+#[serde(serialize_state = "SeSeed")]
+struct CachedModule {
+    source_hash: u64,
+    /// The `compiler_options_hash` of the compiler which checked the module
+    options_hash: u64,
+    /// The modules imported by the module together with their `interface_hash`
+    dependencies: Vec<(String, u64)>,
+    #[serde(state)]
+    typ: ArcType,
+    #[serde(state)]
+    expr: RootExpr<Symbol>,
+}
+
+#[cfg(feature = "serialization")]
+impl CachedModule {
+    fn write<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        use serde::ser::SerializeState;
+
+        writer.write_all(CACHE_MAGIC)?;
+        writer.write_all(&[CACHE_VERSION])?;
+        let mut serializer = ::serde_json::Serializer::new(writer);
+        self.serialize_state(&mut serializer, &SeSeed::default())?;
+        Ok(())
+    }
+
+    fn read<R>(mut reader: R) -> io::Result<CachedModule>
+    where
+        R: io::Read,
+    {
+        use serde::de::DeserializeState;
+
+        let mut header = [0; 9];
+        reader.read_exact(&mut header)?;
+        if &header[..8] != CACHE_MAGIC || header[8] != CACHE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a module cached by this version of gluon",
+            ));
+        }
+        let mut deserializer = ::serde_json::Deserializer::from_reader(reader);
+        let module = CachedModule::deserialize_state(&mut Seed::default(), &mut deserializer)?;
+        Ok(module)
+    }
+}
+
+/// Replaces the aliases in the type of a cached module which are defined by modules that are
+/// already loaded with the aliases of those modules. Aliases are compared by the identity of their
+/// names so the copies which were read from the cache would otherwise be distinct types.
+#[cfg(feature = "serialization")]
+fn share_loaded_aliases(vm: &Thread, typ: ArcType) -> ArcType {
+    use base::types::{walk_move_type, Type};
+
+    let env = vm.get_env();
+    walk_move_type(typ, &mut |typ: &ArcType| {
+        let name = match **typ {
+            Type::Alias(ref alias) => &alias.name,
+            Type::Ident(ref id) => id,
+            _ => return None,
+        };
+        let alias = env.find_type_info(name.definition_name()).ok()?;
+        if alias.name == *name {
+            return None;
+        }
+        Some(match **typ {
+            Type::Alias(_) => alias.as_type().clone(),
+            _ => Type::ident(alias.name.clone()),
+        })
+    })
+}
+
+/// Returns the path which `module` is cached at (`std/option.glucache` for `std.option`)
+#[cfg(feature = "serialization")]
+fn cache_filename(module: &str) -> PathBuf {
+    let mut filename = PathBuf::from(module.replace(".", "/"));
+    filename.set_extension(CACHE_EXTENSION);
+    filename
+}
+
+/// The file extension of the precompiled modules written by `gluon build`
+pub const PRECOMPILED_EXTENSION: &str = "gluc";
+
+/// The bytes which every precompiled module starts with, followed by `PRECOMPILED_VERSION`
+#[cfg(feature = "serialization")]
+const PRECOMPILED_MAGIC: &[u8] = b"GLUC";
+
+/// The version of the precompiled module format. It must be increased whenever the instructions
+/// or the serialized representation of compiled modules change.
+#[cfg(feature = "serialization")]
+const PRECOMPILED_VERSION: u8 = 2;
+
+/// A module compiled to bytecode together with the constants and the exported type it needs to be
+/// loaded without its source. `Import` loads the precompiled module of `std.option` from
+/// `std/option.gluc` instead of compiling `std/option.glu`, as long as the source and the
+/// interfaces of the modules it uses are unchanged.
+///
+/// Precompiling the same sources always writes the same bytes, so precompiled modules can be
+/// shared between machines and compared with each other.
+#[cfg(feature = "serialization")]
+#[derive(DeserializeState, SerializeState)]
+#[serde(deserialize_state = "::vm::serialization::DeSeed")]
+#[serde(serialize_state = "::vm::serialization::SeSeed")]
+pub struct PrecompiledModule {
+    pub source_hash: u64,
+    /// The modules used by the bytecode together with their `interface_hash`
+    pub dependencies: Vec<(String, u64)>,
+    #[serde(state)]
+    pub module: Module,
+}
+
+#[cfg(feature = "serialization")]
+impl PrecompiledModule {
+    pub fn write<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        use serde::ser::SerializeState;
+        use vm::serialization::SeSeed;
+
+        writer.write_all(PRECOMPILED_MAGIC)?;
+        writer.write_all(&[PRECOMPILED_VERSION])?;
+        self.serialize_state(&mut ::bincode::Serializer::new(&mut writer), &SeSeed::new())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
     }
+
+    /// Reads a precompiled module, allocating its constants in `vm`
+    pub fn read<R>(vm: &Thread, mut reader: R) -> io::Result<PrecompiledModule>
+    where
+        R: io::Read,
+    {
+        use vm::serialization::DeSeed;
+
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != PRECOMPILED_MAGIC || header[4] != PRECOMPILED_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a module precompiled by this version of gluon",
+            ));
+        }
+        let mut deserializer = ::bincode::Deserializer::new(
+            ::bincode::read_types::IoReader::new(reader),
+            ::bincode::Infinite,
+        );
+        DeSeed::new(vm)
+            .deserialize(&mut deserializer)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Returns the path of the precompiled module of `module` (`std/option.gluc` for `std.option`)
+#[cfg(feature = "serialization")]
+fn precompiled_filename(module: &str) -> PathBuf {
+    let mut filename = PathBuf::from(module.replace(".", "/"));
+    filename.set_extension(PRECOMPILED_EXTENSION);
+    filename
 }
 
 enum UnloadedModule {
@@ -145,6 +525,13 @@ pub struct Import<I = DefaultImporter> {
 
     /// Map of modules currently being loaded
     loading: Mutex<FnvMap<String, Arc<Mutex<()>>>>,
+    /// Fingerprints of every module which have been loaded
+    fingerprints: RwLock<FnvMap<String, ModuleFingerprint>>,
+    /// Directory which typechecked modules are cached in
+    cache_dir: RwLock<Option<PathBuf>>,
+    /// The typechecked expression of every module loaded from source. A module which is loaded
+    /// again while it is still up to date is compiled from its expression without being checked.
+    typechecked: Mutex<FnvMap<String, RootExpr<Symbol>>>,
 }
 
 impl<I> Import<I> {
@@ -155,9 +542,34 @@ impl<I> Import<I> {
             loaders: RwLock::default(),
             importer: importer,
             loading: Mutex::default(),
+            fingerprints: RwLock::default(),
+            cache_dir: RwLock::default(),
+            typechecked: Mutex::default(),
+        }
+    }
+
+    /// Creates an import macro which finds modules in the same paths and extern loaders as this
+    /// macro but imports them with `importer`
+    pub fn with_importer<J>(&self, importer: J) -> Import<J> {
+        Import {
+            paths: RwLock::new(self.paths.read().unwrap().clone()),
+            loaders: RwLock::new(self.loaders.read().unwrap().clone()),
+            importer: importer,
+            loading: Mutex::default(),
+            fingerprints: RwLock::default(),
+            cache_dir: RwLock::new(self.cache_dir.read().unwrap().clone()),
+            typechecked: Mutex::default(),
         }
     }
 
+    /// Sets the directory which typechecked modules are cached in. A module found in the cache
+    /// is loaded without being parsed or typechecked again as long as its source and the
+    /// interfaces of the modules it imports are unchanged. The cache is only used when gluon is
+    /// built with the `serialization` feature.
+    pub fn set_cache_dir(&self, dir: Option<PathBuf>) {
+        *self.cache_dir.write().unwrap() = dir;
+    }
+
     /// Adds a path to the list of paths which the importer uses to find files
     pub fn add_path<P: Into<PathBuf>>(&self, path: P) {
         self.paths.write().unwrap().push(path.into());
@@ -224,6 +636,443 @@ impl<I> Import<I> {
         })
     }
 
+    /// Returns the fingerprint recorded when `module` were loaded
+    pub fn fingerprint(&self, module: &str) -> Option<ModuleFingerprint> {
+        self.fingerprints.read().unwrap().get(module).cloned()
+    }
+
+    /// Returns `true` if `module` has been loaded from `source` and none of the modules it imports
+    /// have changed their interface since then. Modules which are up to date do not need to be
+    /// typechecked again.
+    pub fn is_up_to_date(&self, module: &str, source: &str) -> bool {
+        let fingerprints = self.fingerprints.read().unwrap();
+        fingerprints.get(module).map_or(false, |fingerprint| {
+            fingerprint.source_hash == Some(hash(source))
+                && fingerprint
+                    .dependencies
+                    .iter()
+                    .all(|&(ref dependency, interface_hash)| {
+                        fingerprints.get(dependency).map(|f| f.interface_hash())
+                            == Some(interface_hash)
+                    })
+        })
+    }
+
+    /// Returns the interface of `module` if it has been loaded from source
+    pub fn interface(&self, module: &str) -> Option<Interface> {
+        let fingerprint = self.fingerprint(module)?;
+        Some(Interface::new(
+            module.to_string(),
+            fingerprint.source_hash?,
+            fingerprint.typ,
+        ))
+    }
+
+    /// Writes an interface file for every module loaded from source into `dir`. The interface of
+    /// `std.option` is written to `dir/std/option.gli`.
+    #[cfg(feature = "serialization")]
+    pub fn write_interfaces<P>(&self, dir: P) -> Result<(), Error>
+    where
+        P: AsRef<::std::path::Path>,
+    {
+        use std::fs;
+
+        let modules: Vec<_> = self.fingerprints.read().unwrap().keys().cloned().collect();
+        for module in modules {
+            let interface = match self.interface(&module) {
+                Some(interface) => interface,
+                None => continue,
+            };
+            let path = dir.as_ref().join(interface_filename(&module));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            interface.write(File::create(path)?)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the interface file of `module` from the first path it is found in
+    #[cfg(feature = "serialization")]
+    pub fn read_interface(&self, module: &str) -> Result<Interface, Error> {
+        let filename = interface_filename(module);
+        let file = self.paths
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|p| File::open(p.join(&filename)).ok())
+            .next()
+            .ok_or_else(|| {
+                Error::String(format!("Could not find an interface for module '{}'", module))
+            })?;
+        Ok(Interface::read(file)?)
+    }
+
+    fn record_fingerprint(
+        &self,
+        vm: &Thread,
+        module: &str,
+        source_hash: Option<u64>,
+        dependencies: Vec<String>,
+    ) {
+        let typ = match vm.get_env().get_binding(module) {
+            Ok((_, typ)) => typ.into_owned(),
+            Err(_) => return,
+        };
+        let mut fingerprints = self.fingerprints.write().unwrap();
+        let dependencies = dependencies
+            .into_iter()
+            .filter_map(|dependency| {
+                let interface_hash = fingerprints.get(&dependency)?.interface_hash();
+                Some((dependency, interface_hash))
+            })
+            .collect();
+        fingerprints.insert(
+            module.to_string(),
+            ModuleFingerprint {
+                source_hash,
+                dependencies,
+                typ,
+            },
+        );
+    }
+
+    /// Loads each of `dependencies`, returning `false` if any of them no longer has the interface
+    /// hash it is paired with
+    fn load_dependencies(
+        &self,
+        compiler: &mut Compiler,
+        vm: &Thread,
+        macros: &mut MacroExpander,
+        dependencies: &[(String, u64)],
+        span: Span<BytePos>,
+    ) -> Result<bool, (Option<ArcType>, MacroError)>
+    where
+        I: Importer,
+    {
+        for &(ref dependency, interface_hash) in dependencies {
+            let id = Symbol::from(format!("@{}", dependency));
+            self.load_module(compiler, vm, macros, &id, span)?;
+            if self.fingerprint(dependency).map(|f| f.interface_hash()) != Some(interface_hash) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Loads `module` from the expression it were typechecked to when it were last loaded, as long
+    /// as it is still up to date with `source` once the modules it imports have been loaded.
+    /// Returns `false` if the module must be typechecked again.
+    fn load_up_to_date_module(
+        &self,
+        compiler: &mut Compiler,
+        vm: &Thread,
+        macros: &mut MacroExpander,
+        module: &str,
+        source: &str,
+        span: Span<BytePos>,
+    ) -> Result<bool, (Option<ArcType>, MacroError)>
+    where
+        I: Importer,
+    {
+        let fingerprint = match self.fingerprint(module) {
+            Some(fingerprint) => fingerprint,
+            None => return Ok(false),
+        };
+        if fingerprint.source_hash != Some(hash(source))
+            || !self.typechecked.lock().unwrap().contains_key(module)
+        {
+            return Ok(false);
+        }
+
+        // The imported modules may have been unloaded as well so they are loaded again before
+        // their interfaces are compared
+        self.load_dependencies(compiler, vm, macros, &fingerprint.dependencies, span)?;
+        if !self.is_up_to_date(module, source) {
+            return Ok(false);
+        }
+        let mut expr = match self.typechecked.lock().unwrap().remove(module) {
+            Some(expr) => expr,
+            None => return Ok(false),
+        };
+
+        debug!("Loading '{}' without typechecking it again", module);
+        let result = self.importer.import_typechecked(
+            compiler,
+            vm,
+            module,
+            source,
+            &mut expr,
+            fingerprint.typ,
+        );
+        self.typechecked
+            .lock()
+            .unwrap()
+            .insert(module.to_string(), expr);
+        result.map_err(|err| (None, err))?;
+        if self.importer.runs_modules() {
+            run_module_hook(vm, module, "__init")
+                .map_err(|err| (None, Box::new(err) as MacroError))?;
+        }
+        Ok(true)
+    }
+
+    /// Loads `module` from a precompiled `.gluc` file in the import paths if it were compiled
+    /// from `source` and the modules it uses still have the interfaces it were compiled against.
+    /// Returns `false` if the module must be loaded from its source instead.
+    #[cfg(feature = "serialization")]
+    fn load_precompiled_module(
+        &self,
+        compiler: &mut Compiler,
+        vm: &Thread,
+        macros: &mut MacroExpander,
+        module: &str,
+        source: &str,
+        span: Span<BytePos>,
+    ) -> Result<bool, (Option<ArcType>, MacroError)>
+    where
+        I: Importer,
+    {
+        let filename = precompiled_filename(module);
+        let path = match self.paths
+            .read()
+            .unwrap()
+            .iter()
+            .map(|p| p.join(&filename))
+            .find(|path| path.is_file())
+        {
+            Some(path) => path,
+            None => return Ok(false),
+        };
+        let precompiled = File::open(&path)
+            .and_then(|file| PrecompiledModule::read(vm, io::BufReader::new(file)));
+        let PrecompiledModule {
+            source_hash,
+            dependencies,
+            module: compiled,
+        } = match precompiled {
+            Ok(precompiled) => precompiled,
+            Err(err) => {
+                warn!("Could not read {}: {}", path.display(), err);
+                return Ok(false);
+            }
+        };
+        if source_hash != hash(source)
+            || !self.load_dependencies(compiler, vm, macros, &dependencies, span)?
+        {
+            return Ok(false);
+        }
+
+        debug!("Loading '{}' from {}", module, path.display());
+        self.importer
+            .import_precompiled(compiler, vm, module, compiled)
+            .map_err(|err| (None, err))?;
+        let dependencies = dependencies
+            .into_iter()
+            .map(|(dependency, _)| dependency)
+            .collect();
+        self.record_fingerprint(vm, module, Some(source_hash), dependencies);
+        if self.importer.runs_modules() {
+            run_module_hook(vm, module, "__init")
+                .map_err(|err| (None, Box::new(err) as MacroError))?;
+        }
+        Ok(true)
+    }
+
+    /// Compiles `source` into a precompiled module which is loaded instead of `source` when
+    /// `module` is imported. The modules which `module` imports are loaded through this macro.
+    #[cfg(feature = "serialization")]
+    pub fn precompile(
+        &self,
+        compiler: &mut Compiler,
+        vm: &Thread,
+        module: &str,
+        source: &str,
+    ) -> Result<PrecompiledModule, ::Error>
+    where
+        I: Importer,
+    {
+        let (expr, typ, metadata) = compiler.extract_metadata(vm, module, source)?;
+        let compiled = compiler.compile_script(vm, module, source, &expr)?;
+
+        // Only the modules which the bytecode refers to need to keep their interface for it to
+        // stay valid
+        let dependencies = compiled
+            .module_globals
+            .iter()
+            .map(|global| {
+                let dependency = global.definition_name();
+                match self.fingerprint(dependency) {
+                    Some(fingerprint) => {
+                        Ok((dependency.to_string(), fingerprint.interface_hash()))
+                    }
+                    None => Err(::Error::from(format!(
+                        "Module '{}' uses '{}' which were not loaded through `import!`",
+                        module, dependency
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(PrecompiledModule {
+            source_hash: hash(source),
+            dependencies,
+            module: Module {
+                typ,
+                metadata,
+                module: compiled,
+            },
+        })
+    }
+
+    /// Loads `module` from the cache directory if it were cached from `source` with the same
+    /// compiler options and the modules it imports still have the interfaces it were typechecked
+    /// against. Returns `false` if the module must be loaded from its source instead.
+    #[cfg(feature = "serialization")]
+    fn load_cached_module(
+        &self,
+        compiler: &mut Compiler,
+        vm: &Thread,
+        macros: &mut MacroExpander,
+        module: &str,
+        source: &str,
+        options_hash: u64,
+        span: Span<BytePos>,
+    ) -> Result<bool, (Option<ArcType>, MacroError)>
+    where
+        I: Importer,
+    {
+        let path = match *self.cache_dir.read().unwrap() {
+            Some(ref dir) => dir.join(cache_filename(module)),
+            None => return Ok(false),
+        };
+        let cached =
+            File::open(&path).and_then(|file| CachedModule::read(io::BufReader::new(file)));
+        let cached = match cached {
+            Ok(cached) => cached,
+            Err(_) => return Ok(false),
+        };
+        if cached.source_hash != hash(source) || cached.options_hash != options_hash {
+            return Ok(false);
+        }
+        let CachedModule {
+            source_hash,
+            dependencies,
+            typ,
+            mut expr,
+            ..
+        } = cached;
+
+        // The dependencies must be loaded before the module as its expression refers to them
+        if !self.load_dependencies(compiler, vm, macros, &dependencies, span)? {
+            return Ok(false);
+        }
+
+        debug!("Loading '{}' from {}", module, path.display());
+        let typ = share_loaded_aliases(vm, typ);
+        self.importer
+            .import_typechecked(compiler, vm, module, source, &mut expr, typ)
+            .map_err(|err| (None, err))?;
+        let dependencies = dependencies
+            .into_iter()
+            .map(|(dependency, _)| dependency)
+            .collect();
+        self.record_fingerprint(vm, module, Some(source_hash), dependencies);
+        self.typechecked
+            .lock()
+            .unwrap()
+            .insert(module.to_string(), expr);
+        Ok(true)
+    }
+
+    /// Writes the typechecked expression of `module`, which were checked by a compiler with
+    /// `options_hash`, to the cache directory if one is set
+    #[cfg(feature = "serialization")]
+    fn write_cached_module(&self, module: &str, options_hash: u64) -> Result<(), Error> {
+        use std::fs;
+
+        let path = match *self.cache_dir.read().unwrap() {
+            Some(ref dir) => dir.join(cache_filename(module)),
+            None => return Ok(()),
+        };
+        let expr = match self.typechecked.lock().unwrap().remove(module) {
+            Some(expr) => expr,
+            None => return Ok(()),
+        };
+        let (source_hash, dependencies, typ) = match self.fingerprint(module) {
+            Some(ModuleFingerprint {
+                source_hash: Some(source_hash),
+                dependencies,
+                typ,
+            }) => (source_hash, dependencies, typ),
+            _ => return Ok(()),
+        };
+        let cached = CachedModule {
+            source_hash,
+            options_hash,
+            dependencies,
+            typ,
+            expr,
+        };
+        let result = path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| cached.write(io::BufWriter::new(File::create(&path)?)));
+        self.typechecked
+            .lock()
+            .unwrap()
+            .insert(module.to_string(), cached.expr);
+        Ok(result?)
+    }
+
+    /// Runs the `__deinit` action of `module`, if it exports one, and removes the module from
+    /// `vm` so that the next `import!` of it loads it again. The fingerprint of the module is
+    /// kept, so if neither its source nor the interfaces it imports have changed by then it is
+    /// loaded without being typechecked again.
+    pub fn unload_module(&self, vm: &Thread, module: &str) -> Result<(), ::Error> {
+        run_module_hook(vm, module, "__deinit")?;
+        vm.remove_global(module);
+        Ok(())
+    }
+
+    /// Unloads `module` and loads it again, running the `__deinit` action of the old module and the
+    /// `__init` action of the new module. Modules which imported the old module keep using it until
+    /// they are reloaded as well.
+    pub fn reload_module(
+        &self,
+        compiler: &mut Compiler,
+        vm: &Thread,
+        module: &str,
+    ) -> Result<(), ::Error>
+    where
+        I: Importer,
+    {
+        use vm::macros::MacroExpander;
+
+        self.unload_module(vm, module)?;
+
+        let module_id = Symbol::from(format!("@{}", module));
+        let mut macros = MacroExpander::new(vm);
+        self.load_module(compiler, vm, &mut macros, &module_id, Span::default())
+            .map_err(|(_, err)| ::Error::Other(err))?;
+        Ok(macros.finish()?)
+    }
+
+    /// Retrieves the source code of `module` without loading it
+    pub fn module_source(
+        &self,
+        vm: &Thread,
+        module: &str,
+    ) -> Result<Cow<'static, str>, MacroError> {
+        let mut filename = module.replace(".", "/");
+        filename.push_str(".glu");
+        match self.get_unloaded_module(vm, module, &filename)? {
+            UnloadedModule::Source(source) => Ok(source),
+            UnloadedModule::Extern(_) => Err(Error::String(format!(
+                "Module '{}' is defined in Rust and has no source",
+                module
+            )).into()),
+        }
+    }
+
     pub fn load_module(
         &self,
         compiler: &mut Compiler,
@@ -265,12 +1114,16 @@ impl<I> Import<I> {
                 .clone()
         };
         let _guard = lock.lock().unwrap();
-        if vm.global_env().global_exists(module_id.definition_name()) {
+        if vm.global_exists(module_id.definition_name()) {
             get_state(macros).visited.pop();
             return Ok(());
         }
 
+        // Modules are loaded synchronously so their execution can't be suspended if the thread runs
+        // out of fuel
+        let fuel = vm.context().set_fuel(None);
         let result = self.load_module_(compiler, vm, macros, module_id, &filename, span);
+        vm.context().set_fuel(fuel);
 
         get_state(macros).visited.pop();
         self.loading.lock().unwrap().remove(module_id.as_ref());
@@ -306,17 +1159,64 @@ impl<I> Import<I> {
             }) => {
                 vm.set_global(module_id.clone(), typ, metadata, value.get_value())
                     .map_err(|err| (None, err.into()))?;
+                self.record_fingerprint(vm, &modulename, None, Vec::new());
             }
             UnloadedModule::Source(file_contents) => {
                 // Modules marked as this would create a cyclic dependency if they included the implicit
                 // prelude
                 let implicit_prelude = !file_contents.starts_with("//@NO-IMPLICIT-PRELUDE");
+                compiler.set_implicit_prelude(implicit_prelude);
+                // Loading the dependencies changes the options of `compiler` so they are hashed
+                // before any module is loaded
+                #[cfg(feature = "serialization")]
+                let options_hash = compiler_options_hash(compiler);
+
+                #[cfg(feature = "serialization")]
+                {
+                    if self.load_precompiled_module(
+                        compiler,
+                        vm,
+                        macros,
+                        &modulename,
+                        &file_contents,
+                        span,
+                    )? {
+                        return Ok(());
+                    }
+                }
+                if self.load_up_to_date_module(
+                    compiler,
+                    vm,
+                    macros,
+                    &modulename,
+                    &file_contents,
+                    span,
+                )? {
+                    return Ok(());
+                }
+                #[cfg(feature = "serialization")]
+                {
+                    if self.load_cached_module(
+                        compiler,
+                        vm,
+                        macros,
+                        &modulename,
+                        &file_contents,
+                        options_hash,
+                        span,
+                    )? {
+                        return Ok(());
+                    }
+                }
+
                 compiler.set_implicit_prelude(implicit_prelude);
 
                 let mut prev_errors = mem::replace(&mut macros.errors, Errors::new());
 
+                get_state(macros).dependencies.push(Vec::new());
                 let result =
                     file_contents.expand_macro_with(compiler, macros, &modulename, &file_contents);
+                let dependencies = get_state(macros).dependencies.pop().unwrap_or_default();
 
                 let has_errors = macros.errors.has_errors();
                 let errors = mem::replace(&mut macros.errors, prev_errors);
@@ -331,7 +1231,7 @@ impl<I> Import<I> {
                     ));
                 }
 
-                let macro_result = match result {
+                let mut macro_result = match result {
                     Ok(m) => m,
                     Err((None, err)) => return Err((None, err.into())),
                     Err((Some(m), err)) => {
@@ -346,8 +1246,30 @@ impl<I> Import<I> {
                     has_errors,
                     &modulename,
                     &file_contents,
-                    macro_result.expr,
+                    &mut macro_result.expr,
                 )?;
+                if !has_errors {
+                    self.record_fingerprint(
+                        vm,
+                        &modulename,
+                        Some(hash(&file_contents[..])),
+                        dependencies,
+                    );
+                    self.typechecked
+                        .lock()
+                        .unwrap()
+                        .insert(modulename.to_string(), macro_result.expr);
+                    #[cfg(feature = "serialization")]
+                    {
+                        if let Err(err) = self.write_cached_module(&modulename, options_hash) {
+                            warn!("Could not cache module '{}': {}", modulename, err);
+                        }
+                    }
+                    if self.importer.runs_modules() {
+                        run_module_hook(vm, &modulename, "__init")
+                            .map_err(|err| (None, Box::new(err) as MacroError))?;
+                    }
+                }
             }
         }
         Ok(())
@@ -410,6 +1332,46 @@ pub fn add_extern_module(thread: &Thread, name: &str, loader: ExternLoader) {
     import.add_loader(name, loader);
 }
 
+/// Returns the name of every module which `expr` imports through the `import!` macro. The
+/// expression should not have had its macros expanded.
+pub fn imported_modules(expr: &SpannedExpr<Symbol>) -> Vec<String> {
+    use base::ast::{walk_expr, Visitor};
+
+    struct Imports(Vec<String>);
+
+    impl<'a, 'ast> Visitor<'a, 'ast> for Imports {
+        type Ident = Symbol;
+
+        fn visit_expr(&mut self, expr: &'a SpannedExpr<'ast, Symbol>) {
+            if let Expr::App(ref id, ref args) = expr.value {
+                match (&id.value, args.first()) {
+                    (&Expr::Ident(ref id), Some(arg)) if id.name.as_ref() == "import!" => {
+                        match arg.value {
+                            Expr::Literal(Literal::String(ref filename)) => {
+                                self.0.push(filename_to_module(filename))
+                            }
+                            _ => {
+                                let mut modulename = String::new();
+                                if expr_to_path(arg, &mut modulename).is_ok() {
+                                    self.0.push(modulename);
+                                }
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    let mut imports = Imports(Vec::new());
+    imports.visit_expr(expr);
+    imports.0.sort();
+    imports.0.dedup();
+    imports.0
+}
+
 fn get_state<'m>(macros: &'m mut MacroExpander) -> &'m mut State {
     macros
         .state
@@ -417,6 +1379,7 @@ fn get_state<'m>(macros: &'m mut MacroExpander) -> &'m mut State {
         .or_insert_with(|| {
             Box::new(State {
                 visited: Vec::new(),
+                dependencies: Vec::new(),
             })
         })
         .downcast_mut::<State>()
@@ -425,17 +1388,20 @@ fn get_state<'m>(macros: &'m mut MacroExpander) -> &'m mut State {
 
 struct State {
     visited: Vec<String>,
+    /// The modules imported by each of the modules currently being loaded (innermost last)
+    dependencies: Vec<Vec<String>>,
 }
 
 impl<I> Macro for Import<I>
 where
     I: Importer,
 {
-    fn expand(
+    fn expand<'ast>(
         &self,
         macros: &mut MacroExpander,
-        args: &mut [SpannedExpr<Symbol>],
-    ) -> Result<SpannedExpr<Symbol>, MacroError> {
+        _arena: &'ast Arena<'ast, Symbol>,
+        args: &mut [SpannedExpr<'ast, Symbol>],
+    ) -> Result<SpannedExpr<'ast, Symbol>, MacroError> {
         // If the modulename has been determined we always want to insert that name into the AST so
         // that we can do completion on the name
         let mut modulename = None;
@@ -457,12 +1423,12 @@ impl<I> Import<I>
 where
     I: Importer,
 {
-    fn expand_(
+    fn expand_<'ast>(
         &self,
         macros: &mut MacroExpander,
-        args: &mut [SpannedExpr<Symbol>],
+        args: &mut [SpannedExpr<'ast, Symbol>],
         caller_modulename: &mut Option<Symbol>,
-    ) -> Result<SpannedExpr<Symbol>, MacroError> {
+    ) -> Result<SpannedExpr<'ast, Symbol>, MacroError> {
         if args.len() != 1 {
             return Err(Error::String("Expected import to get 1 argument".into()).into());
         }
@@ -494,9 +1460,13 @@ where
 
         *caller_modulename = Some(name.clone());
 
+        if let Some(dependencies) = get_state(macros).dependencies.last_mut() {
+            dependencies.push(modulename.trim_left_matches('@').to_string());
+        }
+
         // Only load the script if it is not already loaded
         debug!("Import '{}' {:?}", modulename, get_state(macros).visited);
-        if !vm.global_env().global_exists(&modulename) {
+        if !vm.global_exists(&modulename) {
             if let Err((typ, err)) =
                 self.load_module(&mut Compiler::new(), vm, macros, &name, args[0].span)
             {
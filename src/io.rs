@@ -1,6 +1,8 @@
+use std::cell::RefCell;
 use std::io::{stdin, Read};
 use std::fmt;
 use std::fs::File;
+use std::mem;
 use std::sync::Mutex;
 
 use futures::{Future, IntoFuture};
@@ -21,13 +23,40 @@ use compiler_pipeline::*;
 
 use super::{Compiler, Error};
 
+thread_local! {
+    /// The output which `capture_output` is collecting on this thread
+    static CAPTURED_OUTPUT: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Runs `f`, collecting the output which `std.io.print` and `std.io.println` write on the current
+/// thread instead of writing it to stdout
+pub fn capture_output<F, R>(f: F) -> (R, String)
+where
+    F: FnOnce() -> R,
+{
+    let previous = CAPTURED_OUTPUT.with(|captured| {
+        mem::replace(&mut *captured.borrow_mut(), Some(String::new()))
+    });
+    let result = f();
+    let output = CAPTURED_OUTPUT.with(|captured| mem::replace(&mut *captured.borrow_mut(), previous));
+    (result, output.unwrap_or_default())
+}
+
+fn write_output(s: &str) {
+    CAPTURED_OUTPUT.with(|captured| match *captured.borrow_mut() {
+        Some(ref mut output) => output.push_str(s),
+        None => print!("{}", s),
+    })
+}
+
 fn print(s: &str) -> IO<()> {
-    print!("{}", s);
+    write_output(s);
     IO::Value(())
 }
 
 fn println(s: &str) -> IO<()> {
-    println!("{}", s);
+    write_output(s);
+    write_output("\n");
     IO::Value(())
 }
 
@@ -122,6 +151,8 @@ fn catch<'vm>(
 
     let future = action.call_async(()).then(move |result| match result {
         Ok(value) => Either::A(Ok(IO::Value(value)).into_future()),
+        // An interrupted thread must stop so the interruption is always returned to the host
+        Err(vm::Error::Interrupted) => Either::A(Err(vm::Error::Interrupted).into_future()),
         Err(err) => {
             {
                 let mut context = vm.context();
@@ -183,7 +214,7 @@ fn run_expr(WithVM { vm, value: expr }: WithVM<&str>) -> PrimitiveFuture<IO<RunE
                     let env = vm.global_env().get_env();
                     let typ = execute_value.typ;
                     IO::Value(record_no_decl!{
-                        value => ValuePrinter::new(&*env, &typ, execute_value.value.get_variant()).width(80).to_string(),
+                        value => ValuePrinter::new(&env, &typ, execute_value.value.get_variant()).width(80).to_string(),
                         typ => typ.to_string()
                     })
                 }
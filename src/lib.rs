@@ -16,7 +16,7 @@ extern crate itertools;
 extern crate log;
 #[macro_use]
 extern crate quick_error;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
 extern crate tokio_core;
 
 #[cfg(feature = "serde_derive_state")]
@@ -24,6 +24,10 @@ extern crate tokio_core;
 extern crate serde_derive_state;
 #[cfg(feature = "serde")]
 extern crate serde_state as serde;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
+#[cfg(feature = "bincode")]
+extern crate bincode;
 
 #[macro_use]
 pub extern crate gluon_base as base;
@@ -32,9 +36,13 @@ pub extern crate gluon_parser as parser;
 #[macro_use]
 pub extern crate gluon_vm as vm;
 
+#[cfg(feature = "serialization")]
+pub mod ast_json;
 pub mod compiler_pipeline;
+mod expect;
 pub mod import;
 pub mod io;
+mod line;
 #[cfg(feature = "regex")]
 pub mod regex_bind;
 #[cfg(all(feature = "rand", not(target_arch = "wasm32")))]
@@ -51,12 +59,15 @@ use std::result::Result as StdResult;
 use std::env;
 
 use base::filename_to_module;
-use base::ast::{self, SpannedExpr};
-use base::error::{Errors, InFile};
+use base::ast::{self, Arena, RootExpr, SpannedExpr};
+use base::error::{EmitOptions, Errors, InFile};
 use base::metadata::Metadata;
 use base::symbol::{Symbol, SymbolModule, Symbols};
+use base::timings::{self, Phase};
 use base::types::{ArcType, TypeCache};
-use base::pos::{BytePos, Span, Spanned};
+use base::pos::{BytePos, Location, Span, Spanned};
+
+use check::explain::Explanation;
 
 use vm::Variants;
 use vm::api::{Getable, Hole, OpaqueValue, VmType};
@@ -114,6 +125,101 @@ quick_error! {
     }
 }
 
+impl Error {
+    /// Returns the number of errors contained in `self`
+    pub fn error_count(&self) -> usize {
+        match *self {
+            Error::Parse(ref err) => err.len(),
+            Error::Typecheck(ref err) => err.len(),
+            Error::Macro(ref err) => err.len(),
+            Error::Multiple(ref errors) => errors.into_iter().map(Error::error_count).sum(),
+            Error::IO(_) | Error::VM(_) | Error::Other(_) => 1,
+        }
+    }
+
+    /// Writes the error to `writer` along with excerpts of the source code it occurred in. If
+    /// `options.max_errors` is set, only that many errors are written followed by a summary of
+    /// how many errors were found in total.
+    pub fn emit<W>(&self, writer: &mut W, options: &EmitOptions) -> ::std::fmt::Result
+    where
+        W: ::std::fmt::Write,
+    {
+        let mut remaining = options.max_errors.unwrap_or(usize::max_value());
+        self.emit_limited(writer, options.color, &mut remaining)?;
+
+        let count = self.error_count();
+        match options.max_errors {
+            Some(max_errors) if count > max_errors => {
+                writeln!(writer)?;
+                write!(
+                    writer,
+                    "{} errors were found, only the first {} are shown",
+                    count, max_errors
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn emit_limited<W>(
+        &self,
+        writer: &mut W,
+        color: bool,
+        remaining: &mut usize,
+    ) -> ::std::fmt::Result
+    where
+        W: ::std::fmt::Write,
+    {
+        if *remaining == 0 {
+            return Ok(());
+        }
+        match *self {
+            Error::Parse(ref err) => emit_in_file(writer, err, color, remaining),
+            Error::Typecheck(ref err) => emit_in_file(writer, err, color, remaining),
+            Error::Macro(ref err) => emit_in_file(writer, err, color, remaining),
+            Error::Multiple(ref errors) => {
+                for (i, err) in errors.into_iter().enumerate() {
+                    if *remaining == 0 {
+                        break;
+                    }
+                    if i != 0 {
+                        writeln!(writer)?;
+                    }
+                    err.emit_limited(writer, color, remaining)?;
+                }
+                Ok(())
+            }
+            Error::IO(_) | Error::VM(_) | Error::Other(_) => {
+                *remaining -= 1;
+                write!(writer, "{}", self)
+            }
+        }
+    }
+
+    /// Renders the error to a string, see `emit`
+    pub fn emit_string(&self, options: &EmitOptions) -> String {
+        let mut output = String::new();
+        self.emit(&mut output, options)
+            .expect("Writing to a string should not fail");
+        output
+    }
+}
+
+fn emit_in_file<W, E>(
+    writer: &mut W,
+    err: &InFile<E>,
+    color: bool,
+    remaining: &mut usize,
+) -> ::std::fmt::Result
+where
+    W: ::std::fmt::Write,
+    E: ::std::fmt::Display,
+{
+    let count = ::std::cmp::min(err.len(), *remaining);
+    *remaining -= count;
+    err.emit_first(writer, color, count)
+}
+
 impl From<String> for Error {
     fn from(s: String) -> Self {
         Error::VM(s.into())
@@ -167,8 +273,11 @@ pub type Result<T> = StdResult<T, Error>;
 pub struct Compiler {
     symbols: Symbols,
     implicit_prelude: bool,
+    small_prelude: bool,
     emit_debug_info: bool,
     run_io: bool,
+    warn_shadowing: bool,
+    max_nesting_depth: usize,
 }
 
 impl Default for Compiler {
@@ -197,8 +306,11 @@ impl Compiler {
         Compiler {
             symbols: Symbols::new(),
             implicit_prelude: true,
+            small_prelude: false,
             emit_debug_info: true,
             run_io: false,
+            warn_shadowing: false,
+            max_nesting_depth: check::DEFAULT_MAX_DEPTH,
         }
     }
 
@@ -208,6 +320,14 @@ impl Compiler {
         implicit_prelude set_implicit_prelude: bool
     }
 
+    option!{
+        /// Sets whether the implicit prelude is `std.prelude.small`, which only contains the core
+        /// types and the operators of `Int`, `Float` and `String`, instead of `std.prelude`. Modules
+        /// loaded through `import!` still get the full prelude.
+        /// (default: false)
+        small_prelude set_small_prelude: bool
+    }
+
     option!{
         /// Sets whether the compiler should emit debug information such as source maps and variable
         /// names.
@@ -221,33 +341,55 @@ impl Compiler {
         run_io set_run_io: bool
     }
 
+    option!{
+        /// Sets whether a warning is reported when a let binding or function argument shadows a
+        /// binding of the same type.
+        /// (default: false)
+        warn_shadowing set_warn_shadowing: bool
+    }
+
+    option!{
+        /// Sets how deeply types and expressions may be nested before the typechecker reports an
+        /// error instead of checking them.
+        /// (default: 128)
+        max_nesting_depth set_max_nesting_depth: usize
+    }
+
     pub fn mut_symbols(&mut self) -> &mut Symbols {
         &mut self.symbols
     }
 
-    /// Parse `expr_str`, returning an expression if successful
-    pub fn parse_expr(
+    /// Parse `expr_str` into `arena`, returning an expression if successful
+    pub fn parse_expr<'ast>(
         &mut self,
+        arena: &'ast Arena<'ast, Symbol>,
         type_cache: &TypeCache<Symbol, ArcType>,
         file: &str,
         expr_str: &str,
-    ) -> StdResult<SpannedExpr<Symbol>, InFile<parser::Error>> {
-        self.parse_partial_expr(type_cache, file, expr_str)
+    ) -> StdResult<SpannedExpr<'ast, Symbol>, InFile<parser::Error>> {
+        self.parse_partial_expr(arena, type_cache, file, expr_str)
             .map_err(|(_, err)| err)
     }
 
-    /// Parse `input`, returning an expression if successful
-    pub fn parse_partial_expr(
+    /// Parse `input` into `arena`, returning an expression if successful
+    pub fn parse_partial_expr<'ast>(
         &mut self,
+        arena: &'ast Arena<'ast, Symbol>,
         type_cache: &TypeCache<Symbol, ArcType>,
         file: &str,
         expr_str: &str,
-    ) -> StdResult<SpannedExpr<Symbol>, (Option<SpannedExpr<Symbol>>, InFile<parser::Error>)> {
-        Ok(parser::parse_partial_expr(
-            &mut SymbolModule::new(file.into(), &mut self.symbols),
-            type_cache,
-            expr_str,
-        ).map_err(|(expr, err)| (expr, InFile::new(file, expr_str, err)))?)
+    ) -> StdResult<
+        SpannedExpr<'ast, Symbol>,
+        (Option<SpannedExpr<'ast, Symbol>>, InFile<parser::Error>),
+    > {
+        let mut symbols = SymbolModule::new(file.into(), &mut self.symbols);
+        // Parsing starts the compilation of the module so the fresh symbols are numbered from the
+        // start, making recompilations of the module create the same symbols
+        symbols.reset_fresh_symbols();
+        let result = timings::time(file, Phase::Parse, || {
+            parser::parse_partial_expr(arena, &mut symbols, type_cache, expr_str)
+        });
+        Ok(result.map_err(|(expr, err)| (expr, InFile::new(file, expr_str, err)))?)
     }
 
     /// Parse and typecheck `expr_str` returning the typechecked expression and type of the
@@ -257,7 +399,7 @@ impl Compiler {
         vm: &Thread,
         file: &str,
         expr_str: &str,
-        expr: &mut SpannedExpr<Symbol>,
+        expr: &mut RootExpr<Symbol>,
     ) -> Result<ArcType> {
         expr.typecheck_expected(self, vm, file, expr_str, None)
             .map(|result| result.typ)
@@ -269,23 +411,92 @@ impl Compiler {
         file: &str,
         expr_str: &str,
         expected_type: Option<&ArcType>,
-    ) -> Result<(SpannedExpr<Symbol>, ArcType)> {
-        let TypecheckValue { expr, typ } =
+    ) -> Result<(RootExpr<Symbol>, ArcType)> {
+        let TypecheckValue { expr, typ, .. } =
             expr_str.typecheck_expected(self, vm, file, expr_str, expected_type)?;
         Ok((expr, typ))
     }
 
+    /// Typechecks `expr_str` and explains why the innermost expression which contains `span` were
+    /// inferred to have its type by listing the unifications which lead to it. Returns `None` if
+    /// no expression in `expr_str` contains `span`.
+    pub fn explain_type(
+        &mut self,
+        vm: &Thread,
+        file: &str,
+        expr_str: &str,
+        span: Span<BytePos>,
+    ) -> Result<Option<Explanation<Location>>> {
+        use base::source::Source;
+        use check::typecheck::Typecheck;
+
+        let MacroValue { mut expr } = expr_str
+            .expand_macro(self, vm, file, expr_str)
+            .map_err(|(_, err)| err)?;
+
+        let env = vm.get_env();
+        let mut tc = Typecheck::new(
+            file.into(),
+            &mut self.symbols,
+            &env,
+            vm.global_env().type_cache().clone(),
+        );
+        tc.set_max_depth(self.max_nesting_depth);
+        tc.record_provenance();
+        expr.with_mut(|_, expr| tc.typecheck_expr(expr))
+            .map_err(|err| InFile::new(file, expr_str, err))?;
+        Ok(tc.explain(span)
+            .map(|explanation| explanation.in_source(&Source::new(expr_str))))
+    }
+
+    /// Loads the module `module_name` and checks that the record it exports implements
+    /// `signature`. Every field of `signature` must exist in the module with a type that can be
+    /// assigned to the type in `signature`, while fields which are not part of `signature` are
+    /// ignored. This is useful for checking that a script implements the interface expected from
+    /// it, for instance when loading plugins.
+    ///
+    /// If the module does not implement `signature`, an `Error::Typecheck` containing a
+    /// `TypeError::Unification` with every mismatching field is returned.
+    pub fn check_signature(
+        &mut self,
+        vm: &Thread,
+        module_name: &str,
+        signature: &ArcType,
+    ) -> Result<()> {
+        use base::error::Help;
+        use base::pos;
+        use check::typecheck::TypeError;
+
+        let expr_str = format!("import! {}", module_name);
+        let (_, actual) = self.typecheck_str(vm, "check_signature", &expr_str, None)?;
+        check::check_record_signature(&vm.get_env(), signature, &actual).map_err(|errors| {
+            let span = Span::new(BytePos::from(0), BytePos::from(expr_str.len()));
+            let error = Help {
+                error: TypeError::Unification(
+                    signature.clone(),
+                    actual.clone(),
+                    errors.into_iter().collect(),
+                    Vec::new(),
+                ),
+                help: None,
+            };
+            let errors = Errors::from(vec![pos::spanned(span, error)]);
+            InFile::new(module_name, &expr_str, errors).into()
+        })
+    }
+
     /// Compiles `expr` into a function which can be added and run by the `vm`
     pub fn compile_script(
         &mut self,
         vm: &Thread,
         filename: &str,
         expr_str: &str,
-        expr: &SpannedExpr<Symbol>,
+        expr: &RootExpr<Symbol>,
     ) -> Result<CompiledModule> {
         TypecheckValue {
             expr: expr,
             typ: vm.global_env().type_cache().hole(),
+            warnings: Errors::new(),
         }.compile(self, vm, filename, expr_str, ())
             .map(|result| result.module)
     }
@@ -330,11 +541,11 @@ impl Compiler {
         vm: &Thread,
         file: &str,
         expr_str: &str,
-    ) -> Result<(SpannedExpr<Symbol>, ArcType, Metadata)> {
+    ) -> Result<(RootExpr<Symbol>, ArcType, Metadata)> {
         use check::metadata;
-        let (mut expr, typ) = self.typecheck_str(vm, file, expr_str, None)?;
+        let (expr, typ) = self.typecheck_str(vm, file, expr_str, None)?;
 
-        let (metadata, _) = metadata::metadata(&*vm.get_env(), &mut expr);
+        let (metadata, _) = metadata::metadata(&vm.get_env(), expr.expr());
         Ok((expr, typ, metadata))
     }
 
@@ -472,18 +683,24 @@ impl Compiler {
             .boxed()
     }
 
-    fn include_implicit_prelude(
+    fn include_implicit_prelude<'ast>(
         &mut self,
+        arena: &'ast Arena<'ast, Symbol>,
         type_cache: &TypeCache<Symbol, ArcType>,
         name: &str,
-        expr: &mut SpannedExpr<Symbol>,
+        expr: &mut SpannedExpr<'ast, Symbol>,
     ) {
         use std::mem;
         if name == "std.prelude" {
             return;
         }
 
-        let prelude_expr = self.parse_expr(type_cache, "", PRELUDE).unwrap();
+        let prelude = if self.small_prelude {
+            SMALL_PRELUDE
+        } else {
+            PRELUDE
+        };
+        let prelude_expr = self.parse_expr(arena, type_cache, "", prelude).unwrap();
         let original_expr = mem::replace(expr, prelude_expr);
 
         // Set all spans in the prelude expression to -1 so that completion requests always
@@ -492,10 +709,10 @@ impl Compiler {
         use base::pos::UNKNOWN_EXPANSION;
         struct ExpandedSpans;
 
-        impl MutVisitor for ExpandedSpans {
+        impl<'ast> MutVisitor<'ast> for ExpandedSpans {
             type Ident = Symbol;
 
-            fn visit_expr(&mut self, e: &mut SpannedExpr<Self::Ident>) {
+            fn visit_expr(&mut self, e: &mut SpannedExpr<'ast, Self::Ident>) {
                 e.span.expansion_id = UNKNOWN_EXPANSION;
                 walk_mut_expr(self, e);
             }
@@ -508,7 +725,10 @@ impl Compiler {
         ExpandedSpans.visit_expr(expr);
 
         // Replace the 0 in the prelude with the actual expression
-        fn assign_last_body(l: &mut SpannedExpr<Symbol>, original_expr: SpannedExpr<Symbol>) {
+        fn assign_last_body<'ast>(
+            l: &mut SpannedExpr<'ast, Symbol>,
+            original_expr: SpannedExpr<'ast, Symbol>,
+        ) {
             match l.value {
                 ast::Expr::LetBindings(_, ref mut e) => {
                     assign_last_body(e, original_expr);
@@ -546,9 +766,21 @@ let { error } = import! std.prim
 in ()
 "#;
 
+/// The implicit prelude used when `Compiler::small_prelude` is set
+pub const SMALL_PRELUDE: &'static str = r#"
+let __implicit_prelude = import! std.prelude.small
+and { Bool, Option, Ordering, not, error } = __implicit_prelude
+
+let { (+), (-), (*), (/), (==), (<), (<=), (>=), (>) } = __implicit_prelude.float
+let { (+), (-), (*), (/), (==), (<), (<=), (>=), (>) } = __implicit_prelude.int
+let { (==), (<), (<=), (>=), (>) } = __implicit_prelude.string
+
+in ()
+"#;
+
 #[derive(Default)]
 pub struct VmBuilder {
-    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(all(feature = "async", not(target_arch = "wasm32")))]
     event_loop: Option<::tokio_core::reactor::Remote>,
 }
 
@@ -557,7 +789,7 @@ impl VmBuilder {
         VmBuilder::default()
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(all(feature = "async", not(target_arch = "wasm32")))]
     option!{
         /// Sets then event loop which threads are run on
         /// (default: None)
@@ -565,10 +797,10 @@ impl VmBuilder {
     }
 
     pub fn build(self) -> RootedThread {
-        #[cfg(target_arch = "wasm32")]
+        #[cfg(any(not(feature = "async"), target_arch = "wasm32"))]
         let vm = RootedThread::new();
 
-        #[cfg(not(target_arch = "wasm32"))]
+        #[cfg(all(feature = "async", not(target_arch = "wasm32")))]
         let vm = RootedThread::with_global_state(
             ::vm::vm::GlobalVmStateBuilder::new()
                 .event_loop(self.event_loop)
@@ -579,6 +811,8 @@ impl VmBuilder {
         let import = Import::new(DefaultImporter);
         import.add_path(gluon_path);
         vm.get_macros().insert(String::from("import"), import);
+        vm.get_macros().insert(String::from("line"), ::line::Line);
+        vm.get_macros().insert(String::from("expect"), ::expect::Expect);
 
         Compiler::new()
             .implicit_prelude(false)
@@ -592,15 +826,18 @@ impl VmBuilder {
         add_extern_module(&vm, "std.string.prim", ::vm::primitives::load_string);
         add_extern_module(&vm, "std.char.prim", ::vm::primitives::load_char);
         add_extern_module(&vm, "std.array.prim", ::vm::primitives::load_array);
+        add_extern_module(&vm, "std.math.linear.prim", ::vm::linear::load);
 
         add_extern_module(&vm, "std.lazy", ::vm::lazy::load);
         add_extern_module(&vm, "std.reference", ::vm::reference::load);
 
         add_extern_module(&vm, "std.channel", ::vm::channel::load_channel);
         add_extern_module(&vm, "std.thread.prim", ::vm::channel::load_thread);
-        add_extern_module(&vm, "std.debug", ::vm::debug::load);
+        add_extern_module(&vm, "std.meta.prim", ::vm::meta::load);
+        add_extern_module(&vm, "std.test.prim", ::vm::diff::load);
         add_extern_module(&vm, "std.io.prim", ::io::load);
 
+        load_debug(&vm);
         load_regex(&vm);
         load_random(&vm);
 
@@ -614,6 +851,13 @@ pub fn new_vm() -> RootedThread {
     VmBuilder::default().build()
 }
 
+#[cfg(feature = "debug")]
+fn load_debug(vm: &Thread) {
+    add_extern_module(&vm, "std.debug", ::vm::debug::load);
+}
+#[cfg(not(feature = "debug"))]
+fn load_debug(_: &Thread) {}
+
 #[cfg(feature = "regex")]
 fn load_regex(vm: &Thread) {
     add_extern_module(&vm, "std.regex", ::regex_bind::load);
@@ -642,4 +886,21 @@ mod tests {
             .run_expr::<()>(&thread, "prelude", PRELUDE)
             .unwrap_or_else(|err| panic!("{}", err));
     }
+
+    #[test]
+    fn small_prelude() {
+        let _ = ::env_logger::init();
+
+        let thread = new_vm();
+        let (result, _) = Compiler::new()
+            .small_prelude(true)
+            .run_expr::<bool>(
+                &thread,
+                "test",
+                r#" 1 + 2 == 3 && 1.0 <= 2.0 && "a" < "b" && not False "#,
+            )
+            .unwrap_or_else(|err| panic!("{}", err));
+        assert!(result);
+        assert!(!thread.global_exists("std.prelude"));
+    }
 }
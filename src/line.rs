@@ -0,0 +1,58 @@
+//! The `line!` macro which expands to the line number at which it is used
+use base::ast::{Arena, Expr, Literal, SpannedExpr};
+use base::pos::{self, BytePos};
+use base::source::Lines;
+use base::symbol::Symbol;
+
+use vm::macros::{Error as MacroError, Macro, MacroExpander};
+
+/// The lines of each module which is currently being expanded (innermost last)
+struct State(Vec<Lines>);
+
+fn get_state<'m>(macros: &'m mut MacroExpander) -> &'m mut State {
+    macros
+        .state
+        .entry(String::from("line"))
+        .or_insert_with(|| Box::new(State(Vec::new())))
+        .downcast_mut::<State>()
+        .unwrap()
+}
+
+/// Calls `f` with `source` as the module which `line!` refers to
+pub fn with_source<F, R>(macros: &mut MacroExpander, source: &str, f: F) -> R
+where
+    F: FnOnce(&mut MacroExpander) -> R,
+{
+    get_state(macros).0.push(Lines::new(source.bytes()));
+    let result = f(macros);
+    get_state(macros).0.pop();
+    result
+}
+
+/// Returns the line number (starting from 1) of `pos` in the module which is being expanded
+pub fn line_number(macros: &mut MacroExpander, pos: BytePos) -> Option<i64> {
+    get_state(macros)
+        .0
+        .last()
+        .map(|lines| lines.line_number_at_byte(pos).to_usize() as i64 + 1)
+}
+
+/// `line! ()` expands to the line number (starting from 1) at which it is used
+pub struct Line;
+
+impl Macro for Line {
+    fn expand<'ast>(
+        &self,
+        macros: &mut MacroExpander,
+        _arena: &'ast Arena<'ast, Symbol>,
+        args: &mut [SpannedExpr<'ast, Symbol>],
+    ) -> Result<SpannedExpr<'ast, Symbol>, MacroError> {
+        if args.len() != 1 {
+            return Err("`line!` expects one argument, for example `line! ()`".into());
+        }
+        let span = args[0].span;
+        let line = line_number(macros, span.start)
+            .ok_or_else(|| MacroError::from("`line!` was used outside of a module"))?;
+        Ok(pos::spanned(span, Expr::Literal(Literal::Int(line))))
+    }
+}
@@ -8,10 +8,16 @@ extern crate gluon_vm;
 use futures::{Future, IntoFuture};
 use futures::future::lazy;
 
-use gluon::base::types::Type;
+use std::collections::{BTreeMap, HashMap};
+
+use gluon::base::ast::Arena;
+use gluon::base::kind::Kind;
+use gluon::base::symbol::Symbol;
+use gluon::base::types::{Alias, AliasData, ArcType, Field, Generic, Type};
 use gluon::vm::{Error, ExternModule};
-use gluon::vm::api::{FunctionRef, FutureResult, Userdata, VmType, IO};
-use gluon::vm::thread::{Root, RootStr, RootedThread, Thread, Traverseable};
+use gluon::vm::api::{FunctionRef, FutureResult, Userdata, VmType, WithVM, IO};
+use gluon::vm::linear::Vec4;
+use gluon::vm::thread::{Root, RootStr, RootedThread, Thread, ThreadInternal, Traverseable};
 use gluon::vm::types::VmInt;
 use gluon::Compiler;
 use gluon::import::{add_extern_module, Import};
@@ -88,6 +94,151 @@ fn root_data() {
     assert_eq!(result, 124);
 }
 
+fn int_size(i: VmInt) -> VmInt {
+    i * 10
+}
+
+fn float_size(f: f64) -> VmInt {
+    f as VmInt
+}
+
+fn load_size(thread: &Thread) -> ::gluon::vm::Result<ExternModule> {
+    ExternModule::new(
+        thread,
+        record! {
+            (int_size "size") => primitive!(1 int_size),
+            (float_size "size") => primitive!(1 float_size)
+        },
+    )
+}
+
+#[test]
+fn overloaded_extern_functions() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+        let { size } = import! size
+        size 2 #Int+ size 3.5
+    "#;
+
+    let vm = make_vm();
+    add_extern_module(&vm, "size", load_size);
+
+    let result = Compiler::new()
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(result.0, 23);
+}
+
+#[test]
+fn overloaded_extern_functions_through_projection() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+        let sizes = import! size
+        let { size = s } = sizes
+        sizes.size 2 #Int+ sizes.size 3.5 #Int+ s 4.5
+    "#;
+
+    let vm = make_vm();
+    add_extern_module(&vm, "size", load_size);
+
+    let result = Compiler::new()
+        .run_expr::<VmInt>(&vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(result.0, 27);
+}
+
+#[test]
+fn used_overloaded_binding_is_not_reported_as_unused() {
+    use gluon::compiler_pipeline::Typecheckable;
+
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+        let { size } = import! size
+        size 2
+    "#;
+
+    let vm = make_vm();
+    add_extern_module(&vm, "size", load_size);
+
+    let value = expr.typecheck(&mut Compiler::new(), &vm, "<top>", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(!value.warnings.has_errors(), "{:?}", value.warnings);
+}
+
+#[test]
+fn overloads_are_shown_under_their_name() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    add_extern_module(&vm, "size", load_size);
+
+    let (_, typ) = Compiler::new()
+        .typecheck_str(&vm, "<top>", "import! size", None)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        typ.to_string(),
+        "{ size : Int -> Int, size : Float -> Int }"
+    );
+
+    let err = Compiler::new()
+        .run_expr::<VmInt>(&vm, "<top>", r#"(import! size).size "" "#)
+        .unwrap_err()
+        .to_string();
+    assert!(!err.contains("size@1"), "{}", err);
+}
+
+#[test]
+fn overloads_must_have_different_types() {
+    let _ = ::env_logger::init();
+
+    fn first(i: VmInt) -> VmInt {
+        i
+    }
+    fn second(i: VmInt) -> VmInt {
+        i + 1
+    }
+
+    let vm = make_vm();
+    let result = ExternModule::new(
+        &vm,
+        record! {
+            (first "f") => primitive!(1 first),
+            (second "f") => primitive!(1 second)
+        },
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn overloads_differing_in_type_variable_names_must_have_different_types() {
+    use gluon::vm::api::Generic;
+    use gluon::vm::api::generic::{A, B};
+
+    let _ = ::env_logger::init();
+
+    fn first(x: Generic<A>) -> Generic<A> {
+        x
+    }
+    fn second(x: Generic<B>) -> Generic<B> {
+        x
+    }
+
+    let vm = make_vm();
+    let result = ExternModule::new(
+        &vm,
+        record! {
+            (first "id") => primitive!(1 first),
+            (second "id") => primitive!(1 second)
+        },
+    );
+    assert!(result.is_err());
+}
+
 #[test]
 fn root_string() {
     let _ = ::env_logger::init();
@@ -115,6 +266,112 @@ fn root_string() {
     assert_eq!(result, expected);
 }
 
+#[test]
+fn root_scope_borrows_strings() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    load_script(&vm, "string", r#" "abc" "#).unwrap_or_else(|err| panic!("{}", err));
+
+    {
+        let scope = vm.root_scope();
+        let string: &str = scope
+            .get_global("string")
+            .unwrap_or_else(|err| panic!("{}", err));
+        vm.collect();
+        assert_eq!(string, "abc");
+    }
+    vm.collect();
+}
+
+#[test]
+fn root_scope_roots_unreachable_results() {
+    use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+
+    let _ = ::env_logger::init();
+
+    static DROPPED: AtomicBool = ATOMIC_BOOL_INIT;
+
+    #[derive(Debug)]
+    struct Temporary(VmInt);
+    impl Drop for Temporary {
+        fn drop(&mut self) {
+            DROPPED.store(true, Ordering::SeqCst);
+        }
+    }
+    impl Userdata for Temporary {}
+    impl Traverseable for Temporary {}
+    impl VmType for Temporary {
+        type Type = Temporary;
+    }
+
+    fn temporary(i: VmInt) -> Temporary {
+        Temporary(i)
+    }
+
+    let vm = make_vm();
+    vm.register_type::<Temporary>("Temporary", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    add_extern_module(&vm, "temporary", |thread| {
+        ExternModule::new(thread, primitive!(1 temporary))
+    });
+    let expr = r#"
+        let temporary = import! temporary
+        \i -> temporary (i + 1)
+    "#;
+    load_script(&vm, "make_temporary", expr).unwrap_or_else(|err| panic!("{}", err));
+
+    {
+        let scope = vm.root_scope();
+        let mut make_temporary: FunctionRef<fn(VmInt) -> Temporary> =
+            vm.get_global("make_temporary").unwrap();
+        let temporary: &Temporary = make_temporary
+            .call_scoped(&scope, 1)
+            .unwrap_or_else(|err| panic!("{}", err));
+        // Only the scope refers to the result so the collection must not free it
+        vm.collect();
+        assert!(!DROPPED.load(Ordering::SeqCst));
+        assert_eq!(temporary.0, 2);
+    }
+    vm.collect();
+    assert!(DROPPED.load(Ordering::SeqCst));
+}
+
+#[test]
+fn root_scope_collects_during_call_scoped() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let expr = r#"
+        type List a = | Nil | Cons a (List a)
+        let make n l : Int -> List Int -> List Int =
+            if n #Int== 0 then l else make (n #Int- 1) (Cons n l)
+        let loop n : Int -> Int =
+            if n #Int== 0 then 0
+            else
+                match make 100 Nil with
+                | Cons x _ -> loop (n #Int- x)
+                | Nil -> n
+        loop
+    "#;
+    load_script(&vm, "allocate", expr).unwrap_or_else(|err| panic!("{}", err));
+
+    // Each call to `make` allocates a few kilobytes so the calls below allocate far more than the
+    // limit, which is only possible if the garbage is collected while the scope is active
+    let limit = vm.context().gc.allocated_memory() + 100_000;
+    vm.set_memory_limit(limit);
+    {
+        let scope = vm.root_scope();
+        let mut allocate: FunctionRef<fn(VmInt) -> VmInt> = vm.get_global("allocate").unwrap();
+        let result: VmInt = allocate
+            .call_scoped(&scope, 1000)
+            .unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(result, 0);
+    }
+    let stats = vm.context().gc.stats().clone();
+    assert!(stats.minor_collections + stats.major_collections > 0);
+}
+
 #[test]
 fn array() {
     let _ = ::env_logger::init();
@@ -302,3 +559,906 @@ fn tuples_start_at_0() {
         "{ _0 : Int, _1 : Float, _2 : String }"
     );
 }
+
+#[test]
+fn imported_modules() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    let arena = Arena::new();
+    let expr = Compiler::new()
+        .parse_expr(
+            &arena,
+            vm.global_env().type_cache(),
+            "test",
+            r#"
+let { Option } = import! std.option
+let x = import! "examples/lisp/lisp.glu"
+import! std.option
+"#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        gluon::import::imported_modules(&expr),
+        ["examples.lisp.lisp", "std.option"]
+    );
+}
+
+#[test]
+fn module_fingerprints() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    load_script(&vm, "test", "import! std.map").unwrap_or_else(|err| panic!("{}", err));
+
+    let import = vm.get_macros().get("import");
+    let import = import
+        .as_ref()
+        .and_then(|import| import.downcast_ref::<Import>())
+        .expect("Import macro");
+
+    let fingerprint = import.fingerprint("std.map").expect("std.map fingerprint");
+    assert!(
+        fingerprint
+            .dependencies
+            .iter()
+            .any(|&(ref module, _)| module == "std.prelude")
+    );
+
+    let source = import
+        .module_source(&vm, "std.map")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(import.is_up_to_date("std.map", &source));
+    assert!(!import.is_up_to_date("std.map", "{}"));
+}
+
+#[test]
+fn interface_hash_includes_the_definition_of_aliases() {
+    use gluon::import::ModuleFingerprint;
+
+    let fingerprint = |aliased| ModuleFingerprint {
+        source_hash: None,
+        dependencies: Vec::new(),
+        typ: Type::record(
+            Vec::new(),
+            vec![
+                Field::new(
+                    Symbol::from("x"),
+                    Type::alias(Symbol::from("Value"), aliased),
+                ),
+            ],
+        ),
+    };
+    let int = fingerprint(Type::int());
+    let float = fingerprint(Type::float());
+    // Both types are displayed as `{ x : Value }`
+    assert_eq!(int.typ.to_string(), float.typ.to_string());
+    assert_ne!(int.interface_hash(), float.interface_hash());
+    assert_eq!(int.interface_hash(), fingerprint(Type::int()).interface_hash());
+}
+
+#[test]
+fn module_init_and_deinit_hooks() {
+    use std::cell::RefCell;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    let _ = ::env_logger::init();
+
+    thread_local!(static LOG: RefCell<Vec<String>> = RefCell::new(Vec::new()));
+
+    fn log(message: &str) -> IO<()> {
+        LOG.with(|log| log.borrow_mut().push(message.to_string()));
+        IO::Value(())
+    }
+    fn take_log() -> Vec<String> {
+        LOG.with(|log| ::std::mem::replace(&mut *log.borrow_mut(), Vec::new()))
+    }
+
+    let dir = ::std::env::temp_dir().join("gluon_module_hooks");
+    fs::create_dir_all(&dir).unwrap();
+    let write_module = |version: i32| {
+        let source = format!(
+            r#"
+let {{ log }} = import! hook_log
+{{
+    __init = log "init {0}",
+    __deinit = log "deinit {0}",
+    value = {0},
+}}
+"#,
+            version
+        );
+        File::create(dir.join("hooks.glu"))
+            .unwrap()
+            .write_all(source.as_bytes())
+            .unwrap();
+    };
+
+    let vm = make_vm();
+    add_extern_module(&vm, "hook_log", |thread| {
+        ExternModule::new(thread, record!{ log => primitive!(1 log) })
+    });
+    let import = vm.get_macros().get("import");
+    let import = import
+        .as_ref()
+        .and_then(|import| import.downcast_ref::<Import>())
+        .expect("Import macro");
+    import.add_path(&dir);
+
+    write_module(1);
+    let (value, _) = Compiler::new()
+        .run_expr::<i32>(&vm, "test", "(import! hooks).value")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(value, 1);
+    assert_eq!(take_log(), ["init 1"]);
+
+    write_module(2);
+    import
+        .reload_module(&mut Compiler::new(), &vm, "hooks")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(vm.get_global::<i32>("hooks.value"), Ok(2));
+    assert_eq!(take_log(), ["deinit 1", "init 2"]);
+
+    import
+        .unload_module(&vm, "hooks")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(vm.get_global::<i32>("hooks.value").is_err());
+    assert_eq!(take_log(), ["deinit 2"]);
+}
+
+#[test]
+fn unchanged_module_is_not_typechecked_again() {
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use gluon::base::ast::RootExpr;
+    use gluon::import::{DefaultImporter, Importer};
+    use gluon::vm::macros::Error as MacroError;
+
+    let _ = ::env_logger::init();
+
+    /// Records the name of every module which is typechecked
+    #[derive(Clone, Default)]
+    struct RecordingImporter(Arc<Mutex<Vec<String>>>);
+    impl Importer for RecordingImporter {
+        fn import(
+            &self,
+            compiler: &mut Compiler,
+            vm: &Thread,
+            earlier_errors_exist: bool,
+            modulename: &str,
+            input: &str,
+            expr: &mut RootExpr<Symbol>,
+        ) -> Result<(), (Option<ArcType>, MacroError)> {
+            self.0.lock().unwrap().push(modulename.to_string());
+            DefaultImporter.import(compiler, vm, earlier_errors_exist, modulename, input, expr)
+        }
+    }
+
+    let dir = ::std::env::temp_dir().join("gluon_up_to_date");
+    fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join("checked_once.glu"))
+        .unwrap()
+        .write_all(b"{ value = 1 }")
+        .unwrap();
+
+    let vm = make_vm();
+    let recorder = RecordingImporter::default();
+    let import = {
+        let import = vm.get_macros().get("import");
+        import
+            .as_ref()
+            .and_then(|import| import.downcast_ref::<Import>())
+            .expect("Import macro")
+            .with_importer(recorder.clone())
+    };
+    import.add_path(&dir);
+    vm.get_macros().insert(String::from("import"), import);
+    let typechecked = || {
+        recorder
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|module| *module == "checked_once")
+            .count()
+    };
+    let run = || {
+        Compiler::new()
+            .run_expr::<i32>(&vm, "test", "(import! checked_once).value")
+            .unwrap_or_else(|err| panic!("{}", err))
+            .0
+    };
+
+    assert_eq!(run(), 1);
+    assert_eq!(typechecked(), 1);
+
+    let import = vm.get_macros().get("import");
+    let import = import
+        .as_ref()
+        .and_then(|import| import.downcast_ref::<Import<RecordingImporter>>())
+        .expect("Import macro");
+    import
+        .unload_module(&vm, "checked_once")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(run(), 1);
+    assert_eq!(typechecked(), 1);
+
+    File::create(dir.join("checked_once.glu"))
+        .unwrap()
+        .write_all(b"{ value = 2 }")
+        .unwrap();
+    import
+        .reload_module(&mut Compiler::new(), &vm, "checked_once")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(run(), 2);
+    assert_eq!(typechecked(), 2);
+}
+
+#[test]
+fn register_type_with_explicit_kinds() {
+    let _ = ::env_logger::init();
+
+    struct Container;
+
+    let vm = make_vm();
+    let f = Generic::new(Symbol::from("f"), Kind::function(Kind::typ(), Kind::typ()));
+    let opaque = vm.global_env().type_cache().opaque();
+    vm.register_type_as::<Container>(Alias::from(AliasData::new(
+        Symbol::from("Container"),
+        vec![f],
+        opaque,
+    ))).unwrap_or_else(|err| panic!("{}", err));
+
+    let expr = r#"
+        let f : Container Array -> Int = \c -> 1
+        f
+    "#;
+    Compiler::new()
+        .implicit_prelude(false)
+        .typecheck_str(&vm, "test", expr, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let expr = r#"
+        let f : Container Int -> Int = \c -> 1
+        f
+    "#;
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .typecheck_str(&vm, "test", expr, None);
+    assert!(result.is_err());
+}
+
+#[test]
+fn register_record_type() {
+    let _ = ::env_logger::init();
+
+    struct Point;
+
+    let vm = make_vm();
+    let int = vm.global_env().type_cache().int();
+    let point = Type::record(
+        vec![],
+        vec![
+            Field {
+                name: Symbol::from("x"),
+                typ: int.clone(),
+            },
+            Field {
+                name: Symbol::from("y"),
+                typ: int,
+            },
+        ],
+    );
+    vm.register_type_as::<Point>(Alias::from(AliasData::new(
+        Symbol::from("Point"),
+        vec![],
+        point,
+    ))).unwrap_or_else(|err| panic!("{}", err));
+
+    let expr = r#"
+        { x = 1, y = 2 }
+    "#;
+    let (_, typ) = Compiler::new()
+        .implicit_prelude(false)
+        .typecheck_str(&vm, "test", expr, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(typ.to_string(), "Point");
+}
+
+#[test]
+fn register_variant_type() {
+    let _ = ::env_logger::init();
+
+    struct Shape;
+
+    let vm = make_vm();
+    let name = Symbol::from("Shape");
+    let a = Generic::new(Symbol::from("a"), Kind::typ());
+    let shape: ArcType = Type::app(
+        Type::ident(name.clone()),
+        vec![Type::generic(a.clone())].into_iter().collect(),
+    );
+    let variant = Type::variant(vec![
+        Field {
+            name: Symbol::from("Circle"),
+            typ: Type::function(vec![Type::generic(a.clone())], shape.clone()),
+        },
+        Field {
+            name: Symbol::from("Rectangle"),
+            typ: Type::function(
+                vec![Type::generic(a.clone()), Type::generic(a.clone())],
+                shape,
+            ),
+        },
+    ]);
+    vm.register_type_as::<Shape>(Alias::from(AliasData::new(
+        name,
+        vec![a],
+        variant,
+    ))).unwrap_or_else(|err| panic!("{}", err));
+
+    let expr = r#"
+        let area shape : Shape Int -> Int =
+            match shape with
+            | Circle r -> 3 #Int* r #Int* r
+            | Rectangle w h -> w #Int* h
+        area (Rectangle 2 3) #Int+ area (Circle 1)
+    "#;
+    let (result, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<VmInt>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 9);
+}
+
+#[test]
+fn marshal_data_carrying_enum() {
+    use gluon::vm::api::{Getable, Pushable, ValueRef};
+    use gluon::vm::thread::Context;
+    use gluon::vm::{self, Variants};
+
+    let _ = ::env_logger::init();
+
+    field_decl! { w, h }
+    type Dimensions = record_type!(w => VmInt, h => VmInt);
+
+    #[derive(Debug, PartialEq)]
+    enum Shape {
+        Circle(VmInt),
+        Rectangle { w: VmInt, h: VmInt },
+    }
+
+    impl VmType for Shape {
+        type Type = Self;
+
+        fn make_type(vm: &Thread) -> ArcType {
+            vm.find_type_info("shape.Shape").unwrap().into_type()
+        }
+    }
+
+    impl<'vm> Pushable<'vm> for Shape {
+        fn push(self, vm: &'vm Thread, context: &mut Context) -> vm::Result<()> {
+            let tag = match self {
+                Shape::Circle(r) => {
+                    r.push(vm, context)?;
+                    0
+                }
+                Shape::Rectangle { w, h } => {
+                    record_no_decl!(w => w, h => h).push(vm, context)?;
+                    1
+                }
+            };
+            context.push_new_data(vm, tag, 1)
+        }
+    }
+
+    impl<'vm> Getable<'vm> for Shape {
+        fn from_value(vm: &'vm Thread, value: Variants) -> Shape {
+            match value.as_ref() {
+                ValueRef::Data(data) => {
+                    let arg = data.get_variant(0).unwrap();
+                    match data.tag() {
+                        0 => Shape::Circle(VmInt::from_value(vm, arg)),
+                        _ => match Dimensions::from_value(vm, arg) {
+                            record_p!(w, h) => Shape::Rectangle { w, h },
+                        },
+                    }
+                }
+                _ => panic!("Expected a Shape"),
+            }
+        }
+    }
+
+    let vm = make_vm();
+    let text = r#"
+        type Shape = | Circle Int | Rectangle { w : Int, h : Int }
+        let scale x shape : Int -> Shape -> Shape =
+            match shape with
+            | Circle r -> Circle (r #Int* x)
+            | Rectangle { w, h } -> Rectangle { w = w #Int* x, h = h #Int* x }
+        { Shape, scale }
+    "#;
+    load_script(&vm, "shape", text).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut scale: FunctionRef<fn(VmInt, Shape) -> Shape> = vm.get_global("shape.scale").unwrap();
+    assert_eq!(scale.call(2, Shape::Circle(3)), Ok(Shape::Circle(6)));
+    assert_eq!(
+        scale.call(3, Shape::Rectangle { w: 1, h: 2 }),
+        Ok(Shape::Rectangle { w: 3, h: 6 })
+    );
+}
+
+#[test]
+fn scoped_reference_is_invalid_after_its_scope() {
+    use gluon::vm::api::scoped::{self, Scoped};
+    use gluon::vm::api::{OwnedFunction, RuntimeResult};
+
+    let _ = ::env_logger::init();
+
+    fn sum(values: &Scoped<Vec<VmInt>>) -> RuntimeResult<VmInt, Error> {
+        values.with(|values| values.iter().sum()).into()
+    }
+
+    let vm = make_vm();
+    vm.register_type::<Scoped<Vec<VmInt>>>("Values", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    add_extern_module(&vm, "sum", |thread| {
+        ExternModule::new(thread, primitive!(1 sum))
+    });
+    let text = r#"
+        let sum = import! sum
+        let capture values : Values -> (() -> Int) = \_ -> sum values
+        capture
+    "#;
+    load_script(&vm, "capture", text).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut capture: FunctionRef<fn(Scoped<Vec<VmInt>>) -> OwnedFunction<fn(()) -> VmInt>> =
+        vm.get_global("capture").unwrap();
+    let values = vec![1, 2, 3];
+    let mut stored = scoped::scope(&values, |values| {
+        let mut stored = capture.call(values).unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(stored.call(()), Ok(6));
+        stored
+    });
+    match stored.call(()) {
+        Err(Error::Panic(ref message)) if message.contains("scope ended") => (),
+        result => panic!("Expected the stored reference to be invalid: {:?}", result),
+    }
+}
+
+#[test]
+fn phantom_type_parameters_defined_in_gluon() {
+    use gluon::vm::api::{generic, Phantom};
+
+    let _ = ::env_logger::init();
+
+    #[derive(Debug)]
+    struct Handle(VmInt);
+    impl Userdata for Handle {}
+    impl Traverseable for Handle {}
+    impl VmType for Handle {
+        type Type = Handle;
+    }
+
+    gluon_type!(Player = "phantom_types.Player");
+
+    fn make_handle(id: VmInt) -> Phantom<Handle, generic::A> {
+        Phantom::new(Handle(id))
+    }
+    fn player_id(handle: Phantom<&Handle, Player>) -> VmInt {
+        (handle.0).0
+    }
+
+    let vm = make_vm();
+    vm.register_type::<Handle>("Handle", &["a"])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    add_extern_module(&vm, "handle", |thread| {
+        ExternModule::new(
+            thread,
+            record!{
+                make_handle => primitive!(1 make_handle),
+                player_id => primitive!(1 player_id)
+            },
+        )
+    });
+
+    let text = r#"
+        type Player = | Player
+        type Item = | Item
+        { Player, Item }
+    "#;
+    load_script(&vm, "phantom_types", text).unwrap_or_else(|err| panic!("{}", err));
+
+    let text = r#"
+        let { make_handle, player_id } = import! handle
+        let { Player } = phantom_types
+        let player : Handle Player = make_handle 12
+        player_id player
+    "#;
+    let (result, _) = Compiler::new()
+        .run_expr::<VmInt>(&vm, "player", text)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 12);
+
+    let text = r#"
+        let { make_handle, player_id } = import! handle
+        let { Item } = phantom_types
+        let item : Handle Item = make_handle 12
+        player_id item
+    "#;
+    match Compiler::new().run_expr::<VmInt>(&vm, "item", text) {
+        Err(::gluon::Error::Typecheck(_)) => (),
+        result => panic!("Expected a type error but got {:?}", result),
+    }
+}
+
+#[test]
+fn check_module_signature() {
+    use gluon::check::typecheck::TypeError;
+
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let signature: ArcType = Type::record(
+        vec![],
+        vec![
+            Field {
+                name: Symbol::from("name"),
+                typ: Type::string(),
+            },
+            Field {
+                name: Symbol::from("run"),
+                typ: Type::function(vec![Type::int()], Type::int()),
+            },
+        ],
+    );
+
+    let text = r#"
+        let run x = x + 1
+        { name = "increment", run, extra = 1.0 }
+    "#;
+    load_script(&vm, "plugin", text).unwrap_or_else(|err| panic!("{}", err));
+    Compiler::new()
+        .check_signature(&vm, "plugin", &signature)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let text = r#"
+        { name = 1, extra = 1.0 }
+    "#;
+    load_script(&vm, "broken_plugin", text).unwrap_or_else(|err| panic!("{}", err));
+    match Compiler::new().check_signature(&vm, "broken_plugin", &signature) {
+        Err(::gluon::Error::Typecheck(err)) => {
+            let errors: Vec<_> = err.errors().into_iter().collect();
+            assert_eq!(errors.len(), 1);
+            match errors[0].value.error {
+                TypeError::Unification(_, _, ref errors, _) => assert_eq!(errors.len(), 2),
+                ref err => panic!("Expected a unification error: {}", err),
+            }
+        }
+        result => panic!("Expected a type error but got {:?}", result),
+    }
+}
+
+/// Deterministic pseudo random numbers so that the round trip tests can cover many values without
+/// depending on a random number generator
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> VmInt {
+        self.0 = self.0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) as VmInt - (1 << 30)
+    }
+}
+
+#[test]
+fn vec_and_array_round_trip() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    load_script(
+        &vm,
+        "id_array",
+        r"let id : Array Int -> Array Int = \x -> x in id",
+    ).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut rng = Lcg(1);
+    let mut id: FunctionRef<fn(Vec<VmInt>) -> Vec<VmInt>> = vm.get_global("id_array").unwrap();
+    for len in 0..50 {
+        let values = (0..len).map(|_| rng.next()).collect::<Vec<_>>();
+        assert_eq!(id.call(values.clone()), Ok(values));
+    }
+
+    let mut id: FunctionRef<fn([VmInt; 3]) -> [VmInt; 3]> = vm.get_global("id_array").unwrap();
+    for _ in 0..50 {
+        let values = [rng.next(), rng.next(), rng.next()];
+        assert_eq!(id.call(values), Ok(values));
+    }
+}
+
+#[test]
+fn map_round_trip() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    let text = r#"
+        let { Map } = import! std.map
+        let id : Map String Int -> Map String Int = \x -> x
+        id
+    "#;
+    load_script(&vm, "id_map", text).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut rng = Lcg(2);
+    let mut id: FunctionRef<fn(BTreeMap<String, VmInt>) -> BTreeMap<String, VmInt>> =
+        vm.get_global("id_map").unwrap();
+    for len in 0..50 {
+        let map = (0..len)
+            .map(|_| (rng.next().to_string(), rng.next()))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(id.call(map.clone()), Ok(map));
+    }
+
+    let mut id: FunctionRef<fn(HashMap<String, VmInt>) -> HashMap<String, VmInt>> =
+        vm.get_global("id_map").unwrap();
+    for len in 0..50 {
+        let map = (0..len)
+            .map(|_| (rng.next().to_string(), rng.next()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(id.call(map.clone()), Ok(map));
+    }
+}
+
+#[test]
+fn pushed_map_is_usable_from_gluon() {
+    let _ = ::env_logger::init();
+    let vm = make_vm();
+    let text = r#"
+        let { Map, make } = import! std.map
+        let { ord } = import! std.int
+        let { find, insert } = make ord
+        let find_or_insert k m : Int -> Map Int String -> Map Int String =
+            match find k m with
+            | Some _ -> m
+            | None -> insert k "inserted" m
+        find_or_insert
+    "#;
+    load_script(&vm, "find_or_insert", text).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut find_or_insert: FunctionRef<
+        fn(VmInt, BTreeMap<VmInt, String>) -> BTreeMap<VmInt, String>,
+    > = vm.get_global("find_or_insert").unwrap();
+    let map = (0..100)
+        .map(|i| (i * 2, i.to_string()))
+        .collect::<BTreeMap<_, _>>();
+
+    assert_eq!(find_or_insert.call(10, map.clone()), Ok(map.clone()));
+
+    let mut expected = map.clone();
+    expected.insert(11, "inserted".to_string());
+    assert_eq!(find_or_insert.call(11, map), Ok(expected));
+}
+
+#[test]
+fn forked_thread_has_isolated_globals() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    load_script(&vm, "config", r#" { name = "root" } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let fork = vm.fork().unwrap();
+    assert!(fork.has_isolated_globals());
+    assert!(!vm.has_isolated_globals());
+
+    // Modules loaded before forking are visible to the fork
+    let name: String = fork.get_global("config.name").unwrap();
+    assert_eq!(name, "root");
+
+    load_script(&fork, "request", r#" { id = 1 } "#).unwrap_or_else(|err| panic!("{}", err));
+    assert!(fork.global_exists("request"));
+    assert!(!vm.global_exists("request"));
+
+    // Threads spawned from the fork share its globals
+    let child = fork.new_thread().unwrap();
+    assert!(child.has_isolated_globals());
+    let id: VmInt = child.get_global("request.id").unwrap();
+    assert_eq!(id, 1);
+
+    let expr = r#" let { id } = import! request in id + 1 "#;
+    let (result, _) = Compiler::new()
+        .run_expr::<VmInt>(&fork, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 2);
+    assert!(Compiler::new().run_expr::<VmInt>(&vm, "test", expr).is_err());
+
+    // Reloading a module in the fork leaves the shared module untouched
+    assert!(fork.remove_global("config"));
+    load_script(&fork, "config", r#" { name = "fork" } "#)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let name: String = fork.get_global("config.name").unwrap();
+    assert_eq!(name, "fork");
+    let name: String = vm.get_global("config.name").unwrap();
+    assert_eq!(name, "root");
+}
+
+#[test]
+fn primitives_can_access_host_context() {
+    let _ = ::env_logger::init();
+
+    struct RequestId(VmInt);
+
+    fn request_id(unit: WithVM<()>) -> VmInt {
+        unit.vm
+            .host_context::<RequestId>()
+            .map_or(-1, |id| id.0)
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "request", |thread| {
+        ExternModule::new(thread, primitive!(1 request_id))
+    });
+
+    let text = r#"
+        let request_id = import! request
+        \x -> request_id () + x
+    "#;
+    load_script(&vm, "handler", text).unwrap_or_else(|err| panic!("{}", err));
+    let mut handler: FunctionRef<fn(VmInt) -> VmInt> = vm.get_global("handler").unwrap();
+
+    assert_eq!(
+        vm.call_with_context(RequestId(10), || handler.call(1)),
+        Ok(11)
+    );
+    assert_eq!(
+        vm.call_with_context(RequestId(1), || {
+            vm.call_with_context(RequestId(2), || handler.call(0))
+        }),
+        Ok(2)
+    );
+    assert_eq!(handler.call(0), Ok(-1));
+}
+
+#[test]
+fn build_values_with_value_builder() {
+    use gluon::vm::api::builder::ValueBuilder;
+    use gluon::vm::api::OpaqueValue;
+
+    let _ = ::env_logger::init();
+
+    struct Config;
+
+    impl VmType for Config {
+        type Type = Self;
+
+        fn make_type(vm: &Thread) -> ArcType {
+            vm.find_type_info("config.Config").unwrap().into_type()
+        }
+    }
+
+    fn double(x: VmInt) -> VmInt {
+        x * 2
+    }
+
+    let vm = make_vm();
+    let text = r#"
+        let array = import! std.array.prim
+        type Mode = | Debug | Release Int
+        type Config = { name : String, mode : Mode, ports : Array Int, scale : Int -> Int }
+        let level config : Config -> Int =
+            let level =
+                match config.mode with
+                | Debug -> 0
+                | Release level -> level
+            config.scale (level #Int+ array.len config.ports)
+        { Mode, Config, level }
+    "#;
+    load_script(&vm, "config", text).unwrap_or_else(|err| panic!("{}", err));
+
+    let typ = Config::make_type(&vm);
+    let config = ValueBuilder::record(vec![
+        ("ports", ValueBuilder::array(vec![ValueBuilder::int(80), ValueBuilder::int(443)])),
+        ("scale", ValueBuilder::function(primitive!(1 double))),
+        ("name", ValueBuilder::string("server")),
+        ("mode", ValueBuilder::variant("Release", vec![ValueBuilder::int(3)])),
+    ]).build(&vm, &typ)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let mut level: FunctionRef<fn(OpaqueValue<_, Config>) -> VmInt> =
+        vm.get_global("config.level").unwrap();
+    assert_eq!(level.call(OpaqueValue::from_value(config)), Ok(10));
+
+    let result = ValueBuilder::record(vec![
+        ("ports", ValueBuilder::array(vec![ValueBuilder::string("80")])),
+        ("scale", ValueBuilder::function(primitive!(1 double))),
+        ("name", ValueBuilder::string("server")),
+        ("mode", ValueBuilder::variant("Debug", vec![])),
+    ]).build(&vm, &typ);
+    match result {
+        Err(Error::WrongType(..)) => (),
+        result => panic!("Expected a type error: {:?}", result),
+    }
+}
+
+#[test]
+fn linear_algebra_values_are_passed_to_and_from_gluon() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let expr = r#"
+let { quat, mat4 } = import! std.math.linear
+\angle v ->
+    let rotation = mat4.from_quat (quat.from_axis_angle { x = 0.0, y = 0.0, z = 1.0 } angle)
+    let transform = mat4.mul (mat4.translation { x = 1.0, y = 2.0, z = 3.0 }) rotation
+    match mat4.inverse transform with
+    | Some inverse -> mat4.mul_vec inverse (mat4.mul_vec transform v)
+    | None -> error "Expected the transform to be invertible"
+"#;
+    let (mut transform, _) = Compiler::new()
+        .run_expr::<FunctionRef<fn(f64, Vec4) -> Vec4>>(&vm, "test", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let v = Vec4 {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+    let result = transform
+        .call(::std::f64::consts::FRAC_PI_2, v)
+        .unwrap_or_else(|err| panic!("{}", err));
+    for &(actual, expected) in &[
+        (result.x, v.x),
+        (result.y, v.y),
+        (result.z, v.z),
+        (result.w, v.w),
+    ] {
+        assert!((actual - expected).abs() < 1e-9, "{:?} != {:?}", result, v);
+    }
+}
+
+#[test]
+fn forked_thread_sees_globals_loaded_by_its_parent_after_the_fork() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    let fork = vm.fork().unwrap();
+    let nested_fork = fork.fork().unwrap();
+
+    load_script(&vm, "late", r#" { value = 10 } "#).unwrap_or_else(|err| panic!("{}", err));
+    let value: VmInt = fork.get_global("late.value").unwrap();
+    assert_eq!(value, 10);
+    let value: VmInt = nested_fork.get_global("late.value").unwrap();
+    assert_eq!(value, 10);
+
+    // A global of the fork shadows the global of its parent
+    load_script(&fork, "late", r#" { value = 20 } "#).unwrap_or_else(|err| panic!("{}", err));
+    let value: VmInt = fork.get_global("late.value").unwrap();
+    assert_eq!(value, 20);
+    let value: VmInt = nested_fork.get_global("late.value").unwrap();
+    assert_eq!(value, 20);
+    let value: VmInt = vm.get_global("late.value").unwrap();
+    assert_eq!(value, 10);
+}
+
+#[test]
+fn forked_thread_registers_types_privately() {
+    let _ = ::env_logger::init();
+
+    struct Private;
+
+    let vm = make_vm();
+    let fork = vm.fork().unwrap();
+
+    fork.register_type::<Private>("Private", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    assert!(fork.find_type_info("Private").is_ok());
+    assert!(vm.find_type_info("Private").is_err());
+    assert!(fork.register_type::<Private>("Private", &[]).is_err());
+
+    // Types registered by the parent are visible to the fork and can not be registered again
+    struct Shared;
+    vm.register_type::<Shared>("Shared", &[])
+        .unwrap_or_else(|_| panic!("Could not add type"));
+    assert!(fork.find_type_info("Shared").is_ok());
+    assert!(fork.register_type::<Shared>("Shared", &[]).is_err());
+}
@@ -107,3 +107,23 @@ array.foldable.foldl (\x y -> y.x) 0 [{ x = 4 }]
 "#,
 4
 }
+
+test_expr!{ array_generate,
+r#"
+let prim = import! std.array.prim
+let xs = prim.generate 1000 (\i -> { x = i #Int* 2 })
+(prim.index xs 0).x #Int+ (prim.index xs 999).x #Int+ prim.len xs
+"#,
+2998i32
+}
+
+test_expr!{ sized_array_zip,
+r#"
+let sized = import! std.array.sized
+let xs : sized.Array 3 Float = sized.concat (sized.singleton 1.0) (sized.push (sized.singleton 2.0) 3.0)
+let ys : sized.Array 3 Float = sized.push (sized.push (sized.singleton 4.0) 5.0) 6.0
+let dot = sized.foldable.foldl (\acc x -> acc #Float+ x) 0.0 (sized.zip_with (\x y -> x #Float* y) xs ys)
+dot
+"#,
+32.0f64
+}
@@ -0,0 +1,200 @@
+extern crate env_logger;
+extern crate gluon;
+
+use gluon::vm::compiler::CompiledFunction;
+use gluon::vm::types::Instruction;
+use gluon::vm::types::Instruction::*;
+use gluon::{new_vm, Compiler};
+
+fn compile(source: &str) -> CompiledFunction {
+    let _ = env_logger::init();
+
+    let vm = new_vm();
+    let mut compiler = Compiler::new().implicit_prelude(false);
+    let (expr, _) = compiler
+        .typecheck_str(&vm, "test", source, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+    compiler
+        .compile_script(&vm, "test", source, &expr)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .function
+}
+
+fn count<F>(instructions: &[Instruction], f: F) -> usize
+where
+    F: Fn(&Instruction) -> bool,
+{
+    instructions.iter().filter(|instr| f(instr)).count()
+}
+
+fn is_call(instr: &Instruction) -> bool {
+    match *instr {
+        Call(_) | TailCall(_) => true,
+        _ => false,
+    }
+}
+
+fn is_conditional_jump(instr: &Instruction) -> bool {
+    match *instr {
+        CJump(_) => true,
+        _ => false,
+    }
+}
+
+#[test]
+fn and_compiles_to_branch() {
+    let source = r#"
+let f x : Int -> _ = 0 #Int< x && x #Int< 10
+f
+"#;
+    let function = compile(source);
+    let instructions = &function.inner_functions[0].instructions;
+
+    assert_eq!(count(instructions, is_call), 0, "{:?}", instructions);
+    assert_eq!(count(instructions, is_conditional_jump), 1, "{:?}", instructions);
+}
+
+#[test]
+fn or_compiles_to_branch() {
+    let source = r#"
+let f x : Int -> _ = x #Int< 0 || 10 #Int< x
+f
+"#;
+    let function = compile(source);
+    let instructions = &function.inner_functions[0].instructions;
+
+    assert_eq!(count(instructions, is_call), 0, "{:?}", instructions);
+    assert_eq!(count(instructions, is_conditional_jump), 1, "{:?}", instructions);
+}
+
+#[test]
+fn chained_comparison_evaluates_middle_operand_once() {
+    let source = r#"
+let f g : (Int -> Int) -> _ = 0 #Int< g 1 #Int< 10
+f
+"#;
+    let function = compile(source);
+    let instructions = &function.inner_functions[0].instructions;
+
+    assert_eq!(count(instructions, is_call), 1, "{:?}", instructions);
+}
+
+#[test]
+fn chained_comparison_short_circuits() {
+    let source = r#"
+let f x : Int -> _ = 0 #Int< x #Int< 10
+f
+"#;
+    let function = compile(source);
+    let instructions = &function.inner_functions[0].instructions;
+
+    assert_eq!(count(instructions, is_call), 0, "{:?}", instructions);
+    assert_eq!(count(instructions, is_conditional_jump), 1, "{:?}", instructions);
+    assert_eq!(
+        count(instructions, |instr| *instr == IntLT),
+        2,
+        "{:?}",
+        instructions
+    );
+}
+
+#[test]
+fn recursive_call_in_tail_position_is_a_tail_call() {
+    let source = r#"
+let f n acc : Int -> Int -> Int = if n #Int== 0 then acc else f (n #Int- 1) (acc #Int+ n)
+f
+"#;
+    let function = compile(source);
+    let instructions = &function.inner_functions[0].instructions;
+
+    assert_eq!(
+        count(instructions, |instr| *instr == TailCall(2)),
+        1,
+        "{:?}",
+        instructions
+    );
+    assert_eq!(
+        count(instructions, |instr| *instr == Call(2)),
+        0,
+        "{:?}",
+        instructions
+    );
+}
+
+#[test]
+fn recursive_call_outside_of_tail_position_is_a_call() {
+    let source = r#"
+let f n : Int -> Int = if n #Int== 0 then 0 else n #Int+ f (n #Int- 1)
+f
+"#;
+    let function = compile(source);
+    let instructions = &function.inner_functions[0].instructions;
+
+    assert_eq!(
+        count(instructions, |instr| *instr == Call(1)),
+        1,
+        "{:?}",
+        instructions
+    );
+    assert_eq!(
+        count(instructions, |instr| match *instr {
+            TailCall(_) => true,
+            _ => false,
+        }),
+        0,
+        "{:?}",
+        instructions
+    );
+}
+
+/// Returns `true` if `function` or any of the functions defined in it constructs a variant or a
+/// record
+fn constructs_data(function: &CompiledFunction) -> bool {
+    function.instructions.iter().any(|instr| match *instr {
+        Construct { .. } | ConstructRecord { .. } => true,
+        _ => false,
+    }) || function.inner_functions.iter().any(constructs_data)
+}
+
+/// Returns `true` if a function defined in `function` makes a tail call, as the loop of `fold` does
+fn defines_loop(function: &CompiledFunction) -> bool {
+    function.inner_functions.iter().any(|inner| {
+        count(&inner.instructions, |instr| match *instr {
+            TailCall(_) => true,
+            _ => false,
+        }) != 0 || defines_loop(inner)
+    })
+}
+
+#[test]
+fn imported_iterator_functions_are_fused() {
+    let source = r#"
+let { fold, map, array } = import! std.iter
+let sum xs : Array Int -> Int = fold (\acc x -> acc #Int+ x) 0 (array xs)
+let sum_doubled xs : Array Int -> Int =
+    fold (\acc x -> acc #Int+ x) 0 (map (\x -> x #Int* 2) (array xs))
+{ sum, sum_doubled }
+"#;
+    let function = compile(source);
+    let sum = &function.inner_functions[0];
+    let sum_doubled = &function.inner_functions[1];
+
+    assert!(defines_loop(sum), "{:#?}", sum);
+    assert!(!constructs_data(sum), "{:#?}", sum);
+    assert!(defines_loop(sum_doubled), "{:#?}", sum_doubled);
+    assert!(!constructs_data(sum_doubled), "{:#?}", sum_doubled);
+}
+
+#[test]
+fn iterator_functions_of_a_module_variable_are_fused() {
+    let source = r#"
+let iter = import! std.iter
+let sum xs : Array Int -> Int = iter.fold (\acc x -> acc #Int+ x) 0 (iter.array xs)
+sum
+"#;
+    let function = compile(source);
+    let sum = &function.inner_functions[0];
+
+    assert!(defines_loop(sum), "{:#?}", sum);
+    assert!(!constructs_data(sum), "{:#?}", sum);
+}
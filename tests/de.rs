@@ -207,3 +207,37 @@ fn enum_() {
         .unwrap_or_else(|err| panic!("{}", err));
     assert_eq!(enum_, Enum::C(0, 1));
 }
+
+#[test]
+fn enum_struct_variant_from_record() {
+    let _ = env_logger::init();
+
+    let thread = new_vm();
+    Compiler::new()
+        .implicit_prelude(false)
+        .load_script(
+            &thread,
+            "test",
+            r#"
+                type Enum = | A String | B { string : String, test : Float } | C Int Int
+                { Enum }
+            "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let (De(enum_), _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<De<Enum>>(
+            &thread,
+            "test",
+            r#" let { Enum } = import! "test" in B { string = "abc", test = 1.5 } "#,
+        )
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(
+        enum_,
+        Enum::B {
+            string: "abc".to_string(),
+            test: 1.5,
+        }
+    );
+}
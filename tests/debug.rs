@@ -1,3 +1,5 @@
+// The hooks are not called for functions executed as register instructions
+#![cfg(all(feature = "debug", not(feature = "register_vm")))]
 #[macro_use]
 extern crate collect_mac;
 extern crate env_logger;
@@ -0,0 +1,120 @@
+#![cfg(feature = "jit")]
+extern crate env_logger;
+extern crate gluon;
+
+mod support;
+
+use gluon::Compiler;
+use gluon::vm::jit;
+
+use support::make_vm;
+
+fn run_int(name: &str, expr: &str) -> i32 {
+    let vm = make_vm();
+    let (result, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i32>(&vm, name, expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    result
+}
+
+#[test]
+fn hot_int_function_is_compiled() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+let clamp x : Int -> Int =
+    if x #Int< 0 then 0 else if 100 #Int< x then 100 else x
+let loop n acc : Int -> Int -> Int =
+    if n #Int== 3000 then acc
+    else loop (n #Int+ 1) (acc #Int+ clamp (n #Int- 1500))
+loop 0 0
+"#;
+    let expected = (0..3000)
+        .map(|n| ::std::cmp::min(::std::cmp::max(n - 1500, 0), 100))
+        .sum::<i32>();
+    assert_eq!(run_int("clamp", expr), expected);
+    assert!(
+        jit::compiled_functions().iter().any(|name| name == "clamp"),
+        "{:?}",
+        jit::compiled_functions()
+    );
+}
+
+#[test]
+fn compiled_function_returning_bool() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+let is_small x : Int -> Bool = x #Int< 10
+let loop n acc : Int -> Int -> Int =
+    if n #Int== 2000 then acc
+    else loop (n #Int+ 1) (if is_small (n #Int- 1000) then acc #Int+ 1 else acc)
+loop 0 0
+"#;
+    assert_eq!(run_int("is_small", expr), 1010);
+    assert!(jit::compiled_functions().iter().any(|name| name == "is_small"));
+}
+
+#[test]
+fn unsupported_functions_are_interpreted() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+let half x : Float -> Float = x #Float/ 2.0
+let loop n acc : Int -> Float -> Float =
+    if n #Int== 2000 then acc
+    else loop (n #Int+ 1) (half acc #Float+ 1.0)
+loop 0 0.0
+"#;
+    let vm = make_vm();
+    let (result, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<f64>(&vm, "half", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, 2.0);
+    assert!(!jit::compiled_functions().iter().any(|name| name == "half"));
+}
+
+#[test]
+fn self_tail_call_is_compiled() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+let count_down n acc : Int -> Int -> Int =
+    if n #Int== 0 then acc else count_down (n #Int- 1) (acc #Int+ 2)
+let loop n acc : Int -> Int -> Int =
+    if n #Int== 2000 then acc
+    else loop (n #Int+ 1) (acc #Int+ count_down 10 0)
+loop 0 0
+"#;
+    assert_eq!(run_int("count_down", expr), 40000);
+    assert!(
+        jit::compiled_functions().iter().any(|name| name == "count_down"),
+        "{:?}",
+        jit::compiled_functions()
+    );
+}
+
+#[test]
+fn overflow_wraps_before_and_after_compilation() {
+    let _ = ::env_logger::init();
+
+    // `big 2` overflows, the interpreted calls before `HOT_CALLS` and the compiled calls after it
+    // must agree on the wrapped result
+    let expr = r#"
+let big x : Int -> Int = x #Int* 4611686018427387904 #Int+ 1
+let first = big 2
+let loop n mismatches : Int -> Int -> Int =
+    if n #Int== 2000 then mismatches
+    else loop (n #Int+ 1) (if big 2 #Int== first then mismatches else mismatches #Int+ 1)
+if loop 0 0 #Int== 0 then first else 0
+"#;
+    let vm = make_vm();
+    let (result, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i64>(&vm, "big", expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result, i64::min_value() + 1);
+    assert!(jit::compiled_functions().iter().any(|name| name == "big"));
+}
@@ -1,12 +1,19 @@
 extern crate env_logger;
+extern crate futures;
 extern crate gluon;
+#[macro_use]
+extern crate gluon_vm;
 
 mod support;
 
+use futures::future;
+
 use gluon::{Compiler, Error, Thread};
-use gluon::vm::Error as VMError;
-use gluon::vm::api::{Hole, OpaqueValue};
-use gluon::vm::thread::ThreadInternal;
+use gluon::import::add_extern_module;
+use gluon::vm::{Error as VMError, ExternModule};
+use gluon::vm::api::{Hole, OpaqueValue, OwnedFunction, IO};
+use gluon::vm::types::VmInt;
+use gluon::vm::thread::{FuelResult, ThreadInternal};
 
 use support::make_vm;
 
@@ -50,3 +57,208 @@ fn stack_overflow() {
         Ok(_) => panic!("Expected an error"),
     }
 }
+
+#[test]
+fn tail_calls_run_in_constant_stack_space() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    vm.context().set_max_stack_size(1000);
+
+    let expr = r#"
+let f n acc : Int -> Int -> Int = if n #Int== 0 then acc else g (n #Int- 1) (acc #Int+ 1)
+and g n acc : Int -> Int -> Int = f n acc
+f 100000 0
+"#;
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr_async::<i32>(&vm, "example", expr)
+        .sync_or_error();
+
+    match result {
+        Ok((value, _)) => assert_eq!(value, 100000),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+    }
+}
+
+#[test]
+fn calls_outside_of_tail_position_overflow() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    vm.context().set_max_stack_size(1000);
+
+    let expr = r#"
+let sum n : Int -> Int = if n #Int== 0 then 0 else n #Int+ sum (n #Int- 1)
+sum 100000
+"#;
+    let result = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr_async::<i32>(&vm, "example", expr)
+        .sync_or_error();
+
+    match result {
+        Err(Error::VM(VMError::StackOverflow(1000))) => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected an error"),
+    }
+}
+
+#[test]
+fn out_of_memory_can_be_caught() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    // Load the modules before setting the limit so that only the action counts against it
+    Compiler::new()
+        .run_expr_async::<OpaqueValue<&Thread, Hole>>(&vm, "load", "import! std.io")
+        .sync_or_error()
+        .unwrap_or_else(|err| panic!("{}", err));
+    let loaded = vm.context().gc.allocated_memory();
+    vm.set_memory_limit(loaded + 100_000);
+
+    let text = r#"
+        let prelude = import! std.prelude
+        let io @ { applicative, monad } = import! std.io
+        let { wrap } = prelude.make_Applicative applicative
+        let { (>>=) } = prelude.make_Monad monad
+
+        type List a = | Nil | Cons a (List a)
+        let build n acc : Int -> List Int -> List Int =
+            if n == 0 then acc else build (n - 1) (Cons n acc)
+
+        let action =
+            wrap () >>= \_ ->
+                match build 10000 Nil with
+                | Nil -> wrap "empty"
+                | Cons _ _ -> wrap "built"
+        io.catch action (\_ -> wrap "caught")
+    "#;
+    let result = Compiler::new()
+        .run_io(true)
+        .run_expr::<IO<String>>(&vm, "example", text);
+
+    match result {
+        Ok((IO::Value(value), _)) => assert_eq!(value, "caught"),
+        Ok((IO::Exception(err), _)) => panic!("Unexpected exception `{}`", err),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+    }
+}
+
+#[test]
+fn run_with_fuel_suspends_and_resumes_execution() {
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+
+    let expr = r#"
+let f n acc : Int -> Int -> Int = if n #Int== 0 then acc else f (n #Int- 1) (acc #Int+ 1)
+f 1000 0
+"#;
+    // Compiling and running starts when the future is created so it must be delayed until the
+    // first poll to let the fuel limit the execution
+    let mut future = future::lazy(|| {
+        Compiler::new()
+            .implicit_prelude(false)
+            .run_expr_async::<i32>(&vm, "example", expr)
+    });
+
+    let mut suspensions = 0;
+    let (value, _) = loop {
+        match vm.run_with_fuel(100, future) {
+            Ok(FuelResult::Done(value)) => break value,
+            Ok(FuelResult::Suspended(suspended)) => {
+                suspensions += 1;
+                future = suspended;
+            }
+            Err(err) => panic!("Unexpected error `{:?}`", err),
+        }
+    };
+    assert_eq!(value, 1000);
+    // Each iteration of `f` executes several instructions
+    assert!(suspensions >= 10, "Suspended {} times", suspensions);
+}
+
+#[test]
+fn run_with_fuel_stops_nested_synchronous_calls() {
+    let _ = ::env_logger::init();
+
+    fn apply(mut f: OwnedFunction<fn(VmInt) -> VmInt>, x: VmInt) -> VmInt {
+        // The error is ignored on purpose, the execution must stop regardless
+        f.call(x).unwrap_or(0)
+    }
+
+    let vm = make_vm();
+    add_extern_module(&vm, "apply", |thread| {
+        ExternModule::new(thread, primitive!(2 apply))
+    });
+
+    let expr = r#"
+let apply = import! apply
+let f n : Int -> Int = if n #Int== 0 then 0 else f (n #Int- 1)
+apply f 100000
+"#;
+    let future = future::lazy(|| {
+        Compiler::new()
+            .implicit_prelude(false)
+            .run_expr_async::<i32>(&vm, "example", expr)
+    });
+
+    match vm.run_with_fuel(1000, future) {
+        Err(Error::VM(VMError::OutOfFuel)) => (),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+        Ok(_) => panic!("Expected the execution to run out of fuel"),
+    }
+    // The frames of the aborted call are removed so the thread can be used again
+    let (value, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<i32>(&vm, "example2", "1 #Int+ 2")
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(value, 3);
+}
+
+#[test]
+fn interrupt_stops_execution_and_can_not_be_caught() {
+    use std::thread;
+    use std::time::Duration;
+
+    let _ = ::env_logger::init();
+
+    let vm = make_vm();
+    // Load the modules before the interrupt can happen so that only the action is interrupted
+    Compiler::new()
+        .run_expr_async::<OpaqueValue<&Thread, Hole>>(&vm, "load", "import! std.io")
+        .sync_or_error()
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let interrupter = {
+        let vm = vm.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            vm.interrupt();
+        })
+    };
+
+    let text = r#"
+        let prelude = import! std.prelude
+        let io @ { applicative, monad } = import! std.io
+        let { wrap } = prelude.make_Applicative applicative
+        let { (>>=) } = prelude.make_Monad monad
+
+        let forever n : Int -> String = forever (n + 1)
+
+        let action = wrap () >>= \_ -> wrap (forever 0)
+        io.catch action (\_ -> wrap "caught")
+    "#;
+    let result = Compiler::new()
+        .run_io(true)
+        .run_expr::<IO<String>>(&vm, "example", text);
+    interrupter.join().unwrap();
+
+    match result {
+        Err(Error::VM(VMError::Interrupted)) => (),
+        Ok((IO::Value(value), _)) => panic!("Unexpected value `{}`", value),
+        Ok((IO::Exception(err), _)) => panic!("Unexpected exception `{}`", err),
+        Err(err) => panic!("Unexpected error `{:?}`", err),
+    }
+}
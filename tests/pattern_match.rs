@@ -158,3 +158,34 @@ a #Int+ i #Int+ m
 "#,
 20i32
 }
+
+test_expr!{ match_string_literal,
+r#"
+let f x =
+    match x with
+    | "a" -> 1
+    | "abc" -> 2
+    | _ -> 3
+f "abc" #Int+ f "ab" #Int* 10
+"#,
+32i32
+}
+
+test_expr!{ pattern_arguments,
+r#"
+let add { x, y } (a, b) = x #Int+ y #Int+ a #Int+ b
+let f = \{ x } -> x
+add { x = f { x = 1 }, y = 2 } (3, 4)
+"#,
+10i32
+}
+
+test_expr!{ function_clauses,
+r#"
+let fib 0 = 0
+let fib 1 = 1
+let fib n = fib (n #Int- 1) #Int+ fib (n #Int- 2)
+fib 10
+"#,
+55i32
+}
@@ -0,0 +1,116 @@
+//! Tests for the register instructions of `vm::register`. The programs below are executed as
+//! register instructions when the `register_vm` feature is enabled.
+extern crate env_logger;
+extern crate gluon;
+
+use gluon::import::Import;
+use gluon::vm::register::instruction_counts;
+use gluon::{new_vm, Compiler};
+
+/// Modules which the instruction counts are compared on
+const MODULES: &[&str] = &[
+    "std.prelude",
+    "std.list",
+    "std.map",
+    "std.parser",
+    "std.stream",
+    "std.string",
+    "std.writer",
+];
+
+#[test]
+fn register_instructions_are_fewer_than_stack_instructions() {
+    let _ = env_logger::init();
+
+    let vm = new_vm();
+    let (mut stack_total, mut register_total) = (0, 0);
+    for module in MODULES {
+        let source = {
+            let import = vm.get_macros().get("import");
+            import
+                .as_ref()
+                .and_then(|import| import.downcast_ref::<Import>())
+                .expect("Import macro")
+                .module_source(&vm, module)
+                .unwrap_or_else(|err| panic!("{}", err))
+        };
+        let mut compiler =
+            Compiler::new().implicit_prelude(!source.starts_with("//@NO-IMPLICIT-PRELUDE"));
+        let (expr, _) = compiler
+            .typecheck_str(&vm, module, &source, None)
+            .unwrap_or_else(|err| panic!("{}", err));
+        let compiled = compiler
+            .compile_script(&vm, module, &source, &expr)
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        let (stack, register) = instruction_counts(&compiled.function);
+        stack_total += stack;
+        register_total += register;
+    }
+    let reduction = 100.0 * (1.0 - register_total as f64 / stack_total as f64);
+    assert!(
+        reduction >= 35.0,
+        "Expected at least 35% fewer instructions but got {:.1}% ({} stack instructions, {} \
+         register instructions)",
+        reduction,
+        stack_total,
+        register_total
+    );
+}
+
+fn run_int(source: &str) -> i32 {
+    let _ = env_logger::init();
+
+    let vm = new_vm();
+    Compiler::new()
+        .run_expr::<i32>(&vm, "test", source)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .0
+}
+
+#[test]
+fn recursion_and_arithmetic() {
+    let source = r#"
+let factorial n : Int -> Int =
+    if n #Int< 2 then 1 else n #Int* factorial (n #Int- 1)
+factorial 5
+"#;
+    assert_eq!(run_int(source), 120);
+}
+
+#[test]
+fn deep_tail_calls() {
+    let source = r#"
+let count n acc : Int -> Int -> Int =
+    if n #Int== 0 then acc else count (n #Int- 1) (acc #Int+ 1)
+count 100000 0
+"#;
+    assert_eq!(run_int(source), 100000);
+}
+
+#[test]
+fn closures_records_and_partial_application() {
+    let source = r#"
+let add x y : Int -> Int -> Int = x #Int+ y
+let add2 = add 2
+let record = { f = add2, value = 3 }
+let apply f x : (Int -> Int) -> Int -> Int = f x
+let is_even n : Int -> Bool = if n #Int== 0 then True else is_odd (n #Int- 1)
+and is_odd n : Int -> Bool = if n #Int== 0 then False else is_even (n #Int- 1)
+if is_even 10 then apply record.f record.value else 0
+"#;
+    assert_eq!(run_int(source), 5);
+}
+
+#[test]
+fn pattern_matching() {
+    let source = r#"
+type List a = | Nil | Cons a (List a)
+let sum xs : List Int -> Int =
+    match xs with
+    | Cons x rest -> x #Int+ sum rest
+    | Nil -> 0
+sum (Cons 1 (Cons 2 (Cons 3 Nil)))
+"#;
+    assert_eq!(run_int(source), 6);
+}
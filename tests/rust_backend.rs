@@ -0,0 +1,124 @@
+extern crate env_logger;
+extern crate gluon;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::process::Command;
+
+use gluon::vm::core;
+use gluon::vm::core::rust_backend::emit_rust;
+use gluon::{new_vm, Compiler};
+
+fn emit(source: &str) -> String {
+    let _ = env_logger::init();
+
+    let vm = new_vm();
+    let (expr, _) = Compiler::new()
+        .implicit_prelude(false)
+        .typecheck_str(&vm, "test", source, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let env = vm.get_env();
+    let core_expr = core::translate(&env, expr.expr());
+    emit_rust(core_expr.expr())
+}
+
+#[test]
+fn emit_numeric_functions() {
+    let source = r#"
+let factorial n : Int -> Int =
+    if n #Int< 2 then 1 else n #Int* factorial (n #Int- 1)
+let half x : Float -> Float = x #Float/ 2.0
+{ factorial, half }
+"#;
+    let output = emit(source);
+    assert!(output.contains("pub fn factorial(n: isize) -> isize {"), "{}", output);
+    assert!(output.contains("factorial((n - 1))"), "{}", output);
+    assert!(output.contains("pub fn half(x: f64) -> f64 {"), "{}", output);
+    assert!(!output.contains("Skipped"), "{}", output);
+}
+
+#[test]
+fn skip_functions_which_can_not_be_emitted() {
+    let source = r#"
+let greet name : String -> String = name
+let id x : Int -> Int = x
+let call_greet x : Int -> Int =
+    let _ = greet ""
+    x
+{ greet, id, call_greet }
+"#;
+    let output = emit(source);
+    assert!(output.contains("// Skipped `greet`"), "{}", output);
+    assert!(output.contains("// Skipped `call_greet`"), "{}", output);
+    assert!(output.contains("pub fn id(x: isize) -> isize {"), "{}", output);
+}
+
+/// Compiles `output` together with `main` using `rustc` and runs the resulting program
+fn run_emitted(name: &str, output: &str, main: &str) {
+    let dir = env::temp_dir().join("gluon_rust_backend");
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join(format!("{}.rs", name));
+    let executable = dir.join(name);
+    File::create(&source)
+        .unwrap()
+        .write_all(format!("{}\nfn main() {{\n{}\n}}\n", output, main).as_bytes())
+        .unwrap();
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let compiled = Command::new(rustc)
+        .arg(&source)
+        .arg("-o")
+        .arg(&executable)
+        .output()
+        .unwrap_or_else(|err| panic!("Unable to run rustc: {}", err));
+    assert!(
+        compiled.status.success(),
+        "{}\n{}",
+        output,
+        String::from_utf8_lossy(&compiled.stderr)
+    );
+
+    let run = Command::new(&executable)
+        .output()
+        .unwrap_or_else(|err| panic!("Unable to run {}: {}", executable.display(), err));
+    assert!(
+        run.status.success(),
+        "{}\n{}",
+        output,
+        String::from_utf8_lossy(&run.stderr)
+    );
+}
+
+#[test]
+fn emitted_functions_compile_and_run() {
+    let source = r#"
+let { Bool } = import! std.types
+let factorial n : Int -> Int =
+    if n #Int< 2 then 1 else n #Int* factorial (n #Int- 1)
+let half x : Float -> Float = x #Float/ 2.0
+let sign n : Int -> Int =
+    match n with
+    | 0 -> 0
+    | _ -> if n #Int< 0 then 0 #Int- 1 else 1
+let is_even n : Int -> Bool =
+    let half_n = n #Int/ 2
+    half_n #Int* 2 #Int== n
+let greet name : String -> String = name
+{ factorial, half, sign, is_even, greet }
+"#;
+    let output = emit(source);
+    run_emitted(
+        "emitted_functions_compile_and_run",
+        &output,
+        r#"
+    assert_eq!(factorial(5), 120);
+    assert_eq!(half(3.0), 1.5);
+    assert_eq!(sign(0), 0);
+    assert_eq!(sign(-7), -1);
+    assert_eq!(sign(7), 1);
+    assert!(is_even(4));
+    assert!(!is_even(5));
+"#,
+    );
+}
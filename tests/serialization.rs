@@ -16,6 +16,9 @@ use futures::Future;
 use serde::ser::SerializeState;
 
 use gluon::{new_vm, Compiler};
+use gluon::base;
+use gluon::base::ast::RootExpr;
+use gluon::base::symbol::Symbol;
 use gluon::vm::api::{Hole, OpaqueValue};
 use gluon::vm::thread::{RootedThread, RootedValue, Thread, ThreadInternal};
 use gluon::vm::serialization::{DeSeed, SeSeed};
@@ -197,3 +200,282 @@ fn roundtrip_thread() {
         .unwrap_or_else(|err| panic!("{}", err));
     roundtrip(&thread, &value);
 }
+
+fn serialize_expr(expr: &RootExpr<Symbol>) -> String {
+    let mut buffer = Vec::new();
+    {
+        let mut ser = serde_json::Serializer::pretty(&mut buffer);
+        expr.serialize_state(&mut ser, &base::serialization::SeSeed::default())
+            .unwrap();
+    }
+    String::from_utf8(buffer).unwrap()
+}
+
+#[test]
+fn roundtrip_typechecked_expr() {
+    use serde::de::DeserializeState;
+
+    let thread = new_vm();
+    let text = r#"
+        type Shape = | Circle Float | Rectangle Float Float
+        let area shape : Shape -> Float =
+            match shape with
+            | Circle r -> 3.14 * r * r
+            | Rectangle w h -> w * h
+        let { x, y } = { x = 1, y = "test" }
+        { area, shapes = [Circle 1.5, Rectangle 2.0 3.0], x }
+    "#;
+    let (expr, _) = Compiler::new()
+        .typecheck_str(&thread, "test", text, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    let deserialize_expr = |buffer: &str| -> RootExpr<Symbol> {
+        let mut de = serde_json::Deserializer::from_str(buffer);
+        RootExpr::deserialize_state(&mut base::serialization::Seed::default(), &mut de)
+            .unwrap_or_else(|err| panic!("{}\n{}", err, buffer))
+    };
+
+    // Which nodes are written as shared depends on how many references to them exist outside of
+    // the expression, so the expression is read back once before the serializations are compared.
+    // Symbols are compared by identity so the expressions can't be compared directly.
+    let buffer = serialize_expr(&deserialize_expr(&serialize_expr(&expr)));
+    assert_eq!(buffer, serialize_expr(&deserialize_expr(&buffer)));
+}
+
+#[test]
+fn cached_module_is_checked_again_with_different_options() {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    use gluon::import::{Import, CACHE_EXTENSION};
+
+    let dir = env::temp_dir().join("gluon_module_cache_options");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join("cached_module.glu"))
+        .unwrap()
+        .write_all(b"let f x = x #Int+ 1\n{ f }\n")
+        .unwrap();
+    let cached = dir.join("cache")
+        .join("cached_module")
+        .with_extension(CACHE_EXTENSION);
+
+    let run = |mut compiler: Compiler| {
+        let thread = new_vm();
+        let import = thread.get_macros().get("import").unwrap();
+        let import = import.downcast_ref::<Import>().unwrap();
+        import.add_path(dir.clone());
+        import.set_cache_dir(Some(dir.join("cache")));
+        import
+            .reload_module(&mut compiler, &thread, "cached_module")
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        let mut bytes = Vec::new();
+        File::open(&cached)
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+        bytes
+    };
+
+    let default_options = run(Compiler::new());
+    assert!(default_options.starts_with(b"GLUCACHE"));
+    // Loading the module with the same options uses the module which were cached before
+    assert_eq!(run(Compiler::new()), default_options);
+    // Checking the module with another nesting limit replaces the cached module
+    assert_ne!(run(Compiler::new().max_nesting_depth(1000)), default_options);
+}
+
+#[test]
+fn load_module_from_cache() {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    use gluon::import::{Import, CACHE_EXTENSION};
+
+    let dir = env::temp_dir().join("gluon_module_cache");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    File::create(dir.join("cached_module.glu"))
+        .unwrap()
+        .write_all(b"let f x = x #Int+ 1\n{ f }\n")
+        .unwrap();
+
+    let run = || {
+        let thread = new_vm();
+        {
+            let import = thread.get_macros().get("import").unwrap();
+            let import = import.downcast_ref::<Import>().unwrap();
+            import.add_path(dir.clone());
+            import.set_cache_dir(Some(dir.join("cache")));
+        }
+        let (result, _) = Compiler::new()
+            .run_expr::<i32>(&thread, "test", "let m = import! cached_module\nm.f 2")
+            .unwrap_or_else(|err| panic!("{}", err));
+        result
+    };
+
+    assert_eq!(run(), 3);
+    let cached = dir.join("cache")
+        .join("cached_module")
+        .with_extension(CACHE_EXTENSION);
+    assert!(cached.exists());
+    // The second vm loads the module, and the standard library, from the cache
+    assert_eq!(run(), 3);
+}
+
+#[test]
+fn load_precompiled_module() {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    use gluon::import::{Import, PRECOMPILED_EXTENSION};
+
+    let dir = env::temp_dir().join("gluon_precompiled_module");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let source = "let f x = x #Int+ 1\n{ f }\n";
+    File::create(dir.join("precompiled_module.glu"))
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+
+    let run = || {
+        let thread = new_vm();
+        {
+            let import = thread.get_macros().get("import").unwrap();
+            import.downcast_ref::<Import>().unwrap().add_path(dir.clone());
+        }
+        let (result, _) = Compiler::new()
+            .run_expr::<i32>(&thread, "test", "let m = import! precompiled_module\nm.f 2")
+            .unwrap_or_else(|err| panic!("{}", err));
+        result
+    };
+
+    {
+        let thread = new_vm();
+        let import = thread.get_macros().get("import").unwrap();
+        let import = import.downcast_ref::<Import>().unwrap();
+        // Compile different code than the source so that it is visible which one is loaded
+        let mut precompiled = import
+            .precompile(
+                &mut Compiler::new(),
+                &thread,
+                "precompiled_module",
+                "let f x = x #Int+ 10\n{ f }\n",
+            )
+            .unwrap_or_else(|err| panic!("{}", err));
+        precompiled.source_hash = import
+            .precompile(&mut Compiler::new(), &thread, "precompiled_module", source)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .source_hash;
+        let path = dir.join("precompiled_module")
+            .with_extension(PRECOMPILED_EXTENSION);
+        precompiled.write(File::create(path).unwrap()).unwrap();
+    }
+    assert_eq!(run(), 12);
+
+    // The precompiled module is ignored once the source has changed
+    File::create(dir.join("precompiled_module.glu"))
+        .unwrap()
+        .write_all(b"let f x = x #Int+ 2\n{ f }\n")
+        .unwrap();
+    assert_eq!(run(), 4);
+}
+
+#[test]
+fn precompiled_modules_are_reproducible() {
+    use gluon::import::Import;
+
+    let source = r#"
+        let { x } = { x = 1 }
+        let f x =
+            let y = x #Int+ 1
+            match Some y with
+            | Some x -> x
+            | None -> 0
+        { x, f }
+        "#;
+    let precompile = || {
+        let thread = new_vm();
+        let import = thread.get_macros().get("import").unwrap();
+        let import = import.downcast_ref::<Import>().unwrap();
+        let precompiled = import
+            .precompile(&mut Compiler::new(), &thread, "reproducible", source)
+            .unwrap_or_else(|err| panic!("{}", err));
+        let mut buffer = Vec::new();
+        precompiled.write(&mut buffer).unwrap();
+        buffer
+    };
+    assert_eq!(precompile(), precompile());
+}
+
+#[test]
+fn precompiling_a_module_again_with_the_same_compiler_is_reproducible() {
+    use gluon::import::Import;
+
+    let source = r#"
+        let f { x } = \y -> x #Int+ y
+        { f }
+        "#;
+    let thread = new_vm();
+    let mut compiler = Compiler::new();
+    let mut precompile = || {
+        let import = thread.get_macros().get("import").unwrap();
+        let import = import.downcast_ref::<Import>().unwrap();
+        let precompiled = import
+            .precompile(&mut compiler, &thread, "reproducible", source)
+            .unwrap_or_else(|err| panic!("{}", err));
+        let mut buffer = Vec::new();
+        precompiled.write(&mut buffer).unwrap();
+        buffer
+    };
+    let first = precompile();
+    assert_eq!(first, precompile());
+}
+
+#[test]
+fn run_expr_read_from_ast_json() {
+    use gluon::ast_json;
+    use gluon::compiler_pipeline::Executable;
+
+    let thread = new_vm();
+    let text = r#"
+        let { x } = { x = 1 }
+        let add y = x #Int+ y
+        add 2
+    "#;
+    let expr = {
+        let type_cache = thread.global_env().type_cache();
+        RootExpr::try_new(|arena| {
+            Compiler::new().parse_partial_expr(arena, type_cache, "test", text)
+        }).unwrap_or_else(|(_, err)| panic!("{}", err))
+    };
+
+    let mut buffer = Vec::new();
+    ast_json::write(&mut buffer, &expr).unwrap();
+    let expr = ast_json::read(&buffer[..]).unwrap_or_else(|err| panic!("{}", err));
+
+    // The implicit prelude is only added when parsing so it is not in scope of `expr`
+    let result = expr.run_expr(
+        &mut Compiler::new().implicit_prelude(false),
+        &*thread,
+        "test",
+        text,
+        None,
+    )
+        .wait()
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert_eq!(result.value.get_value(), Value::int(3));
+}
+
+#[test]
+fn ast_json_of_another_version_is_rejected() {
+    use gluon::ast_json;
+
+    let document = format!(r#"{{ "version": {}, "expr": null }}"#, ast_json::VERSION + 1);
+    assert!(ast_json::read(document.as_bytes()).is_err());
+}
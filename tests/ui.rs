@@ -1,5 +1,6 @@
 extern crate gluon;
 
+use gluon::base::error::EmitOptions;
 use gluon::{new_vm, Compiler};
 
 #[test]
@@ -14,3 +15,29 @@ import! undefined
 "#
     );
 }
+
+#[test]
+fn max_errors_summarizes_the_remaining_errors() {
+    let thread = new_vm();
+    let text = r#"
+let x : Int = ""
+let y : Int = ""
+let z : Int = ""
+1
+"#;
+    let err = Compiler::new()
+        .run_expr::<i32>(&thread, "test", text)
+        .unwrap_err();
+    assert_eq!(err.error_count(), 3);
+
+    let output = err.emit_string(&EmitOptions {
+        color: false,
+        max_errors: Some(1),
+    });
+    assert_eq!(output.matches('^').count(), 1, "{}", output);
+    assert!(
+        output.ends_with("3 errors were found, only the first 1 are shown"),
+        "{}",
+        output
+    );
+}
@@ -55,6 +55,98 @@ r" 120.0 #Float/ 4.0
 30.0f64
 }
 
+test_expr!{ prelude intrinsic_is_bound_to_the_vm_global,
+r#"
+let { Bool } = import! std.bool
+// Only the global is used at runtime, the body is only typechecked
+#[intrinsic("string_eq")]
+let eq : String -> String -> Bool = \l r -> False
+eq "abc" "abc"
+"#,
+true
+}
+
+test_expr!{ implicit_arguments_are_passed_at_call_sites,
+r#"
+type Size a = { size : a -> Int }
+#[implicit]
+let size_int : Size Int = { size = \x -> x #Int+ 1 }
+#[implicit]
+let size_float : Size Float = { size = \_ -> 100 }
+let size ?s x : [Size a] -> a -> Int = s.size x
+let size_of_pair ?s x y : [Size a] -> a -> a -> Int = size x #Int+ size y
+size 1 #Int+ size 2.0 #Int+ size_of_pair 10 20
+"#,
+134i32
+}
+
+test_expr!{ implicit_arguments_of_implicit_bindings_are_passed,
+r#"
+type Size a = { size : a -> Int }
+type List a = | Nil | Cons a (List a)
+#[implicit]
+let size_int : Size Int = { size = \x -> x }
+#[implicit]
+let size_list ?s : [Size a] -> Size (List a) =
+    let size_elems xs =
+        match xs with
+        | Nil -> 0
+        | Cons x rest -> s.size x #Int+ size_elems rest
+    { size = size_elems }
+let size ?s x : [Size a] -> a -> Int = s.size x
+size (Cons (Cons 1 (Cons 2 Nil)) (Cons (Cons 10 Nil) Nil))
+"#,
+13i32
+}
+
+test_expr!{ implicit_arguments_are_passed_to_projections,
+r#"
+type Size a = { size : a -> Int }
+#[implicit]
+let size_int : Size Int = { size = \x -> x #Int+ 1 }
+let size ?s x : [Size a] -> a -> Int = s.size x
+let m = { size }
+m.size 1 #Int+ m.size 10
+"#,
+13i32
+}
+
+#[test]
+fn intrinsic_must_have_the_type_of_the_vm_global() {
+    let _ = ::env_logger::init();
+    let text = r#"
+#[intrinsic("string_eq")]
+let eq : Int -> Int -> Bool = \l r -> l == r
+eq 1 2
+"#;
+    let vm = make_vm();
+    match Compiler::new().run_expr::<bool>(&vm, "test", text) {
+        Err(Error::Typecheck(_)) => (),
+        result => panic!("Expected a type error but got {:?}", result),
+    }
+}
+
+test_expr!{ shebang_and_module_attributes,
+r"#!/usr/bin/env gluon
+#![no_implicit_prelude]
+1 #Int+ 2
+",
+3i32
+}
+
+#[test]
+fn no_implicit_prelude_attribute() {
+    let _ = ::env_logger::init();
+    let text = r"#![no_implicit_prelude]
+1 + 2
+";
+    let vm = make_vm();
+    match Compiler::new().run_expr::<i32>(&vm, "test", text) {
+        Err(Error::Typecheck(_)) => (),
+        result => panic!("Expected `+` to be undefined but got {:?}", result),
+    }
+}
+
 #[test]
 fn record() {
     let _ = ::env_logger::init();
@@ -456,6 +548,42 @@ Some 1
 None::<i32>
 }
 
+test_expr!{ operator_sections,
+r#"
+let apply f x = f x
+apply (#Int- 1) (apply (10 #Int-) (apply (.x) { x = 3 }))
+"#,
+6i32
+}
+
+test_expr!{ assert_eq_shows_difference,
+r#"
+let { assert_eq_at } = import! std.test.assert
+let { Test } = import! std.test
+let list @ { List } = import! std.list
+let int = import! std.int
+let test : Test () =
+    assert_eq_at (line! ()) (list.show int.show) (list.eq int.eq) (list.of [1, 2]) (list.of [1, 3])
+match test.writer with
+| Cons message _ -> message
+| Nil -> ""
+"#,
+String::from("Assertion failed at line 7:\n    left: [1, 2]\n   right: [1, 3]\n    diff: [1, [-2-]{+3+}]")
+}
+
+test_expr!{ expect_reports_location_of_expected_string,
+r#"
+let { Test } = import! std.test
+let { List } = import! std.list
+let int = import! std.int
+let test : Test () = expect! int.show 12 "13"
+match test.writer with
+| Cons message _ -> message
+| Nil -> ""
+"#,
+String::from("Expectation failed at line 5 (bytes 132..136):\n    expected: \"13\"\n      actual: \"12\"\n        diff: [-13-]{+12+}")
+}
+
 #[test]
 fn rename_types_after_binding() {
     let _ = ::env_logger::init();
@@ -674,34 +802,42 @@ g 10
                     // Some(StacktraceFrame { name: f.clone(), line: 9 }),
                     Some(StacktraceFrame {
                         name: g.clone(),
+                        module: "<top>".to_string(),
                         line: 7.into(),
                     }),
                     Some(StacktraceFrame {
                         name: f.clone(),
+                        module: "<top>".to_string(),
                         line: 6.into(),
                     }),
                     Some(StacktraceFrame {
                         name: g.clone(),
+                        module: "<top>".to_string(),
                         line: 7.into(),
                     }),
                     Some(StacktraceFrame {
                         name: f.clone(),
+                        module: "<top>".to_string(),
                         line: 6.into(),
                     }),
                     Some(StacktraceFrame {
                         name: g.clone(),
+                        module: "<top>".to_string(),
                         line: 7.into(),
                     }),
                     Some(StacktraceFrame {
                         name: f.clone(),
+                        module: "<top>".to_string(),
                         line: 4.into(),
                     }),
                     Some(StacktraceFrame {
                         name: end.clone(),
+                        module: "<top>".to_string(),
                         line: 1.into(),
                     }),
                     Some(StacktraceFrame {
                         name: error.clone(),
+                        module: String::new(),
                         line: 0.into(),
                     }),
                 ]
@@ -711,6 +847,44 @@ g 10
         Ok(_) => panic!("Expected an error"),
     }
 }
+
+test_expr!{ prelude seq_forces_lazy_value,
+r#"
+let { lazy, seq } = import! std.lazy
+let { ref, load, (<-) } = import! std.reference
+let forced = ref False
+let l = lazy (\_ ->
+    let _ = forced <- True
+    1)
+let _ = seq l ()
+load forced
+"#,
+true
+}
+
+test_expr!{ prelude strict_record_field_does_not_build_thunks,
+r#"
+let { lazy, force } = import! std.lazy
+let go acc n =
+    if n == 0 then force acc.sum
+    else go { !sum = lazy (\_ -> force acc.sum + 1) } (n - 1)
+go { sum = lazy (\_ -> 0) } 100000
+"#,
+100000i32
+}
+
+test_expr!{ debug_backtrace,
+r#"
+let { backtrace } = import! std.debug
+let array = import! std.array.prim
+let f x = { frames = backtrace (), x }
+let { frames } = f 1
+let frame = array.index frames (array.len frames #Int- 1)
+(frame.name, frame.module, frame.line)
+"#,
+(String::from("f"), String::from("<top>"), Some(4i32))
+}
+
 #[test]
 fn completion_with_prelude() {
     let _ = ::env_logger::init();
@@ -745,8 +919,8 @@ let from f : (Int -> Option a) -> Stream a =
 
     let lines = source::Lines::new(source.as_bytes().iter().cloned());
     let result = completion::find(
-        &*vm.get_env(),
-        &expr,
+        &vm.get_env(),
+        expr.expr(),
         lines.offset(14.into(), 29.into()).unwrap(),
     );
     assert_eq!(result, Ok(Type::int()));
@@ -763,7 +937,7 @@ fn completion_with_prelude_at_0() {
         .typecheck_str(&vm, "example", expr, None)
         .unwrap_or_else(|err| panic!("{}", err));
 
-    let result = completion::find(&*vm.get_env(), &expr, BytePos::from(0));
+    let result = completion::find(&vm.get_env(), expr.expr(), BytePos::from(0));
     assert_eq!(result, Ok(Type::int()));
 }
 
@@ -778,7 +952,7 @@ fn suggestion_from_implicit_prelude() {
         .typecheck_str(&vm, "example", expr, None)
         .unwrap_or_else(|err| panic!("{}", err));
 
-    let result = completion::suggest(&*vm.get_env(), &expr, BytePos::from(2));
+    let result = completion::suggest(&vm.get_env(), expr.expr(), BytePos::from(2));
     assert!(!result.is_empty());
 }
 
@@ -805,12 +979,26 @@ fn value_size() {
 fn deep_clone_partial_application() {
     use gluon::base::symbol::Symbol;
     use gluon::base::metadata::Metadata;
+    use gluon::vm::gc::GcSettings;
 
     let _ = ::env_logger::init();
     let vm = RootedThread::new();
 
     assert_eq!(vm.context().gc.allocated_memory(), 0);
 
+    // Allocation buffers are counted as a whole so allocate each value separately to compare the
+    // memory of just the closures
+    let settings = GcSettings {
+        allocation_buffer_size: 0,
+        ..GcSettings::default()
+    };
+    vm.global_env()
+        .gc
+        .lock()
+        .unwrap()
+        .set_settings(settings.clone());
+    vm.set_gc_settings(settings);
+
     let child = vm.new_thread().unwrap();
 
     assert_eq!(child.context().gc.allocated_memory(), 0);
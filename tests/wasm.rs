@@ -0,0 +1,119 @@
+#![cfg(feature = "wasm")]
+extern crate env_logger;
+extern crate gluon;
+
+mod support;
+
+use gluon::Compiler;
+use gluon::vm::api::{Getable, VmType};
+use gluon::vm::wasm;
+
+use support::make_vm;
+
+fn run<T>(name: &str, expr: &str) -> T
+where
+    T: for<'vm> Getable<'vm> + VmType + Send,
+{
+    let vm = make_vm();
+    let (result, _) = Compiler::new()
+        .implicit_prelude(false)
+        .run_expr::<T>(&vm, name, expr)
+        .unwrap_or_else(|err| panic!("{}", err));
+    result
+}
+
+fn assert_used(name: &str) {
+    assert!(
+        wasm::used_functions().iter().any(|used| used == name),
+        "{:?}",
+        wasm::used_functions()
+    );
+}
+
+#[test]
+fn hot_int_function_runs_as_wasm() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+let clamp x : Int -> Int =
+    if x #Int< 0 then 0 else if 100 #Int< x then 100 else x
+// Returns a record so that the loop is interpreted and calls `clamp` from the interpreter
+let loop n acc : Int -> Int -> { total : Int } =
+    if n #Int== 3000 then { total = acc }
+    else loop (n #Int+ 1) (acc #Int+ clamp (n #Int- 1500))
+let result = loop 0 0
+result.total
+"#;
+    let expected = (0..3000)
+        .map(|n| ::std::cmp::min(::std::cmp::max(n - 1500, 0), 100))
+        .sum::<i32>();
+    assert_eq!(run::<i32>("clamp", expr), expected);
+    assert_used("clamp");
+}
+
+#[test]
+fn hot_float_function_runs_as_wasm() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+let average x y : Float -> Float -> Float = (x #Float+ y) #Float/ 2.0
+let loop n acc : Int -> Float -> { total : Float } =
+    if n #Int== 2000 then { total = acc }
+    else loop (n #Int+ 1) (average acc 1.0)
+let result = loop 0 0.0
+result.total
+"#;
+    let expected = (0..2000).fold(0.0, |acc, _| (acc + 1.0) / 2.0);
+    assert_eq!(run::<f64>("average", expr), expected);
+    assert_used("average");
+}
+
+#[test]
+fn hot_function_taking_and_returning_bool_runs_as_wasm() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+let { Bool } = import! std.types
+let xor x y : Bool -> Bool -> Bool =
+    match x with
+    | True -> if y then False else True
+    | False -> y
+let loop n acc : Int -> Bool -> { total : Bool } =
+    if n #Int== 2001 then { total = acc }
+    else loop (n #Int+ 1) (xor acc True)
+let result = loop 0 False
+result.total
+"#;
+    assert_eq!(run::<bool>("xor", expr), true);
+    assert_used("xor");
+}
+
+#[test]
+fn untranslated_functions_are_interpreted() {
+    let _ = ::env_logger::init();
+
+    let expr = r#"
+let first x : { x : Int } -> Int = x.x
+let loop n acc : Int -> Int -> { total : Int } =
+    if n #Int== 2000 then { total = acc }
+    else loop (n #Int+ 1) (acc #Int+ first { x = 1 })
+let result = loop 0 0
+result.total
+"#;
+    assert_eq!(run::<i32>("first", expr), 2000);
+    assert!(!wasm::used_functions().iter().any(|name| name == "first"));
+}
+
+#[test]
+fn deep_tail_recursion_falls_back_to_the_interpreter() {
+    let _ = ::env_logger::init();
+
+    // WebAssembly has no tail calls so the hot call nests too deeply, traps and is interpreted
+    let expr = r#"
+let count n acc : Int -> Int -> Int =
+    if n #Int== 0 then acc else count (n #Int- 1) (acc #Int+ 1)
+count 100000 0
+"#;
+    assert_eq!(run::<i32>("count", expr), 100000);
+    assert!(!wasm::used_functions().iter().any(|name| name == "count"));
+}
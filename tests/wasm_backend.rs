@@ -0,0 +1,104 @@
+extern crate env_logger;
+extern crate gluon;
+extern crate wasmi;
+
+use wasmi::{ImportsBuilder, ModuleInstance, ModuleRef, NopExternals, RuntimeValue};
+
+use gluon::vm::core;
+use gluon::vm::core::wasm_backend::{emit_wasm, WasmModule};
+use gluon::{new_vm, Compiler};
+
+fn emit(source: &str) -> WasmModule {
+    let _ = env_logger::init();
+
+    let vm = new_vm();
+    let (expr, _) = Compiler::new()
+        .implicit_prelude(false)
+        .typecheck_str(&vm, "test", source, None)
+        .unwrap_or_else(|err| panic!("{}", err));
+    let env = vm.get_env();
+    let core_expr = core::translate(&env, expr.expr());
+    emit_wasm(core_expr.expr())
+}
+
+#[test]
+fn emit_numeric_functions() {
+    let source = r#"
+let factorial n : Int -> Int =
+    if n #Int< 2 then 1 else n #Int* factorial (n #Int- 1)
+let half x : Float -> Float = x #Float/ 2.0
+let greet name : String -> String = name
+{ factorial, half, greet }
+"#;
+    let module = emit(source);
+    assert_eq!(&module.bytes[..8], b"\0asm\x01\x00\x00\x00");
+    assert_eq!(module.exports, ["factorial", "half"]);
+    assert_eq!(module.skipped.len(), 1);
+    assert_eq!(module.skipped[0].0, "greet");
+}
+
+/// Loads `module` with `wasmi`, which validates it
+fn instantiate(module: &WasmModule) -> ModuleRef {
+    let loaded =
+        wasmi::Module::from_buffer(&module.bytes).unwrap_or_else(|err| panic!("{}", err));
+    ModuleInstance::new(&loaded, &ImportsBuilder::default())
+        .unwrap_or_else(|err| panic!("{}", err))
+        .assert_no_start()
+}
+
+fn invoke(instance: &ModuleRef, name: &str, args: &[RuntimeValue]) -> RuntimeValue {
+    instance
+        .invoke_export(name, args, &mut NopExternals)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .expect("Return value")
+}
+
+#[test]
+fn emitted_functions_run() {
+    let source = r#"
+let { Bool } = import! std.types
+let factorial n : Int -> Int =
+    if n #Int< 2 then 1 else n #Int* factorial (n #Int- 1)
+let half x : Float -> Float = x #Float/ 2.0
+let sign n : Int -> Int =
+    match n with
+    | 0 -> 0
+    | _ -> if n #Int< 0 then 0 #Int- 1 else 1
+let is_even n : Int -> Bool =
+    let half_n = n #Int/ 2
+    half_n #Int* 2 #Int== n
+{ factorial, half, sign, is_even }
+"#;
+    let module = emit(source);
+    assert!(module.skipped.is_empty(), "{:?}", module.skipped);
+    let instance = instantiate(&module);
+    let call = |name, arg| invoke(&instance, name, &[arg]);
+
+    assert_eq!(call("factorial", RuntimeValue::I64(5)), RuntimeValue::I64(120));
+    assert_eq!(
+        call("half", RuntimeValue::F64(3.0.into())),
+        RuntimeValue::F64(1.5.into())
+    );
+    assert_eq!(call("sign", RuntimeValue::I64(0)), RuntimeValue::I64(0));
+    assert_eq!(call("sign", RuntimeValue::I64(-7)), RuntimeValue::I64(-1));
+    assert_eq!(call("sign", RuntimeValue::I64(7)), RuntimeValue::I64(1));
+    assert_eq!(call("is_even", RuntimeValue::I64(4)), RuntimeValue::I32(1));
+    assert_eq!(call("is_even", RuntimeValue::I64(5)), RuntimeValue::I32(0));
+}
+
+#[test]
+fn functions_with_the_same_name_get_unique_exports() {
+    let source = r#"
+let f x : Int -> Int = x #Int+ 1
+let g x : Int -> Int = f x
+let f x : Int -> Int = g x #Int* 10
+f
+"#;
+    let module = emit(source);
+    assert_eq!(module.exports, ["f", "g", "f_1"]);
+    let instance = instantiate(&module);
+    let call = |name, arg| invoke(&instance, name, &[arg]);
+
+    assert_eq!(call("f", RuntimeValue::I64(1)), RuntimeValue::I64(2));
+    assert_eq!(call("f_1", RuntimeValue::I64(1)), RuntimeValue::I64(20));
+}
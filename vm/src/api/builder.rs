@@ -0,0 +1,367 @@
+//! Construction of gluon values directly from Rust, letting hosts assemble values such as
+//! configuration records without generating and compiling gluon source.
+//!
+//! ```
+//! #[macro_use]
+//! extern crate gluon_vm;
+//! extern crate gluon;
+//!
+//! use gluon::vm::api::VmType;
+//! use gluon::vm::api::builder::ValueBuilder;
+//!
+//! field_decl! { host, port }
+//!
+//! # fn main() {
+//! let thread = gluon::new_vm();
+//! let typ = <record_type!(host => String, port => i32)>::make_type(&thread);
+//! let config = ValueBuilder::record(vec![
+//!     ("port", ValueBuilder::int(8080)),
+//!     ("host", ValueBuilder::string("localhost")),
+//! ]).build(&thread, &typ)
+//!     .unwrap_or_else(|err| panic!("{}", err));
+//! # let _ = config;
+//! # }
+//! ```
+
+use std::fmt;
+
+use base::resolve;
+use base::types::{arg_iter, ArcType, BuiltinType, Type};
+
+use api::{FunctionType, Primitive, Pushable, VmType};
+use check::check_signature;
+use interner::InternedStr;
+use thread::{self, Context, RootedValue, Thread, ThreadInternal};
+use types::{VmIndex, VmInt, VmTag};
+use value::{ArrayDef, Def, RecordDef, ValueRepr};
+use {Error, Result};
+
+type PushFn = Box<FnMut(&Thread, &mut Context) -> Result<()> + Send>;
+
+enum Builder {
+    Int(VmInt),
+    Byte(u8),
+    Float(f64),
+    Char(char),
+    String(String),
+    Record(Vec<(String, ValueBuilder)>),
+    Variant(String, Vec<ValueBuilder>),
+    Array(Vec<ValueBuilder>),
+    Function(fn(&Thread) -> ArcType, PushFn),
+}
+
+/// Describes a gluon value which is constructed by `build`. The value is checked against the type
+/// it is expected to have while it is constructed so a value which does not match its type is
+/// never created.
+pub struct ValueBuilder(Builder);
+
+impl fmt::Debug for ValueBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Builder::Int(i) => write!(f, "{}", i),
+            Builder::Byte(b) => write!(f, "{}b", b),
+            Builder::Float(x) => write!(f, "{}", x),
+            Builder::Char(c) => write!(f, "{:?}", c),
+            Builder::String(ref s) => write!(f, "{:?}", s),
+            Builder::Record(ref fields) => {
+                let mut record = f.debug_map();
+                for &(ref name, ref value) in fields {
+                    record.entry(name, value);
+                }
+                record.finish()
+            }
+            Builder::Variant(ref constructor, ref args) => {
+                let mut variant = f.debug_tuple(constructor);
+                for arg in args {
+                    variant.field(arg);
+                }
+                variant.finish()
+            }
+            Builder::Array(ref elems) => f.debug_list().entries(elems).finish(),
+            Builder::Function(..) => write!(f, "<function>"),
+        }
+    }
+}
+
+impl ValueBuilder {
+    pub fn int(i: VmInt) -> ValueBuilder {
+        ValueBuilder(Builder::Int(i))
+    }
+
+    pub fn byte(b: u8) -> ValueBuilder {
+        ValueBuilder(Builder::Byte(b))
+    }
+
+    pub fn float(f: f64) -> ValueBuilder {
+        ValueBuilder(Builder::Float(f))
+    }
+
+    pub fn char(c: char) -> ValueBuilder {
+        ValueBuilder(Builder::Char(c))
+    }
+
+    pub fn string<S>(s: S) -> ValueBuilder
+    where
+        S: Into<String>,
+    {
+        ValueBuilder(Builder::String(s.into()))
+    }
+
+    /// A record with the fields `fields`. The fields may be given in any order but must be the
+    /// same as the fields of the record type.
+    pub fn record<I, S>(fields: I) -> ValueBuilder
+    where
+        I: IntoIterator<Item = (S, ValueBuilder)>,
+        S: Into<String>,
+    {
+        ValueBuilder(Builder::Record(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.into(), value))
+                .collect(),
+        ))
+    }
+
+    /// The variant `constructor` applied to `args`
+    pub fn variant<S>(constructor: S, args: Vec<ValueBuilder>) -> ValueBuilder
+    where
+        S: Into<String>,
+    {
+        ValueBuilder(Builder::Variant(constructor.into(), args))
+    }
+
+    pub fn array(elems: Vec<ValueBuilder>) -> ValueBuilder {
+        ValueBuilder(Builder::Array(elems))
+    }
+
+    /// A function implemented in Rust, such as one created with the `primitive!` macro
+    pub fn function<F, P>(f: P) -> ValueBuilder
+    where
+        P: Into<Primitive<F>>,
+        F: FunctionType + VmType + Send + 'static,
+    {
+        let mut f = Some(f.into());
+        ValueBuilder(Builder::Function(
+            F::make_type,
+            Box::new(move |thread, context| {
+                f.take()
+                    .expect("ValueBuilder function is only pushed once")
+                    .push(thread, context)
+            }),
+        ))
+    }
+
+    /// Constructs the value on `thread`, returning an error if it does not have the type `typ`
+    pub fn build<'vm>(self, thread: &'vm Thread, typ: &ArcType) -> Result<RootedValue<&'vm Thread>> {
+        let value = {
+            let mut context = thread.context();
+            self.push_checked(thread, &mut context, typ)?;
+            context.stack.pop()
+        };
+        Ok(thread.root_value(value))
+    }
+
+    fn push_checked(self, thread: &Thread, context: &mut Context, typ: &ArcType) -> Result<()> {
+        let check_leaf = |actual: ArcType| {
+            let env = thread.get_env();
+            if check_signature(&env, typ, &actual) {
+                Ok(())
+            } else {
+                Err(Error::WrongType(typ.clone(), actual))
+            }
+        };
+        match self.0 {
+            Builder::Int(i) => {
+                check_leaf(VmInt::make_type(thread))?;
+                i.push(thread, context)
+            }
+            Builder::Byte(b) => {
+                check_leaf(u8::make_type(thread))?;
+                b.push(thread, context)
+            }
+            Builder::Float(f) => {
+                check_leaf(f64::make_type(thread))?;
+                f.push(thread, context)
+            }
+            Builder::Char(c) => {
+                check_leaf(char::make_type(thread))?;
+                c.push(thread, context)
+            }
+            Builder::String(s) => {
+                check_leaf(String::make_type(thread))?;
+                s.push(thread, context)
+            }
+            Builder::Function(make_type, mut push) => {
+                check_leaf(make_type(thread))?;
+                push(thread, context)
+            }
+            Builder::Record(fields) => push_record(thread, context, typ, fields),
+            Builder::Variant(constructor, args) => {
+                push_variant(thread, context, typ, constructor, args)
+            }
+            Builder::Array(elems) => push_array(thread, context, typ, elems),
+        }
+    }
+}
+
+fn remove_aliases(thread: &Thread, typ: &ArcType) -> ArcType {
+    resolve::remove_aliases(&thread.get_env(), typ.clone())
+}
+
+fn push_record(
+    thread: &Thread,
+    context: &mut Context,
+    typ: &ArcType,
+    mut fields: Vec<(String, ValueBuilder)>,
+) -> Result<()> {
+    let resolved = remove_aliases(thread, typ);
+    match *resolved {
+        Type::Record(_) => (),
+        _ => return Err(Error::Message(format!("Expected `{}` but found a record", typ))),
+    }
+
+    let mut names: Vec<InternedStr> = Vec::new();
+    // Push the fields in the order of the type so that they end up at the correct offsets
+    for field in resolved.row_iter() {
+        let name = field.name.as_ref();
+        let i = fields
+            .iter()
+            .position(|&(ref given, _)| given == name)
+            .ok_or_else(|| Error::Message(format!("The record is missing the field `{}`", name)))?;
+        let (_, value) = fields.swap_remove(i);
+        value.push_checked(thread, context, &field.typ)?;
+        names.push(thread.global_env().intern(name)?);
+    }
+    if let Some(&(ref name, _)) = fields.first() {
+        return Err(Error::Message(format!(
+            "The field `{}` does not exist in `{}`",
+            name, typ
+        )));
+    }
+
+    let len = names.len() as VmIndex;
+    let value = {
+        let Context {
+            ref mut gc,
+            ref stack,
+            ..
+        } = *context;
+        thread::alloc(
+            gc,
+            thread,
+            stack,
+            RecordDef {
+                elems: &stack[stack.len() - len..],
+                fields: &names,
+            },
+        )?
+    };
+    for _ in 0..len {
+        context.stack.pop();
+    }
+    context.stack.push(ValueRepr::Data(value));
+    Ok(())
+}
+
+fn push_variant(
+    thread: &Thread,
+    context: &mut Context,
+    typ: &ArcType,
+    constructor: String,
+    args: Vec<ValueBuilder>,
+) -> Result<()> {
+    let resolved = remove_aliases(thread, typ);
+    let (tag, field) = match *resolved {
+        Type::Variant(ref row) => row.row_iter()
+            .enumerate()
+            .find(|&(_, field)| field.name.as_ref() == constructor)
+            .map(|(tag, field)| (tag as VmTag, field.clone()))
+            .ok_or_else(|| {
+                Error::Message(format!(
+                    "The constructor `{}` does not exist in `{}`",
+                    constructor, typ
+                ))
+            })?,
+        _ => {
+            return Err(Error::Message(format!(
+                "Expected `{}` but found the variant `{}`",
+                typ, constructor
+            )))
+        }
+    };
+
+    let arg_types: Vec<_> = arg_iter(&field.typ).cloned().collect();
+    if arg_types.len() != args.len() {
+        return Err(Error::Message(format!(
+            "The constructor `{}` takes {} arguments but {} were given",
+            constructor,
+            arg_types.len(),
+            args.len()
+        )));
+    }
+    if args.is_empty() {
+        context.stack.push(ValueRepr::Tag(tag));
+        return Ok(());
+    }
+
+    let len = args.len() as VmIndex;
+    for (arg, arg_type) in args.into_iter().zip(&arg_types) {
+        arg.push_checked(thread, context, arg_type)?;
+    }
+    let value = {
+        let Context {
+            ref mut gc,
+            ref stack,
+            ..
+        } = *context;
+        thread::alloc(
+            gc,
+            thread,
+            stack,
+            Def {
+                tag: tag,
+                elems: &stack[stack.len() - len..],
+            },
+        )?
+    };
+    for _ in 0..len {
+        context.stack.pop();
+    }
+    context.stack.push(ValueRepr::Data(value));
+    Ok(())
+}
+
+fn push_array(
+    thread: &Thread,
+    context: &mut Context,
+    typ: &ArcType,
+    elems: Vec<ValueBuilder>,
+) -> Result<()> {
+    let resolved = remove_aliases(thread, typ);
+    let elem_type = match *resolved {
+        Type::App(ref array, ref args)
+            if args.len() == 1 && **array == Type::Builtin(BuiltinType::Array) =>
+        {
+            args[0].clone()
+        }
+        _ => return Err(Error::Message(format!("Expected `{}` but found an array", typ))),
+    };
+
+    let len = elems.len() as VmIndex;
+    for elem in elems {
+        elem.push_checked(thread, context, &elem_type)?;
+    }
+    let value = {
+        let Context {
+            ref mut gc,
+            ref stack,
+            ..
+        } = *context;
+        thread::alloc(gc, thread, stack, ArrayDef(&stack[stack.len() - len..]))?
+    };
+    for _ in 0..len {
+        context.stack.pop();
+    }
+    context.stack.push(ValueRepr::Array(value));
+    Ok(())
+}
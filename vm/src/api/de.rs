@@ -177,7 +177,7 @@ where
     T: DeserializeOwned,
 {
     let env = thread.global_env().get_env();
-    let mut deserializer = Deserializer::from_value(thread, &*env, value, typ);
+    let mut deserializer = Deserializer::from_value(thread, &env, value, typ);
     T::deserialize(&mut deserializer)
 }
 
@@ -759,12 +759,67 @@ impl<'de, 'a, 't> VariantAccess<'de> for Enum<'a, 'de, 't> {
     fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
     where
         T: DeserializeSeed<'de>,
+    {
+        self.deserialize_argument(|de| seed.deserialize(de))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    // Struct variants are represented either as variants which take a single record as their
+    // argument or, as before, by positional arguments where the field names are ignored
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.has_record_argument() {
+            self.deserialize_argument(|de| de::Deserializer::deserialize_map(de, visitor))
+        } else {
+            de::Deserializer::deserialize_seq(self.de, visitor)
+        }
+    }
+}
+
+impl<'a, 'de, 't> Enum<'a, 'de, 't> {
+    /// Returns `true` if the current variant takes a single record as its only argument
+    fn has_record_argument(&self) -> bool {
+        let typ = resolve::remove_aliases_cow(self.de.state.env, self.de.typ);
+        match (self.de.input.as_ref(), &**typ) {
+            (ValueRef::Data(data), &Type::Variant(ref row)) => {
+                match row.row_iter().nth(data.tag() as usize) {
+                    Some(field) => {
+                        let mut args = arg_iter(&field.typ);
+                        match (args.next(), args.next()) {
+                            (Some(arg), None) => {
+                                match **resolve::remove_aliases_cow(self.de.state.env, arg) {
+                                    Type::Record(_) => true,
+                                    _ => false,
+                                }
+                            }
+                            _ => false,
+                        }
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Calls `deserialize` with a deserializer for the single argument of the variant
+    fn deserialize_argument<F, R>(self, deserialize: F) -> Result<R>
+    where
+        F: for<'x, 'y> FnOnce(&'x mut Deserializer<'de, 'y>) -> Result<R>,
     {
         let typ = resolve::remove_aliases_cow(self.de.state.env, self.de.typ);
         match (self.de.input.as_ref(), &**typ) {
             (ValueRef::Data(data), &Type::Variant(ref row)) => {
                 match row.row_iter().nth(data.tag() as usize) {
-                    Some(field) => seed.deserialize(&mut Deserializer {
+                    Some(field) => deserialize(&mut Deserializer {
                         input: data.get_variant(0).ok_or_else(|| {
                             VmError::Message("Expected variant to have a value argument".into())
                         })?,
@@ -773,24 +828,10 @@ impl<'de, 'a, 't> VariantAccess<'de> for Enum<'a, 'de, 't> {
                         })?,
                         ..self.de.clone()
                     }),
-                    None => seed.deserialize(self.de),
+                    None => deserialize(self.de),
                 }
             }
-            _ => seed.deserialize(self.de),
+            _ => deserialize(self.de),
         }
     }
-
-    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        de::Deserializer::deserialize_seq(self.de, visitor)
-    }
-
-    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
-    where
-        V: Visitor<'de>,
-    {
-        de::Deserializer::deserialize_seq(self.de, visitor)
-    }
 }
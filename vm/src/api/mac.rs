@@ -260,3 +260,42 @@ macro_rules! record_p {
         }
     }
 }
+
+/// Declares an uninhabited Rust type which stands for the gluon type `$name`, letting types which
+/// only exist in gluon be used as the parameter of `api::Phantom`. The gluon type must have been
+/// loaded before the Rust type is used.
+///
+/// ```rust
+/// #[macro_use]
+/// extern crate gluon_vm;
+///
+/// gluon_type!(Player = "game.Player");
+///
+/// fn main() {}
+/// ```
+#[macro_export]
+macro_rules! gluon_type {
+    ($(#[$attr: meta])* pub $ty: ident = $name: expr) => {
+        $(#[$attr])*
+        pub enum $ty {}
+
+        gluon_type!(@impl $ty = $name);
+    };
+    ($(#[$attr: meta])* $ty: ident = $name: expr) => {
+        $(#[$attr])*
+        enum $ty {}
+
+        gluon_type!(@impl $ty = $name);
+    };
+    (@impl $ty: ident = $name: expr) => {
+        impl $crate::api::VmType for $ty {
+            type Type = $ty;
+
+            fn make_type(vm: &$crate::thread::Thread) -> $crate::internal::ArcType {
+                vm.find_type_info($name)
+                    .unwrap_or_else(|err| panic!("{}", err))
+                    .into_type()
+            }
+        }
+    };
+}
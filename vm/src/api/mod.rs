@@ -5,7 +5,7 @@ use gc::{DataDef, Gc, GcPtr, Move, Traverseable};
 use base::symbol::{Symbol, Symbols};
 use base::scoped_map::ScopedMap;
 use stack::{Lock, StackFrame};
-use vm::{self, Root, RootStr, RootedValue, Status, Thread};
+use vm::{self, Root, RootScope, RootStr, RootedValue, Status, Thread};
 use value::{ArrayDef, ArrayRepr, Cloner, DataStruct, Def, ExternFunction, GcStr, Value,
             ValueArray, ValueRepr};
 use thread::{self, Context, RootedThread, VmRoot};
@@ -17,7 +17,9 @@ use compiler::{CompiledFunction, CompiledModule};
 use std::any::Any;
 use std::cell::Ref;
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::hash::{BuildHasher, Hash};
 use std::marker::PhantomData;
 use std::ops::Deref;
 use std::result::Result as StdResult;
@@ -28,6 +30,8 @@ pub use value::Userdata;
 
 #[macro_use]
 pub mod mac;
+pub mod builder;
+pub mod scoped;
 #[cfg(feature = "serde")]
 pub mod ser;
 #[cfg(feature = "serde")]
@@ -309,6 +313,91 @@ pub mod generic {
     make_generics!{A B C D E F G H I J K L M N O P Q R X Y Z}
 }
 
+/// Wrapper which gives the value `T` a more precise gluon type by setting the last type parameter
+/// of `T`'s gluon type to `P`. Since `P` only exists in the type, values can be converted between
+/// `Phantom` types with different parameters without touching the value itself.
+///
+/// Useful for userdata such as `Handle` registered as `Handle a`, where `a` is a type which is
+/// only meaningful to scripts. `Phantom<Handle, generic::A>` has the gluon type `Handle a` and
+/// `Phantom<&Handle, Player>` has the type `Handle Player` where `Player` can be declared with
+/// `gluon_type!`.
+pub struct Phantom<T, P>(pub T, PhantomData<P>);
+
+impl<T, P> Phantom<T, P> {
+    pub fn new(value: T) -> Phantom<T, P> {
+        Phantom(value, PhantomData)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T, P> Deref for Phantom<T, P> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T, P> fmt::Debug for Phantom<T, P>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T, P> VmType for Phantom<T, P>
+where
+    T: VmType,
+    T::Type: Sized,
+    P: VmType,
+    P::Type: Sized,
+{
+    type Type = Phantom<T::Type, P::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        let param = P::make_type(vm);
+        let typ = T::make_type(vm);
+        match *typ {
+            Type::App(ref ctor, ref args) if !args.is_empty() => {
+                let mut args = args.clone();
+                *args.last_mut().unwrap() = param;
+                Type::app(ctor.clone(), args)
+            }
+            _ => Type::app(typ.clone(), collect![param]),
+        }
+    }
+
+    fn extra_args() -> VmIndex {
+        T::extra_args()
+    }
+}
+
+impl<'vm, T, P> Pushable<'vm> for Phantom<T, P>
+where
+    T: Pushable<'vm>,
+{
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        self.0.push(thread, context)
+    }
+}
+
+impl<'vm, T, P> Getable<'vm> for Phantom<T, P>
+where
+    T: Getable<'vm>,
+{
+    unsafe fn from_value_unsafe(vm: &'vm Thread, value: Variants) -> Self {
+        Phantom::new(T::from_value_unsafe(vm, value))
+    }
+
+    fn from_value(vm: &'vm Thread, value: Variants) -> Self {
+        Phantom::new(T::from_value(vm, value))
+    }
+}
+
 fn insert_forall(
     variables: &mut ScopedMap<Symbol, types::Generic<Symbol>>,
     typ: &ArcType,
@@ -726,7 +815,7 @@ impl<'vm, 's> Pushable<'vm> for &'s String {
 }
 impl<'vm, 's> Pushable<'vm> for &'s str {
     fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
-        let s = unsafe { GcStr::from_utf8_unchecked(context.alloc_with(thread, self.as_bytes())?) };
+        let s = context.alloc_str(thread, self)?;
         context.stack.push(ValueRepr::String(s));
         Ok(())
     }
@@ -863,6 +952,235 @@ where
         Ok(())
     }
 }
+impl<'vm, T> Getable<'vm> for Vec<T>
+where
+    T: Getable<'vm>,
+{
+    fn from_value(vm: &'vm Thread, value: Variants) -> Vec<T> {
+        match value.as_ref() {
+            ValueRef::Array(array) => array.iter().map(|v| T::from_value(vm, v)).collect(),
+            _ => ice!("ValueRef is not an Array"),
+        }
+    }
+}
+
+macro_rules! define_array {
+    ($($id: ident)+) => {
+        impl<T> VmType for [T; count!($($id),+)]
+        where
+            T: VmType,
+            T::Type: Sized,
+        {
+            type Type = Vec<T::Type>;
+
+            fn make_type(thread: &Thread) -> ArcType {
+                Array::<T>::make_type(thread)
+            }
+        }
+
+        impl<'vm, T> Pushable<'vm> for [T; count!($($id),+)]
+        where
+            T: Pushable<'vm>,
+        {
+            fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+                let values: Box<[T]> = Box::new(self);
+                values.into_vec().push(thread, context)
+            }
+        }
+
+        impl<'vm, T> Getable<'vm> for [T; count!($($id),+)]
+        where
+            T: Getable<'vm>,
+        {
+            fn from_value(vm: &'vm Thread, value: Variants) -> [T; count!($($id),+)] {
+                let values = Vec::<T>::from_value(vm, value);
+                let len = count!($($id),+);
+                if values.len() != len {
+                    ice!("Expected an array of length {}, found {}", len, values.len());
+                }
+                let mut iter = values.into_iter();
+                [ $( { let $id = iter.next().unwrap(); $id } ),+ ]
+            }
+        }
+    }
+}
+
+macro_rules! define_arrays {
+    ($first: ident) => {
+        define_array!{ $first }
+    };
+    ($first: ident $($rest: ident)+) => {
+        define_array!{ $first $($rest)+ }
+        define_arrays!{ $($rest)+ }
+    }
+}
+define_arrays! {
+    a b c d e f g h i j k l m n o p q r s t u v w x y z a0 b0 c0 d0 e0 f0
+}
+
+fn make_map_type<K, V>(vm: &Thread) -> ArcType
+where
+    K: VmType,
+    V: VmType,
+{
+    let map_alias = vm.find_type_info("std.map.Map")
+        .unwrap_or_else(|err| panic!("{}", err))
+        .clone()
+        .into_type();
+    Type::app(map_alias, collect![K::make_type(vm), V::make_type(vm)])
+}
+
+/// Pushes `entries`, which must be sorted by their keys, as a balanced `std.map.Map`
+fn push_map<'vm, K, V>(
+    thread: &'vm Thread,
+    context: &mut Context,
+    entries: &mut [Option<(K, V)>],
+) -> Result<()>
+where
+    K: Pushable<'vm>,
+    V: Pushable<'vm>,
+{
+    if entries.is_empty() {
+        // Tip
+        context.stack.push(ValueRepr::Tag(1));
+        return Ok(());
+    }
+    let middle = entries.len() / 2;
+    let (left, rest) = entries.split_at_mut(middle);
+    let (entry, right) = rest.split_first_mut().unwrap();
+    let (key, value) = entry.take().expect("Map entry pushed twice");
+    key.push(thread, context)?;
+    value.push(thread, context)?;
+    push_map(thread, context, left)?;
+    push_map(thread, context, right)?;
+    let result = {
+        let Context {
+            ref mut gc,
+            ref stack,
+            ..
+        } = *context;
+        let fields = &stack[stack.len() - 4..];
+        thread::alloc(
+            gc,
+            thread,
+            stack,
+            Def {
+                tag: 0,
+                elems: fields,
+            },
+        )?
+    };
+    for _ in 0..4 {
+        context.stack.pop();
+    }
+    context.stack.push(ValueRepr::Data(result));
+    Ok(())
+}
+
+fn get_map<'vm, K, V, F>(vm: &'vm Thread, value: Variants, mut insert: F)
+where
+    K: Getable<'vm>,
+    V: Getable<'vm>,
+    F: FnMut(K, V),
+{
+    // Maps built by `std.map.insert` are not balanced so avoid recursing through the tree
+    let mut nodes = vec![value];
+    while let Some(node) = nodes.pop() {
+        match node.as_ref() {
+            ValueRef::Data(data) => if data.tag() == 0 {
+                insert(
+                    K::from_value(vm, data.get_variant(0).unwrap()),
+                    V::from_value(vm, data.get_variant(1).unwrap()),
+                );
+                nodes.extend(data.get_variant(2));
+                nodes.extend(data.get_variant(3));
+            },
+            _ => ice!("ValueRef is not a Map"),
+        }
+    }
+}
+
+impl<K, V> VmType for BTreeMap<K, V>
+where
+    K: VmType,
+    K::Type: Sized,
+    V: VmType,
+    V::Type: Sized,
+{
+    type Type = BTreeMap<K::Type, V::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        make_map_type::<K, V>(vm)
+    }
+}
+
+impl<'vm, K, V> Pushable<'vm> for BTreeMap<K, V>
+where
+    K: Pushable<'vm>,
+    V: Pushable<'vm>,
+{
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        let mut entries = self.into_iter().map(Some).collect::<Vec<_>>();
+        push_map(thread, context, &mut entries)
+    }
+}
+
+impl<'vm, K, V> Getable<'vm> for BTreeMap<K, V>
+where
+    K: Getable<'vm> + Ord,
+    V: Getable<'vm>,
+{
+    fn from_value(vm: &'vm Thread, value: Variants) -> BTreeMap<K, V> {
+        let mut map = BTreeMap::new();
+        get_map(vm, value, |key, value| {
+            map.insert(key, value);
+        });
+        map
+    }
+}
+
+impl<K, V, S> VmType for HashMap<K, V, S>
+where
+    K: VmType,
+    K::Type: Sized,
+    V: VmType,
+    V::Type: Sized,
+{
+    type Type = HashMap<K::Type, V::Type>;
+
+    fn make_type(vm: &Thread) -> ArcType {
+        make_map_type::<K, V>(vm)
+    }
+}
+
+impl<'vm, K, V, S> Pushable<'vm> for HashMap<K, V, S>
+where
+    K: Pushable<'vm> + Ord + Hash,
+    V: Pushable<'vm>,
+    S: BuildHasher,
+{
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        let mut entries = self.into_iter().collect::<Vec<_>>();
+        entries.sort_by(|l, r| l.0.cmp(&r.0));
+        let mut entries = entries.into_iter().map(Some).collect::<Vec<_>>();
+        push_map(thread, context, &mut entries)
+    }
+}
+
+impl<'vm, K, V, S> Getable<'vm> for HashMap<K, V, S>
+where
+    K: Getable<'vm> + Eq + Hash,
+    V: Getable<'vm>,
+    S: BuildHasher + Default,
+{
+    fn from_value(vm: &'vm Thread, value: Variants) -> HashMap<K, V, S> {
+        let mut map = HashMap::default();
+        get_map(vm, value, |key, value| {
+            map.insert(key, value);
+        });
+        map
+    }
+}
 
 impl<'s, T: VmType> VmType for *const T {
     type Type = T::Type;
@@ -1443,6 +1761,7 @@ pub mod record {
 
     use frunk_core::hlist::{h_cons, HCons, HList, HNil, Plucker};
 
+    use base::fnv::FnvMap;
     use base::types;
     use base::types::ArcType;
     use base::symbol::Symbol;
@@ -1534,6 +1853,21 @@ pub mod record {
             let len = T::static_len();
             let mut fields = Vec::with_capacity(len);
             T::field_types(vm, &mut fields);
+
+            // Fields which share a name are overloads of that name, the later ones are renamed to
+            // `name@1`, `name@2` ... and each use of `name` is resolved to one of them by its type
+            let mut overloads = FnvMap::default();
+            for field in &mut fields {
+                let count = overloads
+                    .entry(String::from(field.name.as_ref()))
+                    .or_insert(0);
+                if *count > 0 {
+                    let name = types::overload_name(field.name.as_ref(), *count);
+                    field.name = Symbol::from(name);
+                }
+                *count += 1;
+            }
+
             let type_cache = vm.global_env().type_cache();
             type_cache.record(Vec::new(), fields)
         }
@@ -1612,24 +1946,30 @@ impl<'vm, F: VmType> VmType for RefPrimitive<'vm, F> {
     }
 }
 
-impl<'vm, F> Pushable<'vm> for RefPrimitive<'vm, F>
-where
-    F: VmFunction<'vm> + FunctionType + VmType + 'vm,
-{
-    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+impl<'vm, F> From<RefPrimitive<'vm, F>> for Primitive<F> {
+    fn from(primitive: RefPrimitive<'vm, F>) -> Primitive<F> {
         use std::mem::transmute;
         let extern_function = unsafe {
             // The VM guarantess that it only ever calls this function with itself which should
             // make sure that ignoring the lifetime is safe
             transmute::<extern "C" fn(&'vm Thread) -> Status, extern "C" fn(&Thread) -> Status>(
-                self.function,
+                primitive.function,
             )
         };
         Primitive {
             function: extern_function,
-            name: self.name,
-            _typ: self._typ,
-        }.push(thread, context)
+            name: primitive.name,
+            _typ: primitive._typ,
+        }
+    }
+}
+
+impl<'vm, F> Pushable<'vm> for RefPrimitive<'vm, F>
+where
+    F: VmFunction<'vm> + FunctionType + VmType + 'vm,
+{
+    fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+        Primitive::from(self).push(thread, context)
     }
 }
 
@@ -1901,16 +2241,59 @@ impl<'vm, T, $($args,)* R> Function<T, fn($($args),*) -> R>
 {
     #[allow(non_snake_case)]
     pub fn call(&'vm mut self $(, $args: $args)*) -> Result<R> {
-        match self.call_first($($args),*)? {
-            Async::Ready(value) => Ok(value),
+        let vm = self.value.vm();
+        match self.execute(true $(, $args)*)? {
+            Async::Ready(value) => Self::return_value(vm, value),
             Async::NotReady => Err(Error::Message("Unexpected async".into())),
         }
     }
 
     #[allow(non_snake_case)]
     fn call_first(&'vm self $(, $args: $args)*) -> Result<Async<R>> {
+        let vm = self.value.vm();
+        match self.execute(false $(, $args)*)? {
+            Async::Ready(value) => Self::return_value(vm, value).map(Async::Ready),
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+
+    fn return_value(vm: &Thread, value: Value) -> Result<R> {
+        unsafe {
+            Ok(R::from_value(vm, Variants::new(&value)))
+        }
+    }
+}
+
+impl<'vm, T, $($args,)* R> Function<T, fn($($args),*) -> R>
+    where $($args: Pushable<'vm>,)*
+          T: Deref<Target = Thread>,
+          R: VmType,
+{
+    /// Calls the function and converts its result through `scope`, which must belong to the
+    /// thread of the function. The result is rooted in the scope so it may borrow from it, for
+    /// instance as a `&str` when the function returns a `String`.
+    #[allow(non_snake_case)]
+    pub fn call_scoped<'s, S>(&'vm mut self, scope: &'s RootScope $(, $args: $args)*) -> Result<S>
+        where S: VmType<Type = R::Type> + Getable<'s>,
+    {
+        debug_assert!(
+            scope.thread() as *const Thread == self.value.vm() as *const Thread,
+            "The scope must belong to the thread of the function"
+        );
+        match self.execute(true $(, $args)*)? {
+            Async::Ready(value) => Ok(scope.marshal(value)),
+            Async::NotReady => Err(Error::Message("Unexpected async".into())),
+        }
+    }
+
+    /// Calls the function and returns its result, which is not rooted. A `sync` call can not be
+    /// suspended so it fails with `Error::OutOfFuel` if the fuel of the thread runs out.
+    #[allow(non_snake_case)]
+    fn execute(&'vm self, sync: bool $(, $args: $args)*) -> Result<Async<Value>> {
         let vm = self.value.vm();
         let mut context = vm.context();
+        let frame_level = context.stack.get_frames().len();
+        let stack_len = context.stack.len();
         context.stack.push(self.value.get_variant());
         $(
             $args.push(&vm, &mut context)?;
@@ -1919,21 +2302,29 @@ impl<'vm, T, $($args,)* R> Function<T, fn($($args),*) -> R>
             0.push(&vm, &mut context).unwrap();
         }
         let args = count!($($args),*) + R::extra_args();
-        vm.call_function(context, args).and_then(|async|{
-            match async {
-                Async::Ready(context) => {
-                    let value = context.unwrap().stack.pop();
-                    Self::return_value(vm, value).map(Async::Ready)
+        vm.call_function(context, args)
+            .and_then(|async| match async {
+                Async::NotReady if sync && vm.context().stop_if_out_of_fuel() => {
+                    Err(Error::OutOfFuel)
                 }
-                Async::NotReady => Ok(Async::NotReady),
-            }
-        })
-    }
-
-    fn return_value(vm: &Thread, value: Value) -> Result<R> {
-        unsafe {
-            Ok(R::from_value(vm, Variants::new(&value)))
-        }
+                async => Ok(async),
+            })
+            .map_err(|err| {
+                // Remove the frames and values of the failed call, otherwise the caller would
+                // resume them and run into the same error again (for instance when an allocation
+                // fails)
+                let mut context = vm.context();
+                let mut stack = StackFrame::current(&mut context.stack);
+                while stack.stack.get_frames().len() > frame_level {
+                    if stack.exit_scope().is_err() {
+                        break;
+                    }
+                }
+                let excess = stack.stack.len().saturating_sub(stack_len);
+                stack.stack.pop_many(excess as usize);
+                err
+            })
+            .map(|async| async.map(|context| context.unwrap().stack.pop()))
     }
 }
 
@@ -2026,7 +2417,7 @@ impl<'vm, T: VmType> Pushable<'vm> for TypedBytecode<T> {
             "".into(),
         ));
         compiled_module.function.instructions = self.instructions;
-        let closure = thread.global_env().new_global_thunk(compiled_module)?;
+        let closure = thread.new_global_thunk(compiled_module)?;
         context.stack.push(ValueRepr::Closure(closure));
         Ok(())
     }
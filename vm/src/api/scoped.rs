@@ -0,0 +1,135 @@
+//! Borrowed Rust values which can be passed to gluon without copying them
+//!
+//! ```
+//! extern crate gluon;
+//! #[macro_use]
+//! extern crate gluon_vm;
+//!
+//! use gluon::{Compiler, Thread, new_vm};
+//! use gluon::vm::api::{FunctionRef, RuntimeResult};
+//! use gluon::vm::api::scoped::{self, Scoped};
+//! use gluon::vm::types::VmInt;
+//! use gluon::vm::Error;
+//! # fn main() {
+//!
+//! fn len(values: &Scoped<Vec<VmInt>>) -> RuntimeResult<VmInt, Error> {
+//!     values.with(|values| values.len() as VmInt).into()
+//! }
+//!
+//! let thread = new_vm();
+//! thread
+//!     .register_type::<Scoped<Vec<VmInt>>>("Values", &[])
+//!     .unwrap();
+//! thread
+//!     .define_global("len", primitive!(1 len))
+//!     .unwrap();
+//!
+//! Compiler::new()
+//!     .load_script(&thread, "count", r"let count values : Values -> Int = len values in count")
+//!     .unwrap_or_else(|err| panic!("{}", err));
+//! let mut count: FunctionRef<fn(Scoped<Vec<VmInt>>) -> VmInt> = thread
+//!     .get_global("count")
+//!     .unwrap();
+//!
+//! let values = vec![1, 2, 3];
+//! let result = scoped::scope(&values, |values| count.call(values))
+//!     .unwrap_or_else(|err| panic!("{}", err));
+//! assert_eq!(result, 3);
+//! # }
+//! ```
+use std::any::Any;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use {Error, Result};
+use api::VmType;
+use gc::Traverseable;
+use value::Userdata;
+
+/// Calls `f` with a `Scoped` reference to `value` which can be passed to gluon. Every reference
+/// created by this call is invalidated before `scope` returns so scripts which store the
+/// reference can not read `value` afterwards.
+pub fn scope<T, F, R>(value: &T, f: F) -> R
+where
+    T: Any + Sync,
+    F: FnOnce(Scoped<T>) -> R,
+{
+    // Invalidates the references even if `f` panics
+    struct Invalidate(Arc<RwLock<bool>>);
+
+    impl Drop for Invalidate {
+        fn drop(&mut self) {
+            // Waits until no reference is reading the value
+            *self.0.write().unwrap_or_else(|err| err.into_inner()) = false;
+        }
+    }
+
+    let alive = Invalidate(Arc::new(RwLock::new(true)));
+    f(Scoped {
+        value: value as *const T,
+        alive: alive.0.clone(),
+    })
+}
+
+/// A reference to a Rust value, created by `scope`, which can be pushed to gluon as userdata.
+/// Reading the value after `scope` has returned is an error.
+///
+/// The type of the reference must be registered with `Thread::register_type` before it is pushed.
+pub struct Scoped<T> {
+    value: *const T,
+    alive: Arc<RwLock<bool>>,
+}
+
+// `value` is only read while `alive` is locked and the value which it points to is borrowed by
+// `scope`
+unsafe impl<T: Sync> Send for Scoped<T> {}
+unsafe impl<T: Sync> Sync for Scoped<T> {}
+
+impl<T> Clone for Scoped<T> {
+    fn clone(&self) -> Scoped<T> {
+        Scoped {
+            value: self.value,
+            alive: self.alive.clone(),
+        }
+    }
+}
+
+impl<T> Scoped<T> {
+    /// Calls `f` with the referenced value, returning an error if the `scope` which the reference
+    /// was created in has returned
+    pub fn with<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        let alive = self.alive.read().unwrap_or_else(|err| err.into_inner());
+        if *alive {
+            Ok(f(unsafe { &*self.value }))
+        } else {
+            Err(Error::Message(
+                "Scoped reference was used after its scope ended".into(),
+            ))
+        }
+    }
+
+    /// Returns `true` if the referenced value can still be read
+    pub fn is_alive(&self) -> bool {
+        *self.alive.read().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<T> fmt::Debug for Scoped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Scoped({:p}, alive: {})", self.value, self.is_alive())
+    }
+}
+
+impl<T> Traverseable for Scoped<T> {}
+
+impl<T: Any + Sync> Userdata for Scoped<T> {}
+
+impl<T> VmType for Scoped<T>
+where
+    T: Any,
+{
+    type Type = Scoped<T>;
+}
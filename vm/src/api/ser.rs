@@ -84,6 +84,7 @@ use gluon::vm::api::ser::Ser;
 enum Enum {
     A(i32),
     B(String, i32),
+    C { x: i32, y: i32 },
 }
 
 impl VmType for Enum {
@@ -98,12 +99,13 @@ impl VmType for Enum {
 let thread = new_vm();
 
 let expr = r#"
-type Enum = | A Int | B String Int
+type Enum = | A Int | B String Int | C { x : Int, y : Int }
 
 let f e =
     match e with
     | A a -> a
     | B b c -> c
+    | C { x, y } -> x #Int* y
 
 { Enum, f }
 "#;
@@ -119,6 +121,10 @@ let mut f: FunctionRef<fn (Ser<Enum>) -> i32> = thread
 let result = f.call(Ser(Enum::B("".to_string(), 4))).unwrap_or_else(|err| panic!("{}", err));
 assert_eq!(result, 4);
 
+// Struct variants are passed to gluon as a variant which takes a record
+let result = f.call(Ser(Enum::C { x: 2, y: 3 })).unwrap_or_else(|err| panic!("{}", err));
+assert_eq!(result, 6);
+
 # }
 ```
 */
@@ -535,23 +541,26 @@ impl<'a, 'vm> ser::SerializeStruct for RecordSerializer<'a, 'vm> {
     }
 }
 
-// Similar to `SerializeTupleVariant`, here the `end` method is responsible for
-// closing both of the curly braces opened by `serialize_struct_variant`.
+// Struct variants are pushed as variants which take a single record as their argument, so
+// `end` allocates the record before the variant itself.
 impl<'a, 'vm> ser::SerializeStructVariant for RecordSerializer<'a, 'vm> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        let field = self.thread.global_env().intern(key)?;
+        self.fields.push(field);
         value.serialize(&mut **self)?;
         self.values += 1;
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok> {
-        self.serializer.alloc(self.variant_index, self.values)
+        self.serializer.alloc_record(&self.fields, self.values)?;
+        self.serializer.alloc(self.variant_index, 1)
     }
 }
 
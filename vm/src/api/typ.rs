@@ -529,11 +529,25 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
         Ok(value)
     }
 
+    // Struct variants are mapped to variants which take a single record as their argument
     fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.tuple_variant(fields.len(), visitor)
+        let (value, types) = {
+            let mut map_deserializer = MapDeserializer::new(&mut *self.de, fields.iter().cloned());
+            (
+                visitor.visit_map(&mut map_deserializer)?,
+                map_deserializer.types,
+            )
+        };
+        let record = self.de.state.cache.record(vec![], types);
+        let enum_type = Type::ident(self.de.state.symbols.symbol(self.enum_name));
+        self.de.variant = Some(Field::new(
+            self.de.state.symbols.symbol(self.variant),
+            Type::function(collect![record], enum_type),
+        ));
+        Ok(value)
     }
 }
 
@@ -573,6 +587,7 @@ mod tests {
         A,
         B(i32),
         C(String, f64),
+        D { x: i32 },
     }
 
     #[test]
@@ -595,6 +610,18 @@ mod tests {
                         Type::ident(symbols.symbol("Enum")),
                     ),
                 ),
+                Field::new(
+                    symbols.symbol("D"),
+                    Type::function(
+                        vec![
+                            Type::record(
+                                vec![],
+                                vec![Field::new(symbols.symbol("x"), Type::int())],
+                            ),
+                        ],
+                        Type::ident(symbols.symbol("Enum")),
+                    ),
+                ),
             ])
         );
     }
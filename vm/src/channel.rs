@@ -3,19 +3,27 @@ use std::fmt;
 use std::sync::{Arc, Mutex};
 use std::collections::VecDeque;
 
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
 use futures::Future;
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
 use futures::sync::oneshot;
 
 use base::types::{ArcType, Type};
 
 use {Error, ExternModule, Result as VmResult};
-use api::{primitive, AsyncPushable, Function, FunctionRef, FutureResult, Generic, Getable,
-          OpaqueValue, OwnedFunction, Pushable, RuntimeResult, VmType, WithVM, IO};
+use api::{primitive, Function, FunctionRef, Generic, OpaqueValue, Pushable, RuntimeResult,
+          VmType, WithVM, IO};
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+use api::{AsyncPushable, FutureResult, Getable, OwnedFunction};
 use api::generic::A;
 use gc::{Gc, GcPtr, Traverseable};
 use vm::{RootedThread, Status, Thread};
-use thread::{OwnedContext, ThreadInternal};
-use value::{Callable, GcStr, Userdata, ValueRepr};
+use thread::ThreadInternal;
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+use thread::OwnedContext;
+use value::{GcStr, Userdata, ValueRepr};
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+use value::Callable;
 use stack::{StackFrame, State};
 use types::VmInt;
 
@@ -214,15 +222,15 @@ fn spawn_<'vm>(value: WithVM<'vm, Function<&'vm Thread, fn(())>>) -> VmResult<Ro
 
 type Action = fn(()) -> OpaqueValue<RootedThread, IO<Generic<A>>>;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(any(not(feature = "async"), target_arch = "wasm32"))]
 fn spawn_on<'vm>(
     _thread: RootedThread,
     _action: WithVM<'vm, FunctionRef<Action>>,
 ) -> IO<OpaqueValue<&'vm Thread, IO<Generic<A>>>> {
-    IO::Exception("spawn_on requires the `tokio_core` crate".to_string())
+    IO::Exception("spawn_on requires the `async` feature and the `tokio_core` crate".to_string())
 }
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
 fn spawn_on<'vm>(
     thread: RootedThread,
     action: WithVM<'vm, FunctionRef<Action>>,
@@ -6,6 +6,8 @@ use base::kind::{ArcKind, KindEnv};
 use base::types::{self, Alias, ArcType, BuiltinType, RecordSelector, Type, TypeEnv};
 use base::scoped_map::ScopedMap;
 use base::symbol::{Symbol, SymbolModule, SymbolRef};
+#[cfg(feature = "wasm")]
+use base::fnv::FnvMap;
 use base::pos::{Line, NO_EXPANSION};
 use base::source::Source;
 use core::{self, CExpr, Expr, Pattern};
@@ -36,7 +38,7 @@ enum FieldAccess {
 #[cfg_attr(feature = "serde_derive", serde(serialize_state = "::serialization::SeSeed"))]
 pub struct UpvarInfo {
     pub name: String,
-    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::typ"))]
     pub typ: ArcType,
 }
 
@@ -60,7 +62,7 @@ pub struct DebugInfo {
 #[cfg_attr(feature = "serde_derive_state", serde(serialize_state = "::serialization::SeSeed"))]
 pub struct CompiledModule {
     /// Storage for globals which are needed by the module which is currently being compiled
-    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::typ"))]
     pub module_globals: Vec<Symbol>,
     #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
     pub function: CompiledFunction,
@@ -75,12 +77,15 @@ pub struct CompiledFunction {
     /// The maximum possible number of stack slots needed for this function
     pub max_stack_size: VmIndex,
 
-    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::typ"))]
     pub id: Symbol,
 
-    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::typ"))]
     pub typ: ArcType,
     pub instructions: Vec<Instruction>,
+    /// The number of values pushed by each `Split` in `instructions`, in the order the
+    /// instructions appear. Used to convert the function into register instructions.
+    pub split_fields: Vec<VmIndex>,
 
     #[cfg_attr(feature = "serde_derive_state", serde(state))]
     pub inner_functions: Vec<CompiledFunction>,
@@ -88,11 +93,16 @@ pub struct CompiledFunction {
     #[cfg_attr(feature = "serde_derive_state", serde(state))]
     pub strings: Vec<InternedStr>,
 
-    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::typ"))]
     pub records: Vec<Vec<Symbol>>,
 
     #[cfg_attr(feature = "serde_derive_state", serde(state))]
     pub debug_info: DebugInfo,
+
+    /// The WebAssembly version of the function if it could be translated
+    #[cfg(feature = "wasm")]
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    pub wasm: Option<::wasm::WasmFunction>,
 }
 
 impl From<CompiledFunction> for CompiledModule {
@@ -112,6 +122,7 @@ impl CompiledFunction {
             id: id,
             typ: typ,
             instructions: Vec::new(),
+            split_fields: Vec::new(),
             inner_functions: Vec::new(),
             strings: Vec::new(),
             records: Vec::new(),
@@ -121,6 +132,8 @@ impl CompiledFunction {
                 upvars: Vec::new(),
                 source_name: source_name,
             },
+            #[cfg(feature = "wasm")]
+            wasm: None,
         }
     }
 }
@@ -299,6 +312,13 @@ impl FunctionEnv {
         }
     }
 
+    /// Emits a `Split` of a value with `fields` fields. The caller pushes a stack variable for
+    /// each field.
+    fn emit_split(&mut self, fields: usize) {
+        self.emit(Split);
+        self.function.split_fields.push(fields as VmIndex);
+    }
+
     fn emit_string(&mut self, s: InternedStr) {
         let index = self.add_string_constant(s);
         self.emit(PushString(index as VmIndex));
@@ -360,7 +380,7 @@ pub trait CompilerEnv: TypeEnv {
 impl CompilerEnv for TypeInfos {
     fn find_var(&self, id: &Symbol) -> Option<(Variable<Symbol>, ArcType)> {
         fn count_function_args(typ: &ArcType) -> VmIndex {
-            match typ.as_function() {
+            match typ.remove_forall().as_function() {
                 Some((_, ret)) => 1 + count_function_args(ret),
                 None => 0,
             }
@@ -368,10 +388,10 @@ impl CompilerEnv for TypeInfos {
 
         self.id_to_type
             .iter()
-            .filter_map(|(_, ref alias)| match **alias.unresolved_type() {
+            .filter_map(|(_, ref alias)| match **alias.unresolved_type().remove_forall() {
                 Type::Variant(ref row) => row.row_iter()
                     .enumerate()
-                    .find(|&(_, field)| field.name == *id),
+                    .find(|&(_, field)| field.name.as_ref() == id.definition_name()),
                 _ => None,
             })
             .next()
@@ -394,6 +414,9 @@ pub struct Compiler<'a> {
     source_name: String,
     emit_debug_info: bool,
     empty_symbol: Symbol,
+    /// The WebAssembly versions of the top level functions of the module being compiled
+    #[cfg(feature = "wasm")]
+    wasm_functions: FnvMap<Symbol, ::wasm::WasmFunction>,
 }
 
 impl<'a> KindEnv for Compiler<'a> {
@@ -408,7 +431,9 @@ impl<'a> TypeEnv for Compiler<'a> {
     }
 
     fn find_type_info(&self, id: &SymbolRef) -> Option<&Alias<Symbol, ArcType>> {
-        self.stack_types.get(id)
+        self.stack_types
+            .get(id)
+            .or_else(|| self.globals.find_type_info(id))
     }
 
     fn find_record(
@@ -445,6 +470,8 @@ impl<'a> Compiler<'a> {
             source: source,
             source_name: source_name,
             emit_debug_info: emit_debug_info,
+            #[cfg(feature = "wasm")]
+            wasm_functions: FnvMap::default(),
         }
     }
 
@@ -520,7 +547,7 @@ impl<'a> Compiler<'a> {
         match **x {
             Type::Variant(ref row) => row.row_iter()
                 .enumerate()
-                .find(|&(_, field)| field.name == *constructor)
+                .find(|&(_, field)| field.name.name_eq(constructor))
                 .map(|(tag, _)| tag as VmTag),
             _ => None,
         }
@@ -533,6 +560,11 @@ impl<'a> Compiler<'a> {
         let id = self.empty_symbol.clone();
         let typ = expr.env_type_of(&self.globals);
 
+        #[cfg(feature = "wasm")]
+        {
+            self.wasm_functions = ::wasm::translate(expr);
+        }
+
         env.start_function(self, 0, id, typ);
         info!("COMPILING: {}", expr);
         self.compile(&expr, &mut env, true)?;
@@ -753,11 +785,9 @@ impl<'a> Compiler<'a> {
                                     function.emit(FloatEQ);
                                 }
                                 ast::Literal::String(ref s) => {
-                                    self.load_identifier(&Symbol::from("@string_eq"), function)?;
-                                    let lhs_i = function.stack_size() - 2;
                                     function.emit(Push(lhs_i));
                                     function.emit_string(self.intern(&s)?);
-                                    function.emit(Call(2));
+                                    function.emit(StringEQ);
                                 }
                             };
                             start_jumps.push(function.function.instructions.len());
@@ -775,7 +805,7 @@ impl<'a> Compiler<'a> {
                         Pattern::Constructor(_, ref args) => {
                             function.function.instructions[start_index] =
                                 CJump(function.function.instructions.len() as VmIndex);
-                            function.emit(Split);
+                            function.emit_split(args.len());
                             for arg in args.iter() {
                                 function.push_stack_var(self, arg.name.clone(), arg.typ.clone());
                             }
@@ -830,7 +860,7 @@ impl<'a> Compiler<'a> {
                         function.emit(Construct {
                             tag: variants
                                 .row_iter()
-                                .position(|field| field.name == id.name)
+                                .position(|field| field.name.name_eq(&id.name))
                                 .unwrap() as VmTag,
                             args: exprs.len() as u32,
                         });
@@ -958,7 +988,7 @@ impl<'a> Compiler<'a> {
                                 function.new_stack_var(self, field_name, field.typ.clone());
                             }
                         } else {
-                            function.emit(Split);
+                            function.emit_split(typ.row_iter().count());
                             for field in typ.row_iter() {
                                 let (name, typ) =
                                     match fields.iter().find(|tup| tup.0.name.name_eq(&field.name))
@@ -1013,6 +1043,11 @@ impl<'a> Compiler<'a> {
         let function_index = function.function.inner_functions.len() as VmIndex;
         let free_vars = f.free_vars.len() as VmIndex;
         let FunctionEnv { function, .. } = f;
+        #[cfg(feature = "wasm")]
+        let function = CompiledFunction {
+            wasm: self.wasm_functions.get(&id.name).cloned(),
+            ..function
+        };
         Ok((function_index, free_vars, function))
     }
 }
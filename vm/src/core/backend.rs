@@ -0,0 +1,144 @@
+//! Functionality shared by the experimental backends which translate core into other languages
+//! (`rust_backend` and `wasm_backend`). Both backends only handle functions working on `Int`,
+//! `Float` and `Bool` values.
+use base::ast::TypedIdent;
+use base::symbol::Symbol;
+use base::types::{arg_iter, ArcType};
+
+use core::{CExpr, Closure, Expr, Named};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Scalar {
+    Int,
+    Float,
+    Bool,
+}
+
+impl Scalar {
+    pub(crate) fn from_type(typ: &ArcType) -> Option<Scalar> {
+        match typ.remove_forall().name().map(|name| name.declared_name()) {
+            Some("Int") => Some(Scalar::Int),
+            Some("Float") => Some(Scalar::Float),
+            Some("Bool") => Some(Scalar::Bool),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl BinOp {
+    /// Returns the operator called by `id` if it is either a primitive, `#Int+`, or an operator
+    /// which has been resolved to work on numbers or `Bool`, `+`
+    pub(crate) fn from_ident(id: &TypedIdent<Symbol>) -> Option<BinOp> {
+        let mut iter = arg_iter(id.typ.remove_forall());
+        let is_scalar = iter.by_ref().all(|arg| Scalar::from_type(arg).is_some())
+            && Scalar::from_type(iter.typ).is_some();
+        if !is_scalar {
+            return None;
+        }
+        let name = id.name
+            .declared_name()
+            .trim_left_matches("#Int")
+            .trim_left_matches("#Float");
+        Some(match name {
+            "+" => BinOp::Add,
+            "-" => BinOp::Sub,
+            "*" => BinOp::Mul,
+            "/" => BinOp::Div,
+            "==" => BinOp::Eq,
+            "/=" | "!=" => BinOp::Ne,
+            "<" => BinOp::Lt,
+            "<=" => BinOp::Le,
+            ">" => BinOp::Gt,
+            ">=" => BinOp::Ge,
+            _ => return None,
+        })
+    }
+
+    pub(crate) fn is_comparison(self) -> bool {
+        match self {
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div => false,
+            _ => true,
+        }
+    }
+}
+
+/// Returns the argument and return types of `closure` if they are all numbers or `Bool`
+pub(crate) fn signature(closure: &Closure) -> Result<(Vec<Scalar>, Scalar), String> {
+    let mut iter = arg_iter(closure.name.typ.remove_forall());
+    let mut args = Vec::new();
+    for (arg, arg_type) in closure.args.iter().zip(iter.by_ref()) {
+        args.push(Scalar::from_type(arg_type)
+            .ok_or_else(|| format!("argument `{}` is not a number or `Bool`", arg.name))?);
+    }
+    if args.len() != closure.args.len() {
+        return Err("the type of the function is not known".into());
+    }
+    let ret = Scalar::from_type(iter.typ)
+        .ok_or_else(|| "the return type is not a number or `Bool`".to_string())?;
+    Ok((args, ret))
+}
+
+fn top_level_closures<'a>(mut expr: CExpr<'a>) -> Vec<&'a Closure<'a>> {
+    let mut closures = Vec::new();
+    loop {
+        match *expr {
+            Expr::Let(ref bind, body) => {
+                if let Named::Recursive(ref group) = bind.expr {
+                    closures.extend(group);
+                }
+                expr = body;
+            }
+            // Unpacking a record, `let { x, y } = ...` (as the implicit prelude does)
+            Expr::Match(_, alts) if alts.len() == 1 => expr = alts[0].expr,
+            _ => return closures,
+        }
+    }
+}
+
+/// The top level functions of a module which could be translated, followed by the name of each
+/// function which could not be translated and the reason why
+pub(crate) type Translated<'a, T> = (Vec<(&'a Closure<'a>, T)>, Vec<(Symbol, String)>);
+
+/// Translates the top level functions of `expr` using `translate`, which is called with the
+/// functions which are being translated and should return the result of translating each of them.
+pub(crate) fn translate_functions<'a, T, F>(expr: CExpr<'a>, mut translate: F) -> Translated<'a, T>
+where
+    F: FnMut(&[&'a Closure<'a>]) -> Vec<Result<T, String>>,
+{
+    // Functions which call a function that can't be translated can't be translated either so
+    // keep removing functions until all the remaining functions can be translated
+    let mut skipped = Vec::new();
+    let mut candidates = top_level_closures(expr);
+    loop {
+        let results = translate(&candidates);
+        let mut translated = Vec::new();
+        let mut failed = false;
+        for (closure, result) in candidates.into_iter().zip(results) {
+            match result {
+                Ok(value) => translated.push((closure, value)),
+                Err(reason) => {
+                    skipped.push((closure.name.name.clone(), reason));
+                    failed = true;
+                }
+            }
+        }
+        if !failed {
+            return (translated, skipped);
+        }
+        candidates = translated.into_iter().map(|(closure, _)| closure).collect();
+    }
+}
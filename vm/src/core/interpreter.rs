@@ -1,43 +1,17 @@
 use std::ops::{Deref, DerefMut};
 use base::ast::{Literal, TypedIdent};
-use base::fnv::FnvSet;
 use base::merge::merge_iter;
 use base::kind::{ArcKind, KindEnv};
 use base::types::{Alias, ArcType, RecordSelector, TypeEnv};
 use base::scoped_map::ScopedMap;
 use base::symbol::{Symbol, SymbolRef};
 use core::{self, Allocator, CExpr, Closure, Expr, LetBinding, Named, Pattern};
-use core::optimize::{walk_expr_alloc, DifferentLifetime, ExprProducer, SameLifetime, Visitor};
+use core::optimize::{is_variable_in_expression, walk_expr_alloc, DifferentLifetime, ExprProducer,
+                     SameLifetime, Visitor};
 use types::*;
 
 use {Error, Result};
 
-fn is_variable_in_expression<'a, I>(iter: I, expr: CExpr) -> bool
-where
-    I: IntoIterator<Item = &'a Symbol>,
-{
-    struct FreeVars(FnvSet<Symbol>);
-    impl<'e> Visitor<'e, 'e> for FreeVars {
-        type Producer = SameLifetime<'e>;
-
-        fn visit_expr(&mut self, expr: CExpr<'e>) -> Option<CExpr<'e>> {
-            match *expr {
-                Expr::Ident(ref id, ..) => {
-                    self.0.insert(id.name.clone());
-                    None
-                }
-                _ => walk_expr_alloc(self, expr),
-            }
-        }
-        fn detach_allocator(&self) -> Option<&'e Allocator<'e>> {
-            None
-        }
-    }
-    let mut free_vars = FreeVars(FnvSet::default());
-    free_vars.visit_expr(expr);
-    iter.into_iter().any(|field| free_vars.0.contains(field))
-}
-
 #[derive(Copy, Clone, Debug)]
 enum Reduced<L, G> {
     Local(L),
@@ -714,7 +688,14 @@ impl<'a, 'e> Compiler<'a, 'e> {
             r.as_ref().map(|r| r.as_ref()),
         ) {
             (Some(&Expr::Const(Literal::Int(l), ..)), Some(&Expr::Const(Literal::Int(r), ..))) => {
-                let f = binop!();
+                // Folded the same way as the VM evaluates it, wrapping on overflow
+                let f: fn(i64, i64) -> i64 = match id.name.as_ref().chars().last().unwrap() {
+                    '+' => i64::wrapping_add,
+                    '-' => i64::wrapping_sub,
+                    '*' => i64::wrapping_mul,
+                    '/' => |l, r| l / r,
+                    _ => return Err(format!("Invalid binop `{}`", id.name).into()),
+                };
                 Some(Reduced::Local(
                     self.allocator
                         .arena
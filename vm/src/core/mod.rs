@@ -32,8 +32,12 @@ macro_rules! assert_deq {
 mod grammar;
 pub mod optimize;
 pub mod interpreter;
+pub(crate) mod backend;
+pub mod rust_backend;
+pub mod wasm_backend;
 
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
 use std::iter::once;
@@ -48,11 +52,11 @@ use self::smallvec::SmallVec;
 use pretty::{self, DocAllocator};
 
 use base::ast::{self, Literal, SpannedExpr, SpannedPattern, Typed, TypedIdent};
-use base::fnv::FnvSet;
+use base::fnv::{FnvMap, FnvSet};
 use base::pos::{spanned, BytePos, ExpansionId, Span};
 use base::resolve::remove_aliases_cow;
 use base::symbol::Symbol;
-use base::types::{arg_iter, ArcType, PrimitiveEnv, Type, TypeEnv};
+use base::types::{self, arg_iter, ArcType, PrimitiveEnv, Type, TypeEnv};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Closure<'a> {
@@ -366,13 +370,26 @@ fn is_constructor(s: &Symbol) -> bool {
 }
 
 mod internal {
-    use super::{Allocator, Expr};
+    use std::fmt;
+    use std::mem;
+
+    use super::{Allocator, CExpr, Expr};
 
     pub struct CoreExpr {
         allocator: Allocator<'static>,
         expr: Expr<'static>,
     }
 
+    // The allocator can only be used through `map`, which takes the `CoreExpr` by value, so
+    // nothing is allocated through a shared reference
+    unsafe impl Sync for CoreExpr {}
+
+    impl fmt::Debug for CoreExpr {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "CoreExpr({})", self.expr)
+        }
+    }
+
     impl CoreExpr {
         pub fn new(allocator: Allocator<'static>, expr: Expr<'static>) -> CoreExpr {
             CoreExpr { allocator, expr }
@@ -383,8 +400,23 @@ mod internal {
             &self.expr
         }
 
-        pub fn allocator(&self) -> &Allocator {
-            unsafe { ::std::mem::transmute(&self.allocator) }
+        /// Replaces the expression with the one returned by `f`, which may allocate new
+        /// expressions in the allocator of the `CoreExpr`
+        pub fn map<F>(self, f: F) -> CoreExpr
+        where
+            F: for<'a> FnOnce(&'a Allocator<'a>, CExpr<'a>) -> CExpr<'a>,
+        {
+            let CoreExpr { allocator, expr } = self;
+            // As in `translate` the lifetime of `allocator` is forgotten while the new expression
+            // is moved into the returned `CoreExpr`
+            unsafe {
+                let expr = {
+                    let allocator = &*(&allocator as *const Allocator<'static>);
+                    let expr = f(allocator, allocator.arena.alloc(expr));
+                    mem::transmute::<Expr, Expr<'static>>(expr.clone())
+                };
+                CoreExpr { allocator, expr }
+            }
         }
     }
 }
@@ -405,6 +437,18 @@ impl<'a> Allocator<'a> {
     }
 }
 
+/// Returns the name of the binding and the global it is bound to if the binding is marked with
+/// `#[intrinsic(name)]` (or `#[intrinsic("name")]`)
+fn intrinsic<'b>(
+    bind: &'b ast::ValueBinding<Symbol>,
+) -> Option<(&'b TypedIdent<Symbol>, &'b str)> {
+    let global = bind.intrinsic()?;
+    match bind.name.value {
+        ast::Pattern::Ident(ref id) => Some((id, global)),
+        _ => None,
+    }
+}
+
 pub fn translate(env: &PrimitiveEnv, expr: &SpannedExpr<Symbol>) -> CoreExpr {
     // Here we temporarily forget the lifetime of `translator` so it can be moved into a
     // `CoreExpr`. After we have it in `CoreExpr` the expression is then guaranteed to live as
@@ -426,6 +470,9 @@ pub struct Translator<'a, 'e> {
     pub allocator: Allocator<'a>,
     env: &'e PrimitiveEnv,
     dummy_symbol: TypedIdent<Symbol>,
+    /// The variables which overloaded record fields are bound to (`name@1`, `name@2`, ...).
+    /// Both the patterns and the uses of these variables are mapped to the same symbol
+    overload_symbols: RefCell<FnvMap<String, Symbol>>,
 }
 
 impl<'a, 'e> Translator<'a, 'e> {
@@ -434,6 +481,25 @@ impl<'a, 'e> Translator<'a, 'e> {
             allocator: Allocator::new(),
             env: env,
             dummy_symbol: TypedIdent::new(Symbol::from("")),
+            overload_symbols: RefCell::new(FnvMap::default()),
+        }
+    }
+
+    fn overload_symbol(&self, name: &str) -> Symbol {
+        self.overload_symbols
+            .borrow_mut()
+            .entry(name.to_string())
+            .or_insert_with(|| Symbol::from(name))
+            .clone()
+    }
+
+    fn variable(&self, id: &TypedIdent<Symbol>) -> TypedIdent<Symbol> {
+        match types::overloaded_name(id.name.as_ref()) {
+            Some(_) => TypedIdent {
+                name: self.overload_symbol(id.name.as_ref()),
+                typ: id.typ.clone(),
+            },
+            None => id.clone(),
         }
     }
 
@@ -467,6 +533,20 @@ impl<'a, 'e> Translator<'a, 'e> {
                         let typ = expr.env_type_of(&self.env);
                         self.new_data_constructor(typ, id, new_args, expr.span)
                     }
+                    // Pass the implicit arguments in the same call as the explicit arguments
+                    ast::Expr::ImplicitApp(ref id, ref implicit_args) => {
+                        // Implicit arguments may allocate so they must be collected before
+                        // calling `alloc_extend`
+                        let args: SmallVec<[_; 16]> = implicit_args
+                            .iter()
+                            .map(|arg| self.implicit_arg(arg, function.span))
+                            .chain(new_args)
+                            .collect();
+                        Expr::Call(
+                            arena.alloc(Expr::Ident(self.variable(id), function.span)),
+                            arena.alloc_extend(args.into_iter()),
+                        )
+                    }
                     _ => Expr::Call(
                         self.translate_alloc(function),
                         arena.alloc_extend(new_args.into_iter()),
@@ -506,8 +586,12 @@ impl<'a, 'e> Translator<'a, 'e> {
             ast::Expr::Ident(ref id) => if is_constructor(&id.name) {
                 self.new_data_constructor(id.typ.clone(), id, SmallVec::new(), expr.span)
             } else {
-                Expr::Ident(id.clone(), expr.span)
+                Expr::Ident(self.variable(id), expr.span)
             },
+            ast::Expr::ImplicitApp(ref id, ref implicit_args) => Expr::Call(
+                arena.alloc(Expr::Ident(self.variable(id), expr.span)),
+                self.implicit_args(implicit_args, expr.span),
+            ),
             ast::Expr::IfElse(ref pred, ref if_true, ref if_false) => {
                 let alts: SmallVec<[_; 2]> = collect![
                     Alternative {
@@ -529,7 +613,7 @@ impl<'a, 'e> Translator<'a, 'e> {
             ast::Expr::Infix(ref l, ref op, ref r) => {
                 let args: SmallVec<[_; 2]> = collect![self.translate(l), self.translate(r)];
                 Expr::Call(
-                    arena.alloc(Expr::Ident(op.value.clone(), op.span)),
+                    arena.alloc(Expr::Ident(self.variable(&op.value), op.span)),
                     arena.alloc_extend(args.into_iter()),
                 )
             }
@@ -558,9 +642,23 @@ impl<'a, 'e> Translator<'a, 'e> {
             // =>
             // match expr with
             // | { projection } -> projection
-            ast::Expr::Projection(ref projected_expr, ref projection, ref projected_type) => {
+            ast::Expr::Projection(
+                ref projected_expr,
+                ref projection,
+                ref projected_type,
+                ref implicit_args,
+            ) => {
                 let projected_expr = self.translate_alloc(projected_expr);
-                self.project_expr(expr.span, projected_expr, projection, projected_type)
+                let projected =
+                    self.project_expr(expr.span, projected_expr, projection, projected_type);
+                if implicit_args.is_empty() {
+                    projected
+                } else {
+                    Expr::Call(
+                        arena.alloc(projected),
+                        self.implicit_args(implicit_args, expr.span),
+                    )
+                }
             }
             ast::Expr::Record {
                 ref typ,
@@ -579,6 +677,8 @@ impl<'a, 'e> Translator<'a, 'e> {
 
                 let mut last_span = expr.span;
                 let mut args = SmallVec::<[_; 16]>::new();
+                // The values of fields marked with `!` are bound so they can be passed to `seq`
+                let mut strict_fields = Vec::new();
                 args.extend(exprs.iter().map(|field| {
                     let expr = match field.value {
                         Some(ref expr) => {
@@ -587,9 +687,13 @@ impl<'a, 'e> Translator<'a, 'e> {
                         }
                         None => Expr::Ident(TypedIdent::new(field.name.value.clone()), last_span),
                     };
-                    if needs_bindings {
+                    if needs_bindings || field.strict {
                         let typ = expr.env_type_of(&self.env);
-                        binder.bind(arena.alloc(expr), typ)
+                        let ident = binder.bind(arena.alloc(expr), typ);
+                        if field.strict {
+                            strict_fields.push(ident.clone());
+                        }
+                        ident
                     } else {
                         expr
                     }
@@ -638,6 +742,11 @@ impl<'a, 'e> Translator<'a, 'e> {
                     expr.span.start,
                     expr.span.expansion_id,
                 );
+                let record_constructor = strict_fields
+                    .into_iter()
+                    .fold(record_constructor, |record, field| {
+                        self.seq(field, record, typ, expr.span)
+                    });
                 binder.into_expr(arena, record_constructor)
             }
             ast::Expr::Tuple { ref elems, .. } => if elems.len() == 1 {
@@ -690,6 +799,34 @@ impl<'a, 'e> Translator<'a, 'e> {
         }
     }
 
+    fn implicit_args(
+        &'a self,
+        args: &[ast::ImplicitArg<Symbol>],
+        span: Span<BytePos>,
+    ) -> &'a [Expr<'a>] {
+        // Nested arguments allocate as well so they must be collected before `alloc_extend`
+        let args: SmallVec<[_; 4]> = args.iter().map(|arg| self.implicit_arg(arg, span)).collect();
+        self.allocator.arena.alloc_extend(args.into_iter())
+    }
+
+    /// Translates an implicit argument into the binding it refers to, applied to the implicit
+    /// arguments that the binding takes itself
+    fn implicit_arg(&'a self, arg: &ast::ImplicitArg<Symbol>, span: Span<BytePos>) -> Expr<'a> {
+        if arg.args.is_empty() {
+            Expr::Ident(self.variable(&arg.name), span)
+        } else {
+            let mut binding = self.variable(&arg.name);
+            binding.typ = Type::implicit_function(
+                arg.args.iter().map(|arg| arg.name.typ.clone()).collect(),
+                binding.typ,
+            );
+            Expr::Call(
+                self.allocator.arena.alloc(Expr::Ident(binding, span)),
+                self.implicit_args(&arg.args, span),
+            )
+        }
+    }
+
     fn project_expr(
         &'a self,
         span: Span<BytePos>,
@@ -729,8 +866,16 @@ impl<'a, 'e> Translator<'a, 'e> {
         span_start: BytePos,
     ) -> Expr<'a> {
         let arena = &self.allocator.arena;
-        let is_recursive = binds.iter().all(|bind| bind.args.len() > 0);
-        if is_recursive {
+
+        // Intrinsics do not refer to any of the other bindings so they can be bound before them
+        let (intrinsics, binds): (Vec<_>, Vec<_>) = binds
+            .iter()
+            .partition(|bind| intrinsic(bind).is_some());
+
+        let is_recursive = binds.iter().all(|bind| bind.is_function());
+        let tail = if binds.is_empty() {
+            tail
+        } else if is_recursive {
             let closures = binds
                 .iter()
                 .map(|bind| Closure {
@@ -739,7 +884,11 @@ impl<'a, 'e> Translator<'a, 'e> {
                         ast::Pattern::Ident(ref id) => id.clone(),
                         _ => unreachable!(),
                     },
-                    args: bind.args.iter().map(|arg| arg.value.clone()).collect(),
+                    args: bind.implicit_args
+                        .iter()
+                        .chain(&bind.args)
+                        .map(|arg| arg.value.clone())
+                        .collect(),
                     expr: self.translate_alloc(&bind.expr),
                 })
                 .collect();
@@ -770,14 +919,18 @@ impl<'a, 'e> Translator<'a, 'e> {
                         );
                     }
                 };
-                let named = if bind.args.is_empty() {
+                let named = if !bind.is_function() {
                     Named::Expr(self.translate_alloc(&bind.expr))
                 } else {
                     Named::Recursive(vec![
                         Closure {
                             pos: bind.name.span.start,
                             name: name.clone(),
-                            args: bind.args.iter().map(|arg| arg.value.clone()).collect(),
+                            args: bind.implicit_args
+                                .iter()
+                                .chain(&bind.args)
+                                .map(|arg| arg.value.clone())
+                                .collect(),
                             expr: self.translate_alloc(&bind.expr),
                         },
                     ])
@@ -791,7 +944,48 @@ impl<'a, 'e> Translator<'a, 'e> {
                     arena.alloc(tail),
                 )
             })
-        }
+        };
+
+        intrinsics.iter().rev().fold(tail, |tail, bind| {
+            let (name, global) = intrinsic(bind).expect("intrinsic");
+            // The body of the binding is only used for typechecking, the value is instead loaded
+            // from the global which the vm registered for the intrinsic
+            let global = Expr::Ident(
+                TypedIdent {
+                    name: Symbol::from(format!("@{}", global)),
+                    typ: name.typ.clone(),
+                },
+                bind.expr.span,
+            );
+            Expr::Let(
+                LetBinding {
+                    name: name.clone(),
+                    expr: Named::Expr(arena.alloc(global)),
+                    span_start: bind.expr.span.start,
+                },
+                arena.alloc(tail),
+            )
+        })
+    }
+
+    /// Creates a call to the `@seq` global which forces `value` before `body` is returned
+    fn seq(
+        &'a self,
+        value: Expr<'a>,
+        body: Expr<'a>,
+        typ: &ArcType,
+        span: Span<BytePos>,
+    ) -> Expr<'a> {
+        let arena = &self.allocator.arena;
+        let value_type = value.env_type_of(&self.env);
+        let seq = Expr::Ident(
+            TypedIdent {
+                name: Symbol::from("@seq"),
+                typ: Type::function(vec![value_type, typ.clone()], typ.clone()),
+            },
+            span,
+        );
+        Expr::Call(arena.alloc(seq), arena.alloc_extend(vec![value, body]))
     }
 
     fn bool_constructor(&self, variant: bool) -> TypedIdent<Symbol> {
@@ -1053,21 +1247,31 @@ impl<'a, 'e> PatternTranslator<'a, 'e> {
 
                         fields
                             .iter()
-                            .map(|field| {
-                                field.value.as_ref().map(Cow::Borrowed).unwrap_or_else(|| {
-                                    let field_type = record_type
-                                        .row_iter()
-                                        .find(|f| f.name.name_eq(&field.name.value))
-                                        .map(|f| f.typ.clone())
-                                        .unwrap_or_else(|| Type::hole());
-                                    Cow::Owned(spanned(
-                                        Span::default(),
-                                        ast::Pattern::Ident(TypedIdent {
-                                            name: field.name.value.clone(),
-                                            typ: field_type,
-                                        }),
-                                    ))
-                                })
+                            .flat_map(|field| {
+                                let pattern =
+                                    field.value.as_ref().map(Cow::Borrowed).unwrap_or_else(|| {
+                                        let field_type = record_type
+                                            .row_iter()
+                                            .find(|f| f.name.name_eq(&field.name.value))
+                                            .map(|f| f.typ.clone())
+                                            .unwrap_or_else(|| Type::hole());
+                                        Cow::Owned(spanned(
+                                            Span::default(),
+                                            ast::Pattern::Ident(TypedIdent {
+                                                name: field.name.value.clone(),
+                                                typ: field_type,
+                                            }),
+                                        ))
+                                    });
+                                let overloads = self.overload_bindings(&record_type, field)
+                                    .into_iter()
+                                    .map(|(_, binding)| {
+                                        Cow::Owned(spanned(
+                                            Span::default(),
+                                            ast::Pattern::Ident(binding),
+                                        ))
+                                    });
+                                once(pattern).chain(overloads).collect::<Vec<_>>()
                             })
                             .collect::<Vec<_>>()
                     }
@@ -1550,6 +1754,12 @@ impl<'a, 'e> PatternTranslator<'a, 'e> {
                                 },
                                 x,
                             ));
+                            let record_type = remove_aliases_cow(&self.0.env, typ);
+                            record_fields.extend(
+                                self.overload_bindings(&record_type, field)
+                                    .into_iter()
+                                    .map(|(overload, binding)| (overload, Some(binding.name))),
+                            );
                         }
                     }
                 }
@@ -1565,6 +1775,44 @@ impl<'a, 'e> PatternTranslator<'a, 'e> {
             Pattern::Record(record_fields)
         }
     }
+
+    /// Returns the overloads of the record pattern field `field` (`name@1`, `name@2`, ...) in
+    /// `record_type` along with the variables which they are bound to. A field bound to an
+    /// identifier binds its overloads as well so that the uses of the identifier can be resolved
+    /// to either of them.
+    fn overload_bindings(
+        &self,
+        record_type: &ArcType,
+        field: &ast::PatternField<Symbol, SpannedPattern<Symbol>>,
+    ) -> Vec<(TypedIdent<Symbol>, TypedIdent<Symbol>)> {
+        let binding = match field.value {
+            Some(ref pattern) => match pattern.value {
+                ast::Pattern::Ident(ref id) => &id.name,
+                _ => return Vec::new(),
+            },
+            None => &field.name.value,
+        };
+        record_type
+            .row_iter()
+            .filter_map(|overload| {
+                let (name, index) = types::overloaded_name(overload.name.as_ref())?;
+                if name != field.name.value.declared_name() {
+                    return None;
+                }
+                Some((
+                    TypedIdent {
+                        name: overload.name.clone(),
+                        typ: overload.typ.clone(),
+                    },
+                    TypedIdent {
+                        name: self.0
+                            .overload_symbol(&types::overload_name(binding.as_ref(), index)),
+                        typ: overload.typ.clone(),
+                    },
+                ))
+            })
+            .collect()
+    }
 }
 
 fn unwrap_as(pattern: &ast::Pattern<Symbol>) -> &ast::Pattern<Symbol> {
@@ -1679,8 +1927,13 @@ mod tests {
 
     use vm::RootedThread;
 
-    fn parse_expr(symbols: &mut Symbols, expr_str: &str) -> ast::SpannedExpr<Symbol> {
+    fn parse_expr<'ast>(
+        arena: &'ast ast::Arena<'ast, Symbol>,
+        symbols: &mut Symbols,
+        expr_str: &str,
+    ) -> ast::SpannedExpr<'ast, Symbol> {
         self::parser::parse_expr(
+            arena,
             &mut SymbolModule::new("".into(), symbols),
             &TypeCache::new(),
             expr_str,
@@ -1773,9 +2026,10 @@ mod tests {
 
         let vm = RootedThread::new();
         let env = vm.get_env();
-        let translator = Translator::new(&*env);
+        let translator = Translator::new(&env);
 
-        let expr = parse_expr(&mut symbols, expr_str);
+        let arena = ast::Arena::new();
+        let expr = parse_expr(&arena, &mut symbols, expr_str);
         let core_expr = translator.translate(&expr);
 
         let expected_expr =
@@ -1866,6 +2120,36 @@ mod tests {
         check_translation(expr_str, expected_str);
     }
 
+    #[test]
+    fn strict_record_field() {
+        let expr_str = r#"
+            let x = 1
+            { !x, y = 2 }
+        "#;
+
+        let expected_str = "
+            let x = 1 in
+            let bind_arg0 = x in
+            seq bind_arg0 { x = bind_arg0, y = 2 }
+        ";
+        check_translation(expr_str, expected_str);
+    }
+
+    #[test]
+    fn intrinsic_binding() {
+        let expr_str = r#"
+            #[intrinsic("string_eq")]
+            let eq : String -> String -> Bool = other
+            other eq
+        "#;
+
+        let expected_str = "
+            let eq = string_eq in
+            other eq
+        ";
+        check_translation(expr_str, expected_str);
+    }
+
     #[test]
     fn match_as_pattern() {
         let expr_str = r#"
@@ -1,9 +1,14 @@
+use std::iter::once;
 use std::marker::PhantomData;
+use std::rc::Rc;
 
 use base::ast::TypedIdent;
+use base::fnv::{FnvMap, FnvSet};
 use base::types::{ArcType, Field};
 use base::merge::{merge_fn, merge_iter};
-use base::pos;
+use base::metadata::Metadata;
+use base::pos::{self, BytePos, Span};
+use base::scoped_map::ScopedMap;
 use base::symbol::Symbol;
 
 use core::{Allocator, Alternative, CExpr, Closure, Expr, LetBinding, Named, Pattern};
@@ -159,11 +164,829 @@ impl<'a> Visitor<'a, 'a> for RecognizeUnnecessaryAllocation<'a> {
     }
 }
 
-pub fn optimize<'a>(allocator: &'a Allocator<'a>, expr: &'a Expr<'a>) -> &'a Expr<'a> {
+/// A function which a global module exports and marks with `#[inline]`
+#[derive(Clone)]
+struct ImportedFunction<'g> {
+    args: &'g [TypedIdent<Symbol>],
+    expr: CExpr<'g>,
+    /// The variables of the module which are bound to globals (`let prim = import! std.prim`),
+    /// the only variables of the module which the function may refer to
+    aliases: Rc<FnvMap<Symbol, TypedIdent<Symbol>>>,
+}
+
+type ImportedFunctions<'g> = Rc<FnvMap<String, ImportedFunction<'g>>>;
+
+/// Returns the functions marked `#[inline]` which the module `expr` exports, keyed by the name
+/// of their fields
+fn inline_exports<'g>(
+    expr: CExpr<'g>,
+    metadata: &Metadata,
+) -> FnvMap<String, ImportedFunction<'g>> {
+    let mut aliases = FnvMap::default();
+    let mut closures = FnvMap::default();
+    let mut expr = expr;
+    loop {
+        match *expr {
+            Expr::Let(ref bind, body) => {
+                match bind.expr {
+                    Named::Expr(&Expr::Ident(ref id, _)) if id.name.is_global() => {
+                        aliases.insert(bind.name.name.clone(), id.clone());
+                    }
+                    Named::Recursive(ref group) => for closure in group {
+                        closures.insert(closure.name.name.clone(), closure);
+                    },
+                    Named::Expr(_) => (),
+                }
+                expr = body;
+            }
+            // `let { x } = import! m`
+            Expr::Match(_, alts) if alts.len() == 1 => expr = alts[0].expr,
+            _ => break,
+        }
+    }
+    let (id, args) = match *expr {
+        Expr::Data(ref id, args, ..) => (id, args),
+        _ => return FnvMap::default(),
+    };
+    let aliases = Rc::new(aliases);
+    id.typ
+        .row_iter()
+        .zip(args)
+        .filter_map(|(field, arg)| {
+            let name = field.name.declared_name();
+            let inline = metadata
+                .module
+                .get(name)
+                .map_or(false, |field| field.attributes.contains_key("inline"));
+            let closure = match *arg {
+                Expr::Ident(ref id, _) if inline => *closures.get(&id.name)?,
+                _ => return None,
+            };
+            if is_variable_in_expression(Some(&closure.name.name), closure.expr) {
+                return None;
+            }
+            let function = ImportedFunction {
+                args: &closure.args,
+                expr: closure.expr,
+                aliases: aliases.clone(),
+            };
+            Some((name.to_string(), function))
+        })
+        .collect()
+}
+
+/// Copies an `ImportedFunction` into the allocator of the module which calls it.
+///
+/// Each variable which the function binds gets a new symbol, so inlining the function several
+/// times never binds the same symbol twice, and each position is replaced by the position of the
+/// call as the positions of the function refer to the source of the other module.
+struct Import<'a, 'b> {
+    allocator: &'a Allocator<'a>,
+    aliases: &'b FnvMap<Symbol, TypedIdent<Symbol>>,
+    variables: FnvMap<Symbol, Symbol>,
+    span: Span<BytePos>,
+}
+
+impl<'a, 'b> Import<'a, 'b> {
+    fn bind(&mut self, id: &TypedIdent<Symbol>) -> TypedIdent<Symbol> {
+        let name = Symbol::from(id.name.as_ref());
+        self.variables.insert(id.name.clone(), name.clone());
+        TypedIdent {
+            name,
+            typ: id.typ.clone(),
+        }
+    }
+
+    /// Returns `None` if `id` is a variable of the other module which can't be referred to
+    fn variable(&self, id: &TypedIdent<Symbol>) -> Option<TypedIdent<Symbol>> {
+        match self.variables.get(&id.name) {
+            Some(name) => Some(TypedIdent {
+                name: name.clone(),
+                typ: id.typ.clone(),
+            }),
+            // Globals and primitives are the same in every module
+            None if id.name.is_global() || id.name.as_ref().starts_with('#') => Some(id.clone()),
+            None => self.aliases.get(&id.name).cloned(),
+        }
+    }
+
+    fn expr<'g>(&mut self, expr: CExpr<'g>) -> Option<CExpr<'a>> {
+        let new_expr = match *expr {
+            Expr::Const(ref literal, _) => Expr::Const(literal.clone(), self.span),
+            Expr::Ident(ref id, _) => Expr::Ident(self.variable(id)?, self.span),
+            Expr::Call(f, args) => {
+                let f = self.expr(f)?;
+                Expr::Call(f, self.exprs(args)?)
+            }
+            Expr::Data(ref id, args, ..) => Expr::Data(
+                id.clone(),
+                self.exprs(args)?,
+                self.span.start,
+                self.span.expansion_id,
+            ),
+            Expr::Let(ref bind, body) => {
+                let named = match bind.expr {
+                    Named::Expr(bind_expr) => {
+                        let bind_expr = self.expr(bind_expr)?;
+                        self.bind(&bind.name);
+                        Named::Expr(bind_expr)
+                    }
+                    Named::Recursive(ref closures) => {
+                        let names = closures
+                            .iter()
+                            .map(|closure| self.bind(&closure.name))
+                            .collect::<Vec<_>>();
+                        let mut new_closures = Vec::with_capacity(closures.len());
+                        for (closure, name) in closures.iter().zip(names) {
+                            let args = closure
+                                .args
+                                .iter()
+                                .map(|arg| self.bind(arg))
+                                .collect();
+                            new_closures.push(Closure {
+                                pos: self.span.start,
+                                name,
+                                args,
+                                expr: self.expr(closure.expr)?,
+                            });
+                        }
+                        Named::Recursive(new_closures)
+                    }
+                };
+                let name = TypedIdent {
+                    name: self.variables
+                        .get(&bind.name.name)
+                        .cloned()
+                        .unwrap_or_else(|| bind.name.name.clone()),
+                    typ: bind.name.typ.clone(),
+                };
+                let bind = LetBinding {
+                    name,
+                    expr: named,
+                    span_start: self.span.start,
+                };
+                Expr::Let(bind, self.expr(body)?)
+            }
+            Expr::Match(match_expr, alts) => {
+                let match_expr = self.expr(match_expr)?;
+                let mut new_alts = Vec::with_capacity(alts.len());
+                for alt in alts {
+                    let pattern = self.pattern(&alt.pattern);
+                    new_alts.push(Alternative {
+                        pattern,
+                        expr: self.expr(alt.expr)?,
+                    });
+                }
+                Expr::Match(
+                    match_expr,
+                    self.allocator
+                        .alternative_arena
+                        .alloc_extend(new_alts.into_iter()),
+                )
+            }
+        };
+        Some(self.allocator.arena.alloc(new_expr))
+    }
+
+    fn exprs<'g>(&mut self, exprs: &'g [Expr<'g>]) -> Option<&'a [Expr<'a>]> {
+        let mut new_exprs = Vec::with_capacity(exprs.len());
+        for expr in exprs {
+            new_exprs.push(self.expr(expr)?.clone());
+        }
+        Some(self.allocator.arena.alloc_extend(new_exprs.into_iter()))
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) -> Pattern {
+        match *pattern {
+            Pattern::Constructor(ref id, ref args) => Pattern::Constructor(
+                id.clone(),
+                args.iter().map(|arg| self.bind(arg)).collect(),
+            ),
+            Pattern::Record(ref fields) => Pattern::Record(
+                fields
+                    .iter()
+                    .map(|&(ref field, ref name)| {
+                        let variable = TypedIdent {
+                            name: name.clone().unwrap_or_else(|| field.name.clone()),
+                            typ: field.typ.clone(),
+                        };
+                        (field.clone(), Some(self.bind(&variable).name))
+                    })
+                    .collect(),
+            ),
+            Pattern::Ident(ref id) => Pattern::Ident(self.bind(id)),
+            Pattern::Literal(ref literal) => Pattern::Literal(literal.clone()),
+        }
+    }
+}
+
+/// Fuses loops driven by an iterator's `next : s -> Option (a, s)` function so that the `Option`
+/// and the tuple it returns are not allocated on each step.
+///
+/// ```ignore
+/// let next i = if i < len xs then Some (index xs i, i + 1) else None
+/// let loop i acc =
+///     match next i with
+///     | Some (x, i) -> loop i (acc + x)
+///     | None -> acc
+/// ```
+///
+/// `next` is inlined into the `match`, the `match` is pushed into the branches of the `if` and
+/// the constructors are matched away, leaving a loop over an `Int` index.
+///
+/// Functions which other modules mark with `#[inline]` (such as the functions of `std.iter`) are
+/// inlined wherever they are called. The records they return are not allocated if only their
+/// fields are used, so the `next` function of an iterator created in another module is inlined
+/// as if it was defined locally.
+struct FuseIteration<'a, 'g> {
+    allocator: &'a Allocator<'a>,
+    /// Returns the core expression and the metadata of a global module if other modules may
+    /// inline its functions
+    globals: &'g Fn(&Symbol) -> Option<(CExpr<'g>, &'g Metadata)>,
+    /// The `#[inline]` functions of each global module which has been referred to
+    modules: FnvMap<String, ImportedFunctions<'g>>,
+    /// Variables which are bound to global modules (`let m = import! m`)
+    module_variables: ScopedMap<Symbol, Symbol>,
+    /// Variables which are bound to `#[inline]` functions of global modules
+    imported: ScopedMap<Symbol, ImportedFunction<'g>>,
+    /// Local, non-recursive functions which may be inlined where their result is matched on
+    functions: ScopedMap<Symbol, (Vec<TypedIdent<Symbol>>, &'a Expr<'a>)>,
+    /// Variables which are bound to records, whose fields may be read without matching on them
+    records: ScopedMap<Symbol, &'a Expr<'a>>,
+    /// Functions which were inlined and bindings introduced by inlining. These may be removed once
+    /// they are unused, other bindings are kept so the debugger can still step through them
+    removable: FnvSet<Symbol>,
+}
+
+impl<'a, 'g> FuseIteration<'a, 'g> {
+    fn new_let(
+        &mut self,
+        name: &TypedIdent<Symbol>,
+        expr: &'a Expr<'a>,
+        body: &'a Expr<'a>,
+    ) -> &'a Expr<'a> {
+        self.removable.insert(name.name.clone());
+        self.allocator.arena.alloc(Expr::Let(
+            LetBinding {
+                name: name.clone(),
+                expr: Named::Expr(expr),
+                span_start: pos::BytePos::default(),
+            },
+            body,
+        ))
+    }
+
+    fn new_match(&self, expr: &'a Expr<'a>, alts: &'a [Alternative<'a>]) -> &'a Expr<'a> {
+        self.allocator.arena.alloc(Expr::Match(expr, alts))
+    }
+
+    fn new_alt(&self, pattern: &Pattern, expr: &'a Expr<'a>) -> &'a [Alternative<'a>] {
+        self.allocator.alternative_arena.alloc_extend(once(Alternative {
+            pattern: pattern.clone(),
+            expr,
+        }))
+    }
+
+    fn enter_scope(&mut self) {
+        self.module_variables.enter_scope();
+        self.imported.enter_scope();
+        self.functions.enter_scope();
+        self.records.enter_scope();
+    }
+
+    fn exit_scope(&mut self) {
+        self.module_variables.exit_scope();
+        self.imported.exit_scope();
+        self.functions.exit_scope();
+        self.records.exit_scope();
+    }
+
+    /// Returns the `#[inline]` functions of the module which `expr` refers to
+    fn module(&mut self, expr: CExpr<'a>) -> Option<ImportedFunctions<'g>> {
+        let name = match *expr {
+            Expr::Ident(ref id, _) if id.name.is_global() => id.name.clone(),
+            Expr::Ident(ref id, _) => self.module_variables.get(&id.name)?.clone(),
+            _ => return None,
+        };
+        let functions = match self.modules.get(name.definition_name()) {
+            Some(functions) => functions.clone(),
+            None => {
+                let functions = Rc::new(
+                    (self.globals)(&name)
+                        .map(|(expr, metadata)| inline_exports(expr, metadata))
+                        .unwrap_or_default(),
+                );
+                self.modules
+                    .insert(name.definition_name().to_string(), functions.clone());
+                functions
+            }
+        };
+        if functions.is_empty() {
+            None
+        } else {
+            Some(functions)
+        }
+    }
+
+    /// Inlines the call of `function` with `args`, binding each argument to a parameter
+    fn inline_import(
+        &mut self,
+        function: &ImportedFunction<'g>,
+        span: Span<BytePos>,
+        args: &'a [Expr<'a>],
+    ) -> Option<CExpr<'a>> {
+        if function.args.len() != args.len() {
+            return None;
+        }
+        let (params, body) = {
+            let mut import = Import {
+                allocator: self.allocator,
+                aliases: &function.aliases,
+                variables: FnvMap::default(),
+                span,
+            };
+            let params = function
+                .args
+                .iter()
+                .map(|arg| import.bind(arg))
+                .collect::<Vec<_>>();
+            (params, import.expr(function.expr)?)
+        };
+        Some(
+            params
+                .iter()
+                .zip(args)
+                .rev()
+                .fold(body, |body, (param, arg)| self.new_let(param, arg, body)),
+        )
+    }
+
+    /// Reads the fields which `alt` binds from `record` if they are constants or variables
+    fn project(&mut self, record: CExpr<'a>, alt: &'a Alternative<'a>) -> Option<CExpr<'a>> {
+        let (id, args) = match *record {
+            Expr::Data(ref id, args, ..) => (id, args),
+            _ => return None,
+        };
+        let fields = match alt.pattern {
+            Pattern::Record(ref fields) => fields,
+            _ => return None,
+        };
+        let row = id.typ.row_iter().collect::<Vec<_>>();
+        if row.len() != args.len() {
+            return None;
+        }
+        let mut values = Vec::with_capacity(fields.len());
+        for &(ref field, ref name) in fields {
+            let index = row.iter()
+                .position(|row_field| row_field.name.name_eq(&field.name))?;
+            match args[index] {
+                Expr::Const(..) | Expr::Ident(..) => (),
+                _ => return None,
+            }
+            values.push((name.as_ref().unwrap_or(&field.name), &args[index]));
+        }
+        match *alt.expr {
+            // r.x => x
+            Expr::Ident(ref id, _) if values.len() == 1 && id.name == *values[0].0 => {
+                Some(values[0].1)
+            }
+            _ => self.fuse_match(record, self.new_alt(&alt.pattern, alt.expr)),
+        }
+    }
+
+    /// Returns the name and the function which `bind` defines if it can be inlined
+    fn inlinable_function(
+        bind: &LetBinding<'a>,
+    ) -> Option<(Symbol, (Vec<TypedIdent<Symbol>>, CExpr<'a>))> {
+        let closure = match bind.expr {
+            Named::Recursive(ref closures) if closures.len() == 1 => &closures[0],
+            // A lambda, `let f = \x -> ...`
+            Named::Expr(&Expr::Let(
+                LetBinding {
+                    expr: Named::Recursive(ref closures),
+                    ..
+                },
+                &Expr::Ident(ref id, _),
+            )) if closures.len() == 1 && closures[0].name.name == id.name =>
+            {
+                &closures[0]
+            }
+            _ => return None,
+        };
+        if is_variable_in_expression(Some(&closure.name.name), closure.expr) {
+            None
+        } else {
+            // The binding of a group of recursive functions does not have a name of its own
+            Some((closure.name.name.clone(), (closure.args.clone(), closure.expr)))
+        }
+    }
+
+    /// Pushes `match expr with alts` into each expression which `expr` may return
+    fn push_match(&self, expr: CExpr<'a>, alts: &'a [Alternative<'a>]) -> CExpr<'a> {
+        match *expr {
+            Expr::Let(ref bind, body) => self.allocator
+                .arena
+                .alloc(Expr::Let(bind.clone(), self.push_match(body, alts))),
+            Expr::Match(inner_expr, inner_alts) => {
+                let new_alts = inner_alts
+                    .iter()
+                    .map(|alt| Alternative {
+                        pattern: alt.pattern.clone(),
+                        expr: self.push_match(alt.expr, alts),
+                    })
+                    .collect::<Vec<_>>();
+                self.new_match(
+                    inner_expr,
+                    self.allocator
+                        .alternative_arena
+                        .alloc_extend(new_alts.into_iter()),
+                )
+            }
+            _ => self.new_match(expr, alts),
+        }
+    }
+
+    /// Rewrites `match expr with alts` into an expression which does not need to allocate
+    /// `expr`. Returns `None` if no such rewrite could be done.
+    fn fuse_match(&mut self, expr: CExpr<'a>, alts: &'a [Alternative<'a>]) -> Option<CExpr<'a>> {
+        match *expr {
+            // match f x with ... => match (let y = x in <body of f>) with ...
+            Expr::Call(&Expr::Ident(ref id, _), args) => {
+                let (params, body) = self.functions.get(&id.name)?.clone();
+                if params.len() != args.len() {
+                    return None;
+                }
+                self.removable.insert(id.name.clone());
+                let inlined = params
+                    .iter()
+                    .zip(args)
+                    .rev()
+                    .fold(body, |body, (param, arg)| self.new_let(param, arg, body));
+                Some(self.new_match(inlined, alts))
+            }
+            // match (let x = y in z) with ... => let x = y in match z with ...
+            Expr::Let(ref bind, body) => Some(self.allocator.arena.alloc(Expr::Let(
+                bind.clone(),
+                self.new_match(body, alts),
+            ))),
+            // match (match x with | A -> Some 1 | B -> let y = 2 in None) with ...
+            // =>
+            // match x with | A -> (match Some 1 with ...) | B -> let y = 2 in (match None with ...)
+            Expr::Match(..) => {
+                if !returns_data(expr) {
+                    return None;
+                }
+                Some(self.push_match(expr, alts))
+            }
+            // match Some x with | Some y -> y | None -> 0 => let y = x in y
+            Expr::Data(ref id, args, ..) => {
+                for alt in alts {
+                    match alt.pattern {
+                        Pattern::Constructor(ref ctor, ref params) => {
+                            if ctor.name.name_eq(&id.name) {
+                                return Some(
+                                    params
+                                        .iter()
+                                        .zip(args)
+                                        .rev()
+                                        .fold(alt.expr, |body, (param, arg)| {
+                                            self.new_let(param, arg, body)
+                                        }),
+                                );
+                            }
+                        }
+                        Pattern::Ident(ref name) => {
+                            return Some(self.new_let(name, expr, alt.expr));
+                        }
+                        Pattern::Record(ref fields) => {
+                            // The type may be an alias in which case the fields can't be matched
+                            // up with the arguments
+                            let row = id.typ.row_iter().collect::<Vec<_>>();
+                            if row.len() != args.len() {
+                                return None;
+                            }
+                            return Some(
+                                row.into_iter()
+                                    .zip(args)
+                                    .rev()
+                                    .fold(alt.expr, |body, (field, arg)| {
+                                        match fields.iter().find(|f| f.0.name.name_eq(&field.name)) {
+                                            Some(pattern_field) => {
+                                                let name = TypedIdent {
+                                                    name: pattern_field
+                                                        .1
+                                                        .as_ref()
+                                                        .unwrap_or(&pattern_field.0.name)
+                                                        .clone(),
+                                                    typ: field.typ.clone(),
+                                                };
+                                                self.new_let(&name, arg, body)
+                                            }
+                                            None => body,
+                                        }
+                                    }),
+                            )
+                        }
+                        Pattern::Literal(_) => return None,
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Visits `match expr with | { f, g } -> body` where `expr` is a global module which exports
+    /// `#[inline]` functions, making the calls of them in `body` be inlined
+    fn visit_module_match(
+        &mut self,
+        functions: &ImportedFunctions<'g>,
+        match_expr: CExpr<'a>,
+        alt: &'a Alternative<'a>,
+    ) -> Option<CExpr<'a>> {
+        let fields = match alt.pattern {
+            Pattern::Record(ref fields) => fields,
+            _ => ice!("Expected a record pattern"),
+        };
+        self.imported.enter_scope();
+        for &(ref field, ref name) in fields {
+            if let Some(function) = functions.get(field.name.declared_name()) {
+                let name = name.as_ref().unwrap_or(&field.name).clone();
+                self.imported.insert(name, function.clone());
+            }
+        }
+        let new_body = self.visit_expr(alt.expr);
+        self.imported.exit_scope();
+
+        let body = new_body.unwrap_or(alt.expr);
+        // A projection (`m.f`) which is no longer used after `f` was inlined is removed
+        let is_projection = fields.iter().all(|field| field.1.is_none());
+        if is_projection
+            && !is_variable_in_expression(fields.iter().map(|field| &field.0.name), body)
+        {
+            return Some(body);
+        }
+        new_body.map(|body| self.new_match(match_expr, self.new_alt(&alt.pattern, body)))
+    }
+}
+
+/// Returns `true` if every value which `expr` may return is constructed directly
+fn returns_data(expr: CExpr) -> bool {
+    match *expr {
+        Expr::Data(..) => true,
+        Expr::Let(_, body) => returns_data(body),
+        Expr::Match(_, alts) => alts.iter().all(|alt| returns_data(alt.expr)),
+        _ => false,
+    }
+}
+
+impl<'a, 'g> Visitor<'a, 'a> for FuseIteration<'a, 'g> {
+    type Producer = SameLifetime<'a>;
+
+    fn visit_expr(&mut self, expr: &'a Expr<'a>) -> Option<&'a Expr<'a>> {
+        match *expr {
+            Expr::Let(ref bind, body) => {
+                let new_bind = walk_bind(self, bind);
+
+                // let x = (let y = z in w) in body => let y = z in let x = w in body
+                // Only done for bindings introduced by inlining so that the records which the
+                // inlined functions return are bound directly
+                if self.removable.contains(&bind.name.name) {
+                    if let Named::Expr(&Expr::Let(ref inner, inner_body)) =
+                        new_bind.as_ref().unwrap_or(bind).expr
+                    {
+                        let shadows_variable = match inner.expr {
+                            Named::Recursive(ref closures) => is_variable_in_expression(
+                                closures.iter().map(|closure| &closure.name.name),
+                                body,
+                            ),
+                            Named::Expr(_) => {
+                                is_variable_in_expression(Some(&inner.name.name), body)
+                            }
+                        };
+                        if !shadows_variable {
+                            let outer = self.allocator.arena.alloc(Expr::Let(
+                                LetBinding {
+                                    name: bind.name.clone(),
+                                    expr: Named::Expr(inner_body),
+                                    span_start: bind.span_start,
+                                },
+                                body,
+                            ));
+                            let expr = self.allocator.arena.alloc(Expr::Let(inner.clone(), outer));
+                            return Some(self.visit_expr(expr).unwrap_or(expr));
+                        }
+                    }
+                }
+
+                self.enter_scope();
+                {
+                    let new_bind = new_bind.as_ref().unwrap_or(bind);
+                    if let Some((name, function)) = Self::inlinable_function(new_bind) {
+                        self.functions.insert(name, function);
+                    }
+                    match new_bind.expr {
+                        Named::Expr(&Expr::Ident(ref id, _)) if id.name.is_global() => {
+                            self.module_variables
+                                .insert(bind.name.name.clone(), id.name.clone());
+                        }
+                        Named::Expr(bind_expr @ &Expr::Data(..)) => {
+                            self.records.insert(bind.name.name.clone(), bind_expr);
+                        }
+                        _ => (),
+                    }
+                }
+                let new_body = self.visit_expr(body);
+                self.exit_scope();
+
+                let changed = new_bind.is_some() || new_body.is_some();
+                let bind = new_bind.unwrap_or_else(|| bind.clone());
+                let body = new_body.unwrap_or(body);
+                match bind.expr {
+                    // let x = Some 1 in match x with ... => match Some 1 with ...
+                    // let x = f y in match x with | Some z -> ... => match f y with | Some z -> ...
+                    // (if `f` can be fused). Functions whose results are only destructured, such as
+                    // `let { x } = f y`, are not inlined so they still show up in backtraces
+                    Named::Expr(bind_expr) => match *body {
+                        Expr::Match(&Expr::Ident(ref id, _), alts) if id.name == bind.name.name => {
+                            let used_in_alts = alts.iter()
+                                .any(|alt| is_variable_in_expression(Some(&id.name), alt.expr));
+                            if !used_in_alts {
+                                let matches_variant = alts.iter().any(|alt| match alt.pattern {
+                                    Pattern::Constructor(..) => true,
+                                    _ => false,
+                                });
+                                let expr = match *bind_expr {
+                                    Expr::Data(..) => Some(self.new_match(bind_expr, alts)),
+                                    _ if matches_variant => self.fuse_match(bind_expr, alts),
+                                    _ => None,
+                                };
+                                if let Some(expr) = expr {
+                                    return Some(self.visit_expr(expr).unwrap_or(expr));
+                                }
+                            }
+                        }
+                        _ => (),
+                    },
+                    _ => (),
+                }
+                // Remove allocations which are no longer used after inlining
+                let removable = {
+                    let names = match bind.expr {
+                        Named::Recursive(ref closures) => {
+                            closures.iter().map(|closure| &closure.name.name).collect()
+                        }
+                        Named::Expr(bind_expr @ &Expr::Data(..)) if is_pure(bind_expr) => {
+                            vec![&bind.name.name]
+                        }
+                        Named::Expr(_) => Vec::new(),
+                    };
+                    !names.is_empty() && names.iter().all(|name| self.removable.contains(*name))
+                        && !is_variable_in_expression(names, body)
+                };
+                if removable {
+                    return Some(body);
+                }
+                if !changed {
+                    return None;
+                }
+                Some(self.allocator.arena.alloc(Expr::Let(bind, body)))
+            }
+            Expr::Call(f, args) => {
+                // (match m with | { f } -> f) x => match m with | { f } -> f x
+                if let Expr::Match(module, alts) = *f {
+                    if alts.len() == 1 && self.module(module).is_some() {
+                        let binds_arg_variable = match alts[0].pattern {
+                            Pattern::Record(ref fields) => args.iter().any(|arg| {
+                                is_variable_in_expression(
+                                    fields
+                                        .iter()
+                                        .map(|field| field.1.as_ref().unwrap_or(&field.0.name)),
+                                    arg,
+                                )
+                            }),
+                            _ => true,
+                        };
+                        if !binds_arg_variable {
+                            let call = self.allocator.arena.alloc(Expr::Call(alts[0].expr, args));
+                            let expr = self.new_match(module, self.new_alt(&alts[0].pattern, call));
+                            return Some(self.visit_expr(expr).unwrap_or(expr));
+                        }
+                    }
+                }
+                let new_expr = walk_expr_alloc(self, expr);
+                if let Expr::Call(&Expr::Ident(ref id, span), args) = *new_expr.unwrap_or(expr) {
+                    if let Some(function) = self.imported.get(&id.name).cloned() {
+                        if let Some(inlined) = self.inline_import(&function, span, args) {
+                            return Some(self.visit_expr(inlined).unwrap_or(inlined));
+                        }
+                    }
+                }
+                new_expr
+            }
+            Expr::Match(match_expr, alts) => {
+                let is_record_pattern = alts.len() == 1 && match alts[0].pattern {
+                    Pattern::Record(_) => true,
+                    _ => false,
+                };
+                if is_record_pattern {
+                    if let Some(functions) = self.module(match_expr) {
+                        return self.visit_module_match(&functions, match_expr, &alts[0]);
+                    }
+                    if let Expr::Ident(ref id, _) = *match_expr {
+                        if let Some(record) = self.records.get(&id.name).cloned() {
+                            if let Some(expr) = self.project(record, &alts[0]) {
+                                return Some(self.visit_expr(expr).unwrap_or(expr));
+                            }
+                        }
+                    }
+                }
+                let new_match_expr = self.visit_expr(match_expr);
+                if let Some(fused) = self.fuse_match(new_match_expr.unwrap_or(match_expr), alts) {
+                    return Some(self.visit_expr(fused).unwrap_or(fused));
+                }
+                let new_alts = merge_iter(alts, |alt| walk_alt(self, alt), Clone::clone)
+                    .map(|alts: Vec<_>| {
+                        &*self.allocator
+                            .alternative_arena
+                            .alloc_extend(alts.into_iter())
+                    });
+                if new_match_expr.is_none() && new_alts.is_none() {
+                    return None;
+                }
+                Some(self.new_match(
+                    new_match_expr.unwrap_or(match_expr),
+                    new_alts.unwrap_or(alts),
+                ))
+            }
+            _ => walk_expr_alloc(self, expr),
+        }
+    }
+
+    fn detach_allocator(&self) -> Option<&'a Allocator<'a>> {
+        Some(self.allocator)
+    }
+}
+
+/// Returns `true` if evaluating `expr` has no effects besides allocating
+fn is_pure(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Const(..) | Expr::Ident(..) => true,
+        Expr::Data(_, args, ..) => args.iter().all(is_pure),
+        _ => false,
+    }
+}
+
+/// Returns `true` if any of the variables in `iter` are referred to in `expr`
+pub fn is_variable_in_expression<'a, I>(iter: I, expr: CExpr) -> bool
+where
+    I: IntoIterator<Item = &'a Symbol>,
+{
+    struct FreeVars(FnvSet<Symbol>);
+    impl<'e> Visitor<'e, 'e> for FreeVars {
+        type Producer = SameLifetime<'e>;
+
+        fn visit_expr(&mut self, expr: CExpr<'e>) -> Option<CExpr<'e>> {
+            match *expr {
+                Expr::Ident(ref id, ..) => {
+                    self.0.insert(id.name.clone());
+                    None
+                }
+                _ => walk_expr_alloc(self, expr),
+            }
+        }
+        fn detach_allocator(&self) -> Option<&'e Allocator<'e>> {
+            None
+        }
+    }
+    let mut free_vars = FreeVars(FnvSet::default());
+    free_vars.visit_expr(expr);
+    iter.into_iter().any(|field| free_vars.0.contains(field))
+}
+
+/// Optimizes `expr`. `globals` returns the optimized core expression and the metadata of a global
+/// module if the functions which it marks with `#[inline]` may be inlined into `expr`.
+pub fn optimize<'a, 'g>(
+    allocator: &'a Allocator<'a>,
+    expr: &'a Expr<'a>,
+    globals: &'g Fn(&Symbol) -> Option<(CExpr<'g>, &'g Metadata)>,
+) -> &'a Expr<'a> {
     let mut optimizer = RecognizeUnnecessaryAllocation {
         allocator: allocator,
     };
-    optimizer.visit_expr(expr).unwrap_or(expr)
+    let expr = optimizer.visit_expr(expr).unwrap_or(expr);
+
+    let mut fusion = FuseIteration {
+        allocator,
+        globals,
+        modules: FnvMap::default(),
+        module_variables: ScopedMap::new(),
+        imported: ScopedMap::new(),
+        functions: ScopedMap::new(),
+        records: ScopedMap::new(),
+        removable: FnvSet::default(),
+    };
+    fusion.visit_expr(expr).unwrap_or(expr)
 }
 
 pub fn walk_expr_alloc<'a, 'b, V>(visitor: &mut V, expr: CExpr<'b>) -> Option<CExpr<'a>>
@@ -340,7 +1163,7 @@ mod tests {
             .arena
             .alloc(parse_core_expr(&mut symbols, &allocator, initial_str).unwrap());
 
-        let optimized_expr = optimize(&allocator, initial_expr);
+        let optimized_expr = optimize(&allocator, initial_expr, &|_| None);
 
         let expected_str = r#"
             let l = l
@@ -352,4 +1175,75 @@ mod tests {
         let expected_expr = parse_core_expr(&mut symbols, &allocator, expected_str).unwrap();
         assert_deq!(*optimized_expr, expected_expr);
     }
+
+    #[test]
+    fn fuse_iteration() {
+        let mut symbols = Symbols::new();
+        let allocator = core::Allocator::new();
+
+        let initial_str = r#"
+            let next n =
+                match n with
+                | 0 -> Some { _0 = 1, _1 = 2 }
+                | _ -> None
+                end
+            in
+            let loop i =
+                match next i with
+                | Some p ->
+                    match p with
+                    | { _0 = x, _1 = j } -> loop j
+                    end
+                | None -> 0
+                end
+            in
+            loop 0
+            "#;
+        let initial_expr = allocator
+            .arena
+            .alloc(parse_core_expr(&mut symbols, &allocator, initial_str).unwrap());
+
+        let optimized_expr = optimize(&allocator, initial_expr, &|_| None);
+
+        let expected_str = r#"
+            let loop i =
+                let n = i
+                in
+                match n with
+                | 0 -> let x = 1 in let j = 2 in loop j
+                | _ -> 0
+                end
+            in
+            loop 0
+            "#;
+        let expected_expr = parse_core_expr(&mut symbols, &allocator, expected_str).unwrap();
+        assert_deq!(*optimized_expr, expected_expr);
+    }
+
+    #[test]
+    fn dont_inline_recursive_functions() {
+        let mut symbols = Symbols::new();
+        let allocator = core::Allocator::new();
+
+        let initial_str = r#"
+            let f n =
+                match f n with
+                | Some x -> x
+                | None -> 0
+                end
+            in
+            match f 1 with
+            | Some x -> x
+            | None -> 0
+            end
+            "#;
+        let initial_expr = allocator
+            .arena
+            .alloc(parse_core_expr(&mut symbols, &allocator, initial_str).unwrap());
+
+        let optimized_expr = optimize(&allocator, initial_expr, &|_| None);
+
+        let expected_expr = parse_core_expr(&mut symbols, &allocator, initial_str).unwrap();
+        assert_deq!(*optimized_expr, expected_expr);
+    }
 }
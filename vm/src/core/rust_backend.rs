@@ -0,0 +1,220 @@
+//! Experimental backend which translates the functions of a module into Rust source code.
+//!
+//! Only a small subset of gluon can be translated at the moment: top level functions whose
+//! arguments and return value are `Int`, `Float` or `Bool` and whose bodies only use literals,
+//! arithmetic and comparison operators, `let`, `if`/`match` on those types and calls to other
+//! translated functions. The generated code does not depend on any runtime. Functions which can't
+//! be translated are listed, together with the reason, in a comment at the top of the output.
+use std::fmt::Write;
+
+use base::ast::Literal;
+use base::fnv::{FnvMap, FnvSet};
+use base::symbol::Symbol;
+
+use core::{CExpr, Closure, Expr, Named, Pattern};
+use core::backend::{self, BinOp, Scalar};
+
+fn rust_type(scalar: Scalar) -> &'static str {
+    match scalar {
+        Scalar::Int => "isize",
+        Scalar::Float => "f64",
+        Scalar::Bool => "bool",
+    }
+}
+
+type EmitResult = Result<String, String>;
+
+/// Translates the top level functions of `expr` (a module which has been translated into core)
+/// into Rust functions
+pub fn emit_rust(expr: CExpr) -> String {
+    let (emitted, skipped) = backend::translate_functions(expr, |closures| {
+        let emitter = Emitter::new(closures);
+        closures
+            .iter()
+            .map(|closure| emitter.function(closure))
+            .collect()
+    });
+
+    let mut out = String::new();
+    writeln!(out, "// Generated by gluon from the core representation of a module").unwrap();
+    for &(ref name, ref reason) in &skipped {
+        writeln!(out, "// Skipped `{}`: {}", name.declared_name(), reason).unwrap();
+    }
+    for (_, function) in emitted {
+        writeln!(out).unwrap();
+        out.push_str(&function);
+    }
+    out
+}
+
+/// Returns a valid Rust identifier for `name`
+fn rust_ident(name: &str) -> String {
+    let ident: String = name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if ident.starts_with(char::is_numeric) || ident.is_empty() {
+        format!("_{}", ident)
+    } else {
+        ident
+    }
+}
+
+fn rust_operator(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Eq => "==",
+        BinOp::Ne => "!=",
+        BinOp::Lt => "<",
+        BinOp::Le => "<=",
+        BinOp::Gt => ">",
+        BinOp::Ge => ">=",
+    }
+}
+
+struct Emitter {
+    /// The Rust names of the functions which are being translated
+    functions: FnvMap<Symbol, String>,
+}
+
+impl Emitter {
+    fn new(closures: &[&Closure]) -> Emitter {
+        let mut used = FnvSet::default();
+        let mut functions = FnvMap::default();
+        for closure in closures {
+            // Overloaded bindings share the same name so make each function name unique
+            let base = rust_ident(closure.name.name.declared_name());
+            let mut name = base.clone();
+            let mut i = 1;
+            while !used.insert(name.clone()) {
+                name = format!("{}_{}", base, i);
+                i += 1;
+            }
+            functions.insert(closure.name.name.clone(), name);
+        }
+        Emitter { functions }
+    }
+
+    fn function(&self, closure: &Closure) -> EmitResult {
+        let (arg_types, ret) = backend::signature(closure)?;
+        let args: Vec<_> = closure
+            .args
+            .iter()
+            .zip(arg_types)
+            .map(|(arg, scalar)| {
+                format!("{}: {}", rust_ident(arg.name.declared_name()), rust_type(scalar))
+            })
+            .collect();
+
+        let mut locals: Vec<Symbol> = closure.args.iter().map(|arg| arg.name.clone()).collect();
+        let body = self.expr(closure.expr, &mut locals)?;
+        Ok(format!(
+            "#[allow(unused_parens, unused_variables)]\npub fn {}({}) -> {} {{\n    {}\n}}\n",
+            self.functions[&closure.name.name],
+            args.join(", "),
+            rust_type(ret),
+            body
+        ))
+    }
+
+    fn expr(&self, expr: CExpr, locals: &mut Vec<Symbol>) -> EmitResult {
+        match *expr {
+            Expr::Const(ref literal, _) => match *literal {
+                Literal::Int(i) => Ok(format!("{}", i)),
+                Literal::Float(f) => {
+                    // Some versions of Rust print `2.0` as `2` which would be an integer literal
+                    let mut literal = format!("{:?}", f.into_inner());
+                    if !literal.contains(|c: char| c == '.' || c.is_alphabetic()) {
+                        literal.push_str(".0");
+                    }
+                    Ok(literal)
+                }
+                _ => Err(format!("unsupported literal `{}`", expr)),
+            },
+            Expr::Ident(ref id, _) => {
+                if locals.contains(&id.name) {
+                    Ok(rust_ident(id.name.declared_name()))
+                } else {
+                    Err(format!("`{}` is not a local variable", id.name))
+                }
+            }
+            Expr::Data(ref id, args, ..) if args.is_empty() => {
+                match id.name.declared_name() {
+                    "True" => Ok("true".into()),
+                    "False" => Ok("false".into()),
+                    _ => Err(format!("unsupported constructor `{}`", id.name)),
+                }
+            }
+            Expr::Call(f, args) => {
+                let id = match *f {
+                    Expr::Ident(ref id, _) => id,
+                    _ => return Err(format!("unsupported call `{}`", expr)),
+                };
+                let args = args.iter()
+                    .map(|arg| self.expr(arg, locals))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if let Some(function) = self.functions.get(&id.name) {
+                    return Ok(format!("{}({})", function, args.join(", ")));
+                }
+                match BinOp::from_ident(id) {
+                    Some(op) if args.len() == 2 => {
+                        Ok(format!("({} {} {})", args[0], rust_operator(op), args[1]))
+                    }
+                    _ => Err(format!("call to `{}` which can't be translated", id.name)),
+                }
+            }
+            Expr::Let(ref bind, body) => {
+                let value = match bind.expr {
+                    Named::Expr(value) => value,
+                    Named::Recursive(_) => {
+                        return Err("local functions can't be translated".into())
+                    }
+                };
+                if Scalar::from_type(&bind.name.typ).is_none() {
+                    return Err(format!("`{}` is not a number or `Bool`", bind.name.name));
+                }
+                let value = self.expr(value, locals)?;
+                locals.push(bind.name.name.clone());
+                let body = self.expr(body, locals);
+                locals.pop();
+                Ok(format!(
+                    "{{ let {} = {}; {} }}",
+                    rust_ident(bind.name.name.declared_name()),
+                    value,
+                    body?
+                ))
+            }
+            Expr::Match(scrutinee, alts) => {
+                let mut out = format!("match {} {{", self.expr(scrutinee, locals)?);
+                for alt in alts {
+                    let (pattern, binding) = match alt.pattern {
+                        Pattern::Constructor(ref id, ref args) if args.is_empty() => {
+                            match id.name.declared_name() {
+                                "True" => ("true".to_string(), None),
+                                "False" => ("false".to_string(), None),
+                                _ => return Err(format!("unsupported pattern `{}`", alt.pattern)),
+                            }
+                        }
+                        Pattern::Literal(Literal::Int(i)) => (i.to_string(), None),
+                        Pattern::Ident(ref id) => {
+                            (rust_ident(id.name.declared_name()), Some(id.name.clone()))
+                        }
+                        _ => return Err(format!("unsupported pattern `{}`", alt.pattern)),
+                    };
+                    let bound = binding.is_some();
+                    locals.extend(binding);
+                    let expr = self.expr(alt.expr, locals);
+                    if bound {
+                        locals.pop();
+                    }
+                    write!(out, " {} => {},", pattern, expr?).unwrap();
+                }
+                out.push_str(" }");
+                Ok(out)
+            }
+            _ => Err(format!("unsupported expression `{}`", expr)),
+        }
+    }
+}
@@ -0,0 +1,457 @@
+//! Experimental backend which translates the functions of a module into a standalone WebAssembly
+//! module.
+//!
+//! The same subset as `rust_backend` is supported: top level functions working on `Int` (`i64`),
+//! `Float` (`f64`) and `Bool` (`i32`) values which only use literals, arithmetic and comparison
+//! operators, `let`, `if`/`match` on those types and calls to other translated functions. The
+//! translated functions are exported from the module under their gluon names (followed by a number
+//! if several functions have the same name) and the module has no imports, so it can be
+//! instantiated by any WebAssembly runtime. With the `wasm` feature, the virtual machine runs hot
+//! functions as their translated versions (see `vm::wasm`).
+use base::ast::Literal;
+use base::fnv::FnvMap;
+use base::symbol::Symbol;
+
+use core::{CExpr, Closure, Expr, Named, Pattern};
+use core::backend::{self, BinOp, Scalar};
+
+/// A WebAssembly module produced by `emit_wasm`
+#[derive(Clone, Debug, PartialEq)]
+pub struct WasmModule {
+    /// The module in the WebAssembly binary format
+    pub bytes: Vec<u8>,
+    /// The names of the functions which are exported from the module
+    pub exports: Vec<String>,
+    /// Functions which could not be translated along with the reason why
+    pub skipped: Vec<(String, String)>,
+}
+
+/// A function exported from a `WasmModule`
+#[derive(Clone, Debug)]
+pub(crate) struct Export {
+    /// The gluon function which were translated
+    pub(crate) symbol: Symbol,
+    /// The name of the export in the module
+    pub(crate) name: String,
+    pub(crate) params: Vec<Scalar>,
+    pub(crate) ret: Scalar,
+}
+
+/// Translates the top level functions of `expr` (a module which has been translated into core)
+/// into a WebAssembly module
+pub fn emit_wasm(expr: CExpr) -> WasmModule {
+    translate(expr).0
+}
+
+/// Translates `expr` like `emit_wasm`, also returning the function behind each export
+pub(crate) fn translate(expr: CExpr) -> (WasmModule, Vec<Export>) {
+    let (functions, skipped) = backend::translate_functions(expr, |closures| {
+        let indices: FnvMap<_, _> = closures
+            .iter()
+            .enumerate()
+            .map(|(i, closure)| (closure.name.name.clone(), i as u32))
+            .collect();
+        closures
+            .iter()
+            .map(|closure| Function::translate(&indices, closures, closure))
+            .collect()
+    });
+
+    let mut bytes = b"\0asm".to_vec();
+    bytes.extend(&[1, 0, 0, 0]);
+
+    // Each function gets its own type to keep things simple
+    let mut types = Vec::new();
+    unsigned(&mut types, functions.len() as u64);
+    for &(_, ref function) in &functions {
+        types.push(0x60);
+        unsigned(&mut types, function.params.len() as u64);
+        types.extend(function.params.iter().map(|&param| value_type(param)));
+        types.extend(&[1, value_type(function.ret)]);
+    }
+    section(&mut bytes, 1, &types);
+
+    let mut function_types = Vec::new();
+    unsigned(&mut function_types, functions.len() as u64);
+    for i in 0..functions.len() {
+        unsigned(&mut function_types, i as u64);
+    }
+    section(&mut bytes, 3, &function_types);
+
+    let mut exports = Vec::new();
+    let mut function_exports = Vec::new();
+    unsigned(&mut exports, functions.len() as u64);
+    for (i, &(closure, ref function)) in functions.iter().enumerate() {
+        // Export names must be unique but a module may define several functions with the same
+        // name by shadowing
+        let declared_name = closure.name.name.declared_name();
+        let mut name = declared_name.to_string();
+        let mut suffix = 1;
+        while function_exports
+            .iter()
+            .any(|export: &Export| export.name == name)
+        {
+            name = format!("{}_{}", declared_name, suffix);
+            suffix += 1;
+        }
+        unsigned(&mut exports, name.len() as u64);
+        exports.extend(name.as_bytes());
+        exports.push(0x00);
+        unsigned(&mut exports, i as u64);
+        function_exports.push(Export {
+            symbol: closure.name.name.clone(),
+            name,
+            params: function.params.clone(),
+            ret: function.ret,
+        });
+    }
+    section(&mut bytes, 7, &exports);
+
+    let mut code = Vec::new();
+    unsigned(&mut code, functions.len() as u64);
+    for &(_, ref function) in &functions {
+        let mut body = Vec::new();
+        unsigned(&mut body, function.locals.len() as u64);
+        for &local in &function.locals {
+            body.push(1);
+            body.push(value_type(local));
+        }
+        body.extend(&function.code);
+        body.push(END);
+        unsigned(&mut code, body.len() as u64);
+        code.extend(body);
+    }
+    section(&mut bytes, 10, &code);
+
+    let module = WasmModule {
+        bytes,
+        exports: function_exports
+            .iter()
+            .map(|export| export.name.clone())
+            .collect(),
+        skipped: skipped
+            .into_iter()
+            .map(|(name, reason)| (name.declared_name().to_string(), reason))
+            .collect(),
+    };
+    (module, function_exports)
+}
+
+const UNREACHABLE: u8 = 0x00;
+const IF: u8 = 0x04;
+const ELSE: u8 = 0x05;
+const END: u8 = 0x0B;
+const CALL: u8 = 0x10;
+const LOCAL_GET: u8 = 0x20;
+const LOCAL_SET: u8 = 0x21;
+const I32_CONST: u8 = 0x41;
+const I64_CONST: u8 = 0x42;
+const F64_CONST: u8 = 0x44;
+const I32_EQZ: u8 = 0x45;
+const I64_EQ: u8 = 0x51;
+
+fn value_type(scalar: Scalar) -> u8 {
+    match scalar {
+        Scalar::Int => 0x7E,
+        Scalar::Float => 0x7C,
+        Scalar::Bool => 0x7F,
+    }
+}
+
+fn operator(op: BinOp, operand: Scalar) -> Result<u8, String> {
+    Ok(match (operand, op) {
+        (Scalar::Int, BinOp::Add) => 0x7C,
+        (Scalar::Int, BinOp::Sub) => 0x7D,
+        (Scalar::Int, BinOp::Mul) => 0x7E,
+        (Scalar::Int, BinOp::Div) => 0x7F,
+        (Scalar::Int, BinOp::Eq) => I64_EQ,
+        (Scalar::Int, BinOp::Ne) => 0x52,
+        (Scalar::Int, BinOp::Lt) => 0x53,
+        (Scalar::Int, BinOp::Gt) => 0x55,
+        (Scalar::Int, BinOp::Le) => 0x57,
+        (Scalar::Int, BinOp::Ge) => 0x59,
+        (Scalar::Float, BinOp::Add) => 0xA0,
+        (Scalar::Float, BinOp::Sub) => 0xA1,
+        (Scalar::Float, BinOp::Mul) => 0xA2,
+        (Scalar::Float, BinOp::Div) => 0xA3,
+        (Scalar::Float, BinOp::Eq) => 0x61,
+        (Scalar::Float, BinOp::Ne) => 0x62,
+        (Scalar::Float, BinOp::Lt) => 0x63,
+        (Scalar::Float, BinOp::Gt) => 0x64,
+        (Scalar::Float, BinOp::Le) => 0x65,
+        (Scalar::Float, BinOp::Ge) => 0x66,
+        (Scalar::Bool, BinOp::Eq) => 0x46,
+        (Scalar::Bool, BinOp::Ne) => 0x47,
+        (Scalar::Bool, _) => return Err(format!("`{:?}` is not defined for `Bool`", op)),
+    })
+}
+
+fn section(bytes: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    bytes.push(id);
+    unsigned(bytes, contents.len() as u64);
+    bytes.extend(contents);
+}
+
+/// Writes `value` in the unsigned LEB128 encoding
+fn unsigned(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Writes `value` in the signed LEB128 encoding
+fn signed(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        let sign_bit_clear = byte & 0x40 == 0;
+        if (value == 0 && sign_bit_clear) || (value == -1 && !sign_bit_clear) {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+struct Function {
+    params: Vec<Scalar>,
+    ret: Scalar,
+    /// The locals of the function which are not parameters
+    locals: Vec<Scalar>,
+    code: Vec<u8>,
+}
+
+struct FunctionTranslator<'f> {
+    /// The index of each function which is being translated
+    indices: &'f FnvMap<Symbol, u32>,
+    closures: &'f [&'f Closure<'f>],
+    params: usize,
+    locals: Vec<Scalar>,
+    /// The variables in scope and the local they are stored in
+    scope: Vec<(Symbol, u32, Scalar)>,
+}
+
+impl Function {
+    fn translate<'f>(
+        indices: &'f FnvMap<Symbol, u32>,
+        closures: &'f [&'f Closure<'f>],
+        closure: &Closure,
+    ) -> Result<Function, String> {
+        let (params, ret) = backend::signature(closure)?;
+        let mut translator = FunctionTranslator {
+            indices,
+            closures,
+            params: params.len(),
+            locals: Vec::new(),
+            scope: closure
+                .args
+                .iter()
+                .zip(&params)
+                .enumerate()
+                .map(|(i, (arg, &scalar))| (arg.name.clone(), i as u32, scalar))
+                .collect(),
+        };
+        let mut code = Vec::new();
+        translator.expr(&mut code, closure.expr)?;
+        Ok(Function {
+            params,
+            ret,
+            locals: translator.locals,
+            code,
+        })
+    }
+}
+
+impl<'f> FunctionTranslator<'f> {
+    fn new_local(&mut self, scalar: Scalar) -> u32 {
+        self.locals.push(scalar);
+        (self.params + self.locals.len() - 1) as u32
+    }
+
+    /// Writes the instructions for `expr` to `code`, returning the type of the value it leaves on
+    /// the stack
+    fn expr(&mut self, code: &mut Vec<u8>, expr: CExpr) -> Result<Scalar, String> {
+        match *expr {
+            Expr::Const(ref literal, _) => match *literal {
+                Literal::Int(i) => {
+                    code.push(I64_CONST);
+                    signed(code, i);
+                    Ok(Scalar::Int)
+                }
+                Literal::Float(f) => {
+                    code.push(F64_CONST);
+                    let bits = f.into_inner().to_bits();
+                    code.extend((0..8).map(|i| (bits >> (i * 8)) as u8));
+                    Ok(Scalar::Float)
+                }
+                _ => Err(format!("unsupported literal `{}`", expr)),
+            },
+            Expr::Ident(ref id, _) => {
+                let &(_, local, scalar) = self.scope
+                    .iter()
+                    .rev()
+                    .find(|&&(ref name, _, _)| *name == id.name)
+                    .ok_or_else(|| format!("`{}` is not a local variable", id.name))?;
+                code.push(LOCAL_GET);
+                unsigned(code, u64::from(local));
+                Ok(scalar)
+            }
+            Expr::Data(ref id, args, ..) if args.is_empty() => {
+                let value = match id.name.declared_name() {
+                    "True" => 1,
+                    "False" => 0,
+                    _ => return Err(format!("unsupported constructor `{}`", id.name)),
+                };
+                code.push(I32_CONST);
+                signed(code, value);
+                Ok(Scalar::Bool)
+            }
+            Expr::Call(f, args) => {
+                let id = match *f {
+                    Expr::Ident(ref id, _) => id,
+                    _ => return Err(format!("unsupported call `{}`", expr)),
+                };
+                let mut arg_types = Vec::new();
+                for arg in args {
+                    arg_types.push(self.expr(code, arg)?);
+                }
+                if let Some(&index) = self.indices.get(&id.name) {
+                    let (_, ret) = backend::signature(self.closures[index as usize])?;
+                    code.push(CALL);
+                    unsigned(code, u64::from(index));
+                    return Ok(ret);
+                }
+                match BinOp::from_ident(id) {
+                    Some(op) if args.len() == 2 => {
+                        code.push(operator(op, arg_types[0])?);
+                        Ok(if op.is_comparison() {
+                            Scalar::Bool
+                        } else {
+                            arg_types[0]
+                        })
+                    }
+                    _ => Err(format!("call to `{}` which can't be translated", id.name)),
+                }
+            }
+            Expr::Let(ref bind, body) => {
+                let value = match bind.expr {
+                    Named::Expr(value) => value,
+                    Named::Recursive(_) => {
+                        return Err("local functions can't be translated".into())
+                    }
+                };
+                let scalar = self.expr(code, value)?;
+                let local = self.new_local(scalar);
+                code.push(LOCAL_SET);
+                unsigned(code, u64::from(local));
+
+                self.scope.push((bind.name.name.clone(), local, scalar));
+                let result = self.expr(code, body);
+                self.scope.pop();
+                result
+            }
+            Expr::Match(scrutinee, alts) => {
+                let scalar = self.expr(code, scrutinee)?;
+                let local = self.new_local(scalar);
+                code.push(LOCAL_SET);
+                unsigned(code, u64::from(local));
+
+                // Translate each alternative into a condition (`None` if the alternative always
+                // matches) and the code for its expression
+                let mut branches = Vec::new();
+                let mut result = None;
+                for alt in alts {
+                    let mut condition = vec![LOCAL_GET];
+                    unsigned(&mut condition, u64::from(local));
+                    let condition = match alt.pattern {
+                        Pattern::Constructor(ref id, ref args) if args.is_empty() => {
+                            match id.name.declared_name() {
+                                "True" => Some(condition),
+                                "False" => {
+                                    condition.push(I32_EQZ);
+                                    Some(condition)
+                                }
+                                _ => return Err(format!("unsupported pattern `{}`", alt.pattern)),
+                            }
+                        }
+                        Pattern::Literal(Literal::Int(i)) => {
+                            condition.push(I64_CONST);
+                            signed(&mut condition, i);
+                            condition.push(I64_EQ);
+                            Some(condition)
+                        }
+                        Pattern::Ident(_) => None,
+                        _ => return Err(format!("unsupported pattern `{}`", alt.pattern)),
+                    };
+
+                    if let Pattern::Ident(ref id) = alt.pattern {
+                        self.scope.push((id.name.clone(), local, scalar));
+                    }
+                    let mut branch = Vec::new();
+                    let branch_result = self.expr(&mut branch, alt.expr);
+                    if let Pattern::Ident(_) = alt.pattern {
+                        self.scope.pop();
+                    }
+                    result = Some(branch_result?);
+
+                    let always_matches = condition.is_none();
+                    branches.push((condition, branch));
+                    if always_matches {
+                        break;
+                    }
+                }
+                let result = result.ok_or_else(|| "empty match".to_string())?;
+                branches_code(code, &branches, value_type(result));
+                Ok(result)
+            }
+            _ => Err(format!("unsupported expression `{}`", expr)),
+        }
+    }
+}
+
+/// Writes nested `if` instructions which run the first branch whose condition holds
+fn branches_code(code: &mut Vec<u8>, branches: &[(Option<Vec<u8>>, Vec<u8>)], block_type: u8) {
+    match branches.split_first() {
+        None => code.push(UNREACHABLE),
+        Some((&(None, ref branch), _)) => code.extend(branch),
+        Some((&(Some(ref condition), ref branch), rest)) => {
+            code.extend(condition);
+            code.extend(&[IF, block_type]);
+            code.extend(branch);
+            code.push(ELSE);
+            branches_code(code, rest, block_type);
+            code.push(END);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leb128() {
+        let encode_unsigned = |value| {
+            let mut bytes = Vec::new();
+            unsigned(&mut bytes, value);
+            bytes
+        };
+        let encode_signed = |value| {
+            let mut bytes = Vec::new();
+            signed(&mut bytes, value);
+            bytes
+        };
+        assert_eq!(encode_unsigned(0), [0x00]);
+        assert_eq!(encode_unsigned(624485), [0xE5, 0x8E, 0x26]);
+        assert_eq!(encode_signed(2), [0x02]);
+        assert_eq!(encode_signed(-1), [0x7F]);
+        assert_eq!(encode_signed(-123456), [0xC0, 0xBB, 0x78]);
+        assert_eq!(encode_signed(64), [0xC0, 0x00]);
+    }
+}
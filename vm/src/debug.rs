@@ -1,12 +1,43 @@
 use api::generic::A;
-use api::Generic;
-use thread::Thread;
+use api::{Generic, WithVM};
+use thread::{Thread, ThreadInternal};
+use types::VmInt;
 use {ExternModule, Result};
 
+field_decl! { name, module, line }
+
+/// A frame of `backtrace`. `line` is `None` for extern functions.
+type Frame = record_type!{ name => String, module => String, line => Option<VmInt> };
+
 fn trace(a: Generic<A>) {
     println!("{:?}", a);
 }
 
+/// Returns the frames of the calling thread with the outermost frame first
+fn backtrace(unit: WithVM<()>) -> Vec<Frame> {
+    let stacktrace = unit.vm.context().stack.stacktrace(0);
+    // The last frame is the call to `backtrace` itself
+    let len = stacktrace.frames.len().saturating_sub(1);
+    stacktrace
+        .frames
+        .into_iter()
+        .take(len)
+        .filter_map(|frame| frame)
+        .map(|frame| {
+            let line = if frame.is_extern() {
+                None
+            } else {
+                Some(frame.line.to_usize() as VmInt + 1)
+            };
+            record_no_decl!{
+                name => frame.name.declared_name().to_string(),
+                module => frame.module,
+                line => line
+            }
+        })
+        .collect()
+}
+
 mod std {
     pub use debug;
 }
@@ -17,7 +48,8 @@ pub fn load(vm: &Thread) -> Result<ExternModule> {
     ExternModule::new(
         vm,
         record!{
-            trace => primitive!(1 std::debug::trace)
+            trace => primitive!(1 std::debug::trace),
+            backtrace => primitive!(1 std::debug::backtrace)
         },
     )
 }
@@ -0,0 +1,155 @@
+//! Module which renders the difference between two strings, used by the assertions in
+//! `std.test.assert` and `std.test.expect`
+use thread::Thread;
+use {ExternModule, Result};
+
+/// Comparing longer strings than this (in tokens) falls back to showing both strings in full
+const MAX_TOKENS: usize = 2000;
+
+/// Splits `s` into identifiers, numbers, whitespace and single punctuation characters so that
+/// concatenating the tokens gives back `s`
+fn tokens(s: &str) -> Vec<&str> {
+    fn class(c: char) -> u8 {
+        if c.is_alphanumeric() || c == '_' || c == '.' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut prev = None;
+    for (i, c) in s.char_indices() {
+        let current = class(c);
+        if i != start && (current == 2 || prev != Some(current)) {
+            tokens.push(&s[start..i]);
+            start = i;
+        }
+        prev = Some(current);
+    }
+    if start != s.len() {
+        tokens.push(&s[start..]);
+    }
+    tokens
+}
+
+/// Returns `r` where the tokens only in `l` are marked as `[-removed-]` and the tokens only in `r`
+/// are marked as `{+added+}`
+pub fn diff(l: &str, r: &str) -> String {
+    let l_tokens = tokens(l);
+    let r_tokens = tokens(r);
+    if l_tokens.len() > MAX_TOKENS || r_tokens.len() > MAX_TOKENS {
+        return format!("[-{}-]{{+{}+}}", l, r);
+    }
+
+    // `common[i][j]` is the length of the longest common subsequence of `l_tokens[i..]` and
+    // `r_tokens[j..]`
+    let mut common = vec![vec![0; r_tokens.len() + 1]; l_tokens.len() + 1];
+    for i in (0..l_tokens.len()).rev() {
+        for j in (0..r_tokens.len()).rev() {
+            common[i][j] = if l_tokens[i] == r_tokens[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut output = String::new();
+    let mut removed = String::new();
+    let mut added = String::new();
+    let flush = |output: &mut String, removed: &mut String, added: &mut String| {
+        if !removed.is_empty() {
+            output.push_str(&format!("[-{}-]", removed));
+            removed.clear();
+        }
+        if !added.is_empty() {
+            output.push_str(&format!("{{+{}+}}", added));
+            added.clear();
+        }
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < l_tokens.len() || j < r_tokens.len() {
+        if i < l_tokens.len() && j < r_tokens.len() && l_tokens[i] == r_tokens[j] {
+            flush(&mut output, &mut removed, &mut added);
+            output.push_str(l_tokens[i]);
+            i += 1;
+            j += 1;
+        } else if j == r_tokens.len()
+            || (i < l_tokens.len() && common[i + 1][j] >= common[i][j + 1])
+        {
+            removed.push_str(l_tokens[i]);
+            i += 1;
+        } else {
+            added.push_str(r_tokens[j]);
+            j += 1;
+        }
+    }
+    flush(&mut output, &mut removed, &mut added);
+    output
+}
+
+/// Returns `s` as a string literal which the parser reads back as `s`
+pub fn string_literal(s: &str) -> String {
+    let mut literal = String::with_capacity(s.len() + 2);
+    literal.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+mod std {
+    pub mod test {
+        pub use diff as prim;
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    ExternModule::new(
+        vm,
+        record!{
+            diff => primitive!(2 std::test::prim::diff),
+            string_literal => primitive!(1 std::test::prim::string_literal)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, string_literal};
+
+    #[test]
+    fn diff_equal() {
+        assert_eq!(diff("{ x = 1 }", "{ x = 1 }"), "{ x = 1 }");
+    }
+
+    #[test]
+    fn diff_changed_tokens() {
+        assert_eq!(
+            diff("{ x = 1, y = [1, 2] }", "{ x = 1, y = [1, 3] }"),
+            "{ x = 1, y = [1, [-2-]{+3+}] }"
+        );
+        assert_eq!(diff("Some 1.5", "None"), "[-Some 1.5-]{+None+}");
+        assert_eq!(diff("[1]", "[1, 2]"), "[1{+, 2+}]");
+    }
+
+    #[test]
+    fn string_literal_escapes() {
+        assert_eq!(string_literal("abc"), r#""abc""#);
+        assert_eq!(string_literal("\"a\\b\"\n\tc"), r#""\"a\\b\"\n\tc""#);
+    }
+}
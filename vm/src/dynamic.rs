@@ -50,6 +50,6 @@ where
     FieldIter {
         value: value,
         index: 0,
-        resolved_type: resolve::remove_aliases_cow(&*thread.global_env().get_env(), typ),
+        resolved_type: resolve::remove_aliases_cow(&thread.global_env().get_env(), typ),
     }
 }
@@ -10,10 +10,17 @@ use std::cell::Cell;
 use std::any::{Any, TypeId};
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::sync::atomic::{self, AtomicUsize, ATOMIC_USIZE_INIT};
+use std::time::Duration;
+use std::usize;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 
-use base::fnv::FnvMap;
+use base::fnv::{FnvHasher, FnvMap};
 use interner::InternedStr;
 use types::VmIndex;
+use thread::Thread;
+use value::{GcStr, Userdata};
 use {Error, Result};
 
 #[inline]
@@ -45,6 +52,19 @@ unsafe fn deallocate(ptr: *mut u8, old_size: usize) {
     Vec::<f64>::from_raw_parts(ptr as *mut f64, 0, cap);
 }
 
+/// Rounds `size` up so that a value placed after it in an allocation buffer is aligned
+#[inline]
+fn align_size(size: usize) -> usize {
+    let align = mem::align_of::<f64>();
+    (size + align - 1) & !(align - 1)
+}
+
+fn str_hash(s: &str) -> u64 {
+    let mut hasher = FnvHasher::default();
+    s.hash(&mut hasher);
+    hasher.finish()
+}
+
 /// Pointer type which can only be written to.
 pub struct WriteOnly<'s, T: ?Sized + 's>(*mut T, PhantomData<&'s mut T>);
 
@@ -129,18 +149,270 @@ impl Generation {
     }
 }
 
+/// Runs `f`, returning how long it took
+#[cfg(not(target_arch = "wasm32"))]
+fn timed<F>(f: F) -> Duration
+where
+    F: FnOnce(),
+{
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+// `Instant` is not available on wasm so no pause times are recorded there
+#[cfg(target_arch = "wasm32")]
+fn timed<F>(f: F) -> Duration
+where
+    F: FnOnce(),
+{
+    f();
+    Duration::default()
+}
+
+/// Counter used to give each collection a unique mark. Since the collection of a garbage collector
+/// also marks the values of its child garbage collectors the marks must be unique across all
+/// garbage collectors.
+static MARK_EPOCH: AtomicUsize = ATOMIC_USIZE_INIT;
+
+fn next_mark_epoch() -> usize {
+    MARK_EPOCH.fetch_add(1, atomic::Ordering::Relaxed) + 1
+}
+
+/// Decides how much the heap may grow after a major collection before the next major collection
+/// is run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HeapGrowth {
+    /// Collect when the heap has grown to this many times the memory which survived the last
+    /// major collection
+    Proportional(f64),
+    /// Collect when this many bytes have been allocated since the last major collection
+    Additive(usize),
+}
+
+impl HeapGrowth {
+    fn next_limit(self, live_memory: usize) -> usize {
+        match self {
+            HeapGrowth::Proportional(factor) => (live_memory as f64 * factor) as usize,
+            HeapGrowth::Additive(bytes) => live_memory.saturating_add(bytes),
+        }
+    }
+}
+
+/// Settings which control when and how `Gc` allocates and collects values
+#[derive(Clone, Debug, PartialEq)]
+pub struct GcSettings {
+    /// The size in bytes of the buffers which small values are allocated into. Allocating into a
+    /// buffer only needs to bump a pointer but the memory of a buffer is only returned once every
+    /// value in it has been freed. Set to 0 to allocate each value separately.
+    pub allocation_buffer_size: usize,
+    /// Values of at least this many bytes are allocated into the large object space. They get an
+    /// allocation of their own and allocating them raises the collection threshold by their size,
+    /// so reading a large file into a string does not start a collection which could not free it.
+    pub large_object_size: usize,
+    /// If set, strings of at most this many bytes which survive a collection are remembered and
+    /// returned by later allocations of an equal string instead of allocating a copy
+    pub deduplicate_strings: Option<usize>,
+    /// How many bytes of values may be allocated in the young generation before a minor
+    /// collection is run
+    pub nursery_size: usize,
+    /// How much the heap may grow before a major collection is run
+    pub heap_growth: HeapGrowth,
+    /// If set, the old generation is swept in batches of this many values after a major
+    /// collection, one batch each time the garbage collector is given the chance to collect,
+    /// instead of all at once. This shortens the pause of a major collection at the cost of
+    /// keeping unreachable values around for a while longer. See `incremental_mark` for splitting
+    /// up the mark.
+    pub sweep_batch_size: Option<usize>,
+    /// If set, major collections mark the heap incrementally, traversing this many values each
+    /// time the garbage collector is given the chance to collect, instead of in a single pause.
+    /// The program keeps running between the steps so the roots, and any value which was written
+    /// to while marking, are traversed again in a last short pause which finishes the mark.
+    pub incremental_mark: Option<usize>,
+}
+
+impl Default for GcSettings {
+    fn default() -> GcSettings {
+        GcSettings {
+            allocation_buffer_size: 32 * 1024,
+            large_object_size: 64 * 1024,
+            deduplicate_strings: None,
+            nursery_size: 256 * 1024,
+            heap_growth: HeapGrowth::Proportional(2.0),
+            sweep_batch_size: None,
+            incremental_mark: None,
+        }
+    }
+}
+
+/// Statistics about the collections which a `Gc` has run
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GcStats {
+    /// The number of collections of the young generation
+    pub minor_collections: usize,
+    /// The number of collections of the entire heap
+    pub major_collections: usize,
+    /// The number of bytes of values which survived a minor collection and were moved to the old
+    /// generation
+    pub promoted_bytes: usize,
+    /// The number of bytes which have been returned to the allocator
+    pub freed_bytes: usize,
+    /// The total time spent collecting, including the time spent sweeping batches
+    pub total_pause: Duration,
+    /// The longest time spent in a single collection or sweeping a single batch
+    pub max_pause: Duration,
+    /// The number of objects which have been allocated
+    pub allocations: usize,
+    /// The number of bytes which have been allocated, including the bytes which have been freed
+    pub allocated_bytes: usize,
+}
+
+impl GcStats {
+    fn record_pause(&mut self, pause: Duration) {
+        self.total_pause += pause;
+        if pause > self.max_pause {
+            self.max_pause = pause;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Collection {
+    /// Only sweeps the values allocated since the last collection
+    Minor,
+    /// Sweeps every value
+    Major,
+}
+
+/// Decides which values `mark` marks and how their fields are traversed
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Marking {
+    /// Marks every value of this and the child generations and traverses it right away
+    Full,
+    /// Like `Full` but skips the values of the old generation, which only need to be traversed if
+    /// they may refer to a young value
+    Young,
+    /// Marks only values of this generation and pushes them onto the gray stack, to be traversed
+    /// by a later step of an incremental mark. The child generations are marked when the mark is
+    /// finished since their garbage collectors may collect in between the steps.
+    Incremental,
+}
+
+impl Default for Marking {
+    fn default() -> Marking {
+        Marking::Full
+    }
+}
+
+/// A pointer to a value whose type has been erased, keeping only what is needed to traverse it
+#[derive(Clone, Copy, Debug)]
+struct UntypedPtr {
+    /// The bytes of a possibly fat `*const T`
+    ptr: [usize; 2],
+    traverse: unsafe fn(&[usize; 2], &mut Gc),
+}
+
+impl UntypedPtr {
+    fn new<T: ?Sized + Traverseable>(value: GcPtr<T>) -> UntypedPtr {
+        unsafe fn traverse<T: ?Sized + Traverseable>(ptr: &[usize; 2], gc: &mut Gc) {
+            let value: *const T = mem::transmute_copy(ptr);
+            (*value).traverse(gc)
+        }
+        debug_assert!(mem::size_of::<*const T>() <= mem::size_of::<[usize; 2]>());
+        let mut ptr = [0; 2];
+        unsafe {
+            ptr::copy_nonoverlapping(
+                &value.ptr as *const *const T as *const u8,
+                ptr.as_mut_ptr() as *mut u8,
+                mem::size_of::<*const T>(),
+            );
+        }
+        UntypedPtr {
+            ptr: ptr,
+            traverse: traverse::<T>,
+        }
+    }
+
+    fn header(&self) -> &GcHeader {
+        // The data pointer comes first in a fat pointer as well (see `GcPtr::header`)
+        unsafe {
+            let header = (self.ptr[0] as *const u8).offset(-(GcHeader::value_offset() as isize));
+            &*(header as *const GcHeader)
+        }
+    }
+
+    /// Traverses the fields of the value. Unsafe as the value must not have been freed.
+    unsafe fn traverse(&self, gc: &mut Gc) {
+        (self.traverse)(&self.ptr, gc)
+    }
+}
+
+/// Returns a pointer to `ptr` if values of type `T` may be written to without calling
+/// `Gc::write_barrier`. Userdata such as references and threads are mutated through a lock of
+/// their own so the garbage collector needs to traverse them in every collection instead.
+fn unbarriered_value<T: Any>(ptr: *const T) -> Option<UntypedPtr> {
+    let id = TypeId::of::<T>();
+    if id == TypeId::of::<Box<Userdata>>() {
+        Some(UntypedPtr::new(GcPtr {
+            ptr: ptr as *const Box<Userdata>,
+        }))
+    } else if id == TypeId::of::<Thread>() {
+        Some(UntypedPtr::new(GcPtr {
+            ptr: ptr as *const Thread,
+        }))
+    } else {
+        None
+    }
+}
+
 /// A mark and sweep garbage collector.
+///
+/// Small values are bump allocated into allocation buffers (see
+/// `GcSettings::allocation_buffer_size`). Values are never moved so a buffer is kept alive until
+/// the last value in it has been freed. The whole buffer counts against the memory limit for as
+/// long as it is alive.
+///
+/// Large values (see `GcSettings::large_object_size`) are kept in a list of their own which is
+/// swept by every collection.
+///
+/// Other values are allocated into the young generation and are moved to the old generation once
+/// they survive a collection. A minor collection only marks and sweeps the young generation while
+/// a major collection marks and sweeps the entire heap. The mark of a major collection can be
+/// split into steps (see `GcSettings::incremental_mark`) and so can its sweep (see
+/// `GcSettings::sweep_batch_size`).
+///
+/// Code which writes a pointer into a value after the value has been allocated must call
+/// `write_barrier` on it. The barrier remembers old values which may now refer to young values so
+/// that a minor collection can find the young values without traversing the old generation, and
+/// makes an incremental mark traverse values which it has already traversed again.
 #[derive(Debug)]
 #[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
 #[cfg_attr(feature = "serde_derive", serde(deserialize_state = "::serialization::DeSeed"))]
 #[cfg_attr(feature = "serde_derive", serde(serialize_state = "::serialization::SeSeed"))]
 pub struct Gc {
-    /// Linked list of all objects allocted by this garbage collector.
+    /// Linked list of the objects allocated since the last collection (the young generation)
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     values: Option<AllocPtr>,
-    /// How many bytes which is currently allocated
+    /// Linked list of the objects which have survived a collection (the old generation)
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    old_values: Option<AllocPtr>,
+    /// Objects of the old generation which have not been swept yet after a major collection which
+    /// sweeps in batches
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    unswept_values: Option<AllocPtr>,
+    /// Linked list of the values allocated in the large object space
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    large_values: Option<AllocPtr>,
+    /// The buffer which small values are currently bump allocated into. Each thread has its own
+    /// garbage collector so allocating into it never needs to synchronize with other threads.
+    /// Must be declared after the list of values so that it is dropped after them.
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    buffer: Option<AllocationBuffer>,
+    /// How many bytes which is currently allocated, counting each allocation buffer as a whole
     allocated_memory: usize,
-    /// How many bytes this garbage collector can allocate before a collection is run
+    /// How many bytes of values that were allocated since the last collection
+    young_memory: usize,
+    /// How many bytes this garbage collector can allocate before a major collection is run
     collect_limit: usize,
     /// The maximum number of bytes this garbage collector may contain
     memory_limit: usize,
@@ -148,6 +420,38 @@ pub struct Gc {
     type_infos: FnvMap<TypeId, Box<TypeInfo>>,
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     record_infos: FnvMap<Vec<InternedStr>, Box<TypeInfo>>,
+    /// Strings which survived a collection and can be returned instead of allocating an equal
+    /// string, keyed by the hash of their contents (see `GcSettings::deduplicate_strings`). Does
+    /// not keep the strings alive, each collection removes the strings it found unreachable.
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    strings: FnvMap<u64, GcStr>,
+    /// Strings allocated since the last collection which are added to `strings` if they survive
+    /// it
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    new_strings: Vec<GcStr>,
+    /// The mark of the current or last collection. Values which do not have this mark after the
+    /// mark phase are unreachable.
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    mark_epoch: usize,
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    marking: Marking,
+    /// True while the steps of an incremental mark are running
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    incremental_mark: bool,
+    /// The values which an incremental mark has marked but whose fields it has not traversed yet
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    gray: Vec<UntypedPtr>,
+    /// Old values which have been written to since the last collection and may refer to young
+    /// values (see `write_barrier`)
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    remembered: Vec<UntypedPtr>,
+    /// The values which are written to without a write barrier (see `unbarriered_value`)
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    unbarriered: Vec<UntypedPtr>,
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    settings: GcSettings,
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    stats: GcStats,
     /// The generation of a gc determines what values it needs to copy and what values it can
     /// share. A gc can share values generated by itself (the same generation) and those in an
     /// earlier (lower) generation. It is important to note that two garbage collectors can have
@@ -222,6 +526,8 @@ unsafe impl<T> DataDef for Move<T> {
 struct TypeInfo {
     drop: unsafe fn(*mut ()),
     generation: Generation,
+    /// True if values of this type are written to without a write barrier
+    unbarriered: bool,
     fields: FnvMap<InternedStr, VmIndex>,
     fields_key: Arc<Vec<InternedStr>>,
 }
@@ -229,9 +535,140 @@ struct TypeInfo {
 #[derive(Debug)]
 struct GcHeader {
     next: Option<AllocPtr>,
-    marked: Cell<bool>,
+    /// The mark of the last collection which found this value to be reachable
+    mark: Cell<usize>,
+    /// True once the value has survived a collection
+    old: Cell<bool>,
+    /// True if the value is in the remembered set
+    remembered: Cell<bool>,
     value_size: usize,
     type_info: *const TypeInfo,
+    /// The allocation buffer which this value was allocated in or null if it was allocated
+    /// separately
+    chunk: *mut Chunk,
+}
+
+/// A block of memory which values are allocated into by bumping an offset. A chunk is freed
+/// once the garbage collector has stopped allocating into it (it is retired) and every value
+/// allocated in it has been freed.
+#[derive(Debug)]
+struct Chunk {
+    memory: *mut u8,
+    size: usize,
+    /// The offset of the first unused byte
+    used: usize,
+    /// The number of values in the chunk which have not been freed
+    live: usize,
+    retired: bool,
+}
+
+impl Chunk {
+    fn new(size: usize) -> *mut Chunk {
+        Box::into_raw(Box::new(Chunk {
+            memory: unsafe { allocate(size) },
+            size: size,
+            used: 0,
+            live: 0,
+            retired: false,
+        }))
+    }
+
+    /// Returns true if `size` bytes can be bump allocated in the chunk
+    #[inline]
+    fn fits(&self, size: usize) -> bool {
+        self.size - self.used >= size
+    }
+
+    /// Returns a pointer to `size` unused bytes or `None` if the chunk is full. `size` must be a
+    /// multiple of the alignment of `f64`.
+    #[inline]
+    fn bump(&mut self, size: usize) -> Option<*mut u8> {
+        if !self.fits(size) {
+            return None;
+        }
+        let ptr = unsafe { self.memory.offset(self.used as isize) };
+        self.used += size;
+        self.live += 1;
+        Some(ptr)
+    }
+
+    /// The number of bytes which are returned to the allocator if one more value in the chunk is
+    /// freed
+    fn released_memory(&self) -> usize {
+        if self.retired && self.live == 1 {
+            self.size
+        } else {
+            0
+        }
+    }
+
+    /// Called when a value allocated in `chunk` is freed
+    unsafe fn release(chunk: *mut Chunk) {
+        (*chunk).live -= 1;
+        if (*chunk).live == 0 && (*chunk).retired {
+            Chunk::free(chunk);
+        }
+    }
+
+    /// Called when no more values will be allocated in `chunk`. Returns the number of bytes which
+    /// were returned to the allocator.
+    unsafe fn retire(chunk: *mut Chunk) -> usize {
+        (*chunk).retired = true;
+        if (*chunk).live == 0 {
+            let size = (*chunk).size;
+            Chunk::free(chunk);
+            size
+        } else {
+            0
+        }
+    }
+
+    unsafe fn free(chunk: *mut Chunk) {
+        let chunk = Box::from_raw(chunk);
+        deallocate(chunk.memory, chunk.size);
+    }
+}
+
+/// The chunk which a garbage collector currently allocates into. Retires the chunk when dropped.
+#[derive(Debug)]
+struct AllocationBuffer {
+    chunk: *mut Chunk,
+}
+
+unsafe impl Send for AllocationBuffer {}
+
+impl AllocationBuffer {
+    fn new(size: usize) -> AllocationBuffer {
+        AllocationBuffer {
+            chunk: Chunk::new(size),
+        }
+    }
+
+    #[inline]
+    fn fits(&self, size: usize) -> bool {
+        unsafe { (*self.chunk).fits(size) }
+    }
+
+    #[inline]
+    fn bump(&self, size: usize) -> Option<*mut u8> {
+        unsafe { (*self.chunk).bump(size) }
+    }
+
+    /// Stops allocating into the buffer. Returns the number of bytes which were returned to the
+    /// allocator.
+    fn retire(self) -> usize {
+        let chunk = self.chunk;
+        mem::forget(self);
+        unsafe { Chunk::retire(chunk) }
+    }
+}
+
+impl Drop for AllocationBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            Chunk::retire(self.chunk);
+        }
+    }
 }
 
 struct AllocPtr {
@@ -242,26 +679,51 @@ unsafe impl Send for AllocPtr {}
 
 impl AllocPtr {
     fn new<T>(type_info: *const TypeInfo, value_size: usize) -> AllocPtr {
-        debug_assert!(mem::align_of::<T>() <= mem::align_of::<f64>());
         unsafe {
             let alloc_size = GcHeader::value_offset() + value_size;
-            let ptr = allocate(alloc_size) as *mut GcHeader;
-            ptr::write(
-                ptr,
-                GcHeader {
-                    next: None,
-                    type_info: type_info,
-                    value_size: value_size,
-                    marked: Cell::new(false),
-                },
-            );
-            AllocPtr { ptr: ptr }
+            let ptr = allocate(alloc_size);
+            AllocPtr::init::<T>(ptr, ptr::null_mut(), type_info, value_size)
         }
     }
 
+    /// Unsafe as `ptr` must point to enough memory to fit the header and the value
+    unsafe fn init<T>(
+        ptr: *mut u8,
+        chunk: *mut Chunk,
+        type_info: *const TypeInfo,
+        value_size: usize,
+    ) -> AllocPtr {
+        debug_assert!(mem::align_of::<T>() <= mem::align_of::<f64>());
+        let ptr = ptr as *mut GcHeader;
+        ptr::write(
+            ptr,
+            GcHeader {
+                next: None,
+                type_info: type_info,
+                value_size: value_size,
+                mark: Cell::new(0),
+                old: Cell::new(false),
+                remembered: Cell::new(false),
+                chunk: chunk,
+            },
+        );
+        AllocPtr { ptr: ptr }
+    }
+
     fn size(&self) -> usize {
         GcHeader::value_offset() + self.value_size
     }
+
+    /// The number of bytes which are returned to the allocator when this value is freed. A value
+    /// in an allocation buffer only returns the memory of the buffer, once it is the last value
+    /// in it.
+    fn released_memory(&self) -> usize {
+        if self.chunk.is_null() {
+            self.size()
+        } else {
+            unsafe { (*self.chunk).released_memory() }
+        }
+    }
 }
 
 impl fmt::Debug for AllocPtr {
@@ -280,9 +742,14 @@ impl Drop for AllocPtr {
                 current = next.next.take();
             }
             let size = self.size();
+            let chunk = self.chunk;
             ((*self.type_info).drop)(self.value());
             ptr::read(&*self.ptr);
-            deallocate(self.ptr as *mut u8, size);
+            if chunk.is_null() {
+                deallocate(self.ptr as *mut u8, size);
+            } else {
+                Chunk::release(chunk);
+            }
         }
     }
 }
@@ -317,6 +784,10 @@ impl GcHeader {
     fn generation(&self) -> Generation {
         unsafe { (*self.type_info).generation }
     }
+
+    fn unbarriered(&self) -> bool {
+        unsafe { (*self.type_info).unbarriered }
+    }
 }
 
 /// A pointer to a garbage collected value.
@@ -582,7 +1053,11 @@ where
     fn traverse(&self, gc: &mut Gc) {
         if !gc.mark(*self) {
             // Continue traversing if this ptr was not already marked
-            (**self).traverse(gc);
+            if gc.marking == Marking::Incremental {
+                gc.gray.push(UntypedPtr::new(*self));
+            } else {
+                (**self).traverse(gc);
+            }
         }
     }
 }
@@ -592,11 +1067,26 @@ impl Gc {
     pub fn new(generation: Generation, memory_limit: usize) -> Gc {
         Gc {
             values: None,
+            old_values: None,
+            unswept_values: None,
+            large_values: None,
+            buffer: None,
             allocated_memory: 0,
+            young_memory: 0,
             collect_limit: 100,
             memory_limit: memory_limit,
             type_infos: FnvMap::default(),
             record_infos: FnvMap::default(),
+            strings: FnvMap::default(),
+            new_strings: Vec::new(),
+            mark_epoch: 0,
+            marking: Marking::Full,
+            incremental_mark: false,
+            gray: Vec::new(),
+            remembered: Vec::new(),
+            unbarriered: Vec::new(),
+            settings: GcSettings::default(),
+            stats: GcStats::default(),
             generation: generation,
         }
     }
@@ -609,16 +1099,32 @@ impl Gc {
         self.memory_limit = memory_limit;
     }
 
+    pub fn settings(&self) -> &GcSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: GcSettings) {
+        self.settings = settings;
+    }
+
+    /// Returns statistics about the collections this garbage collector has run
+    pub fn stats(&self) -> &GcStats {
+        &self.stats
+    }
+
     pub fn generation(&self) -> Generation {
         self.generation
     }
 
     pub fn new_child_gc(&self) -> Gc {
-        Gc::new(self.generation.next(), self.memory_limit)
+        let mut gc = Gc::new(self.generation.next(), self.memory_limit);
+        gc.settings = self.settings.clone();
+        gc
     }
 
     /// Allocates a new object. If the garbage collector has hit the collection limit a collection
-    /// will occur.
+    /// will occur. If the allocation would exceed the memory limit a collection of the entire heap
+    /// is run first so that only reachable values count against the limit.
     ///
     /// Unsafe since `roots` must be able to traverse all accesible `GcPtr` values.
     pub unsafe fn alloc_and_collect<R, D>(&mut self, roots: R, def: D) -> Result<GcPtr<D::Value>>
@@ -651,7 +1157,13 @@ impl Gc {
             }
         }
 
-        self.check_collect(Scope1(roots, &def));
+        let needed = self.allocated_memory
+            .saturating_add(self.allocation_cost(def.size()));
+        if needed >= self.memory_limit {
+            self.collect(Scope1(roots, &def));
+        } else {
+            self.check_collect(Scope1(roots, &def));
+        }
         self.alloc(def)
     }
 
@@ -662,7 +1174,14 @@ impl Gc {
         D::Value: Sized + Any,
     {
         let size = def.size();
-        let needed = self.allocated_memory.saturating_add(size);
+        let mut needed = self.allocated_memory
+            .saturating_add(self.allocation_cost(size));
+        if needed >= self.memory_limit && self.unswept_values.is_some() {
+            // Free the unreachable values which are still waiting to be swept before giving up
+            self.finish_sweep();
+            needed = self.allocated_memory
+                .saturating_add(self.allocation_cost(size));
+        }
         if needed >= self.memory_limit {
             return Err(Error::OutOfMemory {
                 limit: self.memory_limit,
@@ -699,6 +1218,7 @@ impl Gc {
                     .or_insert(Box::new(TypeInfo {
                         drop: drop::<D::Value>,
                         generation: self.generation,
+                        unbarriered: false,
                         fields: fields
                             .iter()
                             .enumerate()
@@ -712,115 +1232,505 @@ impl Gc {
                 Entry::Vacant(entry) => &**entry.insert(Box::new(TypeInfo {
                     drop: drop::<D::Value>,
                     generation: self.generation,
+                    unbarriered: unbarriered_value(ptr::null::<D::Value>()).is_some(),
                     fields: FnvMap::default(),
                     fields_key: Arc::new(Vec::new()),
                 })),
             },
         };
-        let mut ptr = AllocPtr::new::<D::Value>(type_info, size);
-        ptr.next = self.values.take();
-        self.allocated_memory += ptr.size();
+        let large = size >= self.settings.large_object_size;
+        let mut ptr = if large {
+            let ptr = AllocPtr::new::<D::Value>(type_info, size);
+            self.allocated_memory += ptr.size();
+            self.collect_limit = self.collect_limit.saturating_add(ptr.size());
+            ptr
+        } else {
+            self.alloc_header::<D::Value>(type_info, size)
+        };
+        self.young_memory += ptr.size();
+        self.stats.allocations += 1;
+        self.stats.allocated_bytes += ptr.size();
         unsafe {
             let p: *mut D::Value = D::Value::make_ptr(&def, ptr.value());
             let ret: *const D::Value = &*def.initialize(WriteOnly::new(p));
             // Check that the returned pointer is the same as the one we sent as an extra precaution
             // that the pointer was initialized
             assert!(ret == p);
-            self.values = Some(ptr);
+            if (*type_info).unbarriered {
+                self.unbarriered.extend(unbarriered_value(p as *const D::Value));
+            }
+            let values = if large {
+                &mut self.large_values
+            } else {
+                &mut self.values
+            };
+            ptr.next = values.take();
+            *values = Some(ptr);
             GcPtr { ptr: p }
         }
     }
 
+    /// Returns a string equal to `s` which survived an earlier collection, if string
+    /// deduplication is enabled and such a string is known
+    pub fn deduplicated_str(&self, s: &str) -> Option<GcStr> {
+        if !self.deduplicates(s) {
+            return None;
+        }
+        match self.strings.get(&str_hash(s)) {
+            Some(&existing) if &*existing == s => Some(existing),
+            _ => None,
+        }
+    }
+
+    /// Lets later allocations of strings equal to `s` return `s` instead, once `s` has survived
+    /// a collection. `s` must have been allocated by this garbage collector.
+    pub fn add_deduplicated_str(&mut self, s: GcStr) {
+        if self.deduplicates(&s) {
+            self.new_strings.push(s);
+        }
+    }
+
+    fn deduplicates(&self, s: &str) -> bool {
+        match self.settings.deduplicate_strings {
+            Some(max_len) => s.len() <= max_len,
+            None => false,
+        }
+    }
+
+    /// Forgets the strings which were not marked and remembers the new strings which were.
+    /// Must be called after marking and before the values are swept.
+    fn sweep_strings(&mut self, collection: Collection) {
+        let mark_epoch = self.mark_epoch;
+        let marked = |s: &GcStr| s.into_inner().header().mark.get() == mark_epoch;
+        // A minor collection does not mark the old strings
+        self.strings.retain(|_, s| {
+            marked(s) || (collection == Collection::Minor && s.into_inner().header().old.get())
+        });
+        for s in self.new_strings.drain(..) {
+            // Of several equal strings only the first one is remembered. The others stay alive
+            // as long as they are referenced, only later allocations are deduplicated.
+            if marked(&s) {
+                self.strings.entry(str_hash(&s)).or_insert(s);
+            }
+        }
+    }
+
+    /// Returns the size of the allocation buffer which a value of `value_size` bytes is allocated
+    /// in or 0 if it is allocated separately
+    fn buffer_size_for(&self, value_size: usize) -> usize {
+        let alloc_size = align_size(GcHeader::value_offset() + value_size);
+        let buffer_size = self.settings.allocation_buffer_size;
+        // Only let values which take up a small part of a buffer use it to avoid wasting most of
+        // the buffer when a large value does not fit. A new buffer is not started if the buffer
+        // alone would exceed the memory limit.
+        if alloc_size > buffer_size / 8
+            || self.allocated_memory.saturating_add(buffer_size) >= self.memory_limit
+        {
+            0
+        } else {
+            buffer_size
+        }
+    }
+
+    /// The number of bytes which allocating a value of `value_size` bytes adds to
+    /// `allocated_memory`
+    fn allocation_cost(&self, value_size: usize) -> usize {
+        let alloc_size = align_size(GcHeader::value_offset() + value_size);
+        if value_size >= self.settings.large_object_size {
+            return GcHeader::value_offset() + value_size;
+        }
+        match self.buffer {
+            Some(ref buffer) if alloc_size <= self.settings.allocation_buffer_size / 8
+                && buffer.fits(alloc_size) =>
+            {
+                0
+            }
+            _ => match self.buffer_size_for(value_size) {
+                0 => GcHeader::value_offset() + value_size,
+                buffer_size => buffer_size,
+            },
+        }
+    }
+
+    /// Allocates the memory for a value of `value_size` bytes. Small values are bump allocated in
+    /// the allocation buffer, starting a new buffer if the current one is full, while larger
+    /// values get an allocation of their own. A new buffer is counted as allocated in its
+    /// entirety.
+    #[inline]
+    fn alloc_header<T>(&mut self, type_info: *const TypeInfo, value_size: usize) -> AllocPtr {
+        let alloc_size = align_size(GcHeader::value_offset() + value_size);
+        if alloc_size <= self.settings.allocation_buffer_size / 8 {
+            if let Some(ref buffer) = self.buffer {
+                if let Some(ptr) = buffer.bump(alloc_size) {
+                    return unsafe {
+                        AllocPtr::init::<T>(ptr, buffer.chunk, type_info, value_size)
+                    };
+                }
+            }
+        }
+        let buffer_size = self.buffer_size_for(value_size);
+        if buffer_size == 0 {
+            let ptr = AllocPtr::new::<T>(type_info, value_size);
+            self.allocated_memory += ptr.size();
+            return ptr;
+        }
+        // The old chunk is freed along with its last value, or right away if it is empty
+        if let Some(buffer) = self.buffer.take() {
+            let released = buffer.retire();
+            self.allocated_memory -= released;
+            self.stats.freed_bytes += released;
+        }
+        let buffer = AllocationBuffer::new(buffer_size);
+        self.allocated_memory += buffer_size;
+        let ptr = buffer
+            .bump(alloc_size)
+            .expect("Value fits in an empty allocation buffer");
+        let chunk = buffer.chunk;
+        self.buffer = Some(buffer);
+        unsafe { AllocPtr::init::<T>(ptr, chunk, type_info, value_size) }
+    }
+
+    /// Must be called after a pointer has been written into `value` (which must have been
+    /// allocated by this garbage collector) in any other way than initializing it with a
+    /// `DataDef`.
+    pub fn write_barrier<T: ?Sized + Traverseable>(&mut self, value: GcPtr<T>) {
+        let header = value.header();
+        if header.old.get() && !header.remembered.get() {
+            header.remembered.set(true);
+            self.remembered.push(UntypedPtr::new(value));
+        }
+        if self.incremental_mark && header.mark.get() == self.mark_epoch {
+            // The fields of `value` may already have been traversed so traverse them again to
+            // find the value which was written
+            self.gray.push(UntypedPtr::new(value));
+        }
+    }
+
+    /// Runs a collection if the heap has grown enough, or runs the next step of an incremental
+    /// mark or a batched sweep. Returns true if a collection was finished.
     pub unsafe fn check_collect<R>(&mut self, roots: R) -> bool
     where
         R: Traverseable + CollectScope,
     {
-        if self.allocated_memory >= self.collect_limit {
-            self.collect(roots);
+        if self.incremental_mark {
+            if self.gray.is_empty() {
+                let batched = self.settings.sweep_batch_size.is_some();
+                self.collect_(roots, Collection::Major, batched);
+                true
+            } else {
+                self.mark_step();
+                false
+            }
+        } else if self.unswept_values.is_some() {
+            self.sweep_batch();
+            false
+        } else if self.allocated_memory >= self.collect_limit {
+            if self.settings.incremental_mark.is_some() {
+                self.start_incremental_mark(roots);
+                false
+            } else {
+                let batched = self.settings.sweep_batch_size.is_some();
+                self.collect_(roots, Collection::Major, batched);
+                true
+            }
+        } else if self.young_memory >= self.settings.nursery_size {
+            self.collect_(roots, Collection::Minor, false);
             true
         } else {
             false
         }
     }
 
-    /// Does a mark and sweep collection by walking from `roots`. This function is unsafe since
-    /// roots need to cover all reachable object.
+    /// Does a mark and sweep collection of the entire heap by walking from `roots`. This function
+    /// is unsafe since roots need to cover all reachable object.
     pub unsafe fn collect<R>(&mut self, roots: R)
     where
         R: Traverseable + CollectScope,
     {
-        info!("Start collect {:?}", self.generation);
+        // The whole heap is marked again so an unfinished incremental mark can be dropped
+        self.abandon_incremental_mark();
+        self.collect_(roots, Collection::Major, false)
+    }
+
+    unsafe fn collect_<R>(&mut self, roots: R, collection: Collection, batched: bool)
+    where
+        R: Traverseable + CollectScope,
+    {
+        info!("Start {:?} collect {:?}", collection, self.generation);
+        let mut pause = Duration::default();
+        if self.incremental_mark {
+            debug_assert!(collection == Collection::Major);
+            pause = timed(|| self.finish_incremental_mark());
+        } else {
+            // The unswept values must be swept before their marks are invalidated by a new mark
+            self.finish_sweep();
+            // `scope` marks the values of any child garbage collectors so the new mark must be
+            // set before it is called
+            self.mark_epoch = next_mark_epoch();
+        }
+        self.marking = match collection {
+            Collection::Minor => Marking::Young,
+            Collection::Major => Marking::Full,
+        };
         roots.scope(self, |self_| {
-            roots.traverse(self_);
-            self_.sweep();
-            self_.collect_limit = 2 * self_.allocated_memory;
-        })
+            let pause = pause + timed(|| {
+                roots.traverse(self_);
+                if collection == Collection::Minor {
+                    self_.traverse_old_roots();
+                }
+                self_.end_mark();
+
+                let old_values = match collection {
+                    Collection::Minor => None,
+                    Collection::Major => self_.old_values.take(),
+                };
+                self_.sweep_young(collection);
+                match collection {
+                    Collection::Minor => self_.stats.minor_collections += 1,
+                    Collection::Major => {
+                        self_.stats.major_collections += 1;
+                        self_.unswept_values = old_values;
+                        // A batched collection leaves the old generation to `sweep_batch`
+                        let count = if batched { 0 } else { usize::MAX };
+                        self_.sweep_unswept(count);
+                    }
+                }
+            });
+            self_.stats.record_pause(pause);
+        });
+        self.marking = Marking::Full;
+    }
+
+    /// Starts an incremental mark by marking the values which `roots` refer to directly. Their
+    /// fields are traversed by the following calls to `mark_step`.
+    unsafe fn start_incremental_mark<R>(&mut self, roots: R)
+    where
+        R: Traverseable,
+    {
+        info!("Start incremental mark {:?}", self.generation);
+        self.finish_sweep();
+        self.mark_epoch = next_mark_epoch();
+        self.incremental_mark = true;
+        let pause = timed(|| {
+            self.marking = Marking::Incremental;
+            roots.traverse(self);
+            self.marking = Marking::Full;
+        });
+        self.stats.record_pause(pause);
+    }
+
+    /// Traverses the fields of the next `GcSettings::incremental_mark` gray values
+    unsafe fn mark_step(&mut self) {
+        let count = self.settings.incremental_mark.unwrap_or(usize::MAX);
+        let pause = timed(|| {
+            self.marking = Marking::Incremental;
+            for _ in 0..count {
+                match self.gray.pop() {
+                    Some(value) => value.traverse(self),
+                    None => break,
+                }
+            }
+            self.marking = Marking::Full;
+        });
+        self.stats.record_pause(pause);
+    }
+
+    /// Traverses what is left of an incremental mark. The roots are traversed again by
+    /// `collect_` afterwards, along with the child generations.
+    ///
+    /// Must be called before `CollectScope::scope` as traversing a thread locks it.
+    unsafe fn finish_incremental_mark(&mut self) {
+        self.marking = Marking::Full;
+        // Values without a write barrier may have been written to since they were traversed
+        for i in 0..self.unbarriered.len() {
+            let value = self.unbarriered[i];
+            if value.header().mark.get() == self.mark_epoch {
+                value.traverse(self);
+            }
+        }
+        while let Some(value) = self.gray.pop() {
+            value.traverse(self);
+        }
+        self.incremental_mark = false;
+    }
+
+    fn abandon_incremental_mark(&mut self) {
+        self.incremental_mark = false;
+        self.gray.clear();
+    }
+
+    /// Traverses the values of the old generation which may refer to young values, for a minor
+    /// collection
+    unsafe fn traverse_old_roots(&mut self) {
+        for i in 0..self.remembered.len() {
+            let value = self.remembered[i];
+            value.traverse(self);
+        }
+        for i in 0..self.unbarriered.len() {
+            let value = self.unbarriered[i];
+            let header = value.header();
+            // Values which have been marked were traversed already, such as the threads which
+            // `CollectScope::scope` has locked
+            if header.old.get() && header.mark.get() != self.mark_epoch {
+                header.mark.set(self.mark_epoch);
+                value.traverse(self);
+            }
+        }
+    }
+
+    /// Called once every reachable value has been marked, before anything is swept
+    fn end_mark(&mut self) {
+        // Every young value which survives is moved to the old generation so no old value can
+        // refer to a young value after the sweep
+        for value in self.remembered.drain(..) {
+            value.header().remembered.set(false);
+        }
+        // Even a minor collection marks all of the old unbarriered values
+        let mark_epoch = self.mark_epoch;
+        self.unbarriered
+            .retain(|value| value.header().mark.get() == mark_epoch);
     }
 
     /// Marks the GcPtr
     /// Returns true if the pointer was already marked
     pub fn mark<T: ?Sized>(&mut self, value: GcPtr<T>) -> bool {
         let header = value.header();
+        let generation = header.generation();
         // We only need to mark and traverse values from this garbage collectors generation
-        if header.generation().is_parent_of(self.generation()) || header.marked.get() {
+        if generation.is_parent_of(self.generation()) || header.mark.get() == self.mark_epoch {
+            return true;
+        }
+        let own_generation = !self.generation.is_parent_of(generation);
+        let skip = match self.marking {
+            Marking::Full => false,
+            // Unbarriered values are traversed in every collection (see `traverse_old_roots`)
+            Marking::Young => own_generation && header.old.get() && !header.unbarriered(),
+            Marking::Incremental => !own_generation,
+        };
+        if skip {
             true
         } else {
-            header.marked.set(true);
+            header.mark.set(self.mark_epoch);
             false
         }
     }
 
-    /// Clears out any unmarked pointers and resets marked pointers.
+    /// Clears out any pointers which were not marked by the last collection of `parent`. Used for
+    /// child garbage collectors whose values are marked by the collection of their parent.
+    /// `finish_sweep` must be called before `parent` starts marking.
     ///
     /// Unsafe as it is up to the caller to make sure that all reachable pointers have been marked
-    pub unsafe fn sweep(&mut self) {
-        fn moving<T>(t: T) -> T {
-            t
+    pub unsafe fn sweep_marked_by(&mut self, parent: &Gc) {
+        debug_assert!(self.unswept_values.is_none());
+        // The mark of `parent` replaces any mark this garbage collector had started
+        self.abandon_incremental_mark();
+        self.mark_epoch = parent.mark_epoch;
+        self.end_mark();
+        self.unswept_values = self.old_values.take();
+        self.sweep_young(Collection::Major);
+        self.finish_sweep();
+    }
+
+    /// Moves the reachable values of the young generation to the old generation and frees the
+    /// rest. The strings and the large object space are swept along with the young generation,
+    /// skipping their old values in a minor collection.
+    unsafe fn sweep_young(&mut self, collection: Collection) {
+        self.sweep_strings(collection);
+
+        let large_values = self.large_values.take();
+        self.large_values = self.sweep_large(large_values, collection);
+
+        let values = self.values.take();
+        let (rest, promoted) = self.sweep_into_old(values, usize::MAX);
+        debug_assert!(rest.is_none());
+
+        self.young_memory = 0;
+        self.stats.promoted_bytes += promoted;
+    }
+
+    /// Sweeps the values which are left from a batched collection
+    pub fn finish_sweep(&mut self) {
+        if self.unswept_values.is_some() {
+            self.sweep_unswept(usize::MAX);
         }
+    }
 
-        let mut count = 0;
-        let mut free_count = 0;
+    /// Sweeps the next `sweep_batch_size` values which are left from a batched collection
+    fn sweep_batch(&mut self) {
+        let batch_size = self.settings.sweep_batch_size.unwrap_or(usize::MAX);
+        let pause = timed(|| self.sweep_unswept(batch_size));
+        self.stats.record_pause(pause);
+    }
 
-        let mut first = self.values.take();
-        {
-            // Pointer to the current pointer (if it exists)
-            let mut maybe_header = &mut first;
-            loop {
-                let mut free = false;
-                let mut replaced_next = None;
-                match *maybe_header {
-                    Some(ref mut header) => {
-                        // If the current pointer is not marked we take the rest of the list and
-                        // move it to `replaced_next`
-                        if !header.marked.get() {
-                            replaced_next = header.next.take();
-                            free = true;
-                        } else {
-                            header.marked.set(false);
-                        }
-                    }
-                    // Reached the end of the list
-                    None => break,
-                }
-                count += 1;
-                if free {
-                    free_count += 1;
-                    // Free the current pointer
-                    self.free(maybe_header.take());
-                    *maybe_header = replaced_next;
-                } else {
-                    // Just move to the next pointer
-                    maybe_header = &mut moving(maybe_header).as_mut().unwrap().next;
-                }
+    fn sweep_unswept(&mut self, count: usize) {
+        let unswept = self.unswept_values.take();
+        self.unswept_values = self.sweep_into_old(unswept, count).0;
+        if self.unswept_values.is_none() {
+            // The major collection is done so we know how much memory survived it
+            self.collect_limit = self.settings.heap_growth.next_limit(self.allocated_memory);
+        }
+    }
+
+    /// Sweeps up to `count` values from the front of `values`, moving the values which were marked
+    /// by the current collection to the old generation and freeing the others. Returns the values
+    /// which were not swept and the number of bytes of the values which were moved.
+    fn sweep_into_old(
+        &mut self,
+        mut values: Option<AllocPtr>,
+        count: usize,
+    ) -> (Option<AllocPtr>, usize) {
+        let mut swept = 0;
+        let mut free_count = 0;
+        let mut moved_bytes = 0;
+        while swept < count {
+            let mut ptr = match values {
+                Some(ptr) => ptr,
+                None => break,
+            };
+            values = ptr.next.take();
+            if ptr.mark.get() == self.mark_epoch {
+                moved_bytes += ptr.size();
+                ptr.old.set(true);
+                ptr.next = self.old_values.take();
+                self.old_values = Some(ptr);
+            } else {
+                free_count += 1;
+                self.free(Some(ptr));
+            }
+            swept += 1;
+        }
+        info!("GC: Freed {} / Traversed {}", free_count, swept);
+        (values, moved_bytes)
+    }
+
+    /// Frees the values of the large object space which were not marked by the current collection.
+    /// A minor collection keeps the old values without looking at their marks.
+    fn sweep_large(
+        &mut self,
+        mut values: Option<AllocPtr>,
+        collection: Collection,
+    ) -> Option<AllocPtr> {
+        let mut kept = None;
+        while let Some(mut ptr) = values {
+            values = ptr.next.take();
+            if ptr.mark.get() == self.mark_epoch
+                || (collection == Collection::Minor && ptr.old.get())
+            {
+                ptr.old.set(true);
+                ptr.next = kept;
+                kept = Some(ptr);
+            } else {
+                self.free(Some(ptr));
             }
         }
-        info!("GC: Freed {} / Traversed {}", free_count, count);
-        self.values = first;
+        kept
     }
 
     fn free(&mut self, header: Option<AllocPtr>) {
         if let Some(ref ptr) = header {
-            self.allocated_memory -= ptr.size();
+            let released = ptr.released_memory();
+            self.allocated_memory -= released;
+            self.stats.freed_bytes += released;
         }
         debug!("FREE: {:?}", header);
         drop(header);
@@ -836,6 +1746,8 @@ mod tests {
     use std::cell::Cell;
     use std::usize;
 
+    use value::GcStr;
+
     use self::Value::*;
 
     impl CollectScope for () {
@@ -857,7 +1769,12 @@ mod tests {
     }
 
     fn object_count(gc: &Gc) -> usize {
-        let mut header: &GcHeader = match gc.values {
+        list_count(&gc.values) + list_count(&gc.old_values) + list_count(&gc.unswept_values)
+            + list_count(&gc.large_values)
+    }
+
+    fn list_count(values: &Option<AllocPtr>) -> usize {
+        let mut header: &GcHeader = match *values {
             Some(ref x) => &**x,
             None => return 0,
         };
@@ -960,6 +1877,356 @@ mod tests {
         assert_eq!(object_count(&gc), 0);
     }
 
+    fn value_alloc_size() -> usize {
+        align_size(GcHeader::value_offset() + mem::size_of::<Vec<Value>>())
+    }
+
+    #[test]
+    fn small_values_share_an_allocation_buffer() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        let first = gc.alloc(Def { elems: &[Int(1)] }).unwrap();
+        let second = gc.alloc(Def { elems: &[Int(2)] }).unwrap();
+        assert!(!first.header().chunk.is_null());
+        assert_eq!(first.header().chunk, second.header().chunk);
+        assert_eq!(
+            second.header() as *const GcHeader as usize - first.header() as *const GcHeader as usize,
+            value_alloc_size()
+        );
+        assert_eq!(gc.allocated_memory, GcSettings::default().allocation_buffer_size);
+    }
+
+    #[test]
+    fn full_allocation_buffer_is_replaced() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        let buffer_size = 8 * value_alloc_size();
+        gc.set_settings(GcSettings {
+            allocation_buffer_size: buffer_size,
+            ..GcSettings::default()
+        });
+
+        let mut stack: Vec<Value> = Vec::new();
+        for i in 0..9 {
+            stack.push(new_data(gc.alloc(Def { elems: &[Int(i)] }).unwrap()));
+        }
+        assert_eq!(gc.allocated_memory, 2 * buffer_size);
+
+        // Freeing the values of the retired buffer frees the buffer itself
+        stack.drain(..8);
+        unsafe {
+            gc.collect(&mut *stack);
+        }
+        assert_eq!(object_count(&gc), 1);
+        assert_eq!(gc.allocated_memory, buffer_size);
+        match stack[0] {
+            Data(ref data) => assert_eq!(data.fields[0], Int(8)),
+            _ => ice!(),
+        }
+    }
+
+    #[test]
+    fn surviving_values_keep_their_buffers_counted() {
+        let buffer_size = 8 * value_alloc_size();
+        // Room for three buffers but not for another buffer or a separately allocated value
+        let mut gc: Gc = Gc::new(Generation::default(), 3 * buffer_size + value_alloc_size() / 2);
+        gc.set_settings(GcSettings {
+            allocation_buffer_size: buffer_size,
+            ..GcSettings::default()
+        });
+
+        // Keep one value alive in each of three buffers
+        let mut stack: Vec<Value> = Vec::new();
+        for i in 0..17 {
+            let value = new_data(gc.alloc(Def { elems: &[Int(i)] }).unwrap());
+            if i % 8 == 0 {
+                stack.push(value);
+            }
+        }
+        unsafe {
+            gc.collect(&mut *stack);
+        }
+        assert_eq!(object_count(&gc), 3);
+        assert_eq!(gc.allocated_memory, 3 * buffer_size);
+
+        // The current buffer still has room
+        for i in 0..7 {
+            gc.alloc(Def { elems: &[Int(i)] }).unwrap();
+        }
+        assert_eq!(gc.allocated_memory, 3 * buffer_size);
+        // Neither a new buffer nor a separate allocation fits in the limit, even though only three
+        // values are alive
+        match gc.alloc(Def { elems: &[Int(0)] }) {
+            Err(Error::OutOfMemory { .. }) => (),
+            result => panic!("Expected OutOfMemory, got {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn disabled_allocation_buffer() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        gc.set_settings(GcSettings {
+            allocation_buffer_size: 0,
+            ..GcSettings::default()
+        });
+        let ptr = gc.alloc(Def { elems: &[Int(1)] }).unwrap();
+        assert!(ptr.header().chunk.is_null());
+        assert_eq!(
+            gc.allocated_memory,
+            GcHeader::value_offset() + mem::size_of::<Vec<Value>>()
+        );
+    }
+
+    #[test]
+    fn large_values_are_allocated_in_the_large_object_space() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        gc.set_settings(GcSettings {
+            large_object_size: mem::size_of::<Vec<Value>>(),
+            ..GcSettings::default()
+        });
+
+        let collect_limit = gc.collect_limit;
+        let mut stack: Vec<Value> = Vec::new();
+        let ptr = gc.alloc(Def { elems: &[Int(1)] }).unwrap();
+        stack.push(new_data(ptr));
+        assert!(ptr.header().chunk.is_null());
+        assert!(gc.values.is_none());
+        assert!(gc.large_values.is_some());
+        assert_eq!(
+            gc.collect_limit,
+            collect_limit + GcHeader::value_offset() + mem::size_of::<Vec<Value>>()
+        );
+
+        stack.clear();
+        unsafe {
+            gc.collect(&mut *stack);
+        }
+        assert!(gc.large_values.is_none());
+        assert_eq!(gc.allocated_memory, 0);
+    }
+
+    #[test]
+    fn minor_collection_promotes_reachable_values() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        gc.set_settings(GcSettings {
+            allocation_buffer_size: 0,
+            nursery_size: 0,
+            ..GcSettings::default()
+        });
+        gc.collect_limit = usize::MAX;
+
+        let mut stack: Vec<Value> = Vec::new();
+        stack.push(new_data(gc.alloc(Def { elems: &[Int(1)] }).unwrap()));
+        gc.alloc(Def { elems: &[Int(2)] }).unwrap();
+        assert!(unsafe { gc.check_collect(&mut *stack) });
+
+        let value_size = GcHeader::value_offset() + mem::size_of::<Vec<Value>>();
+        assert_eq!(list_count(&gc.values), 0);
+        assert_eq!(list_count(&gc.old_values), 1);
+        assert_eq!(gc.stats().minor_collections, 1);
+        assert_eq!(gc.stats().major_collections, 0);
+        assert_eq!(gc.stats().promoted_bytes, value_size);
+        assert_eq!(gc.stats().freed_bytes, value_size);
+    }
+
+    #[test]
+    fn batched_major_collection() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        gc.set_settings(GcSettings {
+            allocation_buffer_size: 0,
+            sweep_batch_size: Some(1),
+            ..GcSettings::default()
+        });
+
+        let mut stack: Vec<Value> = Vec::new();
+        for i in 0..3 {
+            stack.push(new_data(gc.alloc(Def { elems: &[Int(i)] }).unwrap()));
+        }
+        unsafe {
+            gc.collect(&mut *stack);
+        }
+        assert_eq!(list_count(&gc.old_values), 3);
+
+        stack.clear();
+        gc.collect_limit = 0;
+        assert!(unsafe { gc.check_collect(&mut *stack) });
+        assert_eq!(object_count(&gc), 3);
+        for i in (0..3).rev() {
+            assert!(!unsafe { gc.check_collect(&mut *stack) });
+            assert_eq!(object_count(&gc), i);
+        }
+        assert_eq!(gc.allocated_memory(), 0);
+        assert_eq!(gc.stats().major_collections, 2);
+    }
+
+    /// A value which counts how many times it has been traversed
+    struct Node {
+        traversals: Rc<Cell<usize>>,
+        child: Cell<Option<GcPtr<Node>>>,
+    }
+
+    impl Traverseable for Node {
+        fn traverse(&self, gc: &mut Gc) {
+            self.traversals.set(self.traversals.get() + 1);
+            if let Some(child) = self.child.get() {
+                child.traverse(gc);
+            }
+        }
+    }
+
+    fn alloc_node(gc: &mut Gc) -> (GcPtr<Node>, Rc<Cell<usize>>) {
+        let traversals = Rc::new(Cell::new(0));
+        let node = gc.alloc(Move(Node {
+            traversals: traversals.clone(),
+            child: Cell::new(None),
+        })).unwrap();
+        (node, traversals)
+    }
+
+    #[test]
+    fn minor_collection_does_not_traverse_old_values() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        gc.set_settings(GcSettings {
+            allocation_buffer_size: 0,
+            nursery_size: 0,
+            heap_growth: HeapGrowth::Additive(usize::MAX),
+            ..GcSettings::default()
+        });
+
+        let (old, old_traversals) = alloc_node(&mut gc);
+        let mut roots = vec![old];
+        unsafe {
+            gc.collect(&mut *roots);
+        }
+        assert_eq!(old_traversals.get(), 1);
+
+        let (young, young_traversals) = alloc_node(&mut gc);
+        roots.push(young);
+        assert!(unsafe { gc.check_collect(&mut *roots) });
+        assert_eq!(gc.stats().minor_collections, 1);
+        assert_eq!(old_traversals.get(), 1);
+        assert_eq!(young_traversals.get(), 1);
+        assert_eq!(list_count(&gc.old_values), 2);
+    }
+
+    #[test]
+    fn minor_collection_traverses_remembered_values() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        gc.set_settings(GcSettings {
+            allocation_buffer_size: 0,
+            nursery_size: 0,
+            heap_growth: HeapGrowth::Additive(usize::MAX),
+            ..GcSettings::default()
+        });
+
+        let (old, old_traversals) = alloc_node(&mut gc);
+        let mut roots = vec![old];
+        unsafe {
+            gc.collect(&mut *roots);
+        }
+
+        // The young value is only reachable through the old value
+        let (young, young_traversals) = alloc_node(&mut gc);
+        old.child.set(Some(young));
+        gc.write_barrier(old);
+        assert!(unsafe { gc.check_collect(&mut *roots) });
+        assert_eq!(old_traversals.get(), 2);
+        assert_eq!(young_traversals.get(), 1);
+        assert_eq!(list_count(&gc.values), 0);
+        assert_eq!(list_count(&gc.old_values), 2);
+
+        // Both values are old now so neither is traversed
+        gc.alloc(Def { elems: &[Int(1)] }).unwrap();
+        assert!(unsafe { gc.check_collect(&mut *roots) });
+        assert_eq!(old_traversals.get(), 2);
+        assert_eq!(young_traversals.get(), 1);
+        assert_eq!(list_count(&gc.old_values), 2);
+        assert_eq!(gc.stats().minor_collections, 2);
+    }
+
+    #[test]
+    fn incremental_mark() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        gc.set_settings(GcSettings {
+            allocation_buffer_size: 0,
+            incremental_mark: Some(1),
+            ..GcSettings::default()
+        });
+
+        let (root, root_traversals) = alloc_node(&mut gc);
+        let (child, _) = alloc_node(&mut gc);
+        root.child.set(Some(child));
+        let mut roots = vec![root];
+        gc.alloc(Def { elems: &[Int(1)] }).unwrap();
+
+        gc.collect_limit = 0;
+        // Marks the roots, then traverses the root
+        assert!(!unsafe { gc.check_collect(&mut *roots) });
+        assert!(!unsafe { gc.check_collect(&mut *roots) });
+        assert_eq!(root_traversals.get(), 1);
+
+        // Replace the child of the root, which has already been traversed, with a new value
+        let (new_child, new_child_traversals) = alloc_node(&mut gc);
+        root.child.set(Some(new_child));
+        gc.write_barrier(root);
+
+        let mut steps = 0;
+        while !unsafe { gc.check_collect(&mut *roots) } {
+            steps += 1;
+            assert!(steps < 10);
+        }
+        assert_eq!(gc.stats().major_collections, 1);
+        assert_eq!(new_child_traversals.get(), 1);
+        // The old child was marked before it was replaced so only the unreachable value was freed
+        assert_eq!(object_count(&gc), 3);
+
+        unsafe {
+            gc.collect(&mut *roots);
+        }
+        assert_eq!(object_count(&gc), 2);
+    }
+
+    fn alloc_str(gc: &mut Gc, s: &str) -> GcStr {
+        if let Some(existing) = gc.deduplicated_str(s) {
+            return existing;
+        }
+        let gc_str = unsafe { GcStr::from_utf8_unchecked(gc.alloc(s.as_bytes()).unwrap()) };
+        gc.add_deduplicated_str(gc_str);
+        gc_str
+    }
+
+    #[test]
+    fn deduplicate_surviving_strings() {
+        let mut gc: Gc = Gc::new(Generation::default(), usize::MAX);
+        gc.set_settings(GcSettings {
+            deduplicate_strings: Some(5),
+            ..GcSettings::default()
+        });
+
+        let hello = alloc_str(&mut gc, "hello");
+        alloc_str(&mut gc, "world");
+        let long = alloc_str(&mut gc, "hello world");
+        // Strings are only deduplicated once they have survived a collection
+        let hello2 = alloc_str(&mut gc, "hello");
+        assert!(hello2.into_inner().ptr != hello.into_inner().ptr);
+
+        let mut roots = vec![hello, hello2, long];
+        unsafe {
+            gc.collect(&mut *roots);
+        }
+        // One of the surviving strings is reused, the unreachable one is forgotten
+        let hello3 = alloc_str(&mut gc, "hello");
+        assert_eq!(hello3.into_inner().ptr, hello.into_inner().ptr);
+        assert!(gc.deduplicated_str("world").is_none());
+        // Only strings of at most 5 bytes are deduplicated
+        assert!(alloc_str(&mut gc, "hello world").into_inner().ptr != long.into_inner().ptr);
+
+        roots.clear();
+        unsafe {
+            gc.collect(&mut *roots);
+        }
+        assert!(gc.strings.is_empty());
+        assert!(gc.deduplicated_str("hello").is_none());
+    }
+
     pub struct Dropable {
         dropped: Rc<Cell<bool>>,
     }
@@ -0,0 +1,524 @@
+//! Just-in-time compilation of hot functions to native code with Cranelift.
+//!
+//! Every bytecode function counts how many times it is called. The `HOT_CALLS`th call compiles the
+//! bytecode of the function to native code which is then used for that and every later call of
+//! the function. Only a subset of the instructions can be compiled: functions which take `Int`
+//! arguments and only push integers and variables, do integer arithmetic and comparisons, test
+//! tags, branch forward and tail call themselves. A function which uses any other instruction
+//! (other calls, allocations, floats, ...) is marked as unsupported and always runs in the
+//! interpreter. Compiled code is also only used when each argument is an `Int`, calls with other
+//! arguments fall back to the interpreter.
+//!
+//! A self tail call is compiled as a jump back to the first instruction, which is the only
+//! backwards branch that is compiled. Before each such jump the compiled code checks if
+//! `Thread::interrupt` has been called and if it has, it gives up and lets the interpreter run
+//! the call instead, which then reports the interruption. While fuel, hooks or `profile`
+//! counting are active compiled code is not used at all.
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, Once, ONCE_INIT};
+
+use cranelift_codegen::Context as CodegenContext;
+use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::types::{I64, I8};
+use cranelift_codegen::ir::{AbiParam, Ebb, InstBuilder, MemFlags};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_module::{Linkage, Module};
+use cranelift_simplejit::{SimpleJITBackend, SimpleJITBuilder};
+
+use types::{VmIndex, VmInt, VmTag};
+use types::Instruction::*;
+use value::{BytecodeFunction, ClosureData, Value, ValueRepr};
+
+/// How many times a function is called before it is compiled
+pub const HOT_CALLS: usize = 1000;
+
+/// Functions with more arguments than this are never compiled
+const MAX_ARGS: usize = 8;
+
+const UNCOMPILED: usize = 0;
+const UNSUPPORTED: usize = 1;
+
+/// The signature of compiled functions. The arguments are passed as a pointer to an array of
+/// `MAX_ARGS` integers, followed by the interrupt flag of the thread and a pointer which the
+/// result is written to. Returns `0` if the call were interrupted before it finished.
+type CompiledFn = extern "C" fn(*const VmInt, *const AtomicBool, *mut VmInt) -> u8;
+
+/// The compilation state of a `BytecodeFunction`
+#[derive(Default)]
+pub(crate) struct FunctionJit {
+    /// How many times the function has been called before it were compiled
+    calls: AtomicUsize,
+    /// `UNCOMPILED`, `UNSUPPORTED` or the address of the compiled `CompiledFn`
+    code: AtomicUsize,
+    /// If the compiled function returns a tag instead of an integer
+    returns_tag: AtomicBool,
+    /// `0` if the compiled function does not tail call itself, otherwise one more than the index
+    /// of the upvariable which holds the closure that is called
+    self_upvar: AtomicUsize,
+}
+
+impl fmt::Debug for FunctionJit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match self.code.load(Ordering::Relaxed) {
+            UNCOMPILED => "uncompiled",
+            UNSUPPORTED => "unsupported",
+            _ => "compiled",
+        };
+        write!(f, "FunctionJit({})", state)
+    }
+}
+
+// The compilation state does not affect what a function does
+impl PartialEq for FunctionJit {
+    fn eq(&self, _: &FunctionJit) -> bool {
+        true
+    }
+}
+
+/// Calls the compiled code of `closure` with `args` if it has been compiled (compiling it if
+/// this call makes it hot). Returns `None` if the function must be run by the interpreter.
+pub(crate) fn call(
+    closure: &ClosureData,
+    args: &[Value],
+    interrupt: &AtomicBool,
+) -> Option<ValueRepr> {
+    let function = &*closure.function;
+    let jit = &function.jit;
+    let mut code = jit.code.load(Ordering::Acquire);
+    if code == UNCOMPILED {
+        if jit.calls.fetch_add(1, Ordering::Relaxed) + 1 < HOT_CALLS {
+            return None;
+        }
+        code = compile(closure);
+    }
+    if code == UNSUPPORTED || args.len() != function.args as usize {
+        return None;
+    }
+    // The compiled tail calls assume that the upvariable holds this closure, which is only known
+    // to be true for the closure that the function were compiled with
+    match jit.self_upvar.load(Ordering::Relaxed) {
+        0 => (),
+        upvar => match closure.upvars.get(upvar - 1) {
+            Some(upvar) if refers_to(upvar, closure) => (),
+            _ => return None,
+        },
+    }
+
+    let mut ints = [0; MAX_ARGS];
+    for (int, arg) in ints.iter_mut().zip(args) {
+        match arg.get_repr() {
+            ValueRepr::Int(i) => *int = i,
+            _ => return None,
+        }
+    }
+    let compiled: CompiledFn = unsafe { mem::transmute(code) };
+    let mut result = 0;
+    if compiled(ints.as_ptr(), interrupt, &mut result) == 0 {
+        return None;
+    }
+    Some(if jit.returns_tag.load(Ordering::Relaxed) {
+        ValueRepr::Tag(result as VmTag)
+    } else {
+        ValueRepr::Int(result)
+    })
+}
+
+/// Returns the names of the functions which have been compiled, in the order they were compiled
+pub fn compiled_functions() -> Vec<String> {
+    jit().lock().unwrap().compiled.clone()
+}
+
+fn refers_to(value: &Value, closure: &ClosureData) -> bool {
+    match value.get_repr() {
+        ValueRepr::Closure(other) => &*other as *const ClosureData == closure as *const _,
+        _ => false,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Int,
+    Tag,
+    /// The closure being compiled, which can only be tail called
+    Function,
+}
+
+/// The contents of the stack before each reachable instruction
+struct Analysis {
+    /// The kind of each value on the stack before the instruction at the same index, the last
+    /// element is the stack when the function returns
+    stacks: Vec<Option<Vec<Kind>>>,
+    /// Which instructions are the target of a jump
+    targets: Vec<bool>,
+    /// How many values the stack holds at most
+    max_depth: usize,
+    /// If the function tail calls itself
+    calls_self: bool,
+}
+
+/// Checks that every instruction of `function` can be compiled and what the stack contains before
+/// each of them. `self_upvar` is the upvariable which holds the function itself, if any.
+fn analyze(function: &BytecodeFunction, self_upvar: Option<VmIndex>) -> Option<Analysis> {
+    if function.args as usize > MAX_ARGS || mem::size_of::<VmInt>() != 8 {
+        return None;
+    }
+    let instructions = &function.instructions;
+    let mut stacks = vec![None; instructions.len() + 1];
+    let mut targets = vec![false; instructions.len() + 1];
+    let mut max_depth = function.args as usize;
+    let mut calls_self = false;
+
+    let mut pending = vec![(0, vec![Kind::Int; function.args as usize])];
+    while let Some((index, stack)) = pending.pop() {
+        match stacks[index] {
+            Some(ref existing) if *existing == stack => continue,
+            // The stack must look the same no matter how the instruction were reached
+            Some(_) => return None,
+            None => (),
+        }
+        max_depth = ::std::cmp::max(max_depth, stack.len());
+        stacks[index] = Some(stack.clone());
+
+        let instruction = match instructions.get(index) {
+            Some(&instruction) => instruction,
+            None => match stack.last() {
+                Some(&Kind::Int) | Some(&Kind::Tag) => continue,
+                _ => return None,
+            },
+        };
+        let mut stack = stack;
+        let mut next = Some(index + 1);
+        match instruction {
+            Push(i) => {
+                let kind = *stack.get(i as usize)?;
+                stack.push(kind);
+            }
+            PushInt(_) => stack.push(Kind::Int),
+            PushUpVar(i) if Some(i) == self_upvar => stack.push(Kind::Function),
+            Construct { args: 0, .. } => stack.push(Kind::Tag),
+            AddInt | SubtractInt | MultiplyInt | IntLT | IntEQ => {
+                if stack.pop()? != Kind::Int || stack.pop()? != Kind::Int {
+                    return None;
+                }
+                stack.push(match instruction {
+                    IntLT | IntEQ => Kind::Tag,
+                    _ => Kind::Int,
+                });
+            }
+            TestTag(_) => {
+                if *stack.last()? != Kind::Tag {
+                    return None;
+                }
+                stack.push(Kind::Tag);
+            }
+            Jump(target) | CJump(target) => {
+                let target = target as usize;
+                if target <= index || target > instructions.len() {
+                    return None;
+                }
+                if let CJump(_) = instruction {
+                    if stack.pop()? != Kind::Tag {
+                        return None;
+                    }
+                } else {
+                    next = None;
+                }
+                targets[target] = true;
+                pending.push((target, stack.clone()));
+            }
+            TailCall(args) => {
+                // Only calls to the function itself are supported, which continue from the first
+                // instruction with the new arguments
+                let function_index = stack.len().checked_sub(args as usize + 1)?;
+                if args != function.args || stack[function_index] != Kind::Function {
+                    return None;
+                }
+                targets[0] = true;
+                calls_self = true;
+                pending.push((0, stack[function_index + 1..].to_vec()));
+                next = None;
+            }
+            Pop(n) => {
+                let len = stack.len().checked_sub(n as usize)?;
+                stack.truncate(len);
+            }
+            Slide(n) => {
+                let top = stack.pop()?;
+                let len = stack.len().checked_sub(n as usize)?;
+                stack.truncate(len);
+                stack.push(top);
+            }
+            _ => return None,
+        }
+        if let Some(next) = next {
+            pending.push((next, stack));
+        }
+    }
+    Some(Analysis {
+        stacks,
+        targets,
+        max_depth,
+        calls_self,
+    })
+}
+
+struct Jit {
+    module: Module<SimpleJITBackend>,
+    context: CodegenContext,
+    builder_context: FunctionBuilderContext<Variable>,
+    compiled: Vec<String>,
+}
+
+// The module is only used while the mutex is locked and the code it compiled is never freed
+unsafe impl Send for Jit {}
+
+static INIT: Once = ONCE_INIT;
+static mut JIT: *const Mutex<Jit> = 0 as *const _;
+
+fn jit() -> &'static Mutex<Jit> {
+    unsafe {
+        INIT.call_once(|| {
+            let module = Module::new(SimpleJITBuilder::new());
+            let jit = Jit {
+                context: module.make_context(),
+                module,
+                builder_context: FunctionBuilderContext::new(),
+                compiled: Vec::new(),
+            };
+            JIT = Box::into_raw(Box::new(Mutex::new(jit)));
+        });
+        &*JIT
+    }
+}
+
+/// Compiles the function of `closure`, returning the new state of its `FunctionJit`
+fn compile(closure: &ClosureData) -> usize {
+    let function = &*closure.function;
+    let mut jit = jit().lock().unwrap();
+    // Another thread may have compiled the function while the lock were waited on
+    let code = function.jit.code.load(Ordering::Acquire);
+    if code != UNCOMPILED {
+        return code;
+    }
+
+    let self_upvar = closure
+        .upvars
+        .iter()
+        .position(|upvar| refers_to(upvar, closure));
+    let code = match analyze(function, self_upvar.map(|i| i as VmIndex)) {
+        Some(analysis) => match jit.compile(function, &analysis) {
+            Some((code, returns_tag)) => {
+                debug!("JIT compiled {}", function.name);
+                function.jit.returns_tag.store(returns_tag, Ordering::Relaxed);
+                if analysis.calls_self {
+                    let upvar = self_upvar.expect("Self call without an upvariable") + 1;
+                    function.jit.self_upvar.store(upvar, Ordering::Relaxed);
+                }
+                code
+            }
+            None => UNSUPPORTED,
+        },
+        None => UNSUPPORTED,
+    };
+    function.jit.code.store(code, Ordering::Release);
+    code
+}
+
+impl Jit {
+    fn compile(
+        &mut self,
+        function: &BytecodeFunction,
+        analysis: &Analysis,
+    ) -> Option<(usize, bool)> {
+        let Jit {
+            ref mut module,
+            ref mut context,
+            ref mut builder_context,
+            ref mut compiled,
+        } = *self;
+
+        let pointer_type = module.target_config().pointer_type();
+        for _ in 0..3 {
+            context.func.signature.params.push(AbiParam::new(pointer_type));
+        }
+        context.func.signature.returns.push(AbiParam::new(I8));
+
+        let returns_tag = {
+            let mut builder = FunctionBuilder::new(&mut context.func, builder_context);
+            let returns_tag = translate(&mut builder, function, analysis);
+            builder.seal_all_blocks();
+            builder.finalize();
+            returns_tag
+        };
+
+        let name = format!("gluon_jit_{}", compiled.len());
+        let result = module
+            .declare_function(&name, Linkage::Local, &context.func.signature)
+            .and_then(|id| module.define_function(id, context).map(|()| id));
+        module.clear_context(context);
+        match result {
+            Ok(id) => {
+                let code = module.finalize_function(id);
+                compiled.push(function.name.declared_name().to_string());
+                Some((code as usize, returns_tag))
+            }
+            Err(err) => {
+                debug!("Unable to JIT compile {}: {}", function.name, err);
+                None
+            }
+        }
+    }
+}
+
+/// Translates the instructions of `function` which `analysis` has checked, returning if the
+/// function returns a tag
+fn translate(
+    builder: &mut FunctionBuilder<Variable>,
+    function: &BytecodeFunction,
+    analysis: &Analysis,
+) -> bool {
+    let instructions = &function.instructions;
+    // Each slot of the stack is a variable, leaving it to the function builder to construct SSA
+    // values for them
+    let var = Variable::new;
+    for i in 0..analysis.max_depth {
+        builder.declare_var(var(i), I64);
+    }
+
+    let entry = builder.create_ebb();
+    builder.append_ebb_params_for_function_params(entry);
+    builder.switch_to_block(entry);
+    let args = builder.ebb_params(entry)[0];
+    let interrupt = builder.ebb_params(entry)[1];
+    let result_ptr = builder.ebb_params(entry)[2];
+    for i in 0..function.args as usize {
+        let arg = builder.ins().load(I64, MemFlags::new(), args, (i * 8) as i32);
+        builder.def_var(var(i), arg);
+    }
+
+    let ebbs: Vec<Option<Ebb>> = analysis
+        .targets
+        .iter()
+        .map(|&target| if target { Some(builder.create_ebb()) } else { None })
+        .collect();
+
+    // If the current block may fall through to the next instruction. Instructions which can not
+    // fall through are always followed by a jump target (or unreachable instructions)
+    let mut open = true;
+
+    // Self tail calls jump to this block instead of the start of the function if the thread
+    // has been interrupted
+    let interrupted = if analysis.calls_self {
+        builder.ins().jump(ebbs[0].unwrap(), &[]);
+        let ebb = builder.create_ebb();
+        builder.switch_to_block(ebb);
+        let finished = builder.ins().iconst(I8, 0);
+        builder.ins().return_(&[finished]);
+        open = false;
+        Some(ebb)
+    } else {
+        None
+    };
+    for (index, stack) in analysis.stacks.iter().enumerate() {
+        let stack = match *stack {
+            Some(ref stack) => stack,
+            // Unreachable
+            None => continue,
+        };
+        if let Some(ebb) = ebbs[index] {
+            if open {
+                builder.ins().jump(ebb, &[]);
+            }
+            builder.switch_to_block(ebb);
+            open = true;
+        }
+        let depth = stack.len();
+        let top = || var(depth - 1);
+
+        let instruction = match instructions.get(index) {
+            Some(&instruction) => instruction,
+            None => {
+                let result = builder.use_var(top());
+                builder.ins().store(MemFlags::new(), result, result_ptr, 0);
+                let finished = builder.ins().iconst(I8, 1);
+                builder.ins().return_(&[finished]);
+                return stack[depth - 1] == Kind::Tag;
+            }
+        };
+        match instruction {
+            Push(i) => {
+                let value = builder.use_var(var(i as usize));
+                builder.def_var(var(depth), value);
+            }
+            PushInt(i) => {
+                let value = builder.ins().iconst(I64, i as i64);
+                builder.def_var(var(depth), value);
+            }
+            // The function itself is only ever tail called so its slot is never read
+            PushUpVar(_) => {
+                let value = builder.ins().iconst(I64, 0);
+                builder.def_var(var(depth), value);
+            }
+            Construct { tag, .. } => {
+                let value = builder.ins().iconst(I64, i64::from(tag));
+                builder.def_var(var(depth), value);
+            }
+            AddInt | SubtractInt | MultiplyInt | IntLT | IntEQ => {
+                let l = builder.use_var(var(depth - 2));
+                let r = builder.use_var(var(depth - 1));
+                let value = match instruction {
+                    AddInt => builder.ins().iadd(l, r),
+                    SubtractInt => builder.ins().isub(l, r),
+                    MultiplyInt => builder.ins().imul(l, r),
+                    _ => {
+                        let cond = match instruction {
+                            IntLT => IntCC::SignedLessThan,
+                            _ => IntCC::Equal,
+                        };
+                        let b = builder.ins().icmp(cond, l, r);
+                        builder.ins().bint(I64, b)
+                    }
+                };
+                builder.def_var(var(depth - 2), value);
+            }
+            TestTag(tag) => {
+                let value = builder.use_var(top());
+                let b = builder.ins().icmp_imm(IntCC::Equal, value, i64::from(tag));
+                let value = builder.ins().bint(I64, b);
+                builder.def_var(var(depth), value);
+            }
+            Jump(target) => {
+                builder.ins().jump(ebbs[target as usize].unwrap(), &[]);
+                open = false;
+            }
+            CJump(target) => {
+                let value = builder.use_var(top());
+                builder.ins().brnz(value, ebbs[target as usize].unwrap(), &[]);
+            }
+            TailCall(args) => {
+                let args = args as usize;
+                let values: Vec<_> = (depth - args..depth)
+                    .map(|i| builder.use_var(var(i)))
+                    .collect();
+                for (i, value) in values.into_iter().enumerate() {
+                    builder.def_var(var(i), value);
+                }
+                // A plain load is enough as the flag is only ever set from `false` to `true`
+                let flag = builder.ins().load(I8, MemFlags::new(), interrupt, 0);
+                builder.ins().brnz(flag, interrupted.unwrap(), &[]);
+                builder.ins().jump(ebbs[0].unwrap(), &[]);
+                open = false;
+            }
+            Pop(_) => (),
+            Slide(n) => {
+                let value = builder.use_var(top());
+                builder.def_var(var(depth - 1 - n as usize), value);
+            }
+            _ => ice!("Instruction {:?} should have been rejected by `analyze`", instruction),
+        }
+    }
+    ice!("The end of the function should be reachable")
+}
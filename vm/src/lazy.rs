@@ -7,9 +7,9 @@ use std::sync::Mutex;
 use base::types;
 use base::types::{ArcType, Type};
 use gc::{Gc, GcPtr, Move, Traverseable};
-use api::{FunctionRef, Getable, OpaqueValue, RuntimeResult, Userdata, VmType, WithVM};
+use api::{FunctionRef, Getable, OpaqueValue, RuntimeResult, Userdata, ValueRef, VmType, WithVM};
 use api::Generic;
-use api::generic::A;
+use api::generic::{A, B};
 use vm::Thread;
 use {Error, ExternModule, Result, Variants};
 use value::{Cloner, Value};
@@ -103,6 +103,24 @@ fn force(
     }
 }
 
+/// Forces `value` if it is a `Lazy` value and then returns `b`. All other values are already
+/// evaluated so `seq` only needs to look for unevaluated `Lazy` values.
+pub(crate) fn seq(
+    WithVM { vm, value }: WithVM<Generic<A>>,
+    b: Generic<B>,
+) -> RuntimeResult<Generic<B>, Cow<'static, str>> {
+    let value = unsafe { value.get_value() };
+    let variants = unsafe { Variants::new(&value) };
+    if let ValueRef::Userdata(data) = variants.as_ref() {
+        if let Some(lazy) = data.downcast_ref::<Lazy<A>>() {
+            if let RuntimeResult::Panic(err) = force(WithVM { vm, value: lazy }) {
+                return RuntimeResult::Panic(err);
+            }
+        }
+    }
+    RuntimeResult::Return(b)
+}
+
 fn lazy(f: OpaqueValue<&Thread, fn(()) -> A>) -> Lazy<A> {
     unsafe {
         Lazy {
@@ -123,7 +141,8 @@ pub fn load(vm: &Thread) -> Result<ExternModule> {
         vm,
         record!{
             lazy => primitive!(1 std::lazy::lazy),
-            force => primitive!(1 std::lazy::force)
+            force => primitive!(1 std::lazy::force),
+            seq => primitive!(2 std::lazy::seq)
         },
     )
 }
@@ -3,8 +3,17 @@
 // # GLUON
 #![recursion_limit = "1024"]
 
+#[cfg(feature = "debug")]
 #[macro_use]
 extern crate bitflags;
+#[cfg(feature = "jit")]
+extern crate cranelift_codegen;
+#[cfg(feature = "jit")]
+extern crate cranelift_frontend;
+#[cfg(feature = "jit")]
+extern crate cranelift_module;
+#[cfg(feature = "jit")]
+extern crate cranelift_simplejit;
 #[macro_use]
 extern crate collect_mac;
 #[cfg(test)]
@@ -21,8 +30,10 @@ extern crate mopa;
 extern crate pretty;
 #[macro_use]
 extern crate quick_error;
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
 extern crate tokio_core;
+#[cfg(feature = "wasm")]
+extern crate wasmi;
 
 #[cfg(feature = "serde_derive")]
 #[macro_use]
@@ -47,19 +58,29 @@ pub mod api;
 pub mod channel;
 pub mod core;
 pub mod compiler;
+#[cfg(feature = "debug")]
 pub mod debug;
+pub mod diff;
 pub mod dynamic;
 #[macro_use]
 pub mod future;
 pub mod gc;
+#[cfg(feature = "jit")]
+pub mod jit;
 pub mod lazy;
+pub mod linear;
 pub mod macros;
+pub mod meta;
 pub mod thread;
 pub mod primitives;
+pub mod profile;
 pub mod reference;
+pub mod register;
 pub mod stack;
 pub mod types;
 pub mod vm;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 mod array;
 mod interner;
@@ -146,6 +167,9 @@ quick_error! {
         Interrupted {
             display("Thread was interrupted")
         }
+        OutOfFuel {
+            display("The fuel ran out during a call which can not be suspended")
+        }
         Panic(err: String) {
             display("{}", err)
         }
@@ -161,20 +185,49 @@ pub struct ExternModule {
 }
 
 impl ExternModule {
+    /// Creates a module from `value`. Several functions may be registered under the same name
+    /// (`(draw2 "draw") => ..., (draw3 "draw") => ...` in `record!`) as long as their types
+    /// differ, uses of the name are resolved to one of them by their type.
     pub fn new<'vm, T>(thread: &'vm Thread, value: T) -> Result<ExternModule>
     where
         T: VmType + api::Pushable<'vm> + Send + Sync,
     {
+        let typ = T::make_forall_type(thread);
+        check_overloads(&typ)?;
         Ok(ExternModule {
             value: value.marshal(thread)?,
-            typ: T::make_forall_type(thread),
+            typ: typ,
             metadata: Metadata::default(),
         })
     }
 }
 
+/// Returns an error if two of the fields registered under the same name have the same type (up to
+/// the names of their type variables) as it would not be possible to tell which of them a use of
+/// the name refers to
+fn check_overloads(typ: &ArcType) -> Result<()> {
+    use base::types::{alpha_equivalent, field_display_name};
+
+    let fields: Vec<_> = typ.remove_forall().row_iter().collect();
+    for (i, field) in fields.iter().enumerate() {
+        let name = field_display_name(field.name.as_ref());
+        let is_duplicate = fields[..i].iter().any(|other| {
+            field_display_name(other.name.as_ref()) == name
+                && alpha_equivalent(&other.typ, &field.typ)
+        });
+        if is_duplicate {
+            return Err(Error::Message(format!(
+                "`{}` is registered more than once with the type `{}`",
+                name, field.typ
+            )));
+        }
+    }
+    Ok(())
+}
+
 /// Internal types and functions exposed to the main `gluon` crate
 pub mod internal {
+    pub use base::types::ArcType;
     pub use value::{Value, ValuePrinter};
     pub use vm::Global;
 }
@@ -0,0 +1,897 @@
+//! Primitives of `std.math.linear`, vectors, matrices and quaternions of `Float`.
+//!
+//! The types are plain gluon records, so they can be created and matched on from gluon, but every
+//! operation on them is done in Rust on the unboxed floats of the records. Matrices are stored as
+//! their column vectors.
+//!
+//! With the `mint` feature the `mint` types, which `cgmath`, `nalgebra` and most other Rust math
+//! libraries convert to and from, can be passed to and returned from gluon directly.
+
+use std::cmp::Ordering;
+
+use base::types::ArcType;
+
+use api::{Getable, Pushable, VmType};
+use thread::{Context, Thread};
+use {ExternModule, Result, Variants};
+
+field_decl! { x, y, z, w, v, s, x_axis, y_axis, z_axis, w_axis }
+
+macro_rules! record_struct {
+    ($(#[$attr: meta])* pub struct $name: ident { $($field: ident : $typ: ty),+ }) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug, Default, PartialEq)]
+        pub struct $name {
+            $(pub $field: $typ),+
+        }
+
+        impl VmType for $name {
+            type Type = $name;
+            fn make_type(vm: &Thread) -> ArcType {
+                <record_type!($($field => $typ),+)>::make_type(vm)
+            }
+        }
+
+        impl<'vm> Pushable<'vm> for $name {
+            fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+                let record: record_type!($($field => $typ),+) =
+                    record_no_decl!($($field => self.$field),+);
+                record.push(thread, context)
+            }
+        }
+
+        impl<'vm> Getable<'vm> for $name {
+            fn from_value(thread: &'vm Thread, value: Variants) -> Self {
+                let record_p!($($field),+) =
+                    <record_type!($($field => $typ),+)>::from_value(thread, value);
+                $name { $($field),+ }
+            }
+        }
+    };
+}
+
+record_struct! {
+    /// `std.math.linear.Vec2`
+    pub struct Vec2 { x: f64, y: f64 }
+}
+
+record_struct! {
+    /// `std.math.linear.Vec3`
+    pub struct Vec3 { x: f64, y: f64, z: f64 }
+}
+
+record_struct! {
+    /// `std.math.linear.Vec4`
+    pub struct Vec4 { x: f64, y: f64, z: f64, w: f64 }
+}
+
+record_struct! {
+    /// `std.math.linear.Quat`, the quaternion `s + v.x i + v.y j + v.z k`
+    pub struct Quat { v: Vec3, s: f64 }
+}
+
+record_struct! {
+    /// `std.math.linear.Mat3`, a 3x3 matrix stored as its columns
+    pub struct Mat3 { x_axis: Vec3, y_axis: Vec3, z_axis: Vec3 }
+}
+
+record_struct! {
+    /// `std.math.linear.Mat4`, a 4x4 matrix stored as its columns
+    pub struct Mat4 { x_axis: Vec4, y_axis: Vec4, z_axis: Vec4, w_axis: Vec4 }
+}
+
+macro_rules! vector_ops {
+    ($module: ident, $name: ident { $($field: ident),+ }) => {
+        #[doc(hidden)]
+        pub mod $module {
+            use super::*;
+
+            pub fn add(l: $name, r: $name) -> $name {
+                $name { $($field: l.$field + r.$field),+ }
+            }
+
+            pub fn sub(l: $name, r: $name) -> $name {
+                $name { $($field: l.$field - r.$field),+ }
+            }
+
+            pub fn scale(v: $name, factor: f64) -> $name {
+                $name { $($field: v.$field * factor),+ }
+            }
+
+            pub fn dot(l: $name, r: $name) -> f64 {
+                0.0 $(+ l.$field * r.$field)+
+            }
+
+            pub fn length(v: $name) -> f64 {
+                dot(v, v).sqrt()
+            }
+
+            /// Returns `v` scaled to length 1 or `v` itself if it has length 0
+            pub fn normalize(v: $name) -> $name {
+                let len = length(v);
+                if len == 0.0 {
+                    v
+                } else {
+                    scale(v, 1.0 / len)
+                }
+            }
+
+            pub fn lerp(l: $name, r: $name, t: f64) -> $name {
+                $name { $($field: l.$field + (r.$field - l.$field) * t),+ }
+            }
+        }
+    };
+}
+
+vector_ops!(vec2, Vec2 { x, y });
+vector_ops!(vec3, Vec3 { x, y, z });
+vector_ops!(vec4, Vec4 { x, y, z, w });
+
+pub fn vec3_cross(l: Vec3, r: Vec3) -> Vec3 {
+    Vec3 {
+        x: l.y * r.z - l.z * r.y,
+        y: l.z * r.x - l.x * r.z,
+        z: l.x * r.y - l.y * r.x,
+    }
+}
+
+// The matrix algorithms work on column major arrays of `n * n` floats so that they can be shared
+// between `Mat3` and `Mat4`
+
+fn mul_flat(n: usize, l: &[f64], r: &[f64], out: &mut [f64]) {
+    for col in 0..n {
+        for row in 0..n {
+            out[col * n + row] = (0..n).map(|k| l[k * n + row] * r[col * n + k]).sum();
+        }
+    }
+}
+
+fn transpose_flat(n: usize, m: &[f64], out: &mut [f64]) {
+    for col in 0..n {
+        for row in 0..n {
+            out[row * n + col] = m[col * n + row];
+        }
+    }
+}
+
+/// Inverts `m` into `inverse` with Gauss-Jordan elimination, returning the determinant of `m`.
+/// `inverse` is only valid if the determinant is not 0.
+fn invert_flat(n: usize, m: &[f64], inverse: &mut [f64]) -> f64 {
+    let at = |row: usize, col: usize| col * n + row;
+
+    let mut a = [0.0; 16];
+    a[..n * n].copy_from_slice(m);
+    for col in 0..n {
+        for row in 0..n {
+            inverse[at(row, col)] = if row == col { 1.0 } else { 0.0 };
+        }
+    }
+
+    let mut det = 1.0;
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| {
+                a[at(i, col)]
+                    .abs()
+                    .partial_cmp(&a[at(j, col)].abs())
+                    .unwrap_or(Ordering::Equal)
+            })
+            .unwrap();
+        if a[at(pivot, col)] == 0.0 {
+            return 0.0;
+        }
+        if pivot != col {
+            for k in 0..n {
+                a.swap(at(pivot, k), at(col, k));
+                inverse.swap(at(pivot, k), at(col, k));
+            }
+            det = -det;
+        }
+
+        let p = a[at(col, col)];
+        det *= p;
+        for k in 0..n {
+            a[at(col, k)] /= p;
+            inverse[at(col, k)] /= p;
+        }
+        for row in 0..n {
+            let factor = a[at(row, col)];
+            if row != col && factor != 0.0 {
+                for k in 0..n {
+                    a[at(row, k)] -= factor * a[at(col, k)];
+                    inverse[at(row, k)] -= factor * inverse[at(col, k)];
+                }
+            }
+        }
+    }
+    det
+}
+
+impl Vec3 {
+    fn to_flat(self) -> [f64; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    fn from_flat(m: &[f64]) -> Vec3 {
+        Vec3 {
+            x: m[0],
+            y: m[1],
+            z: m[2],
+        }
+    }
+}
+
+impl Vec4 {
+    fn to_flat(self) -> [f64; 4] {
+        [self.x, self.y, self.z, self.w]
+    }
+
+    fn from_flat(m: &[f64]) -> Vec4 {
+        Vec4 {
+            x: m[0],
+            y: m[1],
+            z: m[2],
+            w: m[3],
+        }
+    }
+}
+
+impl Mat3 {
+    fn to_flat(self) -> [f64; 9] {
+        let Mat3 {
+            x_axis: x,
+            y_axis: y,
+            z_axis: z,
+        } = self;
+        [x.x, x.y, x.z, y.x, y.y, y.z, z.x, z.y, z.z]
+    }
+
+    fn from_flat(m: &[f64]) -> Mat3 {
+        let col = |i: usize| Vec3::from_flat(&m[i * 3..]);
+        Mat3 {
+            x_axis: col(0),
+            y_axis: col(1),
+            z_axis: col(2),
+        }
+    }
+}
+
+impl Mat4 {
+    fn to_flat(self) -> [f64; 16] {
+        let Mat4 {
+            x_axis: x,
+            y_axis: y,
+            z_axis: z,
+            w_axis: w,
+        } = self;
+        [
+            x.x, x.y, x.z, x.w, y.x, y.y, y.z, y.w, z.x, z.y, z.z, z.w, w.x, w.y, w.z, w.w
+        ]
+    }
+
+    fn from_flat(m: &[f64]) -> Mat4 {
+        let col = |i: usize| Vec4::from_flat(&m[i * 4..]);
+        Mat4 {
+            x_axis: col(0),
+            y_axis: col(1),
+            z_axis: col(2),
+            w_axis: col(3),
+        }
+    }
+}
+
+macro_rules! matrix_ops {
+    ($module: ident, $name: ident, $vector: ident, $n: expr) => {
+        #[doc(hidden)]
+        pub mod $module {
+            use super::*;
+
+            pub fn mul(l: $name, r: $name) -> $name {
+                let mut out = [0.0; $n * $n];
+                mul_flat($n, &l.to_flat(), &r.to_flat(), &mut out);
+                $name::from_flat(&out)
+            }
+
+            pub fn mul_vec(m: $name, v: $vector) -> $vector {
+                // Multiply with a matrix which has `v` as its first column
+                let mut vm = [0.0; $n * $n];
+                vm[..$n].copy_from_slice(&v.to_flat());
+                let mut out = [0.0; $n * $n];
+                mul_flat($n, &m.to_flat(), &vm, &mut out);
+                $vector::from_flat(&out)
+            }
+
+            pub fn transpose(m: $name) -> $name {
+                let mut out = [0.0; $n * $n];
+                transpose_flat($n, &m.to_flat(), &mut out);
+                $name::from_flat(&out)
+            }
+
+            pub fn determinant(m: $name) -> f64 {
+                let mut inverse = [0.0; $n * $n];
+                invert_flat($n, &m.to_flat(), &mut inverse)
+            }
+
+            pub fn inverse(m: $name) -> Option<$name> {
+                let mut inverse = [0.0; $n * $n];
+                if invert_flat($n, &m.to_flat(), &mut inverse) == 0.0 {
+                    None
+                } else {
+                    Some($name::from_flat(&inverse))
+                }
+            }
+        }
+    };
+}
+
+matrix_ops!(mat3, Mat3, Vec3, 3);
+matrix_ops!(mat4, Mat4, Vec4, 4);
+
+/// The columns of the rotation matrix of the unit quaternion `q`
+fn rotation_columns(q: Quat) -> [Vec3; 3] {
+    let Quat {
+        v: Vec3 { x, y, z },
+        s,
+    } = q;
+    [
+        Vec3 {
+            x: 1.0 - 2.0 * (y * y + z * z),
+            y: 2.0 * (x * y + s * z),
+            z: 2.0 * (x * z - s * y),
+        },
+        Vec3 {
+            x: 2.0 * (x * y - s * z),
+            y: 1.0 - 2.0 * (x * x + z * z),
+            z: 2.0 * (y * z + s * x),
+        },
+        Vec3 {
+            x: 2.0 * (x * z + s * y),
+            y: 2.0 * (y * z - s * x),
+            z: 1.0 - 2.0 * (x * x + y * y),
+        },
+    ]
+}
+
+pub fn mat3_from_quat(q: Quat) -> Mat3 {
+    let cols = rotation_columns(q);
+    Mat3 {
+        x_axis: cols[0],
+        y_axis: cols[1],
+        z_axis: cols[2],
+    }
+}
+
+fn extend(v: Vec3, w: f64) -> Vec4 {
+    Vec4 {
+        x: v.x,
+        y: v.y,
+        z: v.z,
+        w,
+    }
+}
+
+pub fn mat4_from_quat(q: Quat) -> Mat4 {
+    let cols = rotation_columns(q);
+    Mat4 {
+        x_axis: extend(cols[0], 0.0),
+        y_axis: extend(cols[1], 0.0),
+        z_axis: extend(cols[2], 0.0),
+        w_axis: Vec4 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            w: 1.0,
+        },
+    }
+}
+
+pub fn mat4_translation(v: Vec3) -> Mat4 {
+    let mut m = [0.0; 16];
+    for i in 0..4 {
+        m[i * 4 + i] = 1.0;
+    }
+    m[12] = v.x;
+    m[13] = v.y;
+    m[14] = v.z;
+    Mat4::from_flat(&m)
+}
+
+pub fn mat4_scaling(v: Vec3) -> Mat4 {
+    let mut m = [0.0; 16];
+    m[0] = v.x;
+    m[5] = v.y;
+    m[10] = v.z;
+    m[15] = 1.0;
+    Mat4::from_flat(&m)
+}
+
+/// A right handed perspective projection with a clip space depth of -1 to 1
+pub fn mat4_perspective(fovy: f64, aspect: f64, near: f64, far: f64) -> Mat4 {
+    let f = 1.0 / (fovy / 2.0).tan();
+    let mut m = [0.0; 16];
+    m[0] = f / aspect;
+    m[5] = f;
+    m[10] = (far + near) / (near - far);
+    m[11] = -1.0;
+    m[14] = 2.0 * far * near / (near - far);
+    Mat4::from_flat(&m)
+}
+
+/// A right handed view matrix of a camera at `eye` looking at `center`
+pub fn mat4_look_at(eye: Vec3, center: Vec3, up: Vec3) -> Mat4 {
+    let f = vec3::normalize(vec3::sub(center, eye));
+    let s = vec3::normalize(vec3_cross(f, up));
+    let u = vec3_cross(s, f);
+    Mat4 {
+        x_axis: Vec4 {
+            x: s.x,
+            y: u.x,
+            z: -f.x,
+            w: 0.0,
+        },
+        y_axis: Vec4 {
+            x: s.y,
+            y: u.y,
+            z: -f.y,
+            w: 0.0,
+        },
+        z_axis: Vec4 {
+            x: s.z,
+            y: u.z,
+            z: -f.z,
+            w: 0.0,
+        },
+        w_axis: Vec4 {
+            x: -vec3::dot(s, eye),
+            y: -vec3::dot(u, eye),
+            z: vec3::dot(f, eye),
+            w: 1.0,
+        },
+    }
+}
+
+#[doc(hidden)]
+pub mod quat {
+    use super::*;
+
+    /// The rotation of `angle` radians around `axis`
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Quat {
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Quat {
+            v: vec3::scale(vec3::normalize(axis), sin),
+            s: cos,
+        }
+    }
+
+    pub fn mul(l: Quat, r: Quat) -> Quat {
+        Quat {
+            v: vec3::add(
+                vec3::add(vec3::scale(r.v, l.s), vec3::scale(l.v, r.s)),
+                vec3_cross(l.v, r.v),
+            ),
+            s: l.s * r.s - vec3::dot(l.v, r.v),
+        }
+    }
+
+    pub fn conjugate(q: Quat) -> Quat {
+        Quat {
+            v: vec3::scale(q.v, -1.0),
+            s: q.s,
+        }
+    }
+
+    fn dot(l: Quat, r: Quat) -> f64 {
+        vec3::dot(l.v, r.v) + l.s * r.s
+    }
+
+    fn scale(q: Quat, factor: f64) -> Quat {
+        Quat {
+            v: vec3::scale(q.v, factor),
+            s: q.s * factor,
+        }
+    }
+
+    fn add(l: Quat, r: Quat) -> Quat {
+        Quat {
+            v: vec3::add(l.v, r.v),
+            s: l.s + r.s,
+        }
+    }
+
+    pub fn normalize(q: Quat) -> Quat {
+        let len = dot(q, q).sqrt();
+        if len == 0.0 {
+            q
+        } else {
+            scale(q, 1.0 / len)
+        }
+    }
+
+    /// Rotates `v` by the unit quaternion `q`
+    pub fn rotate(q: Quat, v: Vec3) -> Vec3 {
+        let t = vec3::scale(vec3_cross(q.v, v), 2.0);
+        vec3::add(vec3::add(v, vec3::scale(t, q.s)), vec3_cross(q.v, t))
+    }
+
+    /// Spherical interpolation between the unit quaternions `l` and `r`
+    pub fn slerp(l: Quat, r: Quat, t: f64) -> Quat {
+        let mut cos = dot(l, r);
+        // Take the shortest path
+        let r = if cos < 0.0 {
+            cos = -cos;
+            scale(r, -1.0)
+        } else {
+            r
+        };
+        if cos > 0.9995 {
+            // The quaternions are too close for the angle to be computed accurately
+            return normalize(add(l, scale(add(r, scale(l, -1.0)), t)));
+        }
+        let angle = cos.acos();
+        let sin = angle.sin();
+        add(
+            scale(l, ((1.0 - t) * angle).sin() / sin),
+            scale(r, (t * angle).sin() / sin),
+        )
+    }
+}
+
+#[cfg(feature = "mint")]
+mod mint_types {
+    extern crate mint;
+
+    use super::*;
+
+    macro_rules! mint_type {
+        ($mint: ty, $name: ident, |$from: ident| $from_mint: expr, |$to: ident| $to_mint: expr) => {
+            impl From<$mint> for $name {
+                fn from($from: $mint) -> $name {
+                    $from_mint
+                }
+            }
+
+            impl From<$name> for $mint {
+                fn from($to: $name) -> $mint {
+                    $to_mint
+                }
+            }
+
+            impl VmType for $mint {
+                type Type = $name;
+                fn make_type(vm: &Thread) -> ArcType {
+                    $name::make_type(vm)
+                }
+            }
+
+            impl<'vm> Pushable<'vm> for $mint {
+                fn push(self, thread: &'vm Thread, context: &mut Context) -> Result<()> {
+                    $name::from(self).push(thread, context)
+                }
+            }
+
+            impl<'vm> Getable<'vm> for $mint {
+                fn from_value(thread: &'vm Thread, value: Variants) -> Self {
+                    $name::from_value(thread, value).into()
+                }
+            }
+        };
+    }
+
+    mint_type!(
+        mint::Vector2<f64>,
+        Vec2,
+        |v| Vec2 { x: v.x, y: v.y },
+        |v| mint::Vector2 { x: v.x, y: v.y }
+    );
+    mint_type!(
+        mint::Vector3<f64>,
+        Vec3,
+        |v| Vec3 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        },
+        |v| mint::Vector3 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    );
+    mint_type!(
+        mint::Vector4<f64>,
+        Vec4,
+        |v| Vec4 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+            w: v.w,
+        },
+        |v| mint::Vector4 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+            w: v.w,
+        }
+    );
+    mint_type!(
+        mint::Quaternion<f64>,
+        Quat,
+        |q| Quat {
+            v: q.v.into(),
+            s: q.s,
+        },
+        |q| mint::Quaternion {
+            v: q.v.into(),
+            s: q.s,
+        }
+    );
+    mint_type!(
+        mint::ColumnMatrix3<f64>,
+        Mat3,
+        |m| Mat3 {
+            x_axis: m.x.into(),
+            y_axis: m.y.into(),
+            z_axis: m.z.into(),
+        },
+        |m| mint::ColumnMatrix3 {
+            x: m.x_axis.into(),
+            y: m.y_axis.into(),
+            z: m.z_axis.into(),
+        }
+    );
+    mint_type!(
+        mint::ColumnMatrix4<f64>,
+        Mat4,
+        |m| Mat4 {
+            x_axis: m.x.into(),
+            y_axis: m.y.into(),
+            z_axis: m.z.into(),
+            w_axis: m.w.into(),
+        },
+        |m| mint::ColumnMatrix4 {
+            x: m.x_axis.into(),
+            y: m.y_axis.into(),
+            z: m.z_axis.into(),
+            w: m.w_axis.into(),
+        }
+    );
+}
+
+mod std {
+    pub mod math {
+        pub mod linear {
+            pub use linear as prim;
+        }
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    // The primitives are named after their fields so that they can be found with `get_global`
+    ExternModule::new(
+        vm,
+        record!{
+            vec2_add => named_primitive!(
+                2,
+                "std.math.linear.prim.vec2_add",
+                std::math::linear::prim::vec2::add
+            ),
+            vec2_sub => named_primitive!(
+                2,
+                "std.math.linear.prim.vec2_sub",
+                std::math::linear::prim::vec2::sub
+            ),
+            vec2_scale => named_primitive!(
+                2,
+                "std.math.linear.prim.vec2_scale",
+                std::math::linear::prim::vec2::scale
+            ),
+            vec2_dot => named_primitive!(
+                2,
+                "std.math.linear.prim.vec2_dot",
+                std::math::linear::prim::vec2::dot
+            ),
+            vec2_length => named_primitive!(
+                1,
+                "std.math.linear.prim.vec2_length",
+                std::math::linear::prim::vec2::length
+            ),
+            vec2_normalize => named_primitive!(
+                1,
+                "std.math.linear.prim.vec2_normalize",
+                std::math::linear::prim::vec2::normalize
+            ),
+            vec2_lerp => named_primitive!(
+                3,
+                "std.math.linear.prim.vec2_lerp",
+                std::math::linear::prim::vec2::lerp
+            ),
+            vec3_add => named_primitive!(
+                2,
+                "std.math.linear.prim.vec3_add",
+                std::math::linear::prim::vec3::add
+            ),
+            vec3_sub => named_primitive!(
+                2,
+                "std.math.linear.prim.vec3_sub",
+                std::math::linear::prim::vec3::sub
+            ),
+            vec3_scale => named_primitive!(
+                2,
+                "std.math.linear.prim.vec3_scale",
+                std::math::linear::prim::vec3::scale
+            ),
+            vec3_dot => named_primitive!(
+                2,
+                "std.math.linear.prim.vec3_dot",
+                std::math::linear::prim::vec3::dot
+            ),
+            vec3_length => named_primitive!(
+                1,
+                "std.math.linear.prim.vec3_length",
+                std::math::linear::prim::vec3::length
+            ),
+            vec3_normalize => named_primitive!(
+                1,
+                "std.math.linear.prim.vec3_normalize",
+                std::math::linear::prim::vec3::normalize
+            ),
+            vec3_lerp => named_primitive!(
+                3,
+                "std.math.linear.prim.vec3_lerp",
+                std::math::linear::prim::vec3::lerp
+            ),
+            vec3_cross => named_primitive!(
+                2,
+                "std.math.linear.prim.vec3_cross",
+                std::math::linear::prim::vec3_cross
+            ),
+            vec4_add => named_primitive!(
+                2,
+                "std.math.linear.prim.vec4_add",
+                std::math::linear::prim::vec4::add
+            ),
+            vec4_sub => named_primitive!(
+                2,
+                "std.math.linear.prim.vec4_sub",
+                std::math::linear::prim::vec4::sub
+            ),
+            vec4_scale => named_primitive!(
+                2,
+                "std.math.linear.prim.vec4_scale",
+                std::math::linear::prim::vec4::scale
+            ),
+            vec4_dot => named_primitive!(
+                2,
+                "std.math.linear.prim.vec4_dot",
+                std::math::linear::prim::vec4::dot
+            ),
+            vec4_length => named_primitive!(
+                1,
+                "std.math.linear.prim.vec4_length",
+                std::math::linear::prim::vec4::length
+            ),
+            vec4_normalize => named_primitive!(
+                1,
+                "std.math.linear.prim.vec4_normalize",
+                std::math::linear::prim::vec4::normalize
+            ),
+            vec4_lerp => named_primitive!(
+                3,
+                "std.math.linear.prim.vec4_lerp",
+                std::math::linear::prim::vec4::lerp
+            ),
+            mat3_mul => named_primitive!(
+                2,
+                "std.math.linear.prim.mat3_mul",
+                std::math::linear::prim::mat3::mul
+            ),
+            mat3_mul_vec => named_primitive!(
+                2,
+                "std.math.linear.prim.mat3_mul_vec",
+                std::math::linear::prim::mat3::mul_vec
+            ),
+            mat3_transpose => named_primitive!(
+                1,
+                "std.math.linear.prim.mat3_transpose",
+                std::math::linear::prim::mat3::transpose
+            ),
+            mat3_determinant => named_primitive!(
+                1,
+                "std.math.linear.prim.mat3_determinant",
+                std::math::linear::prim::mat3::determinant
+            ),
+            mat3_inverse => named_primitive!(
+                1,
+                "std.math.linear.prim.mat3_inverse",
+                std::math::linear::prim::mat3::inverse
+            ),
+            mat3_from_quat => named_primitive!(
+                1,
+                "std.math.linear.prim.mat3_from_quat",
+                std::math::linear::prim::mat3_from_quat
+            ),
+            mat4_mul => named_primitive!(
+                2,
+                "std.math.linear.prim.mat4_mul",
+                std::math::linear::prim::mat4::mul
+            ),
+            mat4_mul_vec => named_primitive!(
+                2,
+                "std.math.linear.prim.mat4_mul_vec",
+                std::math::linear::prim::mat4::mul_vec
+            ),
+            mat4_transpose => named_primitive!(
+                1,
+                "std.math.linear.prim.mat4_transpose",
+                std::math::linear::prim::mat4::transpose
+            ),
+            mat4_determinant => named_primitive!(
+                1,
+                "std.math.linear.prim.mat4_determinant",
+                std::math::linear::prim::mat4::determinant
+            ),
+            mat4_inverse => named_primitive!(
+                1,
+                "std.math.linear.prim.mat4_inverse",
+                std::math::linear::prim::mat4::inverse
+            ),
+            mat4_from_quat => named_primitive!(
+                1,
+                "std.math.linear.prim.mat4_from_quat",
+                std::math::linear::prim::mat4_from_quat
+            ),
+            mat4_translation => named_primitive!(
+                1,
+                "std.math.linear.prim.mat4_translation",
+                std::math::linear::prim::mat4_translation
+            ),
+            mat4_scaling => named_primitive!(
+                1,
+                "std.math.linear.prim.mat4_scaling",
+                std::math::linear::prim::mat4_scaling
+            ),
+            mat4_perspective => named_primitive!(
+                4,
+                "std.math.linear.prim.mat4_perspective",
+                std::math::linear::prim::mat4_perspective
+            ),
+            mat4_look_at => named_primitive!(
+                3,
+                "std.math.linear.prim.mat4_look_at",
+                std::math::linear::prim::mat4_look_at
+            ),
+            quat_from_axis_angle => named_primitive!(
+                2,
+                "std.math.linear.prim.quat_from_axis_angle",
+                std::math::linear::prim::quat::from_axis_angle
+            ),
+            quat_mul => named_primitive!(
+                2,
+                "std.math.linear.prim.quat_mul",
+                std::math::linear::prim::quat::mul
+            ),
+            quat_conjugate => named_primitive!(
+                1,
+                "std.math.linear.prim.quat_conjugate",
+                std::math::linear::prim::quat::conjugate
+            ),
+            quat_normalize => named_primitive!(
+                1,
+                "std.math.linear.prim.quat_normalize",
+                std::math::linear::prim::quat::normalize
+            ),
+            quat_rotate => named_primitive!(
+                2,
+                "std.math.linear.prim.quat_rotate",
+                std::math::linear::prim::quat::rotate
+            ),
+            quat_slerp => named_primitive!(
+                3,
+                "std.math.linear.prim.quat_slerp",
+                std::math::linear::prim::quat::slerp
+            )
+        },
+    )
+}
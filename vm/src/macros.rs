@@ -3,7 +3,7 @@ use std::any::Any;
 use std::sync::{Arc, RwLock};
 use std::error::Error as StdError;
 
-use base::ast::{self, Expr, MutVisitor, SpannedExpr};
+use base::ast::{self, Arena, Expr, MutVisitor, SpannedExpr};
 use base::pos::{BytePos, Spanned};
 use base::error::Errors as BaseErrors;
 use base::fnv::FnvMap;
@@ -19,26 +19,33 @@ pub type Errors = BaseErrors<SpannedError>;
 /// A trait which abstracts over macros.
 ///
 /// A macro is similiar to a function call but is run at compile time instead of at runtime.
+/// Any expressions created by the expansion are allocated in `arena`.
 pub trait Macro: ::mopa::Any + Send + Sync {
-    fn expand(
+    fn expand<'ast>(
         &self,
         env: &mut MacroExpander,
-        args: &mut [SpannedExpr<Symbol>],
-    ) -> Result<SpannedExpr<Symbol>, Error>;
+        arena: &'ast Arena<'ast, Symbol>,
+        args: &mut [SpannedExpr<'ast, Symbol>],
+    ) -> Result<SpannedExpr<'ast, Symbol>, Error>;
 }
 
 mopafy!(Macro);
 
 impl<F: ::mopa::Any + Clone + Send + Sync> Macro for F
 where
-    F: Fn(&mut MacroExpander, &mut [SpannedExpr<Symbol>]) -> Result<SpannedExpr<Symbol>, Error>,
+    F: for<'ast> Fn(
+        &mut MacroExpander,
+        &'ast Arena<'ast, Symbol>,
+        &mut [SpannedExpr<'ast, Symbol>],
+    ) -> Result<SpannedExpr<'ast, Symbol>, Error>,
 {
-    fn expand(
+    fn expand<'ast>(
         &self,
         env: &mut MacroExpander,
-        args: &mut [SpannedExpr<Symbol>],
-    ) -> Result<SpannedExpr<Symbol>, Error> {
-        self(env, args)
+        arena: &'ast Arena<'ast, Symbol>,
+        args: &mut [SpannedExpr<'ast, Symbol>],
+    ) -> Result<SpannedExpr<'ast, Symbol>, Error> {
+        self(env, arena, args)
     }
 }
 
@@ -71,9 +78,14 @@ impl MacroEnv {
     }
 
     /// Runs the macros in this `MacroEnv` on `expr` using `env` as the context of the expansion
-    pub fn run(&self, vm: &Thread, expr: &mut SpannedExpr<Symbol>) -> Result<(), Errors> {
+    pub fn run<'ast>(
+        &self,
+        vm: &Thread,
+        arena: &'ast Arena<'ast, Symbol>,
+        expr: &mut SpannedExpr<'ast, Symbol>,
+    ) -> Result<(), Errors> {
         let mut expander = MacroExpander::new(vm);
-        expander.visit_expr(expr);
+        expander.run(arena, expr);
         expander.finish()
     }
 }
@@ -103,27 +115,40 @@ impl<'a> MacroExpander<'a> {
         }
     }
 
-    pub fn run(&mut self, expr: &mut SpannedExpr<Symbol>) {
-        self.visit_expr(expr);
+    /// Expands all macros in `expr`, allocating the expanded expressions in `arena`
+    pub fn run<'ast>(
+        &mut self,
+        arena: &'ast Arena<'ast, Symbol>,
+        expr: &mut SpannedExpr<'ast, Symbol>,
+    ) {
+        MacroVisitor {
+            expander: self,
+            arena,
+        }.visit_expr(expr);
         if self.errors.has_errors() {
             info!("Macro errors: {}", self.errors);
         }
     }
 }
 
-impl<'a> MutVisitor for MacroExpander<'a> {
+struct MacroVisitor<'a: 'b, 'b, 'ast> {
+    expander: &'b mut MacroExpander<'a>,
+    arena: &'ast Arena<'ast, Symbol>,
+}
+
+impl<'a, 'b, 'ast> MutVisitor<'ast> for MacroVisitor<'a, 'b, 'ast> {
     type Ident = Symbol;
 
-    fn visit_expr(&mut self, expr: &mut SpannedExpr<Symbol>) {
+    fn visit_expr(&mut self, expr: &mut SpannedExpr<'ast, Symbol>) {
         let replacement = match expr.value {
             Expr::App(ref mut id, ref mut args) => match id.value {
                 Expr::Ident(ref id) if id.name.as_ref().ends_with('!') => {
                     let name = id.name.as_ref();
-                    match self.macros.get(&name[..name.len() - 1]) {
-                        Some(m) => Some(match m.expand(self, args) {
+                    match self.expander.macros.get(&name[..name.len() - 1]) {
+                        Some(m) => Some(match m.expand(self.expander, self.arena, args) {
                             Ok(e) => e,
                             Err(err) => {
-                                self.errors.push(pos::spanned(expr.span, err));
+                                self.expander.errors.push(pos::spanned(expr.span, err));
                                 pos::spanned(expr.span, Expr::Error(None))
                             }
                         }),
@@ -0,0 +1,38 @@
+//! Module which exposes the metadata (documentation and attributes) of globals to gluon code
+use api::WithVM;
+use thread::Thread;
+use {ExternModule, Result};
+
+/// The documentation comment and the `(name, arguments)` pairs of each attribute of a binding
+type BindingMetadata = (Option<String>, Vec<(String, Option<String>)>);
+
+fn get(WithVM { vm, value: name }: WithVM<&str>) -> Option<BindingMetadata> {
+    let env = vm.get_env();
+    env.get_metadata(name).ok().map(|metadata| {
+        (
+            metadata.comment.clone(),
+            metadata
+                .attributes
+                .iter()
+                .map(|(name, arguments)| (name.clone(), arguments.clone()))
+                .collect(),
+        )
+    })
+}
+
+mod std {
+    pub mod meta {
+        pub use meta as prim;
+    }
+}
+
+pub fn load(vm: &Thread) -> Result<ExternModule> {
+    use self::std;
+
+    ExternModule::new(
+        vm,
+        record!{
+            get => primitive!(1 std::meta::prim::get)
+        },
+    )
+}
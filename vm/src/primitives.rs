@@ -4,12 +4,13 @@ use std::string::String as StdString;
 use std::str::FromStr;
 
 use {Error, ExternModule, Variants};
-use api::{generic, primitive, Array, Generic, Getable, Pushable, RuntimeResult, ValueRef, WithVM};
+use api::{generic, primitive, Array, FunctionRef, Generic, Getable, OpaqueValue, Pushable,
+          RuntimeResult, ValueRef, WithVM};
 use api::generic::A;
 use gc::{DataDef, Gc, Traverseable, WriteOnly};
 use Result;
-use vm::{Status, Thread};
-use value::{Def, GcStr, Repr, ValueArray, ValueRepr};
+use vm::{RootedThread, Status, Thread};
+use value::{ArrayDef, Def, GcStr, Repr, ValueArray, ValueRepr};
 use stack::StackFrame;
 use thread::ThreadInternal;
 use types::VmInt;
@@ -93,6 +94,34 @@ pub mod array {
             ))
         }
     }
+
+    /// Creates an array of `len` elements where the element at index `i` is `f i`
+    pub fn generate<'vm>(
+        WithVM { vm, value: len }: WithVM<'vm, VmInt>,
+        mut f: FunctionRef<'vm, fn(VmInt) -> OpaqueValue<RootedThread, generic::A>>,
+    ) -> RuntimeResult<Array<'vm, Generic<generic::A>>, StdString> {
+        // Each element stays rooted until the array has been allocated as calling `f` may collect
+        let elems = match (0..len).map(|i| f.call(i)).collect::<Result<Vec<_>>>() {
+            Ok(elems) => elems,
+            Err(err) => return RuntimeResult::Panic(format!("{}", err)),
+        };
+        let value = {
+            let values: Vec<_> = elems.iter().map(|elem| unsafe { elem.get_value() }).collect();
+            let mut context = vm.context();
+            match context.alloc(ArrayDef(&values)) {
+                Ok(value) => value,
+                Err(err) => return RuntimeResult::Panic(format!("{}", err)),
+            }
+        };
+        // The roots of the elements must be released before the array is rooted
+        drop(elems);
+        unsafe {
+            RuntimeResult::Return(Getable::from_value(
+                vm,
+                Variants::new(&ValueRepr::Array(value).into()),
+            ))
+        }
+    }
 }
 
 mod string {
@@ -136,7 +165,11 @@ mod string {
             let mut context = vm.context();
             let result = context.alloc(StrAppend { lhs: lhs, rhs: rhs });
             match result {
-                Ok(x) => GcStr::from_utf8_unchecked(x),
+                Ok(x) => {
+                    let s = GcStr::from_utf8_unchecked(x);
+                    context.gc.add_deduplicated_str(s);
+                    s
+                }
                 Err(err) => return RuntimeResult::Panic(err),
             }
         };
@@ -381,7 +414,8 @@ pub fn load_array(vm: &Thread) -> Result<ExternModule> {
         record! {
             len => primitive!(1 std::array::prim::len),
             index => primitive!(2 std::array::prim::index),
-            append => primitive!(2 std::array::prim::append)
+            append => primitive!(2 std::array::prim::append),
+            generate => primitive!(2 std::array::prim::generate)
         },
     )
 }
@@ -455,6 +489,8 @@ pub fn load(vm: &Thread) -> Result<ExternModule> {
         named_primitive!(2, "@string_eq", <str as PartialEq>::eq),
     )?;
 
+    vm.define_global("@seq", named_primitive!(2, "@seq", ::lazy::seq))?;
+
     ExternModule::new(
         vm,
         record! {
@@ -0,0 +1,332 @@
+//! Profiling of the gluon code running on a thread.
+//!
+//! `start_sampling` starts a timer which periodically asks the thread to record its call stack.
+//! The stack is recorded at the next call or return which the running code makes, the same place
+//! where `Thread::interrupt` is noticed. The collected `Samples` can be written in the folded
+//! stack format which `inferno` and `flamegraph.pl` turn into flame graphs.
+//!
+//! For measurements which sampling is too coarse for, `enable` makes every thread count the
+//! instructions which each gluon function executes and the objects it allocates. The counters are
+//! exact but slow down the execution noticeably, they are retrieved as a table with `report`.
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+use std::thread::{self as os_thread, JoinHandle};
+use std::time::Duration;
+
+use base::fnv::FnvMap;
+use base::symbol::Symbol;
+
+use gc::GcStats;
+use stack::{Stack, StacktraceFrame};
+use thread::{RootedThread, Thread};
+
+/// The profiling state of a single thread
+#[derive(Debug, Default)]
+pub(crate) struct ThreadProfile {
+    /// Set by the timer when the next call or return should record the stack
+    sample: AtomicBool,
+    /// The number of times each folded stack has been sampled while sampling is enabled
+    samples: Mutex<Option<BTreeMap<String, usize>>>,
+}
+
+impl ThreadProfile {
+    #[inline]
+    pub(crate) fn sample_requested(&self) -> bool {
+        self.sample.load(Ordering::Relaxed)
+    }
+
+    /// Records the frames of `stack` as a sample
+    pub(crate) fn record_sample(&self, stack: &Stack) {
+        self.sample.store(false, Ordering::Relaxed);
+        let folded = fold_stack(stack);
+        // Only frames which are not functions (such as the one entered when calling into the vm)
+        // are on the stack, there is nothing to attribute the sample to
+        if folded.is_empty() {
+            return;
+        }
+        if let Some(ref mut samples) = *self.samples.lock().unwrap() {
+            *samples.entry(folded).or_insert(0) += 1;
+        }
+    }
+}
+
+fn frame_name(frame: &StacktraceFrame) -> String {
+    if frame.is_extern() {
+        frame.name.definition_name().to_string()
+    } else {
+        format!("{}.{}", frame.module, frame.name.declared_name())
+    }
+}
+
+/// Returns the frames of `stack` from the outermost to the innermost, separated by `;`
+fn fold_stack(stack: &Stack) -> String {
+    let mut folded = String::new();
+    for frame in stack.stacktrace(0).frames.iter().filter_map(|frame| frame.as_ref()) {
+        if !folded.is_empty() {
+            folded.push(';');
+        }
+        // `;` separates the frames and the last space separates the count so neither may be part
+        // of a name
+        folded.extend(frame_name(frame).chars().map(|c| match c {
+            ';' | ' ' => '_',
+            c => c,
+        }));
+    }
+    folded
+}
+
+/// The call stacks sampled by `SamplingProfiler`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Samples {
+    /// The number of times each stack were sampled. The frames of a stack are separated by `;`
+    /// and ordered from the outermost to the innermost frame.
+    pub stacks: BTreeMap<String, usize>,
+}
+
+impl Samples {
+    /// The total number of samples
+    pub fn len(&self) -> usize {
+        self.stacks.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+
+    /// Writes the samples in the folded stack format, one `frame;frame;frame count` line per
+    /// stack
+    pub fn write_folded<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        for (stack, count) in &self.stacks {
+            writeln!(writer, "{} {}", stack, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Samples the call stack of a thread until `stop` is called
+pub struct SamplingProfiler {
+    thread: RootedThread,
+    stop: Arc<AtomicBool>,
+    timer: JoinHandle<()>,
+}
+
+/// Starts sampling the call stack of `thread` every `interval`. Only one profiler should sample
+/// a thread at a time.
+///
+/// ```
+/// # extern crate gluon;
+/// # use std::time::Duration;
+/// # use gluon::{new_vm, Compiler};
+/// # use gluon::vm::profile;
+/// # fn main() {
+/// let vm = new_vm();
+/// let profiler = profile::start_sampling(&vm, Duration::from_millis(1));
+/// let expr = r#"
+///     let loop n : Int -> Int = if n #Int== 0 then 0 else loop (n #Int- 1)
+///     loop 100000
+/// "#;
+/// Compiler::new()
+///     .implicit_prelude(false)
+///     .run_expr::<i32>(&vm, "loop", expr)
+///     .unwrap();
+/// let samples = profiler.stop();
+/// let mut folded = Vec::new();
+/// samples.write_folded(&mut folded).unwrap();
+/// # }
+/// ```
+pub fn start_sampling(thread: &Thread, interval: Duration) -> SamplingProfiler {
+    *thread.profile().samples.lock().unwrap() = Some(BTreeMap::new());
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let timer = {
+        let thread = thread.root_thread();
+        let stop = stop.clone();
+        os_thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                os_thread::sleep(interval);
+                thread.profile().sample.store(true, Ordering::Relaxed);
+            }
+        })
+    };
+    SamplingProfiler {
+        thread: thread.root_thread(),
+        stop,
+        timer,
+    }
+}
+
+impl SamplingProfiler {
+    /// Stops sampling and returns the samples which were collected
+    pub fn stop(self) -> Samples {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.timer.join();
+        let profile = self.thread.profile();
+        profile.sample.store(false, Ordering::Relaxed);
+        let stacks = profile.samples.lock().unwrap().take().unwrap_or_default();
+        Samples { stacks }
+    }
+}
+
+static COUNTING: AtomicBool = ATOMIC_BOOL_INIT;
+static INIT: Once = ONCE_INIT;
+static mut COUNTERS: *const Mutex<FnvMap<String, FunctionCounters>> = 0 as *const _;
+
+fn counters() -> &'static Mutex<FnvMap<String, FunctionCounters>> {
+    unsafe {
+        INIT.call_once(|| {
+            COUNTERS = Box::into_raw(Box::new(Mutex::new(FnvMap::default())));
+        });
+        &*COUNTERS
+    }
+}
+
+/// Starts counting the instructions and allocations of each gluon function, discarding anything
+/// counted previously
+pub fn enable() {
+    counters().lock().unwrap().clear();
+    COUNTING.store(true, Ordering::Relaxed);
+}
+
+/// Stops counting. What has been counted so far is still returned by `report`.
+pub fn disable() {
+    COUNTING.store(false, Ordering::Relaxed);
+}
+
+#[inline]
+pub(crate) fn counting() -> bool {
+    COUNTING.load(Ordering::Relaxed)
+}
+
+/// What a single gluon function has executed while counting were enabled
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunctionCounters {
+    /// The module and name of the function (`std.list.map`)
+    pub name: String,
+    pub calls: usize,
+    pub instructions: usize,
+    /// The number of objects which the instructions of the function allocated. Objects
+    /// allocated by extern functions which it called are not included.
+    pub allocations: usize,
+    pub allocated_bytes: usize,
+}
+
+/// The counters of every function which has executed while counting were enabled, ordered by the
+/// number of instructions they executed with the most executed function first
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub functions: Vec<FunctionCounters>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.functions
+            .iter()
+            .map(|function| function.name.len())
+            .chain(Some("function".len()))
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            f,
+            "{:<width$} {:>10} {:>14} {:>12} {:>14}",
+            "function",
+            "calls",
+            "instructions",
+            "allocations",
+            "bytes",
+            width = width
+        )?;
+        for function in &self.functions {
+            writeln!(
+                f,
+                "{:<width$} {:>10} {:>14} {:>12} {:>14}",
+                function.name,
+                function.calls,
+                function.instructions,
+                function.allocations,
+                function.allocated_bytes,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns what has been counted since counting were enabled
+///
+/// ```
+/// # extern crate gluon;
+/// # use gluon::{new_vm, Compiler};
+/// # use gluon::vm::profile;
+/// # fn main() {
+/// let vm = new_vm();
+/// profile::enable();
+/// let expr = r#"
+///     let loop n : Int -> Int = if n #Int== 0 then 0 else loop (n #Int- 1)
+///     loop 100
+/// "#;
+/// Compiler::new()
+///     .implicit_prelude(false)
+///     .run_expr::<i32>(&vm, "loop", expr)
+///     .unwrap();
+/// profile::disable();
+/// let report = profile::report();
+/// let function = report.functions.iter().find(|f| f.name == "loop.loop").unwrap();
+/// assert!(function.calls > 100);
+/// println!("{}", report);
+/// # }
+/// ```
+pub fn report() -> Report {
+    let mut functions: Vec<_> = counters().lock().unwrap().values().cloned().collect();
+    functions.sort_by_key(|function| (Reverse(function.instructions), function.name.clone()));
+    Report { functions }
+}
+
+/// The counters of the dispatch loop when a function started or resumed executing
+pub(crate) struct Start {
+    instructions: usize,
+    allocations: usize,
+    allocated_bytes: usize,
+    call: bool,
+}
+
+impl Start {
+    pub(crate) fn new(instruction_index: usize, instructions: usize, gc_stats: &GcStats) -> Start {
+        Start {
+            instructions,
+            allocations: gc_stats.allocations,
+            allocated_bytes: gc_stats.allocated_bytes,
+            call: instruction_index == 0,
+        }
+    }
+
+    /// Adds what the function `name` of `module` executed since `self` were created
+    pub(crate) fn finish(
+        self,
+        module: &str,
+        name: &Symbol,
+        instructions: usize,
+        gc_stats: &GcStats,
+    ) {
+        let name = format!("{}.{}", module, name.declared_name());
+        let mut counters = counters().lock().unwrap();
+        let function = counters
+            .entry(name.clone())
+            .or_insert_with(|| FunctionCounters {
+                name,
+                ..FunctionCounters::default()
+            });
+        if self.call {
+            function.calls += 1;
+        }
+        function.instructions += instructions.wrapping_sub(self.instructions);
+        function.allocations += gc_stats.allocations - self.allocations;
+        function.allocated_bytes += gc_stats.allocated_bytes - self.allocated_bytes;
+    }
+}
@@ -0,0 +1,842 @@
+//! Experimental register based instruction set.
+//!
+//! The stack machine in `thread` spends a large part of its instructions moving values to the top
+//! of the stack just so that the next instruction can consume them (`Push 0, Push 1, AddInt`).
+//! The instructions in this module instead name the registers (stack slots of the current frame)
+//! they read and write, so the example above becomes a single `Binary` instruction. Instructions
+//! which read several consecutive registers, such as calls, take the values which are not yet
+//! stored in those registers as operands instead of requiring a `Load` for each of them.
+//!
+//! Register `n` is the same slot as stack index `n` of the frame so a function converted with
+//! `convert_function` keeps the frame layout of the stack based version. With the `register_vm`
+//! feature the interpreter converts each function the first time it is called, including those
+//! loaded from precompiled modules, and executes the register instructions instead. Functions
+//! which can't be converted are still executed as stack instructions.
+use std::collections::hash_map::Entry;
+#[cfg(feature = "register_vm")]
+use std::fmt;
+#[cfg(feature = "register_vm")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use base::fnv::FnvMap;
+
+use compiler::CompiledFunction;
+use types::*;
+
+pub type Register = VmIndex;
+
+/// A value read by a register instruction. Constants and the upvariables of the executing
+/// function, which can't change while it executes, can be used directly without first being
+/// loaded into a register.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Operand {
+    Register(Register),
+    UpVar(VmIndex),
+    Int(VmInt),
+    Byte(u8),
+    Float(f64),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BinaryOp {
+    AddInt,
+    SubtractInt,
+    MultiplyInt,
+    DivideInt,
+    IntLT,
+    IntEQ,
+
+    AddByte,
+    SubtractByte,
+    MultiplyByte,
+    DivideByte,
+    ByteLT,
+    ByteEQ,
+
+    AddFloat,
+    SubtractFloat,
+    MultiplyFloat,
+    DivideFloat,
+    FloatLT,
+    FloatEQ,
+
+    StringEQ,
+}
+
+impl BinaryOp {
+    fn from_instruction(instruction: Instruction) -> Option<BinaryOp> {
+        Some(match instruction {
+            AddInt => BinaryOp::AddInt,
+            SubtractInt => BinaryOp::SubtractInt,
+            MultiplyInt => BinaryOp::MultiplyInt,
+            DivideInt => BinaryOp::DivideInt,
+            IntLT => BinaryOp::IntLT,
+            IntEQ => BinaryOp::IntEQ,
+
+            AddByte => BinaryOp::AddByte,
+            SubtractByte => BinaryOp::SubtractByte,
+            MultiplyByte => BinaryOp::MultiplyByte,
+            DivideByte => BinaryOp::DivideByte,
+            ByteLT => BinaryOp::ByteLT,
+            ByteEQ => BinaryOp::ByteEQ,
+
+            AddFloat => BinaryOp::AddFloat,
+            SubtractFloat => BinaryOp::SubtractFloat,
+            MultiplyFloat => BinaryOp::MultiplyFloat,
+            DivideFloat => BinaryOp::DivideFloat,
+            FloatLT => BinaryOp::FloatLT,
+            FloatEQ => BinaryOp::FloatEQ,
+
+            StringEQ => BinaryOp::StringEQ,
+            _ => return None,
+        })
+    }
+}
+
+/// Instructions of the register based virtual machine. Instructions which take several
+/// consecutive values (calls and constructors) read them from `start..start + args`. If their
+/// `operands` field is set the values `Converted::operands[operands..operands + args]` are first
+/// stored in those registers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RegInstruction {
+    /// Stores `value` in `dst`
+    Load { dst: Register, value: Operand },
+    /// Stores the string at `index` in the currently executing function in `dst`
+    LoadString { dst: Register, index: VmIndex },
+    /// Calls the function in `function` with the `args` registers following it. The result is
+    /// stored in `function`. The operands, if any, include the function itself.
+    Call {
+        function: Register,
+        args: VmIndex,
+        operands: Option<VmIndex>,
+    },
+    /// Tailcalls the function in `function`. See `Call`.
+    TailCall {
+        function: Register,
+        args: VmIndex,
+        operands: Option<VmIndex>,
+    },
+    Construct {
+        dst: Register,
+        tag: VmIndex,
+        args: VmIndex,
+        operands: Option<VmIndex>,
+    },
+    ConstructRecord {
+        dst: Register,
+        record: VmIndex,
+        args: VmIndex,
+        operands: Option<VmIndex>,
+    },
+    ConstructArray {
+        dst: Register,
+        args: VmIndex,
+        operands: Option<VmIndex>,
+    },
+    /// Stores the field at `offset` of the object in `src` in `dst`
+    GetOffset {
+        dst: Register,
+        src: Register,
+        offset: VmIndex,
+    },
+    /// Stores the field named by the string at `index` of the object in `src` in `dst`
+    GetField {
+        dst: Register,
+        src: Register,
+        index: VmIndex,
+    },
+    /// Stores the `fields` fields of the data in `src` in `dst..dst + fields`
+    Split {
+        dst: Register,
+        src: Register,
+        fields: VmIndex,
+    },
+    /// Stores `True` in `dst` if the value in `src` is tagged with `tag`, otherwise `False`
+    TestTag {
+        dst: Register,
+        src: Register,
+        tag: VmTag,
+    },
+    Jump(VmIndex),
+    /// Jumps to `target` if `cond` is `True`
+    CJump { cond: Operand, target: VmIndex },
+    MakeClosure {
+        dst: Register,
+        function_index: VmIndex,
+        upvars: VmIndex,
+        operands: Option<VmIndex>,
+    },
+    NewClosure {
+        dst: Register,
+        function_index: VmIndex,
+        upvars: VmIndex,
+    },
+    /// Fills the closure in `closure` with the `upvars` registers following it
+    CloseClosure {
+        closure: Register,
+        upvars: VmIndex,
+        operands: Option<VmIndex>,
+    },
+    Binary {
+        op: BinaryOp,
+        dst: Register,
+        lhs: Operand,
+        rhs: Operand,
+    },
+    /// Returns `value` from the currently executing function
+    Return(Operand),
+}
+
+/// The register instructions of a function
+#[derive(Debug, PartialEq)]
+pub struct Converted {
+    pub instructions: Vec<RegInstruction>,
+    /// The values which instructions with an `operands` field store before they execute
+    pub operands: Vec<Operand>,
+}
+
+/// A `CompiledFunction` whose instructions have been converted into register instructions
+#[derive(Debug)]
+pub struct RegisterFunction {
+    pub code: Converted,
+    /// The converted functions of `CompiledFunction::inner_functions`, `None` if the function
+    /// could not be converted
+    pub inner_functions: Vec<Option<RegisterFunction>>,
+}
+
+/// Converts `function` and all of its inner functions into register instructions. Returns `None`
+/// if the instructions of `function` could not be converted (see `convert`).
+pub fn convert_function(function: &CompiledFunction) -> Option<RegisterFunction> {
+    Some(RegisterFunction {
+        code: convert(
+            function.args,
+            &function.instructions,
+            &function.split_fields,
+        )?,
+        inner_functions: function
+            .inner_functions
+            .iter()
+            .map(convert_function)
+            .collect(),
+    })
+}
+
+/// Returns the number of stack instructions and register instructions in `function` and its inner
+/// functions. Functions which can't be converted are left out of both counts.
+pub fn instruction_counts(function: &CompiledFunction) -> (usize, usize) {
+    let converted = convert(
+        function.args,
+        &function.instructions,
+        &function.split_fields,
+    );
+    let counts = match converted {
+        Some(converted) => (function.instructions.len(), converted.instructions.len()),
+        None => (0, 0),
+    };
+    function
+        .inner_functions
+        .iter()
+        .map(instruction_counts)
+        .fold(counts, |(stack, register), (inner_stack, inner_register)| {
+            (stack + inner_stack, register + inner_register)
+        })
+}
+
+#[cfg(feature = "register_vm")]
+const UNCONVERTED: usize = 0;
+#[cfg(feature = "register_vm")]
+const UNSUPPORTED: usize = 1;
+
+/// The register instructions of a `BytecodeFunction`, converted the first time they are needed
+#[cfg(feature = "register_vm")]
+#[derive(Default)]
+pub(crate) struct RegisterCode {
+    /// `UNCONVERTED`, `UNSUPPORTED` or the address of the `Converted` instructions
+    code: AtomicUsize,
+}
+
+#[cfg(feature = "register_vm")]
+impl RegisterCode {
+    /// Returns the register instructions of a function taking `args` arguments, converting
+    /// `instructions` if they have not been converted yet. Returns `None` if they can't be.
+    pub(crate) fn get(
+        &self,
+        args: VmIndex,
+        instructions: &[Instruction],
+        split_fields: &[VmIndex],
+    ) -> Option<&Converted> {
+        let mut code = self.code.load(Ordering::Acquire);
+        if code == UNCONVERTED {
+            let converted = match convert(args, instructions, split_fields) {
+                Some(converted) => Box::into_raw(Box::new(converted)) as usize,
+                None => UNSUPPORTED,
+            };
+            code = match self.code
+                .compare_exchange(UNCONVERTED, converted, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => converted,
+                Err(existing) => {
+                    // Another thread converted the function first
+                    if converted != UNSUPPORTED {
+                        unsafe {
+                            drop(Box::from_raw(converted as *mut Converted));
+                        }
+                    }
+                    existing
+                }
+            };
+        }
+        if code == UNSUPPORTED {
+            None
+        } else {
+            unsafe { Some(&*(code as *const Converted)) }
+        }
+    }
+}
+
+#[cfg(feature = "register_vm")]
+impl Drop for RegisterCode {
+    fn drop(&mut self) {
+        let code = *self.code.get_mut();
+        if code != UNCONVERTED && code != UNSUPPORTED {
+            unsafe {
+                drop(Box::from_raw(code as *mut Converted));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "register_vm")]
+impl fmt::Debug for RegisterCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = match self.code.load(Ordering::Relaxed) {
+            UNCONVERTED => "unconverted",
+            UNSUPPORTED => "unsupported",
+            _ => "converted",
+        };
+        write!(f, "RegisterCode({})", state)
+    }
+}
+
+// The converted instructions do not affect what a function does
+#[cfg(feature = "register_vm")]
+impl PartialEq for RegisterCode {
+    fn eq(&self, _: &RegisterCode) -> bool {
+        true
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Slot {
+    /// The value is stored in the register of the slot
+    Stored,
+    /// The value has not been written to the register of the slot yet and is read from the
+    /// operand instead
+    Pending(Operand),
+}
+
+struct Converter {
+    stack: Vec<Slot>,
+    out: Vec<RegInstruction>,
+    operands: Vec<Operand>,
+}
+
+impl Converter {
+    fn depth(&self) -> Register {
+        self.stack.len() as Register
+    }
+
+    fn push(&mut self, slot: Slot) {
+        self.stack.push(slot);
+    }
+
+    fn store(&mut self, register: Register) {
+        let slot = &mut self.stack[register as usize];
+        if let Slot::Pending(value) = *slot {
+            self.out.push(RegInstruction::Load {
+                dst: register,
+                value,
+            });
+            *slot = Slot::Stored;
+        }
+    }
+
+    /// Writes all pending values from `start` and up to their registers
+    fn store_from(&mut self, start: Register) {
+        for register in start..self.depth() {
+            self.store(register);
+        }
+    }
+
+    fn operand(&self, register: Register) -> Operand {
+        match self.stack[register as usize] {
+            Slot::Stored => Operand::Register(register),
+            Slot::Pending(value) => value,
+        }
+    }
+
+    /// Returns the register holding the value at `register` which may be an earlier register if
+    /// the value is a pending copy
+    fn register(&mut self, register: Register) -> Register {
+        match self.operand(register) {
+            Operand::Register(source) => source,
+            _ => {
+                self.store(register);
+                register
+            }
+        }
+    }
+
+    /// Returns the operands of an instruction which reads the `count` registers from `start`, or
+    /// `None` if all of those values are already stored in their registers. Once the instruction
+    /// has executed all of the registers are stored.
+    fn operands(&mut self, start: Register, count: VmIndex) -> Option<VmIndex> {
+        let slots = &mut self.stack[start as usize..(start + count) as usize];
+        if slots.iter().all(|slot| *slot == Slot::Stored) {
+            return None;
+        }
+        let index = self.operands.len() as VmIndex;
+        for (register, slot) in (start..).zip(slots) {
+            self.operands.push(match *slot {
+                Slot::Stored => Operand::Register(register),
+                Slot::Pending(value) => value,
+            });
+            *slot = Slot::Stored;
+        }
+        Some(index)
+    }
+
+    /// Pops `n` values and pushes the value stored by the instruction which consumed them
+    fn replace_top(&mut self, n: VmIndex) -> Register {
+        let dst = self.depth() - n;
+        self.stack.truncate(dst as usize);
+        self.stack.push(Slot::Stored);
+        dst
+    }
+}
+
+/// Converts the stack instructions of a function taking `args` arguments into register
+/// instructions. `split_fields` holds the number of fields each `Split` instruction pushes, in
+/// the order the instructions appear (see `CompiledFunction::split_fields`).
+///
+/// Returns `None` if the stack depth of some instruction can't be determined, which is the case
+/// if `split_fields` does not have an entry for every `Split`, for instance in functions which
+/// were compiled before the field counts were recorded.
+pub fn convert(
+    args: VmIndex,
+    instructions: &[Instruction],
+    split_fields: &[VmIndex],
+) -> Option<Converted> {
+    // Record which instructions are jumped to, the values on the stack must all be stored in
+    // their registers at those instructions
+    let mut jump_depths = FnvMap::default();
+    let mut splits = 0;
+    for &instruction in instructions {
+        match instruction {
+            Jump(target) | CJump(target) => {
+                jump_depths.insert(target as usize, None);
+            }
+            Split => splits += 1,
+            _ => (),
+        }
+    }
+    if splits != split_fields.len() {
+        return None;
+    }
+    let mut split_fields = split_fields.iter();
+
+    let mut converter = Converter {
+        stack: vec![Slot::Stored; args as usize],
+        out: Vec::new(),
+        operands: Vec::new(),
+    };
+    let mut reachable = true;
+    // Maps the index of each stack instruction to its register instruction
+    let mut indexes = Vec::with_capacity(instructions.len() + 1);
+    for (index, &instruction) in instructions.iter().enumerate() {
+        if let Some(&depth) = jump_depths.get(&index) {
+            if reachable {
+                converter.store_from(0);
+            } else {
+                let depth = depth?;
+                converter.stack = vec![Slot::Stored; depth];
+            }
+            reachable = true;
+        }
+        indexes.push(converter.out.len() as VmIndex);
+        if !reachable {
+            continue;
+        }
+
+        match instruction {
+            PushInt(i) => converter.push(Slot::Pending(Operand::Int(i))),
+            PushByte(b) => converter.push(Slot::Pending(Operand::Byte(b))),
+            PushFloat(f) => converter.push(Slot::Pending(Operand::Float(f))),
+            Push(i) => {
+                let value = converter.operand(i);
+                converter.push(Slot::Pending(value));
+            }
+            PushString(index) => {
+                let dst = converter.depth();
+                converter.out.push(RegInstruction::LoadString { dst, index });
+                converter.push(Slot::Stored);
+            }
+            PushUpVar(index) => converter.push(Slot::Pending(Operand::UpVar(index))),
+            Call(args) | TailCall(args) => {
+                let function = converter.depth() - args - 1;
+                let operands = converter.operands(function, args + 1);
+                converter.out.push(match instruction {
+                    Call(_) => RegInstruction::Call {
+                        function,
+                        args,
+                        operands,
+                    },
+                    _ => RegInstruction::TailCall {
+                        function,
+                        args,
+                        operands,
+                    },
+                });
+                converter.replace_top(args + 1);
+                if let TailCall(_) = instruction {
+                    reachable = false;
+                }
+            }
+            Construct { tag, args } => {
+                let dst = converter.depth() - args;
+                let operands = converter.operands(dst, args);
+                converter.out.push(RegInstruction::Construct {
+                    dst,
+                    tag,
+                    args,
+                    operands,
+                });
+                converter.replace_top(args);
+            }
+            ConstructRecord { record, args } => {
+                let dst = converter.depth() - args;
+                let operands = converter.operands(dst, args);
+                converter.out.push(RegInstruction::ConstructRecord {
+                    dst,
+                    record,
+                    args,
+                    operands,
+                });
+                converter.replace_top(args);
+            }
+            ConstructArray(args) => {
+                let dst = converter.depth() - args;
+                let operands = converter.operands(dst, args);
+                converter.out.push(RegInstruction::ConstructArray {
+                    dst,
+                    args,
+                    operands,
+                });
+                converter.replace_top(args);
+            }
+            GetOffset(offset) | GetField(offset) => {
+                let dst = converter.depth() - 1;
+                let src = converter.register(dst);
+                converter.out.push(match instruction {
+                    GetOffset(_) => RegInstruction::GetOffset { dst, src, offset },
+                    _ => RegInstruction::GetField {
+                        dst,
+                        src,
+                        index: offset,
+                    },
+                });
+                converter.replace_top(1);
+            }
+            Split => {
+                let fields = *split_fields.next()?;
+                let dst = converter.depth() - 1;
+                let src = converter.register(dst);
+                converter
+                    .out
+                    .push(RegInstruction::Split { dst, src, fields });
+                converter.stack.truncate(dst as usize);
+                for _ in 0..fields {
+                    converter.push(Slot::Stored);
+                }
+            }
+            TestTag(tag) => {
+                let dst = converter.depth();
+                let src = converter.register(dst - 1);
+                converter
+                    .out
+                    .push(RegInstruction::TestTag { dst, src, tag });
+                converter.push(Slot::Stored);
+            }
+            Jump(target) => {
+                converter.store_from(0);
+                record_depth(&mut jump_depths, target, converter.depth())?;
+                converter.out.push(RegInstruction::Jump(target));
+                reachable = false;
+            }
+            CJump(target) => {
+                let cond = converter.operand(converter.depth() - 1);
+                converter.stack.pop();
+                converter.store_from(0);
+                record_depth(&mut jump_depths, target, converter.depth())?;
+                converter.out.push(RegInstruction::CJump { cond, target });
+            }
+            Pop(n) => {
+                let depth = converter.depth() - n;
+                converter.stack.truncate(depth as usize);
+            }
+            Slide(n) => {
+                let value = converter.operand(converter.depth() - 1);
+                let depth = converter.depth() - n;
+                converter.stack.truncate(depth as usize);
+                let dst = depth - 1;
+                match value {
+                    // The register of the value is removed from the stack and may be overwritten
+                    // so the value can't be left pending
+                    Operand::Register(src) if src >= dst => {
+                        if src != dst {
+                            converter.out.push(RegInstruction::Load { dst, value });
+                        }
+                        converter.stack[dst as usize] = Slot::Stored;
+                    }
+                    _ => converter.stack[dst as usize] = Slot::Pending(value),
+                }
+            }
+            MakeClosure {
+                function_index,
+                upvars,
+            } => {
+                let dst = converter.depth() - upvars;
+                let operands = converter.operands(dst, upvars);
+                converter.out.push(RegInstruction::MakeClosure {
+                    dst,
+                    function_index,
+                    upvars,
+                    operands,
+                });
+                converter.replace_top(upvars);
+            }
+            NewClosure {
+                function_index,
+                upvars,
+            } => {
+                let dst = converter.depth();
+                converter.out.push(RegInstruction::NewClosure {
+                    dst,
+                    function_index,
+                    upvars,
+                });
+                converter.push(Slot::Stored);
+            }
+            CloseClosure(upvars) => {
+                let closure = converter.depth() - upvars - 1;
+                converter.store(closure);
+                let operands = converter.operands(closure + 1, upvars);
+                converter.out.push(RegInstruction::CloseClosure {
+                    closure,
+                    upvars,
+                    operands,
+                });
+                converter.stack.truncate(closure as usize);
+            }
+            _ => {
+                let op = BinaryOp::from_instruction(instruction)
+                    .expect("Binary instruction");
+                let rhs = converter.operand(converter.depth() - 1);
+                let lhs = converter.operand(converter.depth() - 2);
+                let dst = converter.replace_top(2);
+                converter.out.push(RegInstruction::Binary { op, dst, lhs, rhs });
+            }
+        }
+    }
+
+    if let Some(&depth) = jump_depths.get(&instructions.len()) {
+        if reachable {
+            converter.store_from(0);
+        } else {
+            converter.stack = vec![Slot::Stored; depth?];
+        }
+        reachable = true;
+    }
+    indexes.push(converter.out.len() as VmIndex);
+    if reachable {
+        let value = converter.operand(converter.depth() - 1);
+        converter.out.push(RegInstruction::Return(value));
+    }
+
+    // Point the jumps at the converted instructions
+    for instruction in &mut converter.out {
+        match *instruction {
+            RegInstruction::Jump(ref mut target)
+            | RegInstruction::CJump { ref mut target, .. } => {
+                *target = indexes[*target as usize];
+            }
+            _ => (),
+        }
+    }
+    Some(Converted {
+        instructions: converter.out,
+        operands: converter.operands,
+    })
+}
+
+fn record_depth(
+    jump_depths: &mut FnvMap<usize, Option<usize>>,
+    target: VmIndex,
+    depth: Register,
+) -> Option<()> {
+    match jump_depths.entry(target as usize) {
+        Entry::Occupied(mut entry) => match *entry.get() {
+            // All jumps to the same instruction must agree on the depth of the stack
+            Some(previous) if previous != depth as usize => None,
+            _ => {
+                entry.insert(Some(depth as usize));
+                Some(())
+            }
+        },
+        Entry::Vacant(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert_instructions(args: VmIndex, instructions: &[Instruction]) -> Option<Converted> {
+        convert(args, instructions, &[])
+    }
+
+    #[test]
+    fn binary_operands_are_read_directly() {
+        // \x y -> x #Int+ y #Int* 2
+        let instructions = [Push(0), Push(1), PushInt(2), MultiplyInt, AddInt];
+        assert_eq!(
+            convert_instructions(2, &instructions),
+            Some(Converted {
+                instructions: vec![
+                    RegInstruction::Binary {
+                        op: BinaryOp::MultiplyInt,
+                        dst: 3,
+                        lhs: Operand::Register(1),
+                        rhs: Operand::Int(2),
+                    },
+                    RegInstruction::Binary {
+                        op: BinaryOp::AddInt,
+                        dst: 2,
+                        lhs: Operand::Register(0),
+                        rhs: Operand::Register(3),
+                    },
+                    RegInstruction::Return(Operand::Register(2)),
+                ],
+                operands: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn call_arguments_are_operands() {
+        // \f x -> f x 1
+        let instructions = [Push(0), Push(1), PushInt(1), Call(2)];
+        assert_eq!(
+            convert_instructions(2, &instructions),
+            Some(Converted {
+                instructions: vec![
+                    RegInstruction::Call {
+                        function: 2,
+                        args: 2,
+                        operands: Some(0),
+                    },
+                    RegInstruction::Return(Operand::Register(2)),
+                ],
+                operands: vec![Operand::Register(0), Operand::Register(1), Operand::Int(1)],
+            })
+        );
+    }
+
+    #[test]
+    fn jumps_are_remapped() {
+        // \x -> if x #Int< 0 then 0 else x
+        let instructions = [
+            Push(0),
+            PushInt(0),
+            IntLT,
+            CJump(6),
+            Push(0),
+            Jump(7),
+            PushInt(0),
+        ];
+        assert_eq!(
+            convert_instructions(1, &instructions),
+            Some(Converted {
+                instructions: vec![
+                    RegInstruction::Binary {
+                        op: BinaryOp::IntLT,
+                        dst: 1,
+                        lhs: Operand::Register(0),
+                        rhs: Operand::Int(0),
+                    },
+                    RegInstruction::CJump {
+                        cond: Operand::Register(1),
+                        target: 4,
+                    },
+                    RegInstruction::Load {
+                        dst: 1,
+                        value: Operand::Register(0),
+                    },
+                    RegInstruction::Jump(5),
+                    RegInstruction::Load {
+                        dst: 1,
+                        value: Operand::Int(0),
+                    },
+                    RegInstruction::Return(Operand::Register(1)),
+                ],
+                operands: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn slide_stores_values_from_removed_registers() {
+        // \x -> let y = x #Int+ 1 in y
+        let instructions = [Push(0), PushInt(1), AddInt, Push(1), Slide(1)];
+        assert_eq!(
+            convert_instructions(1, &instructions),
+            Some(Converted {
+                instructions: vec![
+                    RegInstruction::Binary {
+                        op: BinaryOp::AddInt,
+                        dst: 1,
+                        lhs: Operand::Register(0),
+                        rhs: Operand::Int(1),
+                    },
+                    RegInstruction::Return(Operand::Register(1)),
+                ],
+                operands: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn split_stores_the_fields_in_registers() {
+        // \x -> match x with | Some y -> y
+        let instructions = [Push(0), Split, Push(1), Slide(1)];
+        assert_eq!(
+            convert(1, &instructions, &[1]),
+            Some(Converted {
+                instructions: vec![
+                    RegInstruction::Split {
+                        dst: 1,
+                        src: 0,
+                        fields: 1,
+                    },
+                    RegInstruction::Return(Operand::Register(1)),
+                ],
+                operands: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn split_without_field_count_is_not_converted() {
+        assert_eq!(convert_instructions(1, &[Push(0), Split]), None);
+    }
+}
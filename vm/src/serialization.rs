@@ -405,6 +405,24 @@ pub mod typ {
     use base::types::ArcType;
     use base::symbol::Symbol;
 
+    /// Like `borrow` but for values containing types or symbols, which can also be deserialized
+    /// from the seed used for syntax trees
+    pub fn deserialize<'de, D, T>(seed: &mut DeSeed, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: DeserializeState<'de, ::base::serialization::Seed<Symbol, ArcType<Symbol>>>,
+    {
+        T::deserialize_state(&mut seed.base_seed, deserializer)
+    }
+
+    pub fn serialize<S, T>(value: &T, serializer: S, seed: &SeSeed) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: SerializeState<::base::serialization::SeSeed>,
+    {
+        value.serialize_state(serializer, &seed.node_to_id)
+    }
+
     impl ::std::borrow::Borrow<::base::serialization::Seed<Symbol, ArcType<Symbol>>> for DeSeed {
         fn borrow(&self) -> &::base::serialization::Seed<Symbol, ArcType<Symbol>> {
             &self.base_seed
@@ -683,13 +701,14 @@ impl<'de> DeserializeState<'de, DeSeed> for ExternFunction {
         }
 
         let partial = ExternFunction_::deserialize(deserializer)?;
-        // Wrap any operators with parens so that they are acceptable for `get_global`
+        // Wrap any operators with parens so that they are acceptable for `get_global`. The
+        // globals of the vm itself (`@error`, `@string_eq`, ...) are looked up as they are.
         let mut escaped_id = Cow::Borrowed("");
         let iter = partial
             .id
             .split(|c: char| c == '.')
             .map(|s| {
-                if s.chars()
+                if !s.starts_with('@') && s.chars()
                     .next()
                     .map_or(false, ::base::ast::is_operator_char)
                 {
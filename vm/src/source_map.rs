@@ -62,7 +62,7 @@ pub struct Local {
     pub index: VmIndex,
     #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::symbol"))]
     pub name: Symbol,
-    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::typ"))]
     pub typ: ArcType,
 }
 
@@ -194,11 +194,13 @@ impl Stack {
                         .line(frame.instruction_index);
                     Some(line.map(|line| StacktraceFrame {
                         name: closure.function.name.clone(),
+                        module: closure.function.debug_info.source_name.clone(),
                         line: line,
                     }))
                 }
                 State::Extern(ref ext) => Some(Some(StacktraceFrame {
                     name: ext.id.clone(),
+                    module: String::new(),
                     line: Line::from(0),
                 })),
                 State::Unknown => Some(None),
@@ -483,10 +485,21 @@ impl<'b> IndexMut<RangeFrom<VmIndex>> for StackFrame<'b> {
 
 #[derive(Debug, PartialEq)]
 pub struct StacktraceFrame {
+    /// The function executing in the frame
     pub name: Symbol,
+    /// The module the function were compiled from. Empty for extern functions.
+    pub module: String,
+    /// The line which is executing in the function. Always `0` for extern functions.
     pub line: Line,
 }
 
+impl StacktraceFrame {
+    pub fn is_extern(&self) -> bool {
+        self.module.is_empty()
+    }
+}
+
+/// The frames of a stack with the outermost frame first
 #[derive(Debug, PartialEq)]
 pub struct Stacktrace {
     pub frames: Vec<Option<StacktraceFrame>>,
@@ -497,11 +510,15 @@ impl fmt::Display for Stacktrace {
         writeln!(f, "Stacktrace:\n")?;
         for (i, frame) in self.frames.iter().enumerate() {
             match *frame {
+                Some(ref frame) if frame.is_extern() => {
+                    writeln!(f, "{}: {} <extern>", i, frame.name.declared_name())
+                }
                 Some(ref frame) => writeln!(
                     f,
-                    "{}: {}:Line {}",
+                    "{}: {} at {}:Line {}",
                     i,
                     frame.name.declared_name(),
+                    frame.module,
                     frame.line
                 ),
                 None => writeln!(f, "{}: <unknown>", i),
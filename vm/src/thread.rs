@@ -1,6 +1,6 @@
 //! The thread/vm type
 use std::any::Any;
-use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockWriteGuard};
 use std::cmp::Ordering;
 use std::fmt;
 use std::mem;
@@ -8,13 +8,14 @@ use std::ops::{Add, Deref, DerefMut, Div, Mul, Sub};
 use std::string::String as StdString;
 use std::result::Result as StdResult;
 use std::sync::Arc;
-use std::sync::atomic::{self, AtomicBool};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize, ATOMIC_USIZE_INIT};
 use std::usize;
 
 use futures::{Async, Future, Poll};
 use future::FutureValue;
 
 use base::metadata::Metadata;
+#[cfg(feature = "debug")]
 use base::pos::Line;
 use base::symbol::Symbol;
 use base::types::ArcType;
@@ -22,13 +23,22 @@ use base::types;
 
 use {Error, Result, Variants};
 use macros::MacroEnv;
+use profile::{self, ThreadProfile};
 use api::{Getable, Pushable, ValueRef, VmType};
+use compiler::CompiledModule;
+use core::CoreExpr;
+#[cfg(feature = "debug")]
 use compiler::UpvarInfo;
-use gc::{DataDef, Gc, GcPtr, Generation, Move};
-use source_map::LocalIter;
-use stack::{Frame, Lock, Stack, StackFrame, State};
+use gc::{DataDef, Gc, GcPtr, GcSettings, GcStats, Generation, Move};
+#[cfg(feature = "debug")]
+use source_map::{Local, LocalIter};
+#[cfg(feature = "debug")]
+use stack::Frame;
+use stack::{Lock, Stack, StackFrame, State};
+#[cfg(feature = "register_vm")]
+use register::{BinaryOp, Converted, Operand, RegInstruction};
 use types::*;
-use vm::{GlobalVmState, GlobalVmStateBuilder, VmEnv};
+use vm::{EnvLayer, GlobalVmState, GlobalVmStateBuilder, VmEnvInstance};
 use value::{BytecodeFunction, Callable, ClosureData, ClosureDataDef, ClosureInitDef, Def,
             ExternFunction, GcStr, PartialApplicationDataDef, RecordDef, Userdata, Value,
             ValueRepr};
@@ -74,6 +84,18 @@ where
     }
 }
 
+/// The result of `Thread::run_with_fuel`
+pub enum FuelResult<F>
+where
+    F: Future,
+{
+    /// The future completed before the fuel ran out
+    Done(F::Item),
+    /// The fuel ran out (or an extern function is waiting on a future). The execution continues
+    /// where it left off when the future is passed to `run_with_fuel` again.
+    Suspended(F),
+}
+
 /// Enum signaling a successful or unsuccess ful call to an extern function.
 /// If an error occured the error message is expected to be on the top of the stack.
 #[derive(Eq, PartialEq)]
@@ -181,13 +203,24 @@ impl<'vm> RootedValue<&'vm Thread> {
 /// A rooted userdata value
 pub struct Root<'vm, T: ?Sized + 'vm> {
     roots: &'vm RwLock<Vec<GcPtr<Traverseable + Send + Sync>>>,
+    rooted: GcPtr<Traverseable + Send + Sync>,
     ptr: *const T,
 }
 
 impl<'vm, T: ?Sized> Drop for Root<'vm, T> {
     fn drop(&mut self) {
-        // TODO not safe if the root changes order of being dropped with another root
-        self.roots.write().unwrap().pop();
+        // Roots are usually dropped in the reverse order of their creation but that is not
+        // guaranteed so remove the entry of this root instead of the last one
+        let address = |root: &GcPtr<Traverseable + Send + Sync>| {
+            &**root as *const (Traverseable + Send + Sync) as *const ()
+        };
+        let mut roots = self.roots.write().unwrap();
+        if let Some(index) = roots
+            .iter()
+            .rposition(|root| address(root) == address(&self.rooted))
+        {
+            roots.remove(index);
+        }
     }
 }
 
@@ -208,6 +241,69 @@ impl<'vm> Deref for RootStr<'vm> {
     }
 }
 
+/// A frame of roots for marshalling values between gluon and Rust. Each value retrieved through
+/// the scope is rooted once in the scope's frame, so the strings and userdata it contains can be
+/// borrowed as `&str` or `&T` instead of rooting each of them with `RootStr` or `Root`. All of
+/// the values are unrooted together when the scope is dropped.
+///
+/// The values of the scope are roots of the thread so collections may run while the scope is
+/// active, including during calls made through `Function::call_scoped`.
+pub struct RootScope<'vm> {
+    thread: &'vm Thread,
+    // Identifies the frame of this scope in `Thread::scope_roots`
+    frame: usize,
+}
+
+/// Counter used to give each `RootScope` a unique frame
+static NEXT_SCOPE_FRAME: AtomicUsize = ATOMIC_USIZE_INIT;
+
+impl<'vm> Drop for RootScope<'vm> {
+    fn drop(&mut self) {
+        // Scopes are usually dropped in the reverse order of their creation but that is not
+        // guaranteed so remove the frame of this scope instead of the last one
+        let mut frames = self.thread.scope_roots.write().unwrap();
+        if let Some(index) = frames.iter().rposition(|&(frame, _)| frame == self.frame) {
+            frames.remove(index);
+        }
+    }
+}
+
+impl<'vm> RootScope<'vm> {
+    /// Returns the thread of this scope. Values retrieved through the returned reference can not
+    /// outlive the scope.
+    pub fn thread<'s>(&'s self) -> &'s Thread {
+        self.thread
+    }
+
+    /// Retrieves the global called `name`. Unlike `Thread::get_global` the returned value may
+    /// borrow from the scope.
+    pub fn get_global<'s, T>(&'s self, name: &str) -> Result<T>
+    where
+        T: Getable<'s> + VmType,
+    {
+        let value = self.thread.get_global_value::<T>(name)?;
+        Ok(self.marshal(value))
+    }
+
+    /// Roots `value` in the frame of this scope and converts it into `T`, which may borrow from
+    /// the scope
+    pub(crate) fn marshal<'s, T>(&'s self, value: Value) -> T
+    where
+        T: Getable<'s>,
+    {
+        {
+            let mut frames = self.thread.scope_roots.write().unwrap();
+            let frame = frames
+                .iter_mut()
+                .rev()
+                .find(|&&mut (frame, _)| frame == self.frame)
+                .expect("Frame of the scope");
+            frame.1.push(value.clone());
+        }
+        unsafe { T::from_value_unsafe(self.thread, Variants::new(&value)) }
+    }
+}
+
 struct Roots<'b> {
     vm: GcPtr<Thread>,
     stack: &'b Stack,
@@ -254,7 +350,10 @@ impl<'b> ::gc::CollectScope for Roots<'b> {
                     Vec::push(&mut stack, *child);
                 }
 
-                let context = thread.context.lock().unwrap();
+                let mut context = thread.context.lock().unwrap();
+                // The values of the child are about to be marked so any sweep it has left must be
+                // done first
+                context.gc.finish_sweep();
 
                 // Since we locked the context we need to scan the thread using `Roots` rather than
                 // letting it be scanned normally
@@ -271,7 +370,7 @@ impl<'b> ::gc::CollectScope for Roots<'b> {
 
             // `sweep` all child gcs
             for (_, mut context, _) in locks {
-                context.gc.sweep();
+                context.gc.sweep_marked_by(gc);
             }
         }
     }
@@ -296,6 +395,10 @@ pub struct Thread {
     roots: RwLock<Vec<GcPtr<Traverseable + Send + Sync>>>,
     #[cfg_attr(feature = "serde_derive", serde(state))]
     rooted_values: RwLock<Vec<Value>>,
+    /// The frames of the active `RootScope`s of this thread, holding the values which have been
+    /// retrieved through each scope
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    scope_roots: RwLock<Vec<(usize, Vec<Value>)>>,
     /// All threads which this thread have spawned in turn. Necessary as this thread needs to scan
     /// the roots of all its children as well since those may contain references to this threads
     /// garbage collected values
@@ -305,6 +408,16 @@ pub struct Thread {
     context: Mutex<Context>,
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     interrupt: AtomicBool,
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    profile: ThreadProfile,
+    // The globals of a thread created with `fork` (and the threads spawned from it), layered over
+    // the globals of the thread it was forked from. If this is `None` the globals are the ones
+    // shared through `global_state`
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    env: Option<Arc<EnvLayer>>,
+    // Values attached by the host through `call_with_context`, innermost last
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    host_contexts: RwLock<Vec<Arc<Any + Send + Sync>>>,
 }
 
 impl fmt::Debug for Thread {
@@ -322,7 +435,11 @@ impl VmType for Thread {
 impl Traverseable for Thread {
     fn traverse(&self, gc: &mut Gc) {
         self.traverse_fields_except_stack(gc);
-        self.context.lock().unwrap().stack.traverse(gc);
+        let mut context = self.context.lock().unwrap();
+        // The values of this thread may be marked so any sweep its garbage collector has left
+        // must be done first (see `Roots::scope`)
+        context.gc.finish_sweep();
+        context.stack.traverse(gc);
     }
 }
 
@@ -418,8 +535,12 @@ impl RootedThread {
             global_state: Arc::new(global_state),
             roots: RwLock::new(Vec::new()),
             rooted_values: RwLock::new(Vec::new()),
+            scope_roots: RwLock::new(Vec::new()),
             child_threads: RwLock::new(Vec::new()),
             interrupt: AtomicBool::new(false),
+            profile: ThreadProfile::default(),
+            env: None,
+            host_contexts: RwLock::new(Vec::new()),
         };
         let mut gc = Gc::new(Generation::default(), usize::MAX);
         let vm = gc.alloc(Move(thread))
@@ -452,16 +573,43 @@ impl RootedThread {
 
 impl Thread {
     /// Spawns a new gluon thread with its own stack and heap but while still sharing the same
-    /// global environment
+    /// global environment. Any module loaded through `import!` by either thread is shared with the
+    /// other.
     pub fn new_thread(&self) -> Result<RootedThread> {
+        self.spawn_thread(self.env.clone())
+    }
+
+    /// Spawns a new gluon thread which sees the globals of this thread but keeps its own
+    /// globals to itself.
+    ///
+    /// Modules and types which the returned thread loads (or reloads) and registers are only
+    /// visible to it while the modules which this thread loads, before or after the fork, are
+    /// shared with it. Nothing is copied when forking. This makes it possible to evaluate each
+    /// request of a server in a sandboxed thread without paying for loading the standard library
+    /// again. Threads spawned with `new_thread` from the forked thread share its globals.
+    pub fn fork(&self) -> Result<RootedThread> {
+        let layer = EnvLayer::new(self.env.clone());
+        self.spawn_thread(Some(Arc::new(layer)))
+    }
+
+    /// Returns `true` if this thread has its own globals (see `fork`)
+    pub fn has_isolated_globals(&self) -> bool {
+        self.env.is_some()
+    }
+
+    fn spawn_thread(&self, env: Option<Arc<EnvLayer>>) -> Result<RootedThread> {
         let vm = Thread {
             global_state: self.global_state.clone(),
             parent: Some(self.root_thread()),
             context: Mutex::new(Context::new(self.current_context().gc.new_child_gc())),
             roots: RwLock::new(Vec::new()),
             rooted_values: RwLock::new(Vec::new()),
+            scope_roots: RwLock::new(Vec::new()),
             child_threads: RwLock::new(Vec::new()),
             interrupt: AtomicBool::new(false),
+            profile: ThreadProfile::default(),
+            env: env,
+            host_contexts: RwLock::new(Vec::new()),
         };
         // Enter the top level scope
         {
@@ -562,14 +710,23 @@ impl Thread {
     pub fn get_global<'vm, T>(&'vm self, name: &str) -> Result<T>
     where
         T: Getable<'vm> + VmType,
+    {
+        let value = self.get_global_value::<T>(name)?;
+        unsafe { Ok(T::from_value(self, Variants::new(&value))) }
+    }
+
+    /// Returns the value of the global `name` after checking that its type matches `T`
+    fn get_global_value<T>(&self, name: &str) -> Result<Value>
+    where
+        T: VmType,
     {
         use check::check_signature;
         let env = self.get_env();
         let (value, actual) = env.get_binding(name)?;
         // Finally check that type of the returned value is correct
         let expected = T::make_type(self);
-        if check_signature(&*env, &expected, &actual) {
-            unsafe { Ok(T::from_value(self, Variants::new(&value))) }
+        if check_signature(&env, &expected, &actual) {
+            Ok(value)
         } else {
             Err(Error::WrongType(expected, actual.into_owned()))
         }
@@ -589,12 +746,40 @@ impl Thread {
 
     /// Registers the type `T` as being a gluon type called `name` with generic arguments `args`
     pub fn register_type<T: ?Sized + Any>(&self, name: &str, args: &[&str]) -> Result<ArcType> {
-        self.global_env().register_type::<T>(name, args)
+        let alias = self.global_env().opaque_alias(name, args);
+        self.register_type_as::<T>(alias)
+    }
+
+    /// Registers the type `T` as being the gluon type described by `alias`. See
+    /// `GlobalVmState::register_type_as`. Types registered by a thread created with `fork` are
+    /// only visible to that thread.
+    pub fn register_type_as<T: ?Sized + Any>(
+        &self,
+        alias: types::Alias<Symbol, ArcType>,
+    ) -> Result<ArcType> {
+        match self.env {
+            Some(ref layer) => layer.register_type_as::<T>(self.global_env(), alias),
+            None => self.global_env().register_type_as::<T>(alias),
+        }
     }
 
     /// Locks and retrieves the global environment of the vm
-    pub fn get_env<'b>(&'b self) -> RwLockReadGuard<'b, VmEnv> {
-        self.global_env().get_env()
+    pub fn get_env<'b>(&'b self) -> VmEnvInstance<'b> {
+        VmEnvInstance::new(self.env.as_ref(), self.global_state.env())
+    }
+
+    /// Checks if a global exists called `name`
+    pub fn global_exists(&self, name: &str) -> bool {
+        self.get_env().get_global(name).is_some()
+    }
+
+    /// Removes the global called `name` from the globals of this thread, returning `true` if it
+    /// existed. Code which has already been loaded keeps the values it referenced from the global.
+    pub fn remove_global(&self, name: &str) -> bool {
+        match self.env {
+            Some(ref layer) => layer.remove_global(self.global_env(), name),
+            None => self.global_env().remove_global(name),
+        }
     }
 
     /// Retrieves the macros defined for this vm
@@ -610,6 +795,17 @@ impl Thread {
         })
     }
 
+    /// Opens a scope in which values can be retrieved without rooting each of them. See
+    /// `RootScope`.
+    pub fn root_scope(&self) -> RootScope {
+        let frame = NEXT_SCOPE_FRAME.fetch_add(1, atomic::Ordering::Relaxed);
+        self.scope_roots.write().unwrap().push((frame, Vec::new()));
+        RootScope {
+            thread: self,
+            frame: frame,
+        }
+    }
+
     /// Pushes a value to the top of the stack
     pub fn push<'vm, T>(&'vm self, v: T) -> Result<()>
     where
@@ -624,26 +820,201 @@ impl Thread {
         self.current_context().stack.pop();
     }
 
+    /// Limits the number of bytes which the garbage collected heap of this thread may use. An
+    /// allocation which would exceed the limit fails with `Error::OutOfMemory`, which gluon code
+    /// can catch with `std.io.catch`. Threads spawned from this thread afterwards inherit the
+    /// limit.
     pub fn set_memory_limit(&self, memory_limit: usize) {
         self.current_context().gc.set_memory_limit(memory_limit)
     }
 
+    /// Sets when and how the garbage collector of this thread allocates and collects. Threads
+    /// spawned from this thread afterwards inherit the settings.
+    pub fn set_gc_settings(&self, settings: GcSettings) {
+        self.current_context().gc.set_settings(settings)
+    }
+
+    /// Returns statistics about the collections which the garbage collector of this thread has run
+    pub fn gc_stats(&self) -> GcStats {
+        self.current_context().gc.stats().clone()
+    }
+
+    /// Polls `future`, which runs code on this thread, letting the thread execute at most `fuel`
+    /// instructions. If the fuel runs out the execution is suspended and the future is returned in
+    /// `FuelResult::Suspended` so the host can decide whether to continue running it. This lets
+    /// untrusted code run without the risk of it looping forever.
+    ///
+    /// Since `Compiler::run_expr_async` starts running the expression as soon as it is called it
+    /// needs to be wrapped in `future::lazy` for the fuel to apply to it.
+    ///
+    /// Calls made from Rust with `Function::call` can not be suspended, if the fuel runs out during
+    /// such a call the execution stops with `Error::OutOfFuel` instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate futures;
+    /// # extern crate gluon;
+    /// # use futures::future;
+    /// # use gluon::{new_vm, Compiler};
+    /// # use gluon::vm::thread::FuelResult;
+    /// # fn main() {
+    /// let vm = new_vm();
+    /// let mut future =
+    ///     future::lazy(|| Compiler::new().run_expr_async::<i32>(&vm, "example", "1 + 2"));
+    /// let (value, _) = loop {
+    ///     match vm.run_with_fuel(100, future).unwrap_or_else(|err| panic!("{}", err)) {
+    ///         FuelResult::Done(value) => break value,
+    ///         FuelResult::Suspended(suspended) => future = suspended,
+    ///     }
+    /// };
+    /// assert_eq!(value, 3);
+    /// # }
+    /// ```
+    pub fn run_with_fuel<F>(&self, fuel: usize, mut future: F) -> StdResult<FuelResult<F>, F::Error>
+    where
+        F: Future,
+    {
+        self.current_context().set_fuel(Some(fuel));
+        let result = future.poll();
+        self.current_context().set_fuel(None);
+        match result? {
+            Async::Ready(value) => Ok(FuelResult::Done(value)),
+            Async::NotReady => Ok(FuelResult::Suspended(future)),
+        }
+    }
+
+    /// Interrupts the execution of this thread. As this only sets a flag it can be called from
+    /// any OS thread, for instance to stop a script which has run past its timeout.
+    ///
+    /// The interruption is noticed at the next call made by the running gluon code, which then
+    /// stops with `Error::Interrupted`. Gluon code can not catch the interruption with
+    /// `std.io.catch` so the error is always returned to the host. The thread stays interrupted
+    /// afterwards, so code which should be stoppable on its own is best run on a thread created
+    /// with `new_thread`.
+    ///
+    /// ```
+    /// # extern crate gluon;
+    /// # use std::thread;
+    /// # use std::time::Duration;
+    /// # use gluon::{new_vm, Compiler, Error};
+    /// # use gluon::vm::Error as VmError;
+    /// # fn main() {
+    /// let vm = new_vm();
+    /// let child = vm.new_thread().unwrap();
+    /// let interrupter = {
+    ///     let child = child.clone();
+    ///     thread::spawn(move || {
+    ///         thread::sleep(Duration::from_millis(100));
+    ///         child.interrupt();
+    ///     })
+    /// };
+    /// let expr = "let forever x : Int -> Int = forever x in forever 0";
+    /// let result = Compiler::new()
+    ///     .implicit_prelude(false)
+    ///     .run_expr::<i32>(&child, "forever", expr);
+    /// match result {
+    ///     Err(Error::VM(VmError::Interrupted)) => (),
+    ///     _ => panic!("Expected the thread to be interrupted"),
+    /// }
+    /// interrupter.join().unwrap();
+    /// # }
+    /// ```
     pub fn interrupt(&self) {
         self.interrupt.store(true, atomic::Ordering::Relaxed)
     }
 
+    /// Returns true if `interrupt` has been called on this thread
     pub fn interrupted(&self) -> bool {
         self.interrupt.load(atomic::Ordering::Relaxed)
     }
 
+    pub(crate) fn profile(&self) -> &ThreadProfile {
+        &self.profile
+    }
+
+    /// Makes `context` available to any primitive which runs on this thread while `f` is called,
+    /// retrievable through `host_context`. This lets primitives reach per-call data, such as the
+    /// database handle of the request being served, without storing it in a global.
+    ///
+    /// Contexts nest so a primitive sees the innermost context of the type it asks for. The
+    /// context is removed when `f` returns so it is not visible to any work which `f` leaves
+    /// suspended (such as an unfinished `IO` action).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate gluon;
+    /// # use gluon::{new_vm, Compiler};
+    /// # use gluon::vm::api::FunctionRef;
+    /// # fn main() {
+    /// struct RequestId(u64);
+    ///
+    /// let vm = new_vm();
+    /// Compiler::new()
+    ///     .load_script(&vm, "handler", "\\x -> x + 1")
+    ///     .unwrap_or_else(|err| panic!("{}", err));
+    /// let mut handler: FunctionRef<fn(i32) -> i32> = vm.get_global("handler").unwrap();
+    ///
+    /// let result = vm.call_with_context(RequestId(1), || {
+    ///     assert_eq!(vm.host_context::<RequestId>().map(|id| id.0), Some(1));
+    ///     handler.call(1)
+    /// });
+    /// assert_eq!(result, Ok(2));
+    /// assert!(vm.host_context::<RequestId>().is_none());
+    /// # }
+    /// ```
+    pub fn call_with_context<C, F, R>(&self, context: C, f: F) -> R
+    where
+        C: Any + Send + Sync,
+        F: FnOnce() -> R,
+    {
+        struct PopContext<'a>(&'a RwLock<Vec<Arc<Any + Send + Sync>>>);
+
+        impl<'a> Drop for PopContext<'a> {
+            fn drop(&mut self) {
+                self.0.write().unwrap().pop();
+            }
+        }
+
+        self.host_contexts.write().unwrap().push(Arc::new(context));
+        let _pop = PopContext(&self.host_contexts);
+        f()
+    }
+
+    /// Retrieves the innermost context of type `C` which was attached to this thread with
+    /// `call_with_context`
+    pub fn host_context<C>(&self) -> Option<Arc<C>>
+    where
+        C: Any + Send + Sync,
+    {
+        self.host_contexts
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|context| {
+                let context: &Any = &***context;
+                context.is::<C>()
+            })
+            // Safe since the context was just checked to be a `C`
+            .map(|context| unsafe { Arc::from_raw(Arc::into_raw(context.clone()) as *const C) })
+    }
+
     fn current_context(&self) -> OwnedContext {
         self.context()
     }
 
     fn traverse_fields_except_stack(&self, gc: &mut Gc) {
         self.global_state.traverse(gc);
+        if let Some(ref env) = self.env {
+            env.traverse(gc);
+        }
         self.roots.read().unwrap().traverse(gc);
         self.rooted_values.read().unwrap().traverse(gc);
+        for &(_, ref values) in self.scope_roots.read().unwrap().iter() {
+            values.traverse(gc);
+        }
         self.child_threads.read().unwrap().traverse(gc);
     }
 
@@ -751,6 +1122,13 @@ where
         value: Value,
     ) -> Result<()>;
 
+    /// Keeps `core`, the optimized core expression which the global `name` was compiled from, so
+    /// that the modules which are compiled later can inline its `#[inline]` functions
+    fn set_global_core(&self, name: &Symbol, core: Arc<CoreExpr>);
+
+    /// Creates a thunk for `f`, resolving its globals in the globals of this thread
+    fn new_global_thunk(&self, f: CompiledModule) -> Result<GcPtr<ClosureData>>;
+
     /// `owner` is theread that owns `value` which is not necessarily the same as `self`
     fn deep_clone_value(&self, owner: &Thread, value: Value) -> Result<Value>;
 
@@ -767,9 +1145,11 @@ impl ThreadInternal for Thread {
     /// Roots a userdata
     fn root<'vm, T: Userdata>(&'vm self, v: GcPtr<Box<Userdata>>) -> Option<Root<'vm, T>> {
         v.downcast_ref::<T>().map(|ptr| {
-            self.roots.write().unwrap().push(v.as_traverseable());
+            let rooted = v.as_traverseable();
+            self.roots.write().unwrap().push(rooted);
             Root {
                 roots: &self.roots,
+                rooted: rooted,
                 ptr: ptr,
             }
         })
@@ -777,12 +1157,11 @@ impl ThreadInternal for Thread {
 
     /// Roots a string
     fn root_string<'vm>(&'vm self, ptr: GcStr) -> RootStr<'vm> {
-        self.roots
-            .write()
-            .unwrap()
-            .push(ptr.into_inner().as_traverseable());
+        let rooted = ptr.into_inner().as_traverseable();
+        self.roots.write().unwrap().push(rooted);
         RootStr(Root {
             roots: &self.roots,
+            rooted: rooted,
             ptr: &*ptr,
         })
     }
@@ -871,7 +1250,30 @@ impl ThreadInternal for Thread {
     ) -> Result<()> {
         let value = ::value::Cloner::new(self, &mut self.global_env().gc.lock().unwrap())
             .deep_clone(&value)?;
-        self.global_env().set_global(name, typ, metadata, value)
+        match self.env {
+            Some(ref layer) => layer.set_global(name, typ, metadata, value),
+            None => self.global_env()
+                .env()
+                .write()
+                .unwrap()
+                .set_global(name, typ, metadata, value),
+        }
+    }
+
+    fn set_global_core(&self, name: &Symbol, core: Arc<CoreExpr>) {
+        match self.env {
+            Some(ref layer) => layer.set_global_core(name.definition_name(), core),
+            None => self.global_env()
+                .env()
+                .write()
+                .unwrap()
+                .set_global_core(name.definition_name(), core),
+        }
+    }
+
+    fn new_global_thunk(&self, f: CompiledModule) -> Result<GcPtr<ClosureData>> {
+        self.global_env()
+            .new_global_thunk_in(&self.get_env(), f)
     }
 
     fn deep_clone_value(&self, owner: &Thread, value: Value) -> Result<Value> {
@@ -916,18 +1318,22 @@ impl ThreadInternal for Thread {
     }
 }
 
+#[cfg(feature = "debug")]
 pub type HookFn = Box<FnMut(&Thread, DebugInfo) -> Result<Async<()>> + Send + Sync>;
 
+#[cfg(feature = "debug")]
 pub struct DebugInfo<'a> {
     stack: &'a Stack,
     state: HookFlags,
 }
 
+#[cfg(feature = "debug")]
 pub struct StackInfo<'a> {
     info: &'a DebugInfo<'a>,
     index: usize,
 }
 
+#[cfg(feature = "debug")]
 impl<'a> DebugInfo<'a> {
     /// Returns the reason for the hook being called
     pub fn state(&self) -> HookFlags {
@@ -953,6 +1359,7 @@ impl<'a> DebugInfo<'a> {
     }
 }
 
+#[cfg(feature = "debug")]
 impl<'a> StackInfo<'a> {
     fn frame(&self) -> &Frame {
         &self.info.stack.get_frames()[self.index]
@@ -1017,8 +1424,28 @@ impl<'a> StackInfo<'a> {
             _ => ice!("Attempted to access upvar in non closure function"),
         }
     }
+
+    /// Returns the current value of `local`, which should be one of the locals returned by
+    /// `locals`
+    pub fn local_value(&self, local: &Local) -> Option<Variants> {
+        let index = self.frame().offset as usize + local.index as usize;
+        self.info
+            .stack
+            .get_values()
+            .get(index)
+            .map(|value| unsafe { Variants::new(value) })
+    }
+
+    /// Returns the value of the upvariable at `index`, described by `upvars()[index]`
+    pub fn upvar_value(&self, index: usize) -> Option<Variants> {
+        self.frame()
+            .upvars()
+            .get(index)
+            .map(|value| unsafe { Variants::new(value) })
+    }
 }
 
+#[cfg(feature = "debug")]
 bitflags! {
     #[derive(Default)]
     pub struct HookFlags: u8 {
@@ -1029,6 +1456,7 @@ bitflags! {
     }
 }
 
+#[cfg(feature = "debug")]
 #[derive(Default)]
 struct Hook {
     function: Option<HookFn>,
@@ -1046,9 +1474,21 @@ pub struct Context {
     pub stack: Stack,
     #[cfg_attr(feature = "serde_derive", serde(state))]
     pub gc: Gc,
+    #[cfg(feature = "debug")]
     #[cfg_attr(feature = "serde_derive", serde(skip))]
     hook: Hook,
     max_stack_size: VmIndex,
+    /// The number of instructions which may be executed before the execution is suspended
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    fuel: Option<usize>,
+    /// Set when the fuel ran out in a synchronous call (`Function::call`) which, unlike the
+    /// execution in a future, can not be suspended
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    out_of_fuel: bool,
+    /// The number of instructions which have been executed, used by `profile` to count the
+    /// instructions of each function
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    executed_instructions: usize,
 
     /// Stack of polling functions used for extern functions returning futures
     #[cfg_attr(feature = "serde_derive", serde(skip))]
@@ -1065,12 +1505,16 @@ impl Context {
         Context {
             gc: gc,
             stack: Stack::new(),
+            #[cfg(feature = "debug")]
             hook: Hook {
                 function: None,
                 flags: HookFlags::empty(),
                 previous_instruction_index: usize::max_value(),
             },
             max_stack_size: VmIndex::max_value(),
+            fuel: None,
+            out_of_fuel: false,
+            executed_instructions: 0,
             poll_fns: Vec::new(),
         }
     }
@@ -1086,6 +1530,29 @@ impl Context {
             .map(Value::from)
     }
 
+    /// Replaces the `args` values at the top of the stack with a variant of tag `tag` which takes
+    /// those values as its arguments. Useful when implementing `Pushable` for enums whose variants
+    /// carry data.
+    pub fn push_new_data(&mut self, thread: &Thread, tag: VmTag, args: VmIndex) -> Result<()> {
+        let value = {
+            let fields = &self.stack[self.stack.len() - args..];
+            alloc(
+                &mut self.gc,
+                thread,
+                &self.stack,
+                Def {
+                    tag: tag,
+                    elems: fields,
+                },
+            )?
+        };
+        for _ in 0..args {
+            self.stack.pop();
+        }
+        self.stack.push(ValueRepr::Data(value));
+        Ok(())
+    }
+
     pub fn alloc_with<D>(&mut self, thread: &Thread, data: D) -> Result<GcPtr<D::Value>>
     where
         D: DataDef + Traverseable,
@@ -1102,10 +1569,24 @@ impl Context {
         self.gc.alloc_ignore_limit(data)
     }
 
+    /// Allocates a string containing `s`. If string deduplication is enabled
+    /// (`GcSettings::deduplicate_strings`) an equal string which survived a collection may be
+    /// returned instead.
+    pub fn alloc_str(&mut self, thread: &Thread, s: &str) -> Result<GcStr> {
+        if let Some(existing) = self.gc.deduplicated_str(s) {
+            return Ok(existing);
+        }
+        let gc_str = unsafe { GcStr::from_utf8_unchecked(self.alloc_with(thread, s.as_bytes())?) };
+        self.gc.add_deduplicated_str(gc_str);
+        Ok(gc_str)
+    }
+
+    #[cfg(feature = "debug")]
     pub fn set_hook(&mut self, hook: Option<HookFn>) -> Option<HookFn> {
         mem::replace(&mut self.hook.function, hook)
     }
 
+    #[cfg(feature = "debug")]
     pub fn set_hook_mask(&mut self, flags: HookFlags) {
         self.hook.flags = flags;
     }
@@ -1114,6 +1595,22 @@ impl Context {
         self.max_stack_size = limit;
     }
 
+    /// Sets the number of instructions which may be executed before the execution is suspended,
+    /// returning the previous amount. `None` lets the thread run without a limit.
+    pub fn set_fuel(&mut self, fuel: Option<usize>) -> Option<usize> {
+        self.out_of_fuel = false;
+        mem::replace(&mut self.fuel, fuel)
+    }
+
+    /// Marks the execution as stopped if a synchronous call was suspended because the fuel ran
+    /// out, returning `true` if that was the case
+    pub(crate) fn stop_if_out_of_fuel(&mut self) -> bool {
+        if self.fuel == Some(0) {
+            self.out_of_fuel = true;
+        }
+        self.out_of_fuel
+    }
+
     /// "Returns a future", letting the virtual machine know that `future` must be resolved to
     /// produce the actual value.
     ///
@@ -1162,6 +1659,7 @@ impl<'b> OwnedContext<'b> {
         alloc(gc, self.thread, &stack, data)
     }
 
+    #[cfg(feature = "debug")]
     pub fn debug_info(&self) -> DebugInfo {
         DebugInfo {
             stack: &self.stack,
@@ -1223,24 +1721,30 @@ impl<'b> OwnedContext<'b> {
             if context.thread.interrupted() {
                 return Err(Error::Interrupted);
             }
+            if context.thread.profile.sample_requested() {
+                context.thread.profile.record_sample(&context.stack);
+            }
             debug!("STACK\n{:?}", context.stack.get_frames());
             let state = context.borrow_mut().stack.frame.state;
 
-            let instruction_index = context.borrow_mut().stack.frame.instruction_index;
-            if instruction_index == 0 && context.hook.flags.contains(HookFlags::CALL_FLAG) {
-                match state {
-                    State::Extern(_) | State::Closure(_) => {
-                        let thread = context.thread;
-                        let context = &mut *context;
-                        if let Some(ref mut hook) = context.hook.function {
-                            let info = DebugInfo {
-                                stack: &context.stack,
-                                state: HookFlags::CALL_FLAG,
-                            };
-                            try_ready!(hook(thread, info))
+            #[cfg(feature = "debug")]
+            {
+                let instruction_index = context.borrow_mut().stack.frame.instruction_index;
+                if instruction_index == 0 && context.hook.flags.contains(HookFlags::CALL_FLAG) {
+                    match state {
+                        State::Extern(_) | State::Closure(_) => {
+                            let thread = context.thread;
+                            let context = &mut *context;
+                            if let Some(ref mut hook) = context.hook.function {
+                                let info = DebugInfo {
+                                    stack: &context.stack,
+                                    state: HookFlags::CALL_FLAG,
+                                };
+                                try_ready!(hook(thread, info))
+                            }
                         }
+                        _ => (),
                     }
-                    _ => (),
                 }
             }
 
@@ -1292,11 +1796,29 @@ impl<'b> OwnedContext<'b> {
                                 &context.stack[..]
                             );
 
-                            let new_context = try_ready!(context.execute_(
+                            let start = if profile::counting() {
+                                Some(profile::Start::new(
+                                    instruction_index,
+                                    *context.executed_instructions,
+                                    context.gc.stats(),
+                                ))
+                            } else {
+                                None
+                            };
+                            let result = context.execute_(
                                 instruction_index,
                                 &closure.function.instructions,
                                 &closure.function,
-                            ));
+                            );
+                            if let Some(start) = start {
+                                start.finish(
+                                    &closure.function.debug_info.source_name,
+                                    &closure.function.name,
+                                    *context.executed_instructions,
+                                    context.gc.stats(),
+                                );
+                            }
+                            let new_context = try_ready!(result);
                             if new_context.is_some() {
                                 State::Exists
                             } else {
@@ -1341,6 +1863,12 @@ impl<'b> OwnedContext<'b> {
 
             self = thread.current_context();
 
+            // A call made by the function ran out of fuel and was aborted so the function could
+            // not have finished its work, whatever it returned
+            if self.out_of_fuel {
+                return Err(Error::OutOfFuel);
+            }
+
             if status == Status::Error {
                 return match self.stack.pop().get_repr() {
                     String(s) => Err(Error::Panic(s.to_string())),
@@ -1446,7 +1974,12 @@ impl<'b> OwnedContext<'b> {
             thread: self.thread,
             gc: &mut context.gc,
             stack: StackFrame::current(&mut context.stack),
+            #[cfg(feature = "debug")]
             hook: &mut context.hook,
+            fuel: &mut context.fuel,
+            executed_instructions: &mut context.executed_instructions,
+            #[cfg(feature = "wasm")]
+            max_stack_size: context.max_stack_size,
         }
     }
 }
@@ -1455,23 +1988,34 @@ struct ExecuteContext<'b> {
     thread: &'b Thread,
     stack: StackFrame<'b>,
     gc: &'b mut Gc,
+    #[cfg(feature = "debug")]
     hook: &'b mut Hook,
+    fuel: &'b mut Option<usize>,
+    executed_instructions: &'b mut usize,
+    #[cfg(feature = "wasm")]
+    max_stack_size: VmIndex,
 }
 
 impl<'b> ExecuteContext<'b> {
     fn enter_scope(&mut self, args: VmIndex, state: State) {
         self.stack.enter_scope(args, state);
-        self.hook.previous_instruction_index = usize::max_value();
+        #[cfg(feature = "debug")]
+        {
+            self.hook.previous_instruction_index = usize::max_value();
+        }
     }
 
     fn exit_scope(&mut self) -> StdResult<(), ()> {
         match self.stack.exit_scope() {
             Ok(_) => {
-                if self.hook.flags.bits() != 0 {
-                    // Subtract 1 to compensate for the `Call` instruction adding one earlier
-                    // ensuring that the line hook runs after function calls
-                    self.hook.previous_instruction_index =
-                        self.stack.frame.instruction_index.saturating_sub(1);
+                #[cfg(feature = "debug")]
+                {
+                    if self.hook.flags.bits() != 0 {
+                        // Subtract 1 to compensate for the `Call` instruction adding one earlier
+                        // ensuring that the line hook runs after function calls
+                        self.hook.previous_instruction_index =
+                            self.stack.frame.instruction_index.saturating_sub(1);
+                    }
                 }
                 Ok(())
             }
@@ -1591,25 +2135,86 @@ impl<'b> ExecuteContext<'b> {
                 self.stack.frame
             );
         }
+        #[cfg(feature = "register_vm")]
+        {
+            // Functions which can be converted are always executed as register instructions, as
+            // the instruction index of a suspended frame is only meaningful for one of the
+            // instruction sets. This takes precedence over the jit and WebAssembly.
+            if let Some(code) = function.register_code.get(
+                function.args,
+                &function.instructions,
+                &function.split_fields,
+            ) {
+                return self.execute_registers(index, code, function);
+            }
+        }
+        #[cfg(any(feature = "jit", feature = "wasm"))]
+        {
+            #[cfg(feature = "debug")]
+            let hooked = !self.hook.flags.is_empty();
+            #[cfg(not(feature = "debug"))]
+            let hooked = false;
+            // Run the compiled code instead of the instructions and return its result below
+            if index == 0 && self.fuel.is_none() && !hooked && !profile::counting() {
+                #[cfg(feature = "jit")]
+                {
+                    if let State::Closure(closure) = self.stack.frame.state {
+                        let interrupt = &self.thread.interrupt;
+                        if let Some(result) = ::jit::call(&closure, &self.stack[..], interrupt) {
+                            self.stack.push(result);
+                            index = instructions.len();
+                        }
+                    }
+                }
+                #[cfg(feature = "wasm")]
+                {
+                    if index == 0 {
+                        let remaining_stack =
+                            self.max_stack_size.saturating_sub(self.stack.stack.len());
+                        if let Some(result) =
+                            ::wasm::call(function, &self.stack[..], remaining_stack)
+                        {
+                            self.stack.push(result);
+                            index = instructions.len();
+                        }
+                    }
+                }
+            }
+        }
         while let Some(&instr) = instructions.get(index) {
             debug_instruction(&self.stack, index, instr);
 
-            if self.hook.flags.contains(HookFlags::LINE_FLAG) {
-                if let Some(ref mut hook) = self.hook.function {
-                    let current_line = function.debug_info.source_map.line(index);
-                    let previous_line = function
-                        .debug_info
-                        .source_map
-                        .line(self.hook.previous_instruction_index);
-                    self.hook.previous_instruction_index = index;
-                    if current_line != previous_line {
-                        self.stack.frame.instruction_index = index;
-                        self.stack.store_frame();
-                        let info = DebugInfo {
-                            stack: &self.stack.stack,
-                            state: HookFlags::LINE_FLAG,
-                        };
-                        try_ready!(hook(self.thread, info))
+            if let Some(ref mut fuel) = *self.fuel {
+                if *fuel == 0 {
+                    // Suspend before executing `instr` so that it is executed once the execution
+                    // is resumed with more fuel
+                    self.stack.frame.instruction_index = index;
+                    self.stack.store_frame();
+                    return Ok(Async::NotReady);
+                }
+                *fuel -= 1;
+            }
+            *self.executed_instructions = self.executed_instructions.wrapping_add(1);
+
+            #[cfg(feature = "debug")]
+            {
+                if self.hook.flags.contains(HookFlags::LINE_FLAG) {
+                    if let Some(ref mut hook) = self.hook.function {
+                        let current_line = function.debug_info.source_map.line(index);
+                        let previous_line = function
+                            .debug_info
+                            .source_map
+                            .line(self.hook.previous_instruction_index);
+                        self.hook.previous_instruction_index = index;
+                        if current_line != previous_line {
+                            self.stack.frame.instruction_index = index;
+                            self.stack.store_frame();
+                            let info = DebugInfo {
+                                stack: &self.stack.stack,
+                                state: HookFlags::LINE_FLAG,
+                            };
+                            try_ready!(hook(self.thread, info))
+                        }
                     }
                 }
             }
@@ -1634,47 +2239,7 @@ impl<'b> ExecuteContext<'b> {
                     self.stack.frame.instruction_index = index + 1;
                     return self.do_call(args).map(|x| Async::Ready(Some(x)));
                 }
-                TailCall(mut args) => {
-                    let mut amount = self.stack.len() - args;
-                    if self.stack.frame.excess {
-                        amount += 1;
-                        match self.stack.excess_args() {
-                            Some(excess) => {
-                                debug!("TailCall: Push excess args {:?}", excess.fields);
-                                for value in &excess.fields {
-                                    self.stack.push(value);
-                                }
-                                args += excess.fields.len() as VmIndex;
-                            }
-                            None => ice!("Expected excess args"),
-                        }
-                    }
-                    debug_assert!(
-                        match self.stack.frame.state {
-                            State::Closure(ref c) => c.function.name == function.name,
-                            _ => false,
-                        },
-                        "Attempted to pop {:?} but `{}` was expected",
-                        self.stack.frame.state,
-                        function.name
-                    );
-                    match self.exit_scope() {
-                        Ok(_) => (),
-                        Err(_) => {
-                            self.enter_scope(args + amount + 1, State::Excess);
-                        }
-                    };
-                    info!(
-                        "Clearing {} {} {:?}",
-                        self.stack.len(),
-                        amount,
-                        &self.stack[..]
-                    );
-                    let end = self.stack.len() - args - 1;
-                    self.stack.remove_range(end - amount, end);
-                    debug!("{:?}", &self.stack[..]);
-                    return self.do_call(args).map(|x| Async::Ready(Some(x)));
-                }
+                TailCall(args) => return self.tail_call(args, function),
                 Construct { tag, args } => {
                     let d = {
                         if args == 0 {
@@ -1852,6 +2417,8 @@ impl<'b> ExecuteContext<'b> {
                                     *var = self.stack.pop();
                                 }
                             }
+                            // A collection may have run since the closure was allocated
+                            self.gc.write_barrier(closure);
                             self.stack.pop(); //Remove the closure
                         }
                         x => ice!("Expected closure, got {:?}", x),
@@ -1861,9 +2428,11 @@ impl<'b> ExecuteContext<'b> {
                     let v = self.stack.get_upvar(i).clone();
                     self.stack.push(v);
                 }
-                AddInt => binop_int(self.thread, &mut self.stack, VmInt::add),
-                SubtractInt => binop_int(self.thread, &mut self.stack, VmInt::sub),
-                MultiplyInt => binop_int(self.thread, &mut self.stack, VmInt::mul),
+                // Wraps on overflow, the same as the code compiled by the `jit` and `wasm`
+                // backends
+                AddInt => binop_int(self.thread, &mut self.stack, VmInt::wrapping_add),
+                SubtractInt => binop_int(self.thread, &mut self.stack, VmInt::wrapping_sub),
+                MultiplyInt => binop_int(self.thread, &mut self.stack, VmInt::wrapping_mul),
                 DivideInt => binop_int(self.thread, &mut self.stack, VmInt::div),
                 IntLT => binop_bool(self.thread, &mut self.stack, |l: VmInt, r| l < r),
                 IntEQ => binop_bool(self.thread, &mut self.stack, |l: VmInt, r| l == r),
@@ -1881,10 +2450,396 @@ impl<'b> ExecuteContext<'b> {
                 DivideFloat => binop_f64(self.thread, &mut self.stack, f64::div),
                 FloatLT => binop_bool(self.thread, &mut self.stack, |l: f64, r| l < r),
                 FloatEQ => binop_bool(self.thread, &mut self.stack, |l: f64, r| l == r),
+
+                StringEQ => binop_bool(self.thread, &mut self.stack, |l: &str, r| l == r),
             }
             index += 1;
         }
         let result = self.stack.top().clone();
+        self.return_value(result, function)
+    }
+
+    /// Executes the register instructions which `function` were converted into. Register `n` is
+    /// slot `n` of the current frame, so calls and returns use the same frame layout as the stack
+    /// instructions. Each register instruction uses one unit of fuel. The line hook is not called
+    /// for functions executed this way.
+    #[cfg(feature = "register_vm")]
+    fn execute_registers(
+        &mut self,
+        mut index: usize,
+        code: &Converted,
+        function: &BytecodeFunction,
+    ) -> Result<Async<Option<()>>> {
+        let instructions = &code.instructions[..];
+        debug!(
+            ">>>\nEnter frame {} (registers): {:?}\n{:?}",
+            function.name,
+            &self.stack[..],
+            self.stack.frame
+        );
+        while let Some(&instr) = instructions.get(index) {
+            debug!("{:?}: {:?} -> {:?}", index, instr, self.stack.len());
+
+            if let Some(ref mut fuel) = *self.fuel {
+                if *fuel == 0 {
+                    self.stack.frame.instruction_index = index;
+                    self.stack.store_frame();
+                    return Ok(Async::NotReady);
+                }
+                *fuel -= 1;
+            }
+            *self.executed_instructions = self.executed_instructions.wrapping_add(1);
+
+            match instr {
+                RegInstruction::Load { dst, value } => {
+                    let value = self.operand(value);
+                    self.set_register(dst, value);
+                }
+                RegInstruction::LoadString {
+                    dst,
+                    index: string_index,
+                } => {
+                    let value =
+                        Value::from(String(function.strings[string_index as usize].inner()));
+                    self.set_register(dst, value);
+                }
+                RegInstruction::Call {
+                    function: callee,
+                    args,
+                    operands,
+                } => {
+                    self.store_operands(callee, args + 1, operands, code);
+                    self.set_stack_len(callee + args + 1);
+                    self.stack.frame.instruction_index = index + 1;
+                    return self.do_call(args).map(|x| Async::Ready(Some(x)));
+                }
+                RegInstruction::TailCall {
+                    function: callee,
+                    args,
+                    operands,
+                } => {
+                    self.store_operands(callee, args + 1, operands, code);
+                    self.set_stack_len(callee + args + 1);
+                    return self.tail_call(args, function);
+                }
+                RegInstruction::Construct {
+                    dst,
+                    tag,
+                    args,
+                    operands,
+                } => {
+                    self.store_operands(dst, args, operands, code);
+                    let d = if args == 0 {
+                        ValueRepr::Tag(tag)
+                    } else {
+                        let fields = &self.stack[dst..dst + args];
+                        Data(alloc(
+                            &mut self.gc,
+                            self.thread,
+                            &self.stack.stack,
+                            Def {
+                                tag: tag,
+                                elems: fields,
+                            },
+                        )?)
+                    };
+                    self.set_register(dst, Value::from(d));
+                }
+                RegInstruction::ConstructRecord {
+                    dst,
+                    record,
+                    args,
+                    operands,
+                } => {
+                    self.store_operands(dst, args, operands, code);
+                    let d = if args == 0 {
+                        ValueRepr::Tag(0)
+                    } else {
+                        let fields = &self.stack[dst..dst + args];
+                        unsafe {
+                            let roots = Roots {
+                                vm: GcPtr::from_raw(self.thread),
+                                stack: &self.stack.stack,
+                            };
+                            let field_names = &function.records[record as usize];
+                            Data(self.gc.alloc_and_collect(
+                                roots,
+                                RecordDef {
+                                    elems: fields,
+                                    fields: field_names,
+                                },
+                            )?)
+                        }
+                    };
+                    self.set_register(dst, Value::from(d));
+                }
+                RegInstruction::ConstructArray {
+                    dst,
+                    args,
+                    operands,
+                } => {
+                    self.store_operands(dst, args, operands, code);
+                    let d = {
+                        let fields = &self.stack[dst..dst + args];
+                        alloc(
+                            &mut self.gc,
+                            self.thread,
+                            &self.stack.stack,
+                            ::value::ArrayDef(fields),
+                        )?
+                    };
+                    self.set_register(dst, Value::from(ValueRepr::Array(d)));
+                }
+                RegInstruction::GetOffset { dst, src, offset } => {
+                    let value = match self.stack[src].get_repr() {
+                        Data(data) => data.fields[offset as usize].clone(),
+                        x => return Err(Error::Message(format!("GetOffset on {:?}", x))),
+                    };
+                    self.set_register(dst, value);
+                }
+                RegInstruction::GetField {
+                    dst,
+                    src,
+                    index: string_index,
+                } => {
+                    let field = function.strings[string_index as usize];
+                    let value = match self.stack[src].get_repr() {
+                        Data(data) => data.get_field(field)
+                            .expect("ICE: Field does not exist")
+                            .get_value(),
+                        x => return Err(Error::Message(format!("GetField on {:?}", x))),
+                    };
+                    self.set_register(dst, value);
+                }
+                RegInstruction::Split { dst, src, fields } => {
+                    let value = self.stack[src].get_repr();
+                    match value {
+                        Data(data) => for (register, field) in (dst..dst + fields).zip(&data.fields)
+                        {
+                            self.set_register(register, field.clone());
+                        },
+                        // Zero argument variant
+                        ValueRepr::Tag(_) => (),
+                        _ => {
+                            return Err(Error::Message(
+                                "Op Split called on non data type".to_string(),
+                            ))
+                        }
+                    }
+                }
+                RegInstruction::TestTag { dst, src, tag } => {
+                    let data_tag = match self.stack[src].get_repr() {
+                        Data(ref data) => data.tag(),
+                        ValueRepr::Tag(tag) => tag,
+                        _ => {
+                            return Err(Error::Message(
+                                "Op TestTag called on non data type".to_string(),
+                            ))
+                        }
+                    };
+                    let value = ValueRepr::Tag(if data_tag == tag { 1 } else { 0 });
+                    self.set_register(dst, Value::from(value));
+                }
+                RegInstruction::Jump(target) => {
+                    index = target as usize;
+                    continue;
+                }
+                RegInstruction::CJump { cond, target } => match self.operand(cond).get_repr() {
+                    ValueRepr::Tag(0) => (),
+                    _ => {
+                        index = target as usize;
+                        continue;
+                    }
+                },
+                RegInstruction::MakeClosure {
+                    dst,
+                    function_index,
+                    upvars,
+                    operands,
+                } => {
+                    self.store_operands(dst, upvars, operands, code);
+                    let closure = {
+                        let args = &self.stack[dst..dst + upvars];
+                        let func = function.inner_functions[function_index as usize];
+                        Closure(alloc(
+                            &mut self.gc,
+                            self.thread,
+                            &self.stack.stack,
+                            ClosureDataDef(func, args),
+                        )?)
+                    };
+                    self.set_register(dst, Value::from(closure));
+                }
+                RegInstruction::NewClosure {
+                    dst,
+                    function_index,
+                    upvars,
+                } => {
+                    let closure = {
+                        // Use dummy variables until it is filled
+                        let func = function.inner_functions[function_index as usize];
+                        Closure(alloc(
+                            &mut self.gc,
+                            self.thread,
+                            &self.stack.stack,
+                            ClosureInitDef(func, upvars as usize),
+                        )?)
+                    };
+                    self.set_register(dst, Value::from(closure));
+                }
+                RegInstruction::CloseClosure {
+                    closure,
+                    upvars,
+                    operands,
+                } => {
+                    self.store_operands(closure + 1, upvars, operands, code);
+                    match self.stack[closure].get_repr() {
+                        Closure(mut closure_data) => {
+                            // Unique access should be safe as this closure should not be shared as
+                            // it has just been allocated and havent even had its upvars set yet
+                            // (which is done here).
+                            unsafe {
+                                let values = &self.stack[closure + 1..closure + 1 + upvars];
+                                for (var, value) in
+                                    closure_data.as_mut().upvars.iter_mut().zip(values)
+                                {
+                                    *var = value.clone();
+                                }
+                            }
+                            // A collection may have run since the closure was allocated
+                            self.gc.write_barrier(closure_data);
+                        }
+                        x => ice!("Expected closure, got {:?}", x),
+                    }
+                }
+                RegInstruction::Binary { op, dst, lhs, rhs } => {
+                    let value = binop_registers(op, self.operand(lhs), self.operand(rhs))?;
+                    self.set_register(dst, Value::from(value));
+                }
+                RegInstruction::Return(value) => {
+                    let result = self.operand(value);
+                    return self.return_value(result, function);
+                }
+            }
+            index += 1;
+        }
+        ice!("`{}` has no `Return` instruction", function.name)
+    }
+
+    #[cfg(feature = "register_vm")]
+    fn operand(&self, operand: Operand) -> Value {
+        match operand {
+            Operand::Register(register) => self.stack[register].clone(),
+            Operand::UpVar(index) => self.stack.get_upvar(index).clone(),
+            Operand::Int(i) => Value::from(Int(i)),
+            Operand::Byte(b) => Value::from(ValueRepr::Byte(b)),
+            Operand::Float(f) => Value::from(Float(f)),
+        }
+    }
+
+    /// Stores the `count` values at `operands` in `code` in the registers starting at `start`,
+    /// growing the frame so that all `count` registers exist (even if `count` is zero)
+    #[cfg(feature = "register_vm")]
+    fn store_operands(
+        &mut self,
+        start: VmIndex,
+        count: VmIndex,
+        operands: Option<VmIndex>,
+        code: &Converted,
+    ) {
+        while self.stack.len() < start + count {
+            self.stack.push(Int(0));
+        }
+        if let Some(operands) = operands {
+            let operands = &code.operands[operands as usize..(operands + count) as usize];
+            for (register, &operand) in (start..).zip(operands) {
+                // Values already stored in their register are left as is
+                if operand != Operand::Register(register) {
+                    let value = self.operand(operand);
+                    self.set_register(register, value);
+                }
+            }
+        }
+    }
+
+    /// Stores `value` in `register`, growing the frame if `register` is past its end
+    #[cfg(feature = "register_vm")]
+    fn set_register(&mut self, register: VmIndex, value: Value) {
+        // Registers below `register` which have not been written yet only hold placeholders
+        // which are never read
+        while self.stack.len() < register {
+            self.stack.push(Int(0));
+        }
+        if register == self.stack.len() {
+            self.stack.push(value);
+        } else {
+            self.stack[register] = value;
+        }
+    }
+
+    /// Makes the frame `len` values long so that the arguments of a call are at the top of the
+    /// stack
+    #[cfg(feature = "register_vm")]
+    fn set_stack_len(&mut self, len: VmIndex) {
+        while self.stack.len() < len {
+            self.stack.push(Int(0));
+        }
+        let excess = self.stack.len() - len;
+        self.stack.pop_many(excess as usize);
+    }
+
+    fn tail_call(
+        &mut self,
+        mut args: VmIndex,
+        function: &BytecodeFunction,
+    ) -> Result<Async<Option<()>>> {
+        let mut amount = self.stack.len() - args;
+        if self.stack.frame.excess {
+            amount += 1;
+            match self.stack.excess_args() {
+                Some(excess) => {
+                    debug!("TailCall: Push excess args {:?}", excess.fields);
+                    for value in &excess.fields {
+                        self.stack.push(value);
+                    }
+                    args += excess.fields.len() as VmIndex;
+                }
+                None => ice!("Expected excess args"),
+            }
+        }
+        debug_assert!(
+            match self.stack.frame.state {
+                State::Closure(ref c) => c.function.name == function.name,
+                _ => false,
+            },
+            "Attempted to pop {:?} but `{}` was expected",
+            self.stack.frame.state,
+            function.name
+        );
+        match self.exit_scope() {
+            Ok(_) => (),
+            Err(_) => {
+                self.enter_scope(args + amount + 1, State::Excess);
+            }
+        };
+        info!(
+            "Clearing {} {} {:?}",
+            self.stack.len(),
+            amount,
+            &self.stack[..]
+        );
+        let end = self.stack.len() - args - 1;
+        self.stack.remove_range(end - amount, end);
+        debug!("{:?}", &self.stack[..]);
+        self.do_call(args).map(|x| Async::Ready(Some(x)))
+    }
+
+    /// Leaves the frame of `function`, returning `result` to the caller
+    fn return_value(
+        &mut self,
+        result: Value,
+        function: &BytecodeFunction,
+    ) -> Result<Async<Option<()>>> {
         debug!("Return {:?}", result);
         let len = self.stack.len();
         let frame_has_excess = self.stack.frame.excess;
@@ -1928,6 +2883,44 @@ impl<'b> ExecuteContext<'b> {
     }
 }
 
+#[cfg(feature = "register_vm")]
+fn binop_registers(op: BinaryOp, lhs: Value, rhs: Value) -> Result<ValueRepr> {
+    fn tag(b: bool) -> ValueRepr {
+        ValueRepr::Tag(if b { 1 } else { 0 })
+    }
+    Ok(match (op, lhs.get_repr(), rhs.get_repr()) {
+        (BinaryOp::AddInt, Int(l), Int(r)) => Int(l.wrapping_add(r)),
+        (BinaryOp::SubtractInt, Int(l), Int(r)) => Int(l.wrapping_sub(r)),
+        (BinaryOp::MultiplyInt, Int(l), Int(r)) => Int(l.wrapping_mul(r)),
+        (BinaryOp::DivideInt, Int(l), Int(r)) => Int(l / r),
+        (BinaryOp::IntLT, Int(l), Int(r)) => tag(l < r),
+        (BinaryOp::IntEQ, Int(l), Int(r)) => tag(l == r),
+
+        (BinaryOp::AddByte, ValueRepr::Byte(l), ValueRepr::Byte(r)) => ValueRepr::Byte(l + r),
+        (BinaryOp::SubtractByte, ValueRepr::Byte(l), ValueRepr::Byte(r)) => ValueRepr::Byte(l - r),
+        (BinaryOp::MultiplyByte, ValueRepr::Byte(l), ValueRepr::Byte(r)) => ValueRepr::Byte(l * r),
+        (BinaryOp::DivideByte, ValueRepr::Byte(l), ValueRepr::Byte(r)) => ValueRepr::Byte(l / r),
+        (BinaryOp::ByteLT, ValueRepr::Byte(l), ValueRepr::Byte(r)) => tag(l < r),
+        (BinaryOp::ByteEQ, ValueRepr::Byte(l), ValueRepr::Byte(r)) => tag(l == r),
+
+        (BinaryOp::AddFloat, Float(l), Float(r)) => Float(l + r),
+        (BinaryOp::SubtractFloat, Float(l), Float(r)) => Float(l - r),
+        (BinaryOp::MultiplyFloat, Float(l), Float(r)) => Float(l * r),
+        (BinaryOp::DivideFloat, Float(l), Float(r)) => Float(l / r),
+        (BinaryOp::FloatLT, Float(l), Float(r)) => tag(l < r),
+        (BinaryOp::FloatEQ, Float(l), Float(r)) => tag(l == r),
+
+        (BinaryOp::StringEQ, String(l), String(r)) => tag(&l[..] == &r[..]),
+
+        (op, l, r) => {
+            return Err(Error::Message(format!(
+                "{:?} called on {:?} and {:?}",
+                op, l, r
+            )))
+        }
+    })
+}
+
 #[inline]
 fn binop_int<'b, F, T>(vm: &'b Thread, stack: &mut StackFrame<'b>, f: F)
 where
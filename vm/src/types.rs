@@ -34,6 +34,8 @@ pub enum Instruction {
     /// function is replaced by the result of the call.
     Call(VmIndex),
     /// Tailcalls a function, removing the current stack frame before calling it.
+    /// The compiler emits this for every call in tail position so that self- and mutually
+    /// recursive functions run in constant stack space.
     /// See `Call`.
     TailCall(VmIndex),
     /// Constructs a data value tagged by `tag` by taking the top `args` values of the stack.
@@ -114,6 +116,8 @@ pub enum Instruction {
     DivideFloat,
     FloatLT,
     FloatEQ,
+
+    StringEQ,
 }
 
 impl Instruction {
@@ -141,7 +145,7 @@ impl Instruction {
             PushUpVar(_) => 1,
             AddInt | SubtractInt | MultiplyInt | DivideInt | IntLT | IntEQ | AddFloat | AddByte
             | SubtractByte | MultiplyByte | DivideByte | ByteLT | ByteEQ | SubtractFloat
-            | MultiplyFloat | DivideFloat | FloatLT | FloatEQ => -1,
+            | MultiplyFloat | DivideFloat | FloatLT | FloatEQ | StringEQ => -1,
         }
     }
 }
@@ -151,7 +155,7 @@ impl Instruction {
 #[cfg_attr(feature = "serde_derive", serde(deserialize_state = "::serialization::DeSeed"))]
 #[cfg_attr(feature = "serde_derive", serde(serialize_state = "::serialization::SeSeed"))]
 pub struct TypeInfos {
-    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::typ"))]
     pub id_to_type: FnvMap<String, Alias<Symbol, ArcType>>,
 }
 
@@ -171,7 +175,7 @@ impl TypeEnv for TypeInfos {
         let id = id.definition_name();
         self.id_to_type
             .iter()
-            .filter_map(|(_, ref alias)| match **alias.unresolved_type() {
+            .filter_map(|(_, ref alias)| match **alias.unresolved_type().remove_forall() {
                 Type::Variant(ref row) => row.row_iter().find(|field| field.name.as_ref() == id),
                 _ => None,
             })
@@ -185,10 +189,44 @@ impl TypeEnv for TypeInfos {
 
     fn find_record(
         &self,
-        _fields: &[Symbol],
-        _selector: RecordSelector,
+        fields: &[Symbol],
+        selector: RecordSelector,
     ) -> Option<(ArcType, ArcType)> {
-        None
+        self.id_to_type
+            .values()
+            .find(|alias| match **alias.unresolved_type().remove_forall() {
+                Type::Record(ref row) => {
+                    let record_fields = || {
+                        row.row_iter()
+                            .map(|f| f.name.name())
+                            .chain(row.type_field_iter().map(|f| f.name.name()))
+                    };
+                    selector.matches(record_fields, fields.iter().map(|field| field.name()))
+                }
+                _ => false,
+            })
+            .map(|alias| {
+                let generic_args = alias.params().iter().cloned().map(Type::generic).collect();
+                let typ = Type::<_, ArcType>::app(alias.as_ref().clone(), generic_args);
+                (typ, alias.typ().into_owned())
+            })
+    }
+
+    fn value_names(&self) -> Vec<&str> {
+        self.id_to_type
+            .values()
+            .filter_map(|alias| match **alias.unresolved_type().remove_forall() {
+                Type::Variant(ref row) => {
+                    Some(row.row_iter().map(|field| field.name.declared_name()))
+                }
+                _ => None,
+            })
+            .flat_map(|names| names)
+            .collect()
+    }
+
+    fn type_names(&self) -> Vec<&str> {
+        self.id_to_type.keys().map(|name| &name[..]).collect()
     }
 }
 
@@ -117,6 +117,8 @@ pub struct BytecodeFunction {
     pub args: VmIndex,
     pub max_stack_size: VmIndex,
     pub instructions: Vec<Instruction>,
+    /// See `CompiledFunction::split_fields`
+    pub split_fields: Vec<VmIndex>,
     #[cfg_attr(feature = "serde_derive", serde(state))]
     pub inner_functions: Vec<GcPtr<BytecodeFunction>>,
     #[cfg_attr(feature = "serde_derive", serde(state))]
@@ -125,6 +127,15 @@ pub struct BytecodeFunction {
     pub records: Vec<Vec<InternedStr>>,
     #[cfg_attr(feature = "serde_derive", serde(state))]
     pub debug_info: DebugInfo,
+    #[cfg(feature = "jit")]
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    pub(crate) jit: ::jit::FunctionJit,
+    #[cfg(feature = "register_vm")]
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    pub(crate) register_code: ::register::RegisterCode,
+    #[cfg(feature = "wasm")]
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    pub(crate) wasm: ::wasm::FunctionWasm,
 }
 
 impl Traverseable for BytecodeFunction {
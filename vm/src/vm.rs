@@ -1,16 +1,16 @@
 use std::borrow::Cow;
-use std::sync::{Mutex, RwLock, RwLockReadGuard};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 use std::any::{Any, TypeId};
 use std::result::Result as StdResult;
 use std::string::String as StdString;
 use std::usize;
 
 use base::ast;
-use base::fnv::FnvMap;
+use base::fnv::{FnvMap, FnvSet};
 use base::kind::{ArcKind, Kind, KindEnv};
 use base::metadata::{Metadata, MetadataEnv};
 use base::symbol::{Name, Symbol, SymbolRef};
-use base::types::{Alias, AliasData, AppVec, ArcType, Generic, PrimitiveEnv, RecordSelector, Type,
+use base::types::{Alias, AliasData, ArcType, Field, Generic, PrimitiveEnv, RecordSelector, Type,
                   TypeCache, TypeEnv};
 
 use macros::MacroEnv;
@@ -19,16 +19,17 @@ use types::*;
 use interner::{InternedStr, Interner};
 use gc::{Gc, GcPtr, Generation, Move, Traverseable};
 use compiler::{CompiledFunction, CompiledModule, CompilerEnv, Variable};
+use core::CoreExpr;
 use api::{ValueRef, IO};
 use lazy::Lazy;
 
 use value::{BytecodeFunction, ClosureData, ClosureDataDef, Value};
 
 pub use value::Userdata;
-pub use thread::{Root, RootStr, RootedThread, RootedValue, Status, Thread};
+pub use thread::{Root, RootScope, RootStr, RootedThread, RootedValue, Status, Thread};
 
 fn new_bytecode(
-    env: &VmEnv,
+    env: &VmEnvInstance,
     interner: &mut Interner,
     gc: &mut Gc,
     vm: &GlobalVmState,
@@ -42,7 +43,10 @@ fn new_bytecode(
 
     let globals = module_globals
         .into_iter()
-        .map(|index| env.globals[index.definition_name()].value.clone())
+        .map(|index| match env.get_global(index.definition_name()) {
+            Some(global) => global.value.clone(),
+            None => ice!("Undefined global `{}`", index),
+        })
         .collect::<Vec<_>>();
 
     gc.alloc(ClosureDataDef(bytecode_function, &globals))
@@ -59,6 +63,7 @@ fn new_bytecode_function(
         args,
         max_stack_size,
         instructions,
+        split_fields,
         inner_functions,
         strings,
         records,
@@ -85,10 +90,17 @@ fn new_bytecode_function(
         args: args,
         max_stack_size: max_stack_size,
         instructions: instructions,
+        split_fields: split_fields,
         inner_functions: fs?,
         strings: strings,
         records: records?,
         debug_info: debug_info,
+        #[cfg(feature = "jit")]
+        jit: Default::default(),
+        #[cfg(feature = "register_vm")]
+        register_code: Default::default(),
+        #[cfg(feature = "wasm")]
+        wasm: ::wasm::FunctionWasm::new(f.wasm, max_stack_size),
     }))
 }
 
@@ -99,11 +111,15 @@ fn new_bytecode_function(
 pub struct Global {
     #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::symbol"))]
     pub id: Symbol,
-    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::typ"))]
     pub typ: ArcType,
     pub metadata: Metadata,
     #[cfg_attr(feature = "serde_derive_state", serde(state))]
     pub value: Value,
+    /// The optimized core expression of the module, kept if other modules may inline the
+    /// functions which it marks with `#[inline]`
+    #[cfg_attr(feature = "serde_derive", serde(skip))]
+    pub core: Option<Arc<CoreExpr>>,
 }
 
 impl Traverseable for Global {
@@ -119,7 +135,7 @@ pub struct GlobalVmState {
     #[cfg_attr(feature = "serde_derive", serde(state))]
     env: RwLock<VmEnv>,
 
-    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::borrow"))]
+    #[cfg_attr(feature = "serde_derive", serde(state_with = "::serialization::typ"))]
     generics: RwLock<FnvMap<StdString, ArcType>>,
 
     #[cfg_attr(feature = "serde_derive", serde(skip))]
@@ -145,7 +161,7 @@ pub struct GlobalVmState {
     pub generation_0_threads: RwLock<Vec<GcPtr<Thread>>>,
 
     #[cfg_attr(feature = "serde_derive", serde(skip))]
-    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(all(feature = "async", not(target_arch = "wasm32")))]
     event_loop: Option<::std::panic::AssertUnwindSafe<::tokio_core::reactor::Remote>>,
 }
 
@@ -160,8 +176,8 @@ impl Traverseable for GlobalVmState {
     }
 }
 
-/// A borrowed structure which implements `CompilerEnv`, `TypeEnv` and `KindEnv` allowing the
-/// typechecker and compiler to lookup things in the virtual machine.
+/// The globals and types of a vm, either the ones shared by all threads or the ones which a thread
+/// created with `Thread::fork` has added itself
 #[derive(Debug)]
 #[cfg_attr(feature = "serde_derive", derive(DeserializeState, SerializeState))]
 #[cfg_attr(feature = "serde_derive", serde(deserialize_state = "::serialization::DeSeed"))]
@@ -173,42 +189,230 @@ pub struct VmEnv {
     pub globals: FnvMap<StdString, Global>,
 }
 
-impl CompilerEnv for VmEnv {
-    fn find_var(&self, id: &Symbol) -> Option<(Variable<Symbol>, ArcType)> {
+impl VmEnv {
+    fn new() -> VmEnv {
+        VmEnv {
+            globals: FnvMap::default(),
+            type_infos: TypeInfos::new(),
+        }
+    }
+
+    pub(crate) fn set_global(
+        &mut self,
+        id: Symbol,
+        typ: ArcType,
+        metadata: Metadata,
+        value: Value,
+    ) -> Result<()> {
+        assert!(value.generation().is_root());
+        assert!(
+            id.as_ref().matches('@').next() == Some("@"),
+            "Global symbols must be prefix with '@'"
+        );
+        let global = Global {
+            id: id.clone(),
+            typ: typ,
+            metadata: metadata,
+            value: value,
+            core: None,
+        };
         self.globals
-            .get(id.definition_name())
+            .insert(StdString::from(id.definition_name()), global);
+        Ok(())
+    }
+
+    pub(crate) fn set_global_core(&mut self, name: &str, core: Arc<CoreExpr>) {
+        if let Some(global) = self.globals.get_mut(name) {
+            global.core = Some(core);
+        }
+    }
+}
+
+/// The globals and types which a thread created with `Thread::fork` has loaded or registered
+/// itself. Anything which is not found in a layer is looked up in the layer of the thread it was
+/// forked from, and finally in the environment which is shared by all threads.
+#[derive(Debug)]
+pub(crate) struct EnvLayer {
+    env: RwLock<LayerEnv>,
+    parent: Option<Arc<EnvLayer>>,
+}
+
+#[derive(Debug)]
+struct LayerEnv {
+    env: VmEnv,
+    // Globals of the parent layers which have been removed from this layer
+    removed: FnvSet<StdString>,
+}
+
+impl Traverseable for EnvLayer {
+    fn traverse(&self, gc: &mut Gc) {
+        for g in self.env.read().unwrap().env.globals.values() {
+            g.traverse(gc);
+        }
+        // The thread which owns the parent layer may be dropped before the fork
+        if let Some(ref parent) = self.parent {
+            parent.traverse(gc);
+        }
+    }
+}
+
+impl EnvLayer {
+    pub(crate) fn new(parent: Option<Arc<EnvLayer>>) -> EnvLayer {
+        EnvLayer {
+            env: RwLock::new(LayerEnv {
+                env: VmEnv::new(),
+                removed: FnvSet::default(),
+            }),
+            parent: parent,
+        }
+    }
+
+    pub(crate) fn set_global(
+        &self,
+        id: Symbol,
+        typ: ArcType,
+        metadata: Metadata,
+        value: Value,
+    ) -> Result<()> {
+        let mut env = self.env.write().unwrap();
+        env.removed.remove(id.definition_name());
+        env.env.set_global(id, typ, metadata, value)
+    }
+
+    pub(crate) fn set_global_core(&self, name: &str, core: Arc<CoreExpr>) {
+        self.env.write().unwrap().env.set_global_core(name, core)
+    }
+
+    /// Removes the global called `name` from this layer and hides it in the parent layers,
+    /// returning `true` if it existed
+    pub(crate) fn remove_global(&self, vm: &GlobalVmState, name: &str) -> bool {
+        let mut env = self.env.write().unwrap();
+        let removed = env.env.globals.remove(name).is_some();
+        let inherited = VmEnvInstance::new(self.parent.as_ref(), &vm.env)
+            .get_global(name)
+            .is_some();
+        if inherited {
+            env.removed.insert(name.into());
+        }
+        removed || inherited
+    }
+
+    /// Registers `T` as the type described by `alias` in this layer only
+    pub(crate) fn register_type_as<T: ?Sized + Any>(
+        &self,
+        vm: &GlobalVmState,
+        alias: Alias<Symbol, ArcType>,
+    ) -> Result<ArcType> {
+        let mut env = self.env.write().unwrap();
+        let parent = VmEnvInstance::new(self.parent.as_ref(), &vm.env);
+        vm.register_type_in::<T>(&mut env.env.type_infos, Some(&parent), alias)
+    }
+}
+
+/// A borrowed structure which implements `CompilerEnv`, `TypeEnv` and `KindEnv` allowing the
+/// typechecker and compiler to lookup things in the virtual machine.
+pub struct VmEnvInstance<'a> {
+    // The layers of a forked thread, starting with its own layer
+    layers: Vec<RwLockReadGuard<'a, LayerEnv>>,
+    shared: RwLockReadGuard<'a, VmEnv>,
+}
+
+impl<'a> VmEnvInstance<'a> {
+    pub(crate) fn new(layer: Option<&'a Arc<EnvLayer>>, shared: &'a RwLock<VmEnv>) -> Self {
+        let mut layers = Vec::new();
+        let mut next = layer;
+        while let Some(layer) = next {
+            layers.push(layer.env.read().unwrap());
+            next = layer.parent.as_ref();
+        }
+        VmEnvInstance {
+            layers: layers,
+            shared: shared.read().unwrap(),
+        }
+    }
+
+    // Returns the first result of `f` when it is called on each environment, from the innermost
+    // layer to the shared environment
+    fn find_map<'s, T, F>(&'s self, f: F) -> Option<T>
+    where
+        F: FnMut(&'s VmEnv) -> Option<T>,
+    {
+        self.layers
+            .iter()
+            .map(|layer| &layer.env)
+            .chain(Some(&*self.shared))
+            .filter_map(f)
+            .next()
+    }
+
+    fn envs(&self) -> Vec<&VmEnv> {
+        self.layers
+            .iter()
+            .map(|layer| &layer.env)
+            .chain(Some(&*self.shared))
+            .collect()
+    }
+
+    /// Returns the global called `name`
+    pub fn get_global(&self, name: &str) -> Option<&Global> {
+        for layer in &self.layers {
+            if let Some(global) = layer.env.globals.get(name) {
+                return Some(global);
+            }
+            if layer.removed.contains(name) {
+                return None;
+            }
+        }
+        self.shared.globals.get(name)
+    }
+
+    /// Returns the name and value of every global
+    pub fn globals(&self) -> Vec<(&str, &Global)> {
+        // Globals of the inner layers shadow (or remove) the globals of the outer layers
+        let mut hidden = FnvSet::default();
+        let mut globals = Vec::new();
+        for layer in &self.layers {
+            for (name, global) in &layer.env.globals {
+                if hidden.insert(&name[..]) {
+                    globals.push((&name[..], global));
+                }
+            }
+            hidden.extend(layer.removed.iter().map(|name| &name[..]));
+        }
+        globals.extend(
+            self.shared
+                .globals
+                .iter()
+                .filter(|&(name, _)| !hidden.contains(&name[..]))
+                .map(|(name, global)| (&name[..], global)),
+        );
+        globals
+    }
+}
+
+impl<'a> CompilerEnv for VmEnvInstance<'a> {
+    fn find_var(&self, id: &Symbol) -> Option<(Variable<Symbol>, ArcType)> {
+        self.get_global(id.definition_name())
             .map(|g| (Variable::UpVar(g.id.clone()), g.typ.clone()))
-            .or_else(|| self.type_infos.find_var(id))
+            .or_else(|| self.find_map(|env| env.type_infos.find_var(id)))
     }
 }
 
-impl KindEnv for VmEnv {
+impl<'a> KindEnv for VmEnvInstance<'a> {
     fn find_kind(&self, type_name: &SymbolRef) -> Option<ArcKind> {
-        self.type_infos.find_kind(type_name)
+        self.find_map(|env| env.type_infos.find_kind(type_name))
     }
 }
-impl TypeEnv for VmEnv {
+
+impl<'a> TypeEnv for VmEnvInstance<'a> {
     fn find_type(&self, id: &SymbolRef) -> Option<&ArcType> {
-        self.globals
-            .get(id.definition_name())
+        self.get_global(id.definition_name())
             .map(|g| &g.typ)
-            .or_else(|| {
-                self.type_infos
-                    .id_to_type
-                    .values()
-                    .filter_map(|alias| match **alias.unresolved_type() {
-                        Type::Variant(ref row) => row.row_iter()
-                            .find(|field| *field.name == *id)
-                            .map(|field| &field.typ),
-                        _ => None,
-                    })
-                    .next()
-                    .map(|ctor| ctor)
-            })
+            .or_else(|| self.find_map(|env| env.type_infos.find_type(id)))
     }
 
     fn find_type_info(&self, id: &SymbolRef) -> Option<&Alias<Symbol, ArcType>> {
-        self.type_infos.find_type_info(id)
+        self.find_map(|env| env.type_infos.find_type_info(id))
     }
 
     fn find_record(
@@ -216,11 +420,26 @@ impl TypeEnv for VmEnv {
         fields: &[Symbol],
         selector: RecordSelector,
     ) -> Option<(ArcType, ArcType)> {
-        self.type_infos.find_record(fields, selector)
+        self.find_map(|env| env.type_infos.find_record(fields, selector))
+    }
+
+    fn value_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.globals().into_iter().map(|(name, _)| name).collect();
+        for env in self.envs() {
+            names.extend(env.type_infos.value_names());
+        }
+        names
+    }
+
+    fn type_names(&self) -> Vec<&str> {
+        self.envs()
+            .into_iter()
+            .flat_map(|env| env.type_infos.type_names())
+            .collect()
     }
 }
 
-impl PrimitiveEnv for VmEnv {
+impl<'a> PrimitiveEnv for VmEnvInstance<'a> {
     fn get_bool(&self) -> &ArcType {
         self.find_type_info("std.types.Bool")
             .map(|alias| match alias {
@@ -231,9 +450,9 @@ impl PrimitiveEnv for VmEnv {
     }
 }
 
-impl MetadataEnv for VmEnv {
+impl<'a> MetadataEnv for VmEnvInstance<'a> {
     fn get_metadata(&self, id: &Symbol) -> Option<&Metadata> {
-        self.globals.get(id.definition_name()).map(|g| &g.metadata)
+        self.get_global(id.definition_name()).map(|g| &g.metadata)
     }
 }
 
@@ -249,12 +468,12 @@ where
     }
 }
 
-impl VmEnv {
+impl<'a> VmEnvInstance<'a> {
     pub fn find_type_info(&self, name: &str) -> Result<Cow<Alias<Symbol, ArcType>>> {
         let name = Name::new(name);
         let module_str = name.module().as_str();
         if module_str == "" {
-            return match self.type_infos.id_to_type.get(name.as_str()) {
+            return match self.find_map(|env| env.type_infos.id_to_type.get(name.as_str())) {
                 Some(alias) => Ok(Cow::Borrowed(alias)),
                 None => Err(Error::UndefinedBinding(name.as_str().into())),
             };
@@ -274,8 +493,9 @@ impl VmEnv {
     pub fn get_binding(&self, name: &str) -> Result<(Value, Cow<ArcType>)> {
         use base::resolve;
 
-        let globals = &self.globals;
-        let mut module = Name::new(name.trim_right_matches('@'));
+        // The globals of the vm are stored without their `@` prefix
+        let name = name.trim_left_matches('@');
+        let mut module = Name::new(name);
         let global;
         // Try to find a global by successively reducing the module path
         // Input: "x.y.z.w"
@@ -287,7 +507,7 @@ impl VmEnv {
             if module.as_str() == "" {
                 return Err(Error::UndefinedBinding(name.into()));
             }
-            if let Some(g) = globals.get(module.as_str()) {
+            if let Some(g) = self.get_global(module.as_str()) {
                 global = g;
                 break;
             }
@@ -339,15 +559,13 @@ impl VmEnv {
     }
 
     pub fn get_metadata(&self, name_str: &str) -> Result<&Metadata> {
-        let globals = &self.globals;
         let name = Name::new(name_str);
         let mut components = name.components();
         let global = match components.next() {
-            Some(comp) => globals
-                .get(comp)
+            Some(comp) => self.get_global(comp)
                 .or_else(|| {
                     components = name.name().components();
-                    globals.get(name.module().as_str())
+                    self.get_global(name.module().as_str())
                 })
                 .ok_or_else(|| Error::MetadataDoesNotExist(name_str.into()))?,
             None => return Err(Error::MetadataDoesNotExist(name_str.into())),
@@ -381,7 +599,7 @@ macro_rules! option {
 
 #[derive(Default)]
 pub struct GlobalVmStateBuilder {
-    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(all(feature = "async", not(target_arch = "wasm32")))]
     event_loop: Option<::tokio_core::reactor::Remote>,
 }
 
@@ -391,16 +609,13 @@ impl GlobalVmStateBuilder {
     }
 
     option!(
-        #[cfg(not(target_arch = "wasm32"))]
+        #[cfg(all(feature = "async", not(target_arch = "wasm32")))]
         event_loop set_event_loop: Option<::tokio_core::reactor::Remote>
     );
 
     pub fn build(self) -> GlobalVmState {
         let mut vm = GlobalVmState {
-            env: RwLock::new(VmEnv {
-                globals: FnvMap::default(),
-                type_infos: TypeInfos::new(),
-            }),
+            env: RwLock::new(VmEnv::new()),
             generics: RwLock::new(FnvMap::default()),
             typeids: RwLock::new(FnvMap::default()),
             interner: RwLock::new(Interner::new()),
@@ -409,7 +624,7 @@ impl GlobalVmStateBuilder {
             type_cache: TypeCache::new(),
             generation_0_threads: RwLock::new(Vec::new()),
 
-            #[cfg(not(target_arch = "wasm32"))]
+            #[cfg(all(feature = "async", not(target_arch = "wasm32")))]
             event_loop: self.event_loop.map(::std::panic::AssertUnwindSafe),
         };
         vm.add_types().unwrap();
@@ -417,6 +632,31 @@ impl GlobalVmStateBuilder {
     }
 }
 
+/// The constructors of a variant are looked up directly by the typechecker so they need to be
+/// quantified over the parameters of the alias they belong to
+fn quantify_constructors(alias: Alias<Symbol, ArcType>) -> Alias<Symbol, ArcType> {
+    let typ = match **alias.unresolved_type().remove_forall() {
+        Type::Variant(ref row) if !alias.params().is_empty() => Some(Type::variant(
+            row.row_iter()
+                .map(|field| Field {
+                    name: field.name.clone(),
+                    typ: Type::forall(alias.params().to_owned(), field.typ.clone()),
+                })
+                .collect(),
+        )),
+        _ => None,
+    };
+    let typ = match typ {
+        Some(typ) => typ,
+        None => return alias,
+    };
+    Alias::from(AliasData::new(
+        alias.name.clone(),
+        alias.params().to_owned(),
+        typ,
+    ))
+}
+
 impl GlobalVmState {
     fn add_types(&mut self) -> StdResult<(), (TypeId, ArcType)> {
         use base::types::BuiltinType;
@@ -457,7 +697,7 @@ impl GlobalVmState {
         Ok(())
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(all(feature = "async", not(target_arch = "wasm32")))]
     pub fn get_event_loop(&self) -> Option<::tokio_core::reactor::Remote> {
         self.event_loop.as_ref().map(|x| x.0.clone())
     }
@@ -467,10 +707,19 @@ impl GlobalVmState {
     }
 
     pub fn new_global_thunk(&self, f: CompiledModule) -> Result<GcPtr<ClosureData>> {
-        let env = self.env.read().unwrap();
+        self.new_global_thunk_in(&self.get_env(), f)
+    }
+
+    /// Creates a global thunk whose globals are resolved in `env` instead of the shared
+    /// environment
+    pub(crate) fn new_global_thunk_in(
+        &self,
+        env: &VmEnvInstance,
+        f: CompiledModule,
+    ) -> Result<GcPtr<ClosureData>> {
         let mut interner = self.interner.write().unwrap();
         let mut gc = self.gc.lock().unwrap();
-        new_bytecode(&env, &mut interner, &mut gc, self, f)
+        new_bytecode(env, &mut interner, &mut gc, self, f)
     }
 
     pub fn get_type<T: ?Sized + Any>(&self) -> ArcType {
@@ -493,28 +742,10 @@ impl GlobalVmState {
         self.env.read().unwrap().globals.get(name).is_some()
     }
 
-    pub(crate) fn set_global(
-        &self,
-        id: Symbol,
-        typ: ArcType,
-        metadata: Metadata,
-        value: Value,
-    ) -> Result<()> {
-        assert!(value.generation().is_root());
-        assert!(
-            id.as_ref().matches('@').next() == Some("@"),
-            "Global symbols must be prefix with '@'"
-        );
-        let mut env = self.env.write().unwrap();
-        let globals = &mut env.globals;
-        let global = Global {
-            id: id.clone(),
-            typ: typ,
-            metadata: metadata,
-            value: value,
-        };
-        globals.insert(StdString::from(id.definition_name()), global);
-        Ok(())
+    /// Removes the global called `name`, returning `true` if it existed. Code which has already
+    /// been loaded keeps the values it referenced from the global.
+    pub fn remove_global(&self, name: &str) -> bool {
+        self.env.write().unwrap().globals.remove(name).is_some()
     }
 
     pub fn get_generic(&self, name: &str) -> ArcType {
@@ -529,30 +760,63 @@ impl GlobalVmState {
 
     /// Registers a new type called `name`
     pub fn register_type<T: ?Sized + Any>(&self, name: &str, args: &[&str]) -> Result<ArcType> {
+        self.register_type_as::<T>(self.opaque_alias(name, args))
+    }
+
+    /// Returns an alias of an opaque type called `name`
+    pub(crate) fn opaque_alias(&self, name: &str, args: &[&str]) -> Alias<Symbol, ArcType> {
+        let args = args.iter()
+            .map(|g| match *self.get_generic(g) {
+                Type::Generic(ref g) => g.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        Alias::from(AliasData::new(
+            Symbol::from(name),
+            args,
+            self.type_cache.opaque(),
+        ))
+    }
+
+    /// Registers `T` as the type described by `alias`. The parameters of `alias` may be of any
+    /// kind and if it is a record or a variant then scripts can construct and match on values of
+    /// the type
+    pub fn register_type_as<T: ?Sized + Any>(
+        &self,
+        alias: Alias<Symbol, ArcType>,
+    ) -> Result<ArcType> {
         let mut env = self.env.write().unwrap();
-        let type_infos = &mut env.type_infos;
-        if type_infos.id_to_type.contains_key(name) {
-            Err(Error::TypeAlreadyExists(name.into()))
-        } else {
-            let id = TypeId::of::<T>();
-            let arg_types: AppVec<_> = args.iter().map(|g| self.get_generic(g)).collect();
-            let args = arg_types
-                .iter()
-                .map(|g| match **g {
-                    Type::Generic(ref g) => g.clone(),
-                    _ => unreachable!(),
-                })
-                .collect();
-            let n = Symbol::from(name);
-            let typ: ArcType = Type::app(Type::ident(n.clone()), arg_types);
-            self.typeids.write().unwrap().insert(id, typ.clone());
-            let t = self.typeids.read().unwrap().get(&id).unwrap().clone();
-            type_infos.id_to_type.insert(
-                name.into(),
-                Alias::from(AliasData::new(n, args, self.type_cache.opaque())),
-            );
-            Ok(t)
+        self.register_type_in::<T>(&mut env.type_infos, None, alias)
+    }
+
+    // Registers `T` in `type_infos`. The types of `parent` are also visible wherever
+    // `type_infos` is used so they may not be registered again.
+    fn register_type_in<T: ?Sized + Any>(
+        &self,
+        type_infos: &mut TypeInfos,
+        parent: Option<&VmEnvInstance>,
+        alias: Alias<Symbol, ArcType>,
+    ) -> Result<ArcType> {
+        let name = StdString::from(alias.name.definition_name());
+        let exists = type_infos.id_to_type.contains_key(&name)
+            || parent.map_or(false, |parent| {
+                parent
+                    .find_map(|env| env.type_infos.id_to_type.get(&name))
+                    .is_some()
+            });
+        if exists {
+            return Err(Error::TypeAlreadyExists(name));
         }
+        let arg_types = alias.params().iter().cloned().map(Type::generic).collect();
+        let typ: ArcType = Type::app(Type::ident(alias.name.clone()), arg_types);
+        self.typeids
+            .write()
+            .unwrap()
+            .insert(TypeId::of::<T>(), typ.clone());
+        type_infos
+            .id_to_type
+            .insert(name, quantify_constructors(alias));
+        Ok(typ)
     }
 
     pub fn get_macros(&self) -> &MacroEnv {
@@ -566,7 +830,11 @@ impl GlobalVmState {
     }
 
     /// Returns a borrowed structure which implements `CompilerEnv`
-    pub fn get_env<'b>(&'b self) -> RwLockReadGuard<'b, VmEnv> {
-        self.env.read().unwrap()
+    pub fn get_env<'b>(&'b self) -> VmEnvInstance<'b> {
+        VmEnvInstance::new(None, &self.env)
+    }
+
+    pub(crate) fn env(&self) -> &RwLock<VmEnv> {
+        &self.env
     }
 }
@@ -0,0 +1,284 @@
+//! Execution of hot functions as WebAssembly.
+//!
+//! When a module is compiled, the top level functions which `core::wasm_backend` can translate
+//! (functions working on `Int`, `Float` and `Bool` values) are translated into a WebAssembly module
+//! and each of them remembers its export in that module. The `HOT_CALLS`th call of such a function
+//! loads the module with `wasmi` and that and every later call runs the exported function instead
+//! of the bytecode, as long as the arguments have the types the function were translated with.
+//! `wasmi` instances can't be shared between threads so each thread instantiates the module the
+//! first time it calls one of its functions.
+//!
+//! WebAssembly calls do not use the stack of the thread, and WebAssembly has no tail calls, so a
+//! call may nest at most `CALL_DEPTH` calls and is only made if the stack of the thread has room
+//! for that many calls of the functions of the module. If the WebAssembly code traps (dividing by
+//! zero, nesting calls too deeply) the call is run by the interpreter instead, which then reports
+//! any error as usual, and the function is always interpreted from then on.
+//!
+//! Like the jit, WebAssembly functions are not used at all while fuel, hooks or `profile` counting
+//! are active. Unlike the jit, a running WebAssembly call does not check `Thread::interrupt`.
+//! Functions of modules which are loaded from their serialized bytecode are always interpreted.
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::{Arc, Mutex, Once, Weak, ONCE_INIT};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use wasmi::{self, ImportsBuilder, ModuleInstance, ModuleRef, NopExternals, RuntimeValue,
+            StackRecycler};
+
+use base::fnv::FnvMap;
+use base::symbol::Symbol;
+
+use core::CExpr;
+use core::backend::Scalar;
+use core::wasm_backend::{self, Export};
+use types::{VmIndex, VmInt, VmTag};
+use value::{BytecodeFunction, Value, ValueRepr};
+
+/// How many times a function is called before it is run as WebAssembly
+pub const HOT_CALLS: usize = 1000;
+
+/// The maximum number of nested calls in a WebAssembly call
+const CALL_DEPTH: usize = 1024;
+
+/// The size in bytes of the WebAssembly value stack of each thread
+const VALUE_STACK_SIZE: usize = 256 * 1024;
+
+/// A WebAssembly module translated from the functions of a gluon module
+struct Module {
+    bytes: Vec<u8>,
+    /// The largest number of stack slots that a call of one of the functions of the module uses
+    /// when it is interpreted
+    max_frame_size: AtomicUsize,
+    /// `None` until one of the functions of the module is called for the `HOT_CALLS`th time,
+    /// `Some(None)` if `wasmi` could not load the module
+    loaded: Mutex<Option<Option<Arc<wasmi::Module>>>>,
+}
+
+impl Module {
+    fn add_frame_size(&self, size: usize) {
+        let mut current = self.max_frame_size.load(Ordering::Relaxed);
+        while current < size {
+            match self.max_frame_size.compare_exchange(
+                current,
+                size,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    fn load(&self) -> Option<Arc<wasmi::Module>> {
+        let mut loaded = self.loaded.lock().unwrap();
+        if loaded.is_none() {
+            *loaded = Some(match wasmi::Module::from_buffer(&self.bytes) {
+                Ok(module) => Some(Arc::new(module)),
+                Err(err) => {
+                    debug!("Unable to load WebAssembly module: {}", err);
+                    None
+                }
+            });
+        }
+        loaded.as_ref().and_then(|module| module.clone())
+    }
+}
+
+/// A function of a `CompiledFunction` which has been translated into WebAssembly
+#[derive(Clone)]
+pub struct WasmFunction {
+    module: Arc<Module>,
+    export: Export,
+}
+
+impl fmt::Debug for WasmFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WasmFunction({})", self.export.name)
+    }
+}
+
+/// The WebAssembly state of a `BytecodeFunction`
+#[derive(Default)]
+pub(crate) struct FunctionWasm {
+    function: Option<WasmFunction>,
+    /// How many times the function has been called before it became hot
+    calls: AtomicUsize,
+    /// If the function has been run as WebAssembly
+    used: AtomicBool,
+    /// If the WebAssembly code has trapped
+    trapped: AtomicBool,
+}
+
+impl FunctionWasm {
+    /// Creates the state of a function which uses `max_stack_size` stack slots when interpreted
+    pub(crate) fn new(function: Option<WasmFunction>, max_stack_size: VmIndex) -> FunctionWasm {
+        if let Some(ref function) = function {
+            // The function itself is also pushed to the stack when it is called
+            function.module.add_frame_size(max_stack_size as usize + 1);
+        }
+        FunctionWasm {
+            function,
+            ..FunctionWasm::default()
+        }
+    }
+}
+
+impl fmt::Debug for FunctionWasm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.function {
+            Some(ref function) => write!(f, "FunctionWasm({})", function.export.name),
+            None => write!(f, "FunctionWasm(untranslated)"),
+        }
+    }
+}
+
+// The WebAssembly state does not affect what a function does
+impl PartialEq for FunctionWasm {
+    fn eq(&self, _: &FunctionWasm) -> bool {
+        true
+    }
+}
+
+/// Translates the top level functions of `expr`, a module which has been translated into core,
+/// returning the function of each symbol which could be translated
+pub(crate) fn translate(expr: CExpr) -> FnvMap<Symbol, WasmFunction> {
+    let (module, exports) = wasm_backend::translate(expr);
+    for &(ref name, ref reason) in &module.skipped {
+        debug!("Unable to translate {} into WebAssembly: {}", name, reason);
+    }
+    let module = Arc::new(Module {
+        bytes: module.bytes,
+        max_frame_size: AtomicUsize::new(1),
+        loaded: Mutex::new(None),
+    });
+    exports
+        .into_iter()
+        .map(|export| {
+            let function = WasmFunction {
+                module: module.clone(),
+                export: export.clone(),
+            };
+            (export.symbol, function)
+        })
+        .collect()
+}
+
+thread_local! {
+    /// The instances of the modules which functions have been called on this thread, keyed by
+    /// the address of the `Module`
+    static INSTANCES: RefCell<FnvMap<usize, (Weak<Module>, ModuleRef)>> =
+        RefCell::new(FnvMap::default());
+
+    /// The stacks used by WebAssembly calls on this thread
+    static STACK: RefCell<StackRecycler> =
+        RefCell::new(StackRecycler::with_limits(VALUE_STACK_SIZE, CALL_DEPTH));
+}
+
+fn instance(module: &Arc<Module>) -> Option<ModuleRef> {
+    INSTANCES.with(|instances| {
+        let key = &**module as *const Module as usize;
+        if let Some(&(ref weak, ref instance)) = instances.borrow().get(&key) {
+            // A dropped module may have been at the same address
+            if weak.upgrade().map_or(false, |other| Arc::ptr_eq(&other, module)) {
+                return Some(instance.clone());
+            }
+        }
+        let loaded = module.load()?;
+        let instance = match ModuleInstance::new(&loaded, &ImportsBuilder::default()) {
+            Ok(instance) => instance.assert_no_start(),
+            Err(err) => {
+                debug!("Unable to instantiate WebAssembly module: {}", err);
+                return None;
+            }
+        };
+        let mut instances = instances.borrow_mut();
+        instances.retain(|_, &mut (ref weak, _)| weak.upgrade().is_some());
+        instances.insert(key, (Arc::downgrade(module), instance.clone()));
+        Some(instance)
+    })
+}
+
+/// Calls the WebAssembly version of `function` with `args` if it has one and it is hot (which
+/// this call may make it). `remaining_stack` is the number of stack slots the thread may still
+/// use. Returns `None` if the function must be run by the interpreter.
+pub(crate) fn call(
+    function: &BytecodeFunction,
+    args: &[Value],
+    remaining_stack: VmIndex,
+) -> Option<ValueRepr> {
+    let state = &function.wasm;
+    let wasm = state.function.as_ref()?;
+    if state.calls.load(Ordering::Relaxed) < HOT_CALLS
+        && state.calls.fetch_add(1, Ordering::Relaxed) + 1 < HOT_CALLS
+    {
+        return None;
+    }
+    if state.trapped.load(Ordering::Relaxed) || args.len() != wasm.export.params.len() {
+        return None;
+    }
+    // A call which would overflow the stack of the thread when interpreted must not succeed
+    let max_frame_size = wasm.module.max_frame_size.load(Ordering::Relaxed);
+    if (remaining_stack as usize) / max_frame_size < CALL_DEPTH {
+        return None;
+    }
+
+    let mut wasm_args = Vec::with_capacity(args.len());
+    for (arg, &param) in args.iter().zip(&wasm.export.params) {
+        wasm_args.push(match (param, arg.get_repr()) {
+            (Scalar::Int, ValueRepr::Int(i)) => RuntimeValue::I64(i as i64),
+            (Scalar::Float, ValueRepr::Float(f)) => RuntimeValue::F64(f.into()),
+            (Scalar::Bool, ValueRepr::Tag(tag)) => RuntimeValue::I32(tag as i32),
+            _ => return None,
+        });
+    }
+    let instance = instance(&wasm.module)?;
+    let result = STACK.with(|stack| {
+        instance.invoke_export_with_stack(
+            &wasm.export.name,
+            &wasm_args,
+            &mut NopExternals,
+            &mut stack.borrow_mut(),
+        )
+    });
+    let result = match result {
+        Ok(result) => result,
+        Err(err) => {
+            debug!("WebAssembly call of {} failed: {}", wasm.export.name, err);
+            state.trapped.store(true, Ordering::Relaxed);
+            return None;
+        }
+    };
+    let result = match (wasm.export.ret, result) {
+        (Scalar::Int, Some(RuntimeValue::I64(i))) => ValueRepr::Int(i as VmInt),
+        (Scalar::Float, Some(RuntimeValue::F64(f))) => ValueRepr::Float(f.to_float()),
+        (Scalar::Bool, Some(RuntimeValue::I32(tag))) => ValueRepr::Tag(tag as VmTag),
+        (_, result) => ice!(
+            "WebAssembly function {} returned {:?}",
+            wasm.export.name,
+            result
+        ),
+    };
+    if !state.used.swap(true, Ordering::Relaxed) {
+        used().lock().unwrap().push(function.name.declared_name().to_string());
+    }
+    Some(result)
+}
+
+static INIT: Once = ONCE_INIT;
+static mut USED: *const Mutex<Vec<String>> = 0 as *const _;
+
+fn used() -> &'static Mutex<Vec<String>> {
+    unsafe {
+        INIT.call_once(|| {
+            USED = Box::into_raw(Box::new(Mutex::new(Vec::new())));
+        });
+        &*USED
+    }
+}
+
+/// Returns the names of the functions which have been run as WebAssembly, in the order they were
+/// first run
+pub fn used_functions() -> Vec<String> {
+    used().lock().unwrap().clone()
+}